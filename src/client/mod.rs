@@ -0,0 +1,3 @@
+pub mod pointz_client;
+
+pub use pointz_client::{ClockSample, DiscoveredServer, PointZClient};
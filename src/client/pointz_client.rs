@@ -0,0 +1,751 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{
+    Command, CommandDatagram, CommandEnvelope, DiscoveryResponse, EncryptedDatagram,
+    HandshakeDatagram, PingExchange, Reply, WireFormat,
+};
+use crate::features::auth::{command_signing, noise_channel, viewer_ticket};
+use crate::utils;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use snow::StatelessTransportState;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// A server that answered a discovery broadcast
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer {
+    /// Command endpoint on the address the answer came from
+    pub addr: SocketAddr,
+    pub info: DiscoveryResponse,
+}
+
+/// How the server answered a reliable command
+enum Delivery {
+    Acked,
+    ConfirmRequired(u64),
+}
+
+/// One clock probe: round trip and the server clock's lead over ours
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSample {
+    pub rtt: Duration,
+    pub offset_ms: i64,
+}
+
+/// Keys and counters of a completed Noise handshake
+struct Channel {
+    session: u64,
+    transport: StatelessTransportState,
+    nonce: AtomicU64,
+}
+
+/// Typed client for the command protocol over UDP
+///
+/// Replies carry no request id, so `request`-style calls discard stale datagrams
+/// before sending and take the next reply as theirs; don't issue them concurrently.
+pub struct PointZClient {
+    socket: UdpSocket,
+    token: Option<String>,
+    /// Server-issued signing session, once [`Self::sign`] has opened one
+    signing: Option<u64>,
+    channel: Option<Channel>,
+    seq: AtomicU64,
+    last_exchange: Mutex<Option<PingExchange>>,
+    reply_timeout: Duration,
+    format: WireFormat,
+}
+
+impl PointZClient {
+    /// Broadcasts `DISCOVER` and collects the servers that answer within `wait`
+    pub async fn discover(wait: Duration) -> Result<Vec<DiscoveredServer>> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(true)?;
+        socket
+            .send_to(
+                ServerConfig::DISCOVER_MESSAGE.as_bytes(),
+                (Ipv4Addr::BROADCAST, ServerConfig::DISCOVERY_PORT),
+            )
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + wait;
+        let mut buf = [0; ServerConfig::DISCOVERY_BUFFER_SIZE];
+        let mut servers: Vec<DiscoveredServer> = Vec::new();
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await
+        {
+            let (size, from) = received?;
+            let Ok(info) = serde_json::from_slice::<DiscoveryResponse>(&buf[..size]) else {
+                continue;
+            };
+            let addr = SocketAddr::new(from.ip(), ServerConfig::COMMAND_PORT);
+            // A server answers on every interface socket the broadcast reached
+            if !servers.iter().any(|server| server.addr == addr) {
+                servers.push(DiscoveredServer { addr, info });
+            }
+        }
+        Ok(servers)
+    }
+
+    /// Opens a socket to the command port at `server`; no datagram is sent yet
+    pub async fn connect(server: SocketAddr) -> Result<Self> {
+        let local = if server.is_ipv4() {
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+        } else {
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(server).await?;
+        Ok(Self {
+            socket,
+            token: None,
+            signing: None,
+            channel: None,
+            seq: AtomicU64::new(0),
+            last_exchange: Mutex::new(None),
+            reply_timeout: Duration::from_millis(ServerConfig::CLIENT_REPLY_TIMEOUT_MS),
+            format: WireFormat::Json,
+        })
+    }
+
+    /// Authenticates every command with a token from an earlier `pair`
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_reply_timeout(mut self, timeout: Duration) -> Self {
+        self.reply_timeout = timeout;
+        self
+    }
+
+    /// Encodes commands as MessagePack or CBOR instead of JSON; the server replies in kind
+    ///
+    /// CBOR batches need [`Self::select_format`] instead, or the server reads them as MessagePack.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// A screen preview `?ticket=` good for `lifetime`, so the token stays off plain HTTP
+    pub fn preview_ticket(&self, lifetime: Duration) -> Option<String> {
+        let token = self.token.as_deref()?;
+        Some(viewer_ticket::derive(
+            token,
+            utils::unix_now() + lifetime.as_secs(),
+        ))
+    }
+
+    /// Sends a command without waiting; input commands only get a reply when refused
+    pub async fn send(&self, command: Command) -> Result<()> {
+        let envelope = CommandEnvelope {
+            auth: self.clear_token(),
+            seq: Some(self.seq.fetch_add(1, Ordering::Relaxed)),
+            sent_at_ms: None,
+            client_sent_at_ms: Some(utils::unix_now_ms()),
+            reliable: false,
+            confirm: None,
+            signed: false,
+            encrypted: false,
+            command,
+        };
+        self.socket.send(&self.encode(&envelope)?).await?;
+        Ok(())
+    }
+
+    /// Sends a command the server acknowledges, retransmitting until it does
+    ///
+    /// Every copy carries the same `seq`, so the server runs the command once however many
+    /// arrive. Use it for clicks and keys that must not be lost.
+    pub async fn send_reliable(&self, command: Command) -> Result<()> {
+        match self.deliver(command, None).await? {
+            Delivery::Acked => Ok(()),
+            Delivery::ConfirmRequired(_) => {
+                bail!("The server holds this command for confirmation; use send_confirmed")
+            }
+        }
+    }
+
+    /// Sends a destructive command and confirms it with the nonce the server hands back
+    ///
+    /// Only call this once the user has agreed to the command, such as a guarded shortcut.
+    pub async fn send_confirmed(&self, command: Command) -> Result<()> {
+        let Delivery::ConfirmRequired(nonce) = self.deliver(command.clone(), None).await? else {
+            return Ok(());
+        };
+        match self.deliver(command, Some(nonce)).await? {
+            Delivery::Acked => Ok(()),
+            Delivery::ConfirmRequired(_) => bail!("The server did not accept the confirmation"),
+        }
+    }
+
+    async fn deliver(&self, command: Command, confirm: Option<u64>) -> Result<Delivery> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let envelope = CommandEnvelope {
+            auth: self.clear_token(),
+            seq: Some(seq),
+            sent_at_ms: None,
+            client_sent_at_ms: Some(utils::unix_now_ms()),
+            reliable: true,
+            confirm,
+            signed: false,
+            encrypted: false,
+            command,
+        };
+        let datagram = self.encode(&envelope)?;
+        let retransmit_after = Duration::from_millis(ServerConfig::CLIENT_RETRANSMIT_MS);
+        let attempts = ServerConfig::CLIENT_RETRANSMITS + 1;
+        self.discard_pending();
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        for _ in 0..attempts {
+            self.socket.send(&datagram).await?;
+            let deadline = tokio::time::Instant::now() + retransmit_after;
+            while let Ok(received) =
+                tokio::time::timeout_at(deadline, self.socket.recv(&mut buf)).await
+            {
+                match self.decode_reply(&buf[..received?]) {
+                    Ok(Reply::Ack { seq: acked }) if acked == seq => return Ok(Delivery::Acked),
+                    Ok(Reply::ConfirmRequired { nonce, .. }) => {
+                        return Ok(Delivery::ConfirmRequired(nonce))
+                    }
+                    Ok(Reply::Denied { reason }) => bail!("Denied: {}", reason),
+                    Ok(Reply::Invalid { reason, .. }) => bail!("Invalid: {}", reason),
+                    _ => {}
+                }
+            }
+        }
+        bail!("No acknowledgement after {} attempts", attempts)
+    }
+
+    /// Sends several commands in one datagram, applied in order; the token goes on the first
+    ///
+    /// The server stops at the first refused command and replies only for that one.
+    pub async fn send_batch(&self, commands: Vec<Command>) -> Result<()> {
+        if commands.len() > ServerConfig::MAX_BATCH_COMMANDS {
+            bail!(
+                "At most {} commands fit in a batch",
+                ServerConfig::MAX_BATCH_COMMANDS
+            );
+        }
+        let client_sent_at_ms = utils::unix_now_ms();
+        let envelopes = commands
+            .into_iter()
+            .enumerate()
+            .map(|(index, command)| CommandEnvelope {
+                auth: self.clear_token().filter(|_| index == 0),
+                seq: Some(self.seq.fetch_add(1, Ordering::Relaxed)),
+                sent_at_ms: None,
+                client_sent_at_ms: Some(client_sent_at_ms),
+                reliable: false,
+                confirm: None,
+                signed: false,
+                encrypted: false,
+                command,
+            })
+            .collect();
+        let datagram = CommandDatagram::Batch(envelopes);
+        self.socket.send(&self.encode(&datagram)?).await?;
+        Ok(())
+    }
+
+    /// Sends a command and waits for the server's reply; `Denied` and `Invalid` become errors
+    pub async fn request(&self, command: Command) -> Result<Reply> {
+        self.discard_pending();
+        self.send(command).await?;
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        let size = tokio::time::timeout(self.reply_timeout, self.socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow!("No reply within {:?}", self.reply_timeout))??;
+        let reply = self.decode_reply(&buf[..size]).context("Malformed reply")?;
+        match reply {
+            Reply::Denied { reason } => bail!("Denied: {}", reason),
+            Reply::Invalid { reason, .. } => bail!("Invalid: {}", reason),
+            reply => Ok(reply),
+        }
+    }
+
+    /// Exchanges the PIN shown on the host for a token, which later commands then carry
+    pub async fn pair(&mut self, pin: &str, device_name: &str) -> Result<String> {
+        let reply = self
+            .request(Command::Pair {
+                pin: pin.to_string(),
+                device_name: device_name.to_string(),
+            })
+            .await?;
+        self.accept_pairing(reply, "Pair")
+    }
+
+    /// Asks to be approved on the host instead of sending a PIN
+    ///
+    /// Waits until someone approves or denies the request there, or until it expires.
+    pub async fn request_pairing(&mut self, device_name: &str) -> Result<String> {
+        let reply = self
+            .request(Command::RequestPairing {
+                device_name: device_name.to_string(),
+            })
+            .await?;
+        let Reply::PairPending {
+            expires_in_secs, ..
+        } = reply
+        else {
+            return self.accept_pairing(reply, "RequestPairing");
+        };
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(expires_in_secs);
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        loop {
+            let size = tokio::time::timeout_at(deadline, self.socket.recv(&mut buf))
+                .await
+                .map_err(|_| anyhow!("Pairing request expired without a decision"))??;
+            if let Ok(reply @ (Reply::Paired { .. } | Reply::PairRejected { .. })) =
+                self.decode_reply(&buf[..size])
+            {
+                return self.accept_pairing(reply, "RequestPairing");
+            }
+        }
+    }
+
+    /// Keeps the token from a `Paired` reply; anything else is an error
+    fn accept_pairing(&mut self, reply: Reply, command: &str) -> Result<String> {
+        match reply {
+            Reply::Paired { token } => {
+                self.token = Some(token.clone());
+                Ok(token)
+            }
+            Reply::PairRejected {
+                reason,
+                retry_after_secs: Some(secs),
+            } => bail!("Pairing rejected: {} (retry in {}s)", reason, secs),
+            Reply::PairRejected { reason, .. } => bail!("Pairing rejected: {}", reason),
+            other => bail!("Unexpected reply to {}: {:?}", command, other),
+        }
+    }
+
+    /// Probes the server clock, feeding the previous exchange to its estimate
+    pub async fn ping(&self) -> Result<ClockSample> {
+        let previous = *self.lock_last_exchange();
+        let client_time_ms = utils::unix_now_ms();
+        let started = Instant::now();
+        let reply = self
+            .request(Command::Ping {
+                client_time_ms,
+                previous,
+            })
+            .await?;
+        let rtt = started.elapsed();
+        let Reply::Pong { server_time_ms, .. } = reply else {
+            bail!("Unexpected reply to Ping: {:?}", reply);
+        };
+        let rtt_ms = rtt.as_millis() as u64;
+        *self.lock_last_exchange() = Some(PingExchange {
+            client_time_ms,
+            server_time_ms,
+            received_ms: client_time_ms + rtt_ms,
+        });
+        Ok(ClockSample {
+            rtt,
+            offset_ms: server_time_ms as i64 - (client_time_ms + rtt_ms / 2) as i64,
+        })
+    }
+
+    /// Unlocks a session the server locked after inactivity, with the host's session PIN if
+    /// it set one; returns the idle seconds that lock it again
+    pub async fn refresh_session(&self, pin: Option<&str>) -> Result<u64> {
+        let reply = self
+            .request(Command::RefreshSession {
+                pin: pin.map(str::to_string),
+            })
+            .await?;
+        let Reply::SessionRefreshed { idle_lock_secs } = reply else {
+            bail!("Unexpected reply to RefreshSession: {:?}", reply);
+        };
+        Ok(idle_lock_secs)
+    }
+
+    /// Agrees with the server on the first of `formats` it can use, and sends in it from now on
+    pub async fn select_format(&mut self, formats: &[WireFormat]) -> Result<WireFormat> {
+        let reply = self
+            .request(Command::SelectFormat {
+                formats: formats.to_vec(),
+            })
+            .await?;
+        let Reply::FormatSelected { format } = reply else {
+            bail!("Unexpected reply to SelectFormat: {:?}", reply);
+        };
+        self.format = format;
+        Ok(format)
+    }
+
+    /// Switches the server's settings profile, returning its canonical name
+    pub async fn set_profile(&self, name: &str) -> Result<String> {
+        let reply = self
+            .request(Command::SetProfile {
+                name: name.to_string(),
+            })
+            .await?;
+        match reply {
+            Reply::ProfileChanged { name } => Ok(name),
+            Reply::ProfileRejected { reason, available } => {
+                bail!("{}; available: {}", reason, available.join(", "))
+            }
+            other => bail!("Unexpected reply to SetProfile: {:?}", other),
+        }
+    }
+
+    /// Opens a signing session; every later command is signed with the token instead of carrying it
+    ///
+    /// For servers with `auth.require_signed`. Call it again after the server restarts, or
+    /// once a command is refused for an unknown session.
+    pub async fn sign(&mut self) -> Result<()> {
+        let token = self.token.as_deref().context("Pair before signing")?;
+        let request = CommandDatagram::Signed(command_signing::request_session(token));
+        self.signing = None;
+        self.discard_pending();
+        self.socket.send(&self.format.encode(&request)?).await?;
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        let size = tokio::time::timeout(self.reply_timeout, self.socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow!("No signing session within {:?}", self.reply_timeout))??;
+        match self.format.decode(&buf[..size])? {
+            Reply::SigningSession { session } => {
+                self.signing = Some(session);
+                Ok(())
+            }
+            Reply::Denied { reason } => bail!("Signing session refused: {}", reason),
+            other => bail!("Unexpected reply to signing request: {:?}", other),
+        }
+    }
+
+    /// Opens a Noise channel keyed with the token; every later command and reply is sealed
+    ///
+    /// Call it again after the server restarts, since the session doesn't survive that.
+    pub async fn encrypt(&mut self) -> Result<()> {
+        let token = self
+            .token
+            .as_deref()
+            .context("Pair before opening an encrypted channel")?;
+        let mut initiator = noise_channel::handshake(token, true)?;
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        let len = initiator.write_message(&[], &mut buf)?;
+        let hello = CommandDatagram::Handshake(HandshakeDatagram {
+            signer: command_signing::signer_id(token),
+            handshake: utils::to_hex(&buf[..len]),
+        });
+        self.channel = None;
+        self.discard_pending();
+        self.socket.send(&self.format.encode(&hello)?).await?;
+        let size = tokio::time::timeout(self.reply_timeout, self.socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow!("No handshake reply within {:?}", self.reply_timeout))??;
+        let Reply::HandshakeAccepted { session, handshake } = self.format.decode(&buf[..size])?
+        else {
+            bail!("Unexpected reply to handshake");
+        };
+        let message = utils::from_hex(&handshake).context("Malformed handshake reply")?;
+        initiator.read_message(&message, &mut buf)?;
+        self.channel = Some(Channel {
+            session,
+            transport: initiator.into_stateless_transport_mode()?,
+            nonce: AtomicU64::new(0),
+        });
+        Ok(())
+    }
+
+    /// The token to put in `auth`; signed and encrypted datagrams keep it off the wire
+    fn clear_token(&self) -> Option<String> {
+        self.token
+            .clone()
+            .filter(|_| self.signing.is_none() && self.channel.is_none())
+    }
+
+    /// Encodes an envelope or batch, sealed or signed when the client was set up for it
+    fn encode<T: Serialize>(&self, payload: &T) -> Result<Vec<u8>> {
+        if let Some(channel) = &self.channel {
+            let nonce = channel.nonce.fetch_add(1, Ordering::Relaxed);
+            let sealed =
+                noise_channel::encrypt(&channel.transport, nonce, &serde_json::to_vec(payload)?)?;
+            return self
+                .format
+                .encode(&CommandDatagram::Encrypted(EncryptedDatagram {
+                    session: channel.session,
+                    nonce,
+                    ciphertext: utils::to_hex(&sealed),
+                }));
+        }
+        match (self.token.as_deref(), self.signing) {
+            (Some(token), Some(session)) => {
+                let signed = command_signing::sign(token, session, serde_json::to_string(payload)?);
+                self.format.encode(&CommandDatagram::Signed(signed))
+            }
+            _ => self.format.encode(payload),
+        }
+    }
+
+    /// Decodes a reply, unsealing it if it came back encrypted
+    ///
+    /// Replies are always maps, whose first byte tells every format apart.
+    fn decode_reply(&self, bytes: &[u8]) -> Result<Reply> {
+        match (WireFormat::detect(bytes).decode(bytes)?, &self.channel) {
+            (Reply::Encrypted { nonce, ciphertext }, Some(channel)) => {
+                let ciphertext = utils::from_hex(&ciphertext).context("Malformed ciphertext")?;
+                let plaintext = noise_channel::decrypt(&channel.transport, nonce, &ciphertext)?;
+                Ok(serde_json::from_slice(&plaintext)?)
+            }
+            (Reply::Encrypted { .. }, None) => bail!("Encrypted reply without a channel"),
+            (reply, _) => Ok(reply),
+        }
+    }
+
+    /// Drops replies to earlier fire-and-forget commands so they aren't taken for the next one
+    fn discard_pending(&self) {
+        let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+        while self.socket.try_recv(&mut buf).is_ok() {}
+    }
+
+    fn lock_last_exchange(&self) -> std::sync::MutexGuard<'_, Option<PingExchange>> {
+        self.last_exchange
+            .lock()
+            .expect("Last ping exchange mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers each envelope with `respond` and hands back what it received, batches unpacked
+    async fn fake_server(
+        respond: fn(&CommandEnvelope) -> Reply,
+    ) -> (
+        SocketAddr,
+        tokio::sync::mpsc::UnboundedReceiver<CommandEnvelope>,
+    ) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+            while let Ok((size, from)) = socket.recv_from(&mut buf).await {
+                let format = WireFormat::detect(&buf[..size]);
+                let datagram: CommandDatagram = format.decode(&buf[..size]).unwrap();
+                for envelope in datagram.into_envelopes() {
+                    let reply = format.encode(&respond(&envelope)).unwrap();
+                    socket.send_to(&reply, from).await.unwrap();
+                    let _ = sender.send(envelope);
+                }
+            }
+        });
+        (addr, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_pair_stores_token_for_later_commands() {
+        let (addr, mut received) = fake_server(|envelope| match envelope.command {
+            Command::Pair { .. } => Reply::Paired {
+                token: "secret".to_string(),
+            },
+            _ => Reply::Denied {
+                reason: "nope".to_string(),
+            },
+        })
+        .await;
+        let mut client = PointZClient::connect(addr).await.unwrap();
+
+        assert_eq!(client.pair("123456", "test").await.unwrap(), "secret");
+        client
+            .send(Command::MouseClick { button: 1 })
+            .await
+            .unwrap();
+
+        let pair = received.recv().await.unwrap();
+        assert!(pair.auth.is_none());
+        let click = received.recv().await.unwrap();
+        assert_eq!(click.auth.as_deref(), Some("secret"));
+        assert!(click.seq > pair.seq);
+    }
+
+    #[tokio::test]
+    async fn test_request_pairing_waits_for_approval() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+            let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+            for reply in [
+                Reply::PairPending {
+                    request_id: 1,
+                    expires_in_secs: 5,
+                },
+                Reply::Paired {
+                    token: "approved".to_string(),
+                },
+            ] {
+                let encoded = WireFormat::Json.encode(&reply).unwrap();
+                socket.send_to(&encoded, from).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+
+        let mut client = PointZClient::connect(addr).await.unwrap();
+        assert_eq!(client.request_pairing("Pixel").await.unwrap(), "approved");
+        assert_eq!(client.token(), Some("approved"));
+    }
+
+    #[tokio::test]
+    async fn test_ping_reports_previous_exchange() {
+        let (addr, mut received) = fake_server(|envelope| match envelope.command {
+            Command::Ping { client_time_ms, .. } => Reply::Pong {
+                client_time_ms,
+                server_time_ms: client_time_ms + 1000,
+            },
+            _ => unreachable!(),
+        })
+        .await;
+        let client = PointZClient::connect(addr).await.unwrap();
+
+        let sample = client.ping().await.unwrap();
+        assert!(sample.offset_ms > 900);
+        client.ping().await.unwrap();
+
+        let first = received.recv().await.unwrap();
+        let second = received.recv().await.unwrap();
+        let Command::Ping {
+            client_time_ms,
+            previous: None,
+        } = first.command
+        else {
+            panic!("first ping echoed an exchange: {:?}", first.command);
+        };
+        let Command::Ping {
+            previous: Some(previous),
+            ..
+        } = second.command
+        else {
+            panic!("second ping didn't echo the first: {:?}", second.command);
+        };
+        assert_eq!(previous.client_time_ms, client_time_ms);
+        assert_eq!(previous.server_time_ms, client_time_ms + 1000);
+        assert!(previous.received_ms >= client_time_ms);
+    }
+
+    #[tokio::test]
+    async fn test_denied_request_is_an_error() {
+        let (addr, _received) = fake_server(|_| Reply::Denied {
+            reason: "outside active hours".to_string(),
+        })
+        .await;
+        let client = PointZClient::connect(addr).await.unwrap();
+        let error = client.set_profile("Couch").await.unwrap_err();
+        assert!(error.to_string().contains("outside active hours"));
+    }
+
+    #[tokio::test]
+    async fn test_message_pack_client_reads_message_pack_replies() {
+        let (addr, mut received) = fake_server(|envelope| match envelope.command {
+            Command::Ping { client_time_ms, .. } => Reply::Pong {
+                client_time_ms,
+                server_time_ms: client_time_ms,
+            },
+            _ => unreachable!(),
+        })
+        .await;
+        let client = PointZClient::connect(addr)
+            .await
+            .unwrap()
+            .with_wire_format(WireFormat::MessagePack);
+
+        client.ping().await.unwrap();
+        assert!(matches!(
+            received.recv().await.unwrap().command,
+            Command::Ping { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_selected_format_is_used_for_later_commands() {
+        let (addr, mut received) = fake_server(|envelope| match &envelope.command {
+            Command::SelectFormat { formats } => Reply::FormatSelected { format: formats[0] },
+            Command::Ping { client_time_ms, .. } => Reply::Pong {
+                client_time_ms: *client_time_ms,
+                server_time_ms: *client_time_ms,
+            },
+            _ => unreachable!(),
+        })
+        .await;
+        let mut client = PointZClient::connect(addr).await.unwrap();
+
+        let format = client.select_format(&[WireFormat::Cbor]).await.unwrap();
+        assert_eq!(format, WireFormat::Cbor);
+        assert_eq!(client.encode(&Command::Heartbeat).unwrap()[0], 0xa1);
+        client.ping().await.unwrap();
+        received.recv().await.unwrap();
+        assert!(matches!(
+            received.recv().await.unwrap().command,
+            Command::Ping { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_arrives_in_order_in_one_datagram() {
+        let (addr, mut received) = fake_server(|_| Reply::Denied {
+            reason: "ignored".to_string(),
+        })
+        .await;
+        let client = PointZClient::connect(addr)
+            .await
+            .unwrap()
+            .with_token("secret")
+            .with_wire_format(WireFormat::MessagePack);
+
+        client
+            .send_batch(vec![
+                Command::MouseMove { x: 3.0, y: 1.0 },
+                Command::MouseMove { x: 2.0, y: 0.0 },
+                Command::MouseClick { button: 1 },
+            ])
+            .await
+            .unwrap();
+
+        let mut envelopes = Vec::new();
+        for _ in 0..3 {
+            envelopes.push(received.recv().await.unwrap());
+        }
+        assert!(matches!(envelopes[2].command, Command::MouseClick { .. }));
+        assert!(envelopes
+            .iter()
+            .all(|envelope| envelope.auth.as_deref() == Some("secret")));
+        assert!(envelopes[0].seq < envelopes[1].seq);
+    }
+
+    #[tokio::test]
+    async fn test_reliable_send_retransmits_until_acknowledged() {
+        static COPIES: AtomicU64 = AtomicU64::new(0);
+        let (addr, mut received) = fake_server(|envelope| {
+            if COPIES.fetch_add(1, Ordering::SeqCst) == 0 {
+                // Stands in for a lost acknowledgement
+                return Reply::ProfileChanged {
+                    name: "unrelated".to_string(),
+                };
+            }
+            Reply::Ack {
+                seq: envelope.seq.unwrap(),
+            }
+        })
+        .await;
+        let client = PointZClient::connect(addr).await.unwrap();
+
+        client
+            .send_reliable(Command::MouseClick { button: 1 })
+            .await
+            .unwrap();
+
+        let first = received.recv().await.unwrap();
+        let retransmit = received.recv().await.unwrap();
+        assert!(first.reliable && retransmit.reliable);
+        assert_eq!(first.seq, retransmit.seq);
+    }
+}
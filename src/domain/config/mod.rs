@@ -1,3 +1,6 @@
+pub mod secrets;
 pub mod server_config;
+pub mod settings;
 
 pub use server_config::ServerConfig;
+pub use settings::Settings;
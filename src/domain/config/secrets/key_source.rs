@@ -0,0 +1,273 @@
+//! Protecting the secrets key with a passphrase, the OS keychain or the TPM
+//!
+//! The keychain and TPM are reached through the tools each OS ships: `security` on macOS,
+//! `secret-tool` and `systemd-creds` on Linux. Windows keeps the key wrapped with DPAPI,
+//! which ties it to the account that ran `encrypt-secrets`.
+
+use super::{random_bytes, UnlockMethod, NONCE_LEN};
+use crate::domain::config::ServerConfig;
+use anyhow::{anyhow, bail, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::io::IsTerminal;
+
+/// Protects `key` with `method`, returning the salt and wrapped key to keep in `secrets.json`
+pub(super) fn wrap(method: UnlockMethod, key: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    match method {
+        UnlockMethod::Passphrase => {
+            let passphrase = rpassword::prompt_password("Passphrase for secrets: ")?;
+            if passphrase.is_empty() {
+                bail!("The passphrase must not be empty");
+            }
+            if rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+                bail!("Passphrases don't match");
+            }
+            wrap_with_passphrase(key, &passphrase)
+        }
+        UnlockMethod::Keychain => Ok((Vec::new(), keychain::store(key)?)),
+        UnlockMethod::Tpm => Ok((Vec::new(), tpm::seal(key)?)),
+    }
+}
+
+/// Recovers the key `wrap` protected
+pub(super) fn unwrap(method: UnlockMethod, salt: &[u8], wrapped: &[u8]) -> Result<Vec<u8>> {
+    match method {
+        UnlockMethod::Passphrase => unwrap_with_passphrase(salt, wrapped, &passphrase()?),
+        UnlockMethod::Keychain => keychain::load(wrapped),
+        UnlockMethod::Tpm => tpm::unseal(wrapped),
+    }
+}
+
+pub(super) fn wrap_with_passphrase(key: &[u8], passphrase: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+    let salt = random_bytes(ServerConfig::BUNDLE_SALT_LEN);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = passphrase_cipher(passphrase, &salt)?
+        .encrypt(&nonce, key)
+        .map_err(|_| anyhow!("Failed to wrap the secrets key"))?;
+    let mut wrapped = nonce.to_vec();
+    wrapped.extend_from_slice(&ciphertext);
+    Ok((salt, wrapped))
+}
+
+pub(super) fn unwrap_with_passphrase(
+    salt: &[u8],
+    wrapped: &[u8],
+    passphrase: &str,
+) -> Result<Vec<u8>> {
+    if wrapped.len() < NONCE_LEN {
+        bail!("The wrapped secrets key is truncated");
+    }
+    let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+    passphrase_cipher(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Wrong passphrase for secrets"))
+}
+
+fn passphrase_cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the secrets key: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key))
+}
+
+/// From the environment when set, otherwise typed at the terminal
+fn passphrase() -> Result<String> {
+    if let Ok(passphrase) = std::env::var(ServerConfig::SECRETS_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Secrets are passphrase-protected; set {} when running without a terminal",
+            ServerConfig::SECRETS_PASSPHRASE_ENV
+        );
+    }
+    Ok(rpassword::prompt_password("Passphrase for secrets: ")?)
+}
+
+/// Runs `program` with `input` on stdin, returning its stdout
+#[cfg(not(windows))]
+fn run_with_input(program: &str, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(target_os = "macos")]
+mod keychain {
+    use super::run_with_input;
+    use crate::domain::config::ServerConfig;
+    use crate::utils::{from_hex, to_hex};
+    use anyhow::{anyhow, Result};
+
+    /// Files the key as a generic password; `security -i` reads it from stdin, off the argv
+    pub fn store(key: &[u8]) -> Result<Vec<u8>> {
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            ServerConfig::SECRETS_KEYCHAIN_SERVICE,
+            ServerConfig::SECRETS_KEYCHAIN_ACCOUNT,
+            to_hex(key)
+        );
+        run_with_input("security", &["-i"], command.as_bytes())?;
+        Ok(Vec::new())
+    }
+
+    pub fn load(_wrapped: &[u8]) -> Result<Vec<u8>> {
+        let stdout = run_with_input(
+            "security",
+            &[
+                "find-generic-password",
+                "-s",
+                ServerConfig::SECRETS_KEYCHAIN_SERVICE,
+                "-a",
+                ServerConfig::SECRETS_KEYCHAIN_ACCOUNT,
+                "-w",
+            ],
+            &[],
+        )?;
+        from_hex(String::from_utf8_lossy(&stdout).trim())
+            .ok_or_else(|| anyhow!("The keychain entry for secrets is not a key"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod keychain {
+    use super::run_with_input;
+    use crate::domain::config::ServerConfig;
+    use crate::utils::{from_hex, to_hex};
+    use anyhow::{anyhow, Result};
+
+    const ATTRIBUTES: [&str; 4] = [
+        "service",
+        ServerConfig::SECRETS_KEYCHAIN_SERVICE,
+        "account",
+        ServerConfig::SECRETS_KEYCHAIN_ACCOUNT,
+    ];
+
+    /// Stores the key in the Secret Service (GNOME Keyring, KWallet) through `secret-tool`
+    pub fn store(key: &[u8]) -> Result<Vec<u8>> {
+        let mut args = vec!["store", "--label=PointZerver secrets"];
+        args.extend(ATTRIBUTES);
+        run_with_input("secret-tool", &args, to_hex(key).as_bytes())?;
+        Ok(Vec::new())
+    }
+
+    pub fn load(_wrapped: &[u8]) -> Result<Vec<u8>> {
+        let mut args = vec!["lookup"];
+        args.extend(ATTRIBUTES);
+        let stdout = run_with_input("secret-tool", &args, &[])?;
+        from_hex(String::from_utf8_lossy(&stdout).trim())
+            .ok_or_else(|| anyhow!("The keyring entry for secrets is not a key"))
+    }
+}
+
+#[cfg(windows)]
+mod keychain {
+    use anyhow::Result;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{LocalFree, HLOCAL};
+    use windows::Win32::Security::Cryptography::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    /// Wraps the key with DPAPI under the current account; the wrapped key goes in the file
+    pub fn store(key: &[u8]) -> Result<Vec<u8>> {
+        dpapi(key, true)
+    }
+
+    pub fn load(wrapped: &[u8]) -> Result<Vec<u8>> {
+        dpapi(wrapped, false)
+    }
+
+    fn dpapi(data: &[u8], protect: bool) -> Result<Vec<u8>> {
+        let input = CRYPT_INTEGER_BLOB {
+            cbData: data.len() as u32,
+            pbData: data.as_ptr() as *mut u8,
+        };
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        unsafe {
+            if protect {
+                CryptProtectData(
+                    &input,
+                    PCWSTR::null(),
+                    None,
+                    None,
+                    None,
+                    CRYPTPROTECT_UI_FORBIDDEN,
+                    &mut output,
+                )?;
+            } else {
+                CryptUnprotectData(
+                    &input,
+                    None,
+                    None,
+                    None,
+                    None,
+                    CRYPTPROTECT_UI_FORBIDDEN,
+                    &mut output,
+                )?;
+            }
+            let bytes = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+            let _ = LocalFree(HLOCAL(output.pbData as *mut _));
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod tpm {
+    use super::run_with_input;
+    use crate::domain::config::ServerConfig;
+    use anyhow::Result;
+
+    /// Seals the key to the TPM; the returned credential only decrypts on this machine
+    pub fn seal(key: &[u8]) -> Result<Vec<u8>> {
+        let name = format!("--name={}", ServerConfig::SECRETS_TPM_NAME);
+        run_with_input(
+            "systemd-creds",
+            &["encrypt", "--with-key=tpm2", &name, "-", "-"],
+            key,
+        )
+    }
+
+    pub fn unseal(wrapped: &[u8]) -> Result<Vec<u8>> {
+        let name = format!("--name={}", ServerConfig::SECRETS_TPM_NAME);
+        run_with_input("systemd-creds", &["decrypt", &name, "-", "-"], wrapped)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod tpm {
+    use anyhow::{bail, Result};
+
+    const UNSUPPORTED: &str =
+        "TPM unlocking is only supported on Linux with systemd-creds; use keychain instead";
+
+    pub fn seal(_key: &[u8]) -> Result<Vec<u8>> {
+        bail!(UNSUPPORTED)
+    }
+
+    pub fn unseal(_wrapped: &[u8]) -> Result<Vec<u8>> {
+        bail!(UNSUPPORTED)
+    }
+}
@@ -0,0 +1,196 @@
+//! Credentials kept encrypted in the config directory, sealed with XChaCha20-Poly1305
+
+mod key_source;
+
+use crate::domain::config::ServerConfig;
+use crate::utils::{from_hex, to_hex};
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+const NONCE_LEN: usize = 24;
+const SECRETS_VERSION: u32 = 1;
+
+static KEY: OnceLock<Key> = OnceLock::new();
+
+/// Where the key for sealed values comes from at startup
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnlockMethod {
+    /// Typed at startup, or read from `POINTZERVER_PASSPHRASE`
+    Passphrase,
+    /// macOS Keychain, the Secret Service on Linux, or DPAPI on Windows
+    Keychain,
+    /// Sealed to this machine's TPM with `systemd-creds` (Linux)
+    Tpm,
+}
+
+impl std::str::FromStr for UnlockMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "passphrase" => Ok(Self::Passphrase),
+            "keychain" => Ok(Self::Keychain),
+            "tpm" => Ok(Self::Tpm),
+            _ => bail!(
+                "Unknown unlock method '{}'; use passphrase, keychain or tpm",
+                name
+            ),
+        }
+    }
+}
+
+/// Contents of `secrets.json`
+#[derive(Serialize, Deserialize, Debug)]
+struct KeyFile {
+    version: u32,
+    unlock: UnlockMethod,
+    /// Argon2id salt, for `passphrase`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    salt: String,
+    /// The key as the unlock method sealed it; empty when the keychain holds it instead
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    wrapped_key: String,
+}
+
+/// Recovers the key named in `path`, prompting if it's passphrase-protected
+///
+/// Returns the unlock method, or `None` when secrets were never encrypted.
+pub fn unlock(path: &Path) -> Result<Option<UnlockMethod>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let file: KeyFile =
+        serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))?;
+    let salt = from_hex(&file.salt).ok_or_else(|| anyhow!("Invalid salt in secrets file"))?;
+    let wrapped =
+        from_hex(&file.wrapped_key).ok_or_else(|| anyhow!("Invalid key in secrets file"))?;
+    let key = key_source::unwrap(file.unlock, &salt, &wrapped)?;
+    if key.len() != ServerConfig::SECRET_KEY_LEN {
+        bail!(
+            "The {:?} unlock returned a key of the wrong length",
+            file.unlock
+        );
+    }
+    let _ = KEY.set(*Key::from_slice(&key));
+    Ok(Some(file.unlock))
+}
+
+/// Creates a key protected by `method`, records it in `path` and unlocks with it
+pub fn enable(path: &Path, method: UnlockMethod) -> Result<()> {
+    if path.exists() {
+        bail!("Secrets are already encrypted; see {}", path.display());
+    }
+    let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+    let (salt, wrapped) = key_source::wrap(method, &key)?;
+    let file = KeyFile {
+        version: SECRETS_VERSION,
+        unlock: method,
+        salt: to_hex(&salt),
+        wrapped_key: to_hex(&wrapped),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    let _ = KEY.set(key);
+    Ok(())
+}
+
+/// `value` as it should be written to disk: sealed once unlocked, plain otherwise
+pub fn seal(value: &str) -> Result<String> {
+    match KEY.get() {
+        Some(key) => seal_with(key, value),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// `value` as read from disk, opened if it was sealed
+///
+/// Values without the prefix predate encryption and are read as they are.
+pub fn open(value: &str) -> Result<String> {
+    if !is_sealed(value) {
+        return Ok(value.to_string());
+    }
+    let key = KEY
+        .get()
+        .ok_or_else(|| anyhow!("Found an encrypted secret, but secrets haven't been unlocked"))?;
+    open_with(key, value)
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(ServerConfig::SECRET_PREFIX)
+}
+
+pub(crate) fn seal_with(key: &Key, value: &str) -> Result<String> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key)
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt a secret"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}{}",
+        ServerConfig::SECRET_PREFIX,
+        to_hex(&sealed)
+    ))
+}
+
+pub(crate) fn open_with(key: &Key, value: &str) -> Result<String> {
+    let sealed = value
+        .strip_prefix(ServerConfig::SECRET_PREFIX)
+        .and_then(from_hex)
+        .filter(|sealed| sealed.len() >= NONCE_LEN)
+        .ok_or_else(|| anyhow!("Malformed encrypted secret"))?;
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = XChaCha20Poly1305::new(key)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Encrypted secret doesn't match the unlocked key"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_values_open_only_with_their_key() {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let sealed = seal_with(&key, "0123abcd").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("0123abcd"));
+        assert_eq!(open_with(&key, &sealed).unwrap(), "0123abcd");
+
+        let other = XChaCha20Poly1305::generate_key(&mut OsRng);
+        assert!(open_with(&other, &sealed).is_err());
+        assert!(open_with(&key, "sealed:00ff").is_err());
+        assert_eq!(open("plain-room").unwrap(), "plain-room");
+    }
+
+    #[test]
+    fn test_passphrase_wrapped_key_round_trips() {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let (salt, wrapped) = key_source::wrap_with_passphrase(&key, "correct horse").unwrap();
+        let unwrapped = key_source::unwrap_with_passphrase(&salt, &wrapped, "correct horse");
+        assert_eq!(unwrapped.unwrap(), key.to_vec());
+        assert!(key_source::unwrap_with_passphrase(&salt, &wrapped, "battery staple").is_err());
+    }
+}
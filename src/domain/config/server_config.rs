@@ -3,15 +3,302 @@ pub struct ServerConfig;
 impl ServerConfig {
     pub const DISCOVERY_PORT: u16 = 45454;
     pub const COMMAND_PORT: u16 = 45455;
+    pub const COMMAND_TCP_PORT: u16 = 45456;
+    pub const PREVIEW_PORT: u16 = 45457;
+    pub const STATUS_PORT: u16 = 45460;
     pub const DISCOVER_MESSAGE: &'static str = "DISCOVER";
     pub const DISCOVERY_BUFFER_SIZE: usize = 1024;
+    pub const DISCOVERY_RESCAN_INTERVAL_SECS: u64 = 30;
     pub const COMMAND_BUFFER_SIZE: usize = 4096;
+    pub const MAX_BATCH_COMMANDS: usize = 64;
     pub const UNKNOWN_HOSTNAME: &'static str = "Unknown";
 
+    // Dispatch queue
+    pub const DISPATCH_QUEUE_CAPACITY: usize = 256;
+    pub const DISPATCH_LATENCY_HISTORY: usize = 10;
+    pub const DISPATCH_LATENCY_SAMPLES: usize = 500;
+    pub const STALE_MOVE_DEFAULT_BUDGET_MS: u64 = 150;
+    pub const REORDER_DEFAULT_WINDOW: usize = 16;
+    pub const REORDER_DEFAULT_MAX_DELAY_MS: u64 = 30;
+    /// Sequence numbers remembered behind the highest one, for dropping duplicates
+    pub const REPLAY_WINDOW: u64 = 64;
+    /// Signing sessions a token keeps open at once; opening another closes the oldest
+    pub const SIGNING_SESSIONS_PER_TOKEN: usize = 4;
+    /// Replies waiting for a TCP client before further ones are dropped
+    pub const TCP_REPLY_QUEUE: usize = 32;
+    /// A failed input group skips its steps this long at most, in case its `EndGroup` was lost
+    pub const INPUT_GROUP_FAILED_TTL_MS: u64 = 2000;
+
+    // Client sessions
+    pub const SESSION_DEFAULT_TIMEOUT_SECS: u64 = 5;
+    pub const SESSION_CHECK_MS: u64 = 500;
+    /// Paired clients quiet this long get a `Probe`; one unanswered by the next marks them offline
+    pub const CLIENT_PROBE_INTERVAL_SECS: u64 = 30;
+    /// A token quiet this long starts a new session rather than resuming its old one
+    pub const SESSION_RESUME_TTL_SECS: u64 = 3600;
+    pub const SESSION_MAX: usize = 1024;
+
+    // Clock sync
+    pub const CLOCK_SYNC_SAMPLES: usize = 8;
+
+    // Diagnostics
+    pub const PROFILE_DEFAULT_SECS: u64 = 1;
+    pub const PROFILE_MAX_SECS: u64 = 10;
+    pub const FLAMEGRAPH_SAMPLE_HZ: i32 = 100;
+
+    // Persistence
+    pub const APP_DIR_NAME: &'static str = "pointzerver";
+    pub const SETTINGS_FILE: &'static str = "settings.json";
+    pub const TOKENS_FILE: &'static str = "tokens.json";
+    pub const ADMIN_TOKEN_FILE: &'static str = "admin.token";
+    /// Present once `encrypt-secrets` has run; holds the wrapped key for sealed values
+    pub const SECRETS_FILE: &'static str = "secrets.json";
+    pub const LOG_FILE: &'static str = "pointzerver.log";
+    pub const LOCK_FILE: &'static str = "pointzerver.lock";
+    /// Created next to the executable in portable mode
+    pub const PORTABLE_DIR_NAME: &'static str = "pointzerver-data";
+
+    // Single instance
+    pub const TAKEOVER_TIMEOUT_SECS: u64 = 10;
+    pub const TAKEOVER_POLL_MS: u64 = 100;
+
+    // Config bundles (export-config / import-config)
+    pub const BUNDLE_MAGIC: &'static [u8; 4] = b"PZB1";
+    pub const BUNDLE_SALT_LEN: usize = 16;
+
+    // Encrypted secrets (encrypt-secrets)
+    pub const SECRET_PREFIX: &'static str = "sealed:";
+    pub const SECRET_KEY_LEN: usize = 32;
+    /// Read instead of prompting, for services that start without a terminal
+    pub const SECRETS_PASSPHRASE_ENV: &'static str = "POINTZERVER_PASSPHRASE";
+    /// Service and account the key is filed under in the OS keychain
+    pub const SECRETS_KEYCHAIN_SERVICE: &'static str = "pointzerver";
+    pub const SECRETS_KEYCHAIN_ACCOUNT: &'static str = "secrets";
+    /// Credential name the key is sealed under with `systemd-creds`
+    pub const SECRETS_TPM_NAME: &'static str = "pointzerver-secrets";
+
+    // Audit log
+    pub const AUDIT_LOG_FILE: &'static str = "audit.log";
+    pub const AUDIT_DEFAULT_MAX_BYTES: u64 = 5 * 1024 * 1024;
+    pub const AUDIT_DEFAULT_KEEP: usize = 3;
+    pub const AUDIT_QUERY_DEFAULT: usize = 100;
+    pub const AUDIT_QUERY_MAX: usize = 1000;
+
+    // Webhooks
+    pub const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+    pub const WEBHOOK_SIGNATURE_HEADER: &'static str = "X-PointZerver-Signature";
+    /// The body's `at`, which the signature covers too, so receivers can refuse replays
+    pub const WEBHOOK_TIMESTAMP_HEADER: &'static str = "X-PointZerver-Timestamp";
+    /// Posts in flight at once; events past this are dropped rather than queued
+    pub const WEBHOOK_MAX_IN_FLIGHT: usize = 8;
+    /// Denied commands sent one by one per window; the rest are counted into a summary
+    pub const WEBHOOK_DENIED_BURST: u32 = 5;
+    pub const WEBHOOK_DENIED_WINDOW_SECS: u64 = 60;
+
+    // Support bundles (support-bundle)
+    /// Only the newest part of the log goes in, which is where the problem usually is
+    pub const SUPPORT_LOG_TAIL_BYTES: u64 = 1024 * 1024;
+    pub const SUPPORT_FETCH_TIMEOUT_SECS: u64 = 5;
+
+    // Soak tests (soak)
+    pub const SOAK_DEFAULT_MINUTES: u64 = 60;
+    pub const SOAK_DEFAULT_INTERVAL_MS: u64 = 20;
+    /// A probe unanswered this long counts as lost, even if its `Pong` turns up later
+    pub const SOAK_REPLY_TIMEOUT_MS: u64 = 2000;
+    pub const SOAK_WINDOW_SECS: u64 = 60;
+
     // Input simulation delays
     pub const MOUSE_CLICK_DELAY_MS: u64 = 10;
     #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
     pub const DOUBLE_CLICK_TIMEOUT_MS: u64 = 350;
+    pub const MAX_CLICK_COUNT: u8 = 3;
+    pub const MULTI_CLICK_INTERVAL_MS: u64 = 40;
+    pub const LONG_PRESS_MAX_MS: u64 = 5000;
+    pub const DRAG_MAX_MS: u64 = 5000;
+    /// Time between the interpolated moves of a `Drag`, about one frame at 120 Hz
+    pub const DRAG_STEP_MS: u64 = 8;
+    pub const GESTURE_DEFAULT_MS: u64 = 250;
+    pub const GESTURE_MAX_MS: u64 = 2000;
+    /// Touch gestures report progress this often
+    pub const GESTURE_STEP_MS: u64 = 8;
+    /// How far apart a gesture's fingers start, in pixels
+    pub const GESTURE_SPREAD_PX: f64 = 200.0;
+    pub const GESTURE_MAX_FINGERS: u8 = 5;
+    /// Gives X11 clients time to read a character before its keycode is remapped to the next
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub const TYPE_TEXT_REMAP_DELAY_MS: u64 = 5;
     pub const FALLBACK_SCREEN_WIDTH: f64 = 1920.0;
     pub const FALLBACK_SCREEN_HEIGHT: f64 = 1080.0;
+    pub const DESKTOP_BOUNDS_REFRESH_SECS: u64 = 5;
+    /// Focus changes often, so the window a confined pointer stays in is re-read sooner
+    pub const FOCUSED_WINDOW_REFRESH_MS: u64 = 250;
+
+    // Pointer smoothing
+    /// A pause this long ends a stroke; whatever the filter still holds back is caught up
+    pub const SMOOTHING_STROKE_GAP_MS: u64 = 150;
+    pub const SMOOTHING_DERIVATIVE_CUTOFF_HZ: f64 = 1.0;
+    pub const SMOOTHING_MIN_ALPHA: f64 = 0.05;
+    pub const SMOOTHING_MIN_CUTOFF_HZ: f64 = 0.01;
+
+    // Touch jitter rejection
+    pub const JITTER_BURST_MIN_PX: f64 = 80.0;
+    pub const JITTER_BURST_RATIO: f64 = 10.0;
+    /// Moves a stroke needs before a spike can be judged against them
+    pub const JITTER_BURST_MIN_SAMPLES: usize = 3;
+    pub const JITTER_AVERAGE_WINDOW: usize = 8;
+
+    // Hot corners
+    pub const HOT_CORNER_DEFAULT_DWELL_MS: u64 = 600;
+    pub const HOT_CORNER_DEFAULT_SIZE_PX: f64 = 2.0;
+    pub const HOT_CORNER_CHECK_MS: u64 = 100;
+
+    // Command validation
+    pub const MAX_MOUSE_BUTTON: u8 = 5;
+    /// Largest scroll delta per command on either axis
+    pub const MAX_SCROLL_DELTA: f64 = 1000.0;
+    /// Longest `TypeText`, in characters
+    pub const MAX_TYPE_TEXT_CHARS: usize = 1000;
+    pub const MAX_BRIGHTNESS_PERCENT: u8 = 100;
+
+    // Pairing brute-force protection
+    pub const PAIRING_MAX_FAILED_ATTEMPTS: u32 = 3;
+    pub const PAIRING_LOCKOUT_BASE_SECS: u64 = 30;
+    pub const PAIRING_LOCKOUT_MAX_SECS: u64 = 3600;
+
+    // Pairing approval on the host
+    pub const PAIRING_REQUEST_TTL_SECS: u64 = 120;
+    pub const PAIRING_MAX_PENDING_REQUESTS: usize = 8;
+    pub const PAIRING_QR_SVG_PX: u32 = 256;
+
+    // Confirming destructive commands
+    pub const CONFIRMATION_TTL_SECS: u64 = 15;
+    pub const CONFIRMATION_MAX_PENDING: usize = 32;
+
+    // Per-client rate limiting
+    pub const RATE_LIMIT_DEFAULT_PER_SEC: u32 = 500;
+    pub const RATE_LIMIT_DEFAULT_BURST: u32 = 250;
+    /// Clients tracked before idle ones, whose buckets have refilled, are forgotten
+    pub const RATE_LIMIT_MAX_CLIENTS: usize = 256;
+
+    // Guest access
+    pub const GUEST_TOKEN_DEFAULT_MINUTES: u64 = 30;
+    pub const GUEST_TOKEN_MAX_MINUTES: u64 = 24 * 60;
+
+    // Geofencing
+    pub const GEOFENCE_CHECK_INTERVAL_SECS: u64 = 15;
+
+    // Cursor size
+    pub const CURSOR_MAX_SCALE: f64 = 4.0;
+    pub const CURSOR_RESTORE_IDLE_SECS: u64 = 60;
+    pub const CURSOR_RESTORE_CHECK_SECS: u64 = 5;
+
+    // Zoom follow
+    /// Magnification `ZoomFollow` applies on Windows, where the server does the zooming itself
+    pub const ZOOM_FOLLOW_LEVEL: f64 = 2.0;
+    pub const CURSOR_PARKING_CHECK_SECS: u64 = 1;
+    /// Kept off the corner pixel itself so parking doesn't set off the OS's hot corners
+    pub const CURSOR_PARKING_INSET_PX: f64 = 2.0;
+
+    // Scroll calibration
+    /// Lines `CalibrateScroll` scrolls, enough to see which way the page went
+    pub const SCROLL_PROBE_LINES: f64 = 5.0;
+
+    // Pointer tuning
+    /// Motion watched before suggesting a curve, from a client's first move
+    pub const POINTER_TUNING_WINDOW_SECS: u64 = 60;
+    /// Moves needed before suggesting; a client that barely moved keeps being watched
+    pub const POINTER_TUNING_MIN_MOVES: u32 = 200;
+    /// Length of a typical move after tuning, in desktop units
+    pub const POINTER_TUNING_TARGET_MOVE: f64 = 6.0;
+    /// How much longer fast moves (95th percentile) should be than typical ones after tuning
+    pub const POINTER_TUNING_TARGET_SPREAD: f64 = 8.0;
+
+    // Game mode
+    /// Nice value the server runs at on Linux and macOS while a client is in game mode
+    pub const GAME_MODE_NICE: i32 = -5;
+
+    // Display brightness
+    /// Percentage points per `BrightnessUp` or `BrightnessDown`
+    pub const BRIGHTNESS_STEP_PERCENT: i16 = 10;
+
+    // Hover captions
+    pub const CAPTION_DEFAULT_POLL_MS: u64 = 250;
+
+    // Local input detection
+    pub const PRESENCE_POLL_INTERVAL_MS: u64 = 100;
+    pub const PRESENCE_INJECTION_TOLERANCE_MS: u64 = 50;
+    pub const PRESENCE_DEFAULT_GRACE_MS: u64 = 3000;
+    /// Ctrl+Alt+Esc, pressed twice, suspends remote input
+    pub const KILL_SWITCH_KEY: &'static str = "Escape";
+    /// Both presses of the kill-switch hotkey must land within this
+    pub const KILL_SWITCH_DOUBLE_PRESS_MS: u64 = 600;
+
+    // Macro recording
+    pub const MACRO_DEFAULT_MAX_SECS: u64 = 30;
+    pub const MACRO_DEFAULT_COUNTDOWN_SECS: u64 = 3;
+    /// Ctrl+Alt+Enter at the desk allows a recording, and later saves it
+    pub const MACRO_CONSENT_KEY: &'static str = "\n";
+    /// How long the desk has to press the consent hotkey before the request lapses
+    pub const MACRO_CONSENT_TIMEOUT_SECS: u64 = 60;
+    /// Pointer motion within this of the previous recorded move is folded into it
+    pub const MACRO_MOVE_MERGE_MS: u64 = 16;
+
+    // Screen preview
+    pub const PREVIEW_DEFAULT_FPS: u32 = 5;
+    pub const PREVIEW_MAX_FPS: u32 = 15;
+    pub const PREVIEW_DEFAULT_MAX_WIDTH: usize = 480;
+    pub const PREVIEW_DEFAULT_QUALITY: u8 = 60;
+    pub const PREVIEW_BOUNDARY: &'static str = "pointzerver-frame";
+    /// Streams and snapshots served at once; they all share one capture loop
+    pub const PREVIEW_MAX_VIEWERS: usize = 4;
+    /// Furthest ahead a viewer ticket may expire, so a sniffed one is soon worthless
+    pub const PREVIEW_TICKET_MAX_SECS: u64 = 300;
+
+    // Notification mirroring
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub const NOTIFICATION_POLL_INTERVAL_MS: u64 = 2000;
+
+    // Rendezvous
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_RELAY_PORT: u16 = 45470;
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_KEEPALIVE_SECS: u64 = 20;
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_PEER_TIMEOUT_SECS: u64 = 60;
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_SWEEP_SECS: u64 = 10;
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_MAX_ROOMS: usize = 10_000;
+    #[cfg(feature = "rendezvous")]
+    pub const RENDEZVOUS_PUNCH_ATTEMPTS: usize = 3;
+
+    // Typed client
+    #[cfg(feature = "client")]
+    pub const CLIENT_REPLY_TIMEOUT_MS: u64 = 2000;
+    pub const CLIENT_RETRANSMIT_MS: u64 = 150;
+    pub const CLIENT_RETRANSMITS: u32 = 4;
+
+    // Windows service and its input agent
+    #[cfg(windows)]
+    pub const SERVICE_NAME: &'static str = "PointZerver";
+    #[cfg(windows)]
+    pub const AGENT_PIPE_PREFIX: &'static str = r"\\.\pipe\pointzerver-agent";
+    #[cfg(windows)]
+    pub const AGENT_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+    // Telemetry
+    pub const TELEMETRY_INTERVAL_HOURS: u64 = 24;
+
+    // Stats dashboard
+    pub const STATS_SLICE_SECS: u64 = 10;
+    pub const STATS_LONGEST_WINDOW_SECS: u64 = 15 * 60;
+    /// Sessions heard from this recently count as active clients
+    pub const STATS_ACTIVE_CLIENT_SECS: u64 = 60;
+    /// Per-client activity for `/clients/{id}/activity`, in 5-minute slices over a day
+    pub const ACTIVITY_BUCKET_SECS: u64 = 5 * 60;
+    pub const ACTIVITY_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+    // Clipboard peer sync (payload must fit a single command datagram)
+    pub const CLIPBOARD_SYNC_MAX_BYTES: usize = 2048;
+    pub const CLIPBOARD_SYNC_DEDUP_WINDOW_MS: u64 = 2000;
 }
@@ -0,0 +1,837 @@
+use chrono::NaiveTime;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{MacroStep, PointerAcceleration, Scope, SensitivityCurve, Smoothing};
+use crate::utils::migrations::{self, Schema};
+
+/// No migrations yet; the first change to the format of an existing section adds one
+const SETTINGS_SCHEMA: Schema = Schema {
+    name: "settings",
+    migrations: &[],
+};
+
+/// Runtime settings loaded from `settings.json` in the config directory
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub active_hours: ActiveHoursSettings,
+    pub audit: AuditSettings,
+    pub auth: AuthSettings,
+    pub captions: CaptionSettings,
+    pub clipboard_sync: ClipboardSyncSettings,
+    pub control: ControlSettings,
+    pub cursor_parking: CursorParkingSettings,
+    pub game_mode: GameModeSettings,
+    pub geofence: GeofenceSettings,
+    pub heartbeat: HeartbeatSettings,
+    pub hot_corners: HotCornerSettings,
+    pub jitter: JitterSettings,
+    pub key_guard: KeyGuardSettings,
+    pub latency: LatencySettings,
+    pub local_input: LocalInputSettings,
+    pub macros: MacroSettings,
+    pub network: NetworkSettings,
+    pub notifications: NotificationSettings,
+    /// Gain for each client move by its length, after any learned pointer curve
+    pub pointer_acceleration: PointerAcceleration,
+    pub pointer_tuning: PointerTuningSettings,
+    pub power: PowerSettings,
+    /// Live thumbnail of the display under the pointer, served over HTTP
+    pub preview: PreviewSettings,
+    pub profiles: ProfileSettings,
+    pub protocol: ProtocolSettings,
+    pub rate_limit: RateLimitSettings,
+    pub reorder: ReorderSettings,
+    pub rendezvous: RendezvousSettings,
+    pub scroll: ScrollSettings,
+    /// Pointer jitter filter for clients that haven't picked their own with `SetSmoothing`
+    pub smoothing: Smoothing,
+    pub telemetry: TelemetrySettings,
+    pub webhooks: WebhookSettings,
+}
+
+/// Daily window outside of which remote commands are refused
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ActiveHoursSettings {
+    pub enabled: bool,
+    /// Local time the window opens, as `HH:MM`
+    pub start: NaiveTime,
+    /// Local time the window closes; earlier than `start` wraps past midnight
+    pub end: NaiveTime,
+}
+
+impl Default for ActiveHoursSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: NaiveTime::from_hms_opt(8, 0, 0).expect("valid time"),
+            end: NaiveTime::from_hms_opt(22, 0, 0).expect("valid time"),
+        }
+    }
+}
+
+/// Append-only log of the commands remote clients injected, for admins to review
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AuditSettings {
+    pub enabled: bool,
+    /// Size at which `audit.log` is rotated
+    pub max_bytes: u64,
+    /// Rotated files kept besides the live one
+    pub keep: usize,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: ServerConfig::AUDIT_DEFAULT_MAX_BYTES,
+            keep: ServerConfig::AUDIT_DEFAULT_KEEP,
+        }
+    }
+}
+
+/// Command authentication
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Reject commands that carry no token (pairing is always allowed)
+    pub require_token: bool,
+    /// Refuse tokens sent in the clear; clients must send signed datagrams instead
+    pub require_signed: bool,
+    /// Refuse commands that need a scope unless they arrive over a Noise channel
+    pub require_encryption: bool,
+    /// Lock a client's session after this many seconds without input, until it sends
+    /// `RefreshSession`; 0 never locks
+    pub idle_lock_secs: u64,
+    /// PIN that `RefreshSession` must carry; empty lets the token alone refresh
+    pub session_pin: String,
+}
+
+/// Spoken feedback of the UI element under the remote pointer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CaptionSettings {
+    pub enabled: bool,
+    pub poll_ms: u64,
+}
+
+impl Default for CaptionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_ms: ServerConfig::CAPTION_DEFAULT_POLL_MS,
+        }
+    }
+}
+
+/// Clipboard propagation to other pointzerver instances (relay/KVM mode)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClipboardSyncSettings {
+    pub enabled: bool,
+    pub peers: Vec<SocketAddr>,
+    pub max_bytes: usize,
+}
+
+impl Default for ClipboardSyncSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: Vec::new(),
+            max_bytes: ServerConfig::CLIPBOARD_SYNC_MAX_BYTES,
+        }
+    }
+}
+
+/// Moving the cursor out of the way, e.g. off a video, once remote input stops
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CursorParkingSettings {
+    /// Seconds without remote commands before parking; `None` never parks
+    pub idle_secs: Option<u64>,
+    pub spot: ParkingSpot,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParkingSpot {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// The low-latency pointer path for games, for clients listed here or that ask with `SetGameMode`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameModeSettings {
+    /// Client ids in game mode from the moment they connect
+    pub clients: Vec<String>,
+    /// Fills in a single lost move by repeating the one before it
+    pub prediction: bool,
+    /// Runs the server at raised scheduling priority while any client is in game mode
+    pub raise_priority: bool,
+}
+
+impl Default for GameModeSettings {
+    fn default() -> Self {
+        Self {
+            clients: Vec::new(),
+            prediction: false,
+            raise_priority: true,
+        }
+    }
+}
+
+/// Networks on which remote control is allowed
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GeofenceSettings {
+    pub enabled: bool,
+    pub trusted_subnets: Vec<IpNet>,
+    pub trusted_ssids: Vec<String>,
+}
+
+/// Which connected clients may drive the pointer and keyboard
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub mode: ControlMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlMode {
+    /// Every client's input runs
+    #[default]
+    Shared,
+    /// The first client to send input has control until it releases it or goes silent
+    FirstCome,
+    /// Input runs only from the client that claimed control with `TakeControl`
+    Exclusive,
+}
+
+/// Expiry of clients that go silent, letting go of the input they held
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HeartbeatSettings {
+    pub enabled: bool,
+    /// Silence after which a client's held buttons, keys and modifiers are released
+    pub timeout_secs: u64,
+}
+
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_secs: ServerConfig::SESSION_DEFAULT_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Desktop actions fired when the remote-driven pointer rests in a screen corner
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HotCornerSettings {
+    pub top_left: Option<CornerAction>,
+    pub top_right: Option<CornerAction>,
+    pub bottom_left: Option<CornerAction>,
+    pub bottom_right: Option<CornerAction>,
+    /// How long the pointer has to stay in the corner
+    pub dwell_ms: u64,
+    /// Distance from the corner, in pixels on each axis, that still counts as in it
+    pub size_px: f64,
+}
+
+impl Default for HotCornerSettings {
+    fn default() -> Self {
+        Self {
+            top_left: None,
+            top_right: None,
+            bottom_left: None,
+            bottom_right: None,
+            dwell_ms: ServerConfig::HOT_CORNER_DEFAULT_DWELL_MS,
+            size_px: ServerConfig::HOT_CORNER_DEFAULT_SIZE_PX,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CornerAction {
+    /// Mission Control on macOS, Task View on Windows, the overview on Linux desktops
+    MissionControl,
+    ShowDesktop,
+    Lock,
+}
+
+/// Ignoring accidental touch-sensor motion during precise work
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct JitterSettings {
+    /// Moves are held back until they add up to this many pixels; 0 passes everything
+    pub dead_zone_px: f64,
+    /// Drop a lone move much larger than the stroke's recent ones, such as a palm touch
+    pub burst_rejection: bool,
+    /// Moves at most this large are never treated as a burst
+    pub burst_min_px: f64,
+    /// How many times the stroke's average move size a burst must exceed
+    pub burst_ratio: f64,
+}
+
+impl Default for JitterSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone_px: 0.0,
+            burst_rejection: false,
+            burst_min_px: ServerConfig::JITTER_BURST_MIN_PX,
+            burst_ratio: ServerConfig::JITTER_BURST_RATIO,
+        }
+    }
+}
+
+/// Shortcuts that close apps, lock the screen or sign out, held back from non-admin clients
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KeyGuardSettings {
+    pub enabled: bool,
+    /// `modifier+…+key`, e.g. `"alt+F4"`; modifiers are `ctrl`, `alt`, `shift` and `meta`
+    pub chords: Vec<String>,
+    pub action: GuardAction,
+}
+
+/// What a non-admin client gets when it sends a guarded chord
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardAction {
+    /// Held until the sender confirms it with the nonce the server hands back
+    #[default]
+    Confirm,
+    /// Always refused
+    Block,
+}
+
+impl Default for KeyGuardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chords: ["ctrl+alt+Delete", "meta+q", "alt+F4", "meta+l"]
+                .map(String::from)
+                .to_vec(),
+            action: GuardAction::Confirm,
+        }
+    }
+}
+
+/// Dropping pointer motion that arrives too late to be useful
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LatencySettings {
+    /// Discard timestamped mouse moves older than `budget_ms`; clicks and keys always run
+    pub discard_stale_moves: bool,
+    pub budget_ms: u64,
+}
+
+impl LatencySettings {
+    /// How old a timestamped move may be before the queue drops it; `None` keeps them all
+    pub fn stale_move_budget(&self) -> Option<Duration> {
+        self.discard_stale_moves
+            .then(|| Duration::from_millis(self.budget_ms))
+    }
+}
+
+impl Default for LatencySettings {
+    fn default() -> Self {
+        Self {
+            discard_stale_moves: false,
+            budget_ms: ServerConfig::STALE_MOVE_DEFAULT_BUDGET_MS,
+        }
+    }
+}
+
+/// Yielding to the person physically at the machine
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LocalInputSettings {
+    /// Suppress remote input while local mouse/keyboard activity is detected
+    pub auto_yield: bool,
+    /// How long remote input stays suppressed after the last local input
+    pub grace_ms: u64,
+    /// Ctrl+Alt+Esc pressed twice suspends remote input until pressed twice again
+    pub kill_switch: bool,
+}
+
+impl Default for LocalInputSettings {
+    fn default() -> Self {
+        Self {
+            auto_yield: false,
+            grace_ms: ServerConfig::PRESENCE_DEFAULT_GRACE_MS,
+            kill_switch: true,
+        }
+    }
+}
+
+/// Input sequences recorded at this desk with the status API and run remotely with `RunMacro`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MacroSettings {
+    /// Longest recording allowed, whatever a request asks for
+    pub max_secs: u64,
+    /// Seconds counted down between starting a recording and capturing input
+    pub countdown_secs: u64,
+    /// Recorded macros by name
+    pub saved: BTreeMap<String, Vec<MacroStep>>,
+}
+
+impl Default for MacroSettings {
+    fn default() -> Self {
+        Self {
+            max_secs: ServerConfig::MACRO_DEFAULT_MAX_SECS,
+            countdown_secs: ServerConfig::MACRO_DEFAULT_COUNTDOWN_SECS,
+            saved: BTreeMap::new(),
+        }
+    }
+}
+
+/// Socket tuning for congested or QoS-managed networks, applied to discovery and commands
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// UDP receive buffer in bytes; the OS default when unset
+    pub recv_buffer_bytes: Option<usize>,
+    /// DSCP code point (0–63) marked on outgoing datagrams, e.g. 46 (EF) for interactive traffic
+    pub dscp: Option<u8>,
+    /// Set `SO_REUSEADDR` so a restarted server can rebind its ports immediately
+    pub reuse_address: bool,
+    /// Also accept commands over TCP for clients that want ordered, reliable delivery
+    pub tcp: bool,
+    /// Client subnets answered by discovery and the command ports; every address when empty
+    pub allow: Vec<IpNet>,
+    /// Client subnets always ignored, even when inside `allow`
+    pub deny: Vec<IpNet>,
+}
+
+impl NetworkSettings {
+    /// Whether a client at `ip` may discover the server and send it commands
+    pub fn admits(&self, ip: IpAddr) -> bool {
+        // Dual-stack sockets report IPv4 clients as `::ffff:a.b.c.d`
+        let ip = ip.to_canonical();
+        let listed = |nets: &[IpNet]| nets.iter().any(|net| net.contains(&ip));
+        (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.deny)
+    }
+}
+
+/// Forwarding desktop notification app names and titles to clients
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+}
+
+/// Learning a sensitivity curve from how each paired client moves the pointer
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PointerTuningSettings {
+    /// Watch the first minute of motion from each paired client without a curve
+    pub enabled: bool,
+    /// Save and use suggested curves instead of only publishing them
+    pub apply: bool,
+    /// Curves by client id; they apply even with `enabled` off, and a removed one is relearned
+    pub curves: HashMap<String, SensitivityCurve>,
+}
+
+/// Locking, sleeping, shutting down and restarting the host with `Power`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PowerSettings {
+    /// Whether non-admin clients must confirm each `Power` command with a nonce
+    pub confirm: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self { confirm: true }
+    }
+}
+
+/// The MJPEG thumbnail stream clients show while controlling the host
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PreviewSettings {
+    pub enabled: bool,
+    /// Frames per second, capped at 15
+    pub fps: u32,
+    /// Frames wider than this are scaled down, keeping the aspect ratio
+    pub max_width: usize,
+    /// JPEG quality from 1 to 100
+    pub quality: u8,
+}
+
+impl Default for PreviewSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fps: ServerConfig::PREVIEW_DEFAULT_FPS,
+            max_width: ServerConfig::PREVIEW_DEFAULT_MAX_WIDTH,
+            quality: ServerConfig::PREVIEW_DEFAULT_QUALITY,
+        }
+    }
+}
+
+/// Named bundles of input tuning and permissions that clients or the tray switch between
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProfileSettings {
+    /// Profile applied at startup; switching at runtime does not rewrite this file
+    pub active: Option<String>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self {
+            active: None,
+            profiles: vec![
+                Profile {
+                    name: "Presentation".to_string(),
+                    gestures: false,
+                    scopes: vec![Scope::Pointer, Scope::Keyboard],
+                    ..Profile::default()
+                },
+                Profile {
+                    name: "Couch".to_string(),
+                    pointer_sensitivity: 1.8,
+                    scroll_speed: 2.0,
+                    ..Profile::default()
+                },
+                Profile {
+                    name: "Precision".to_string(),
+                    pointer_sensitivity: 0.4,
+                    scroll_speed: 0.5,
+                    ..Profile::default()
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Multiplier on relative pointer motion
+    pub pointer_sensitivity: f64,
+    /// Multiplier on scroll deltas
+    pub scroll_speed: f64,
+    /// Inverts scroll direction
+    pub natural_scroll: bool,
+    /// Allow multi-clicks and long presses; when off they arrive as single clicks
+    pub gestures: bool,
+    /// Command scopes allowed while active, on top of what each token grants
+    pub scopes: Vec<Scope>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pointer_sensitivity: 1.0,
+            scroll_speed: 1.0,
+            natural_scroll: false,
+            gestures: true,
+            scopes: vec![
+                Scope::Pointer,
+                Scope::Keyboard,
+                Scope::Clipboard,
+                Scope::System,
+                Scope::Power,
+                Scope::Files,
+            ],
+        }
+    }
+}
+
+/// Old command and field names still accepted while clients migrate to renamed ones
+///
+/// These add to the server's built-in aliases, and replace any built-in alias with the same
+/// old name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProtocolSettings {
+    /// Old command `type` to its current name
+    pub command_aliases: HashMap<String, String>,
+    /// Per current command `type`, old field name to current one
+    pub field_aliases: HashMap<String, HashMap<String, String>>,
+}
+
+/// Per-client token bucket that keeps a flooding client from starving everyone else
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    /// Commands each client address may send per second, sustained
+    pub per_sec: u32,
+    /// Commands a client may send at once after being quiet
+    pub burst: u32,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            per_sec: ServerConfig::RATE_LIMIT_DEFAULT_PER_SEC,
+            burst: ServerConfig::RATE_LIMIT_DEFAULT_BURST,
+        }
+    }
+}
+
+/// Restoring send order of sequenced commands that UDP delivered out of order or twice
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReorderSettings {
+    pub enabled: bool,
+    /// Drop commands whose `seq` was already seen, even with reordering off
+    pub deduplicate: bool,
+    /// Commands held per client before a missing one is given up on
+    pub window: usize,
+    /// Longest a command waits for an earlier one that hasn't arrived
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReorderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deduplicate: true,
+            window: ServerConfig::REORDER_DEFAULT_WINDOW,
+            max_delay_ms: ServerConfig::REORDER_DEFAULT_MAX_DELAY_MS,
+        }
+    }
+}
+
+/// NAT traversal via a user-run relay; only used in builds with the `rendezvous` feature
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RendezvousSettings {
+    pub enabled: bool,
+    /// Relay address as `host:port`
+    pub relay: Option<String>,
+    /// Shared with the client to find each other on the relay; use a long random string
+    pub room: String,
+}
+
+/// How injected scrolling feels on this host, whatever the client sends
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScrollSettings {
+    /// Axes that run against the wheel convention on this host, found with `CalibrateScroll`
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// Multiplier on every scroll delta
+    pub speed: f64,
+    /// Reverses both axes, so content follows the fingers as on a touchscreen
+    pub natural: bool,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        Self {
+            invert_x: false,
+            invert_y: false,
+            speed: 1.0,
+            natural: false,
+        }
+    }
+}
+
+/// Opt-in anonymous usage reporting
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub interval_hours: u64,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_hours: ServerConfig::TELEMETRY_INTERVAL_HOURS,
+        }
+    }
+}
+
+/// Signed JSON POSTs of security-relevant events, for a SIEM or a chat channel
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    /// Every URL gets every notable event
+    pub urls: Vec<String>,
+    /// Key for the `X-PointZerver-Signature` HMAC; requests go unsigned when empty
+    pub secret: String,
+}
+
+/// Stands in for a secret setting wherever settings leave the process
+pub const REDACTED: &str = "[redacted]";
+
+/// Settings that identify the user's network or let others join their relay room
+const SECRET_SETTINGS: &[&str] = &[
+    "/auth/session_pin",
+    "/rendezvous/room",
+    "/rendezvous/relay",
+    "/telemetry/endpoint",
+    "/webhooks/urls",
+    "/webhooks/secret",
+    "/geofence/trusted_ssids",
+];
+
+impl Settings {
+    /// These settings as JSON with every secret replaced by [`REDACTED`]
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut settings = serde_json::to_value(self).unwrap_or_default();
+        for pointer in SECRET_SETTINGS {
+            // Unset secrets stay as they are, so a client can still tell a PIN isn't set
+            if let Some(value) = settings.pointer_mut(pointer) {
+                if !is_unset(value) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        settings
+    }
+
+    /// Loads settings from `path`, falling back to defaults if the file is missing or invalid
+    pub fn load_or_default(path: &Path) -> Self {
+        let contents = match migrations::load(path, &SETTINGS_SCHEMA) {
+            Ok(Some(contents)) => contents,
+            Ok(None) => return Self::default(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        serde_json::from_value(contents).unwrap_or_else(|e| {
+            log::warn!("Invalid settings in {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Writes `value` as the `key` section of the settings file, leaving the rest as it was
+    ///
+    /// An older file is migrated first, and the file written always records its `version`.
+    pub fn update_file<T: Serialize>(path: &Path, key: &str, value: &T) -> anyhow::Result<()> {
+        let mut root =
+            migrations::load(path, &SETTINGS_SCHEMA)?.unwrap_or_else(|| serde_json::json!({}));
+        let sections = match &mut root {
+            serde_json::Value::Object(sections) => sections,
+            _ => anyhow::bail!("{} is not a JSON object", path.display()),
+        };
+        sections
+            .entry("version")
+            .or_insert_with(|| SETTINGS_SCHEMA.current().into());
+        sections.insert(key.to_string(), serde_json::to_value(value)?);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&root)? + "\n")?;
+        Ok(())
+    }
+}
+
+fn is_unset(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::String(text) => text.is_empty(),
+        serde_json::Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_settings_use_defaults() {
+        let json = r#"{"clipboard_sync":{"enabled":true,"peers":["192.168.1.20:45455"]}}"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert!(settings.clipboard_sync.enabled);
+        assert_eq!(settings.clipboard_sync.peers.len(), 1);
+        assert_eq!(
+            settings.clipboard_sync.max_bytes,
+            ServerConfig::CLIPBOARD_SYNC_MAX_BYTES
+        );
+    }
+
+    #[test]
+    fn test_session_pin_and_room_redacted() {
+        let mut settings = Settings::default();
+        let value = settings.redacted();
+        assert_eq!(value.pointer("/auth/session_pin").unwrap(), "");
+        assert_eq!(value.pointer("/rendezvous/room").unwrap(), "");
+        settings.auth.session_pin = "4821".to_string();
+        settings.rendezvous.room = "blue-otter-harbor".to_string();
+        let value = settings.redacted();
+        assert_eq!(value.pointer("/auth/session_pin").unwrap(), REDACTED);
+        assert_eq!(value.pointer("/rendezvous/room").unwrap(), REDACTED);
+        assert!(!value.to_string().contains("4821"));
+    }
+
+    #[test]
+    fn test_active_hours_accept_hh_mm() {
+        let json = r#"{"active_hours":{"enabled":true,"start":"07:30","end":"21:00"}}"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            settings.active_hours.start,
+            NaiveTime::from_hms_opt(7, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_network_allow_and_deny_lists() {
+        let json = r#"{"network":{"allow":["192.168.1.0/24"],"deny":["192.168.1.66/32"]}}"#;
+        let network = serde_json::from_str::<Settings>(json).unwrap().network;
+        assert!(network.admits("192.168.1.20".parse().unwrap()));
+        assert!(network.admits("::ffff:192.168.1.20".parse().unwrap()));
+        assert!(!network.admits("192.168.1.66".parse().unwrap()));
+        assert!(!network.admits("10.0.0.5".parse().unwrap()));
+        assert!(NetworkSettings::default().admits("10.0.0.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let settings = Settings::load_or_default(Path::new("/nonexistent/settings.json"));
+        assert!(!settings.clipboard_sync.enabled);
+    }
+
+    #[test]
+    fn test_updated_files_record_their_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let created = dir.path().join("created.json");
+        Settings::update_file(&created, "scroll", &ScrollSettings::default()).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&created).unwrap()).unwrap();
+        assert_eq!(written["version"], SETTINGS_SCHEMA.current());
+
+        let unversioned = dir.path().join("unversioned.json");
+        std::fs::write(&unversioned, r#"{"heartbeat":{"timeout_secs":9}}"#).unwrap();
+        Settings::update_file(&unversioned, "scroll", &ScrollSettings::default()).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&unversioned).unwrap()).unwrap();
+        assert_eq!(written["version"], SETTINGS_SCHEMA.current());
+        assert_eq!(written["heartbeat"]["timeout_secs"], 9);
+    }
+}
@@ -1,10 +1,39 @@
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-/// Mouse button type alias for clarity
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{named_key, Scope, Smoothing, TouchGesture, WireFormat};
+
+/// 1 left, 2 right, 3 middle, 4 back and 5 forward
 pub type MouseButton = u8;
 
+/// Refuses NaN and infinities, which would otherwise poison the tracked cursor position
+fn finite<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Float(value),
+            &"a finite number",
+        ))
+    }
+}
+
+/// Middle of a display, where `MoveToDisplay` goes without a position
+fn centre() -> f64 {
+    0.5
+}
+
+fn gesture_ms() -> u64 {
+    ServerConfig::GESTURE_DEFAULT_MS
+}
+
 /// Modifier keys state
-#[derive(Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
 pub struct ModifierKeys {
     #[serde(default)]
     pub ctrl: bool,
@@ -16,17 +45,109 @@ pub struct ModifierKeys {
     pub meta: bool,
 }
 
+/// A completed `Ping` and `Pong`, echoed back so the server can time that one exchange
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub struct PingExchange {
+    /// The `Pong`'s `client_time_ms`
+    pub client_time_ms: u64,
+    /// The `Pong`'s `server_time_ms`
+    pub server_time_ms: u64,
+    /// When the `Pong` arrived, on the client's clock
+    pub received_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CaretDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CaretUnit {
+    Char,
+    Word,
+    Line,
+    Document,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollAxis {
+    X,
+    Y,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerState {
+    On,
+    Off,
+}
+
+/// What `Power` does to the whole system, unlike `DisplayPower`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    Lock,
+    Sleep,
+    Shutdown,
+    Restart,
+}
+
+/// Keys on a keyboard's media row, for using a phone as a media remote
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum MediaKey {
+    PlayPause,
+    NextTrack,
+    PrevTrack,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+}
+
 /// Command sent from client to server
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Command {
     MouseMove {
+        #[serde(deserialize_with = "finite")]
         x: f64,
+        #[serde(deserialize_with = "finite")]
         y: f64,
     },
     MouseClick {
         button: MouseButton,
     },
+    /// Repeated clicks delivered as one gesture; `count` 3 is a triple-click
+    MouseMultiClick {
+        button: MouseButton,
+        count: u8,
+    },
+    /// Holds `button` for `duration_ms` before releasing it
+    MouseLongPress {
+        button: MouseButton,
+        duration_ms: u64,
+    },
+    /// Presses `button`, moves by `dx`, `dy` evenly over `duration_ms`, then releases it
+    Drag {
+        button: MouseButton,
+        #[serde(deserialize_with = "finite")]
+        dx: f64,
+        #[serde(deserialize_with = "finite")]
+        dy: f64,
+        #[serde(default)]
+        duration_ms: u64,
+    },
+    /// Plays `gesture` out over `duration_ms` where the platform can inject touch
+    TouchGesture {
+        gesture: TouchGesture,
+        #[serde(default = "gesture_ms")]
+        duration_ms: u64,
+    },
     MouseDown {
         button: MouseButton,
     },
@@ -34,15 +155,30 @@ pub enum Command {
         button: MouseButton,
     },
     MouseScroll {
+        #[serde(deserialize_with = "finite")]
         delta_x: f64,
+        #[serde(deserialize_with = "finite")]
         delta_y: f64,
     },
+    /// Scrolls down on `y` or right on `x`, for the user to say whether that's what they saw
+    CalibrateScroll {
+        axis: ScrollAxis,
+    },
+    /// The user's answer to `CalibrateScroll`; `correct: false` inverts the axis from now on
+    ConfirmScrollDirection {
+        axis: ScrollAxis,
+        correct: bool,
+    },
     KeyPress {
+        /// A single character, or a named key such as `PageDown` or `F5`
+        #[schemars(schema_with = "named_key::key_schema")]
         key: String,
         #[serde(default)]
         modifiers: ModifierKeys,
     },
     KeyRelease {
+        /// A single character, or a named key such as `PageDown` or `F5`
+        #[schemars(schema_with = "named_key::key_schema")]
         key: String,
         #[serde(default)]
         modifiers: ModifierKeys,
@@ -53,13 +189,280 @@ pub enum Command {
     ModifierRelease {
         modifier: String,
     },
+    /// Taps a media key, which works whatever app has focus
+    MediaKey {
+        key: MediaKey,
+    },
+    /// Types `text` as-is through the platform's Unicode input, whatever the keyboard layout
+    TypeText {
+        text: String,
+    },
+    /// Enlarges the cursor for audience visibility; `1.0` restores the default size
+    SetCursorSize {
+        #[serde(deserialize_with = "finite")]
+        scale: f64,
+    },
+    /// Filters this client's pointer motion to reduce jitter from noisy touch input
+    SetSmoothing {
+        smoothing: Smoothing,
+    },
+    /// Sends this client's pointer motion down the low-latency path meant for games
+    SetGameMode {
+        enabled: bool,
+    },
+    /// Keeps the pointer inside the focused window, e.g. while driving one full-screen app
+    ConfineCursor {
+        enabled: bool,
+    },
+    /// Puts the pointer `x`, `y` of the way across display `display`, each from 0 to 1
+    MoveToDisplay {
+        display: usize,
+        #[serde(default = "centre", deserialize_with = "finite")]
+        x: f64,
+        #[serde(default = "centre", deserialize_with = "finite")]
+        y: f64,
+    },
+    /// Pans the OS screen zoom to follow the pointer, for presenting on a large display
+    ZoomFollow {
+        enabled: bool,
+    },
+    /// Blanks or wakes the displays without sleeping the system
+    DisplayPower {
+        state: PowerState,
+    },
+    /// Locks the session or sleeps, shuts down or restarts the host
+    Power {
+        action: PowerAction,
+    },
+    NightLight {
+        enabled: bool,
+    },
+    /// Steps the built-in display's brightness by 10 percentage points
+    BrightnessUp,
+    BrightnessDown,
+    BrightnessSet {
+        percent: u8,
+    },
+    /// Switches to a named settings profile
+    SetProfile {
+        name: String,
+    },
+    /// Plays back a macro recorded at the host, with its original timing
+    RunMacro {
+        name: String,
+    },
+    /// Text-editing helpers sent as the platform's own key chords
+    SelectWord,
+    SelectLine,
+    SelectAll,
+    MoveCaret {
+        direction: CaretDirection,
+        unit: CaretUnit,
+        #[serde(default)]
+        extend: bool,
+    },
+    /// Starts a group; if any grouped step fails, held input is released and reported
+    BeginGroup {
+        #[serde(default)]
+        id: Option<String>,
+    },
+    EndGroup,
+    /// Sets the host clipboard; `origin` identifies the relaying peer in clipboard sync
+    ClipboardSet {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        origin: Option<String>,
+    },
+    /// Exchanges the PIN shown on the host for an auth token
+    Pair {
+        pin: String,
+        #[serde(default)]
+        device_name: String,
+    },
+    /// Asks for pairing to be approved on the host, for users who'd rather not type a PIN
+    RequestPairing {
+        #[serde(default)]
+        device_name: String,
+    },
+    /// Unlocks a session that sat idle past `auth.idle_lock_secs`, without pairing again
+    RefreshSession {
+        /// The host's session PIN, when it set one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pin: Option<String>,
+    },
+    /// Clock probe; the client estimates its offset to the server from the `Pong`
+    Ping {
+        client_time_ms: u64,
+        /// The client's previous exchange, feeding the session's clock estimate
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous: Option<PingExchange>,
+    },
+    /// Claims the pointer and keyboard when `control.mode` isn't `shared`
+    TakeControl,
+    /// Gives control back so another client can take it
+    ReleaseControl,
+    /// Keeps the client's session alive while it has nothing else to send, such as mid-drag
+    Heartbeat,
+    /// Asks for the monitor layout; answered with `Displays`
+    GetDisplays,
+    /// Agrees on the encoding for the rest of the session; the server picks the first it can use
+    SelectFormat {
+        formats: Vec<WireFormat>,
+    },
+}
+
+impl Command {
+    /// Variant name, matching the wire `type` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::MouseMove { .. } => "MouseMove",
+            Command::MouseClick { .. } => "MouseClick",
+            Command::MouseMultiClick { .. } => "MouseMultiClick",
+            Command::MouseLongPress { .. } => "MouseLongPress",
+            Command::Drag { .. } => "Drag",
+            Command::TouchGesture { .. } => "TouchGesture",
+            Command::MouseDown { .. } => "MouseDown",
+            Command::MouseUp { .. } => "MouseUp",
+            Command::MouseScroll { .. } => "MouseScroll",
+            Command::CalibrateScroll { .. } => "CalibrateScroll",
+            Command::ConfirmScrollDirection { .. } => "ConfirmScrollDirection",
+            Command::KeyPress { .. } => "KeyPress",
+            Command::KeyRelease { .. } => "KeyRelease",
+            Command::ModifierPress { .. } => "ModifierPress",
+            Command::ModifierRelease { .. } => "ModifierRelease",
+            Command::MediaKey { .. } => "MediaKey",
+            Command::TypeText { .. } => "TypeText",
+            Command::SetCursorSize { .. } => "SetCursorSize",
+            Command::SetSmoothing { .. } => "SetSmoothing",
+            Command::SetGameMode { .. } => "SetGameMode",
+            Command::ConfineCursor { .. } => "ConfineCursor",
+            Command::MoveToDisplay { .. } => "MoveToDisplay",
+            Command::ZoomFollow { .. } => "ZoomFollow",
+            Command::DisplayPower { .. } => "DisplayPower",
+            Command::Power { .. } => "Power",
+            Command::NightLight { .. } => "NightLight",
+            Command::BrightnessUp => "BrightnessUp",
+            Command::BrightnessDown => "BrightnessDown",
+            Command::BrightnessSet { .. } => "BrightnessSet",
+            Command::SetProfile { .. } => "SetProfile",
+            Command::RunMacro { .. } => "RunMacro",
+            Command::SelectWord => "SelectWord",
+            Command::SelectLine => "SelectLine",
+            Command::SelectAll => "SelectAll",
+            Command::MoveCaret { .. } => "MoveCaret",
+            Command::BeginGroup { .. } => "BeginGroup",
+            Command::EndGroup => "EndGroup",
+            Command::ClipboardSet { .. } => "ClipboardSet",
+            Command::Pair { .. } => "Pair",
+            Command::RequestPairing { .. } => "RequestPairing",
+            Command::RefreshSession { .. } => "RefreshSession",
+            Command::Ping { .. } => "Ping",
+            Command::GetDisplays => "GetDisplays",
+            Command::TakeControl => "TakeControl",
+            Command::ReleaseControl => "ReleaseControl",
+            Command::Heartbeat => "Heartbeat",
+            Command::SelectFormat { .. } => "SelectFormat",
+        }
+    }
+
+    /// Lets go of something an earlier command started, so it must never be dropped
+    pub fn restores_state(&self) -> bool {
+        matches!(
+            self,
+            Command::MouseUp { .. }
+                | Command::KeyRelease { .. }
+                | Command::ModifierRelease { .. }
+                | Command::EndGroup
+        )
+    }
+
+    /// Too consequential to accept without a token even when `auth.require_token` is off
+    pub fn needs_token(&self) -> bool {
+        matches!(
+            self,
+            Command::Power { .. } | Command::DisplayPower { .. } | Command::RunMacro { .. }
+        )
+    }
+
+    /// Scope a token needs to issue this command; `None` means always allowed
+    pub fn scope(&self) -> Option<Scope> {
+        match self {
+            Command::MouseMove { .. }
+            | Command::MouseClick { .. }
+            | Command::MouseMultiClick { .. }
+            | Command::MouseLongPress { .. }
+            | Command::Drag { .. }
+            | Command::TouchGesture { .. }
+            | Command::MouseDown { .. }
+            | Command::MouseUp { .. }
+            | Command::MouseScroll { .. }
+            | Command::CalibrateScroll { .. }
+            | Command::ConfirmScrollDirection { .. }
+            | Command::SetCursorSize { .. }
+            | Command::SetSmoothing { .. }
+            | Command::SetGameMode { .. }
+            | Command::ConfineCursor { .. }
+            | Command::MoveToDisplay { .. }
+            | Command::ZoomFollow { .. }
+            | Command::TakeControl => Some(Scope::Pointer),
+            Command::KeyPress { .. }
+            | Command::KeyRelease { .. }
+            | Command::ModifierPress { .. }
+            | Command::ModifierRelease { .. }
+            | Command::MediaKey { .. }
+            | Command::TypeText { .. }
+            | Command::SelectWord
+            | Command::SelectLine
+            | Command::SelectAll
+            | Command::MoveCaret { .. }
+            | Command::RunMacro { .. } => Some(Scope::Keyboard),
+            Command::ClipboardSet { .. } => Some(Scope::Clipboard),
+            Command::DisplayPower { .. } | Command::Power { .. } => Some(Scope::Power),
+            Command::NightLight { .. }
+            | Command::BrightnessUp
+            | Command::BrightnessDown
+            | Command::BrightnessSet { .. }
+            | Command::SetProfile { .. } => Some(Scope::System),
+            Command::BeginGroup { .. }
+            | Command::EndGroup
+            | Command::Pair { .. }
+            | Command::RequestPairing { .. }
+            | Command::RefreshSession { .. }
+            | Command::Ping { .. }
+            | Command::GetDisplays
+            | Command::ReleaseControl
+            | Command::Heartbeat
+            | Command::SelectFormat { .. } => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde::de::value::{Error as ValueError, F64Deserializer};
+    use serde::de::IntoDeserializer;
     use serde_json;
 
+    #[test]
+    fn test_finite_rejects_nan_and_infinity() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let deserializer: F64Deserializer<ValueError> = value.into_deserializer();
+            let error = finite(deserializer).unwrap_err();
+            assert!(error.to_string().contains("expected a finite number"));
+        }
+        let deserializer: F64Deserializer<ValueError> = 2.5.into_deserializer();
+        assert_eq!(finite(deserializer).unwrap(), 2.5);
+    }
+
+    #[test]
+    fn test_parse_mouse_move_rejects_out_of_range() {
+        let json = r#"{"type":"MouseMove","x":1e999,"y":0}"#;
+        assert!(serde_json::from_str::<Command>(json).is_err());
+        let json = r#"{"type":"MouseMove","x":NaN,"y":0}"#;
+        assert!(serde_json::from_str::<Command>(json).is_err());
+    }
+
     #[test]
     fn test_parse_mouse_move() {
         let json = r#"{"type":"MouseMove","x":100.5,"y":200.5}"#;
@@ -87,6 +490,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_media_key() {
+        let json = r#"{"type":"MediaKey","key":"VolumeDown"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            Command::MediaKey {
+                key: MediaKey::VolumeDown
+            }
+        ));
+    }
+
     #[test]
     fn test_parse_mouse_click() {
         let json = r#"{"type":"MouseClick","button":1}"#;
@@ -97,6 +512,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_mouse_long_press() {
+        let json = r#"{"type":"MouseLongPress","button":1,"duration_ms":600}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        match cmd {
+            Command::MouseLongPress {
+                button,
+                duration_ms,
+            } => {
+                assert_eq!(button, 1);
+                assert_eq!(duration_ms, 600);
+            }
+            _ => panic!("Expected MouseLongPress"),
+        }
+    }
+
+    #[test]
+    fn test_parse_move_caret() {
+        let json = r#"{"type":"MoveCaret","direction":"left","unit":"word"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        match cmd {
+            Command::MoveCaret {
+                direction,
+                unit,
+                extend,
+            } => {
+                assert_eq!(direction, CaretDirection::Left);
+                assert_eq!(unit, CaretUnit::Word);
+                assert!(!extend);
+            }
+            _ => panic!("Expected MoveCaret"),
+        }
+    }
+
+    #[test]
+    fn test_parse_display_power() {
+        let json = r#"{"type":"DisplayPower","state":"off"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            Command::DisplayPower {
+                state: PowerState::Off
+            }
+        ));
+        assert_eq!(cmd.scope(), Some(Scope::Power));
+    }
+
+    #[test]
+    fn test_parse_power() {
+        let json = r#"{"type":"Power","action":"sleep"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            Command::Power {
+                action: PowerAction::Sleep
+            }
+        ));
+        assert_eq!(cmd.scope(), Some(Scope::Power));
+    }
+
+    #[test]
+    fn test_move_to_display_defaults_to_the_centre() {
+        let json = r#"{"type":"MoveToDisplay","display":1,"x":0.25}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            Command::MoveToDisplay { display: 1, x, y } if x == 0.25 && y == 0.5
+        ));
+        assert_eq!(cmd.scope(), Some(Scope::Pointer));
+    }
+
+    #[test]
+    fn test_parse_confine_cursor() {
+        let json = r#"{"type":"ConfineCursor","enabled":true}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        assert!(matches!(cmd, Command::ConfineCursor { enabled: true }));
+        assert_eq!(cmd.scope(), Some(Scope::Pointer));
+    }
+
+    #[test]
+    fn test_parse_clipboard_set_without_origin() {
+        let json = r#"{"type":"ClipboardSet","text":"hello"}"#;
+        let cmd: Command = serde_json::from_str(json).unwrap();
+        match cmd {
+            Command::ClipboardSet { text, origin } => {
+                assert_eq!(text, "hello");
+                assert!(origin.is_none());
+            }
+            _ => panic!("Expected ClipboardSet"),
+        }
+    }
+
     #[test]
     fn test_parse_invalid_json() {
         let json = r#"{"type":"InvalidCommand"}"#;
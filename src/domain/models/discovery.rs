@@ -1,6 +1,33 @@
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DiscoveryResponse {
     pub hostname: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternate_endpoints: Vec<AlternateEndpoint>,
+    /// Set when the server also takes commands over TCP on this port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_port: Option<u16>,
+    /// Set when the server streams a preview of its screen over HTTP on this port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_port: Option<u16>,
+}
+
+/// Command endpoint reachable over a VPN/overlay network rather than the LAN
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct AlternateEndpoint {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub network: OverlayNetwork,
+    pub interface: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayNetwork {
+    Tailscale,
+    WireGuard,
+    ZeroTier,
 }
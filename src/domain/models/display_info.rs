@@ -0,0 +1,18 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One monitor, in the desktop coordinates pointer moves are tracked in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct DisplayInfo {
+    /// Position left to right, then top to bottom; what `MoveToDisplay` addresses
+    pub index: usize,
+    pub left: f64,
+    pub top: f64,
+    /// Exclusive, like a width added to `left`
+    pub right: f64,
+    pub bottom: f64,
+    /// Physical pixels per desktop unit, e.g. 2 on a Retina panel
+    pub scale_factor: f64,
+    /// The display at the desktop's origin, which holds the menu bar or taskbar
+    pub primary: bool,
+}
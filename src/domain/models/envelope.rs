@@ -0,0 +1,207 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::Command;
+
+/// Wire wrapper around a command carrying per-packet metadata
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CommandEnvelope {
+    /// Bearer token issued at pairing or minted for a guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+    /// Client-assigned, increasing per session; carried across address changes and used to
+    /// drop duplicated datagrams
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Send time in server-clock unix milliseconds, using the offset learned from `Ping`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent_at_ms: Option<u64>,
+    /// Send time on the client's own clock, converted with the session's clock estimate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_sent_at_ms: Option<u64>,
+    /// Asks for an `Ack` carrying `seq`; the client retransmits until one arrives
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reliable: bool,
+    /// Nonce from a `ConfirmRequired` reply, resent once the user agreed to a destructive command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<u64>,
+    /// Set by the server once the datagram's MAC or AEAD tag checked out; never on the wire
+    #[serde(skip)]
+    pub signed: bool,
+    /// Set by the server for commands that arrived over a Noise channel; never on the wire
+    #[serde(skip)]
+    pub encrypted: bool,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+/// Payload of one command datagram: a single envelope, or several applied in order
+///
+/// Batching lets a high-rate client coalesce moves and the click that ends them into one
+/// packet. Batch entries without `auth` use the last token given earlier in the batch.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum CommandDatagram {
+    Batch(Vec<CommandEnvelope>),
+    Signed(SignedDatagram),
+    Handshake(HandshakeDatagram),
+    Encrypted(EncryptedDatagram),
+    Single(CommandEnvelope),
+}
+
+/// A datagram authenticated by an HMAC keyed with the token, which itself stays off the wire
+///
+/// Signed envelopes must each carry a `seq`, checked per server-issued session rather than
+/// per source address, so a spoofed sender can neither forge commands nor replay captured ones.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SignedDatagram {
+    /// First 16 hex digits of the token's SHA-256
+    pub signer: String,
+    /// HMAC-SHA256 of `"<session>:<payload>"`, or of the empty `payload` asking for a session,
+    /// keyed with the token, in hex
+    pub mac: String,
+    /// A single envelope or batch as JSON, without `auth`; empty to ask for a session
+    pub payload: String,
+    /// Chosen by the server in `SigningSession`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+/// First message of a Noise `NNpsk0` handshake, keyed with a PSK derived from the token
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct HandshakeDatagram {
+    /// First 16 hex digits of the token's SHA-256, as for signed datagrams
+    pub signer: String,
+    /// The initiator's handshake message, in hex
+    pub handshake: String,
+}
+
+/// A command or batch sealed with the keys of a completed Noise handshake
+///
+/// Envelopes inside need a `seq`, checked per session as for signed datagrams.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct EncryptedDatagram {
+    /// Chosen by the server in `HandshakeAccepted`
+    pub session: u64,
+    /// Never reused by the client within a session
+    pub nonce: u64,
+    /// A single envelope or batch as JSON, without `auth`, sealed and in hex
+    pub ciphertext: String,
+}
+
+impl CommandDatagram {
+    /// Envelopes carried; sealed datagrams count once until they're opened
+    pub fn len(&self) -> usize {
+        match self {
+            CommandDatagram::Batch(envelopes) => envelopes.len(),
+            CommandDatagram::Signed(_)
+            | CommandDatagram::Handshake(_)
+            | CommandDatagram::Encrypted(_)
+            | CommandDatagram::Single(_) => 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The envelopes in order, with batch tokens carried forward
+    ///
+    /// Signed and encrypted datagrams have to be opened first; unopened, they and
+    /// handshakes yield nothing.
+    pub fn into_envelopes(self) -> Vec<CommandEnvelope> {
+        match self {
+            CommandDatagram::Single(envelope) => vec![envelope],
+            CommandDatagram::Signed(_)
+            | CommandDatagram::Handshake(_)
+            | CommandDatagram::Encrypted(_) => Vec::new(),
+            CommandDatagram::Batch(mut envelopes) => {
+                let mut auth = None;
+                for envelope in &mut envelopes {
+                    match &envelope.auth {
+                        Some(token) => auth = Some(token.clone()),
+                        None => envelope.auth = auth.clone(),
+                    }
+                }
+                envelopes
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_envelope_with_auth() {
+        let json = r#"{"type":"MouseMove","x":10,"y":-4.5,"auth":"abc"}"#;
+        let envelope: CommandEnvelope = serde_json::from_str(json).unwrap();
+        assert_eq!(envelope.auth.as_deref(), Some("abc"));
+        match envelope.command {
+            Command::MouseMove { x, y } => {
+                assert_eq!(x, 10.0);
+                assert_eq!(y, -4.5);
+            }
+            _ => panic!("Expected MouseMove"),
+        }
+    }
+
+    #[test]
+    fn test_parse_envelope_without_auth() {
+        let json = r#"{"type":"MouseClick","button":1}"#;
+        let envelope: CommandEnvelope = serde_json::from_str(json).unwrap();
+        assert!(envelope.auth.is_none());
+        assert!(envelope.seq.is_none());
+        assert!(envelope.sent_at_ms.is_none());
+    }
+
+    #[test]
+    fn test_batch_carries_auth_forward() {
+        let json = r#"[
+            {"type":"MouseMove","x":1,"y":0,"auth":"abc"},
+            {"type":"MouseMove","x":2,"y":0},
+            {"type":"MouseClick","button":1,"auth":"def"},
+            {"type":"MouseClick","button":2}
+        ]"#;
+        let datagram: CommandDatagram = serde_json::from_str(json).unwrap();
+        assert_eq!(datagram.len(), 4);
+        let tokens: Vec<_> = datagram
+            .into_envelopes()
+            .into_iter()
+            .map(|envelope| envelope.auth)
+            .collect();
+        assert_eq!(
+            tokens,
+            ["abc", "abc", "def", "def"].map(|token| Some(token.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_signed_datagram_is_recognized() {
+        let json = r#"{"signer":"0123456789abcdef","mac":"00","payload":"{}"}"#;
+        let datagram: CommandDatagram = serde_json::from_str(json).unwrap();
+        assert!(matches!(datagram, CommandDatagram::Signed(_)));
+    }
+
+    #[test]
+    fn test_noise_datagrams_are_recognized() {
+        let hello = r#"{"signer":"0123456789abcdef","handshake":"00"}"#;
+        let sealed = r#"{"session":7,"nonce":0,"ciphertext":"00"}"#;
+        assert!(matches!(
+            serde_json::from_str(hello).unwrap(),
+            CommandDatagram::Handshake(_)
+        ));
+        assert!(matches!(
+            serde_json::from_str(sealed).unwrap(),
+            CommandDatagram::Encrypted(_)
+        ));
+    }
+
+    #[test]
+    fn test_single_envelope_is_a_datagram() {
+        let json = r#"{"type":"MouseClick","button":1}"#;
+        let datagram: CommandDatagram = serde_json::from_str(json).unwrap();
+        assert!(matches!(datagram, CommandDatagram::Single(_)));
+    }
+}
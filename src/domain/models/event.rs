@@ -0,0 +1,108 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::domain::models::{PowerAction, SensitivityCurve};
+use crate::input::desktop_session::SessionState;
+
+/// Server-side event published to `/events` subscribers
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ServerEvent {
+    DevicePaired {
+        device_name: String,
+    },
+    /// A token was revoked through the status API; commands carrying it are now refused
+    DeviceRevoked {
+        device_name: String,
+    },
+    /// A device asked to pair without a PIN; approve or deny it on `/pairing/requests`
+    PairingRequested {
+        id: u64,
+        device_name: String,
+        addr: String,
+        expires_in_secs: u64,
+    },
+    PairingFailed {
+        addr: String,
+    },
+    PairingLockedOut {
+        addr: String,
+        duration_secs: u64,
+    },
+    GeofenceSuspended,
+    GeofenceResumed,
+    LocalInputDetected {
+        grace_ms: u64,
+    },
+    /// The person at the desk pressed the kill switch; held input was released
+    RemoteInputSuspended,
+    RemoteInputResumed,
+    CommandDenied {
+        addr: String,
+        reason: String,
+    },
+    /// A client asked to lock, sleep, shut down or restart the machine
+    PowerRequested {
+        addr: String,
+        action: PowerAction,
+    },
+    HoverCaption {
+        text: String,
+    },
+    DesktopNotification {
+        app: String,
+        title: String,
+    },
+    /// Control passed to `addr`, or was freed when `None`
+    ControllerChanged {
+        addr: Option<String>,
+    },
+    /// A client went silent past the heartbeat timeout; `released` counts the inputs let go
+    ClientTimedOut {
+        addr: String,
+        released: usize,
+    },
+    SessionResumed {
+        device_name: String,
+        addr: String,
+        previous_addr: String,
+    },
+    DesktopSessionChanged {
+        state: SessionState,
+    },
+    ProfileChanged {
+        name: String,
+    },
+    /// Pointer tuning learned a curve for a client, and `applied` says whether it's in use
+    PointerTuningSuggested {
+        client_id: String,
+        device_name: String,
+        curve: SensitivityCurve,
+        applied: bool,
+    },
+    /// A macro recording waits up to `consent_secs` for the person at the desk to allow it
+    MacroRecordingRequested {
+        name: String,
+        secs: u64,
+        consent_secs: u64,
+    },
+    /// A macro recording at the desk begins capturing in `seconds_left` seconds
+    MacroRecordingCountdown {
+        name: String,
+        seconds_left: u64,
+    },
+    /// A macro recording is capturing local input for up to `secs` seconds
+    MacroRecordingStarted {
+        name: String,
+        secs: u64,
+    },
+    /// A macro recording ended and the desk saved it with this many steps
+    MacroRecorded {
+        name: String,
+        steps: usize,
+    },
+    /// A macro recording was refused, stopped or not saved at the desk, and nothing was kept
+    MacroRecordingCancelled {
+        name: String,
+    },
+}
@@ -0,0 +1,10 @@
+use crate::domain::models::Command;
+use serde::{Deserialize, Serialize};
+
+/// One command of a recorded macro
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MacroStep {
+    /// Pause before this step, counted from the one before it
+    pub after_ms: u64,
+    pub command: Command,
+}
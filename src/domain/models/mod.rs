@@ -1,5 +1,36 @@
 pub mod command;
 pub mod discovery;
+pub mod display_info;
+pub mod envelope;
+pub mod event;
+pub mod macro_step;
+pub mod named_key;
+pub mod pointer_acceleration;
+pub mod reply;
+pub mod schema;
+pub mod scope;
+pub mod sensitivity_curve;
+pub mod smoothing;
+pub mod touch_gesture;
+pub mod wire_format;
 
-pub use command::{Command, ModifierKeys};
-pub use discovery::DiscoveryResponse;
+pub use command::{
+    CaretDirection, CaretUnit, Command, MediaKey, ModifierKeys, PingExchange, PowerAction,
+    PowerState, ScrollAxis,
+};
+pub use discovery::{AlternateEndpoint, DiscoveryResponse, OverlayNetwork};
+pub use display_info::DisplayInfo;
+pub use envelope::{
+    CommandDatagram, CommandEnvelope, EncryptedDatagram, HandshakeDatagram, SignedDatagram,
+};
+pub use event::ServerEvent;
+pub use macro_step::MacroStep;
+pub use named_key::NamedKey;
+pub use pointer_acceleration::PointerAcceleration;
+pub use reply::Reply;
+pub use schema::ProtocolSchema;
+pub use scope::Scope;
+pub use sensitivity_curve::SensitivityCurve;
+pub use smoothing::Smoothing;
+pub use touch_gesture::TouchGesture;
+pub use wire_format::WireFormat;
@@ -0,0 +1,90 @@
+use schemars::{json_schema, Schema, SchemaGenerator};
+
+/// A key with no character of its own, named as in the DOM's `KeyboardEvent.key`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedKey {
+    ArrowLeft,
+    ArrowRight,
+    ArrowUp,
+    ArrowDown,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Escape,
+    Insert,
+    Delete,
+    /// F1 through F24
+    Function(u8),
+}
+
+impl NamedKey {
+    pub const MAX_FUNCTION_KEY: u8 = 24;
+
+    const NAMES: [(&'static str, NamedKey); 11] = [
+        ("ArrowLeft", NamedKey::ArrowLeft),
+        ("ArrowRight", NamedKey::ArrowRight),
+        ("ArrowUp", NamedKey::ArrowUp),
+        ("ArrowDown", NamedKey::ArrowDown),
+        ("Home", NamedKey::Home),
+        ("End", NamedKey::End),
+        ("PageUp", NamedKey::PageUp),
+        ("PageDown", NamedKey::PageDown),
+        ("Escape", NamedKey::Escape),
+        ("Insert", NamedKey::Insert),
+        ("Delete", NamedKey::Delete),
+    ];
+
+    /// `None` for anything else, including single characters such as `F` alone
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "Esc" => return Some(NamedKey::Escape),
+            "Del" => return Some(NamedKey::Delete),
+            _ => {}
+        }
+        if let Some((_, key)) = Self::NAMES.iter().find(|(known, _)| *known == name) {
+            return Some(*key);
+        }
+        let number: u8 = name.strip_prefix('F')?.parse().ok()?;
+        let canonical = format!("F{}", number) == name;
+        (canonical && (1..=Self::MAX_FUNCTION_KEY).contains(&number))
+            .then_some(NamedKey::Function(number))
+    }
+
+    /// Every name `parse` accepts, aliases aside
+    pub fn names() -> Vec<String> {
+        Self::NAMES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain((1..=Self::MAX_FUNCTION_KEY).map(|n| format!("F{}", n)))
+            .collect()
+    }
+}
+
+/// Schema of a `KeyPress` key: one character or one of the named keys
+pub fn key_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            { "type": "string", "minLength": 1, "maxLength": 1 },
+            { "enum": NamedKey::names() },
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_names_and_function_keys() {
+        assert_eq!(NamedKey::parse("PageDown"), Some(NamedKey::PageDown));
+        assert_eq!(NamedKey::parse("Esc"), Some(NamedKey::Escape));
+        assert_eq!(NamedKey::parse("F24"), Some(NamedKey::Function(24)));
+        for not_named in ["F", "F0", "F25", "F01", "f4", "a", "Enter"] {
+            assert_eq!(NamedKey::parse(not_named), None, "{}", not_named);
+        }
+        assert!(NamedKey::names()
+            .iter()
+            .all(|name| NamedKey::parse(name).is_some()));
+    }
+}
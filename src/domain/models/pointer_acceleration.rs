@@ -0,0 +1,92 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Transfer function from the length of a client's move to the gain it is injected with
+///
+/// Gains depend on each move's length in client units, so short precise moves stay short
+/// while a quick swipe can cross a large desktop.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PointerAcceleration {
+    /// The same gain for every move
+    Linear { multiplier: f64 },
+    /// X11-style: the part of a move past `threshold` is multiplied by `acceleration`, then
+    /// the whole move by `multiplier`
+    Classic {
+        multiplier: f64,
+        acceleration: f64,
+        threshold: f64,
+    },
+    /// `[length, gain]` points sorted by length, joined by straight lines and held flat
+    /// beyond both ends
+    Curve { points: Vec<[f64; 2]> },
+}
+
+impl Default for PointerAcceleration {
+    fn default() -> Self {
+        Self::Linear { multiplier: 1.0 }
+    }
+}
+
+impl PointerAcceleration {
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let length = x.hypot(y);
+        if length == 0.0 {
+            return (x, y);
+        }
+        let gain = match self {
+            Self::Linear { multiplier } => *multiplier,
+            Self::Classic {
+                multiplier,
+                acceleration,
+                threshold,
+            } => match length > *threshold {
+                true => multiplier * (threshold + (length - threshold) * acceleration) / length,
+                false => *multiplier,
+            },
+            Self::Curve { points } => interpolate(points, length),
+        };
+        (x * gain, y * gain)
+    }
+}
+
+fn interpolate(points: &[[f64; 2]], length: f64) -> f64 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return 1.0;
+    };
+    if length <= first[0] {
+        return first[1];
+    }
+    for pair in points.windows(2) {
+        let ([from, from_gain], [to, to_gain]) = (pair[0], pair[1]);
+        if length <= to {
+            return from_gain + (to_gain - from_gain) * (length - from) / (to - from);
+        }
+    }
+    last[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_accelerates_past_threshold_and_curve_interpolates() {
+        let classic = PointerAcceleration::Classic {
+            multiplier: 2.0,
+            acceleration: 3.0,
+            threshold: 4.0,
+        };
+        assert_eq!(classic.apply(3.0, 0.0), (6.0, 0.0));
+        assert_eq!(classic.apply(0.0, -10.0), (0.0, -44.0));
+
+        let curve = PointerAcceleration::Curve {
+            points: vec![[2.0, 1.0], [10.0, 3.0], [20.0, 5.0]],
+        };
+        assert_eq!(curve.apply(1.0, 0.0), (1.0, 0.0));
+        assert_eq!(curve.apply(6.0, 0.0), (12.0, 0.0));
+        assert_eq!(curve.apply(15.0, 0.0), (60.0, 0.0));
+        assert_eq!(curve.apply(100.0, 0.0), (500.0, 0.0));
+        assert_eq!(PointerAcceleration::default().apply(3.0, 4.0), (3.0, 4.0));
+    }
+}
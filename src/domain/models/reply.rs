@@ -0,0 +1,103 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::models::{DisplayInfo, ScrollAxis, WireFormat};
+
+/// Reply sent from server back to the client that issued a command
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(tag = "type")]
+pub enum Reply {
+    Paired {
+        token: String,
+    },
+    /// `RefreshSession` unlocked the session; it locks again after `idle_lock_secs` without input
+    SessionRefreshed {
+        idle_lock_secs: u64,
+    },
+    /// `RequestPairing` is waiting on the host; `Paired` or `PairRejected` follows once decided
+    PairPending {
+        request_id: u64,
+        expires_in_secs: u64,
+    },
+    PairRejected {
+        reason: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+    Denied {
+        reason: String,
+    },
+    /// The command parsed but carried a value that can't be injected, such as button 7
+    Invalid {
+        field: String,
+        reason: String,
+    },
+    /// A `reliable` command was received; a retransmit of it is acknowledged again, not re-run
+    Ack {
+        seq: u64,
+    },
+    /// Answer to `Ping`; offset ≈ `server_time_ms` - (`client_time_ms` + RTT / 2)
+    Pong {
+        client_time_ms: u64,
+        server_time_ms: u64,
+    },
+    /// Answer to `SelectFormat`, already encoded in `format`
+    FormatSelected {
+        format: WireFormat,
+    },
+    /// Sent unprompted to a paired client that has gone quiet; answer with `Heartbeat`
+    Probe,
+    /// Answer to `GetDisplays`, numbered as `MoveToDisplay` addresses them
+    Displays {
+        displays: Vec<DisplayInfo>,
+    },
+    /// `CalibrateScroll` scrolled; ask the user whether the page went `expected`
+    ScrollProbed {
+        axis: ScrollAxis,
+        expected: String,
+    },
+    ScrollCalibrated {
+        axis: ScrollAxis,
+        inverted: bool,
+    },
+    ProfileChanged {
+        name: String,
+    },
+    ProfileRejected {
+        reason: String,
+        available: Vec<String>,
+    },
+    GroupCompleted {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        steps: usize,
+    },
+    /// A grouped step failed; held input was released and the rest of the group is skipped
+    GroupFailed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        executed: Vec<String>,
+        failed: String,
+        error: String,
+    },
+    /// The responder's half of a Noise handshake; send `EncryptedDatagram`s to `session` next
+    HandshakeAccepted {
+        session: u64,
+        handshake: String,
+    },
+    /// A signing session opened for the token; sign later datagrams for `session`
+    SigningSession {
+        session: u64,
+    },
+    /// Any other reply, sealed for a client that talks over a Noise channel
+    Encrypted {
+        nonce: u64,
+        ciphertext: String,
+    },
+    /// The command is destructive; resend it with `confirm` set to `nonce` once the user agrees
+    ConfirmRequired {
+        command: String,
+        nonce: u64,
+        expires_in_secs: u64,
+    },
+}
@@ -0,0 +1,45 @@
+use schemars::{schema_for, Schema};
+use serde::Serialize;
+
+use crate::domain::models::{CommandDatagram, DiscoveryResponse, Reply, ServerEvent};
+
+/// JSON Schemas for everything on the wire, served at `/schema` for client code generation
+#[derive(Serialize, Debug)]
+pub struct ProtocolSchema {
+    pub version: &'static str,
+    /// Datagram sent to the command port: one envelope or an array of them
+    pub command: Schema,
+    /// Datagram sent back on the command port
+    pub reply: Schema,
+    /// Entry on the `/events` stream
+    pub event: Schema,
+    /// Answer to a `DISCOVER` broadcast
+    pub discovery: Schema,
+}
+
+impl ProtocolSchema {
+    pub fn generate() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            command: schema_for!(CommandDatagram),
+            reply: schema_for!(Reply),
+            event: schema_for!(ServerEvent),
+            discovery: schema_for!(DiscoveryResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_schema_lists_wire_tags() {
+        let command = serde_json::to_string(&ProtocolSchema::generate().command).unwrap();
+        for tag in ["MouseMove", "SetProfile", "Ping"] {
+            assert!(command.contains(tag), "missing {}", tag);
+        }
+        assert!(command.contains("\"auth\""));
+        assert!(command.contains("\"PageDown\""));
+    }
+}
@@ -0,0 +1,17 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Class of commands a token may issue
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Pointer,
+    Keyboard,
+    Clipboard,
+    /// Host-level settings such as night light and the active profile
+    System,
+    /// Turning the displays off and on, and locking, sleeping or shutting down the host
+    Power,
+    /// File transfers; no command needs it yet, so it is reserved for when they arrive
+    Files,
+}
@@ -0,0 +1,49 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Pointer gain that grows with the length of each move, as learned by pointer tuning
+///
+/// A move of `pivot` client units is multiplied by `gain`. Longer moves get more and shorter
+/// ones less, by `(length / pivot) ^ exponent`, held within a quarter and four times `gain`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+pub struct SensitivityCurve {
+    pub gain: f64,
+    pub pivot: f64,
+    /// 0 for a flat multiplier
+    pub exponent: f64,
+}
+
+impl SensitivityCurve {
+    const MAX_SPREAD: f64 = 4.0;
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let length = x.hypot(y);
+        let spread = match length > 0.0 && self.pivot > 0.0 {
+            true => (length / self.pivot)
+                .powf(self.exponent)
+                .clamp(1.0 / Self::MAX_SPREAD, Self::MAX_SPREAD),
+            false => 1.0,
+        };
+        let factor = self.gain * spread;
+        (x * factor, y * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain_grows_with_move_length_within_bounds() {
+        let curve = SensitivityCurve {
+            gain: 2.0,
+            pivot: 4.0,
+            exponent: 0.5,
+        };
+        assert_eq!(curve.apply(4.0, 0.0), (8.0, 0.0));
+        assert_eq!(curve.apply(0.0, -16.0), (0.0, -64.0));
+        assert_eq!(curve.apply(1.0, 0.0), (1.0, 0.0));
+        assert_eq!(curve.apply(1e6, 0.0), (8e6, 0.0));
+        assert_eq!(curve.apply(0.0, 0.0), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,15 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Jitter filter applied to a client's pointer motion before injection
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Smoothing {
+    #[default]
+    Off,
+    /// Exponential moving average per move; lower `alpha` (0–1] smooths more and lags more
+    Ema { alpha: f64 },
+    /// 1€ filter: heavy smoothing while the pointer creeps, almost none while it flies.
+    /// `min_cutoff` (Hz) sets the smoothing at rest, `beta` how fast it lets go with speed.
+    OneEuro { min_cutoff: f64, beta: f64 },
+}
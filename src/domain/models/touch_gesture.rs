@@ -0,0 +1,33 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A multi-finger touch gesture, made around the pointer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TouchGesture {
+    /// Two fingers spreading to `scale` times their distance apart, zooming in above 1 and
+    /// out below it
+    Pinch { scale: f64 },
+    /// Two fingers turning by `degrees`, clockwise
+    Rotate { degrees: f64 },
+    /// `fingers` moving together by `dx`, `dy`
+    Swipe {
+        dx: f64,
+        dy: f64,
+        #[serde(default = "two")]
+        fingers: u8,
+    },
+}
+
+fn two() -> u8 {
+    2
+}
+
+impl TouchGesture {
+    pub fn fingers(&self) -> u8 {
+        match self {
+            Self::Pinch { .. } | Self::Rotate { .. } => 2,
+            Self::Swipe { fingers, .. } => *fingers,
+        }
+    }
+}
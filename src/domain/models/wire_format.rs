@@ -0,0 +1,125 @@
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Encoding of a command datagram, detected per packet or agreed with `SelectFormat`
+///
+/// MessagePack and CBOR carry the same maps as JSON, keyed by the same field names, and save
+/// the text parse on every mouse move at high report rates.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    Cbor,
+}
+
+impl WireFormat {
+    /// Every format the server speaks, in its order of preference
+    pub const ALL: [WireFormat; 3] = [WireFormat::Json, WireFormat::MessagePack, WireFormat::Cbor];
+
+    /// MessagePack maps and batch arrays open with a fixmap (0x80–0x8f), fixarray (0x90–0x9f),
+    /// array16/32 (0xdc, 0xdd) or map16/32 (0xde, 0xdf) byte, none of which can start a JSON
+    /// document. CBOR maps open with 0xa0–0xbf, a MessagePack fixstr that never starts a
+    /// datagram; CBOR arrays share MessagePack's bytes, so a CBOR batch needs `SelectFormat`.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.first() {
+            Some(0x80..=0x9f | 0xdc..=0xdf) => WireFormat::MessagePack,
+            Some(0xa0..=0xbf) => WireFormat::Cbor,
+            _ => WireFormat::Json,
+        }
+    }
+
+    /// Whether messages can be framed one per line, as the TCP transport does
+    pub fn is_text(self) -> bool {
+        self == WireFormat::Json
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T> {
+        Ok(match self {
+            WireFormat::Json => serde_json::from_slice(bytes)?,
+            WireFormat::MessagePack => rmp_serde::from_slice(bytes)?,
+            WireFormat::Cbor => ciborium::from_reader(bytes)?,
+        })
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        Ok(match self {
+            WireFormat::Json => serde_json::to_vec(value)?,
+            // Named fields keep structs as maps, which flattened and tagged types need
+            WireFormat::MessagePack => rmp_serde::to_vec_named(value)?,
+            WireFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes)?;
+                bytes
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{Command, CommandEnvelope, Reply};
+
+    #[test]
+    fn test_message_pack_round_trips_an_envelope() {
+        let envelope = CommandEnvelope {
+            auth: Some("token".to_string()),
+            seq: Some(7),
+            sent_at_ms: None,
+            client_sent_at_ms: None,
+            reliable: false,
+            confirm: None,
+            signed: false,
+            encrypted: false,
+            command: Command::MouseMove { x: 3.5, y: -2.0 },
+        };
+        let bytes = WireFormat::MessagePack.encode(&envelope).unwrap();
+        assert_eq!(WireFormat::detect(&bytes), WireFormat::MessagePack);
+        assert!(bytes.len() < serde_json::to_vec(&envelope).unwrap().len());
+
+        let decoded: CommandEnvelope = WireFormat::MessagePack.decode(&bytes).unwrap();
+        assert_eq!(decoded.auth.as_deref(), Some("token"));
+        assert!(matches!(decoded.command, Command::MouseMove { x, y } if x == 3.5 && y == -2.0));
+    }
+
+    #[test]
+    fn test_json_is_detected_and_replies_encode_both_ways() {
+        assert_eq!(
+            WireFormat::detect(br#"{"type":"SelectAll"}"#),
+            WireFormat::Json
+        );
+        assert_eq!(WireFormat::detect(b" {}"), WireFormat::Json);
+
+        let reply = Reply::Denied {
+            reason: "nope".to_string(),
+        };
+        for format in WireFormat::ALL {
+            let bytes = format.encode(&reply).unwrap();
+            let decoded: Reply = format.decode(&bytes).unwrap();
+            assert!(matches!(decoded, Reply::Denied { reason } if reason == "nope"));
+            assert_eq!(WireFormat::detect(&bytes), format);
+        }
+    }
+
+    #[test]
+    fn test_cbor_round_trips_a_tagged_command() {
+        let bytes = WireFormat::Cbor
+            .encode(&Command::KeyPress {
+                key: "a".to_string(),
+                modifiers: Default::default(),
+            })
+            .unwrap();
+        assert_eq!(WireFormat::detect(&bytes), WireFormat::Cbor);
+        let decoded: CommandEnvelope = WireFormat::Cbor.decode(&bytes).unwrap();
+        assert!(matches!(decoded.command, Command::KeyPress { key, .. } if key == "a"));
+        assert_eq!(
+            serde_json::to_string(&WireFormat::MessagePack).unwrap(),
+            r#""msgpack""#
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::domain::config::settings::AuditSettings;
+use crate::domain::models::Command;
+use crate::features::auth::token_store::DeviceInfo;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// One command a remote client had injected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub at_ms: u64,
+    /// Token id, as listed by `/clients`; `None` when the command carried no token
+    pub client_id: Option<String>,
+    pub device_name: Option<String>,
+    pub addr: SocketAddr,
+    /// Command type only; keys typed and clipboard text stay out of the log
+    pub command: String,
+}
+
+/// Append-only record of who injected what, one JSON line per command
+///
+/// Pointer motion and scrolling aren't recorded; at 120 Hz they'd bury everything else.
+/// When the file passes `max_bytes` it's renamed to `audit.log.1`, shifting older files up,
+/// and only `keep` of them are kept.
+pub struct AuditLog {
+    settings: AuditSettings,
+    path: PathBuf,
+    writer: Mutex<Option<Writer>>,
+}
+
+struct Writer {
+    file: File,
+    len: u64,
+}
+
+impl AuditLog {
+    pub fn new(settings: AuditSettings, path: PathBuf) -> Self {
+        Self {
+            settings,
+            path,
+            writer: Mutex::new(None),
+        }
+    }
+
+    /// Appends an entry for `command` when it's the kind of input the log keeps
+    pub fn record(&self, command: &Command, holder: Option<&DeviceInfo>, addr: SocketAddr) {
+        if !self.settings.enabled || !Self::audits(command) {
+            return;
+        }
+        let entry = AuditEntry {
+            at_ms: utils::unix_now_ms(),
+            client_id: holder.map(|holder| holder.id.clone()),
+            device_name: holder.map(|holder| holder.device_name.clone()),
+            addr,
+            command: command.kind().to_string(),
+        };
+        if let Err(e) = self.append(&entry) {
+            log::warn!("Failed to write {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Newest entries first, across the rotated files, optionally only from one client
+    pub fn recent(&self, limit: usize, client_id: Option<&str>) -> Vec<AuditEntry> {
+        let mut entries = Vec::new();
+        for path in self.files() {
+            let Ok(file) = File::open(&path) else {
+                break;
+            };
+            let mut lines: Vec<AuditEntry> = BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str(&line).ok())
+                .filter(|entry: &AuditEntry| {
+                    client_id.is_none_or(|id| entry.client_id.as_deref() == Some(id))
+                })
+                .collect();
+            lines.reverse();
+            entries.extend(lines.into_iter().take(limit - entries.len()));
+            if entries.len() >= limit {
+                break;
+            }
+        }
+        entries
+    }
+
+    /// Commands that end up as input on the desktop, minus pointer motion
+    fn audits(command: &Command) -> bool {
+        command.scope().is_some()
+            && !matches!(
+                command,
+                Command::MouseMove { .. } | Command::MouseScroll { .. }
+            )
+    }
+
+    fn append(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut writer = self.lock_writer();
+        let full = writer.as_ref().is_some_and(|writer| {
+            writer.len > 0 && writer.len + line.len() as u64 > self.settings.max_bytes
+        });
+        if full {
+            *writer = None;
+            self.rotate()?;
+        }
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => writer.insert(self.open()?),
+        };
+        writer.file.write_all(&line)?;
+        writer.len += line.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> std::io::Result<Writer> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let len = file.metadata()?.len();
+        Ok(Writer { file, len })
+    }
+
+    /// Shifts `audit.log.N` to `audit.log.N+1`, dropping the oldest, and starts a fresh file
+    fn rotate(&self) -> std::io::Result<()> {
+        let files = self.files();
+        let _ = std::fs::remove_file(&files[files.len() - 1]);
+        for pair in files.windows(2).rev() {
+            if pair[0].exists() {
+                std::fs::rename(&pair[0], &pair[1])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The live file followed by its rotations, newest first
+    fn files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.path.clone())
+            .chain((1..=self.settings.keep).map(|n| rotated(&self.path, n)))
+            .collect()
+    }
+
+    fn lock_writer(&self) -> MutexGuard<'_, Option<Writer>> {
+        self.writer.lock().expect("Audit log mutex poisoned")
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::auth::token_store::Role;
+    use tempfile::TempDir;
+
+    fn audit_log(max_bytes: u64) -> (TempDir, AuditLog) {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(
+            AuditSettings {
+                enabled: true,
+                max_bytes,
+                keep: 2,
+            },
+            dir.path().join("audit.log"),
+        );
+        (dir, log)
+    }
+
+    fn device(id: &str) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            device_name: format!("Phone {}", id),
+            paired_at: 0,
+            role: Role::Device,
+            expires_at: None,
+            scopes: None,
+        }
+    }
+
+    fn addr() -> SocketAddr {
+        "192.168.1.5:40000".parse().unwrap()
+    }
+
+    #[test]
+    fn test_records_input_but_not_motion_or_typed_text() {
+        let (_dir, log) = audit_log(1024 * 1024);
+        let key = Command::KeyPress {
+            key: "p".to_string(),
+            modifiers: Default::default(),
+        };
+        log.record(&key, Some(&device("a1")), addr());
+        log.record(&Command::MouseMove { x: 1.0, y: 1.0 }, None, addr());
+        log.record(&Command::Heartbeat, None, addr());
+        log.record(&Command::MouseClick { button: 1 }, None, addr());
+
+        let entries = log.recent(10, None);
+        let commands: Vec<&str> = entries.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, ["MouseClick", "KeyPress"]);
+        assert_eq!(entries[1].device_name.as_deref(), Some("Phone a1"));
+        let written = std::fs::read_to_string(&log.path).unwrap();
+        assert!(!written.contains(r#""p""#));
+
+        let only_a1 = log.recent(10, Some("a1"));
+        assert_eq!(only_a1.len(), 1);
+    }
+
+    #[test]
+    fn test_rotates_and_keeps_reading_older_files() {
+        let (_dir, log) = audit_log(400);
+        for _ in 0..12 {
+            log.record(&Command::SelectAll, Some(&device("b2")), addr());
+        }
+        assert!(rotated(&log.path, 1).exists());
+        assert!(rotated(&log.path, 2).exists());
+        assert!(!rotated(&log.path, 3).exists());
+        assert!(std::fs::metadata(&log.path).unwrap().len() <= 400);
+
+        let entries = log.recent(5, None);
+        assert_eq!(entries.len(), 5);
+        assert!(entries
+            .windows(2)
+            .all(|pair| pair[0].at_ms >= pair[1].at_ms));
+        assert!(log.recent(100, None).len() < 12);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::domain::config::secrets;
+use crate::domain::config::settings::WebhookSettings;
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{PowerAction, ServerEvent};
+use crate::features::events::event_bus::EventBus;
+use crate::utils::{self, unix_now};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Semaphore;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Body of a webhook POST
+#[derive(Serialize, Debug)]
+struct WebhookPayload<'a> {
+    /// `paired`, `denied`, `kill_switch` or `power`
+    kind: &'static str,
+    /// One readable line, which chat webhooks such as Discord's show as the message
+    content: String,
+    host: String,
+    at: u64,
+    /// Absent on a summary of denied commands that weren't sent one by one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a ServerEvent>,
+    /// Denied commands the summary stands for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suppressed: Option<u64>,
+}
+
+/// Posts pairings, denied commands, kill-switch presses and power requests to user URLs
+///
+/// Bodies are signed with HMAC-SHA256 over `<at>.<raw JSON>`, sent as
+/// `X-PointZerver-Signature: sha256=<hex>` next to `X-PointZerver-Timestamp: <at>`, so
+/// receivers can drop forged and replayed ones. Denied commands can be triggered by anyone
+/// who can send a datagram, so only a few per minute go out on their own.
+pub struct WebhookService {
+    settings: WebhookSettings,
+    events: EventBus,
+}
+
+/// The signing key and the posts still in flight
+struct Sender {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    host: String,
+    secret: String,
+    in_flight: Arc<Semaphore>,
+}
+
+impl WebhookService {
+    pub fn new(settings: WebhookSettings, events: EventBus) -> Self {
+        Self { settings, events }
+    }
+
+    /// Forwards notable events until the bus closes; returns at once unless enabled with URLs
+    pub async fn run(&self) {
+        if !self.settings.enabled || self.settings.urls.is_empty() {
+            return;
+        }
+        let secret = match secrets::open(&self.settings.secret) {
+            Ok(secret) => secret,
+            Err(e) => {
+                log::warn!("Webhooks disabled: {}", e);
+                return;
+            }
+        };
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(ServerConfig::WEBHOOK_TIMEOUT_SECS))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                log::warn!("Webhooks disabled: {}", e);
+                return;
+            }
+        };
+
+        log::info!("Sending webhooks to {} URL(s)", self.settings.urls.len());
+        let sender = Sender {
+            client,
+            urls: self.settings.urls.clone(),
+            host: utils::get_hostname(),
+            secret,
+            in_flight: Arc::new(Semaphore::new(ServerConfig::WEBHOOK_MAX_IN_FLIGHT)),
+        };
+        let window = Duration::from_secs(ServerConfig::WEBHOOK_DENIED_WINDOW_SECS);
+        let mut denials = DenialLimit::new(ServerConfig::WEBHOOK_DENIED_BURST, window);
+        let mut summaries = tokio::time::interval(window);
+        let mut events = self.events.subscribe();
+        loop {
+            let event = tokio::select! {
+                received = events.recv() => match received {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("Webhooks fell behind and skipped {} events", missed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = summaries.tick() => {
+                    if let Some(suppressed) = denials.take_suppressed() {
+                        let content = format!(
+                            "Refused {} more commands in the last {} seconds",
+                            suppressed,
+                            window.as_secs()
+                        );
+                        sender.send("denied", content, None, Some(suppressed));
+                    }
+                    continue;
+                }
+            };
+            let Some((kind, content)) = summarize(&event) else {
+                continue;
+            };
+            if matches!(event, ServerEvent::CommandDenied { .. }) && !denials.allow(Instant::now())
+            {
+                continue;
+            }
+            sender.send(kind, content, Some(&event), None);
+        }
+    }
+}
+
+impl Sender {
+    /// Posts to every URL, or drops the webhook when too many posts are still in flight
+    fn send(
+        &self,
+        kind: &'static str,
+        content: String,
+        event: Option<&ServerEvent>,
+        suppressed: Option<u64>,
+    ) {
+        let at = unix_now();
+        let payload = WebhookPayload {
+            kind,
+            content,
+            host: self.host.clone(),
+            at,
+            event,
+            suppressed,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                log::warn!("Failed to encode webhook: {}", e);
+                return;
+            }
+        };
+        let signature = (!self.secret.is_empty()).then(|| sign(&self.secret, at, &body));
+        for url in &self.urls {
+            let Ok(permit) = Arc::clone(&self.in_flight).try_acquire_owned() else {
+                log::warn!("Too many webhooks in flight; dropped a {} webhook", kind);
+                return;
+            };
+            let post = post(
+                self.client.clone(),
+                url.clone(),
+                body.clone(),
+                at,
+                signature.clone(),
+            );
+            tokio::spawn(async move {
+                post.await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Lets `burst` denied commands per window through as their own webhooks and counts the rest
+struct DenialLimit {
+    burst: u32,
+    window: Duration,
+    window_start: Option<Instant>,
+    sent: u32,
+    suppressed: u64,
+}
+
+impl DenialLimit {
+    fn new(burst: u32, window: Duration) -> Self {
+        Self {
+            burst,
+            window,
+            window_start: None,
+            sent: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Whether a denied command at `now` gets its own webhook
+    fn allow(&mut self, now: Instant) -> bool {
+        let expired = self
+            .window_start
+            .is_none_or(|start| now.saturating_duration_since(start) >= self.window);
+        if expired {
+            self.window_start = Some(now);
+            self.sent = 0;
+        }
+        if self.sent < self.burst {
+            self.sent += 1;
+            return true;
+        }
+        self.suppressed += 1;
+        false
+    }
+
+    /// Denied commands held back since the last summary
+    fn take_suppressed(&mut self) -> Option<u64> {
+        (self.suppressed > 0).then(|| std::mem::take(&mut self.suppressed))
+    }
+}
+
+async fn post(
+    client: reqwest::Client,
+    url: String,
+    body: Vec<u8>,
+    at: u64,
+    signature: Option<String>,
+) {
+    let mut request = client
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(ServerConfig::WEBHOOK_TIMESTAMP_HEADER, at.to_string())
+        .body(body);
+    if let Some(signature) = signature {
+        request = request.header(ServerConfig::WEBHOOK_SIGNATURE_HEADER, signature);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => log::warn!("Webhook {} returned {}", url, response.status()),
+        Err(e) => log::warn!("Webhook {} failed: {}", url, e),
+    }
+}
+
+/// The kind and readable line for events worth a webhook; `None` for the rest
+fn summarize(event: &ServerEvent) -> Option<(&'static str, String)> {
+    match event {
+        ServerEvent::DevicePaired { device_name } => Some((
+            "paired",
+            format!("{} paired with this computer", device_name),
+        )),
+        ServerEvent::CommandDenied { addr, reason } => Some((
+            "denied",
+            format!("Refused a command from {}: {}", addr, reason),
+        )),
+        ServerEvent::RemoteInputSuspended => Some((
+            "kill_switch",
+            "The kill switch was pressed; remote input is suspended".to_string(),
+        )),
+        ServerEvent::PowerRequested { addr, action } => {
+            let verb = match action {
+                PowerAction::Lock => "lock",
+                PowerAction::Sleep => "sleep",
+                PowerAction::Shutdown => "shut down",
+                PowerAction::Restart => "restart",
+            };
+            Some(("power", format!("{} asked to {} this computer", addr, verb)))
+        }
+        _ => None,
+    }
+}
+
+/// HMAC of `<at>.<body>`, so a captured request can't be replayed under a later timestamp
+fn sign(secret: &str, at: u64, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.", at).as_bytes());
+    mac.update(body);
+    format!("sha256={}", utils::to_hex(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_notable_events_are_sent_and_bodies_are_signed() {
+        let (kind, content) = summarize(&ServerEvent::PowerRequested {
+            addr: "192.168.1.5:50000".to_string(),
+            action: PowerAction::Shutdown,
+        })
+        .unwrap();
+        assert_eq!(kind, "power");
+        assert_eq!(
+            content,
+            "192.168.1.5:50000 asked to shut down this computer"
+        );
+        assert!(summarize(&ServerEvent::HoverCaption {
+            text: "OK".to_string()
+        })
+        .is_none());
+
+        // The timestamp is covered, so a body can't be replayed as a later one
+        assert_ne!(sign("Jefe", 1, b"body"), sign("Jefe", 2, b"body"));
+        let mut mac = HmacSha256::new_from_slice(b"Jefe").unwrap();
+        mac.update(b"1700000000.what do ya want for nothing?");
+        assert_eq!(
+            sign("Jefe", 1_700_000_000, b"what do ya want for nothing?"),
+            format!("sha256={}", utils::to_hex(&mac.finalize().into_bytes()))
+        );
+    }
+
+    #[test]
+    fn test_denials_past_the_burst_are_summarized_once_per_window() {
+        let window = Duration::from_secs(60);
+        let mut limit = DenialLimit::new(2, window);
+        let now = Instant::now();
+        assert!(limit.allow(now));
+        assert!(limit.allow(now));
+        assert!(!limit.allow(now));
+        assert!(!limit.allow(now + Duration::from_secs(1)));
+        assert_eq!(limit.take_suppressed(), Some(2));
+        assert_eq!(limit.take_suppressed(), None);
+
+        assert!(!limit.allow(now + Duration::from_secs(30)));
+        assert!(limit.allow(now + window));
+        assert_eq!(limit.take_suppressed(), Some(1));
+    }
+}
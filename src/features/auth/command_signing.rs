@@ -0,0 +1,347 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{CommandDatagram, CommandEnvelope, SignedDatagram};
+use crate::features::auth::token_store::TokenStore;
+use crate::features::command::replay_window::ReplayWindow;
+use crate::utils;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hex digits of the token's SHA-256 that name the signer
+const SIGNER_ID_LEN: usize = 16;
+
+/// Why a signed datagram was thrown away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SignatureError {
+    #[error("no live token matches the signer")]
+    UnknownSigner,
+    #[error("MAC does not match the payload")]
+    BadMac,
+    #[error("payload is not a command or batch")]
+    MalformedPayload,
+    #[error("signed commands must carry a seq")]
+    MissingSeq,
+    #[error("signed commands need a session; send an empty payload to open one")]
+    MissingSession,
+    #[error("unknown signing session; open a new one")]
+    UnknownSession,
+}
+
+/// What a signed datagram turned out to be
+#[derive(Debug)]
+pub enum Opened {
+    /// A request for a session, answered with `SigningSession`
+    Session(u64),
+    /// Commands signed for `session`, carrying the signer's token and marked signed
+    Envelopes {
+        session: u64,
+        envelopes: Vec<CommandEnvelope>,
+    },
+}
+
+/// Opens HMAC-signed datagrams and tracks which sequence numbers each session has used
+///
+/// The MAC proves the sender holds the token without the token crossing the network. Sessions
+/// are issued by the server and kept in memory, so a restart forgets them, and a datagram
+/// captured before it names a session the server no longer accepts. A client that starts
+/// counting again opens a new session.
+pub struct CommandSigning {
+    tokens: Arc<TokenStore>,
+    sessions: Mutex<HashMap<u64, Session>>,
+}
+
+struct Session {
+    token: String,
+    opened: Instant,
+    window: ReplayWindow,
+}
+
+impl CommandSigning {
+    pub fn new(tokens: Arc<TokenStore>) -> Self {
+        Self {
+            tokens,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks the MAC, then opens a session or returns the envelopes signed for one
+    pub fn open(&self, datagram: SignedDatagram) -> Result<Opened, SignatureError> {
+        let token = self
+            .tokens
+            .find_signer(&datagram.signer)
+            .ok_or(SignatureError::UnknownSigner)?;
+        let mac = utils::from_hex(&datagram.mac).ok_or(SignatureError::BadMac)?;
+        keyed(&token, datagram.session, &datagram.payload)
+            .verify_slice(&mac)
+            .map_err(|_| SignatureError::BadMac)?;
+
+        let Some(session) = datagram.session else {
+            return match datagram.payload.is_empty() {
+                true => Ok(Opened::Session(self.start(token))),
+                false => Err(SignatureError::MissingSession),
+            };
+        };
+        let known = self
+            .lock_sessions()
+            .get(&session)
+            .is_some_and(|open| open.token == token);
+        if !known {
+            return Err(SignatureError::UnknownSession);
+        }
+        let envelopes = match serde_json::from_str(&datagram.payload) {
+            Ok(CommandDatagram::Signed(_)) | Err(_) => {
+                return Err(SignatureError::MalformedPayload)
+            }
+            Ok(inner) => inner.into_envelopes(),
+        };
+        if envelopes.iter().any(|envelope| envelope.seq.is_none()) {
+            return Err(SignatureError::MissingSeq);
+        }
+        let envelopes = envelopes
+            .into_iter()
+            .map(|envelope| CommandEnvelope {
+                auth: Some(token.clone()),
+                signed: true,
+                ..envelope
+            })
+            .collect();
+        Ok(Opened::Envelopes { session, envelopes })
+    }
+
+    /// Records `seq` in `session`, returning false if it was already used there
+    ///
+    /// Never restarts a session's count, so a capture that has slid out of the window can't
+    /// be replayed later.
+    pub fn admit(&self, session: u64, seq: u64) -> bool {
+        self.lock_sessions()
+            .get_mut(&session)
+            .is_some_and(|open| open.window.admit_strict(seq))
+    }
+
+    /// Opens a session for `token`, closing its oldest once it has too many
+    fn start(&self, token: String) -> u64 {
+        let mut sessions = self.lock_sessions();
+        let mut opened: Vec<_> = sessions
+            .iter()
+            .filter(|(_, open)| open.token == token)
+            .map(|(id, open)| (open.opened, *id))
+            .collect();
+        opened.sort();
+        let excess = (opened.len() + 1).saturating_sub(ServerConfig::SIGNING_SESSIONS_PER_TOKEN);
+        for (_, id) in opened.into_iter().take(excess) {
+            sessions.remove(&id);
+        }
+        let session = rand::thread_rng().next_u64();
+        sessions.insert(
+            session,
+            Session {
+                token,
+                opened: Instant::now(),
+                window: ReplayWindow::default(),
+            },
+        );
+        session
+    }
+
+    fn lock_sessions(&self) -> MutexGuard<'_, HashMap<u64, Session>> {
+        self.sessions
+            .lock()
+            .expect("Signing sessions mutex poisoned")
+    }
+}
+
+/// Names a token on the wire without revealing it
+pub fn signer_id(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    let mut id = utils::to_hex(&digest);
+    id.truncate(SIGNER_ID_LEN);
+    id
+}
+
+/// Wraps a JSON-encoded envelope or batch in a datagram signed with `token` for `session`
+pub fn sign(token: &str, session: u64, payload: String) -> SignedDatagram {
+    SignedDatagram {
+        signer: signer_id(token),
+        mac: utils::to_hex(
+            &keyed(token, Some(session), &payload)
+                .finalize()
+                .into_bytes(),
+        ),
+        payload,
+        session: Some(session),
+    }
+}
+
+/// A datagram asking for a signing session, answered with `SigningSession`
+pub fn request_session(token: &str) -> SignedDatagram {
+    SignedDatagram {
+        signer: signer_id(token),
+        mac: utils::to_hex(&keyed(token, None, "").finalize().into_bytes()),
+        payload: String::new(),
+        session: None,
+    }
+}
+
+/// The MAC covers the session too, so a capture can't be moved into a fresh one
+fn keyed(token: &str, session: Option<u64>, payload: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(token.as_bytes()).expect("HMAC accepts keys of any length");
+    if let Some(session) = session {
+        mac.update(format!("{}:", session).as_bytes());
+    }
+    mac.update(payload.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::auth::token_store::temp_store;
+    use tempfile::TempDir;
+
+    fn signing() -> (TempDir, CommandSigning, String) {
+        let (dir, tokens) = temp_store();
+        let token = tokens.issue("Pixel").unwrap();
+        (dir, CommandSigning::new(Arc::new(tokens)), token)
+    }
+
+    fn session(signing: &CommandSigning, token: &str) -> u64 {
+        match signing.open(request_session(token)).unwrap() {
+            Opened::Session(session) => session,
+            opened => panic!("Expected a session, got {:?}", opened),
+        }
+    }
+
+    fn envelopes(opened: Opened) -> Vec<CommandEnvelope> {
+        match opened {
+            Opened::Envelopes { envelopes, .. } => envelopes,
+            opened => panic!("Expected envelopes, got {:?}", opened),
+        }
+    }
+
+    #[test]
+    fn test_signed_datagram_opens_with_the_token() {
+        let (_dir, signing, token) = signing();
+        let session = session(&signing, &token);
+        let payload = r#"[{"type":"MouseMove","x":1,"y":0,"seq":1},{"type":"MouseClick","button":1,"seq":2}]"#;
+        let envelopes = envelopes(
+            signing
+                .open(sign(&token, session, payload.to_string()))
+                .unwrap(),
+        );
+        assert_eq!(envelopes.len(), 2);
+        assert!(envelopes
+            .iter()
+            .all(|envelope| envelope.signed && envelope.auth.as_deref() == Some(token.as_str())));
+    }
+
+    #[test]
+    fn test_tampered_or_foreign_datagrams_are_rejected() {
+        let (_dir, signing, token) = signing();
+        let session = session(&signing, &token);
+        let mut datagram = sign(
+            &token,
+            session,
+            r#"{"type":"MouseClick","button":1,"seq":1}"#.to_string(),
+        );
+        datagram.payload = r#"{"type":"MouseClick","button":2,"seq":1}"#.to_string();
+        assert_eq!(signing.open(datagram).unwrap_err(), SignatureError::BadMac);
+
+        // Moving a capture into a session the attacker had opened breaks the MAC too
+        let mut moved = sign(
+            &token,
+            session,
+            r#"{"type":"MouseClick","button":1,"seq":1}"#.to_string(),
+        );
+        moved.session = Some(self::session(&signing, &token));
+        assert_eq!(signing.open(moved).unwrap_err(), SignatureError::BadMac);
+
+        let stranger = sign(
+            "not-a-token",
+            session,
+            r#"{"type":"SelectAll","seq":1}"#.to_string(),
+        );
+        assert_eq!(
+            signing.open(stranger).unwrap_err(),
+            SignatureError::UnknownSigner
+        );
+    }
+
+    #[test]
+    fn test_signed_commands_need_a_seq() {
+        let (_dir, signing, token) = signing();
+        let session = session(&signing, &token);
+        let datagram = sign(
+            &token,
+            session,
+            r#"{"type":"MouseClick","button":1}"#.to_string(),
+        );
+        assert_eq!(
+            signing.open(datagram).unwrap_err(),
+            SignatureError::MissingSeq
+        );
+    }
+
+    #[test]
+    fn test_replayed_seq_is_refused_per_session() {
+        let (_dir, signing, token) = signing();
+        let session = session(&signing, &token);
+        let payload = r#"{"type":"MouseClick","button":1,"seq":5}"#;
+        let first = envelopes(
+            signing
+                .open(sign(&token, session, payload.to_string()))
+                .unwrap(),
+        );
+        let again = envelopes(
+            signing
+                .open(sign(&token, session, payload.to_string()))
+                .unwrap(),
+        );
+        assert!(signing.admit(session, first[0].seq.unwrap()));
+        assert!(!signing.admit(session, again[0].seq.unwrap()));
+
+        // A client that starts counting again does so in a session of its own
+        let restarted = self::session(&signing, &token);
+        assert!(signing.admit(restarted, 5));
+    }
+
+    #[test]
+    fn test_captures_are_refused_once_their_session_is_gone() {
+        let (_dir, signing, token) = signing();
+        let capture = sign(
+            &token,
+            session(&signing, &token),
+            r#"{"type":"MouseClick","button":1,"seq":1}"#.to_string(),
+        );
+        // A restarted server has no sessions at all
+        let restarted = CommandSigning::new(signing.tokens.clone());
+        assert_eq!(
+            restarted.open(capture.clone()).unwrap_err(),
+            SignatureError::UnknownSession
+        );
+        for _ in 0..ServerConfig::SIGNING_SESSIONS_PER_TOKEN {
+            session(&signing, &token);
+        }
+        assert_eq!(
+            signing.open(capture).unwrap_err(),
+            SignatureError::UnknownSession
+        );
+
+        let payload = r#"{"type":"SelectAll","seq":1}"#.to_string();
+        let unsessioned = SignedDatagram {
+            signer: signer_id(&token),
+            mac: utils::to_hex(&keyed(&token, None, &payload).finalize().into_bytes()),
+            payload,
+            session: None,
+        };
+        assert_eq!(
+            signing.open(unsessioned).unwrap_err(),
+            SignatureError::MissingSession
+        );
+    }
+}
@@ -0,0 +1,10 @@
+pub mod command_signing;
+pub mod noise_channel;
+pub mod pairing_lockout;
+pub mod pairing_qr;
+pub mod pairing_requests;
+pub mod pairing_service;
+pub mod pairing_toast;
+pub mod secret_storage;
+pub mod token_store;
+pub mod viewer_ticket;
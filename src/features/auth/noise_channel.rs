@@ -0,0 +1,280 @@
+use crate::domain::models::{
+    CommandDatagram, CommandEnvelope, EncryptedDatagram, HandshakeDatagram, Reply,
+};
+use crate::features::auth::token_store::TokenStore;
+use crate::features::command::replay_window::ReplayWindow;
+use crate::utils;
+use anyhow::Result;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use snow::{HandshakeState, StatelessTransportState};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Ephemeral keys on both sides, bound to the pairing token through the PSK
+const NOISE_PARAMS: &str = "Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s";
+/// Keeps the PSK unrelated to the signer id, which is a hash of the bare token
+const PSK_CONTEXT: &[u8] = b"pointzerver noise psk v1";
+const TAG_LEN: usize = 16;
+/// Largest Noise handshake message this pattern produces: an ephemeral key and an empty AEAD
+const HANDSHAKE_LEN: usize = 32 + TAG_LEN;
+
+/// Why a handshake or encrypted datagram was thrown away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ChannelError {
+    #[error("no live token matches the signer")]
+    UnknownSigner,
+    #[error("handshake failed")]
+    Handshake,
+    #[error("no such session")]
+    UnknownSession,
+    #[error("ciphertext does not decrypt")]
+    Decrypt,
+    #[error("payload is not a command or batch")]
+    MalformedPayload,
+    #[error("encrypted commands must carry a seq")]
+    MissingSeq,
+}
+
+/// Noise sessions opened by paired clients, one per token
+///
+/// The handshake only succeeds for a client that holds the token, so a session is as good as
+/// the token without the token or any command ever crossing the network in the clear.
+pub struct NoiseChannels {
+    tokens: Arc<TokenStore>,
+    sessions: Mutex<HashMap<u64, Channel>>,
+}
+
+struct Channel {
+    token: String,
+    transport: StatelessTransportState,
+    /// Nonce for the next sealed reply
+    next_nonce: u64,
+    window: ReplayWindow,
+}
+
+impl NoiseChannels {
+    pub fn new(tokens: Arc<TokenStore>) -> Self {
+        Self {
+            tokens,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Answers a client's handshake, replacing any earlier session for the same token
+    pub fn accept(&self, hello: HandshakeDatagram) -> Result<Reply, ChannelError> {
+        let token = self
+            .tokens
+            .find_signer(&hello.signer)
+            .ok_or(ChannelError::UnknownSigner)?;
+        let message = utils::from_hex(&hello.handshake).ok_or(ChannelError::Handshake)?;
+        let mut responder = handshake(&token, false).map_err(|_| ChannelError::Handshake)?;
+        let mut buf = [0u8; HANDSHAKE_LEN];
+        responder
+            .read_message(&message, &mut buf)
+            .map_err(|_| ChannelError::Handshake)?;
+        let len = responder
+            .write_message(&[], &mut buf)
+            .map_err(|_| ChannelError::Handshake)?;
+        let transport = responder
+            .into_stateless_transport_mode()
+            .map_err(|_| ChannelError::Handshake)?;
+
+        let session = rand::thread_rng().next_u64();
+        let mut sessions = self.lock_sessions();
+        sessions.retain(|_, channel| channel.token != token);
+        sessions.insert(
+            session,
+            Channel {
+                token,
+                transport,
+                next_nonce: 0,
+                window: ReplayWindow::default(),
+            },
+        );
+        Ok(Reply::HandshakeAccepted {
+            session,
+            handshake: utils::to_hex(&buf[..len]),
+        })
+    }
+
+    /// Decrypts the envelopes, which then carry the session's token and count as signed
+    pub fn open(&self, datagram: EncryptedDatagram) -> Result<Vec<CommandEnvelope>, ChannelError> {
+        let ciphertext = utils::from_hex(&datagram.ciphertext).ok_or(ChannelError::Decrypt)?;
+        let (token, plaintext) = {
+            let sessions = self.lock_sessions();
+            let channel = sessions
+                .get(&datagram.session)
+                .ok_or(ChannelError::UnknownSession)?;
+            let plaintext = decrypt(&channel.transport, datagram.nonce, &ciphertext)
+                .map_err(|_| ChannelError::Decrypt)?;
+            (channel.token.clone(), plaintext)
+        };
+
+        let envelopes = match serde_json::from_slice(&plaintext) {
+            Ok(inner @ (CommandDatagram::Single(_) | CommandDatagram::Batch(_))) => {
+                inner.into_envelopes()
+            }
+            _ => return Err(ChannelError::MalformedPayload),
+        };
+        if envelopes.iter().any(|envelope| envelope.seq.is_none()) {
+            return Err(ChannelError::MissingSeq);
+        }
+        Ok(envelopes
+            .into_iter()
+            .map(|envelope| CommandEnvelope {
+                auth: Some(token.clone()),
+                signed: true,
+                encrypted: true,
+                ..envelope
+            })
+            .collect())
+    }
+
+    /// Records `seq` in `session`, returning false if it was already used there
+    ///
+    /// A new handshake starts a new count, and the old session's datagrams no longer open.
+    pub fn admit(&self, session: u64, seq: u64) -> bool {
+        self.lock_sessions()
+            .get_mut(&session)
+            .is_some_and(|channel| channel.window.admit_strict(seq))
+    }
+
+    /// Seals a reply for `session`; `None` once the session has been replaced
+    pub fn seal(&self, session: u64, reply: &Reply) -> Result<Option<Reply>> {
+        let plaintext = serde_json::to_vec(reply)?;
+        let mut sessions = self.lock_sessions();
+        let Some(channel) = sessions.get_mut(&session) else {
+            return Ok(None);
+        };
+        let nonce = channel.next_nonce;
+        channel.next_nonce += 1;
+        Ok(Some(Reply::Encrypted {
+            nonce,
+            ciphertext: utils::to_hex(&encrypt(&channel.transport, nonce, &plaintext)?),
+        }))
+    }
+
+    fn lock_sessions(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Channel>> {
+        self.sessions.lock().expect("Noise sessions mutex poisoned")
+    }
+}
+
+/// Handshake state for either side, keyed with the PSK derived from `token`
+pub fn handshake(token: &str, initiator: bool) -> Result<HandshakeState> {
+    let psk = Sha256::new()
+        .chain_update(PSK_CONTEXT)
+        .chain_update(token.as_bytes())
+        .finalize();
+    let builder = snow::Builder::new(NOISE_PARAMS.parse()?).psk(0, &psk);
+    Ok(match initiator {
+        true => builder.build_initiator()?,
+        false => builder.build_responder()?,
+    })
+}
+
+pub fn encrypt(
+    transport: &StatelessTransportState,
+    nonce: u64,
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let mut sealed = vec![0u8; plaintext.len() + TAG_LEN];
+    let len = transport.write_message(nonce, plaintext, &mut sealed)?;
+    sealed.truncate(len);
+    Ok(sealed)
+}
+
+pub fn decrypt(
+    transport: &StatelessTransportState,
+    nonce: u64,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let len = transport.read_message(nonce, ciphertext, &mut plaintext)?;
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::auth::command_signing::signer_id;
+    use crate::features::auth::token_store::temp_store;
+    use tempfile::TempDir;
+
+    fn channels() -> (TempDir, NoiseChannels, String) {
+        let (dir, tokens) = temp_store();
+        let token = tokens.issue("Pixel").unwrap();
+        (dir, NoiseChannels::new(Arc::new(tokens)), token)
+    }
+
+    /// Runs the client half of the handshake against `channels`
+    fn connect(channels: &NoiseChannels, token: &str) -> (u64, StatelessTransportState) {
+        let mut initiator = handshake(token, true).unwrap();
+        let mut buf = [0u8; HANDSHAKE_LEN];
+        let len = initiator.write_message(&[], &mut buf).unwrap();
+        let hello = HandshakeDatagram {
+            signer: signer_id(token),
+            handshake: utils::to_hex(&buf[..len]),
+        };
+        let Reply::HandshakeAccepted { session, handshake } = channels.accept(hello).unwrap()
+        else {
+            panic!("handshake not accepted");
+        };
+        initiator
+            .read_message(&utils::from_hex(&handshake).unwrap(), &mut buf)
+            .unwrap();
+        (session, initiator.into_stateless_transport_mode().unwrap())
+    }
+
+    #[test]
+    fn test_commands_and_replies_round_trip() {
+        let (_dir, channels, token) = channels();
+        let (session, transport) = connect(&channels, &token);
+        let payload = br#"{"type":"MouseClick","button":1,"seq":1}"#;
+        let datagram = EncryptedDatagram {
+            session,
+            nonce: 0,
+            ciphertext: utils::to_hex(&encrypt(&transport, 0, payload).unwrap()),
+        };
+        let envelopes = channels.open(datagram).unwrap();
+        assert!(envelopes[0].encrypted && envelopes[0].auth.as_deref() == Some(token.as_str()));
+
+        let reply = channels.seal(session, &Reply::Ack { seq: 1 }).unwrap();
+        let Some(Reply::Encrypted { nonce, ciphertext }) = reply else {
+            panic!("reply not sealed");
+        };
+        let opened = decrypt(&transport, nonce, &utils::from_hex(&ciphertext).unwrap()).unwrap();
+        let reply: Reply = serde_json::from_slice(&opened).unwrap();
+        assert!(matches!(reply, Reply::Ack { seq: 1 }));
+    }
+
+    #[test]
+    fn test_wrong_token_fails_the_handshake() {
+        let (_dir, channels, token) = channels();
+        let mut initiator = handshake("guessed", true).unwrap();
+        let mut buf = [0u8; HANDSHAKE_LEN];
+        let len = initiator.write_message(&[], &mut buf).unwrap();
+        let hello = HandshakeDatagram {
+            signer: signer_id(&token),
+            handshake: utils::to_hex(&buf[..len]),
+        };
+        assert_eq!(channels.accept(hello).unwrap_err(), ChannelError::Handshake);
+    }
+
+    #[test]
+    fn test_new_handshake_replaces_the_session() {
+        let (_dir, channels, token) = channels();
+        let (first, transport) = connect(&channels, &token);
+        let (_second, _) = connect(&channels, &token);
+        let datagram = EncryptedDatagram {
+            session: first,
+            nonce: 0,
+            ciphertext: utils::to_hex(&encrypt(&transport, 0, b"{}").unwrap()),
+        };
+        assert_eq!(
+            channels.open(datagram).unwrap_err(),
+            ChannelError::UnknownSession
+        );
+    }
+}
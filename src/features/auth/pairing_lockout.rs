@@ -0,0 +1,136 @@
+use crate::domain::config::ServerConfig;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Tracks failed PIN attempts per source IP and escalates lockouts
+pub struct PairingLockout {
+    max_failures: u32,
+    base: Duration,
+    max: Duration,
+    entries: HashMap<IpAddr, AttemptState>,
+}
+
+#[derive(Default)]
+struct AttemptState {
+    failures: u32,
+    level: u32,
+    locked_until: Option<Instant>,
+}
+
+/// Active lockout as reported on `/status`
+#[derive(Serialize, Debug, Clone)]
+pub struct LockoutInfo {
+    pub addr: String,
+    pub remaining_secs: u64,
+    pub level: u32,
+}
+
+impl PairingLockout {
+    pub fn new() -> Self {
+        Self {
+            max_failures: ServerConfig::PAIRING_MAX_FAILED_ATTEMPTS,
+            base: Duration::from_secs(ServerConfig::PAIRING_LOCKOUT_BASE_SECS),
+            max: Duration::from_secs(ServerConfig::PAIRING_LOCKOUT_MAX_SECS),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the remaining lockout for `ip`, if any
+    pub fn remaining(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let until = self.entries.get(&ip)?.locked_until?;
+        until.checked_duration_since(now).filter(|d| !d.is_zero())
+    }
+
+    /// Records a wrong PIN and returns the lockout duration if this attempt triggered one
+    pub fn record_failure(&mut self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let entry = self.entries.entry(ip).or_default();
+        entry.failures += 1;
+        if entry.failures < self.max_failures {
+            return None;
+        }
+
+        let duration = self
+            .base
+            .saturating_mul(2u32.saturating_pow(entry.level))
+            .min(self.max);
+        entry.failures = 0;
+        entry.level += 1;
+        entry.locked_until = Some(now + duration);
+        Some(duration)
+    }
+
+    /// Clears all history for `ip` after a successful pairing
+    pub fn record_success(&mut self, ip: IpAddr) {
+        self.entries.remove(&ip);
+    }
+
+    pub fn active(&self, now: Instant) -> Vec<LockoutInfo> {
+        self.entries
+            .iter()
+            .filter_map(|(ip, entry)| {
+                let remaining = entry.locked_until?.checked_duration_since(now)?;
+                Some(LockoutInfo {
+                    addr: ip.to_string(),
+                    remaining_secs: remaining.as_secs(),
+                    level: entry.level,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for PairingLockout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip() -> IpAddr {
+        "192.168.1.50".parse().unwrap()
+    }
+
+    #[test]
+    fn test_locks_after_max_failures() {
+        let mut lockout = PairingLockout::new();
+        let now = Instant::now();
+        for _ in 1..ServerConfig::PAIRING_MAX_FAILED_ATTEMPTS {
+            assert!(lockout.record_failure(ip(), now).is_none());
+        }
+        let duration = lockout.record_failure(ip(), now).unwrap();
+        assert_eq!(duration.as_secs(), ServerConfig::PAIRING_LOCKOUT_BASE_SECS);
+        assert!(lockout.remaining(ip(), now).is_some());
+        assert!(lockout.remaining(ip(), now + duration).is_none());
+    }
+
+    #[test]
+    fn test_lockout_escalates_and_caps() {
+        let mut lockout = PairingLockout::new();
+        let now = Instant::now();
+        let mut last = Duration::ZERO;
+        for _ in 0..20 {
+            let mut triggered = None;
+            for _ in 0..ServerConfig::PAIRING_MAX_FAILED_ATTEMPTS {
+                triggered = lockout.record_failure(ip(), now);
+            }
+            let duration = triggered.unwrap();
+            assert!(duration >= last);
+            last = duration;
+        }
+        assert_eq!(last.as_secs(), ServerConfig::PAIRING_LOCKOUT_MAX_SECS);
+    }
+
+    #[test]
+    fn test_success_resets_history() {
+        let mut lockout = PairingLockout::new();
+        let now = Instant::now();
+        lockout.record_failure(ip(), now);
+        lockout.record_success(ip());
+        assert!(lockout.active(now).is_empty());
+    }
+}
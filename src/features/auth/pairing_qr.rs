@@ -0,0 +1,78 @@
+use crate::domain::config::ServerConfig;
+use crate::features::auth::pairing_service::PairingService;
+use crate::utils;
+use anyhow::Result;
+use qrcode::render::{svg, unicode};
+use qrcode::QrCode;
+use serde::Serialize;
+
+/// What a phone needs to find this server and pair with it, as encoded in the pairing QR code
+#[derive(Serialize, Debug, Clone)]
+pub struct PairingPayload {
+    pub hostname: String,
+    pub ip: Option<String>,
+    pub port: u16,
+    /// The current one-time PIN; the code goes stale once a device pairs with it
+    pub pin: String,
+}
+
+impl PairingPayload {
+    pub fn current(pairing: &PairingService) -> Self {
+        Self {
+            hostname: utils::get_hostname(),
+            ip: utils::get_local_ip().map(|ip| ip.to_string()),
+            port: ServerConfig::COMMAND_PORT,
+            pin: pairing.pin(),
+        }
+    }
+
+    pub fn to_svg(&self) -> Result<String> {
+        Ok(self
+            .code()?
+            .render::<svg::Color>()
+            .min_dimensions(
+                ServerConfig::PAIRING_QR_SVG_PX,
+                ServerConfig::PAIRING_QR_SVG_PX,
+            )
+            .build())
+    }
+
+    /// Two modules per character cell, so the code stays square in a terminal
+    pub fn to_terminal(&self) -> Result<String> {
+        Ok(self
+            .code()?
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build())
+    }
+
+    fn code(&self) -> Result<QrCode> {
+        Ok(QrCode::new(serde_json::to_vec(self)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> PairingPayload {
+        PairingPayload {
+            hostname: "desk".to_string(),
+            ip: Some("192.168.1.10".to_string()),
+            port: ServerConfig::COMMAND_PORT,
+            pin: "123456".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_renders_for_terminal_and_svg() {
+        let terminal = payload().to_terminal().unwrap();
+        let rows: Vec<_> = terminal.lines().collect();
+        assert!(rows.len() > 10);
+        assert!(rows
+            .iter()
+            .all(|row| row.chars().count() == rows[0].chars().count()));
+        assert!(payload().to_svg().unwrap().starts_with("<?xml"));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::domain::config::ServerConfig;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Pairing requests waiting for someone at the host to approve or deny them
+pub struct PairingRequests {
+    ttl: Duration,
+    next_id: u64,
+    pending: BTreeMap<u64, PendingRequest>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingRequest {
+    pub device_name: String,
+    pub addr: SocketAddr,
+    expires_at: Instant,
+}
+
+/// Pending request as listed on `/pairing/requests`
+#[derive(Serialize, Debug, Clone)]
+pub struct PairingRequestInfo {
+    pub id: u64,
+    pub device_name: String,
+    pub addr: String,
+    pub expires_in_secs: u64,
+}
+
+impl PairingRequests {
+    pub fn new() -> Self {
+        Self {
+            ttl: Duration::from_secs(ServerConfig::PAIRING_REQUEST_TTL_SECS),
+            next_id: 1,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Queues a request, replacing an earlier one from the same address
+    ///
+    /// Returns `None` when too many are already waiting.
+    pub fn open(&mut self, device_name: &str, addr: SocketAddr, now: Instant) -> Option<u64> {
+        self.prune(now);
+        self.pending.retain(|_, request| request.addr != addr);
+        if self.pending.len() >= ServerConfig::PAIRING_MAX_PENDING_REQUESTS {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(
+            id,
+            PendingRequest {
+                device_name: device_name.to_string(),
+                addr,
+                expires_at: now + self.ttl,
+            },
+        );
+        Some(id)
+    }
+
+    /// Removes and returns request `id` unless it has expired
+    pub fn take(&mut self, id: u64, now: Instant) -> Option<PendingRequest> {
+        self.prune(now);
+        self.pending.remove(&id)
+    }
+
+    pub fn list(&mut self, now: Instant) -> Vec<PairingRequestInfo> {
+        self.prune(now);
+        self.pending
+            .iter()
+            .map(|(id, request)| PairingRequestInfo {
+                id: *id,
+                device_name: request.device_name.clone(),
+                addr: request.addr.to_string(),
+                expires_in_secs: request.expires_at.duration_since(now).as_secs(),
+            })
+            .collect()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        self.pending.retain(|_, request| request.expires_at > now);
+    }
+}
+
+impl Default for PairingRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, last], 50000))
+    }
+
+    #[test]
+    fn test_request_expires() {
+        let mut requests = PairingRequests::new();
+        let now = Instant::now();
+        let id = requests.open("Pixel 8", addr(5), now).unwrap();
+        assert_eq!(requests.list(now)[0].device_name, "Pixel 8");
+
+        let expired = now + requests.ttl();
+        assert!(requests.list(expired).is_empty());
+        assert_eq!(requests.take(id, expired), None);
+    }
+
+    #[test]
+    fn test_repeat_from_same_address_replaces_request() {
+        let mut requests = PairingRequests::new();
+        let now = Instant::now();
+        let first = requests.open("Pixel 8", addr(5), now).unwrap();
+        let second = requests.open("Pixel 8", addr(5), now).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(requests.take(first, now), None);
+        assert_eq!(requests.take(second, now).unwrap().addr, addr(5));
+    }
+
+    #[test]
+    fn test_pending_requests_are_capped() {
+        let mut requests = PairingRequests::new();
+        let now = Instant::now();
+        for last in 0..ServerConfig::PAIRING_MAX_PENDING_REQUESTS as u8 {
+            assert!(requests.open("Phone", addr(last), now).is_some());
+        }
+        assert_eq!(requests.open("Phone", addr(200), now), None);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::domain::models::{Reply, ServerEvent};
+use crate::features::auth::pairing_lockout::{LockoutInfo, PairingLockout};
+use crate::features::auth::pairing_requests::{PairingRequestInfo, PairingRequests};
+use crate::features::auth::token_store::TokenStore;
+use crate::features::events::event_bus::EventBus;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Exchanges a one-time PIN shown on the host, or an approval given there, for a bearer token
+pub struct PairingService {
+    pin: Mutex<String>,
+    lockout: Mutex<PairingLockout>,
+    requests: Mutex<PairingRequests>,
+    /// Replies to approval requests, decided on the host and waiting to go out
+    decisions: Mutex<VecDeque<(SocketAddr, Reply)>>,
+    decided: Notify,
+    tokens: Arc<TokenStore>,
+    events: EventBus,
+}
+
+impl PairingService {
+    pub fn new(tokens: Arc<TokenStore>, events: EventBus) -> Self {
+        let service = Self {
+            pin: Mutex::new(String::new()),
+            lockout: Mutex::new(PairingLockout::new()),
+            requests: Mutex::new(PairingRequests::new()),
+            decisions: Mutex::new(VecDeque::new()),
+            decided: Notify::new(),
+            tokens,
+            events,
+        };
+        service.rotate_pin();
+        service
+    }
+
+    /// Validates a pairing attempt and returns the reply for the client
+    pub fn pair(&self, pin: &str, device_name: &str, addr: SocketAddr) -> Reply {
+        let now = Instant::now();
+        let ip = addr.ip();
+
+        if let Some(remaining) = self.lock_lockout().remaining(ip, now) {
+            log::warn!("Pairing attempt from locked-out {}", addr);
+            return Reply::PairRejected {
+                reason: "too many attempts".to_string(),
+                retry_after_secs: Some(remaining.as_secs().max(1)),
+            };
+        }
+
+        let matches = {
+            let current = self.pin.lock().expect("Pairing PIN mutex poisoned");
+            *current == pin
+        };
+
+        if !matches {
+            log::warn!("Pairing attempt from {} with wrong PIN", addr);
+            let locked_for = self.record_failure(addr, now);
+            return Reply::PairRejected {
+                reason: "invalid PIN".to_string(),
+                retry_after_secs: locked_for.map(|d| d.as_secs()),
+            };
+        }
+
+        let reply = self.grant(device_name, addr);
+        if matches!(reply, Reply::Paired { .. }) {
+            self.rotate_pin();
+        }
+        reply
+    }
+
+    /// Queues a request for approval on the host instead of typing the PIN
+    pub fn request_approval(&self, device_name: &str, addr: SocketAddr) -> Reply {
+        let now = Instant::now();
+        if let Some(remaining) = self.lock_lockout().remaining(addr.ip(), now) {
+            log::warn!("Pairing request from locked-out {}", addr);
+            return Reply::PairRejected {
+                reason: "too many attempts".to_string(),
+                retry_after_secs: Some(remaining.as_secs().max(1)),
+            };
+        }
+
+        let mut requests = self.lock_requests();
+        let Some(id) = requests.open(device_name, addr, now) else {
+            log::warn!("Pairing request from {} refused; too many pending", addr);
+            return Reply::PairRejected {
+                reason: "too many pending requests".to_string(),
+                retry_after_secs: Some(requests.ttl().as_secs()),
+            };
+        };
+        let expires_in_secs = requests.ttl().as_secs();
+        drop(requests);
+
+        log::info!("Device '{}' at {} asks to pair", device_name, addr);
+        self.events.publish(ServerEvent::PairingRequested {
+            id,
+            device_name: device_name.to_string(),
+            addr: addr.to_string(),
+            expires_in_secs,
+        });
+        Reply::PairPending {
+            request_id: id,
+            expires_in_secs,
+        }
+    }
+
+    /// Requests still waiting for a decision
+    pub fn pending_requests(&self) -> Vec<PairingRequestInfo> {
+        self.lock_requests().list(Instant::now())
+    }
+
+    /// Issues a token for request `id` and sends it to the device; `false` if it's gone
+    pub fn approve(&self, id: u64) -> bool {
+        let Some(request) = self.lock_requests().take(id, Instant::now()) else {
+            return false;
+        };
+        let reply = self.grant(&request.device_name, request.addr);
+        self.decide(request.addr, reply);
+        true
+    }
+
+    /// Turns request `id` down, counting it as a failed attempt; `false` if it's gone
+    pub fn deny(&self, id: u64) -> bool {
+        let Some(request) = self.lock_requests().take(id, Instant::now()) else {
+            return false;
+        };
+        log::info!(
+            "Denied pairing for '{}' from {}",
+            request.device_name,
+            request.addr
+        );
+        let locked_for = self.record_failure(request.addr, Instant::now());
+        self.decide(
+            request.addr,
+            Reply::PairRejected {
+                reason: "denied".to_string(),
+                retry_after_secs: locked_for.map(|d| d.as_secs()),
+            },
+        );
+        true
+    }
+
+    /// Waits for the next approval or denial to send back to its device
+    pub async fn next_decision(&self) -> (SocketAddr, Reply) {
+        loop {
+            if let Some(decision) = self.lock_decisions().pop_front() {
+                return decision;
+            }
+            self.decided.notified().await;
+        }
+    }
+
+    fn decide(&self, addr: SocketAddr, reply: Reply) {
+        self.lock_decisions().push_back((addr, reply));
+        self.decided.notify_one();
+    }
+
+    /// Counts a failed attempt from `addr`, returning the lockout it triggered
+    fn record_failure(&self, addr: SocketAddr, now: Instant) -> Option<Duration> {
+        self.events.publish(ServerEvent::PairingFailed {
+            addr: addr.to_string(),
+        });
+        let ip = addr.ip();
+        let locked_for = self.lock_lockout().record_failure(ip, now);
+        if let Some(duration) = locked_for {
+            log::warn!("Locking out {} from pairing for {:?}", ip, duration);
+            self.events.publish(ServerEvent::PairingLockedOut {
+                addr: ip.to_string(),
+                duration_secs: duration.as_secs(),
+            });
+        }
+        locked_for
+    }
+
+    fn grant(&self, device_name: &str, addr: SocketAddr) -> Reply {
+        match self.tokens.issue(device_name) {
+            Ok(token) => {
+                log::info!("Paired device '{}' from {}", device_name, addr);
+                self.lock_lockout().record_success(addr.ip());
+                self.events.publish(ServerEvent::DevicePaired {
+                    device_name: device_name.to_string(),
+                });
+                Reply::Paired { token }
+            }
+            Err(e) => {
+                log::error!("Failed to persist pairing token: {}", e);
+                Reply::PairRejected {
+                    reason: "server error".to_string(),
+                    retry_after_secs: None,
+                }
+            }
+        }
+    }
+
+    /// The PIN a device can pair with right now
+    pub fn pin(&self) -> String {
+        self.pin.lock().expect("Pairing PIN mutex poisoned").clone()
+    }
+
+    /// Source addresses currently locked out of pairing
+    pub fn lockouts(&self) -> Vec<LockoutInfo> {
+        self.lock_lockout().active(Instant::now())
+    }
+
+    /// Replaces the PIN so each one can only be used once
+    fn rotate_pin(&self) {
+        let pin = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        log::info!("Pairing PIN: {}", pin);
+        *self.pin.lock().expect("Pairing PIN mutex poisoned") = pin;
+    }
+
+    fn lock_lockout(&self) -> MutexGuard<'_, PairingLockout> {
+        self.lockout.lock().expect("Pairing lockout mutex poisoned")
+    }
+
+    fn lock_requests(&self) -> MutexGuard<'_, PairingRequests> {
+        self.requests
+            .lock()
+            .expect("Pairing requests mutex poisoned")
+    }
+
+    fn lock_decisions(&self) -> MutexGuard<'_, VecDeque<(SocketAddr, Reply)>> {
+        self.decisions
+            .lock()
+            .expect("Pairing decisions mutex poisoned")
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(windows)]
+use crate::domain::models::ServerEvent;
+use crate::features::auth::pairing_service::PairingService;
+#[cfg(windows)]
+use crate::features::events::event_bus::EventBus;
+use crate::input::desk_notice::escape_xml;
+#[cfg(windows)]
+use crate::input::desk_notice::TOAST_APP_ID;
+#[cfg(windows)]
+use std::sync::Arc;
+
+/// Button pressed on a pairing toast, round-tripped through the toast's `arguments`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastAction {
+    Approve(u64),
+    Deny(u64),
+}
+
+impl ToastAction {
+    pub fn parse(arguments: &str) -> Option<Self> {
+        let (verb, id) = arguments.split_once(':')?;
+        let id = id.parse().ok()?;
+        match verb {
+            "approve" => Some(Self::Approve(id)),
+            "deny" => Some(Self::Deny(id)),
+            _ => None,
+        }
+    }
+
+    pub fn arguments(self) -> String {
+        match self {
+            Self::Approve(id) => format!("approve:{}", id),
+            Self::Deny(id) => format!("deny:{}", id),
+        }
+    }
+
+    /// Decides the request; `false` if it was already decided elsewhere or has expired
+    pub fn apply(self, pairing: &PairingService) -> bool {
+        match self {
+            Self::Approve(id) => pairing.approve(id),
+            Self::Deny(id) => pairing.deny(id),
+        }
+    }
+}
+
+/// Toast XML asking to approve request `id`, which stays up until a button is pressed
+pub fn toast_xml(id: u64, device_name: &str, addr: &str, expires_in_secs: u64) -> String {
+    format!(
+        concat!(
+            r#"<toast scenario="reminder"><visual><binding template="ToastGeneric">"#,
+            "<text>Pair {}?</text>",
+            "<text>{} wants to control this computer. The request expires in {} seconds.</text>",
+            "</binding></visual><actions>",
+            r#"<action content="Approve" arguments="{}" activationType="foreground"/>"#,
+            r#"<action content="Deny" arguments="{}" activationType="foreground"/>"#,
+            "</actions></toast>"
+        ),
+        escape_xml(device_name),
+        escape_xml(addr),
+        expires_in_secs,
+        ToastAction::Approve(id).arguments(),
+        ToastAction::Deny(id).arguments(),
+    )
+}
+
+/// Shows an Approve/Deny toast for every pairing request until the event bus closes
+#[cfg(windows)]
+pub async fn run(pairing: Arc<PairingService>, events: EventBus) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut receiver = events.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(ServerEvent::PairingRequested {
+                id,
+                device_name,
+                addr,
+                expires_in_secs,
+            }) => {
+                let xml = toast_xml(id, &device_name, &addr, expires_in_secs);
+                if let Err(e) = show(&xml, pairing.clone()) {
+                    log::warn!("Failed to show pairing toast: {}", e);
+                }
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Windows calls `Activated` on one of its own threads when a button is pressed
+#[cfg(windows)]
+fn show(xml: &str, pairing: Arc<PairingService>) -> windows::core::Result<()> {
+    use windows::core::{IInspectable, Interface, HSTRING};
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::Foundation::TypedEventHandler;
+    use windows::UI::Notifications::{
+        ToastActivatedEventArgs, ToastNotification, ToastNotificationManager,
+    };
+
+    let document = XmlDocument::new()?;
+    document.LoadXml(&HSTRING::from(xml))?;
+    let toast = ToastNotification::CreateToastNotification(&document)?;
+    toast.Activated(&TypedEventHandler::new(
+        move |_, args: &Option<IInspectable>| {
+            let Some(args) = args else {
+                return Ok(());
+            };
+            let arguments = args.cast::<ToastActivatedEventArgs>()?.Arguments()?;
+            // Clicking the toast body rather than a button carries no action
+            if let Some(action) = ToastAction::parse(&arguments.to_string()) {
+                if !action.apply(&pairing) {
+                    log::info!("Pairing request was already decided or has expired");
+                }
+            }
+            Ok(())
+        },
+    ))?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(TOAST_APP_ID))?.Show(&toast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_arguments_round_trip() {
+        for action in [ToastAction::Approve(3), ToastAction::Deny(12)] {
+            assert_eq!(ToastAction::parse(&action.arguments()), Some(action));
+        }
+        assert_eq!(ToastAction::parse(""), None);
+        assert_eq!(ToastAction::parse("pair:3"), None);
+        assert_eq!(ToastAction::parse("approve:x"), None);
+    }
+
+    #[test]
+    fn test_device_name_cannot_inject_markup() {
+        let xml = toast_xml(
+            3,
+            r#"Pixel</text><action arguments="approve:4"/>"#,
+            "192.168.1.5:50000",
+            120,
+        );
+        assert!(xml.contains("Pixel&lt;/text&gt;&lt;action arguments=&quot;approve:4&quot;/&gt;"));
+        assert_eq!(xml.matches("<action ").count(), 2);
+        assert!(xml.contains(r#"arguments="approve:3""#));
+    }
+}
@@ -0,0 +1,37 @@
+//! `pointzerver encrypt-secrets <passphrase|keychain|tpm>`: seals the stored credentials
+
+use crate::domain::config::secrets::{self, UnlockMethod};
+use crate::domain::config::Settings;
+use crate::features::auth::token_store::TokenStore;
+use crate::utils::paths;
+use anyhow::{Context, Result};
+
+/// Creates the secrets key, then rewrites the tokens and secret settings sealed with it
+pub fn encrypt(method: UnlockMethod) -> Result<()> {
+    secrets::enable(&paths::secrets_file(), method)?;
+    let tokens = TokenStore::load(paths::tokens_file(), &paths::admin_token_file())
+        .context("Failed to load tokens")?;
+    tokens.reseal()?;
+    let settings_path = paths::settings_file();
+    let settings = Settings::load_or_default(&settings_path);
+    if !settings.rendezvous.room.is_empty() {
+        let mut rendezvous = settings.rendezvous.clone();
+        rendezvous.room = secrets::seal(&rendezvous.room)?;
+        Settings::update_file(&settings_path, "rendezvous", &rendezvous)?;
+    }
+    if !settings.auth.session_pin.is_empty() {
+        let mut auth = settings.auth.clone();
+        auth.session_pin = secrets::seal(&auth.session_pin)?;
+        Settings::update_file(&settings_path, "auth", &auth)?;
+    }
+    if !settings.webhooks.secret.is_empty() {
+        let mut webhooks = settings.webhooks.clone();
+        webhooks.secret = secrets::seal(&webhooks.secret)?;
+        Settings::update_file(&settings_path, "webhooks", &webhooks)?;
+    }
+    println!(
+        "Encrypted paired tokens, the session PIN, the rendezvous room and the webhook secret; they unlock with {:?} from now on",
+        method
+    );
+    Ok(())
+}
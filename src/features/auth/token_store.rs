@@ -0,0 +1,480 @@
+use crate::domain::config::secrets;
+use crate::domain::models::Scope;
+use crate::features::auth::command_signing::signer_id;
+use crate::utils::migrations::{self, Schema};
+use crate::utils::unix_now;
+use anyhow::Result;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const TOKEN_BYTES: usize = 32;
+const ADMIN_NAME: &str = "admin";
+
+/// Version 1 was a bare array of devices, which had nowhere to record a version
+const TOKENS_SCHEMA: Schema = Schema {
+    name: "tokens",
+    migrations: &[wrap_device_list],
+};
+
+/// What kind of holder a token was issued to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Local tools (tray, web panel) reading the admin token file
+    Admin,
+    #[default]
+    Device,
+    Guest,
+    /// Status displays that watch `/events` and `/status` but can't send input or change anything
+    Observer,
+}
+
+/// A device that completed pairing, or a guest, holding a bearer token
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairedDevice {
+    pub device_name: String,
+    pub token: String,
+    pub paired_at: u64,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Allowed command scopes; `None` grants everything
+    #[serde(default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// Public view of a token holder that never exposes the token
+#[derive(Serialize, Debug, Clone)]
+pub struct DeviceInfo {
+    /// Signer id of the token, used to revoke it through `DELETE /clients/{id}`
+    pub id: String,
+    pub device_name: String,
+    pub paired_at: u64,
+    pub role: Role,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl DeviceInfo {
+    pub fn allows(&self, scope: Scope) -> bool {
+        if self.role == Role::Observer {
+            return false;
+        }
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+
+    /// Narrows `scopes` to what this holder may do itself, so nothing it mints outranks it
+    pub fn delegable(&self, scopes: Vec<Scope>) -> Vec<Scope> {
+        scopes
+            .into_iter()
+            .filter(|scope| self.allows(*scope))
+            .collect()
+    }
+}
+
+/// Persistent store of tokens issued at pairing time or minted for guests
+pub struct TokenStore {
+    path: PathBuf,
+    admin_token: String,
+    devices: RwLock<Vec<PairedDevice>>,
+}
+
+impl TokenStore {
+    /// Loads previously issued tokens from `path` and the admin token from `admin_path`,
+    /// creating the admin token on first run
+    ///
+    /// Sealed tokens need [`secrets::unlock`] to have run first.
+    pub fn load(path: PathBuf, admin_path: &Path) -> Result<Self> {
+        let mut devices: Vec<PairedDevice> = match migrations::load(&path, &TOKENS_SCHEMA)? {
+            Some(mut file) => serde_json::from_value(file["devices"].take())?,
+            None => Vec::new(),
+        };
+        for device in &mut devices {
+            device.token = secrets::open(&device.token)?;
+        }
+        Ok(Self {
+            path,
+            admin_token: load_or_create_admin_token(admin_path)?,
+            devices: RwLock::new(devices),
+        })
+    }
+
+    /// Mints and persists a new token for `device_name`
+    pub fn issue(&self, device_name: &str) -> Result<String> {
+        self.insert(PairedDevice {
+            device_name: device_name.to_string(),
+            token: generate_token(),
+            paired_at: unix_now(),
+            role: Role::Device,
+            expires_at: None,
+            scopes: None,
+        })
+    }
+
+    /// Mints a guest token valid for `minutes` and limited to `scopes`
+    pub fn issue_guest(
+        &self,
+        label: &str,
+        minutes: u64,
+        scopes: Vec<Scope>,
+    ) -> Result<(String, u64)> {
+        let now = unix_now();
+        let expires_at = now + minutes * 60;
+        let token = self.insert(PairedDevice {
+            device_name: label.to_string(),
+            token: generate_token(),
+            paired_at: now,
+            role: Role::Guest,
+            expires_at: Some(expires_at),
+            scopes: Some(scopes),
+        })?;
+        Ok((token, expires_at))
+    }
+
+    /// Mints a read-only token for a status display; it never expires
+    pub fn issue_observer(&self, label: &str) -> Result<String> {
+        self.insert(PairedDevice {
+            device_name: label.to_string(),
+            token: generate_token(),
+            paired_at: unix_now(),
+            role: Role::Observer,
+            expires_at: None,
+            scopes: Some(Vec::new()),
+        })
+    }
+
+    /// Returns the holder of `token` if it exists and has not expired, comparing in constant time
+    pub fn validate(&self, token: &str) -> Option<DeviceInfo> {
+        if constant_time_eq(self.admin_token.as_bytes(), token.as_bytes()) {
+            return Some(DeviceInfo {
+                id: signer_id(&self.admin_token),
+                device_name: ADMIN_NAME.to_string(),
+                paired_at: 0,
+                role: Role::Admin,
+                expires_at: None,
+                scopes: None,
+            });
+        }
+
+        let now = unix_now();
+        let devices = self.devices.read().expect("Token store lock poisoned");
+        let mut found = None;
+        for device in devices.iter() {
+            if constant_time_eq(device.token.as_bytes(), token.as_bytes()) {
+                found = Some(device);
+            }
+        }
+        found
+            .filter(|device| !is_expired(device, now))
+            .map(DeviceInfo::from)
+    }
+
+    /// Returns the live token whose signer id is `signer`, for opening signed datagrams
+    pub fn find_signer(&self, signer: &str) -> Option<String> {
+        if signer_id(&self.admin_token) == signer {
+            return Some(self.admin_token.clone());
+        }
+        let now = unix_now();
+        let devices = self.devices.read().expect("Token store lock poisoned");
+        devices
+            .iter()
+            .filter(|device| !is_expired(device, now))
+            .find(|device| signer_id(&device.token) == signer)
+            .map(|device| device.token.clone())
+    }
+
+    pub fn devices(&self) -> Vec<DeviceInfo> {
+        let now = unix_now();
+        let devices = self.devices.read().expect("Token store lock poisoned");
+        devices
+            .iter()
+            .filter(|device| !is_expired(device, now))
+            .map(DeviceInfo::from)
+            .collect()
+    }
+
+    /// Deletes the token whose signer id is `id`, returning its holder if there was one
+    pub fn revoke(&self, id: &str) -> Result<Option<DeviceInfo>> {
+        let mut devices = self.devices.write().expect("Token store lock poisoned");
+        let Some(index) = devices
+            .iter()
+            .position(|device| signer_id(&device.token) == id)
+        else {
+            return Ok(None);
+        };
+        let revoked = devices.remove(index);
+        self.save(&devices)?;
+        Ok(Some(DeviceInfo::from(&revoked)))
+    }
+
+    /// Limits the token whose signer id is `id` to `scopes`, or lifts the limit with `None`,
+    /// returning its holder if there was one
+    pub fn set_scopes(&self, id: &str, scopes: Option<Vec<Scope>>) -> Result<Option<DeviceInfo>> {
+        let mut devices = self.devices.write().expect("Token store lock poisoned");
+        let Some(device) = devices
+            .iter_mut()
+            .find(|device| signer_id(&device.token) == id)
+        else {
+            return Ok(None);
+        };
+        device.scopes = scopes;
+        let updated = DeviceInfo::from(&*device);
+        self.save(&devices)?;
+        Ok(Some(updated))
+    }
+
+    /// Writes the tokens again, sealing them if secrets have been unlocked since they were read
+    pub fn reseal(&self) -> Result<()> {
+        let devices = self.devices.read().expect("Token store lock poisoned");
+        self.save(&devices)
+    }
+
+    fn insert(&self, device: PairedDevice) -> Result<String> {
+        let token = device.token.clone();
+        let now = unix_now();
+
+        let mut devices = self.devices.write().expect("Token store lock poisoned");
+        devices.retain(|existing| !is_expired(existing, now));
+        devices.push(device);
+        self.save(&devices)?;
+        Ok(token)
+    }
+
+    fn save(&self, devices: &[PairedDevice]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let sealed = devices
+            .iter()
+            .map(|device| {
+                Ok(PairedDevice {
+                    token: secrets::seal(&device.token)?,
+                    ..device.clone()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let file = serde_json::json!({
+            "version": TOKENS_SCHEMA.current(),
+            "devices": sealed,
+        });
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+impl From<&PairedDevice> for DeviceInfo {
+    fn from(device: &PairedDevice) -> Self {
+        Self {
+            id: signer_id(&device.token),
+            device_name: device.device_name.clone(),
+            paired_at: device.paired_at,
+            role: device.role,
+            expires_at: device.expires_at,
+            scopes: device.scopes.clone(),
+        }
+    }
+}
+
+fn is_expired(device: &PairedDevice, now: u64) -> bool {
+    device
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+}
+
+fn load_or_create_admin_token(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let token = generate_token();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, &token)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    log::info!("Created admin token at {}", path.display());
+    Ok(token)
+}
+
+fn wrap_device_list(devices: Value) -> Result<Value> {
+    Ok(serde_json::json!({ "devices": devices }))
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// A store in a fresh temp directory, deleted when the returned `TempDir` drops
+#[cfg(test)]
+pub(crate) fn temp_store() -> (tempfile::TempDir, TokenStore) {
+    let dir = tempfile::tempdir().unwrap();
+    let store = TokenStore::load(
+        dir.path().join("tokens.json"),
+        &dir.path().join("admin.token"),
+    )
+    .unwrap();
+    (dir, store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issued_token_validates() {
+        let (_dir, store) = temp_store();
+        let token = store.issue("Pixel").unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_eq!(store.validate(&token).unwrap().device_name, "Pixel");
+        assert!(store.validate("not-a-token").is_none());
+    }
+
+    #[test]
+    fn test_narrowed_device_delegates_only_its_own_scopes() {
+        let (_dir, store) = temp_store();
+        let token = store.issue("Pixel").unwrap();
+        let mut holder = store.validate(&token).unwrap();
+        let requested = vec![Scope::Pointer, Scope::Power];
+        assert_eq!(holder.delegable(requested.clone()), requested);
+        holder.scopes = Some(vec![Scope::Pointer, Scope::Keyboard]);
+        assert_eq!(holder.delegable(requested), vec![Scope::Pointer]);
+    }
+
+    #[test]
+    fn test_tokens_survive_reload() {
+        let (_dir, store) = temp_store();
+        let token = store.issue("Pixel").unwrap();
+        let admin_path = store.path.with_file_name("admin.token");
+        let reloaded = TokenStore::load(store.path.clone(), &admin_path).unwrap();
+        assert!(reloaded.validate(&token).is_some());
+        assert!(reloaded.validate(&store.admin_token).is_some());
+    }
+
+    #[test]
+    fn test_unversioned_tokens_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.json");
+        std::fs::write(
+            &path,
+            r#"[{ "device_name": "Pixel", "token": "abc123", "paired_at": 1 }]"#,
+        )
+        .unwrap();
+
+        let store = TokenStore::load(path.clone(), &dir.path().join("admin.token")).unwrap();
+        assert_eq!(store.validate("abc123").unwrap().device_name, "Pixel");
+        store.issue("Tablet").unwrap();
+        let saved: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], 2);
+        assert_eq!(saved["devices"].as_array().unwrap().len(), 2);
+        assert!(dir.path().join("tokens.json.v1.bak").exists());
+    }
+
+    #[test]
+    fn test_guest_token_is_scoped() {
+        let (_dir, store) = temp_store();
+        let (token, _) = store
+            .issue_guest("Colleague", 30, vec![Scope::Pointer])
+            .unwrap();
+        let info = store.validate(&token).unwrap();
+        assert_eq!(info.role, Role::Guest);
+        assert!(info.allows(Scope::Pointer));
+        assert!(!info.allows(Scope::Keyboard));
+    }
+
+    #[test]
+    fn test_observer_token_allows_no_scope() {
+        let (_dir, store) = temp_store();
+        let token = store.issue_observer("Hallway tablet").unwrap();
+        let info = store.validate(&token).unwrap();
+        assert_eq!(info.role, Role::Observer);
+        for scope in [
+            Scope::Pointer,
+            Scope::Keyboard,
+            Scope::Clipboard,
+            Scope::System,
+            Scope::Power,
+            Scope::Files,
+        ] {
+            assert!(!info.allows(scope));
+        }
+    }
+
+    #[test]
+    fn test_expired_guest_token_rejected() {
+        let (_dir, store) = temp_store();
+        let (token, _) = store
+            .issue_guest("Colleague", 0, vec![Scope::Pointer])
+            .unwrap();
+        assert!(store.validate(&token).is_none());
+    }
+
+    #[test]
+    fn test_revoked_token_no_longer_validates() {
+        let (_dir, store) = temp_store();
+        let token = store.issue("Pixel").unwrap();
+        let kept = store.issue("Tablet").unwrap();
+        let id = store.validate(&token).unwrap().id;
+
+        assert_eq!(store.revoke(&id).unwrap().unwrap().device_name, "Pixel");
+        assert!(store.validate(&token).is_none());
+        assert!(store.find_signer(&id).is_none());
+        assert!(store.validate(&kept).is_some());
+        assert!(store.revoke(&id).unwrap().is_none());
+
+        let admin_path = store.path.with_file_name("admin.token");
+        let reloaded = TokenStore::load(store.path.clone(), &admin_path).unwrap();
+        assert!(reloaded.validate(&token).is_none());
+    }
+
+    #[test]
+    fn test_scoped_device_limited_until_lifted() {
+        let (_dir, store) = temp_store();
+        let token = store.issue("Clicker").unwrap();
+        let id = store.validate(&token).unwrap().id;
+
+        let limited = store
+            .set_scopes(&id, Some(vec![Scope::Pointer, Scope::Keyboard]))
+            .unwrap()
+            .unwrap();
+        assert!(limited.allows(Scope::Keyboard));
+        let info = store.validate(&token).unwrap();
+        assert!(info.allows(Scope::Pointer));
+        assert!(!info.allows(Scope::Clipboard));
+        assert!(!info.allows(Scope::Power));
+
+        store.set_scopes(&id, None).unwrap().unwrap();
+        assert!(store.validate(&token).unwrap().allows(Scope::Files));
+        assert!(store.set_scopes("missing", None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
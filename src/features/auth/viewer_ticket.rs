@@ -0,0 +1,77 @@
+//! Short-lived screen preview tickets, so the pairing token never crosses plain HTTP
+//!
+//! A client derives a ticket from its token on its own; the server finds the token by the
+//! signer id and checks the MAC, the same way it opens signed datagrams.
+
+use crate::domain::config::ServerConfig;
+use crate::features::auth::command_signing::signer_id;
+use crate::features::auth::token_store::TokenStore;
+use crate::utils;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `<signer id>.<expires_at>.<hex MAC>`, good until `expires_at` in Unix seconds
+pub fn derive(token: &str, expires_at: u64) -> String {
+    let mac = keyed(token, expires_at).finalize().into_bytes();
+    format!(
+        "{}.{}.{}",
+        signer_id(token),
+        expires_at,
+        utils::to_hex(&mac)
+    )
+}
+
+/// Returns the token `ticket` was derived from, if it is live and expires soon enough
+pub fn open(tokens: &TokenStore, ticket: &str, now: u64) -> Option<String> {
+    let mut parts = ticket.splitn(3, '.');
+    let (signer, expires_at, mac) = (parts.next()?, parts.next()?, parts.next()?);
+    let expires_at: u64 = expires_at.parse().ok()?;
+    if expires_at <= now || expires_at > now + ServerConfig::PREVIEW_TICKET_MAX_SECS {
+        return None;
+    }
+    let token = tokens.find_signer(signer)?;
+    keyed(&token, expires_at)
+        .verify_slice(&utils::from_hex(mac)?)
+        .ok()?;
+    Some(token)
+}
+
+fn keyed(token: &str, expires_at: u64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(token.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("preview:{}", expires_at).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::auth::token_store::temp_store;
+
+    #[test]
+    fn test_ticket_opens_to_its_token_until_it_expires() {
+        let (_dir, tokens) = temp_store();
+        let token = tokens.issue("Pixel").unwrap();
+        let ticket = derive(&token, 1_000 + 60);
+        assert!(!ticket.contains(&token));
+        assert_eq!(
+            open(&tokens, &ticket, 1_000).as_deref(),
+            Some(token.as_str())
+        );
+        assert!(open(&tokens, &ticket, 1_060).is_none());
+    }
+
+    #[test]
+    fn test_forged_or_long_lived_tickets_are_refused() {
+        let (_dir, tokens) = temp_store();
+        let token = tokens.issue("Pixel").unwrap();
+        let far = derive(&token, 1_000 + ServerConfig::PREVIEW_TICKET_MAX_SECS + 1);
+        assert!(open(&tokens, &far, 1_000).is_none());
+        let stretched = derive(&token, 1_060).replace(".1060.", ".1120.");
+        assert!(open(&tokens, &stretched, 1_000).is_none());
+        assert!(open(&tokens, &derive("not-a-token", 1_060), 1_000).is_none());
+        assert!(open(&tokens, "garbage", 1_000).is_none());
+    }
+}
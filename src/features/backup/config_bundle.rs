@@ -0,0 +1,253 @@
+//! Passphrase-encrypted bundles for moving configuration and pairings to another machine
+//!
+//! Layout: magic, Argon2id salt, XChaCha20-Poly1305 nonce, then the sealed JSON payload.
+
+use crate::domain::config::secrets;
+use crate::domain::config::ServerConfig;
+use crate::utils::paths;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Config-directory files carried in a bundle; the admin token stays tied to its machine
+const BUNDLED_FILES: &[&str] = &[ServerConfig::SETTINGS_FILE, ServerConfig::TOKENS_FILE];
+
+const NONCE_LEN: usize = 24;
+
+/// Settings fields that `encrypt-secrets` seals, as (section, field)
+const SEALED_SETTINGS: &[(&str, &str)] = &[
+    ("rendezvous", "room"),
+    ("auth", "session_pin"),
+    ("webhooks", "secret"),
+];
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Payload {
+    /// File name to contents
+    files: BTreeMap<String, String>,
+}
+
+/// `pointzerver export-config <bundle>`
+pub fn export(bundle: &Path) -> Result<()> {
+    let dir = paths::config_dir();
+    secrets::unlock(&paths::secrets_file())?;
+    let mut files = BTreeMap::new();
+    for name in BUNDLED_FILES {
+        match std::fs::read_to_string(dir.join(name)) {
+            Ok(contents) => {
+                // The bundle is encrypted already, and the target machine has its own key
+                let opened = map_secrets(name, &contents, secrets::open)?;
+                files.insert(name.to_string(), opened);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", name)),
+        }
+    }
+    if files.is_empty() {
+        bail!("Nothing to export in {}", dir.display());
+    }
+
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if passphrase.is_empty() {
+        bail!("The passphrase must not be empty");
+    }
+    if rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        bail!("Passphrases don't match");
+    }
+    let sealed = seal(&Payload { files }, &passphrase)?;
+    std::fs::write(bundle, sealed)
+        .with_context(|| format!("Failed to write {}", bundle.display()))?;
+    println!(
+        "Exported configuration and pairings to {}",
+        bundle.display()
+    );
+    Ok(())
+}
+
+/// `pointzerver import-config <bundle>`: replaces the bundled files in the config directory
+pub fn import(bundle: &Path) -> Result<()> {
+    let sealed =
+        std::fs::read(bundle).with_context(|| format!("Failed to read {}", bundle.display()))?;
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    let payload = open(&sealed, &passphrase)?;
+
+    let dir = paths::config_dir();
+    std::fs::create_dir_all(&dir)?;
+    secrets::unlock(&paths::secrets_file())?;
+    for (name, contents) in &payload.files {
+        if !BUNDLED_FILES.contains(&name.as_str()) {
+            log::warn!("Skipping unexpected file '{}' in bundle", name);
+            continue;
+        }
+        let sealed = map_secrets(name, contents, secrets::seal)?;
+        std::fs::write(dir.join(name), sealed)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+    println!(
+        "Imported configuration into {}; restart PointZerver to apply it",
+        dir.display()
+    );
+    Ok(())
+}
+
+/// Rewrites the secret values in bundled file `name` with `transform`, leaving the rest as is
+fn map_secrets(
+    name: &str,
+    contents: &str,
+    transform: impl Fn(&str) -> Result<String>,
+) -> Result<String> {
+    let mut file: serde_json::Value =
+        serde_json::from_str(contents).with_context(|| format!("Invalid {}", name))?;
+    let apply = |value: Option<&mut serde_json::Value>| -> Result<()> {
+        if let Some(value) = value {
+            if let Some(secret) = value.as_str().filter(|secret| !secret.is_empty()) {
+                *value = transform(secret)?.into();
+            }
+        }
+        Ok(())
+    };
+    match name {
+        ServerConfig::SETTINGS_FILE => {
+            for (section, field) in SEALED_SETTINGS {
+                apply(file.get_mut(*section).and_then(|s| s.get_mut(*field)))?;
+            }
+        }
+        ServerConfig::TOKENS_FILE => {
+            // Files from before the version wrapper hold the device list at the top level
+            let devices = match file.get_mut("devices") {
+                Some(devices) => devices,
+                None => &mut file,
+            };
+            for device in devices.as_array_mut().into_iter().flatten() {
+                apply(device.get_mut("token"))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+fn seal(payload: &Payload, passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; ServerConfig::BUNDLE_SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let cipher = cipher(passphrase, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, serde_json::to_vec(payload)?.as_slice())
+        .map_err(|_| anyhow!("Failed to encrypt the bundle"))?;
+
+    let mut sealed = ServerConfig::BUNDLE_MAGIC.to_vec();
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(sealed: &[u8], passphrase: &str) -> Result<Payload> {
+    let rest = sealed
+        .strip_prefix(ServerConfig::BUNDLE_MAGIC.as_slice())
+        .ok_or_else(|| anyhow!("Not a PointZerver config bundle"))?;
+    if rest.len() < ServerConfig::BUNDLE_SALT_LEN + NONCE_LEN {
+        bail!("Config bundle is truncated");
+    }
+    let (salt, rest) = rest.split_at(ServerConfig::BUNDLE_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let plaintext = cipher(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted bundle"))?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the bundle key: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> Payload {
+        let mut files = BTreeMap::new();
+        files.insert(
+            ServerConfig::SETTINGS_FILE.to_string(),
+            r#"{"auth":{"require_token":true}}"#.to_string(),
+        );
+        Payload { files }
+    }
+
+    #[test]
+    fn test_bundle_round_trips() {
+        let sealed = seal(&payload(), "correct horse").unwrap();
+        assert!(sealed.starts_with(ServerConfig::BUNDLE_MAGIC));
+        assert_eq!(open(&sealed, "correct horse").unwrap(), payload());
+    }
+
+    #[test]
+    fn test_wrong_passphrase_rejected() {
+        let sealed = seal(&payload(), "correct horse").unwrap();
+        assert!(open(&sealed, "battery staple").is_err());
+    }
+
+    #[test]
+    fn test_sealed_secrets_round_trip_between_keys() {
+        let source = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let target = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let sealed = |value: &str| secrets::seal_with(&source, value).unwrap();
+        let settings = serde_json::json!({
+            "version": 3,
+            "rendezvous": { "room": sealed("room-1"), "enabled": true },
+            "auth": { "session_pin": sealed("4321"), "require_token": true },
+            "webhooks": { "secret": sealed("hook"), "urls": [] },
+        });
+        let tokens = serde_json::json!({
+            "version": 1,
+            "devices": [{ "device_name": "phone", "token": sealed("abcd"), "paired_at": 1 }],
+        });
+
+        let mut files = BTreeMap::new();
+        for (name, file) in [
+            (ServerConfig::SETTINGS_FILE, &settings),
+            (ServerConfig::TOKENS_FILE, &tokens),
+        ] {
+            let opened = map_secrets(name, &file.to_string(), |value| {
+                secrets::open_with(&source, value)
+            });
+            files.insert(name.to_string(), opened.unwrap());
+        }
+        let bundle = seal(&Payload { files }, "correct horse").unwrap();
+        let imported = open(&bundle, "correct horse").unwrap();
+        assert!(!imported.files.values().any(|file| file.contains("sealed:")));
+        assert!(imported.files[ServerConfig::SETTINGS_FILE].contains("room-1"));
+
+        let resealed = |name: &str| -> serde_json::Value {
+            let contents = map_secrets(name, &imported.files[name], |value| {
+                secrets::seal_with(&target, value)
+            });
+            serde_json::from_str(&contents.unwrap()).unwrap()
+        };
+        let opened = |value: &serde_json::Value| {
+            secrets::open_with(&target, value.as_str().unwrap()).unwrap()
+        };
+        let settings = resealed(ServerConfig::SETTINGS_FILE);
+        assert_eq!(opened(&settings["rendezvous"]["room"]), "room-1");
+        assert_eq!(opened(&settings["auth"]["session_pin"]), "4321");
+        assert_eq!(opened(&settings["webhooks"]["secret"]), "hook");
+        assert_eq!(settings["rendezvous"]["enabled"], true);
+        let tokens = resealed(ServerConfig::TOKENS_FILE);
+        assert_eq!(opened(&tokens["devices"][0]["token"]), "abcd");
+    }
+
+    #[test]
+    fn test_foreign_file_rejected() {
+        assert!(open(b"{\"files\":{}}", "correct horse").is_err());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod pointer_tuning;
+pub mod scroll_calibration;
@@ -0,0 +1,256 @@
+use crate::domain::config::server_config::ServerConfig;
+use crate::domain::config::settings::{PointerTuningSettings, Settings};
+use crate::domain::models::{Command, SensitivityCurve, ServerEvent};
+use crate::features::events::event_bus::EventBus;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Per-client sensitivity curves, suggested from how far each client moves per report
+///
+/// Clients scale touch motion very differently, so one slider can't suit them all. For the
+/// first minute of a paired client's motion the server builds a histogram of move lengths,
+/// then suggests a curve that brings a typical move to a comfortable length and adds
+/// acceleration when fast moves aren't much longer than slow ones. Suggestions are
+/// published as events; with `apply` set they're also saved to the `pointer_tuning` settings.
+pub struct PointerTuning {
+    settings: Mutex<PointerTuningSettings>,
+    settings_path: PathBuf,
+    events: EventBus,
+    watching: Mutex<HashMap<String, Watch>>,
+}
+
+struct Watch {
+    started: Instant,
+    lengths: Histogram,
+    /// Suggested already; not watched again until restart
+    done: bool,
+}
+
+/// Move lengths in power-of-two buckets from 1/16 of a unit up
+#[derive(Default)]
+struct Histogram {
+    counts: [u32; Histogram::BUCKETS],
+    total: u32,
+}
+
+impl Histogram {
+    const BUCKETS: usize = 20;
+    /// The first bucket starts at 2^-OFFSET
+    const OFFSET: i32 = 4;
+
+    fn record(&mut self, length: f64) {
+        let bucket =
+            (length.log2().floor() as i32 + Self::OFFSET).clamp(0, Self::BUCKETS as i32 - 1);
+        self.counts[bucket as usize] += 1;
+        self.total += 1;
+    }
+
+    /// Geometric middle of the bucket holding the `fraction` quantile
+    fn quantile(&self, fraction: f64) -> f64 {
+        let rank = (f64::from(self.total) * fraction).ceil().max(1.0) as u32;
+        let mut seen = 0;
+        let bucket = self
+            .counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(Self::BUCKETS - 1);
+        2f64.powf(bucket as f64 - f64::from(Self::OFFSET) + 0.5)
+    }
+
+    fn suggest(&self) -> SensitivityCurve {
+        const MAX_EXPONENT: f64 = 0.6;
+        let typical = self.quantile(0.5);
+        let spread = self.quantile(0.95) / typical;
+        let exponent = match spread > 1.0 {
+            true => ServerConfig::POINTER_TUNING_TARGET_SPREAD.ln() / spread.ln() - 1.0,
+            false => MAX_EXPONENT,
+        };
+        let round = |value: f64| (value * 100.0).round() / 100.0;
+        SensitivityCurve {
+            gain: round((ServerConfig::POINTER_TUNING_TARGET_MOVE / typical).clamp(0.5, 4.0)),
+            pivot: round(typical),
+            exponent: round(exponent.clamp(0.0, MAX_EXPONENT)),
+        }
+    }
+}
+
+impl PointerTuning {
+    pub fn new(settings: PointerTuningSettings, settings_path: PathBuf, events: EventBus) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            settings_path,
+            events,
+            watching: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `command` is motion this might change, worth finding its client for
+    pub fn tunes(&self, command: &Command) -> bool {
+        let settings = self.lock_settings();
+        matches!(command, Command::MouseMove { .. })
+            && (settings.enabled || !settings.curves.is_empty())
+    }
+
+    /// Applies the client's curve to a move, or watches the move while there isn't one
+    pub fn adjust(&self, command: Command, client_id: &str, device_name: &str) -> Command {
+        self.adjust_at(command, client_id, device_name, Instant::now())
+    }
+
+    fn adjust_at(
+        &self,
+        command: Command,
+        client_id: &str,
+        device_name: &str,
+        now: Instant,
+    ) -> Command {
+        let Command::MouseMove { x, y } = command else {
+            return command;
+        };
+        let settings = self.lock_settings();
+        if let Some(curve) = settings.curves.get(client_id) {
+            let (x, y) = curve.apply(x, y);
+            return Command::MouseMove { x, y };
+        }
+        if !settings.enabled || (x == 0.0 && y == 0.0) {
+            return command;
+        }
+        drop(settings);
+        if let Some(curve) = self.watch(client_id, x.hypot(y), now) {
+            self.suggest(client_id, device_name, curve);
+        }
+        command
+    }
+
+    /// Records a move length, returning a suggestion once the client has been watched long enough
+    fn watch(&self, client_id: &str, length: f64, now: Instant) -> Option<SensitivityCurve> {
+        let mut watching = self.lock_watching();
+        let watch = watching
+            .entry(client_id.to_string())
+            .or_insert_with(|| Watch {
+                started: now,
+                lengths: Histogram::default(),
+                done: false,
+            });
+        if watch.done {
+            return None;
+        }
+        watch.lengths.record(length);
+        let window = Duration::from_secs(ServerConfig::POINTER_TUNING_WINDOW_SECS);
+        if now.duration_since(watch.started) < window
+            || watch.lengths.total < ServerConfig::POINTER_TUNING_MIN_MOVES
+        {
+            return None;
+        }
+        watch.done = true;
+        Some(watch.lengths.suggest())
+    }
+
+    fn suggest(&self, client_id: &str, device_name: &str, curve: SensitivityCurve) {
+        let mut settings = self.lock_settings();
+        log::info!(
+            "Suggested pointer curve for {}: gain {} at {}, exponent {}",
+            device_name,
+            curve.gain,
+            curve.pivot,
+            curve.exponent
+        );
+        if settings.apply {
+            settings.curves.insert(client_id.to_string(), curve);
+            if let Err(e) = Settings::update_file(&self.settings_path, "pointer_tuning", &*settings)
+            {
+                log::warn!("Failed to save {}: {}", self.settings_path.display(), e);
+            }
+        }
+        self.events.publish(ServerEvent::PointerTuningSuggested {
+            client_id: client_id.to_string(),
+            device_name: device_name.to_string(),
+            curve,
+            applied: settings.apply,
+        });
+    }
+
+    fn lock_settings(&self) -> MutexGuard<'_, PointerTuningSettings> {
+        self.settings.lock().expect("Pointer tuning mutex poisoned")
+    }
+
+    fn lock_watching(&self) -> MutexGuard<'_, HashMap<String, Watch>> {
+        self.watching.lock().expect("Pointer tuning mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_even_moves_get_gain_and_acceleration() {
+        let mut lengths = Histogram::default();
+        for length in [0.8, 1.0, 1.2, 1.5, 1.9] {
+            for _ in 0..100 {
+                lengths.record(length);
+            }
+        }
+        let curve = lengths.suggest();
+        assert_eq!(curve.pivot, 1.41);
+        assert_eq!(curve.gain, 4.0);
+        assert_eq!(curve.exponent, 0.6);
+
+        let mut flicks = Histogram::default();
+        for _ in 0..90 {
+            flicks.record(6.0);
+        }
+        for _ in 0..10 {
+            flicks.record(300.0);
+        }
+        let curve = flicks.suggest();
+        assert_eq!(curve.gain, 1.06);
+        assert_eq!(curve.exponent, 0.0);
+    }
+
+    #[test]
+    fn test_applies_after_the_window_and_saves_the_curve() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let events = EventBus::new();
+        let mut received = events.subscribe();
+        let tuning = PointerTuning::new(
+            PointerTuningSettings {
+                enabled: true,
+                apply: true,
+                ..PointerTuningSettings::default()
+            },
+            path.clone(),
+            events,
+        );
+        let start = Instant::now();
+        let window = Duration::from_secs(ServerConfig::POINTER_TUNING_WINDOW_SECS);
+        let moves = ServerConfig::POINTER_TUNING_MIN_MOVES;
+        for n in 0..moves {
+            let at = start + window * n / (moves - 1);
+            let command = Command::MouseMove { x: 1.0, y: 0.0 };
+            let moved = tuning.adjust_at(command, "c1", "Pixel", at);
+            assert!(matches!(moved, Command::MouseMove { x, .. } if x == 1.0));
+        }
+
+        let Ok(ServerEvent::PointerTuningSuggested { curve, applied, .. }) = received.try_recv()
+        else {
+            panic!("no suggestion published");
+        };
+        assert!(applied);
+        let moved = tuning.adjust(Command::MouseMove { x: 1.0, y: 0.0 }, "c1", "Pixel");
+        let expected = curve.apply(1.0, 0.0).0;
+        assert!(matches!(moved, Command::MouseMove { x, .. } if x == expected && x > 1.0));
+        assert!(matches!(
+            tuning.adjust(Command::MouseMove { x: 1.0, y: 0.0 }, "c2", "Tablet"),
+            Command::MouseMove { x, .. } if x == 1.0
+        ));
+
+        let saved = Settings::load_or_default(&path);
+        assert_eq!(saved.pointer_tuning.curves.get("c1"), Some(&curve));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::domain::config::server_config::ServerConfig;
+use crate::domain::config::settings::{ScrollSettings, Settings};
+use crate::domain::models::{Command, ScrollAxis};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+/// Per-axis scroll inversion, learned by asking the user which way a probe scroll went
+///
+/// A positive delta should scroll down on `y` and right on `x`. Hosts, drivers and apps
+/// disagree on that, most often horizontally, so an axis the user says went the wrong way
+/// is flipped for every client and saved to the `scroll` settings.
+pub struct ScrollCalibration {
+    inverted: Mutex<ScrollSettings>,
+    settings_path: PathBuf,
+}
+
+impl ScrollCalibration {
+    pub fn new(settings: ScrollSettings, settings_path: PathBuf) -> Self {
+        Self {
+            inverted: Mutex::new(settings),
+            settings_path,
+        }
+    }
+
+    /// Flips scroll deltas on the inverted axes
+    pub fn apply(&self, command: Command) -> Command {
+        let Command::MouseScroll { delta_x, delta_y } = command else {
+            return command;
+        };
+        let inverted = self.lock_inverted();
+        Command::MouseScroll {
+            delta_x: if inverted.invert_x { -delta_x } else { delta_x },
+            delta_y: if inverted.invert_y { -delta_y } else { delta_y },
+        }
+    }
+
+    /// The scroll `CalibrateScroll` injects, already corrected, and the way it should go
+    ///
+    /// The input handler reverses every scroll under natural scrolling, so the probe starts
+    /// out reversed to come out the way it says.
+    pub fn probe(&self, axis: ScrollAxis) -> (Command, &'static str) {
+        let lines = match self.lock_inverted().natural {
+            true => -ServerConfig::SCROLL_PROBE_LINES,
+            false => ServerConfig::SCROLL_PROBE_LINES,
+        };
+        let (scroll, expected) = match axis {
+            ScrollAxis::X => ((lines, 0.0), "right"),
+            ScrollAxis::Y => ((0.0, lines), "down"),
+        };
+        let command = Command::MouseScroll {
+            delta_x: scroll.0,
+            delta_y: scroll.1,
+        };
+        (self.apply(command), expected)
+    }
+
+    /// Records the user's answer to a probe, returning whether `axis` is now inverted
+    ///
+    /// A wrong direction flips the axis and saves it. If the file can't be written, the
+    /// flip still holds until restart.
+    pub fn confirm(&self, axis: ScrollAxis, correct: bool) -> bool {
+        let mut inverted = self.lock_inverted();
+        let flag = match axis {
+            ScrollAxis::X => &mut inverted.invert_x,
+            ScrollAxis::Y => &mut inverted.invert_y,
+        };
+        if correct {
+            return *flag;
+        }
+        *flag = !*flag;
+        let now_inverted = *flag;
+        log::info!(
+            "Scroll axis {:?} is now {}",
+            axis,
+            if now_inverted {
+                "inverted"
+            } else {
+                "not inverted"
+            }
+        );
+        if let Err(e) = Settings::update_file(&self.settings_path, "scroll", &*inverted) {
+            log::warn!("Failed to save {}: {}", self.settings_path.display(), e);
+        }
+        now_inverted
+    }
+
+    fn lock_inverted(&self) -> MutexGuard<'_, ScrollSettings> {
+        self.inverted
+            .lock()
+            .expect("Scroll calibration mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_follows_inversion() {
+        let dir = tempfile::tempdir().unwrap();
+        let calibration = ScrollCalibration::new(
+            ScrollSettings {
+                invert_x: true,
+                ..ScrollSettings::default()
+            },
+            dir.path().join("settings.json"),
+        );
+        let (command, expected) = calibration.probe(ScrollAxis::X);
+        assert_eq!(expected, "right");
+        assert!(matches!(
+            command,
+            Command::MouseScroll { delta_x, delta_y } if delta_x < 0.0 && delta_y == 0.0
+        ));
+        let (command, _) = calibration.probe(ScrollAxis::Y);
+        assert!(matches!(
+            command,
+            Command::MouseScroll { delta_x, delta_y } if delta_x == 0.0 && delta_y > 0.0
+        ));
+
+        let natural = ScrollCalibration::new(
+            ScrollSettings {
+                natural: true,
+                ..ScrollSettings::default()
+            },
+            dir.path().join("settings.json"),
+        );
+        let (command, _) = natural.probe(ScrollAxis::Y);
+        assert!(matches!(
+            command,
+            Command::MouseScroll { delta_y, .. } if delta_y < 0.0
+        ));
+    }
+
+    #[test]
+    fn test_wrong_direction_inverts_and_saves_alongside_other_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(&path, r#"{ "network": { "tcp": true } }"#).unwrap();
+        let calibration = ScrollCalibration::new(ScrollSettings::default(), path.clone());
+
+        assert!(!calibration.confirm(ScrollAxis::Y, true));
+        assert!(calibration.confirm(ScrollAxis::X, false));
+        let scrolled = calibration.apply(Command::MouseScroll {
+            delta_x: 2.0,
+            delta_y: 1.0,
+        });
+        assert!(matches!(
+            scrolled,
+            Command::MouseScroll { delta_x, delta_y } if delta_x == -2.0 && delta_y == 1.0
+        ));
+
+        let saved = Settings::load_or_default(&path);
+        assert!(saved.scroll.invert_x);
+        assert!(!saved.scroll.invert_y);
+        assert!(saved.network.tcp);
+    }
+}
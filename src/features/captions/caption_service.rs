@@ -0,0 +1,73 @@
+use crate::domain::config::settings::CaptionSettings;
+use crate::domain::models::ServerEvent;
+use crate::features::events::event_bus::EventBus;
+use crate::input::a11y;
+use std::time::Duration;
+
+/// Publishes the accessible name under the cursor whenever it changes, so a client can speak it
+pub struct CaptionService {
+    settings: CaptionSettings,
+    events: EventBus,
+}
+
+impl CaptionService {
+    pub fn new(settings: CaptionSettings, events: EventBus) -> Self {
+        Self { settings, events }
+    }
+
+    pub async fn run(&self) {
+        if !self.settings.enabled {
+            return;
+        }
+        if !a11y::SUPPORTED {
+            log::warn!("Hover captions are not supported on this platform");
+            return;
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_millis(self.settings.poll_ms));
+        let mut last = None;
+        loop {
+            interval.tick().await;
+            // Accessibility queries cost a round trip into the focused app; skip them unless someone listens
+            if !self.events.has_subscribers() {
+                last = None;
+                continue;
+            }
+            let Ok(name) = tokio::task::spawn_blocking(a11y::hovered_element_name).await else {
+                continue;
+            };
+            if let Some(text) = changed(&mut last, name) {
+                self.events.publish(ServerEvent::HoverCaption { text });
+            }
+        }
+    }
+}
+
+/// Returns the new caption when it differs from the last one published
+fn changed(last: &mut Option<String>, current: Option<String>) -> Option<String> {
+    if current == *last {
+        return None;
+    }
+    *last = current.clone();
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_changes_are_published() {
+        let mut last = None;
+        assert_eq!(
+            changed(&mut last, Some("Save".to_string())),
+            Some("Save".to_string())
+        );
+        assert_eq!(changed(&mut last, Some("Save".to_string())), None);
+        assert_eq!(changed(&mut last, None), None);
+        assert_eq!(
+            changed(&mut last, Some("Save".to_string())),
+            Some("Save".to_string())
+        );
+    }
+}
@@ -0,0 +1 @@
+pub mod caption_service;
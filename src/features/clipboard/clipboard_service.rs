@@ -0,0 +1,72 @@
+use crate::domain::config::settings::ClipboardSyncSettings;
+use crate::features::clipboard::peer_sync::PeerSync;
+#[cfg(windows)]
+use crate::features::service::agent_link;
+use anyhow::Result;
+use std::sync::Mutex;
+
+/// Applies clipboard updates to the host and relays them to peers when sync is enabled
+pub struct ClipboardService {
+    clipboard: Mutex<Option<arboard::Clipboard>>,
+    peer_sync: Option<PeerSync>,
+}
+
+impl ClipboardService {
+    pub async fn new(settings: &ClipboardSyncSettings) -> Result<Self> {
+        let peer_sync = if settings.enabled {
+            let instance_id = format!("{}-{}", crate::utils::get_hostname(), std::process::id());
+            log::info!(
+                "Clipboard sync enabled with {} peer(s) as {}",
+                settings.peers.len(),
+                instance_id
+            );
+            Some(PeerSync::new(settings, instance_id).await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            clipboard: Mutex::new(None),
+            peer_sync,
+        })
+    }
+
+    /// Handles a clipboard update from a client (`origin` is None) or a peer
+    pub async fn handle_set(&self, text: String, origin: Option<String>) -> Result<()> {
+        let Some(peer_sync) = &self.peer_sync else {
+            if origin.is_some() {
+                log::debug!("Ignoring peer clipboard update: sync disabled");
+                return Ok(());
+            }
+            return self.set_local(&text).await;
+        };
+
+        if !peer_sync.accept(&text, origin.as_deref()) {
+            return Ok(());
+        }
+
+        self.set_local(&text).await?;
+        peer_sync.propagate(&text, origin.as_deref()).await;
+        log::debug!(
+            "Clipboard propagated (origin: {})",
+            origin.as_deref().unwrap_or(peer_sync.instance_id())
+        );
+        Ok(())
+    }
+
+    /// A Windows service's clipboard is session 0's, so its agent sets the user's instead
+    async fn set_local(&self, text: &str) -> Result<()> {
+        #[cfg(windows)]
+        if let Some(agent) = agent_link::installed() {
+            return agent.set_clipboard(text).await;
+        }
+        let mut clipboard = self.clipboard.lock().expect("Clipboard mutex poisoned");
+        if clipboard.is_none() {
+            *clipboard = Some(arboard::Clipboard::new()?);
+        }
+        if let Some(clipboard) = clipboard.as_mut() {
+            clipboard.set_text(text)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+pub mod clipboard_service;
+pub mod peer_sync;
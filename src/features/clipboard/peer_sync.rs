@@ -0,0 +1,132 @@
+use crate::domain::config::settings::ClipboardSyncSettings;
+use crate::domain::config::ServerConfig;
+use crate::domain::models::Command;
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Forwards clipboard updates to linked pointzerver peers
+pub struct PeerSync {
+    socket: UdpSocket,
+    peers: Vec<SocketAddr>,
+    max_bytes: usize,
+    instance_id: String,
+    recent: Mutex<RecentClips>,
+}
+
+impl PeerSync {
+    pub async fn new(settings: &ClipboardSyncSettings, instance_id: String) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        Ok(Self {
+            socket,
+            peers: settings.peers.clone(),
+            max_bytes: settings.max_bytes,
+            instance_id,
+            recent: Mutex::new(RecentClips::new(Duration::from_millis(
+                ServerConfig::CLIPBOARD_SYNC_DEDUP_WINDOW_MS,
+            ))),
+        })
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Returns false for payloads that are oversized, our own, or already seen recently
+    pub fn accept(&self, text: &str, origin: Option<&str>) -> bool {
+        if text.len() > self.max_bytes {
+            log::warn!(
+                "Clipboard payload of {} bytes exceeds sync limit of {}",
+                text.len(),
+                self.max_bytes
+            );
+            return false;
+        }
+        if origin == Some(self.instance_id.as_str()) {
+            return false;
+        }
+        self.recent
+            .lock()
+            .expect("Recent clips mutex poisoned")
+            .insert(text, Instant::now())
+    }
+
+    /// Sends the clipboard text to every configured peer, preserving the original origin
+    pub async fn propagate(&self, text: &str, origin: Option<&str>) {
+        let command = Command::ClipboardSet {
+            text: text.to_string(),
+            origin: Some(origin.unwrap_or(&self.instance_id).to_string()),
+        };
+        let Ok(payload) = serde_json::to_vec(&command) else {
+            return;
+        };
+
+        for peer in &self.peers {
+            if let Err(e) = self.socket.send_to(&payload, peer).await {
+                log::warn!("Clipboard sync to {} failed: {}", peer, e);
+            }
+        }
+    }
+}
+
+/// Short-lived record of clipboard contents already applied, used to break A→B→A loops
+struct RecentClips {
+    window: Duration,
+    entries: VecDeque<(u64, Instant)>,
+}
+
+impl RecentClips {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Records the text and returns true if it was not seen within the window
+    fn insert(&mut self, text: &str, now: Instant) -> bool {
+        while let Some((_, seen)) = self.entries.front() {
+            if now.duration_since(*seen) <= self.window {
+                break;
+            }
+            self.entries.pop_front();
+        }
+
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.entries.iter().any(|(h, _)| *h == hash) {
+            return false;
+        }
+        self.entries.push_back((hash, now));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_clips_rejects_repeat_within_window() {
+        let mut recent = RecentClips::new(Duration::from_millis(100));
+        let now = Instant::now();
+        assert!(recent.insert("hello", now));
+        assert!(!recent.insert("hello", now + Duration::from_millis(50)));
+        assert!(recent.insert("world", now + Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_recent_clips_accepts_repeat_after_window() {
+        let mut recent = RecentClips::new(Duration::from_millis(100));
+        let now = Instant::now();
+        assert!(recent.insert("hello", now));
+        assert!(recent.insert("hello", now + Duration::from_millis(150)));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::domain::models::{CommandDatagram, WireFormat};
+use crate::features::command::protocol_aliases::ProtocolAliases;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+
+/// How a client's messages reach the server, which limits the formats it can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    /// One message per line, so only text formats survive the framing
+    Tcp,
+}
+
+impl Transport {
+    pub fn carries(self, format: WireFormat) -> bool {
+        match self {
+            Transport::Udp => true,
+            Transport::Tcp => format.is_text(),
+        }
+    }
+}
+
+/// Wire format of each client, the one place commands are decoded and replies encoded
+///
+/// Without a `SelectFormat` each datagram's format is detected from its first byte. Once a
+/// client agrees on a binary format, its binary datagrams are read in that format, which
+/// tells a CBOR batch from a MessagePack one.
+#[derive(Default)]
+pub struct CodecRegistry {
+    clients: Mutex<HashMap<SocketAddr, ClientCodec>>,
+    aliases: ProtocolAliases,
+}
+
+#[derive(Default, Clone, Copy)]
+struct ClientCodec {
+    /// Format agreed with `SelectFormat`
+    selected: Option<WireFormat>,
+    /// Format of the client's last well-formed message, so replies come back in kind
+    last: WireFormat,
+}
+
+impl CodecRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_aliases(aliases: ProtocolAliases) -> Self {
+        Self {
+            clients: Mutex::default(),
+            aliases,
+        }
+    }
+
+    /// Decodes a datagram or TCP line from `addr`, returning the format it was read in
+    ///
+    /// A message that doesn't decode is read again with old command and field names
+    /// renamed, so current clients don't pay for the aliases.
+    pub fn decode_command(
+        &self,
+        bytes: &[u8],
+        addr: SocketAddr,
+    ) -> Result<(CommandDatagram, WireFormat)> {
+        let detected = WireFormat::detect(bytes);
+        let selected = self
+            .lock_clients()
+            .get(&addr)
+            .and_then(|codec| codec.selected);
+        let format = match selected {
+            Some(selected) if !selected.is_text() && !detected.is_text() => selected,
+            _ => detected,
+        };
+        match format.decode(bytes) {
+            Ok(datagram) => Ok((datagram, format)),
+            Err(e) => match self.decode_aliased(bytes, format) {
+                Some(datagram) => Ok((datagram, format)),
+                None => Err(e),
+            },
+        }
+    }
+
+    fn decode_aliased(&self, bytes: &[u8], format: WireFormat) -> Option<CommandDatagram> {
+        let mut message: serde_json::Value = format.decode(bytes).ok()?;
+        if !self.aliases.rewrite(&mut message) {
+            return None;
+        }
+        serde_json::from_value(message).ok()
+    }
+
+    /// Records the format `addr` last got through with, for replies
+    pub fn remember(&self, addr: SocketAddr, format: WireFormat) {
+        self.lock_clients().entry(addr).or_default().last = format;
+    }
+
+    /// Encodes a reply to `addr` in the format it last used
+    pub fn encode_reply<T: Serialize>(&self, value: &T, addr: SocketAddr) -> Result<Vec<u8>> {
+        let format = self
+            .lock_clients()
+            .get(&addr)
+            .map(|codec| codec.last)
+            .unwrap_or_default();
+        format.encode(value)
+    }
+
+    /// Settles on the first of `offered` that the server speaks and `transport` carries
+    ///
+    /// Falls back to JSON, which every client and transport supports.
+    pub fn select(
+        &self,
+        addr: SocketAddr,
+        transport: Transport,
+        offered: &[WireFormat],
+    ) -> WireFormat {
+        let format = offered
+            .iter()
+            .copied()
+            .find(|format| WireFormat::ALL.contains(format) && transport.carries(*format))
+            .unwrap_or_default();
+        *self.lock_clients().entry(addr).or_default() = ClientCodec {
+            selected: Some(format),
+            last: format,
+        };
+        format
+    }
+
+    fn lock_clients(&self) -> MutexGuard<'_, HashMap<SocketAddr, ClientCodec>> {
+        self.clients.lock().expect("Codec registry mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{Command, CommandEnvelope};
+
+    fn addr() -> SocketAddr {
+        "192.168.1.5:40000".parse().unwrap()
+    }
+
+    fn batch() -> Vec<CommandEnvelope> {
+        [
+            r#"{"type":"MouseMove","x":1,"y":2}"#,
+            r#"{"type":"SelectAll"}"#,
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_selected_cbor_reads_batches_detection_would_take_for_message_pack() {
+        let registry = CodecRegistry::new();
+        let bytes = WireFormat::Cbor.encode(&batch()).unwrap();
+        assert_eq!(WireFormat::detect(&bytes), WireFormat::MessagePack);
+        assert!(registry.decode_command(&bytes, addr()).is_err());
+
+        let format = registry.select(addr(), Transport::Udp, &[WireFormat::Cbor]);
+        assert_eq!(format, WireFormat::Cbor);
+        let (datagram, format) = registry.decode_command(&bytes, addr()).unwrap();
+        assert_eq!(format, WireFormat::Cbor);
+        assert_eq!(datagram.len(), 2);
+
+        // JSON still gets through after a binary format was selected
+        let (datagram, format) = registry
+            .decode_command(br#"{"type":"SelectAll"}"#, addr())
+            .unwrap();
+        assert_eq!(format, WireFormat::Json);
+        assert!(matches!(
+            datagram.into_envelopes()[0].command,
+            Command::SelectAll
+        ));
+    }
+
+    #[test]
+    fn test_old_names_decode_in_every_format() {
+        let registry = CodecRegistry::new();
+        let old = serde_json::json!({"type": "Scroll", "dx": 1.5, "dy": -2.0});
+        for format in WireFormat::ALL {
+            let bytes = format.encode(&old).unwrap();
+            let (datagram, decoded_in) = registry.decode_command(&bytes, addr()).unwrap();
+            assert_eq!(decoded_in, format);
+            assert!(matches!(
+                datagram.into_envelopes()[0].command,
+                Command::MouseScroll { delta_x, delta_y } if delta_x == 1.5 && delta_y == -2.0
+            ));
+        }
+        assert!(registry
+            .decode_command(br#"{"type":"Teleport"}"#, addr())
+            .is_err());
+    }
+
+    #[test]
+    fn test_tcp_only_selects_text_formats() {
+        let registry = CodecRegistry::new();
+        let offered = [WireFormat::MessagePack, WireFormat::Cbor];
+        assert_eq!(
+            registry.select(addr(), Transport::Tcp, &offered),
+            WireFormat::Json
+        );
+        assert_eq!(
+            registry.select(addr(), Transport::Udp, &offered),
+            WireFormat::MessagePack
+        );
+        let reply = registry.encode_reply(&batch(), addr()).unwrap();
+        assert_eq!(WireFormat::detect(&reply), WireFormat::MessagePack);
+    }
+}
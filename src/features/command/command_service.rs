@@ -1,38 +1,267 @@
-use crate::domain::config::ServerConfig;
-use crate::domain::models::Command;
-use crate::input::InputHandler;
-use anyhow::Result;
+use crate::domain::config::settings::NetworkSettings;
+use crate::domain::config::{ServerConfig, Settings};
+use crate::domain::models::{
+    Command, CommandDatagram, CommandEnvelope, HandshakeDatagram, MacroStep, PointerAcceleration,
+    PowerState, Reply, Scope, ServerEvent,
+};
+use crate::features::audit::audit_log::AuditLog;
+use crate::features::auth::command_signing::{CommandSigning, Opened, SignatureError};
+use crate::features::auth::noise_channel::{ChannelError, NoiseChannels};
+use crate::features::auth::pairing_service::PairingService;
+use crate::features::auth::token_store::{DeviceInfo, TokenStore};
+use crate::features::calibration::pointer_tuning::PointerTuning;
+use crate::features::calibration::scroll_calibration::ScrollCalibration;
+use crate::features::clipboard::clipboard_service::ClipboardService;
+use crate::features::command::codec_registry::{CodecRegistry, Transport};
+use crate::features::command::confirmations::Confirmations;
+use crate::features::command::dispatch_queue::DispatchQueue;
+use crate::features::command::held_input::HeldInput;
+use crate::features::command::input_group::InputGroup;
+use crate::features::command::policy::{CommandPolicy, Denial};
+use crate::features::command::protocol_aliases::ProtocolAliases;
+use crate::features::command::rate_limiter::RateLimiter;
+use crate::features::command::reorder_buffer::{Arrival, ReorderBuffer};
+use crate::features::command::tcp_transport::TcpTransport;
+use crate::features::command::udp_socket;
+use crate::features::command::validation;
+use crate::features::cursor::cursor_parking::CursorParking;
+use crate::features::cursor::cursor_service::CursorService;
+use crate::features::events::event_bus::EventBus;
+use crate::features::game_mode::game_mode_service::GameModeService;
+use crate::features::hot_corners::hot_corner_service::HotCornerService;
+use crate::features::macros::macro_recorder::MacroRecorder;
+use crate::features::presence::kill_switch::KillSwitch;
+use crate::features::presence::presence_monitor::PresenceMonitor;
+use crate::features::profile::profile_service::ProfileService;
+#[cfg(feature = "rendezvous")]
+use crate::features::rendezvous::{
+    protocol::RendezvousMessage, rendezvous_service::RendezvousService,
+};
+#[cfg(windows)]
+use crate::features::service::agent_link::{self, AgentLink};
+use crate::features::session::client_registry::ClientRegistry;
+use crate::features::session::session_registry::{SessionRegistry, Touch};
+use crate::features::smoothing::smoothing_service::SmoothingService;
+use crate::features::stats::stats_registry::StatsRegistry;
+use crate::input::plan::{self, InputStep};
+use crate::input::{display, pointer, power, InputHandler};
+use crate::utils;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, Notify};
+
+/// Settings and shared services the command pipeline reads from and reports to
+pub struct CommandContext {
+    pub settings: Arc<Settings>,
+    pub pairing: Arc<PairingService>,
+    pub tokens: Arc<TokenStore>,
+    pub stats: Arc<StatsRegistry>,
+    pub presence: Arc<PresenceMonitor>,
+    pub kill_switch: Arc<KillSwitch>,
+    pub audit: Arc<AuditLog>,
+    pub calibration: Arc<ScrollCalibration>,
+    pub tuning: Arc<PointerTuning>,
+    pub macros: Arc<MacroRecorder>,
+    pub profiles: Arc<ProfileService>,
+    pub queue: Arc<DispatchQueue>,
+    pub sessions: Arc<SessionRegistry>,
+    pub clients: Arc<ClientRegistry>,
+    pub events: EventBus,
+}
+
+/// Server-issued session whose replay window a sealed datagram's `seq`s are checked in
+#[derive(Clone, Copy)]
+enum SealedSession {
+    Signing(u64),
+    Noise(u64),
+}
 
 /// Service that receives and processes commands from clients
 pub struct CommandService {
-    socket: UdpSocket,
+    socket: Arc<UdpSocket>,
+    tcp: Option<TcpTransport>,
+    /// Client subnets allowed to send commands
+    network: NetworkSettings,
     input_handler: InputHandler,
+    clipboard_service: ClipboardService,
+    pairing_service: Arc<PairingService>,
+    signing: CommandSigning,
+    noise: NoiseChannels,
+    policy: Arc<CommandPolicy>,
+    stats: Arc<StatsRegistry>,
+    presence: Arc<PresenceMonitor>,
+    kill_switch: Arc<KillSwitch>,
+    audit: Arc<AuditLog>,
+    calibration: Arc<ScrollCalibration>,
+    tuning: Arc<PointerTuning>,
+    acceleration: PointerAcceleration,
+    macros: Arc<MacroRecorder>,
+    profiles: Arc<ProfileService>,
+    events: EventBus,
+    queue: Arc<DispatchQueue>,
+    /// Sequenced commands waiting for a late predecessor, with their `sent_at_ms`
+    reorder: ReorderBuffer<Option<(Command, Option<u64>)>>,
+    reorder_held: Notify,
+    sessions: Arc<SessionRegistry>,
+    clients: Arc<ClientRegistry>,
+    groups: Mutex<HashMap<SocketAddr, InputGroup>>,
+    codecs: CodecRegistry,
+    /// Noise session each sender last used, so replies go back sealed
+    channels: Mutex<HashMap<SocketAddr, u64>>,
+    cursor: Arc<CursorService>,
+    parking: CursorParking,
+    hot_corners: Arc<HotCornerService>,
+    smoothing: SmoothingService,
+    game_mode: GameModeService,
+    last_denial: Mutex<Option<(SocketAddr, Denial)>>,
+    confirmations: Mutex<Confirmations>,
+    rate_limiter: Mutex<RateLimiter>,
+    #[cfg(feature = "rendezvous")]
+    rendezvous: Option<Arc<RendezvousService>>,
 }
 
 impl CommandService {
     /// Creates a new CommandService bound to the command port
-    pub async fn new(input_handler: InputHandler) -> Result<Self> {
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", ServerConfig::COMMAND_PORT)).await?;
-        socket.set_broadcast(true)?;
+    pub async fn new(
+        input_handler: InputHandler,
+        clipboard_service: ClipboardService,
+        policy: Arc<CommandPolicy>,
+        context: CommandContext,
+    ) -> Result<Self> {
+        let CommandContext {
+            settings,
+            pairing,
+            tokens,
+            stats,
+            presence,
+            kill_switch,
+            audit,
+            calibration,
+            tuning,
+            macros,
+            profiles,
+            queue,
+            sessions,
+            clients,
+            events,
+        } = context;
+        let network = &settings.network;
+        let socket = udp_socket::bind(
+            SocketAddr::from(([0, 0, 0, 0], ServerConfig::COMMAND_PORT)),
+            network,
+            network.reuse_address,
+        )
+        .with_context(|| {
+            format!(
+                "Failed to bind command port {}; another program may be using it",
+                ServerConfig::COMMAND_PORT
+            )
+        })?;
+        let tcp = match network.tcp {
+            true => Some(TcpTransport::bind().await?),
+            false => None,
+        };
         Ok(Self {
-            socket,
+            socket: Arc::new(socket),
+            tcp,
+            network: network.clone(),
             input_handler,
+            clipboard_service,
+            pairing_service: pairing,
+            signing: CommandSigning::new(tokens.clone()),
+            noise: NoiseChannels::new(tokens),
+            policy,
+            stats,
+            presence,
+            kill_switch,
+            audit,
+            calibration,
+            tuning,
+            acceleration: settings.pointer_acceleration.clone(),
+            macros,
+            profiles,
+            events,
+            queue,
+            reorder: ReorderBuffer::new(settings.reorder.clone()),
+            reorder_held: Notify::new(),
+            sessions,
+            clients,
+            groups: Mutex::new(HashMap::new()),
+            codecs: CodecRegistry::with_aliases(ProtocolAliases::new(&settings.protocol)),
+            channels: Mutex::new(HashMap::new()),
+            cursor: Arc::new(CursorService::new()),
+            parking: CursorParking::new(settings.cursor_parking.clone()),
+            hot_corners: Arc::new(HotCornerService::new(settings.hot_corners.clone())),
+            smoothing: SmoothingService::new(settings.smoothing, settings.jitter.clone()),
+            game_mode: GameModeService::new(settings.game_mode.clone()),
+            last_denial: Mutex::new(None),
+            confirmations: Mutex::new(Confirmations::new()),
+            rate_limiter: Mutex::new(RateLimiter::new(settings.rate_limit.clone())),
+            #[cfg(feature = "rendezvous")]
+            rendezvous: None,
         })
     }
 
-    /// Runs the command loop, processing incoming commands indefinitely
+    /// The command socket, shared with rendezvous so NAT mappings point at it
+    #[cfg(feature = "rendezvous")]
+    pub fn socket(&self) -> Arc<UdpSocket> {
+        self.socket.clone()
+    }
+
+    /// Routes relay and hole-punch datagrams on the command socket to rendezvous
+    #[cfg(feature = "rendezvous")]
+    pub fn set_rendezvous(&mut self, rendezvous: Arc<RendezvousService>) {
+        self.rendezvous = Some(rendezvous);
+    }
+
+    /// Cursor size overrides, restored when their client goes quiet
+    pub fn cursor(&self) -> Arc<CursorService> {
+        self.cursor.clone()
+    }
+
+    /// Corner actions driven by where injected moves leave the pointer
+    pub fn hot_corners(&self) -> Arc<HotCornerService> {
+        self.hot_corners.clone()
+    }
+
+    /// Runs the receive loops and the dispatch worker, processing commands indefinitely
     pub async fn run(&self) -> Result<()> {
+        tokio::join!(
+            self.receive(),
+            self.receive_tcp(),
+            self.process_queue(),
+            self.release_reordered(),
+            self.park_when_idle(),
+            self.expire_silent_clients(),
+            self.probe_quiet_clients(),
+            self.release_on_kill_switch(),
+            self.deliver_pairing_decisions()
+        );
+        Ok(())
+    }
+
+    async fn receive(&self) {
         let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
 
         loop {
             match self.socket.recv_from(&mut buf).await {
-                Ok((size, _addr)) => {
-                    if let Ok(command) = serde_json::from_slice::<Command>(&buf[..size]) {
-                        if let Err(e) = self.input_handler.handle_command(command).await {
-                            log::error!("Command error: {}", e);
+                Ok((size, addr)) => {
+                    #[cfg(feature = "rendezvous")]
+                    if let Some(rendezvous) = &self.rendezvous {
+                        if let Ok(message) =
+                            serde_json::from_slice::<RendezvousMessage>(&buf[..size])
+                        {
+                            rendezvous.handle(message, addr).await;
+                            continue;
                         }
                     }
+                    if self.admits(addr) {
+                        self.handle_message(&buf[..size], addr).await;
+                    }
                 }
                 Err(e) => {
                     log::error!("Command receive error: {}", e);
@@ -40,4 +269,1013 @@ impl CommandService {
             }
         }
     }
+
+    /// Feeds commands from TCP clients into the same path as datagrams
+    async fn receive_tcp(&self) {
+        let Some(tcp) = &self.tcp else {
+            return;
+        };
+        log::info!(
+            "Accepting commands over TCP on port {}",
+            ServerConfig::COMMAND_TCP_PORT
+        );
+        let (inbox, mut messages) =
+            mpsc::channel::<(Vec<u8>, SocketAddr)>(ServerConfig::DISPATCH_QUEUE_CAPACITY);
+        let handle = async {
+            while let Some((message, addr)) = messages.recv().await {
+                self.handle_message(&message, addr).await;
+            }
+        };
+        tokio::join!(tcp.run(inbox, |addr| self.admits(addr)), handle);
+    }
+
+    /// Checks `addr` against the network allow and deny lists, counting those it keeps out
+    fn admits(&self, addr: SocketAddr) -> bool {
+        if self.network.admits(addr.ip()) {
+            return true;
+        }
+        log::debug!("Ignoring {}; not an allowed client subnet", addr);
+        self.stats.record_blocked();
+        false
+    }
+
+    /// Charges `addr` for `commands` commands, counting and dropping the datagram when it's
+    /// out of tokens
+    ///
+    /// No reply goes back; answering a flood would only add to it.
+    fn within_rate(&self, addr: SocketAddr, commands: usize) -> bool {
+        let ip = addr.ip().to_canonical();
+        let admitted = self
+            .rate_limiter
+            .lock()
+            .expect("Rate limiter mutex poisoned")
+            .admit(ip, commands, Instant::now());
+        if !admitted {
+            log::debug!("Throttled {}; over its command rate", addr);
+            self.stats.record_throttled();
+        }
+        admitted
+    }
+
+    async fn handle_message(&self, message: &[u8], addr: SocketAddr) {
+        let (datagram, format) = match self.codecs.decode_command(message, addr) {
+            Ok((datagram, format))
+                if !datagram.is_empty() && datagram.len() <= ServerConfig::MAX_BATCH_COMMANDS =>
+            {
+                (datagram, format)
+            }
+            _ => {
+                self.stats.record_malformed();
+                return;
+            }
+        };
+        // Charged before any MAC or decryption, so a flood doesn't cost crypto work either
+        if !self.within_rate(addr, datagram.len()) {
+            return;
+        }
+        let sealed = matches!(
+            datagram,
+            CommandDatagram::Signed(_) | CommandDatagram::Encrypted(_)
+        );
+        let (envelopes, session) = match datagram {
+            CommandDatagram::Handshake(hello) => {
+                self.codecs.remember(addr, format);
+                return self.accept_handshake(hello, addr).await;
+            }
+            CommandDatagram::Signed(signed) => match self.signing.open(signed) {
+                Ok(Opened::Envelopes { session, envelopes }) => {
+                    (envelopes, Some(SealedSession::Signing(session)))
+                }
+                Ok(Opened::Session(session)) => {
+                    self.codecs.remember(addr, format);
+                    return self
+                        .answer_signed(Reply::SigningSession { session }, addr)
+                        .await;
+                }
+                Err(e @ (SignatureError::MissingSession | SignatureError::UnknownSession)) => {
+                    self.drop_unopened("signed", e, false, addr);
+                    let reply = Reply::Denied {
+                        reason: e.to_string(),
+                    };
+                    return self.answer_signed(reply, addr).await;
+                }
+                Err(e) => {
+                    let forged =
+                        matches!(e, SignatureError::UnknownSigner | SignatureError::BadMac);
+                    return self.drop_unopened("signed", e, forged, addr);
+                }
+            },
+            CommandDatagram::Encrypted(sealed) => {
+                let session = sealed.session;
+                match self.noise.open(sealed) {
+                    Ok(envelopes) => (envelopes, Some(SealedSession::Noise(session))),
+                    Err(e) => {
+                        return self.drop_unopened("encrypted", e, e == ChannelError::Decrypt, addr)
+                    }
+                }
+            }
+            datagram => (datagram.into_envelopes(), None),
+        };
+        // A sealed batch was charged as one command until it could be opened
+        if sealed && envelopes.len() > 1 && !self.within_rate(addr, envelopes.len() - 1) {
+            return;
+        }
+        self.codecs.remember(addr, format);
+        match session {
+            Some(SealedSession::Noise(session)) => self.lock_channels().insert(addr, session),
+            _ => self.lock_channels().remove(&addr),
+        };
+        // A refused step stops the batch; later steps were planned around it
+        for envelope in envelopes {
+            let fresh = match (session, envelope.seq) {
+                (Some(SealedSession::Signing(session)), Some(seq)) => {
+                    self.signing.admit(session, seq)
+                }
+                (Some(SealedSession::Noise(session)), Some(seq)) => self.noise.admit(session, seq),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            if !fresh {
+                if let Err(e) = self.drop_signed_replay(&envelope, addr).await {
+                    log::error!("Command error: {}", e);
+                }
+                continue;
+            }
+            match self.handle_envelope(envelope, addr).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    log::error!("Command error: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Replies to a signed datagram that opened a session or named one the server doesn't have
+    async fn answer_signed(&self, reply: Reply, addr: SocketAddr) {
+        self.lock_channels().remove(&addr);
+        if let Err(e) = self.reply(&reply, addr).await {
+            log::error!("Failed to answer signed datagram from {}: {}", addr, e);
+        }
+    }
+
+    /// Answers a Noise handshake; a failed one gets no reply, like a forged datagram
+    async fn accept_handshake(&self, hello: HandshakeDatagram, addr: SocketAddr) {
+        self.lock_channels().remove(&addr);
+        match self.noise.accept(hello) {
+            Ok(reply) => {
+                if let Err(e) = self.reply(&reply, addr).await {
+                    log::error!("Failed to answer handshake from {}: {}", addr, e);
+                }
+            }
+            Err(e) => self.drop_unopened("handshake", e, true, addr),
+        }
+    }
+
+    /// Counts a datagram whose MAC or ciphertext didn't check out as forged, anything else
+    /// wrong with it as malformed
+    fn drop_unopened(
+        &self,
+        kind: &str,
+        error: impl std::fmt::Display,
+        forged: bool,
+        addr: SocketAddr,
+    ) {
+        log::debug!("Dropped {} datagram from {}: {}", kind, addr, error);
+        match forged {
+            true => self.stats.record_forged(),
+            false => self.stats.record_malformed(),
+        }
+    }
+
+    /// Drains the dispatch queue so slow injection never blocks the socket
+    async fn process_queue(&self) {
+        loop {
+            let queued = self.queue.pop().await;
+            // Input admitted just before the kill switch was pressed must not run after it
+            if self.kill_switch.is_engaged() && queued.command.scope().is_some() {
+                continue;
+            }
+            let result = match queued.done {
+                Some(done) => {
+                    let result = self.inject_step(queued.command, queued.addr).await;
+                    let _ = done.send(result.is_ok());
+                    result
+                }
+                None => self.dispatch(queued.command, queued.addr).await,
+            };
+            if let Err(e) = result {
+                log::error!("Command error: {}", e);
+            }
+            self.queue.record_latency(queued.received.elapsed());
+            #[cfg(feature = "test-hooks")]
+            self.queue.hooks().dispatched();
+        }
+    }
+
+    /// Applies the command policy, replying to the sender when a command is refused
+    ///
+    /// Returns whether the command was accepted.
+    async fn handle_envelope(&self, envelope: CommandEnvelope, addr: SocketAddr) -> Result<bool> {
+        let admitted = self.policy.evaluate(&envelope, addr).and_then(|holder| {
+            // Otherwise a client could answer the desk's macro consent hotkey itself
+            let injects = matches!(
+                envelope.command.scope(),
+                Some(Scope::Pointer | Scope::Keyboard)
+            );
+            if injects && self.macros.is_awaiting_desk() {
+                return Err(Denial::AwaitingDesk);
+            }
+            let changed = self
+                .clients
+                .admit(addr, &envelope.command, Instant::now())?;
+            Ok((holder, changed))
+        });
+        let holder = match admitted {
+            Ok((holder, changed)) => {
+                if changed {
+                    self.publish_controller();
+                }
+                holder
+            }
+            Err(denial) => {
+                log::debug!("Denied {:?} from {}: {}", envelope.command, addr, denial);
+                self.stats.record_denied();
+                self.publish_denial(addr, denial);
+                let reply = Reply::Denied {
+                    reason: denial.to_string(),
+                };
+                return self.reply(&reply, addr).await.map(|()| false);
+            }
+        };
+        *self.lock_last_denial() = None;
+        if let Err(invalid) = validation::validate_envelope(&envelope) {
+            log::debug!(
+                "Invalid {} from {}: {}",
+                envelope.command.kind(),
+                addr,
+                invalid
+            );
+            self.stats.record_invalid();
+            self.sequence(addr, envelope.seq, false, None);
+            let reply = Reply::Invalid {
+                field: invalid.field().to_string(),
+                reason: invalid.to_string(),
+            };
+            return self.reply(&reply, addr).await.map(|()| false);
+        }
+        if let Some(reply) = self.hold_for_confirmation(&envelope, holder.as_ref(), addr) {
+            self.sequence(addr, envelope.seq, false, None);
+            return self.reply(&reply, addr).await.map(|()| false);
+        }
+        self.audit.record(&envelope.command, holder.as_ref(), addr);
+        let client_id = holder.as_ref().map(|holder| holder.id.as_str());
+        self.stats
+            .record_command(envelope.command.kind(), client_id);
+        let game_moves = self
+            .game_mode
+            .admit(addr, client_id, envelope.seq, &envelope.command);
+        let token = envelope.auth.as_deref();
+        if let (Some(holder), Some(token)) = (holder.as_ref(), token) {
+            self.track_session(token, holder, addr, envelope.seq);
+        }
+        if let Command::Heartbeat | Command::TakeControl | Command::ReleaseControl =
+            envelope.command
+        {
+            self.sequence(addr, envelope.seq, false, None);
+            return Ok(true);
+        }
+        if let Command::Ping {
+            client_time_ms,
+            previous,
+        } = envelope.command
+        {
+            // Answered off-queue so a backlog doesn't skew the offset estimate
+            let server_time_ms = utils::unix_now_ms();
+            if let (Some(token), Some(previous)) = (token, previous) {
+                self.sessions.record_clock(token, &previous);
+            }
+            self.sequence(addr, envelope.seq, false, None);
+            let reply = Reply::Pong {
+                client_time_ms,
+                server_time_ms,
+            };
+            return self.reply(&reply, addr).await.map(|()| true);
+        }
+        if let Command::RefreshSession { pin } = &envelope.command {
+            self.sequence(addr, envelope.seq, false, None);
+            let reply = match self.policy.refresh_session(&envelope, pin.as_deref(), addr) {
+                Ok(holder) => {
+                    log::info!("{} ({}) refreshed its session", holder.device_name, addr);
+                    Reply::SessionRefreshed {
+                        idle_lock_secs: self.policy.idle_lock_secs(),
+                    }
+                }
+                Err(denial) => {
+                    log::debug!("Refused session refresh from {}: {}", addr, denial);
+                    self.stats.record_denied();
+                    self.publish_denial(addr, denial);
+                    Reply::Denied {
+                        reason: denial.to_string(),
+                    }
+                }
+            };
+            return self.reply(&reply, addr).await.map(|()| true);
+        }
+        if let Command::SelectFormat { formats } = &envelope.command {
+            let transport = match self.tcp.as_ref().is_some_and(|tcp| tcp.serves(addr)) {
+                true => Transport::Tcp,
+                false => Transport::Udp,
+            };
+            let format = self.codecs.select(addr, transport, formats);
+            log::debug!("{} selected {:?} over {:?}", addr, format, transport);
+            self.sequence(addr, envelope.seq, false, None);
+            let reply = Reply::FormatSelected { format };
+            return self.reply(&reply, addr).await.map(|()| true);
+        }
+        let sent_at_ms = envelope.sent_at_ms.or_else(|| {
+            let client_sent_at_ms = envelope.client_sent_at_ms?;
+            self.sessions.to_server_time(token?, client_sent_at_ms)
+        });
+        match game_moves {
+            // Game moves skip the reorder wait; the placeholder keeps later commands in order
+            Some(moves) => {
+                self.sequence(addr, envelope.seq, false, None);
+                for command in moves {
+                    self.queue.push_uncoalesced(command, addr, sent_at_ms);
+                }
+            }
+            None => self.sequence(
+                addr,
+                envelope.seq,
+                envelope.reliable,
+                Some((envelope.command, sent_at_ms)),
+            ),
+        }
+        if let Some(holder) = &holder {
+            self.policy.record_input(holder);
+        }
+        match envelope.seq.filter(|_| envelope.reliable) {
+            Some(seq) => self.reply(&Reply::Ack { seq }, addr).await.map(|()| true),
+            None => Ok(true),
+        }
+    }
+
+    /// Asks for a destructive command to be confirmed, unless it carries the nonce for it
+    ///
+    /// Returns the reply to send instead of running the command.
+    fn hold_for_confirmation(
+        &self,
+        envelope: &CommandEnvelope,
+        holder: Option<&DeviceInfo>,
+        addr: SocketAddr,
+    ) -> Option<Reply> {
+        if !self.policy.needs_confirmation(envelope, holder) {
+            return None;
+        }
+        let now = Instant::now();
+        let mut confirmations = self
+            .confirmations
+            .lock()
+            .expect("Confirmations mutex poisoned");
+        let command = &envelope.command;
+        if envelope
+            .confirm
+            .is_some_and(|nonce| confirmations.redeem(nonce, addr, command, now))
+        {
+            return None;
+        }
+        log::debug!("Holding {} from {} for confirmation", command.kind(), addr);
+        Some(match confirmations.issue(addr, command, now) {
+            Some(nonce) => Reply::ConfirmRequired {
+                command: command.kind().to_string(),
+                nonce,
+                expires_in_secs: confirmations.ttl().as_secs(),
+            },
+            None => Reply::Denied {
+                reason: Denial::TooManyConfirmations.to_string(),
+            },
+        })
+    }
+
+    /// Drops a signed command whose `seq` its token already used, wherever it came from
+    ///
+    /// A retransmitted reliable command is acknowledged again, since its first `Ack` was lost.
+    async fn drop_signed_replay(&self, envelope: &CommandEnvelope, addr: SocketAddr) -> Result<()> {
+        log::debug!(
+            "Dropped replayed signed seq {:?} from {}",
+            envelope.seq,
+            addr
+        );
+        self.stats.record_replayed();
+        match envelope.seq.filter(|_| envelope.reliable) {
+            Some(seq) => self.reply(&Reply::Ack { seq }, addr).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Queues commands in send order; `None` marks a sequence number used up off-queue
+    fn sequence(
+        &self,
+        addr: SocketAddr,
+        seq: Option<u64>,
+        reliable: bool,
+        item: Option<(Command, Option<u64>)>,
+    ) {
+        let placeholder = item.is_none();
+        match self
+            .reorder
+            .accept(addr, seq, reliable, item, Instant::now())
+        {
+            Arrival::Ready(ready) => {
+                for (command, sent_at_ms) in ready.into_iter().flatten() {
+                    self.queue.push(command, addr, sent_at_ms);
+                }
+            }
+            Arrival::Held => {
+                if !placeholder {
+                    self.stats.record_reordered();
+                }
+                self.reorder_held.notify_one();
+            }
+            Arrival::Replayed if placeholder => {}
+            Arrival::Replayed => {
+                log::debug!("Dropped replayed seq {:?} from {}", seq, addr);
+                self.stats.record_replayed();
+            }
+        }
+    }
+
+    /// Queues commands held behind a gap once the missing command is given up on
+    async fn release_reordered(&self) {
+        if !self.reorder.enabled() {
+            return;
+        }
+        loop {
+            match self.reorder.next_deadline() {
+                Some(deadline) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline.into()) => {}
+                        _ = self.reorder_held.notified() => {}
+                    }
+                }
+                None => self.reorder_held.notified().await,
+            }
+            for (addr, item) in self.reorder.expire(Instant::now()) {
+                if let Some((command, sent_at_ms)) = item {
+                    self.queue.push(command, addr, sent_at_ms);
+                }
+            }
+        }
+    }
+
+    /// Probes paired clients that have gone quiet, so `/clients` can tell which are online
+    async fn probe_quiet_clients(&self) {
+        let every = Duration::from_secs(ServerConfig::CLIENT_PROBE_INTERVAL_SECS);
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            for addr in self.sessions.probe(Instant::now(), every) {
+                if let Err(e) = self.reply(&Reply::Probe, addr).await {
+                    log::debug!("Failed to probe {}: {}", addr, e);
+                }
+            }
+        }
+    }
+
+    /// Routes a command to the service that owns it
+    async fn dispatch(&self, command: Command, addr: SocketAddr) -> Result<()> {
+        self.cursor.touch(addr);
+        if let Some(position) = self.parking.wake(Instant::now()) {
+            if let Err(e) = self.move_pointer_to(position).await {
+                log::warn!("Failed to bring the cursor back from parking: {}", e);
+            }
+        }
+        #[cfg(windows)]
+        if let Some(agent) = agent_link::installed() {
+            if let Some(result) = self.forward_to_agent(agent, &command, addr).await {
+                return result;
+            }
+        }
+        match command {
+            Command::SetCursorSize { scale } => self.cursor.set_scale(scale, addr).await,
+            Command::SetSmoothing { smoothing } => match self.smoothing.set(addr, smoothing) {
+                Some(held_back) => self.inject(held_back).await,
+                None => Ok(()),
+            },
+            Command::DisplayPower { state } => {
+                let on = state == PowerState::On;
+                tokio::task::spawn_blocking(move || display::set_power(on)).await?
+            }
+            Command::Power { action } => {
+                log::info!("Power action {:?} requested by {}", action, addr);
+                self.events.publish(ServerEvent::PowerRequested {
+                    addr: addr.to_string(),
+                    action,
+                });
+                tokio::task::spawn_blocking(move || power::perform(action)).await?
+            }
+            Command::NightLight { enabled } => {
+                tokio::task::spawn_blocking(move || display::set_night_light(enabled)).await?
+            }
+            Command::BrightnessUp | Command::BrightnessDown => {
+                let delta = match command {
+                    Command::BrightnessUp => ServerConfig::BRIGHTNESS_STEP_PERCENT,
+                    _ => -ServerConfig::BRIGHTNESS_STEP_PERCENT,
+                };
+                let percent =
+                    tokio::task::spawn_blocking(move || display::step_brightness(delta)).await??;
+                log::debug!("Brightness now {}%", percent);
+                Ok(())
+            }
+            Command::BrightnessSet { percent } => {
+                tokio::task::spawn_blocking(move || display::set_brightness(percent)).await?
+            }
+            Command::ClipboardSet { text, origin } => {
+                self.clipboard_service.handle_set(text, origin).await
+            }
+            Command::Pair { pin, device_name } => {
+                let reply = self.pairing_service.pair(&pin, &device_name, addr);
+                self.reply(&reply, addr).await
+            }
+            Command::RequestPairing { device_name } => {
+                let reply = self.pairing_service.request_approval(&device_name, addr);
+                self.reply(&reply, addr).await
+            }
+            Command::SetProfile { name } => {
+                let reply = match self.profiles.activate(&name) {
+                    Ok(name) => Reply::ProfileChanged { name },
+                    Err(e) => Reply::ProfileRejected {
+                        reason: e.to_string(),
+                        available: self.profiles.names(),
+                    },
+                };
+                self.reply(&reply, addr).await
+            }
+            Command::RunMacro { name } => self.run_macro(&name, addr).await,
+            Command::GetDisplays => {
+                let displays = tokio::task::spawn_blocking(pointer::display_info).await?;
+                self.reply(&Reply::Displays { displays }, addr).await
+            }
+            Command::MoveToDisplay { display, x, y } => {
+                let target =
+                    tokio::task::spawn_blocking(move || pointer::point_on_display(display, x, y))
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("No display {}", display))?;
+                // A pointer the server hasn't moved yet has no tracked position to move from
+                if self.input_handler.pointer_position().is_none() {
+                    self.inject(Command::MouseMove { x: 0.0, y: 0.0 }).await?;
+                }
+                self.move_pointer_to(target).await
+            }
+            Command::CalibrateScroll { axis } => {
+                let (probe, expected) = self.calibration.probe(axis);
+                self.inject(probe).await?;
+                let reply = Reply::ScrollProbed {
+                    axis,
+                    expected: expected.to_string(),
+                };
+                self.reply(&reply, addr).await
+            }
+            Command::ConfirmScrollDirection { axis, correct } => {
+                let inverted = self.calibration.confirm(axis, correct);
+                self.reply(&Reply::ScrollCalibrated { axis, inverted }, addr)
+                    .await
+            }
+            Command::BeginGroup { id } => {
+                self.lock_groups().insert(addr, InputGroup::new(id));
+                Ok(())
+            }
+            Command::EndGroup => {
+                let Some(group) = self.lock_groups().remove(&addr) else {
+                    return Ok(());
+                };
+                if group.failed() {
+                    return Ok(());
+                }
+                let reply = Reply::GroupCompleted {
+                    id: group.id,
+                    steps: group.executed.len(),
+                };
+                self.reply(&reply, addr).await
+            }
+            command => self.dispatch_input(command, addr).await,
+        }
+    }
+
+    /// Injects an input command, tracking it when the sender has a group open
+    async fn dispatch_input(&self, command: Command, addr: SocketAddr) -> Result<()> {
+        let command = match self.tuning.tunes(&command) {
+            true => match self.sessions.client_at(addr) {
+                Some((client_id, device_name)) => {
+                    self.tuning.adjust(command, &client_id, &device_name)
+                }
+                None => command,
+            },
+            false => command,
+        };
+        let playing = self.game_mode.plays(addr);
+        // Games apply their own acceleration, and would fight this one
+        let command = match command {
+            Command::MouseMove { x, y } if !playing => {
+                let (x, y) = self.acceleration.apply(x, y);
+                Command::MouseMove { x, y }
+            }
+            command => command,
+        };
+        let command = self.calibration.apply(self.profiles.adjust(command));
+        // Smoothing trades latency for steadiness, which games don't want
+        let command = match playing {
+            true => command,
+            false => {
+                let Some(command) = self.smoothing.apply(addr, command) else {
+                    return Ok(());
+                };
+                if let Some(held_back) = self.smoothing.settle_before(addr, &command) {
+                    self.inject(held_back).await?;
+                }
+                command
+            }
+        };
+        let in_group = {
+            let mut groups = self.lock_groups();
+            match groups.get(&addr).map(|group| group.skips(Instant::now())) {
+                Some(true) => return Ok(()),
+                Some(false) if groups[&addr].failed() => {
+                    log::debug!(
+                        "Input group from {} failed and never ended; closing it",
+                        addr
+                    );
+                    groups.remove(&addr);
+                    false
+                }
+                Some(false) => true,
+                None => false,
+            }
+        };
+        // Waiting out a press or drag on the worker would stall every client's input behind it
+        let (command, later) = match command {
+            Command::MouseLongPress { button, .. } | Command::Drag { button, .. } => {
+                let steps = timed_steps(plan::plan(&command).unwrap_or_default());
+                (Command::MouseDown { button }, steps)
+            }
+            command => (command, Vec::new()),
+        };
+        let step = command.clone();
+        let result = match command {
+            Command::MouseMove { x, y } if playing => self.inject_relative(x, y).await,
+            command => self.inject(command).await,
+        };
+        if result.is_ok() {
+            self.clients.record(addr, &step);
+            let mut held = HeldInput::default();
+            held.record(&step);
+            self.play_later(held, later, addr);
+        }
+        if !in_group {
+            return result;
+        }
+
+        match result {
+            Ok(()) => {
+                if let Some(group) = self.lock_groups().get_mut(&addr) {
+                    group.record(&step);
+                }
+                Ok(())
+            }
+            Err(error) => self.abort_group(&step, error, addr).await,
+        }
+    }
+
+    /// Queues `steps` one at a time, their pauses timed here rather than on the worker
+    ///
+    /// `held` is what the worker already pressed for them. Presses are tracked like any other
+    /// held input, so a kill switch or silent client releases them. Playback stops when the
+    /// kill switch engages or a step fails, and then lets go of whatever it still holds.
+    fn play_later(&self, mut held: HeldInput, steps: Vec<MacroStep>, addr: SocketAddr) {
+        if steps.is_empty() {
+            return;
+        }
+        let (queue, kill_switch) = (self.queue.clone(), self.kill_switch.clone());
+        tokio::spawn(async move {
+            for step in steps {
+                tokio::time::sleep(Duration::from_millis(step.after_ms)).await;
+                let command = step.command.clone();
+                let injected = !kill_switch.is_engaged()
+                    && queue.push_step(step.command, addr).await == Ok(true);
+                if !injected {
+                    log::debug!("Playback for {} stopped before {}", addr, command.kind());
+                    for release in held.release_commands() {
+                        let _ = queue.push_step(release, addr).await;
+                    }
+                    return;
+                }
+                held.record(&command);
+            }
+        });
+    }
+
+    /// Sends devices the outcome of pairing requests decided on the host
+    async fn deliver_pairing_decisions(&self) {
+        loop {
+            let (addr, reply) = self.pairing_service.next_decision().await;
+            if let Err(e) = self.reply(&reply, addr).await {
+                log::warn!("Failed to send pairing decision to {}: {}", addr, e);
+            }
+        }
+    }
+
+    /// Lets go of what a silent client held, so a dropped link can't leave a drag stuck
+    async fn expire_silent_clients(&self) {
+        let mut interval =
+            tokio::time::interval(Duration::from_millis(ServerConfig::SESSION_CHECK_MS));
+        loop {
+            interval.tick().await;
+            for expired in self.clients.expire(Instant::now()) {
+                let addr = expired.addr;
+                self.lock_groups().remove(&addr);
+                self.game_mode.forget(addr);
+                if expired.had_control {
+                    log::info!("Client {} went silent; control is free", addr);
+                    self.publish_controller();
+                }
+                if expired.release.is_empty() {
+                    log::debug!("Client {} went silent", addr);
+                    continue;
+                }
+                log::info!(
+                    "Client {} went silent; releasing {} held inputs",
+                    addr,
+                    expired.release.len()
+                );
+                self.events.publish(ServerEvent::ClientTimedOut {
+                    addr: addr.to_string(),
+                    released: expired.release.len(),
+                });
+                for command in expired.release {
+                    if let Err(e) = self.inject(command).await {
+                        log::error!("Failed to release held input: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets go of everything clients hold the moment the kill switch is engaged
+    async fn release_on_kill_switch(&self) {
+        loop {
+            self.kill_switch.engaged().await;
+            self.lock_groups().clear();
+            if self.clients.revoke_control().is_some() {
+                self.publish_controller();
+            }
+            let release = self.clients.release_all();
+            if !release.is_empty() {
+                log::info!(
+                    "Kill switch engaged; releasing {} held inputs",
+                    release.len()
+                );
+            }
+            for command in release {
+                if let Err(e) = self.inject(command).await {
+                    log::error!("Failed to release held input: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Moves the cursor to its parking spot whenever remote input goes idle
+    ///
+    /// Needs the tracked pointer position, so it stays off under the Windows input agent.
+    async fn park_when_idle(&self) {
+        if !self.parking.enabled() {
+            return;
+        }
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(ServerConfig::CURSOR_PARKING_CHECK_SECS));
+        loop {
+            interval.tick().await;
+            let Some(position) = self.input_handler.pointer_position() else {
+                continue;
+            };
+            let Some(spot) =
+                self.parking
+                    .park(Instant::now(), position, &pointer::desktop_bounds())
+            else {
+                continue;
+            };
+            log::debug!("Remote input idle; parking the cursor");
+            if let Err(e) = self.move_pointer_to(spot).await {
+                log::warn!("Failed to park the cursor: {}", e);
+            }
+        }
+    }
+
+    /// Moves straight to `target`, bypassing smoothing, groups and hot corners
+    async fn move_pointer_to(&self, (x, y): (f64, f64)) -> Result<()> {
+        let Some((from_x, from_y)) = self.input_handler.pointer_position() else {
+            return Ok(());
+        };
+        let command = Command::MouseMove {
+            x: x - from_x,
+            y: y - from_y,
+        };
+        let result = self.input_handler.handle_command(command).await;
+        self.presence.record_injected();
+        result
+    }
+
+    /// Display commands act on the user's monitors, which a Windows service only reaches
+    /// through its agent; `None` for commands the service handles itself
+    #[cfg(windows)]
+    async fn forward_to_agent(
+        &self,
+        agent: &AgentLink,
+        command: &Command,
+        addr: SocketAddr,
+    ) -> Option<Result<()>> {
+        match command {
+            Command::DisplayPower { .. }
+            | Command::NightLight { .. }
+            | Command::BrightnessUp
+            | Command::BrightnessDown
+            | Command::BrightnessSet { .. } => Some(agent.send(command).await),
+            Command::MoveToDisplay { .. } => {
+                let result = agent.send(command).await;
+                self.presence.record_injected();
+                Some(result)
+            }
+            Command::GetDisplays => Some(match agent.displays().await {
+                Ok(displays) => self.reply(&Reply::Displays { displays }, addr).await,
+                Err(e) => Err(e),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Plays back a recorded macro with its timing; once the kill switch is engaged only its
+    /// releases run, so nothing it pressed stays down
+    async fn run_macro(&self, name: &str, addr: SocketAddr) -> Result<()> {
+        let Some(steps) = self.macros.get(name) else {
+            let reply = Reply::Invalid {
+                field: "name".to_string(),
+                reason: format!("no macro named '{}'", name),
+            };
+            return self.reply(&reply, addr).await;
+        };
+        log::info!("Running macro '{}' for {}", name, addr);
+        self.play_later(HeldInput::default(), steps, addr);
+        Ok(())
+    }
+
+    async fn inject(&self, command: Command) -> Result<()> {
+        let moved = matches!(command, Command::MouseMove { .. });
+        let result = self.input_handler.handle_command(command).await;
+        self.presence.record_injected();
+        if moved && result.is_ok() {
+            if let Some(position) = self.input_handler.pointer_position() {
+                self.hot_corners.observe(position);
+            }
+        }
+        result
+    }
+
+    /// Injects a step of an already planned command and tracks what it let go of
+    async fn inject_step(&self, command: Command, addr: SocketAddr) -> Result<()> {
+        let step = command.clone();
+        self.inject(command).await?;
+        self.clients.record(addr, &step);
+        Ok(())
+    }
+
+    async fn inject_relative(&self, dx: f64, dy: f64) -> Result<()> {
+        let result = self.input_handler.move_relative(dx, dy).await;
+        self.presence.record_injected();
+        result
+    }
+
+    /// Releases whatever the failed group still holds and tells the sender how far it got
+    async fn abort_group(
+        &self,
+        failed: &Command,
+        error: anyhow::Error,
+        addr: SocketAddr,
+    ) -> Result<()> {
+        let Some((id, executed, release)) = self.lock_groups().get_mut(&addr).map(|group| {
+            group.fail(Instant::now());
+            (
+                group.id.clone(),
+                group.executed.clone(),
+                group.release_commands(),
+            )
+        }) else {
+            return Err(error);
+        };
+
+        log::warn!("Input group step {} failed: {}", failed.kind(), error);
+        for command in release {
+            let step = command.clone();
+            match self.inject(command).await {
+                Ok(()) => self.clients.record(addr, &step),
+                Err(e) => log::error!("Failed to release grouped input: {}", e),
+            }
+        }
+        let reply = Reply::GroupFailed {
+            id,
+            executed: executed.into_iter().map(String::from).collect(),
+            failed: failed.kind().to_string(),
+            error: error.to_string(),
+        };
+        self.reply(&reply, addr).await
+    }
+
+    fn lock_groups(&self) -> std::sync::MutexGuard<'_, HashMap<SocketAddr, InputGroup>> {
+        self.groups.lock().expect("Input groups mutex poisoned")
+    }
+
+    fn lock_channels(&self) -> std::sync::MutexGuard<'_, HashMap<SocketAddr, u64>> {
+        self.channels.lock().expect("Noise channels mutex poisoned")
+    }
+
+    fn track_session(&self, token: &str, holder: &DeviceInfo, addr: SocketAddr, seq: Option<u64>) {
+        if let Touch::Resumed { from } = self.sessions.touch(token, holder, addr, seq) {
+            log::info!(
+                "Session for '{}' resumed from {} (was {})",
+                holder.device_name,
+                addr,
+                from
+            );
+            self.events.publish(ServerEvent::SessionResumed {
+                device_name: holder.device_name.clone(),
+                addr: addr.to_string(),
+                previous_addr: from.to_string(),
+            });
+        }
+    }
+
+    fn publish_controller(&self) {
+        self.events.publish(ServerEvent::ControllerChanged {
+            addr: self.clients.controller().map(|addr| addr.to_string()),
+        });
+    }
+
+    /// Publishes a denial event once per streak so a refused pointer stream doesn't flood subscribers
+    fn publish_denial(&self, addr: SocketAddr, denial: Denial) {
+        let mut last = self.lock_last_denial();
+        if *last == Some((addr, denial)) {
+            return;
+        }
+        *last = Some((addr, denial));
+        drop(last);
+        self.events.publish(ServerEvent::CommandDenied {
+            addr: addr.to_string(),
+            reason: denial.to_string(),
+        });
+    }
+
+    fn lock_last_denial(&self) -> std::sync::MutexGuard<'_, Option<(SocketAddr, Denial)>> {
+        self.last_denial.lock().expect("Last denial mutex poisoned")
+    }
+
+    /// Answers over whichever transport `addr` used, sealed if it last sent encrypted
+    async fn reply(&self, reply: &Reply, addr: SocketAddr) -> Result<()> {
+        let session = self.lock_channels().get(&addr).copied();
+        let sealed = match session {
+            Some(session) => self.noise.seal(session, reply)?,
+            None => None,
+        };
+        let encoded = self
+            .codecs
+            .encode_reply(sealed.as_ref().unwrap_or(reply), addr)?;
+        if let Some(tcp) = &self.tcp {
+            if tcp.send(addr, encoded.clone()) {
+                return Ok(());
+            }
+        }
+        self.socket.send_to(&encoded, addr).await?;
+        Ok(())
+    }
+}
+
+/// The steps of a planned long press or drag after its `MouseDown`, as timed commands
+fn timed_steps(steps: Vec<InputStep>) -> Vec<MacroStep> {
+    let mut pause_ms = 0;
+    let mut timed = Vec::new();
+    for step in steps {
+        let command = match step {
+            InputStep::Wait { ms } => {
+                pause_ms += ms;
+                continue;
+            }
+            InputStep::MouseMove { dx, dy } => Command::MouseMove { x: dx, y: dy },
+            InputStep::MouseUp { button } => Command::MouseUp { button },
+            _ => continue,
+        };
+        timed.push(MacroStep {
+            after_ms: std::mem::take(&mut pause_ms),
+            command,
+        });
+    }
+    timed
 }
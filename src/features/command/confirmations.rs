@@ -0,0 +1,139 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::Command;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Destructive commands held back until their sender resends them with the nonce it was given
+///
+/// The nonce only runs the exact command it was issued for, once, from the same address,
+/// so a client has to ask its user and come back rather than fire the command blind.
+pub struct Confirmations {
+    ttl: Duration,
+    pending: HashMap<u64, PendingConfirmation>,
+}
+
+struct PendingConfirmation {
+    addr: SocketAddr,
+    /// The command as JSON, compared when the nonce is redeemed
+    command: String,
+    expires_at: Instant,
+}
+
+impl Confirmations {
+    pub fn new() -> Self {
+        Self {
+            ttl: Duration::from_secs(ServerConfig::CONFIRMATION_TTL_SECS),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Issues a nonce for `command`, replacing any confirmation `addr` still had pending
+    ///
+    /// Returns `None` when too many are already waiting.
+    pub fn issue(&mut self, addr: SocketAddr, command: &Command, now: Instant) -> Option<u64> {
+        self.prune(now);
+        self.pending.retain(|_, pending| pending.addr != addr);
+        if self.pending.len() >= ServerConfig::CONFIRMATION_MAX_PENDING {
+            return None;
+        }
+        let nonce = rand::thread_rng().next_u64();
+        self.pending.insert(
+            nonce,
+            PendingConfirmation {
+                addr,
+                command: fingerprint(command),
+                expires_at: now + self.ttl,
+            },
+        );
+        Some(nonce)
+    }
+
+    /// Uses up `nonce` if `addr` was given it for this very `command` and it hasn't expired
+    pub fn redeem(
+        &mut self,
+        nonce: u64,
+        addr: SocketAddr,
+        command: &Command,
+        now: Instant,
+    ) -> bool {
+        self.prune(now);
+        let matches = self
+            .pending
+            .get(&nonce)
+            .is_some_and(|pending| pending.addr == addr && pending.command == fingerprint(command));
+        if matches {
+            self.pending.remove(&nonce);
+        }
+        matches
+    }
+
+    fn prune(&mut self, now: Instant) {
+        self.pending.retain(|_, pending| pending.expires_at > now);
+    }
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fingerprint(command: &Command) -> String {
+    serde_json::to_string(command).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::ModifierKeys;
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, last], 50000))
+    }
+
+    fn close_window() -> Command {
+        Command::KeyPress {
+            key: "F4".to_string(),
+            modifiers: ModifierKeys {
+                alt: true,
+                ..ModifierKeys::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_nonce_runs_its_command_once() {
+        let mut confirmations = Confirmations::new();
+        let now = Instant::now();
+        let nonce = confirmations.issue(addr(5), &close_window(), now).unwrap();
+        assert!(!confirmations.redeem(nonce, addr(6), &close_window(), now));
+        assert!(!confirmations.redeem(nonce, addr(5), &Command::SelectAll, now));
+        assert!(confirmations.redeem(nonce, addr(5), &close_window(), now));
+        assert!(!confirmations.redeem(nonce, addr(5), &close_window(), now));
+    }
+
+    #[test]
+    fn test_nonce_expires() {
+        let mut confirmations = Confirmations::new();
+        let now = Instant::now();
+        let nonce = confirmations.issue(addr(5), &close_window(), now).unwrap();
+        let expired = now + confirmations.ttl();
+        assert!(!confirmations.redeem(nonce, addr(5), &close_window(), expired));
+    }
+
+    #[test]
+    fn test_new_request_replaces_pending_nonce() {
+        let mut confirmations = Confirmations::new();
+        let now = Instant::now();
+        let first = confirmations.issue(addr(5), &close_window(), now).unwrap();
+        let second = confirmations.issue(addr(5), &close_window(), now).unwrap();
+        assert!(!confirmations.redeem(first, addr(5), &close_window(), now));
+        assert!(confirmations.redeem(second, addr(5), &close_window(), now));
+    }
+}
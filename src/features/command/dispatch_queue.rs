@@ -0,0 +1,421 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::Command;
+#[cfg(any(test, feature = "test-hooks"))]
+use crate::features::command::pipeline_hooks::{PipelineHooks, QueueDecision};
+use crate::utils;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+
+/// Accepted commands waiting for the input worker
+///
+/// Consecutive relative moves from the same sender are merged while queued, so a slow
+/// injection backend catches up instead of replaying every intermediate position.
+pub struct DispatchQueue {
+    state: Mutex<QueueState>,
+    ready: Notify,
+    stale_move_budget: Option<Duration>,
+    #[cfg(any(test, feature = "test-hooks"))]
+    hooks: PipelineHooks,
+}
+
+/// A command popped from the queue, stamped with when it arrived
+pub struct Queued {
+    pub command: Command,
+    pub addr: SocketAddr,
+    pub received: Instant,
+    /// Client send time in server-clock unix milliseconds, when the client stamped it
+    pub sent_at_ms: Option<u64>,
+    /// Set for a later step of a command already planned, which is injected as it is rather
+    /// than adjusted again; told whether it was injected
+    pub done: Option<oneshot::Sender<bool>>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: VecDeque<Queued>,
+    coalesced_moves: u64,
+    dropped: u64,
+    discarded_stale: u64,
+    latencies: VecDeque<Duration>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct QueueSnapshot {
+    pub depth: usize,
+    pub oldest_pending_ms: Option<u64>,
+    pub coalesced_moves: u64,
+    pub dropped: u64,
+    pub discarded_stale: u64,
+    /// Receive-to-injected time of the most recent dispatches, oldest first
+    pub recent_latencies_us: Vec<u64>,
+}
+
+/// Receive-to-injected time of recent dispatches, in microseconds
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LatencyPercentiles {
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+impl DispatchQueue {
+    /// Moves stamped longer than `stale_move_budget` ago are dropped at dispatch
+    pub fn new(stale_move_budget: Option<Duration>) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            ready: Notify::new(),
+            stale_move_budget,
+            #[cfg(any(test, feature = "test-hooks"))]
+            hooks: PipelineHooks::new(),
+        }
+    }
+
+    /// Enqueues a command, merging it into a pending move when possible
+    pub fn push(&self, command: Command, addr: SocketAddr, sent_at_ms: Option<u64>) {
+        self.enqueue(command, addr, sent_at_ms, true, None);
+    }
+
+    /// Enqueues a command without merging it, so every move is injected as it was sent
+    pub fn push_uncoalesced(&self, command: Command, addr: SocketAddr, sent_at_ms: Option<u64>) {
+        self.enqueue(command, addr, sent_at_ms, false, None);
+    }
+
+    /// Enqueues a later step of a command the worker already started, such as a drag's moves
+    ///
+    /// Resolves to whether it was injected; a step dropped or skipped never is.
+    pub fn push_step(&self, command: Command, addr: SocketAddr) -> oneshot::Receiver<bool> {
+        let (done, injected) = oneshot::channel();
+        self.enqueue(command, addr, None, false, Some(done));
+        injected
+    }
+
+    fn enqueue(
+        &self,
+        command: Command,
+        addr: SocketAddr,
+        sent_at_ms: Option<u64>,
+        merge: bool,
+        done: Option<oneshot::Sender<bool>>,
+    ) {
+        let mut state = self.lock_state();
+        if let (true, Command::MouseMove { x, y }) = (merge, &command) {
+            if let Some(Queued {
+                command: Command::MouseMove { x: px, y: py },
+                addr: pending_addr,
+                sent_at_ms: pending_sent_at,
+                done: None,
+                ..
+            }) = state.pending.back_mut()
+            {
+                if *pending_addr == addr {
+                    *px += x;
+                    *py += y;
+                    // The merged move is only as stale as its newest part
+                    *pending_sent_at = sent_at_ms.or(*pending_sent_at);
+                    #[cfg(any(test, feature = "test-hooks"))]
+                    self.hooks
+                        .record(addr, QueueDecision::Coalesced { x: *px, y: *py });
+                    state.coalesced_moves += 1;
+                    return;
+                }
+            }
+        }
+        if state.pending.len() >= ServerConfig::DISPATCH_QUEUE_CAPACITY
+            && !self.make_room(&mut state, &command)
+        {
+            state.dropped += 1;
+            #[cfg(any(test, feature = "test-hooks"))]
+            self.hooks.record(
+                addr,
+                QueueDecision::Dropped {
+                    kind: command.kind(),
+                },
+            );
+            return;
+        }
+        #[cfg(any(test, feature = "test-hooks"))]
+        self.hooks.record(
+            addr,
+            QueueDecision::Queued {
+                kind: command.kind(),
+            },
+        );
+        state.pending.push_back(Queued {
+            command,
+            addr,
+            received: Instant::now(),
+            sent_at_ms,
+            done,
+        });
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    /// Frees a slot in the full queue for `command`, returning false when it should be dropped
+    ///
+    /// A pending move goes first, since later motion makes up for it. A release must never be
+    /// dropped, or a button or key would stay held on the host, so it pushes out any command
+    /// that isn't one, or overfills a queue holding nothing else.
+    fn make_room(&self, state: &mut QueueState, command: &Command) -> bool {
+        let position = |evictable: fn(&Command) -> bool| {
+            state
+                .pending
+                .iter()
+                .position(|queued| evictable(&queued.command))
+        };
+        let oldest_move = position(|pending| matches!(pending, Command::MouseMove { .. }));
+        let index = match (oldest_move, command.restores_state()) {
+            (Some(index), _) => index,
+            (None, false) => return false,
+            (None, true) => match position(|pending| !pending.restores_state()) {
+                Some(index) => index,
+                None => return true,
+            },
+        };
+        if let Some(evicted) = state.pending.remove(index) {
+            log::debug!(
+                "Dispatch queue full; dropped a pending {} from {}",
+                evicted.command.kind(),
+                evicted.addr
+            );
+            state.dropped += 1;
+            #[cfg(any(test, feature = "test-hooks"))]
+            self.hooks.record(
+                evicted.addr,
+                QueueDecision::Evicted {
+                    kind: evicted.command.kind(),
+                },
+            );
+        }
+        true
+    }
+
+    /// Waits for the next command in arrival order, skipping moves past the latency budget
+    ///
+    /// Clicks and keys always run; only pointer motion is worth less than replaying it late.
+    pub async fn pop(&self) -> Queued {
+        loop {
+            {
+                let mut state = self.lock_state();
+                while let Some(queued) = state.pending.pop_front() {
+                    if !self.is_stale(&queued, utils::unix_now_ms()) {
+                        return queued;
+                    }
+                    state.discarded_stale += 1;
+                    #[cfg(any(test, feature = "test-hooks"))]
+                    self.hooks
+                        .record(queued.addr, QueueDecision::DiscardedStale);
+                }
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    fn is_stale(&self, queued: &Queued, now_ms: u64) -> bool {
+        let (Some(budget), Some(sent_at_ms)) = (self.stale_move_budget, queued.sent_at_ms) else {
+            return false;
+        };
+        matches!(queued.command, Command::MouseMove { .. })
+            && now_ms.saturating_sub(sent_at_ms) > budget.as_millis() as u64
+    }
+
+    /// Dispatch notifications and coalescing decisions, for tests of the pipeline
+    #[cfg(any(test, feature = "test-hooks"))]
+    pub fn hooks(&self) -> &PipelineHooks {
+        &self.hooks
+    }
+
+    /// Records how long a command took from receipt until it was handled
+    pub fn record_latency(&self, latency: Duration) {
+        let mut state = self.lock_state();
+        if state.latencies.len() == ServerConfig::DISPATCH_LATENCY_SAMPLES {
+            state.latencies.pop_front();
+        }
+        state.latencies.push_back(latency);
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        let state = self.lock_state();
+        QueueSnapshot {
+            depth: state.pending.len(),
+            oldest_pending_ms: state
+                .pending
+                .front()
+                .map(|queued| queued.received.elapsed().as_millis() as u64),
+            coalesced_moves: state.coalesced_moves,
+            dropped: state.dropped,
+            discarded_stale: state.discarded_stale,
+            recent_latencies_us: state
+                .latencies
+                .iter()
+                .skip(
+                    state
+                        .latencies
+                        .len()
+                        .saturating_sub(ServerConfig::DISPATCH_LATENCY_HISTORY),
+                )
+                .map(|latency| latency.as_micros() as u64)
+                .collect(),
+        }
+    }
+
+    /// Percentiles over the last few hundred dispatches; `None` before the first
+    pub fn latency_percentiles(&self) -> Option<LatencyPercentiles> {
+        let mut latencies: Vec<u64> = self
+            .lock_state()
+            .latencies
+            .iter()
+            .map(|latency| latency.as_micros() as u64)
+            .collect();
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        Some(LatencyPercentiles {
+            p50_us: percentile(50),
+            p90_us: percentile(90),
+            p99_us: percentile(99),
+            max_us: latencies[latencies.len() - 1],
+        })
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().expect("Dispatch queue mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 10], port))
+    }
+
+    #[tokio::test]
+    async fn test_consecutive_moves_are_coalesced() {
+        let queue = DispatchQueue::new(None);
+        queue.push(Command::MouseMove { x: 1.0, y: 2.0 }, addr(1), None);
+        queue.push(Command::MouseMove { x: 3.0, y: -1.0 }, addr(1), None);
+
+        let snapshot = queue.snapshot();
+        assert_eq!(snapshot.depth, 1);
+        assert_eq!(snapshot.coalesced_moves, 1);
+        assert_eq!(
+            queue.hooks().take_decisions(),
+            [
+                (addr(1), QueueDecision::Queued { kind: "MouseMove" }),
+                (addr(1), QueueDecision::Coalesced { x: 4.0, y: 1.0 }),
+            ]
+        );
+        match queue.pop().await.command {
+            Command::MouseMove { x, y } => assert_eq!((x, y), (4.0, 1.0)),
+            _ => panic!("Expected MouseMove"),
+        }
+    }
+
+    #[test]
+    fn test_moves_are_not_merged_across_other_commands_or_senders() {
+        let queue = DispatchQueue::new(None);
+        queue.push(Command::MouseMove { x: 1.0, y: 0.0 }, addr(1), None);
+        queue.push(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2), None);
+        queue.push(Command::MouseClick { button: 1 }, addr(2), None);
+        queue.push(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2), None);
+        assert_eq!(queue.snapshot().depth, 4);
+
+        queue.push_uncoalesced(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2), None);
+        queue.push_uncoalesced(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2), None);
+        assert_eq!(queue.snapshot().depth, 6);
+
+        // A drag's planned step must not absorb a client move, which still needs adjusting
+        let _injected = queue.push_step(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2));
+        queue.push(Command::MouseMove { x: 1.0, y: 0.0 }, addr(2), None);
+        assert_eq!(queue.snapshot().depth, 8);
+    }
+
+    #[tokio::test]
+    async fn test_stale_moves_are_discarded_but_clicks_run() {
+        let queue = DispatchQueue::new(Some(Duration::from_millis(100)));
+        let long_ago = utils::unix_now_ms() - 5_000;
+        queue.push(
+            Command::MouseMove { x: 1.0, y: 0.0 },
+            addr(1),
+            Some(long_ago),
+        );
+        queue.push(Command::MouseClick { button: 1 }, addr(1), Some(long_ago));
+
+        assert!(matches!(
+            queue.pop().await.command,
+            Command::MouseClick { .. }
+        ));
+        assert_eq!(queue.snapshot().discarded_stale, 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_evicts_moves_and_never_drops_releases() {
+        let queue = DispatchQueue::new(None);
+        queue.push(Command::MouseMove { x: 1.0, y: 0.0 }, addr(1), None);
+        for _ in 1..ServerConfig::DISPATCH_QUEUE_CAPACITY {
+            queue.push(Command::MouseClick { button: 1 }, addr(2), None);
+        }
+        queue.hooks().take_decisions();
+
+        // The pending move makes room for a click, and then the queue is full of clicks
+        queue.push(Command::MouseClick { button: 2 }, addr(2), None);
+        queue.push(Command::MouseClick { button: 3 }, addr(2), None);
+        let release = Command::KeyRelease {
+            key: "a".to_string(),
+            modifiers: Default::default(),
+        };
+        queue.push(release, addr(1), None);
+        assert_eq!(
+            queue.hooks().take_decisions(),
+            [
+                (addr(1), QueueDecision::Evicted { kind: "MouseMove" }),
+                (addr(2), QueueDecision::Queued { kind: "MouseClick" }),
+                (addr(2), QueueDecision::Dropped { kind: "MouseClick" }),
+                (addr(2), QueueDecision::Evicted { kind: "MouseClick" }),
+                (addr(1), QueueDecision::Queued { kind: "KeyRelease" }),
+            ]
+        );
+
+        let snapshot = queue.snapshot();
+        assert_eq!(snapshot.depth, ServerConfig::DISPATCH_QUEUE_CAPACITY);
+        assert_eq!(snapshot.dropped, 3);
+        let mut last = None;
+        while queue.snapshot().depth > 0 {
+            last = Some(queue.pop().await.command);
+        }
+        assert!(matches!(last, Some(Command::KeyRelease { .. })));
+    }
+
+    #[test]
+    fn test_latency_history_is_bounded() {
+        let queue = DispatchQueue::new(None);
+        for ms in 0..(ServerConfig::DISPATCH_LATENCY_HISTORY as u64 + 5) {
+            queue.record_latency(Duration::from_millis(ms));
+        }
+        let latencies = queue.snapshot().recent_latencies_us;
+        assert_eq!(latencies.len(), ServerConfig::DISPATCH_LATENCY_HISTORY);
+        assert_eq!(latencies[0], 5_000);
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let queue = DispatchQueue::new(None);
+        assert_eq!(queue.latency_percentiles(), None);
+        for ms in 1..=100 {
+            queue.record_latency(Duration::from_millis(ms));
+        }
+        let percentiles = queue.latency_percentiles().unwrap();
+        assert_eq!(percentiles.p50_us, 50_000);
+        assert_eq!(percentiles.p99_us, 99_000);
+        assert_eq!(percentiles.max_us, 100_000);
+    }
+}
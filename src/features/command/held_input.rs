@@ -0,0 +1,109 @@
+use crate::domain::models::Command;
+
+/// Keys, modifiers and mouse buttons pressed by injected commands and not yet let go
+#[derive(Debug, Default)]
+pub struct HeldInput {
+    buttons: Vec<u8>,
+    keys: Vec<String>,
+    modifiers: Vec<&'static str>,
+}
+
+impl HeldInput {
+    /// Updates what is held after `command` was injected
+    pub fn record(&mut self, command: &Command) {
+        match command {
+            Command::MouseDown { button } => push_unique(&mut self.buttons, *button),
+            Command::MouseUp { button } => self.buttons.retain(|held| held != button),
+            Command::KeyPress { key, modifiers } => {
+                push_unique(&mut self.keys, key.clone());
+                for (pressed, name) in [
+                    (modifiers.ctrl, "ctrl"),
+                    (modifiers.alt, "alt"),
+                    (modifiers.shift, "shift"),
+                    (modifiers.meta, "meta"),
+                ] {
+                    if pressed {
+                        push_unique(&mut self.modifiers, name);
+                    }
+                }
+            }
+            Command::KeyRelease { key, .. } => self.keys.retain(|held| held != key),
+            Command::ModifierPress { modifier } => {
+                if let Some(name) = modifier_name(modifier) {
+                    push_unique(&mut self.modifiers, name);
+                }
+            }
+            Command::ModifierRelease { modifier } => {
+                if let Some(name) = modifier_name(modifier) {
+                    self.modifiers.retain(|held| *held != name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buttons.is_empty() && self.keys.is_empty() && self.modifiers.is_empty()
+    }
+
+    /// Commands that let go of everything still held, keys before modifiers
+    pub fn release_commands(&self) -> Vec<Command> {
+        let keys = self.keys.iter().map(|key| Command::KeyRelease {
+            key: key.clone(),
+            modifiers: Default::default(),
+        });
+        let modifiers = self
+            .modifiers
+            .iter()
+            .map(|modifier| Command::ModifierRelease {
+                modifier: modifier.to_string(),
+            });
+        let buttons = self
+            .buttons
+            .iter()
+            .map(|button| Command::MouseUp { button: *button });
+        keys.chain(modifiers).chain(buttons).collect()
+    }
+}
+
+fn modifier_name(modifier: &str) -> Option<&'static str> {
+    match modifier.to_lowercase().as_str() {
+        "ctrl" | "control" => Some("ctrl"),
+        "alt" => Some("alt"),
+        "shift" => Some("shift"),
+        "meta" | "super" | "cmd" => Some("meta"),
+        _ => None,
+    }
+}
+
+fn push_unique<T: PartialEq>(items: &mut Vec<T>, item: T) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::ModifierKeys;
+
+    #[test]
+    fn test_key_press_modifiers_count_as_held() {
+        let mut held = HeldInput::default();
+        held.record(&Command::KeyPress {
+            key: "a".to_string(),
+            modifiers: ModifierKeys {
+                shift: true,
+                ..ModifierKeys::default()
+            },
+        });
+        held.record(&Command::KeyRelease {
+            key: "a".to_string(),
+            modifiers: ModifierKeys::default(),
+        });
+        match held.release_commands().as_slice() {
+            [Command::ModifierRelease { modifier }] => assert_eq!(modifier, "shift"),
+            other => panic!("Unexpected release commands: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::Command;
+use crate::features::command::held_input::HeldInput;
+use std::time::{Duration, Instant};
+
+/// Steps of a `BeginGroup`/`EndGroup` sequence and the input they left held
+///
+/// If a step fails, the held keys and buttons are released so a half-applied
+/// chord or drag can't stay stuck on the host.
+#[derive(Debug, Default)]
+pub struct InputGroup {
+    pub id: Option<String>,
+    pub executed: Vec<&'static str>,
+    /// When a step failed; later steps are skipped until `EndGroup`
+    failed_at: Option<Instant>,
+    held: HeldInput,
+}
+
+impl InputGroup {
+    pub fn new(id: Option<String>) -> Self {
+        Self {
+            id,
+            ..Self::default()
+        }
+    }
+
+    /// Records a step that executed successfully
+    pub fn record(&mut self, command: &Command) {
+        self.executed.push(command.kind());
+        self.held.record(command);
+    }
+
+    pub fn fail(&mut self, now: Instant) {
+        self.failed_at = Some(now);
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed_at.is_some()
+    }
+
+    /// Whether a step arriving at `now` belongs to the failed group and should be skipped
+    ///
+    /// Once the failure is old, the `EndGroup` must have been lost, and the step is new input.
+    pub fn skips(&self, now: Instant) -> bool {
+        let ttl = Duration::from_millis(ServerConfig::INPUT_GROUP_FAILED_TTL_MS);
+        self.failed_at
+            .is_some_and(|failed_at| now.saturating_duration_since(failed_at) < ttl)
+    }
+
+    /// Commands that let go of everything this group still holds, keys before modifiers
+    pub fn release_commands(&self) -> Vec<Command> {
+        self.held.release_commands()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::ModifierKeys;
+
+    #[test]
+    fn test_releases_only_what_is_still_held() {
+        let mut group = InputGroup::new(Some("copy".to_string()));
+        group.record(&Command::ModifierPress {
+            modifier: "Control".to_string(),
+        });
+        group.record(&Command::MouseDown { button: 1 });
+        group.record(&Command::KeyPress {
+            key: "c".to_string(),
+            modifiers: ModifierKeys::default(),
+        });
+        group.record(&Command::MouseUp { button: 1 });
+
+        let kinds: Vec<_> = group.release_commands().iter().map(Command::kind).collect();
+        assert_eq!(kinds, vec!["KeyRelease", "ModifierRelease"]);
+        assert_eq!(group.executed.len(), 4);
+    }
+
+    #[test]
+    fn test_failed_group_stops_skipping_when_end_group_is_lost() {
+        let mut group = InputGroup::new(None);
+        let now = Instant::now();
+        assert!(!group.skips(now));
+
+        group.fail(now);
+        assert!(group.failed());
+        assert!(group.skips(now + Duration::from_millis(100)));
+        let ttl = Duration::from_millis(ServerConfig::INPUT_GROUP_FAILED_TTL_MS);
+        assert!(!group.skips(now + ttl));
+    }
+}
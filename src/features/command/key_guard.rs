@@ -0,0 +1,168 @@
+use crate::domain::config::settings::{GuardAction, KeyGuardSettings};
+use crate::domain::models::{Command, CommandEnvelope, ModifierKeys};
+use crate::features::auth::token_store::{DeviceInfo, Role};
+use crate::features::command::policy::Denial;
+
+/// A guarded shortcut such as `ctrl+alt+Delete`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    modifiers: ModifierKeys,
+    key: String,
+}
+
+impl Chord {
+    /// Parses `modifier+…+key`; `None` for an unknown modifier or a missing key
+    pub fn parse(chord: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty())?;
+        let mut modifiers = ModifierKeys::default();
+        for part in parts {
+            let held = match part.to_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.ctrl,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "meta" | "super" | "cmd" | "win" => &mut modifiers.meta,
+                _ => return None,
+            };
+            *held = true;
+        }
+        Some(Self {
+            modifiers,
+            key: key.to_string(),
+        })
+    }
+
+    /// Whether pressing `key` with `modifiers` sends this chord; extra modifiers still count
+    pub fn matches(&self, key: &str, modifiers: &ModifierKeys) -> bool {
+        key.eq_ignore_ascii_case(&self.key)
+            && (!self.modifiers.ctrl || modifiers.ctrl)
+            && (!self.modifiers.alt || modifiers.alt)
+            && (!self.modifiers.shift || modifiers.shift)
+            && (!self.modifiers.meta || modifiers.meta)
+    }
+}
+
+/// Holds back shortcuts that close apps, lock the screen or sign out unless an admin sends them
+///
+/// A phone in a pocket or a guest with a pointer-and-keyboard token shouldn't be able to
+/// end someone's session with one stray key press.
+pub struct KeyGuard {
+    chords: Vec<Chord>,
+    action: GuardAction,
+}
+
+impl KeyGuard {
+    pub fn new(settings: &KeyGuardSettings) -> Self {
+        let chords = match settings.enabled {
+            true => settings
+                .chords
+                .iter()
+                .filter_map(|chord| {
+                    let parsed = Chord::parse(chord);
+                    if parsed.is_none() {
+                        log::warn!("Ignoring key guard chord '{}'", chord);
+                    }
+                    parsed
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+        Self {
+            chords,
+            action: settings.action,
+        }
+    }
+
+    /// Refuses a guarded chord from anyone but the admin token when the guard blocks outright
+    pub fn check(
+        &self,
+        envelope: &CommandEnvelope,
+        holder: Option<&DeviceInfo>,
+    ) -> Result<(), Denial> {
+        match self.action {
+            GuardAction::Block if self.guards(envelope, holder) => Err(Denial::ShortcutBlocked),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the sender has to confirm this chord with a nonce before it runs
+    pub fn needs_confirmation(
+        &self,
+        envelope: &CommandEnvelope,
+        holder: Option<&DeviceInfo>,
+    ) -> bool {
+        self.action == GuardAction::Confirm && self.guards(envelope, holder)
+    }
+
+    fn guards(&self, envelope: &CommandEnvelope, holder: Option<&DeviceInfo>) -> bool {
+        let Command::KeyPress { key, modifiers } = &envelope.command else {
+            return false;
+        };
+        !holder.is_some_and(|holder| holder.role == Role::Admin)
+            && self
+                .chords
+                .iter()
+                .any(|chord| chord.matches(key, modifiers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(action: GuardAction) -> KeyGuard {
+        KeyGuard::new(&KeyGuardSettings {
+            enabled: true,
+            action,
+            ..KeyGuardSettings::default()
+        })
+    }
+
+    fn envelope(json: &str) -> CommandEnvelope {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_chord_parses_modifiers_and_key() {
+        let chord = Chord::parse("Ctrl+Alt+Delete").unwrap();
+        let pressed = ModifierKeys {
+            ctrl: true,
+            alt: true,
+            shift: true,
+            meta: false,
+        };
+        assert!(chord.matches("delete", &pressed));
+        assert!(!chord.matches("Delete", &ModifierKeys::default()));
+        assert_eq!(Chord::parse("hyper+q"), None);
+        assert_eq!(Chord::parse("alt+"), None);
+    }
+
+    #[test]
+    fn test_guarded_chord_needs_confirmation() {
+        let guard = guard(GuardAction::Confirm);
+        let close = envelope(r#"{"type":"KeyPress","key":"F4","modifiers":{"alt":true}}"#);
+        assert!(guard.needs_confirmation(&close, None));
+        assert_eq!(guard.check(&close, None), Ok(()));
+
+        let plain = envelope(r#"{"type":"KeyPress","key":"F4"}"#);
+        assert!(!guard.needs_confirmation(&plain, None));
+    }
+
+    #[test]
+    fn test_block_refuses_everyone_but_admin() {
+        let guard = guard(GuardAction::Block);
+        let lock = envelope(r#"{"type":"KeyPress","key":"l","modifiers":{"meta":true}}"#);
+        assert_eq!(guard.check(&lock, None), Err(Denial::ShortcutBlocked));
+        assert!(!guard.needs_confirmation(&lock, None));
+
+        let admin = DeviceInfo {
+            id: String::new(),
+            device_name: "admin".to_string(),
+            paired_at: 0,
+            role: Role::Admin,
+            expires_at: None,
+            scopes: None,
+        };
+        assert_eq!(guard.check(&lock, Some(&admin)), Ok(()));
+    }
+}
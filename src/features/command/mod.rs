@@ -1 +1,18 @@
+pub mod codec_registry;
 pub mod command_service;
+pub mod confirmations;
+pub mod dispatch_queue;
+pub mod held_input;
+pub mod input_group;
+pub mod key_guard;
+#[cfg(any(test, feature = "test-hooks"))]
+pub mod pipeline_hooks;
+pub mod policy;
+pub mod protocol_aliases;
+pub mod rate_limiter;
+pub mod reorder_buffer;
+pub mod replay_window;
+pub mod simulation;
+pub mod tcp_transport;
+pub mod udp_socket;
+pub mod validation;
@@ -0,0 +1,106 @@
+//! Instrumentation for tests of the command pipeline, built with the `test-hooks` feature
+//!
+//! Tests await dispatches and read what the dispatch queue decided instead of sleeping and
+//! hoping the input worker has caught up.
+
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+use tokio::sync::watch;
+
+/// What the dispatch queue did with a pushed or popped command
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueDecision {
+    Queued {
+        kind: &'static str,
+    },
+    /// Merged into the pending move from the same sender, which now totals `x`, `y`
+    Coalesced {
+        x: f64,
+        y: f64,
+    },
+    /// The queue was full
+    Dropped {
+        kind: &'static str,
+    },
+    /// Pushed out of a full queue to make room for a later command
+    Evicted {
+        kind: &'static str,
+    },
+    /// A move older than the latency budget, skipped at dispatch
+    DiscardedStale,
+}
+
+pub struct PipelineHooks {
+    dispatched: watch::Sender<u64>,
+    decisions: Mutex<Vec<(SocketAddr, QueueDecision)>>,
+}
+
+impl Default for PipelineHooks {
+    fn default() -> Self {
+        Self {
+            dispatched: watch::Sender::new(0),
+            decisions: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl PipelineHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, addr: SocketAddr, decision: QueueDecision) {
+        self.lock_decisions().push((addr, decision));
+    }
+
+    /// Counts a command the input worker has finished with, whether or not it succeeded
+    pub(crate) fn dispatched(&self) {
+        self.dispatched.send_modify(|count| *count += 1);
+    }
+
+    /// Commands the input worker has finished with since startup
+    pub fn dispatched_count(&self) -> u64 {
+        *self.dispatched.borrow()
+    }
+
+    /// Waits until the input worker has finished with at least `count` commands
+    pub async fn wait_dispatched(&self, count: u64) {
+        let mut dispatched = self.dispatched.subscribe();
+        // The sender lives in `self`, so the channel can't close while borrowed
+        let _ = dispatched.wait_for(|dispatched| *dispatched >= count).await;
+    }
+
+    /// Decisions since the last call, oldest first
+    pub fn take_decisions(&self) -> Vec<(SocketAddr, QueueDecision)> {
+        std::mem::take(&mut *self.lock_decisions())
+    }
+
+    fn lock_decisions(&self) -> MutexGuard<'_, Vec<(SocketAddr, QueueDecision)>> {
+        self.decisions
+            .lock()
+            .expect("Pipeline hooks mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_dispatched_returns_once_count_reached() {
+        let hooks = Arc::new(PipelineHooks::new());
+        let worker = hooks.clone();
+        tokio::spawn(async move {
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+                worker.dispatched();
+            }
+        });
+        tokio::time::timeout(Duration::from_secs(5), hooks.wait_dispatched(3))
+            .await
+            .unwrap();
+        assert_eq!(hooks.dispatched_count(), 3);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::domain::config::Settings;
+use crate::domain::models::{Command, CommandEnvelope, Scope};
+use crate::features::auth::token_store::{DeviceInfo, Role, TokenStore};
+use crate::features::command::key_guard::KeyGuard;
+use crate::features::geofence::geofence_service::GeofenceService;
+use crate::features::presence::desktop_session::DesktopSession;
+use crate::features::presence::kill_switch::KillSwitch;
+use crate::features::presence::presence_monitor::PresenceMonitor;
+use crate::features::profile::profile_service::ProfileService;
+use crate::features::schedule::active_hours::ActiveHours;
+use crate::features::session::session_lock::SessionLock;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Reason a command was refused before dispatch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Denial {
+    #[error("remote control suspended: untrusted network")]
+    UntrustedNetwork,
+    #[error("outside active hours")]
+    OutsideActiveHours,
+    #[error("auth token required")]
+    TokenRequired,
+    #[error("invalid or expired token")]
+    InvalidToken,
+    #[error("token must be sent in a signed datagram")]
+    SignatureRequired,
+    #[error("commands must arrive over an encrypted channel")]
+    EncryptionRequired,
+    #[error("local user is active")]
+    LocalUserActive,
+    #[error("remote input suspended at the host")]
+    KillSwitchEngaged,
+    #[error("waiting for the person at the host to answer a prompt")]
+    AwaitingDesk,
+    #[error("desktop session is disconnected")]
+    SessionDisconnected,
+    #[error("command not permitted for this token")]
+    OutOfScope,
+    #[error("command disabled by the active profile")]
+    DisabledByProfile,
+    #[error("another client is in control")]
+    NotController,
+    #[error("too many commands waiting for confirmation")]
+    TooManyConfirmations,
+    #[error("shortcut blocked for this client")]
+    ShortcutBlocked,
+    #[error("session locked after inactivity; send RefreshSession")]
+    SessionLocked,
+    #[error("wrong session PIN")]
+    WrongSessionPin,
+    #[error("too many wrong session PINs; try again later")]
+    RefreshLockedOut,
+}
+
+/// Decides whether an incoming command may be dispatched
+pub struct CommandPolicy {
+    tokens: Arc<TokenStore>,
+    geofence: Arc<GeofenceService>,
+    presence: Arc<PresenceMonitor>,
+    desktop_session: Arc<DesktopSession>,
+    kill_switch: Arc<KillSwitch>,
+    profiles: Arc<ProfileService>,
+    active_hours: ActiveHours,
+    key_guard: KeyGuard,
+    session_lock: SessionLock,
+    /// Whether `Power` needs confirming, as a guarded shortcut does
+    confirm_power: bool,
+    require_token: bool,
+    require_signed: bool,
+    require_encryption: bool,
+    clipboard_peers: Vec<IpAddr>,
+}
+
+impl CommandPolicy {
+    pub fn new(
+        tokens: Arc<TokenStore>,
+        geofence: Arc<GeofenceService>,
+        presence: Arc<PresenceMonitor>,
+        desktop_session: Arc<DesktopSession>,
+        kill_switch: Arc<KillSwitch>,
+        profiles: Arc<ProfileService>,
+        settings: &Settings,
+    ) -> Self {
+        Self {
+            tokens,
+            geofence,
+            presence,
+            desktop_session,
+            kill_switch,
+            profiles,
+            active_hours: ActiveHours::new(settings.active_hours.clone()),
+            key_guard: KeyGuard::new(&settings.key_guard),
+            session_lock: SessionLock::new(&settings.auth),
+            confirm_power: settings.power.confirm,
+            require_token: settings.auth.require_token,
+            require_signed: settings.auth.require_signed,
+            require_encryption: settings.auth.require_encryption,
+            clipboard_peers: settings
+                .clipboard_sync
+                .peers
+                .iter()
+                .map(|peer| peer.ip())
+                .collect(),
+        }
+    }
+
+    /// Returns the token holder when the command carried a valid token
+    pub fn evaluate(
+        &self,
+        envelope: &CommandEnvelope,
+        addr: SocketAddr,
+    ) -> Result<Option<DeviceInfo>, Denial> {
+        if self.geofence.is_suspended() {
+            return Err(Denial::UntrustedNetwork);
+        }
+        let Some(scope) = envelope.command.scope() else {
+            return Ok(None);
+        };
+        if self.kill_switch.is_engaged() {
+            return Err(Denial::KillSwitchEngaged);
+        }
+        if self.is_peer_clipboard(&envelope.command, addr) {
+            return Ok(None);
+        }
+        if !self.active_hours.is_open() {
+            return Err(Denial::OutsideActiveHours);
+        }
+        if self.require_encryption && !envelope.encrypted {
+            return Err(Denial::EncryptionRequired);
+        }
+
+        let holder = match envelope.auth.as_deref() {
+            None if self.require_token || envelope.command.needs_token() => {
+                return Err(Denial::TokenRequired)
+            }
+            None => None,
+            Some(_) if self.require_signed && !envelope.signed => {
+                return Err(Denial::SignatureRequired)
+            }
+            Some(token) => {
+                let holder = self.tokens.validate(token).ok_or(Denial::InvalidToken)?;
+                if !holder.allows(scope) {
+                    return Err(Denial::OutOfScope);
+                }
+                self.session_lock.check(&holder, Instant::now())?;
+                Some(holder)
+            }
+        };
+        // A restrictive profile must never lock clients out of switching back
+        let switches_profile = matches!(envelope.command, Command::SetProfile { .. });
+        if !switches_profile && !self.profiles.permits(scope) {
+            return Err(Denial::DisabledByProfile);
+        }
+        self.key_guard.check(envelope, holder.as_ref())?;
+
+        if matches!(scope, Scope::Pointer | Scope::Keyboard) {
+            // Windows drops input injected into a disconnected session without an error
+            if self.desktop_session.is_disconnected() {
+                return Err(Denial::SessionDisconnected);
+            }
+            if self.presence.is_local_active() {
+                return Err(Denial::LocalUserActive);
+            }
+        }
+        Ok(holder)
+    }
+
+    /// Whether `holder` may watch the screen preview, under the same rules as moving the pointer
+    pub fn admit_viewer(&self, holder: &DeviceInfo) -> Result<(), Denial> {
+        if self.geofence.is_suspended() {
+            return Err(Denial::UntrustedNetwork);
+        }
+        if self.kill_switch.is_engaged() {
+            return Err(Denial::KillSwitchEngaged);
+        }
+        if !self.active_hours.is_open() {
+            return Err(Denial::OutsideActiveHours);
+        }
+        if !holder.allows(Scope::Pointer) {
+            return Err(Denial::OutOfScope);
+        }
+        self.session_lock.check(holder, Instant::now())
+    }
+
+    /// Unlocks the idle session of the token `envelope` carries
+    pub fn refresh_session(
+        &self,
+        envelope: &CommandEnvelope,
+        pin: Option<&str>,
+        addr: SocketAddr,
+    ) -> Result<DeviceInfo, Denial> {
+        let token = envelope.auth.as_deref().ok_or(Denial::TokenRequired)?;
+        if self.require_signed && !envelope.signed {
+            return Err(Denial::SignatureRequired);
+        }
+        let holder = self.tokens.validate(token).ok_or(Denial::InvalidToken)?;
+        self.session_lock
+            .refresh(&holder, pin, addr.ip(), Instant::now())?;
+        Ok(holder)
+    }
+
+    /// Keeps `holder`'s session from locking; only for commands that were dispatched
+    pub fn record_input(&self, holder: &DeviceInfo) {
+        self.session_lock.record_input(holder, Instant::now());
+    }
+
+    pub fn idle_lock_secs(&self) -> u64 {
+        self.session_lock.idle_lock_secs()
+    }
+
+    /// Whether `envelope` is destructive enough that its sender must confirm it with a nonce
+    pub fn needs_confirmation(
+        &self,
+        envelope: &CommandEnvelope,
+        holder: Option<&DeviceInfo>,
+    ) -> bool {
+        let unconfirmed_power = self.confirm_power
+            && matches!(envelope.command, Command::Power { .. })
+            && !holder.is_some_and(|holder| holder.role == Role::Admin);
+        unconfirmed_power || self.key_guard.needs_confirmation(envelope, holder)
+    }
+
+    /// Relayed clipboard updates from configured peers carry no client token
+    fn is_peer_clipboard(&self, command: &Command, addr: SocketAddr) -> bool {
+        matches!(
+            command,
+            Command::ClipboardSet {
+                origin: Some(_),
+                ..
+            }
+        ) && self.clipboard_peers.contains(&addr.ip())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::config::settings::AuthSettings;
+    use crate::features::auth::token_store::temp_store;
+    use crate::features::events::event_bus::EventBus;
+    use tempfile::TempDir;
+
+    fn policy(require_token: bool) -> (TempDir, CommandPolicy, Arc<TokenStore>) {
+        policy_with(|auth| auth.require_token = require_token)
+    }
+
+    fn policy_with(
+        configure: impl FnOnce(&mut AuthSettings),
+    ) -> (TempDir, CommandPolicy, Arc<TokenStore>) {
+        let (dir, tokens) = temp_store();
+        let tokens = Arc::new(tokens);
+        let mut settings = Settings::default();
+        configure(&mut settings.auth);
+        let geofence = Arc::new(GeofenceService::new(
+            settings.geofence.clone(),
+            EventBus::new(),
+        ));
+        let presence = Arc::new(PresenceMonitor::new(
+            settings.local_input.clone(),
+            EventBus::new(),
+        ));
+        let profiles = Arc::new(ProfileService::new(
+            settings.profiles.clone(),
+            EventBus::new(),
+        ));
+        (
+            dir,
+            CommandPolicy::new(
+                tokens.clone(),
+                geofence,
+                presence,
+                Arc::new(DesktopSession::new(EventBus::new())),
+                Arc::new(KillSwitch::new(EventBus::new())),
+                profiles,
+                &settings,
+            ),
+            tokens,
+        )
+    }
+
+    fn envelope(json: &str) -> CommandEnvelope {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn addr() -> SocketAddr {
+        "192.168.1.50:50000".parse().unwrap()
+    }
+
+    #[test]
+    fn test_unauthenticated_allowed_unless_required() {
+        let (_open_dir, open, _) = policy(false);
+        let (_strict_dir, strict, _) = policy(true);
+        let cmd = envelope(r#"{"type":"MouseClick","button":1}"#);
+        assert!(open.evaluate(&cmd, addr()).is_ok());
+        assert!(matches!(
+            strict.evaluate(&cmd, addr()),
+            Err(Denial::TokenRequired)
+        ));
+    }
+
+    #[test]
+    fn test_pairing_never_requires_token() {
+        let (_dir, strict, _) = policy(true);
+        let cmd = envelope(r#"{"type":"Pair","pin":"123456"}"#);
+        assert!(strict.evaluate(&cmd, addr()).is_ok());
+    }
+
+    #[test]
+    fn test_guest_token_limited_to_scope() {
+        let (_dir, policy, tokens) = policy(true);
+        let (token, _) = tokens
+            .issue_guest("Guest", 5, vec![Scope::Pointer])
+            .unwrap();
+        let click = envelope(&format!(
+            r#"{{"type":"MouseClick","button":1,"auth":"{}"}}"#,
+            token
+        ));
+        let key = envelope(&format!(
+            r#"{{"type":"KeyPress","key":"a","auth":"{}"}}"#,
+            token
+        ));
+        assert!(policy.evaluate(&click, addr()).is_ok());
+        assert!(matches!(
+            policy.evaluate(&key, addr()),
+            Err(Denial::OutOfScope)
+        ));
+    }
+
+    #[test]
+    fn test_kill_switch_refuses_input_but_not_ping() {
+        let (_dir, policy, _) = policy(false);
+        policy.kill_switch.set(true);
+        let click = envelope(r#"{"type":"MouseClick","button":1}"#);
+        assert!(matches!(
+            policy.evaluate(&click, addr()),
+            Err(Denial::KillSwitchEngaged)
+        ));
+        let ping = envelope(r#"{"type":"Ping","client_time_ms":1}"#);
+        assert!(policy.evaluate(&ping, addr()).is_ok());
+    }
+
+    #[test]
+    fn test_clear_token_refused_when_signing_required() {
+        let (_dir, policy, tokens) = policy_with(|auth| auth.require_signed = true);
+        let token = tokens.issue("Pixel").unwrap();
+        let mut click = envelope(&format!(
+            r#"{{"type":"MouseClick","button":1,"auth":"{}"}}"#,
+            token
+        ));
+        assert!(matches!(
+            policy.evaluate(&click, addr()),
+            Err(Denial::SignatureRequired)
+        ));
+        click.signed = true;
+        assert!(policy.evaluate(&click, addr()).is_ok());
+    }
+
+    #[test]
+    fn test_plain_commands_refused_when_encryption_required() {
+        let (_dir, policy, tokens) = policy_with(|auth| auth.require_encryption = true);
+        let token = tokens.issue("Pixel").unwrap();
+        let mut click = envelope(&format!(
+            r#"{{"type":"MouseClick","button":1,"auth":"{}"}}"#,
+            token
+        ));
+        click.signed = true;
+        assert!(matches!(
+            policy.evaluate(&click, addr()),
+            Err(Denial::EncryptionRequired)
+        ));
+        click.encrypted = true;
+        assert!(policy.evaluate(&click, addr()).is_ok());
+        let pair = envelope(r#"{"type":"Pair","pin":"123456"}"#);
+        assert!(policy.evaluate(&pair, addr()).is_ok());
+    }
+
+    #[test]
+    fn test_power_and_macros_need_a_scoped_token_even_when_tokens_are_optional() {
+        let (_dir, policy, tokens) = policy(false);
+        for command in [
+            r#"{"type":"Power","action":"sleep"}"#,
+            r#"{"type":"DisplayPower","state":"off"}"#,
+            r#"{"type":"RunMacro","name":"greet"}"#,
+        ] {
+            assert!(matches!(
+                policy.evaluate(&envelope(command), addr()),
+                Err(Denial::TokenRequired)
+            ));
+        }
+        let (token, _) = tokens
+            .issue_guest("Guest", 5, vec![Scope::Pointer, Scope::Keyboard])
+            .unwrap();
+        let sleep = envelope(&format!(
+            r#"{{"type":"Power","action":"sleep","auth":"{}"}}"#,
+            token
+        ));
+        assert!(matches!(
+            policy.evaluate(&sleep, addr()),
+            Err(Denial::OutOfScope)
+        ));
+        let run = envelope(&format!(
+            r#"{{"type":"RunMacro","name":"greet","auth":"{}"}}"#,
+            token
+        ));
+        assert!(policy.evaluate(&run, addr()).is_ok());
+    }
+
+    #[test]
+    fn test_viewers_need_the_pointer_scope_and_an_open_host() {
+        let (_dir, policy, tokens) = policy(false);
+        let (token, _) = tokens
+            .issue_guest("Guest", 5, vec![Scope::Pointer])
+            .unwrap();
+        let viewer = tokens.validate(&token).unwrap();
+        let observer = tokens
+            .validate(&tokens.issue_observer("Wall").unwrap())
+            .unwrap();
+        assert!(policy.admit_viewer(&viewer).is_ok());
+        assert_eq!(policy.admit_viewer(&observer), Err(Denial::OutOfScope));
+        policy.kill_switch.set(true);
+        assert_eq!(policy.admit_viewer(&viewer), Err(Denial::KillSwitchEngaged));
+    }
+
+    #[test]
+    fn test_power_needs_confirmation_except_from_admin() {
+        let (_dir, policy, _) = policy(false);
+        let sleep = envelope(r#"{"type":"Power","action":"sleep"}"#);
+        assert!(policy.needs_confirmation(&sleep, None));
+        let admin = DeviceInfo {
+            id: "admin".to_string(),
+            device_name: "Tray".to_string(),
+            paired_at: 0,
+            role: Role::Admin,
+            expires_at: None,
+            scopes: None,
+        };
+        assert!(!policy.needs_confirmation(&sleep, Some(&admin)));
+        let night_light = envelope(r#"{"type":"NightLight","enabled":true}"#);
+        assert!(!policy.needs_confirmation(&night_light, None));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::domain::config::settings::ProtocolSettings;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Old names still accepted for renamed commands and fields
+///
+/// Built in are the short command names of early clients and their `dx`/`dy` scroll fields;
+/// `protocol` settings add more for a migration in progress. Aliases only touch the top level
+/// of each envelope, where the command's `type` and fields sit.
+pub struct ProtocolAliases {
+    commands: HashMap<String, String>,
+    fields: HashMap<String, HashMap<String, String>>,
+}
+
+impl Default for ProtocolAliases {
+    fn default() -> Self {
+        Self::new(&ProtocolSettings::default())
+    }
+}
+
+impl ProtocolAliases {
+    const COMMANDS: [(&'static str, &'static str); 3] = [
+        ("Move", "MouseMove"),
+        ("Click", "MouseClick"),
+        ("Scroll", "MouseScroll"),
+    ];
+
+    const FIELDS: [(&'static str, &'static str, &'static str); 2] = [
+        ("MouseScroll", "dx", "delta_x"),
+        ("MouseScroll", "dy", "delta_y"),
+    ];
+
+    pub fn new(settings: &ProtocolSettings) -> Self {
+        let mut commands: HashMap<String, String> = Self::COMMANDS
+            .iter()
+            .map(|(old, current)| (old.to_string(), current.to_string()))
+            .collect();
+        commands.extend(settings.command_aliases.clone());
+
+        let mut fields: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for (command, old, current) in Self::FIELDS {
+            fields
+                .entry(command.to_string())
+                .or_default()
+                .insert(old.to_string(), current.to_string());
+        }
+        for (command, renames) in &settings.field_aliases {
+            fields
+                .entry(command.clone())
+                .or_default()
+                .extend(renames.clone());
+        }
+        Self { commands, fields }
+    }
+
+    /// Renames old commands and fields in a decoded envelope or batch, returning whether any
+    /// were found
+    pub fn rewrite(&self, message: &mut Value) -> bool {
+        match message {
+            Value::Array(batch) => batch
+                .iter_mut()
+                .fold(false, |renamed, envelope| self.rewrite(envelope) | renamed),
+            Value::Object(envelope) => self.rewrite_envelope(envelope),
+            _ => false,
+        }
+    }
+
+    fn rewrite_envelope(&self, envelope: &mut Map<String, Value>) -> bool {
+        let mut renamed = false;
+        if let Some(Value::String(kind)) = envelope.get_mut("type") {
+            if let Some(current) = self.commands.get(kind.as_str()) {
+                *kind = current.clone();
+                renamed = true;
+            }
+        }
+        let Some(Value::String(kind)) = envelope.get("type") else {
+            return renamed;
+        };
+        let Some(renames) = self.fields.get(kind) else {
+            return renamed;
+        };
+        for (old, current) in renames {
+            // A field sent under both names keeps the current one
+            if envelope.contains_key(current) {
+                continue;
+            }
+            if let Some(value) = envelope.remove(old) {
+                envelope.insert(current.clone(), value);
+                renamed = true;
+            }
+        }
+        renamed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rewrites_built_in_and_configured_aliases() {
+        let settings = ProtocolSettings {
+            command_aliases: HashMap::from([("Tap".to_string(), "MouseClick".to_string())]),
+            field_aliases: HashMap::from([(
+                "MouseClick".to_string(),
+                HashMap::from([("btn".to_string(), "button".to_string())]),
+            )]),
+        };
+        let aliases = ProtocolAliases::new(&settings);
+
+        let mut batch = json!([
+            {"type": "Move", "x": 1, "y": 2, "auth": "token"},
+            {"type": "Scroll", "dx": 0.5, "dy": 1, "delta_y": 3},
+            {"type": "Tap", "btn": 2},
+        ]);
+        assert!(aliases.rewrite(&mut batch));
+        assert_eq!(
+            batch,
+            json!([
+                {"type": "MouseMove", "x": 1, "y": 2, "auth": "token"},
+                {"type": "MouseScroll", "delta_x": 0.5, "dy": 1, "delta_y": 3},
+                {"type": "MouseClick", "button": 2},
+            ])
+        );
+
+        let mut current = json!({"type": "MouseMove", "x": 1, "y": 2});
+        assert!(!aliases.rewrite(&mut current));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::domain::config::settings::RateLimitSettings;
+use crate::domain::config::ServerConfig;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Instant;
+
+/// Token buckets per client IP, so one client can't flood the input pipeline
+///
+/// Keyed by IP rather than address and port, so a client can't dodge its limit by sending
+/// from many ports. Every command costs one token; a batch costs one per command.
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            settings,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes `cost` tokens from `ip`'s bucket, returning false when it doesn't hold that many
+    pub fn admit(&mut self, ip: IpAddr, cost: usize, now: Instant) -> bool {
+        if !self.settings.enabled {
+            return true;
+        }
+        if self.buckets.len() >= ServerConfig::RATE_LIMIT_MAX_CLIENTS {
+            self.forget_idle(now);
+        }
+        let burst = f64::from(self.settings.burst.max(1));
+        let per_sec = f64::from(self.settings.per_sec);
+        let bucket = self.buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(burst);
+        bucket.refilled_at = now;
+
+        let cost = cost as f64;
+        if bucket.tokens < cost {
+            return false;
+        }
+        bucket.tokens -= cost;
+        true
+    }
+
+    /// Drops buckets that have refilled completely, which behave exactly like new ones
+    fn forget_idle(&mut self, now: Instant) {
+        let burst = f64::from(self.settings.burst.max(1));
+        let per_sec = f64::from(self.settings.per_sec);
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens + elapsed * per_sec < burst
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter(per_sec: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimitSettings {
+            enabled: true,
+            per_sec,
+            burst,
+        })
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, last])
+    }
+
+    #[test]
+    fn test_burst_then_sustained_rate() {
+        let mut limiter = limiter(100, 10);
+        let now = Instant::now();
+        assert!((0..10).all(|_| limiter.admit(ip(5), 1, now)));
+        assert!(!limiter.admit(ip(5), 1, now));
+
+        let later = now + Duration::from_millis(50);
+        assert!(limiter.admit(ip(5), 5, later));
+        assert!(!limiter.admit(ip(5), 1, later));
+    }
+
+    #[test]
+    fn test_clients_have_their_own_buckets() {
+        let mut limiter = limiter(100, 10);
+        let now = Instant::now();
+        assert!(limiter.admit(ip(5), 10, now));
+        assert!(!limiter.admit(ip(5), 1, now));
+        assert!(limiter.admit(ip(6), 1, now));
+    }
+
+    #[test]
+    fn test_disabled_admits_everything() {
+        let mut limiter = RateLimiter::new(RateLimitSettings {
+            enabled: false,
+            ..RateLimitSettings::default()
+        });
+        assert!(limiter.admit(ip(5), 10_000, Instant::now()));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::domain::config::settings::ReorderSettings;
+use crate::domain::config::ServerConfig;
+use crate::features::command::replay_window::ReplayWindow;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Puts each sender's sequenced commands back in send order before dispatch
+///
+/// A command that arrives ahead of a missing one is held until the gap fills, for at most
+/// `max_delay_ms` or until `window` commands are waiting; then the gap is given up on.
+/// With reordering off, duplicates are still dropped but nothing is held back.
+/// Commands without a `seq` pass straight through.
+pub struct ReorderBuffer<T> {
+    settings: ReorderSettings,
+    streams: Mutex<HashMap<SocketAddr, Stream<T>>>,
+    seen: Mutex<HashMap<SocketAddr, ReplayWindow>>,
+}
+
+struct Stream<T> {
+    /// Sequence number the next in-order command carries
+    next: u64,
+    held: BTreeMap<u64, Held<T>>,
+}
+
+struct Held<T> {
+    item: T,
+    deadline: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Arrival<T> {
+    /// In order; these are ready to dispatch, oldest first
+    Ready(Vec<T>),
+    /// Waiting for an earlier command that hasn't arrived yet
+    Held,
+    /// Its place in the sequence has already passed: a duplicate, or too late to reorder
+    Replayed,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(settings: ReorderSettings) -> Self {
+        Self {
+            settings,
+            streams: Mutex::new(HashMap::new()),
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    /// Takes one command; `reliable` ones are de-duplicated even with `deduplicate` off,
+    /// because their senders retransmit until acknowledged
+    pub fn accept(
+        &self,
+        addr: SocketAddr,
+        seq: Option<u64>,
+        reliable: bool,
+        item: T,
+        now: Instant,
+    ) -> Arrival<T> {
+        let Some(seq) = seq else {
+            return Arrival::Ready(vec![item]);
+        };
+        if !self.settings.enabled {
+            return self.deduplicate(addr, seq, reliable, item);
+        }
+        let window = self.settings.window.max(1) as u64;
+        let mut streams = self.lock_streams();
+        let stream = streams.entry(addr).or_insert_with(|| Stream {
+            next: seq,
+            held: BTreeMap::new(),
+        });
+
+        if seq < stream.next {
+            // Far behind means the client restarted its counter rather than a late packet
+            if stream.next - seq <= ServerConfig::REPLAY_WINDOW.max(window) {
+                return Arrival::Replayed;
+            }
+            *stream = Stream {
+                next: seq,
+                held: BTreeMap::new(),
+            };
+        }
+        if seq > stream.next {
+            if stream.held.contains_key(&seq) {
+                return Arrival::Replayed;
+            }
+            let deadline = now + Duration::from_millis(self.settings.max_delay_ms);
+            stream.held.insert(seq, Held { item, deadline });
+            if stream.held.len() < window as usize {
+                return Arrival::Held;
+            }
+            return Arrival::Ready(stream.skip_gap());
+        }
+
+        stream.next = seq + 1;
+        let mut ready = vec![item];
+        ready.extend(stream.release_in_order());
+        Arrival::Ready(ready)
+    }
+
+    fn deduplicate(&self, addr: SocketAddr, seq: u64, reliable: bool, item: T) -> Arrival<T> {
+        if !self.settings.deduplicate && !reliable {
+            return Arrival::Ready(vec![item]);
+        }
+        let mut seen = self.seen.lock().expect("Replay window mutex poisoned");
+        if seen.entry(addr).or_default().admit(seq) {
+            Arrival::Ready(vec![item])
+        } else {
+            Arrival::Replayed
+        }
+    }
+
+    /// Gives up on gaps that have been open too long, releasing what waited behind them
+    pub fn expire(&self, now: Instant) -> Vec<(SocketAddr, T)> {
+        let mut released = Vec::new();
+        for (addr, stream) in self.lock_streams().iter_mut() {
+            let overdue = stream
+                .held
+                .values()
+                .next()
+                .is_some_and(|held| held.deadline <= now);
+            if overdue {
+                released.extend(stream.skip_gap().into_iter().map(|item| (*addr, item)));
+            }
+        }
+        released
+    }
+
+    /// When the oldest held command's gap will be given up on
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.lock_streams()
+            .values()
+            .filter_map(|stream| stream.held.values().next())
+            .map(|held| held.deadline)
+            .min()
+    }
+
+    fn lock_streams(&self) -> MutexGuard<'_, HashMap<SocketAddr, Stream<T>>> {
+        self.streams.lock().expect("Reorder buffer mutex poisoned")
+    }
+}
+
+impl<T> Stream<T> {
+    /// Jumps past the first gap and releases the run that follows it
+    fn skip_gap(&mut self) -> Vec<T> {
+        if let Some(&first) = self.held.keys().next() {
+            self.next = first;
+        }
+        self.release_in_order()
+    }
+
+    fn release_in_order(&mut self) -> Vec<T> {
+        let mut ready = Vec::new();
+        while let Some(held) = self.held.remove(&self.next) {
+            ready.push(held.item);
+            self.next += 1;
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer() -> ReorderBuffer<&'static str> {
+        ReorderBuffer::new(ReorderSettings {
+            enabled: true,
+            deduplicate: true,
+            window: 4,
+            max_delay_ms: 30,
+        })
+    }
+
+    fn client() -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 5], 50000))
+    }
+
+    #[test]
+    fn test_button_up_waits_for_late_button_down() {
+        let buffer = buffer();
+        let now = Instant::now();
+        assert_eq!(
+            buffer.accept(client(), Some(0), false, "move", now),
+            Arrival::Ready(vec!["move"])
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(2), false, "up", now),
+            Arrival::Held
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(1), false, "down", now),
+            Arrival::Ready(vec!["down", "up"])
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(1), false, "down", now),
+            Arrival::Replayed
+        );
+    }
+
+    #[test]
+    fn test_lost_command_is_skipped_after_max_delay() {
+        let buffer = buffer();
+        let now = Instant::now();
+        buffer.accept(client(), Some(0), false, "move", now);
+        assert_eq!(
+            buffer.accept(client(), Some(2), false, "up", now),
+            Arrival::Held
+        );
+        assert_eq!(
+            buffer.next_deadline(),
+            Some(now + Duration::from_millis(30))
+        );
+        assert!(buffer.expire(now + Duration::from_millis(29)).is_empty());
+        assert_eq!(
+            buffer.expire(now + Duration::from_millis(30)),
+            vec![(client(), "up")]
+        );
+        assert_eq!(buffer.next_deadline(), None);
+        assert_eq!(
+            buffer.accept(client(), Some(1), false, "down", now),
+            Arrival::Replayed
+        );
+    }
+
+    #[test]
+    fn test_full_window_gives_up_on_the_gap() {
+        let buffer = buffer();
+        let now = Instant::now();
+        buffer.accept(client(), Some(0), false, "a", now);
+        for (seq, item) in [(2, "c"), (3, "d"), (4, "e")] {
+            assert_eq!(
+                buffer.accept(client(), Some(seq), false, item, now),
+                Arrival::Held
+            );
+        }
+        assert_eq!(
+            buffer.accept(client(), Some(5), false, "f", now),
+            Arrival::Ready(vec!["c", "d", "e", "f"])
+        );
+    }
+
+    #[test]
+    fn test_restarted_counter_starts_a_new_stream() {
+        let buffer = buffer();
+        let now = Instant::now();
+        buffer.accept(client(), Some(500), false, "old", now);
+        assert_eq!(
+            buffer.accept(client(), Some(0), false, "new", now),
+            Arrival::Ready(vec!["new"])
+        );
+    }
+
+    #[test]
+    fn test_unsequenced_passes_through() {
+        let buffer = buffer();
+        let now = Instant::now();
+        buffer.accept(client(), Some(0), false, "a", now);
+        assert_eq!(
+            buffer.accept(client(), None, false, "b", now),
+            Arrival::Ready(vec!["b"])
+        );
+    }
+
+    #[test]
+    fn test_without_reordering_late_commands_run_and_duplicates_drop() {
+        let buffer = ReorderBuffer::new(ReorderSettings::default());
+        let now = Instant::now();
+        assert_eq!(
+            buffer.accept(client(), Some(5), false, "click", now),
+            Arrival::Ready(vec!["click"])
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(3), false, "move", now),
+            Arrival::Ready(vec!["move"])
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(5), false, "click", now),
+            Arrival::Replayed
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(3), false, "move", now),
+            Arrival::Replayed
+        );
+        assert_eq!(buffer.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_reliable_retransmit_is_dropped_with_deduplication_off() {
+        let buffer = ReorderBuffer::new(ReorderSettings {
+            deduplicate: false,
+            ..ReorderSettings::default()
+        });
+        let now = Instant::now();
+        buffer.accept(client(), Some(1), true, "click", now);
+        assert_eq!(
+            buffer.accept(client(), Some(1), true, "click", now),
+            Arrival::Replayed
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(2), false, "move", now),
+            Arrival::Ready(vec!["move"])
+        );
+        assert_eq!(
+            buffer.accept(client(), Some(2), false, "move", now),
+            Arrival::Ready(vec!["move"])
+        );
+    }
+}
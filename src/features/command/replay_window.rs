@@ -0,0 +1,82 @@
+use crate::domain::config::ServerConfig;
+
+/// The highest sequence number seen and which of the ones just below it have arrived
+#[derive(Default, Debug)]
+pub struct ReplayWindow {
+    highest: Option<u64>,
+    /// Bit `n` is set once `highest - n` has been seen
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// Records `seq`, returning false for one already seen
+    pub fn admit(&mut self, seq: u64) -> bool {
+        self.record(seq, false)
+    }
+
+    /// Like `admit`, but never takes a seq far behind the highest for a restarted counter
+    ///
+    /// For senders that must keep counting up across restarts, so an old capture can't
+    /// be replayed once it has slid out of the window.
+    pub fn admit_strict(&mut self, seq: u64) -> bool {
+        self.record(seq, true)
+    }
+
+    fn record(&mut self, seq: u64, strict: bool) -> bool {
+        let Some(highest) = self.highest else {
+            *self = ReplayWindow {
+                highest: Some(seq),
+                seen: 1,
+            };
+            return true;
+        };
+        if seq > highest {
+            let shift = seq - highest;
+            self.seen = if shift >= u64::BITS as u64 {
+                0
+            } else {
+                self.seen << shift
+            } | 1;
+            self.highest = Some(seq);
+            return true;
+        }
+        let age = highest - seq;
+        if age >= ServerConfig::REPLAY_WINDOW {
+            if strict {
+                return false;
+            }
+            // Nothing delivers a packet this late; the client started counting again
+            *self = ReplayWindow::default();
+            return self.admit(seq);
+        }
+        let bit = 1 << age;
+        let fresh = self.seen & bit == 0;
+        self.seen |= bit;
+        fresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_window_slides_and_resets_far_behind() {
+        let mut window = ReplayWindow::default();
+        assert!(window.admit(100));
+        assert!(window.admit(200));
+        assert!(window.admit(150));
+        assert!(!window.admit(150));
+        assert!(window.admit(100));
+        assert!(!window.admit(100));
+    }
+
+    #[test]
+    fn test_strict_window_never_resets() {
+        let mut window = ReplayWindow::default();
+        assert!(window.admit_strict(1_000));
+        assert!(window.admit_strict(990));
+        assert!(!window.admit_strict(10));
+        assert!(window.admit_strict(1_001));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::domain::models::{CommandEnvelope, Scope};
+use crate::features::command::policy::CommandPolicy;
+use crate::features::command::validation;
+use crate::features::profile::profile_service::ProfileService;
+use crate::input::plan::{self, InputStep};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+
+/// What the server would do with a command, without doing it
+#[derive(Serialize, Debug)]
+pub struct Simulation {
+    pub command: &'static str,
+    pub scope: Option<Scope>,
+    pub allowed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denial: Option<String>,
+    /// Validation failure the sender would get back as an `Invalid` reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid: Option<String>,
+    /// Allowed, but only after the sender confirms it with a nonce
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub needs_confirmation: bool,
+    /// Profile whose transforms were applied to the steps
+    pub profile: Option<String>,
+    /// Events the backend would inject; `None` when the server handles the command itself
+    pub steps: Option<Vec<InputStep>>,
+}
+
+/// Runs `envelope` through the live policy and the active profile, then plans its injection
+///
+/// The policy sees a loopback sender, and the envelope's own `auth` decides the token checks.
+pub fn simulate(
+    envelope: &CommandEnvelope,
+    policy: &CommandPolicy,
+    profiles: &ProfileService,
+) -> Simulation {
+    let sender = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+    let (denial, needs_confirmation) = match policy.evaluate(envelope, sender) {
+        Ok(holder) => (None, policy.needs_confirmation(envelope, holder.as_ref())),
+        Err(denial) => (Some(denial.to_string()), false),
+    };
+    let invalid = validation::validate_envelope(envelope)
+        .err()
+        .map(|invalid| invalid.to_string());
+    let command = profiles.adjust(envelope.command.clone());
+    Simulation {
+        command: command.kind(),
+        scope: command.scope(),
+        allowed: denial.is_none() && invalid.is_none(),
+        denial,
+        invalid,
+        needs_confirmation,
+        profile: profiles.active_name(),
+        steps: plan::plan(&command),
+    }
+}
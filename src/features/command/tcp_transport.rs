@@ -0,0 +1,163 @@
+use crate::domain::config::ServerConfig;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Reply queues of the connected clients, by their address
+type Peers = Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>>>;
+
+/// Ordered, reliable command delivery for clients on lossy Wi-Fi
+///
+/// Carries the same JSON envelopes and replies as the UDP socket, one per line. Binary
+/// formats could contain a newline, so `SelectFormat` keeps TCP clients on JSON.
+pub struct TcpTransport {
+    listener: TcpListener,
+    peers: Peers,
+}
+
+impl TcpTransport {
+    pub async fn bind() -> Result<Self> {
+        let port = ServerConfig::COMMAND_TCP_PORT;
+        let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))
+            .await
+            .with_context(|| format!("Failed to bind command TCP port {}", port))?;
+        Ok(Self {
+            listener,
+            peers: Arc::default(),
+        })
+    }
+
+    /// Accepts clients that `admits` lets in and forwards every line they send to `inbox`
+    pub async fn run(
+        &self,
+        inbox: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+        admits: impl Fn(SocketAddr) -> bool,
+    ) {
+        loop {
+            match self.listener.accept().await {
+                Ok((_, addr)) if !admits(addr) => {}
+                Ok((stream, addr)) => {
+                    log::info!("Command client {} connected over TCP", addr);
+                    tokio::spawn(serve(stream, addr, inbox.clone(), self.peers.clone()));
+                }
+                Err(e) => log::error!("Command TCP accept error: {}", e),
+            }
+        }
+    }
+
+    /// Whether `addr` is connected over TCP
+    pub fn serves(&self, addr: SocketAddr) -> bool {
+        lock_peers(&self.peers).contains_key(&addr)
+    }
+
+    /// Queues `message` for `addr`; `false` when `addr` isn't a TCP client
+    pub fn send(&self, addr: SocketAddr, message: Vec<u8>) -> bool {
+        let Some(peer) = lock_peers(&self.peers).get(&addr).cloned() else {
+            return false;
+        };
+        if peer.try_send(message).is_err() {
+            log::warn!("Dropping reply to TCP client {}; it stopped reading", addr);
+        }
+        true
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    addr: SocketAddr,
+    inbox: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+    peers: Peers,
+) {
+    // Keystrokes are tiny; waiting to batch them only adds latency
+    if let Err(e) = stream.set_nodelay(true) {
+        log::debug!("Could not disable Nagle for {}: {}", addr, e);
+    }
+    let (read, mut write) = stream.into_split();
+    let (outbox, mut replies) = mpsc::channel::<Vec<u8>>(ServerConfig::TCP_REPLY_QUEUE);
+    lock_peers(&peers).insert(addr, outbox);
+    let writer = tokio::spawn(async move {
+        while let Some(mut reply) = replies.recv().await {
+            reply.push(b'\n');
+            if write.write_all(&reply).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let limit = ServerConfig::COMMAND_BUFFER_SIZE as u64;
+    let mut reader = BufReader::new(read);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match (&mut reader)
+            .take(limit + 1)
+            .read_until(b'\n', &mut line)
+            .await
+        {
+            Ok(0) => break,
+            Ok(size) if size as u64 > limit => {
+                log::warn!("TCP client {} sent a line over {} bytes", addr, limit);
+                break;
+            }
+            Ok(_) => {
+                let message = line.trim_ascii();
+                if message.is_empty() {
+                    continue;
+                }
+                if inbox.send((message.to_vec(), addr)).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                log::debug!("TCP client {} read error: {}", addr, e);
+                break;
+            }
+        }
+    }
+
+    lock_peers(&peers).remove(&addr);
+    writer.abort();
+    log::info!("Command client {} disconnected from TCP", addr);
+}
+
+fn lock_peers(peers: &Peers) -> MutexGuard<'_, HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>> {
+    peers.lock().expect("TCP peers mutex poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+
+    #[tokio::test]
+    async fn test_lines_reach_inbox_and_replies_come_back() {
+        let transport = Arc::new(TcpTransport {
+            listener: TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            peers: Arc::default(),
+        });
+        let server_addr = transport.listener.local_addr().unwrap();
+        let (inbox, mut messages) = mpsc::channel(4);
+        let running = transport.clone();
+        tokio::spawn(async move { running.run(inbox, |_| true).await });
+
+        let mut client = TcpStream::connect(server_addr).await.unwrap();
+        client
+            .write_all(b"{\"type\":\"SelectAll\"}\n\n{\"type\":\"EndGroup\"}\n")
+            .await
+            .unwrap();
+        let (first, addr) = messages.recv().await.unwrap();
+        let (second, _) = messages.recv().await.unwrap();
+        assert_eq!(first, b"{\"type\":\"SelectAll\"}");
+        assert_eq!(second, b"{\"type\":\"EndGroup\"}");
+
+        assert!(transport.send(addr, b"{\"type\":\"Denied\"}".to_vec()));
+        assert!(!transport.send(server_addr, Vec::new()));
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).await.unwrap();
+        assert_eq!(reply, "{\"type\":\"Denied\"}\n");
+    }
+}
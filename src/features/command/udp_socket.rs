@@ -0,0 +1,35 @@
+use crate::domain::config::settings::NetworkSettings;
+use anyhow::Result;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+/// Binds a broadcast-capable UDP socket with the configured buffer and QoS options
+///
+/// Buffer and DSCP are best effort: the OS may cap the one and ignore the other.
+pub fn bind(addr: SocketAddr, network: &NetworkSettings, reuse_address: bool) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if reuse_address {
+        socket.set_reuse_address(true)?;
+    }
+    if let Some(bytes) = network.recv_buffer_bytes {
+        if let Err(e) = socket.set_recv_buffer_size(bytes) {
+            log::warn!(
+                "Could not set receive buffer to {} bytes on {}: {}",
+                bytes,
+                addr,
+                e
+            );
+        }
+    }
+    if let Some(dscp) = network.dscp {
+        // DSCP occupies the upper six bits of the TOS byte
+        if let Err(e) = socket.set_tos_v4(u32::from(dscp & 0x3f) << 2) {
+            log::warn!("Could not set DSCP {} on {}: {}", dscp, addr, e);
+        }
+    }
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
@@ -0,0 +1,314 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{Command, CommandEnvelope, Smoothing, TouchGesture};
+
+/// Why a well-formed command carries a value the backends can't inject
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum InvalidCommand {
+    #[error("{field} must be a finite number")]
+    NotFinite { field: &'static str },
+    #[error("{field} {value} is outside ±{max}")]
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        max: f64,
+    },
+    #[error(
+        "button {button} is not supported; use 1 (left), 2 (right), 3 (middle), 4 (back) or 5 (forward)"
+    )]
+    UnsupportedButton { button: u8 },
+    #[error("{field} must not be empty")]
+    Empty { field: &'static str },
+    #[error("{field} is longer than {max} characters")]
+    TooLong { field: &'static str, max: usize },
+    #[error("{field} must be {expected}")]
+    Unexpected {
+        field: &'static str,
+        expected: &'static str,
+    },
+}
+
+impl InvalidCommand {
+    /// Wire name of the offending field
+    pub fn field(&self) -> &'static str {
+        match self {
+            InvalidCommand::NotFinite { field }
+            | InvalidCommand::OutOfRange { field, .. }
+            | InvalidCommand::Empty { field }
+            | InvalidCommand::TooLong { field, .. }
+            | InvalidCommand::Unexpected { field, .. } => field,
+            InvalidCommand::UnsupportedButton { .. } => "button",
+        }
+    }
+}
+
+/// Checks the command, and that a `reliable` one has a `seq` to acknowledge
+pub fn validate_envelope(envelope: &CommandEnvelope) -> Result<(), InvalidCommand> {
+    if envelope.reliable && envelope.seq.is_none() {
+        return Err(InvalidCommand::Unexpected {
+            field: "seq",
+            expected: "set on reliable commands",
+        });
+    }
+    validate(&envelope.command)
+}
+
+/// Checks the numbers and names in `command` before they reach an OS API
+pub fn validate(command: &Command) -> Result<(), InvalidCommand> {
+    match command {
+        Command::MouseMove { x, y } => {
+            finite("x", *x)?;
+            finite("y", *y)
+        }
+        Command::MouseScroll { delta_x, delta_y } => {
+            within("delta_x", *delta_x, ServerConfig::MAX_SCROLL_DELTA)?;
+            within("delta_y", *delta_y, ServerConfig::MAX_SCROLL_DELTA)
+        }
+        Command::MoveToDisplay { x, y, .. } => {
+            fraction("x", *x)?;
+            fraction("y", *y)
+        }
+        Command::Drag { button, dx, dy, .. } => {
+            supported_button(*button)?;
+            finite("dx", *dx)?;
+            finite("dy", *dy)
+        }
+        Command::TouchGesture { gesture, .. } => valid_gesture(gesture),
+        Command::SetCursorSize { scale } => finite("scale", *scale),
+        Command::SetSmoothing { smoothing } => valid_smoothing(smoothing),
+        Command::MouseClick { button }
+        | Command::MouseMultiClick { button, .. }
+        | Command::MouseLongPress { button, .. }
+        | Command::MouseDown { button }
+        | Command::MouseUp { button } => supported_button(*button),
+        Command::KeyPress { key, .. } | Command::KeyRelease { key, .. } => not_empty("key", key),
+        Command::ModifierPress { modifier } | Command::ModifierRelease { modifier } => {
+            not_empty("modifier", modifier)
+        }
+        Command::BrightnessSet { percent } if *percent > ServerConfig::MAX_BRIGHTNESS_PERCENT => {
+            Err(InvalidCommand::Unexpected {
+                field: "percent",
+                expected: "at most 100",
+            })
+        }
+        Command::TypeText { text } => {
+            not_empty("text", text)?;
+            at_most("text", text, ServerConfig::MAX_TYPE_TEXT_CHARS)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn finite(field: &'static str, value: f64) -> Result<(), InvalidCommand> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(InvalidCommand::NotFinite { field })
+    }
+}
+
+fn within(field: &'static str, value: f64, max: f64) -> Result<(), InvalidCommand> {
+    finite(field, value)?;
+    if value.abs() <= max {
+        Ok(())
+    } else {
+        Err(InvalidCommand::OutOfRange { field, value, max })
+    }
+}
+
+fn fraction(field: &'static str, value: f64) -> Result<(), InvalidCommand> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(InvalidCommand::Unexpected {
+            field,
+            expected: "from 0 to 1",
+        })
+    }
+}
+
+fn supported_button(button: u8) -> Result<(), InvalidCommand> {
+    if (1..=ServerConfig::MAX_MOUSE_BUTTON).contains(&button) {
+        Ok(())
+    } else {
+        Err(InvalidCommand::UnsupportedButton { button })
+    }
+}
+
+fn valid_smoothing(smoothing: &Smoothing) -> Result<(), InvalidCommand> {
+    let (field, valid, expected) = match *smoothing {
+        Smoothing::Off => return Ok(()),
+        Smoothing::Ema { alpha } => (
+            "alpha",
+            alpha > 0.0 && alpha <= 1.0,
+            "above 0 and at most 1",
+        ),
+        Smoothing::OneEuro { min_cutoff, beta } => {
+            finite("beta", beta)?;
+            if beta < 0.0 {
+                return Err(InvalidCommand::Unexpected {
+                    field: "beta",
+                    expected: "0 or more",
+                });
+            }
+            (
+                "min_cutoff",
+                min_cutoff > 0.0 && min_cutoff.is_finite(),
+                "a positive frequency",
+            )
+        }
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidCommand::Unexpected { field, expected })
+    }
+}
+
+fn valid_gesture(gesture: &TouchGesture) -> Result<(), InvalidCommand> {
+    match *gesture {
+        TouchGesture::Pinch { scale } if !(scale > 0.0 && scale.is_finite()) => {
+            Err(InvalidCommand::Unexpected {
+                field: "scale",
+                expected: "a positive number",
+            })
+        }
+        TouchGesture::Pinch { .. } => Ok(()),
+        TouchGesture::Rotate { degrees } => finite("degrees", degrees),
+        TouchGesture::Swipe { dx, dy, fingers } => {
+            finite("dx", dx)?;
+            finite("dy", dy)?;
+            if (1..=ServerConfig::GESTURE_MAX_FINGERS).contains(&fingers) {
+                Ok(())
+            } else {
+                Err(InvalidCommand::Unexpected {
+                    field: "fingers",
+                    expected: "from 1 to 5",
+                })
+            }
+        }
+    }
+}
+
+fn not_empty(field: &'static str, value: &str) -> Result<(), InvalidCommand> {
+    if value.is_empty() {
+        Err(InvalidCommand::Empty { field })
+    } else {
+        Ok(())
+    }
+}
+
+fn at_most(field: &'static str, value: &str, max: usize) -> Result<(), InvalidCommand> {
+    if value.chars().count() <= max {
+        Ok(())
+    } else {
+        Err(InvalidCommand::TooLong { field, max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_ordinary_commands() {
+        assert!(validate(&Command::MouseMove { x: -12.5, y: 3.0 }).is_ok());
+        assert!(validate(&Command::MouseClick { button: 3 }).is_ok());
+        assert!(validate(&Command::SelectAll).is_ok());
+    }
+
+    #[test]
+    fn test_reliable_command_needs_seq() {
+        let envelope: CommandEnvelope =
+            serde_json::from_str(r#"{"type":"MouseClick","button":1,"reliable":true}"#).unwrap();
+        assert_eq!(validate_envelope(&envelope).unwrap_err().field(), "seq");
+        let envelope = CommandEnvelope {
+            seq: Some(4),
+            ..envelope
+        };
+        assert!(validate_envelope(&envelope).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_garbage_numbers() {
+        let nan = validate(&Command::MouseMove {
+            x: 0.0,
+            y: f64::NAN,
+        });
+        assert_eq!(nan, Err(InvalidCommand::NotFinite { field: "y" }));
+
+        let scroll = validate(&Command::MouseScroll {
+            delta_x: 0.0,
+            delta_y: 1e9,
+        })
+        .unwrap_err();
+        assert_eq!(scroll.field(), "delta_y");
+
+        let button = validate(&Command::MouseDown { button: 0 }).unwrap_err();
+        assert_eq!(button, InvalidCommand::UnsupportedButton { button: 0 });
+        assert!(validate(&Command::MouseClick { button: 5 }).is_ok());
+        assert_eq!(
+            validate(&Command::MouseUp { button: 6 }),
+            Err(InvalidCommand::UnsupportedButton { button: 6 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_unusable_smoothing() {
+        let ema = validate(&Command::SetSmoothing {
+            smoothing: Smoothing::Ema { alpha: 0.0 },
+        })
+        .unwrap_err();
+        assert_eq!(ema.field(), "alpha");
+        assert!(validate(&Command::SetSmoothing {
+            smoothing: Smoothing::OneEuro {
+                min_cutoff: 1.0,
+                beta: 0.007,
+            },
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn test_rejects_empty_keys() {
+        let key = validate(&Command::KeyPress {
+            key: String::new(),
+            modifiers: Default::default(),
+        });
+        assert_eq!(key, Err(InvalidCommand::Empty { field: "key" }));
+    }
+
+    #[test]
+    fn test_rejects_brightness_over_100() {
+        assert!(validate(&Command::BrightnessSet { percent: 100 }).is_ok());
+        let brightness = validate(&Command::BrightnessSet { percent: 101 }).unwrap_err();
+        assert_eq!(brightness.field(), "percent");
+    }
+
+    #[test]
+    fn test_display_position_is_a_fraction() {
+        let at = |x: f64| Command::MoveToDisplay {
+            display: 0,
+            x,
+            y: 1.0,
+        };
+        assert!(validate(&at(0.0)).is_ok());
+        assert_eq!(validate(&at(1.5)).unwrap_err().field(), "x");
+    }
+
+    #[test]
+    fn test_limits_typed_text() {
+        let max = ServerConfig::MAX_TYPE_TEXT_CHARS;
+        let text = |len: usize| Command::TypeText {
+            text: "é".repeat(len),
+        };
+        assert!(validate(&text(max)).is_ok());
+        assert_eq!(
+            validate(&text(max + 1)),
+            Err(InvalidCommand::TooLong { field: "text", max })
+        );
+        assert_eq!(
+            validate(&text(0)),
+            Err(InvalidCommand::Empty { field: "text" })
+        );
+    }
+}
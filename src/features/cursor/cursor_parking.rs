@@ -0,0 +1,127 @@
+use crate::domain::config::settings::{CursorParkingSettings, ParkingSpot};
+use crate::domain::config::ServerConfig;
+use crate::input::pointer::DesktopBounds;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Decides when to move the cursor to its parking spot and where to put it back
+///
+/// Parking waits for a remote command after startup or the last parking, so it never
+/// grabs a cursor that only the local user has been moving.
+pub struct CursorParking {
+    settings: CursorParkingSettings,
+    state: Mutex<ParkingState>,
+}
+
+#[derive(Default)]
+struct ParkingState {
+    last_activity: Option<Instant>,
+    parked_from: Option<(f64, f64)>,
+}
+
+impl CursorParking {
+    pub fn new(settings: CursorParkingSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(ParkingState::default()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.settings.idle_secs.is_some()
+    }
+
+    /// Notes remote activity; returns where the pointer was before it got parked
+    pub fn wake(&self, now: Instant) -> Option<(f64, f64)> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut state = self.lock_state();
+        state.last_activity = Some(now);
+        state.parked_from.take()
+    }
+
+    /// The spot to move to when remote input has been idle long enough, remembering `position`
+    pub fn park(
+        &self,
+        now: Instant,
+        position: (f64, f64),
+        bounds: &DesktopBounds,
+    ) -> Option<(f64, f64)> {
+        let idle = Duration::from_secs(self.settings.idle_secs?);
+        let mut state = self.lock_state();
+        let last_activity = state.last_activity?;
+        if now.saturating_duration_since(last_activity) < idle {
+            return None;
+        }
+        state.last_activity = None;
+        state.parked_from = Some(position);
+        Some(self.spot(bounds))
+    }
+
+    fn spot(&self, bounds: &DesktopBounds) -> (f64, f64) {
+        let inset = ServerConfig::CURSOR_PARKING_INSET_PX;
+        let left = bounds.left + inset;
+        let right = bounds.right - 1.0 - inset;
+        let top = bounds.top + inset;
+        let bottom = bounds.bottom - 1.0 - inset;
+        let spot = match self.settings.spot {
+            ParkingSpot::TopLeft => (left, top),
+            ParkingSpot::TopRight => (right, top),
+            ParkingSpot::BottomLeft => (left, bottom),
+            ParkingSpot::BottomRight => (right, bottom),
+        };
+        bounds.clamp(spot)
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, ParkingState> {
+        self.state.lock().expect("Cursor parking mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parking() -> CursorParking {
+        CursorParking::new(CursorParkingSettings {
+            idle_secs: Some(10),
+            spot: ParkingSpot::BottomRight,
+        })
+    }
+
+    #[test]
+    fn test_parks_after_idle_and_restores_on_activity() {
+        let parking = parking();
+        let bounds = DesktopBounds::fallback();
+        let start = Instant::now();
+        assert_eq!(parking.wake(start), None);
+
+        assert_eq!(
+            parking.park(start + Duration::from_secs(5), (400.0, 300.0), &bounds),
+            None
+        );
+        let spot = parking.park(start + Duration::from_secs(10), (400.0, 300.0), &bounds);
+        assert_eq!(spot, Some((1917.0, 1077.0)));
+        assert_eq!(
+            parking.park(start + Duration::from_secs(60), (1917.0, 1077.0), &bounds),
+            None
+        );
+
+        assert_eq!(
+            parking.wake(start + Duration::from_secs(61)),
+            Some((400.0, 300.0))
+        );
+        assert_eq!(parking.wake(start + Duration::from_secs(62)), None);
+    }
+
+    #[test]
+    fn test_never_parks_before_any_remote_activity() {
+        let parking = parking();
+        let later = Instant::now() + Duration::from_secs(3600);
+        assert_eq!(
+            parking.park(later, (10.0, 10.0), &DesktopBounds::fallback()),
+            None
+        );
+    }
+}
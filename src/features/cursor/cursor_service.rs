@@ -0,0 +1,133 @@
+use crate::domain::config::ServerConfig;
+#[cfg(windows)]
+use crate::features::service::agent_link;
+use crate::input::cursor_size;
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Temporary cursor enlargement for presenters, undone when the client that asked goes quiet
+pub struct CursorService {
+    active: Mutex<Option<CursorOverride>>,
+}
+
+struct CursorOverride {
+    original: f64,
+    owner: SocketAddr,
+    last_seen: Instant,
+}
+
+impl CursorOverride {
+    fn expired(&self, now: Instant) -> bool {
+        now.duration_since(self.last_seen)
+            >= Duration::from_secs(ServerConfig::CURSOR_RESTORE_IDLE_SECS)
+    }
+}
+
+impl CursorService {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(None),
+        }
+    }
+
+    /// Applies `scale`, remembering the size to restore the first time it changes
+    pub async fn set_scale(&self, scale: f64, addr: SocketAddr) -> Result<()> {
+        let scale = scale.clamp(1.0, ServerConfig::CURSOR_MAX_SCALE);
+        let known = self.lock_active().as_ref().map(|current| current.original);
+        let original = match known {
+            Some(original) => original,
+            None => current_scale().await?,
+        };
+
+        apply_scale(scale).await?;
+        let mut active = self.lock_active();
+        // Another client's first change may have recorded the original meanwhile
+        let original = active.as_ref().map_or(original, |current| current.original);
+        *active = Some(CursorOverride {
+            original,
+            owner: addr,
+            last_seen: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Notes activity from `addr`, keeping its override alive
+    pub fn touch(&self, addr: SocketAddr) {
+        if let Some(current) = self.lock_active().as_mut() {
+            if current.owner == addr {
+                current.last_seen = Instant::now();
+            }
+        }
+    }
+
+    /// Restores the original size once the owning client has been silent long enough
+    pub async fn run(&self) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(ServerConfig::CURSOR_RESTORE_CHECK_SECS));
+        loop {
+            interval.tick().await;
+            let expired = {
+                let mut active = self.lock_active();
+                match active.as_ref() {
+                    Some(current) if current.expired(Instant::now()) => active.take(),
+                    _ => None,
+                }
+            };
+            let Some(current) = expired else {
+                continue;
+            };
+            log::info!("Client {} went quiet; restoring cursor size", current.owner);
+            if let Err(e) = apply_scale(current.original).await {
+                log::warn!("Failed to restore cursor size: {}", e);
+            }
+        }
+    }
+
+    fn lock_active(&self) -> std::sync::MutexGuard<'_, Option<CursorOverride>> {
+        self.active.lock().expect("Cursor override mutex poisoned")
+    }
+}
+
+/// The size in the user's session, which a Windows service reaches through its agent
+///
+/// Linux runs gsettings and the others make a system call, so neither runs on the runtime.
+async fn current_scale() -> Result<f64> {
+    #[cfg(windows)]
+    if let Some(agent) = agent_link::installed() {
+        return agent.cursor_scale().await;
+    }
+    tokio::task::spawn_blocking(cursor_size::current_scale).await?
+}
+
+async fn apply_scale(scale: f64) -> Result<()> {
+    #[cfg(windows)]
+    if let Some(agent) = agent_link::installed() {
+        return agent.set_cursor_scale(scale).await;
+    }
+    tokio::task::spawn_blocking(move || cursor_size::set_scale(scale)).await?
+}
+
+impl Default for CursorService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_expires_after_idle_window() {
+        let now = Instant::now();
+        let current = CursorOverride {
+            original: 1.0,
+            owner: SocketAddr::from(([192, 168, 1, 5], 50000)),
+            last_seen: now,
+        };
+        assert!(!current.expired(now + Duration::from_secs(1)));
+        assert!(current.expired(now + Duration::from_secs(ServerConfig::CURSOR_RESTORE_IDLE_SECS)));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod cursor_parking;
+pub mod cursor_service;
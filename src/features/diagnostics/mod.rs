@@ -0,0 +1,3 @@
+pub mod process_profile;
+pub mod soak_test;
+pub mod support_bundle;
@@ -0,0 +1,130 @@
+#[cfg(all(feature = "profiling", unix))]
+use crate::domain::config::ServerConfig;
+use serde::Serialize;
+use std::time::Duration;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// Resource usage of the server process over a sampling window
+#[derive(Serialize, Debug, Clone)]
+pub struct ProcessProfile {
+    pub sample_ms: u64,
+    /// Percent of a single core; can exceed 100 on multi-core hosts
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+    pub virtual_bytes: u64,
+    pub runtime: RuntimeProfile,
+}
+
+/// Tokio scheduler counters at the end of the window
+#[derive(Serialize, Debug, Clone)]
+pub struct RuntimeProfile {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+}
+
+/// Samples this process for `window`, which is raised to the minimum the OS can measure CPU over
+pub async fn sample(window: Duration) -> Option<ProcessProfile> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let window = window.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    let refresh = ProcessRefreshKind::nothing().with_cpu().with_memory();
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, refresh);
+    tokio::time::sleep(window).await;
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, refresh);
+
+    let process = system.process(pid)?;
+    Some(ProcessProfile {
+        sample_ms: window.as_millis() as u64,
+        cpu_percent: process.cpu_usage(),
+        rss_bytes: process.memory(),
+        virtual_bytes: process.virtual_memory(),
+        runtime: runtime_profile(),
+    })
+}
+
+/// Outcome of a flamegraph capture
+#[cfg(all(feature = "profiling", unix))]
+pub enum Flamegraph {
+    Svg(Vec<u8>),
+    /// No thread used any CPU during the window
+    Idle,
+    /// Another capture is in progress; only one can run at a time
+    Busy,
+}
+
+/// CPU flamegraph of every thread in the process over `window`
+///
+/// Starting the profiler and symbolizing its samples both take a while, so they run on
+/// the blocking pool.
+#[cfg(all(feature = "profiling", unix))]
+pub async fn flamegraph(window: Duration) -> anyhow::Result<Flamegraph> {
+    let started = tokio::task::spawn_blocking(|| {
+        pprof::ProfilerGuardBuilder::default()
+            .frequency(ServerConfig::FLAMEGRAPH_SAMPLE_HZ)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+    })
+    .await?;
+    let Ok(guard) = started else {
+        return Ok(Flamegraph::Busy);
+    };
+    tokio::time::sleep(window).await;
+    tokio::task::spawn_blocking(move || {
+        let report = guard.report().build()?;
+        if report.data.is_empty() {
+            return Ok(Flamegraph::Idle);
+        }
+        let mut svg = Vec::new();
+        report.flamegraph(&mut svg)?;
+        Ok(Flamegraph::Svg(svg))
+    })
+    .await?
+}
+
+fn runtime_profile() -> RuntimeProfile {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    RuntimeProfile {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sample_reports_own_memory() {
+        let profile = sample(Duration::ZERO).await.unwrap();
+        assert!(profile.rss_bytes > 0);
+        assert!(profile.sample_ms >= sysinfo::MINIMUM_CPU_UPDATE_INTERVAL.as_millis() as u64);
+    }
+
+    #[cfg(all(feature = "profiling", unix))]
+    #[tokio::test]
+    async fn test_flamegraph_renders_busy_threads() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let spinning = tokio::task::spawn_blocking({
+            let done = done.clone();
+            // Pure arithmetic, since samples landing in the vDSO clock are dropped
+            move || {
+                while !done.load(Ordering::Relaxed) {
+                    (0..100_000u64).fold(0, |sum, n| std::hint::black_box(sum ^ n));
+                }
+            }
+        });
+        let capture = flamegraph(Duration::from_millis(250)).await.unwrap();
+        done.store(true, Ordering::Relaxed);
+        spinning.await.unwrap();
+        let Flamegraph::Svg(svg) = capture else {
+            panic!("expected a flamegraph of the spinning thread");
+        };
+        assert!(String::from_utf8_lossy(&svg).contains("<svg"));
+    }
+}
@@ -0,0 +1,506 @@
+//! `pointzerver soak`: pings an instance for hours and reports drops and latency
+//!
+//! Probes are `Ping` commands, which the server answers off its queue, so the numbers are the
+//! network's rather than input injection's. Each `Pong` carries the server's clock, which
+//! splits every round trip into its two directions. The offset between the clocks comes from
+//! the fastest round trip, where queuing was least and the halves were closest to even.
+
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{Command, CommandEnvelope, Reply};
+use crate::utils::{self, paths};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::MissedTickBehavior;
+
+const USAGE: &str = "Usage: pointzerver soak [--target host[:port]] [--minutes N] \
+                     [--interval-ms N] [--token TOKEN] [--report report.json]";
+
+/// What `pointzerver soak` was asked to do
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoakOptions {
+    /// `host` or `host:port`; the command port is assumed without one
+    pub target: String,
+    pub duration: Duration,
+    /// Time between probes
+    pub interval: Duration,
+    /// Sent with every probe; defaults to the local admin token for loopback targets
+    pub token: Option<String>,
+    pub report: PathBuf,
+}
+
+impl Default for SoakOptions {
+    fn default() -> Self {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        Self {
+            target: Ipv4Addr::LOCALHOST.to_string(),
+            duration: Duration::from_secs(ServerConfig::SOAK_DEFAULT_MINUTES * 60),
+            interval: Duration::from_millis(ServerConfig::SOAK_DEFAULT_INTERVAL_MS),
+            token: None,
+            report: PathBuf::from(format!("pointzerver-soak-{}.json", stamp)),
+        }
+    }
+}
+
+impl SoakOptions {
+    /// Reads the arguments after `soak`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("{} needs a value\n{}", flag, USAGE))?;
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| anyhow!("{} must be a positive number", flag))
+            };
+            match flag.as_str() {
+                "--target" => options.target = value.clone(),
+                "--minutes" => options.duration = Duration::from_secs(number()? * 60),
+                "--interval-ms" => options.interval = Duration::from_millis(number()?),
+                "--token" => options.token = Some(value.clone()),
+                "--report" => options.report = PathBuf::from(&value),
+                _ => bail!("Unknown option {}\n{}", flag, USAGE),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Written to `--report` at the end of a run
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SoakReport {
+    pub target: String,
+    pub started_at: u64,
+    pub duration_secs: u64,
+    pub interval_ms: u64,
+    pub sent: u64,
+    pub received: u64,
+    pub lost: u64,
+    pub loss_percent: f64,
+    pub rtt_us: Option<Percentiles>,
+    /// One-way delays, from the clock offset the fastest round trip implies
+    pub upstream_ms: Option<Percentiles>,
+    pub downstream_ms: Option<Percentiles>,
+    /// The run in `SOAK_WINDOW_SECS` slices, to spot when the network went bad
+    pub windows: Vec<SoakWindow>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SoakWindow {
+    /// Seconds into the run the slice starts
+    pub start_secs: u64,
+    pub sent: u64,
+    pub lost: u64,
+    pub rtt_us: Option<Percentiles>,
+}
+
+/// A probe that was answered
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Seconds into the run it was sent
+    at_secs: u64,
+    rtt: Duration,
+    sent_ms: u64,
+    server_ms: u64,
+}
+
+/// A probe on its way
+struct InFlight {
+    sent: Instant,
+    sent_ms: u64,
+}
+
+/// Probes sent so far and what became of them
+struct Probes {
+    started: Instant,
+    /// Keyed by the `client_time_ms` each `Pong` echoes
+    in_flight: HashMap<u64, InFlight>,
+    samples: Vec<Sample>,
+    /// Seconds into the run each lost probe was sent
+    lost_at: Vec<u64>,
+    last_id: u64,
+}
+
+impl Probes {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            in_flight: HashMap::new(),
+            samples: Vec::new(),
+            lost_at: Vec::new(),
+            last_id: 0,
+        }
+    }
+
+    /// The next probe's `Ping`, now counted as in flight
+    fn next(&mut self, token: Option<String>) -> CommandEnvelope {
+        // The id doubles as the send time, kept unique when probes share a millisecond
+        let sent_ms = utils::unix_now_ms();
+        let id = sent_ms.max(self.last_id + 1);
+        self.last_id = id;
+        self.in_flight.insert(
+            id,
+            InFlight {
+                sent: Instant::now(),
+                sent_ms,
+            },
+        );
+        CommandEnvelope {
+            auth: token,
+            seq: None,
+            sent_at_ms: None,
+            client_sent_at_ms: None,
+            reliable: false,
+            confirm: None,
+            signed: false,
+            encrypted: false,
+            command: Command::Ping {
+                client_time_ms: id,
+                previous: None,
+            },
+        }
+    }
+
+    fn answered(&mut self, client_time_ms: u64, server_time_ms: u64) {
+        if let Some(probe) = self.in_flight.remove(&client_time_ms) {
+            self.samples.push(Sample {
+                at_secs: probe.sent.duration_since(self.started).as_secs(),
+                rtt: probe.sent.elapsed(),
+                sent_ms: probe.sent_ms,
+                server_ms: server_time_ms,
+            });
+        }
+    }
+
+    /// Gives up on probes older than `timeout`, or on all of them once the run is over
+    fn expire(&mut self, timeout: Duration) {
+        let started = self.started;
+        let lost_at = &mut self.lost_at;
+        self.in_flight.retain(|_, probe| {
+            let waiting = probe.sent.elapsed() < timeout;
+            if !waiting {
+                lost_at.push(probe.sent.duration_since(started).as_secs());
+            }
+            waiting
+        });
+    }
+
+    fn sent(&self) -> usize {
+        self.samples.len() + self.lost_at.len() + self.in_flight.len()
+    }
+
+    fn progress(&self) -> String {
+        let elapsed_secs = self.started.elapsed().as_secs();
+        let mut recent: Vec<u64> = self
+            .samples
+            .iter()
+            .rev()
+            .take_while(|sample| sample.at_secs + ServerConfig::SOAK_WINDOW_SECS > elapsed_secs)
+            .map(|sample| sample.rtt.as_micros() as u64)
+            .collect();
+        let median_us = percentiles(&mut recent).map_or(0, |p| p.p50);
+        format!(
+            "{:>4} min: {} of {} answered, {} lost, median {:.1} ms in the last minute",
+            elapsed_secs / 60,
+            self.samples.len(),
+            self.sent(),
+            self.lost_at.len(),
+            median_us as f64 / 1000.0
+        )
+    }
+}
+
+/// `pointzerver soak [options]`
+pub async fn run(options: SoakOptions) -> Result<()> {
+    let target = resolve(&options.target).await?;
+    let token = options.token.clone().or_else(|| {
+        target
+            .ip()
+            .is_loopback()
+            .then(|| std::fs::read_to_string(paths::admin_token_file()).ok())
+            .flatten()
+            .map(|token| token.trim().to_string())
+    });
+    let local = match target {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(target).await?;
+    println!(
+        "Soaking {} for {} minutes, one probe every {} ms; Ctrl+C ends early",
+        target,
+        options.duration.as_secs() / 60,
+        options.interval.as_millis()
+    );
+
+    let started_at = utils::unix_now();
+    let timeout = Duration::from_millis(ServerConfig::SOAK_REPLY_TIMEOUT_MS);
+    let mut probes = Probes::new();
+    let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+    let mut ticker = tokio::time::interval(options.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut progress = tokio::time::interval(Duration::from_secs(ServerConfig::SOAK_WINDOW_SECS));
+    progress.tick().await;
+    let ending = tokio::time::sleep(options.duration);
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ending, ctrl_c);
+
+    // Probe until the time is up, then wait out the last replies
+    let mut drain_until = None;
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if drain_until.is_none() => {
+                let probe = probes.next(token.clone());
+                socket.send(&serde_json::to_vec(&probe)?).await?;
+                probes.expire(timeout);
+            }
+            received = socket.recv(&mut buf) => {
+                let Ok(size) = received else { continue };
+                match serde_json::from_slice::<Reply>(&buf[..size]) {
+                    Ok(Reply::Pong { client_time_ms, server_time_ms }) => {
+                        probes.answered(client_time_ms, server_time_ms);
+                    }
+                    Ok(Reply::Denied { reason }) => {
+                        bail!("{} refused the probes: {}", target, reason);
+                    }
+                    _ => {}
+                }
+            }
+            _ = progress.tick(), if drain_until.is_none() => println!("{}", probes.progress()),
+            _ = &mut ending, if drain_until.is_none() => {
+                drain_until = Some(tokio::time::Instant::now() + timeout);
+            }
+            _ = &mut ctrl_c, if drain_until.is_none() => {
+                drain_until = Some(tokio::time::Instant::now() + timeout);
+            }
+            _ = tokio::time::sleep_until(drain_until.unwrap_or_else(tokio::time::Instant::now)),
+                if drain_until.is_some() => break,
+        }
+    }
+    probes.expire(Duration::ZERO);
+
+    let report = build_report(
+        &options,
+        started_at,
+        probes.started.elapsed(),
+        &probes.samples,
+        &probes.lost_at,
+    );
+    std::fs::write(&options.report, serde_json::to_vec_pretty(&report)?)
+        .with_context(|| format!("Failed to write {}", options.report.display()))?;
+    print_summary(&report);
+    println!("Report written to {}", options.report.display());
+    Ok(())
+}
+
+async fn resolve(target: &str) -> Result<SocketAddr> {
+    let target = match target.contains(':') {
+        true => target.to_string(),
+        false => format!("{}:{}", target, ServerConfig::COMMAND_PORT),
+    };
+    let mut addrs = tokio::net::lookup_host(&target)
+        .await
+        .with_context(|| format!("Could not resolve {}", target))?;
+    addrs
+        .next()
+        .ok_or_else(|| anyhow!("Could not resolve {}", target))
+}
+
+fn build_report(
+    options: &SoakOptions,
+    started_at: u64,
+    elapsed: Duration,
+    samples: &[Sample],
+    lost_at: &[u64],
+) -> SoakReport {
+    let received = samples.len() as u64;
+    let lost = lost_at.len() as u64;
+    let sent = received + lost;
+    let mut rtts: Vec<u64> = samples
+        .iter()
+        .map(|sample| sample.rtt.as_micros() as u64)
+        .collect();
+    let (mut upstream, mut downstream) = one_way_delays(samples);
+
+    let window_count = elapsed.as_secs() / ServerConfig::SOAK_WINDOW_SECS + 1;
+    let windows = (0..window_count)
+        .map(|index| {
+            let start_secs = index * ServerConfig::SOAK_WINDOW_SECS;
+            let within = |at: u64| at / ServerConfig::SOAK_WINDOW_SECS == index;
+            let mut rtts: Vec<u64> = samples
+                .iter()
+                .filter(|sample| within(sample.at_secs))
+                .map(|sample| sample.rtt.as_micros() as u64)
+                .collect();
+            let lost = lost_at.iter().filter(|at| within(**at)).count() as u64;
+            SoakWindow {
+                start_secs,
+                sent: rtts.len() as u64 + lost,
+                lost,
+                rtt_us: percentiles(&mut rtts),
+            }
+        })
+        .filter(|window| window.sent > 0)
+        .collect();
+
+    SoakReport {
+        target: options.target.clone(),
+        started_at,
+        duration_secs: elapsed.as_secs(),
+        interval_ms: options.interval.as_millis() as u64,
+        sent,
+        received,
+        lost,
+        loss_percent: match sent {
+            0 => 0.0,
+            _ => lost as f64 * 100.0 / sent as f64,
+        },
+        rtt_us: percentiles(&mut rtts),
+        upstream_ms: percentiles(&mut upstream),
+        downstream_ms: percentiles(&mut downstream),
+        windows,
+    }
+}
+
+/// Splits each round trip at the server's clock, offset so the fastest one splits evenly
+fn one_way_delays(samples: &[Sample]) -> (Vec<u64>, Vec<u64>) {
+    let Some(fastest) = samples.iter().min_by_key(|sample| sample.rtt) else {
+        return (Vec::new(), Vec::new());
+    };
+    let offset_ms =
+        fastest.server_ms as i64 - (fastest.sent_ms as i64 + fastest.rtt.as_millis() as i64 / 2);
+    samples
+        .iter()
+        .map(|sample| {
+            let rtt_ms = sample.rtt.as_millis() as i64;
+            let upstream =
+                (sample.server_ms as i64 - offset_ms - sample.sent_ms as i64).clamp(0, rtt_ms);
+            (upstream as u64, (rtt_ms - upstream) as u64)
+        })
+        .unzip()
+}
+
+fn percentiles(values: &mut [u64]) -> Option<Percentiles> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let percentile = |p: usize| values[(values.len() - 1) * p / 100];
+    Some(Percentiles {
+        p50: percentile(50),
+        p90: percentile(90),
+        p99: percentile(99),
+        max: values[values.len() - 1],
+    })
+}
+
+fn print_summary(report: &SoakReport) {
+    println!(
+        "{} probes to {}, {} lost ({:.2}%)",
+        report.sent, report.target, report.lost, report.loss_percent
+    );
+    if let Some(rtt) = report.rtt_us {
+        println!(
+            "Round trip: median {:.1} ms, p90 {:.1} ms, p99 {:.1} ms, max {:.1} ms",
+            rtt.p50 as f64 / 1000.0,
+            rtt.p90 as f64 / 1000.0,
+            rtt.p99 as f64 / 1000.0,
+            rtt.max as f64 / 1000.0
+        );
+    }
+    if let (Some(up), Some(down)) = (report.upstream_ms, report.downstream_ms) {
+        println!(
+            "One way, p99: {} ms to the server, {} ms back",
+            up.p99, down.p99
+        );
+    }
+    let worst = report
+        .windows
+        .iter()
+        .filter(|window| window.lost > 0)
+        .max_by_key(|window| window.lost);
+    if let Some(worst) = worst {
+        println!(
+            "Worst minute began {} min in, with {} of {} probes lost",
+            worst.start_secs / 60,
+            worst.lost,
+            worst.sent
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at_secs: u64, rtt_ms: u64, sent_ms: u64, server_ms: u64) -> Sample {
+        Sample {
+            at_secs,
+            rtt: Duration::from_millis(rtt_ms),
+            sent_ms,
+            server_ms,
+        }
+    }
+
+    #[test]
+    fn test_parses_options() {
+        let args = [
+            "--target",
+            "den-pc",
+            "--minutes",
+            "90",
+            "--interval-ms",
+            "50",
+        ];
+        let options = SoakOptions::parse(args.map(String::from)).unwrap();
+        assert_eq!(options.target, "den-pc");
+        assert_eq!(options.duration, Duration::from_secs(90 * 60));
+        assert_eq!(options.interval, Duration::from_millis(50));
+        assert!(SoakOptions::parse(["--minutes", "0"].map(String::from)).is_err());
+        assert!(SoakOptions::parse(["--target"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_report_counts_losses_per_window_and_splits_round_trips() {
+        // The server clock runs 1000 ms ahead; the second probe's extra delay is all upstream
+        let samples = [
+            sample(0, 10, 5_000, 6_005),
+            sample(1, 30, 6_000, 7_025),
+            sample(70, 10, 75_000, 76_005),
+        ];
+        let report = build_report(
+            &SoakOptions::default(),
+            0,
+            Duration::from_secs(80),
+            &samples,
+            &[65, 66],
+        );
+        assert_eq!((report.sent, report.received, report.lost), (5, 3, 2));
+        assert_eq!(report.loss_percent, 40.0);
+        assert_eq!(report.rtt_us.unwrap().max, 30_000);
+        assert_eq!(report.upstream_ms.unwrap().max, 25);
+        assert_eq!(report.downstream_ms.unwrap().max, 5);
+
+        assert_eq!(report.windows.len(), 2);
+        assert_eq!((report.windows[0].sent, report.windows[0].lost), (2, 0));
+        assert_eq!((report.windows[1].sent, report.windows[1].lost), (3, 2));
+    }
+}
@@ -0,0 +1,257 @@
+//! `pointzerver support-bundle`: one zip with what a bug report needs
+//!
+//! Tokens, the rendezvous room and Wi-Fi names never go in; the bundle is meant to be
+//! attached to a public issue.
+
+use crate::domain::config::settings::REDACTED;
+use crate::domain::config::{secrets, ServerConfig, Settings};
+use crate::features::auth::token_store::TokenStore;
+use crate::utils::paths;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Status routes asked of the running instance, and the file each lands in
+const DIAGNOSTICS: &[(&str, &str)] = &[
+    ("/status", "status.json"),
+    ("/stats", "stats.json"),
+    ("/debug/queue", "queue.json"),
+    ("/debug/profile", "profile.json"),
+];
+
+/// Hex runs this long are tokens or token hashes; signer ids are 16 digits and stay readable
+const SECRET_HEX_LEN: usize = 32;
+
+#[derive(Serialize)]
+struct PlatformInfo {
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    os_version: Option<String>,
+    kernel_version: Option<String>,
+    cpus: usize,
+    memory_bytes: u64,
+    /// `x11` or `wayland` on Linux, which decides how input is injected
+    session_type: Option<String>,
+    portable: bool,
+}
+
+/// Holder of a paired token, without its name or token
+#[derive(Serialize)]
+struct PairingSummary {
+    role: String,
+    paired_at: u64,
+    expires_at: Option<u64>,
+    scopes: Option<Value>,
+}
+
+/// `pointzerver support-bundle [bundle.zip]`
+pub async fn run(bundle: Option<PathBuf>) -> Result<()> {
+    let bundle = bundle.unwrap_or_else(default_bundle_path);
+    secrets::unlock(&paths::secrets_file())?;
+    let tokens = TokenStore::load(paths::tokens_file(), &paths::admin_token_file())
+        .context("Failed to load tokens")?;
+    let settings = Settings::load_or_default(&paths::settings_file());
+    let secrets = Secrets::collect(&settings);
+
+    let file = std::fs::File::create(&bundle)
+        .with_context(|| format!("Failed to create {}", bundle.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let mut add = |name: &str, contents: &[u8]| -> Result<()> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+        Ok(())
+    };
+
+    add(
+        "platform.json",
+        &serde_json::to_vec_pretty(&platform_info())?,
+    )?;
+    add(
+        "settings.json",
+        &serde_json::to_vec_pretty(&settings.redacted())?,
+    )?;
+    add(
+        "pairings.json",
+        &serde_json::to_vec_pretty(&pairings(&tokens))?,
+    )?;
+
+    let admin_token = std::fs::read_to_string(paths::admin_token_file()).unwrap_or_default();
+    for (route, name) in DIAGNOSTICS {
+        let contents = match fetch(route, admin_token.trim()).await {
+            Ok(body) => secrets.redact(&body),
+            Err(e) => format!("Not collected: {:#}\n", e),
+        };
+        add(name, contents.as_bytes())?;
+    }
+
+    let log = match paths::log_file() {
+        Some(path) => match read_tail(&path, ServerConfig::SUPPORT_LOG_TAIL_BYTES) {
+            Ok(log) => secrets.redact(&log),
+            Err(e) => format!("Failed to read {}: {}\n", path.display(), e),
+        },
+        None => "Logs go to stderr outside portable mode; attach what the service manager \
+                 collected (journalctl, Console.app or Event Viewer)\n"
+            .to_string(),
+    };
+    add("pointzerver.log", log.as_bytes())?;
+    zip.finish()?;
+
+    println!(
+        "Wrote {}; look it over before attaching it to a bug report",
+        bundle.display()
+    );
+    Ok(())
+}
+
+fn default_bundle_path() -> PathBuf {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    PathBuf::from(format!("pointzerver-support-{}.zip", stamp))
+}
+
+fn platform_info() -> PlatformInfo {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    PlatformInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        os_version: sysinfo::System::long_os_version(),
+        kernel_version: sysinfo::System::kernel_version(),
+        cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        memory_bytes: system.total_memory(),
+        session_type: std::env::var("XDG_SESSION_TYPE").ok(),
+        portable: paths::log_file().is_some(),
+    }
+}
+
+fn pairings(tokens: &TokenStore) -> Vec<PairingSummary> {
+    tokens
+        .devices()
+        .into_iter()
+        .map(|device| PairingSummary {
+            role: format!("{:?}", device.role).to_lowercase(),
+            paired_at: device.paired_at,
+            expires_at: device.expires_at,
+            scopes: device
+                .scopes
+                .map(|scopes| serde_json::to_value(scopes).unwrap_or_default()),
+        })
+        .collect()
+}
+
+/// Asks the running instance's status server, which only listens on loopback
+async fn fetch(route: &str, admin_token: &str) -> Result<String> {
+    let url = format!("http://127.0.0.1:{}{}", ServerConfig::STATUS_PORT, route);
+    let response = reqwest::Client::new()
+        .get(&url)
+        .bearer_auth(admin_token)
+        .timeout(Duration::from_secs(
+            ServerConfig::SUPPORT_FETCH_TIMEOUT_SECS,
+        ))
+        .send()
+        .await
+        .context("PointZerver is not running or did not answer")?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+fn read_tail(path: &Path, max_bytes: u64) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    Ok(String::from_utf8_lossy(&tail).into_owned())
+}
+
+/// Strings that must not appear anywhere in the bundle
+struct Secrets {
+    known: Vec<String>,
+}
+
+impl Secrets {
+    fn collect(settings: &Settings) -> Self {
+        let known = [
+            Some(settings.rendezvous.room.clone()),
+            secrets::open(&settings.rendezvous.room).ok(),
+            settings.rendezvous.relay.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(settings.geofence.trusted_ssids.iter().cloned())
+        .filter(|secret| !secret.is_empty())
+        .collect();
+        Self { known }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        let mut text = redact_hex_runs(text);
+        for secret in &self.known {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+        text
+    }
+}
+
+fn redact_hex_runs(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut run = String::new();
+    for c in text.chars() {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+            continue;
+        }
+        flush_hex_run(&mut redacted, &mut run);
+        redacted.push(c);
+    }
+    flush_hex_run(&mut redacted, &mut run);
+    redacted
+}
+
+fn flush_hex_run(redacted: &mut String, run: &mut String) {
+    match run.len() >= SECRET_HEX_LEN {
+        true => redacted.push_str(REDACTED),
+        false => redacted.push_str(run),
+    }
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_and_known_secrets_redacted() {
+        let mut settings = Settings::default();
+        settings.rendezvous.room = "blue-otter-harbor".to_string();
+        let secrets = Secrets::collect(&settings);
+        let token = "ab".repeat(32);
+        let log = format!(
+            "Accepted {} from 192.168.1.5 in room blue-otter-harbor, signer 0123456789abcdef",
+            token
+        );
+        assert_eq!(
+            secrets.redact(&log),
+            "Accepted [redacted] from 192.168.1.5 in room [redacted], signer 0123456789abcdef"
+        );
+    }
+
+    #[test]
+    fn test_secret_settings_stripped() {
+        let mut settings = Settings::default();
+        settings.rendezvous.room = "blue-otter-harbor".to_string();
+        settings.geofence.trusted_ssids = vec!["Home".to_string()];
+        let value = settings.redacted();
+        assert_eq!(value.pointer("/rendezvous/room").unwrap(), REDACTED);
+        assert_eq!(value.pointer("/geofence/trusted_ssids").unwrap(), REDACTED);
+        assert!(value.pointer("/telemetry/endpoint").unwrap().is_null());
+        assert_eq!(value.pointer("/rate_limit/enabled").unwrap(), true);
+    }
+}
@@ -1,49 +1,165 @@
+use crate::domain::config::settings::NetworkSettings;
 use crate::domain::config::ServerConfig;
 use crate::domain::models::DiscoveryResponse;
+use crate::features::command::udp_socket;
+use crate::features::discovery::overlay;
+use crate::features::lifecycle::readiness::Readiness;
 use crate::utils::get_hostname;
 use anyhow::Result;
+use if_addrs::get_if_addrs;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
 
+/// Answers discovery broadcasts on the wildcard address and on every interface address
+///
+/// Virtual adapters (Tailscale, ZeroTier) often don't deliver broadcasts, so clients there
+/// probe the interface address directly; replying from the socket the probe hit keeps the
+/// reply's source address on that network.
 pub struct DiscoveryService {
-    pub(crate) socket: UdpSocket,
-    pub(crate) response: DiscoveryResponse,
+    wildcard: UdpSocket,
+    hostname: Arc<str>,
+    network: NetworkSettings,
+    /// Set when the screen preview is being served
+    preview_port: Option<u16>,
 }
 
 impl DiscoveryService {
-    pub async fn new() -> Result<Self> {
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", ServerConfig::DISCOVERY_PORT)).await?;
-        socket.set_broadcast(true)?;
-        let response = DiscoveryResponse {
-            hostname: get_hostname(),
-        };
-        Ok(Self { socket, response })
+    pub async fn new(network: NetworkSettings, preview_port: Option<u16>) -> Result<Self> {
+        let wildcard = bind(Ipv4Addr::UNSPECIFIED, &network)?;
+        let hostname = Arc::from(get_hostname());
+        Ok(Self {
+            wildcard,
+            hostname,
+            network,
+            preview_port,
+        })
     }
 
-    pub fn is_discovery_request(&self, request: &str) -> bool {
-        request.trim() == ServerConfig::DISCOVER_MESSAGE
+    /// Serves the wildcard socket and binds new interface addresses as they appear
+    ///
+    /// Stays silent until `readiness` opens; probes sent meanwhile wait in the socket buffer.
+    pub async fn run(self, readiness: Readiness) -> Result<()> {
+        let Self {
+            wildcard,
+            hostname,
+            network,
+            preview_port,
+        } = self;
+        let ports = Ports {
+            tcp: network.tcp.then_some(ServerConfig::COMMAND_TCP_PORT),
+            preview: preview_port,
+        };
+        let network = Arc::new(network);
+        readiness.wait().await;
+        tokio::spawn(listen(wildcard, hostname.clone(), ports, network.clone()));
+
+        let mut bound = HashSet::new();
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            ServerConfig::DISCOVERY_RESCAN_INTERVAL_SECS,
+        ));
+        loop {
+            interval.tick().await;
+            for ip in candidate_addresses() {
+                if bound.contains(&ip) {
+                    continue;
+                }
+                match bind(ip, &network) {
+                    Ok(socket) => {
+                        log::info!("Discovery listening on {}", ip);
+                        bound.insert(ip);
+                        tokio::spawn(listen(socket, hostname.clone(), ports, network.clone()));
+                    }
+                    Err(e) => log::debug!("Could not bind discovery on {}: {}", ip, e),
+                }
+            }
+        }
     }
+}
+
+/// Optional services a discovery reply advertises
+#[derive(Clone, Copy)]
+struct Ports {
+    tcp: Option<u16>,
+    preview: Option<u16>,
+}
+
+pub fn is_discovery_request(request: &str) -> bool {
+    request.trim() == ServerConfig::DISCOVER_MESSAGE
+}
+
+async fn listen(
+    socket: UdpSocket,
+    hostname: Arc<str>,
+    ports: Ports,
+    network: Arc<NetworkSettings>,
+) {
+    let mut buf = [0; ServerConfig::DISCOVERY_BUFFER_SIZE];
 
-    async fn send_response(&self, addr: std::net::SocketAddr) {
-        let Ok(json) = serde_json::to_string(&self.response) else {
-            return;
+    loop {
+        let Ok((size, addr)) = socket.recv_from(&mut buf).await else {
+            continue;
         };
-        let _ = self.socket.send_to(json.as_bytes(), addr).await;
+        // Stay invisible to clients that couldn't send commands anyway
+        if !network.admits(addr.ip()) {
+            continue;
+        }
+
+        let request = String::from_utf8_lossy(&buf[..size]);
+        if !is_discovery_request(&request) {
+            continue;
+        }
+
+        // Overlay addresses come and go with the VPN, so they're looked up per request
+        let response = DiscoveryResponse {
+            hostname: hostname.to_string(),
+            alternate_endpoints: overlay::overlay_endpoints(),
+            tcp_port: ports.tcp,
+            preview_port: ports.preview,
+        };
+        let Ok(json) = serde_json::to_vec(&response) else {
+            continue;
+        };
+        let _ = socket.send_to(&json, addr).await;
     }
+}
 
-    pub async fn run(&self) -> Result<()> {
-        let mut buf = [0; ServerConfig::DISCOVERY_BUFFER_SIZE];
+/// Non-loopback IPv4 addresses, including virtual adapters
+fn candidate_addresses() -> Vec<Ipv4Addr> {
+    let Ok(interfaces) = get_if_addrs() else {
+        return Vec::new();
+    };
+    interfaces
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter_map(|iface| match iface.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect()
+}
 
-        loop {
-            let Ok((size, addr)) = self.socket.recv_from(&mut buf).await else {
-                continue;
-            };
+/// Binds the discovery port, sharing it between the wildcard and per-interface sockets
+fn bind(ip: Ipv4Addr, network: &NetworkSettings) -> Result<UdpSocket> {
+    // Windows already allows a specific bind next to a wildcard one; SO_REUSEADDR there
+    // would instead let other processes hijack the port, so it stays opt-in
+    let reuse_address = cfg!(not(windows)) || network.reuse_address;
+    udp_socket::bind(
+        SocketAddr::from((ip, ServerConfig::DISCOVERY_PORT)),
+        network,
+        reuse_address,
+    )
+}
 
-            let request = String::from_utf8_lossy(&buf[..size]);
-            if !self.is_discovery_request(&request) {
-                continue;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            self.send_response(addr).await;
-        }
+    #[test]
+    fn test_discovery_request_ignores_whitespace() {
+        assert!(is_discovery_request("DISCOVER\n"));
+        assert!(!is_discovery_request("DISCOVERY"));
     }
 }
@@ -1 +1,2 @@
 pub mod discovery_service;
+pub mod overlay;
@@ -0,0 +1,85 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{AlternateEndpoint, OverlayNetwork};
+use if_addrs::get_if_addrs;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// Tailscale hands out addresses from the CGNAT range and a fixed ULA prefix
+const TAILSCALE_RANGES: [&str; 2] = ["100.64.0.0/10", "fd7a:115c:a1e0::/48"];
+
+/// Addresses on VPN/overlay interfaces, so clients off the LAN can still reach the server
+pub fn overlay_endpoints() -> Vec<AlternateEndpoint> {
+    let Ok(interfaces) = get_if_addrs() else {
+        return Vec::new();
+    };
+    interfaces
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter_map(|iface| {
+            let network = classify(&iface.name, iface.ip())?;
+            Some(AlternateEndpoint {
+                ip: iface.ip(),
+                port: ServerConfig::COMMAND_PORT,
+                network,
+                interface: iface.name.clone(),
+            })
+        })
+        .collect()
+}
+
+fn classify(interface: &str, ip: IpAddr) -> Option<OverlayNetwork> {
+    let name = interface.to_ascii_lowercase();
+    let in_tailscale_range = TAILSCALE_RANGES
+        .iter()
+        .filter_map(|range| range.parse::<IpNet>().ok())
+        .any(|range| range.contains(&ip));
+
+    if name.starts_with("tailscale") || in_tailscale_range {
+        Some(OverlayNetwork::Tailscale)
+    } else if name.starts_with("zt") || name.contains("zerotier") {
+        Some(OverlayNetwork::ZeroTier)
+    } else if name.starts_with("wg") || name.contains("wireguard") {
+        Some(OverlayNetwork::WireGuard)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cgnat_address_is_tailscale_on_any_interface() {
+        assert_eq!(
+            classify("utun4", ip("100.101.102.103")),
+            Some(OverlayNetwork::Tailscale)
+        );
+        assert_eq!(
+            classify("utun4", ip("fd7a:115c:a1e0::1")),
+            Some(OverlayNetwork::Tailscale)
+        );
+    }
+
+    #[test]
+    fn test_interface_names() {
+        assert_eq!(
+            classify("wg0", ip("10.8.0.2")),
+            Some(OverlayNetwork::WireGuard)
+        );
+        assert_eq!(
+            classify("ztabcdef12", ip("10.147.17.5")),
+            Some(OverlayNetwork::ZeroTier)
+        );
+    }
+
+    #[test]
+    fn test_lan_address_is_not_overlay() {
+        assert_eq!(classify("eth0", ip("192.168.1.20")), None);
+        assert_eq!(classify("wlan0", ip("100.128.0.1")), None);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::domain::config::settings::GameModeSettings;
+use crate::domain::models::Command;
+use crate::features::game_mode::priority;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+
+/// Clients on the low-latency pointer path meant for games
+///
+/// Their moves skip the reorder wait, queue merging and smoothing, and are injected through
+/// the OS's relative motion path that games reading raw mouse input see. A client is in game
+/// mode when the `game_mode` settings list it or it asks with `SetGameMode`, and asking wins.
+/// With `prediction` on, a move arriving one `seq` past the next expected one is taken to
+/// follow a lost move, which is filled in by repeating the move before it.
+pub struct GameModeService {
+    settings: GameModeSettings,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    players: HashMap<SocketAddr, Player>,
+    raised: bool,
+}
+
+struct Player {
+    enabled: bool,
+    /// Highest `seq` seen from the client, from any command
+    last_seq: Option<u64>,
+    last_move: Option<(f64, f64)>,
+}
+
+impl Player {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            last_seq: None,
+            last_move: None,
+        }
+    }
+}
+
+impl GameModeService {
+    pub fn new(settings: GameModeSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Whether moves from `addr` take the game path
+    pub fn plays(&self, addr: SocketAddr) -> bool {
+        self.lock_state()
+            .players
+            .get(&addr)
+            .is_some_and(|player| player.enabled)
+    }
+
+    /// Moves to queue as they are for a command just received, or `None` when the command
+    /// takes the usual path
+    ///
+    /// `SetGameMode` is taken here, so the moves right behind it are already routed by it.
+    /// A move that arrives after a later command is dropped, as is everything when empty.
+    pub fn admit(
+        &self,
+        addr: SocketAddr,
+        client_id: Option<&str>,
+        seq: Option<u64>,
+        command: &Command,
+    ) -> Option<Vec<Command>> {
+        let mut state = self.lock_state();
+        if let Command::SetGameMode { enabled } = *command {
+            log::info!(
+                "Game mode {} for {}",
+                if enabled { "on" } else { "off" },
+                addr
+            );
+            state.players.insert(addr, Player::new(enabled));
+            self.update_priority(&mut state);
+            return Some(Vec::new());
+        }
+        if let Entry::Vacant(vacant) = state.players.entry(addr) {
+            let listed = client_id.is_some_and(|id| self.settings.clients.iter().any(|c| c == id));
+            if !listed {
+                return None;
+            }
+            vacant.insert(Player::new(true));
+            self.update_priority(&mut state);
+        }
+        let player = state.players.get_mut(&addr)?;
+        if !player.enabled {
+            return None;
+        }
+
+        let previous = player.last_seq;
+        if let Some(seq) = seq {
+            player.last_seq = Some(previous.map_or(seq, |last| last.max(seq)));
+        }
+        let Command::MouseMove { x, y } = *command else {
+            return None;
+        };
+        let mut moves = Vec::new();
+        match (seq, previous) {
+            (Some(seq), Some(last)) if seq <= last => return Some(moves),
+            (Some(seq), Some(last)) if seq == last + 2 && self.settings.prediction => {
+                if let Some((x, y)) = player.last_move {
+                    moves.push(Command::MouseMove { x, y });
+                }
+            }
+            _ => {}
+        }
+        player.last_move = Some((x, y));
+        moves.push(Command::MouseMove { x, y });
+        Some(moves)
+    }
+
+    /// Drops a client that went silent, lowering priority again if it was the last player
+    pub fn forget(&self, addr: SocketAddr) {
+        let mut state = self.lock_state();
+        if state.players.remove(&addr).is_some() {
+            self.update_priority(&mut state);
+        }
+    }
+
+    fn update_priority(&self, state: &mut State) {
+        let playing = state.players.values().any(|player| player.enabled);
+        if !self.settings.raise_priority || playing == state.raised {
+            return;
+        }
+        state.raised = playing;
+        match priority::set_raised(playing) {
+            Ok(()) => log::debug!(
+                "Scheduling priority {}",
+                if playing { "raised" } else { "restored" }
+            ),
+            Err(e) => log::warn!("Failed to change scheduling priority for game mode: {}", e),
+        }
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Game mode mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr() -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 10], 5000))
+    }
+
+    fn moves(commands: Option<Vec<Command>>) -> Vec<(f64, f64)> {
+        commands
+            .expect("not in game mode")
+            .into_iter()
+            .map(|command| match command {
+                Command::MouseMove { x, y } => (x, y),
+                other => panic!("unexpected {}", other.kind()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_listed_client_fills_in_one_lost_move_and_drops_late_ones() {
+        let game = GameModeService::new(GameModeSettings {
+            clients: vec!["pad".to_string()],
+            prediction: true,
+            raise_priority: false,
+        });
+        let step = |seq, x| {
+            game.admit(
+                addr(),
+                Some("pad"),
+                Some(seq),
+                &Command::MouseMove { x, y: 0.0 },
+            )
+        };
+
+        assert!(game
+            .admit(
+                addr(),
+                Some("phone"),
+                Some(1),
+                &Command::MouseMove { x: 1.0, y: 0.0 }
+            )
+            .is_none());
+        assert_eq!(moves(step(1, 2.0)), [(2.0, 0.0)]);
+        assert!(game.plays(addr()));
+        assert_eq!(moves(step(3, 4.0)), [(2.0, 0.0), (4.0, 0.0)]);
+        assert!(moves(step(2, 3.0)).is_empty());
+        assert_eq!(moves(step(6, 5.0)), [(5.0, 0.0)]);
+
+        // A click in between isn't mistaken for a lost move
+        let click = Command::MouseClick { button: 1 };
+        assert!(game.admit(addr(), Some("pad"), Some(7), &click).is_none());
+        assert_eq!(moves(step(8, 6.0)), [(6.0, 0.0)]);
+
+        let off = Command::SetGameMode { enabled: false };
+        assert!(moves(game.admit(addr(), Some("pad"), Some(9), &off)).is_empty());
+        assert!(!game.plays(addr()));
+        assert!(step(10, 7.0).is_none());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod game_mode_service;
+pub mod priority;
@@ -0,0 +1,61 @@
+//! Scheduling priority of the server, raised while a client is in game mode
+//!
+//! Raising it past normal needs administrator rights on Linux and macOS, where a refusal is
+//! returned for the caller to log; game mode works the same without it.
+
+use anyhow::Result;
+
+#[cfg(windows)]
+pub fn set_raised(raised: bool) -> Result<()> {
+    use windows::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, ABOVE_NORMAL_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+    };
+
+    let class = match raised {
+        true => ABOVE_NORMAL_PRIORITY_CLASS,
+        false => NORMAL_PRIORITY_CLASS,
+    };
+    unsafe { SetPriorityClass(GetCurrentProcess(), class)? };
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn set_raised(raised: bool) -> Result<()> {
+    use crate::domain::config::ServerConfig;
+    use std::ffi::c_int;
+
+    const PRIO_PROCESS: c_int = 0;
+
+    extern "C" {
+        fn setpriority(which: c_int, who: u32, prio: c_int) -> c_int;
+    }
+
+    let nice = match raised {
+        true => ServerConfig::GAME_MODE_NICE,
+        false => 0,
+    };
+    for thread in threads() {
+        if unsafe { setpriority(PRIO_PROCESS, thread, nice) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Linux keeps a nice value per thread, so every thread of the process is set
+#[cfg(target_os = "linux")]
+fn threads() -> Vec<u32> {
+    std::fs::read_dir("/proc/self/task")
+        .map(|tasks| {
+            tasks
+                .filter_map(|task| task.ok()?.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![0])
+}
+
+/// `0` is the whole process
+#[cfg(target_os = "macos")]
+fn threads() -> Vec<u32> {
+    vec![0]
+}
@@ -0,0 +1,214 @@
+use crate::domain::config::settings::GeofenceSettings;
+use crate::domain::config::ServerConfig;
+use crate::domain::models::ServerEvent;
+use crate::features::events::event_bus::EventBus;
+use crate::features::geofence::ssid;
+use if_addrs::get_if_addrs;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Suspends command processing while the host is off its trusted networks
+pub struct GeofenceService {
+    settings: GeofenceSettings,
+    events: EventBus,
+    trusted: AtomicBool,
+    suspended: AtomicBool,
+    network: Mutex<NetworkState>,
+}
+
+#[derive(Default)]
+struct NetworkState {
+    /// Seen by the last check
+    ssid: Option<String>,
+    /// The SSID a manual re-enable happened on; it holds until the host moves
+    resumed_on: Option<Option<String>>,
+}
+
+impl NetworkState {
+    /// Records a check's result; true when command processing must be suspended
+    fn observe(&mut self, trusted: bool, ssid: Option<String>) -> bool {
+        if trusted || self.resumed_on.as_ref() != Some(&ssid) {
+            self.resumed_on = None;
+        }
+        self.ssid = ssid;
+        !trusted && self.resumed_on.is_none()
+    }
+
+    fn resume(&mut self) {
+        self.resumed_on = Some(self.ssid.clone());
+    }
+}
+
+/// Geofence state as reported on `/status`
+#[derive(Serialize, Debug, Clone)]
+pub struct GeofenceStatus {
+    pub enabled: bool,
+    pub trusted: bool,
+    pub suspended: bool,
+}
+
+impl GeofenceService {
+    /// Call `check` once before accepting commands; `run` keeps it current after that
+    pub fn new(settings: GeofenceSettings, events: EventBus) -> Self {
+        Self {
+            settings,
+            events,
+            trusted: AtomicBool::new(true),
+            suspended: AtomicBool::new(false),
+            network: Mutex::new(NetworkState::default()),
+        }
+    }
+
+    /// True while commands must be refused
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::Relaxed)
+    }
+
+    /// Explicitly resumes command processing after a geofence trip, until the network changes
+    pub fn enable(&self) {
+        let mut network = self.lock_network();
+        if self.suspended.swap(false, Ordering::Relaxed) {
+            network.resume();
+            log::info!("Command processing re-enabled by user");
+            self.events.publish(ServerEvent::GeofenceResumed);
+        }
+    }
+
+    pub fn status(&self) -> GeofenceStatus {
+        GeofenceStatus {
+            enabled: self.settings.enabled,
+            trusted: self.trusted.load(Ordering::Relaxed),
+            suspended: self.is_suspended(),
+        }
+    }
+
+    /// Re-evaluates the network periodically; returns immediately when disabled
+    pub async fn run(&self) {
+        if !self.settings.enabled {
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            ServerConfig::GEOFENCE_CHECK_INTERVAL_SECS,
+        ));
+        loop {
+            interval.tick().await;
+            self.check().await;
+        }
+    }
+
+    /// Looks at the network now; does nothing when disabled
+    pub async fn check(&self) {
+        if !self.settings.enabled {
+            return;
+        }
+        // Asking for the SSID runs a command-line tool
+        let (addrs, ssid) = match tokio::task::spawn_blocking(current_network).await {
+            Ok(network) => network,
+            Err(e) => {
+                log::warn!("Geofence check failed: {}", e);
+                return;
+            }
+        };
+
+        let trusted = is_trusted(&self.settings, &addrs, ssid.as_deref());
+        let was_trusted = self.trusted.swap(trusted, Ordering::Relaxed);
+        let suspend = self.lock_network().observe(trusted, ssid);
+
+        if suspend && !self.suspended.swap(true, Ordering::Relaxed) {
+            log::warn!("Not on a trusted network - command processing suspended");
+            self.events.publish(ServerEvent::GeofenceSuspended);
+        } else if trusted && !was_trusted {
+            log::info!("Back on a trusted network - re-enable command processing to resume");
+        }
+    }
+
+    fn lock_network(&self) -> std::sync::MutexGuard<'_, NetworkState> {
+        self.network
+            .lock()
+            .expect("Geofence network mutex poisoned")
+    }
+}
+
+/// Non-loopback addresses and the Wi-Fi SSID, which a manual re-enable is tied to
+fn current_network() -> (Vec<IpAddr>, Option<String>) {
+    let addrs = get_if_addrs()
+        .map(|ifaces| {
+            ifaces
+                .iter()
+                .filter(|iface| !iface.is_loopback())
+                .map(|iface| iface.ip())
+                .collect()
+        })
+        .unwrap_or_default();
+    (addrs, ssid::current_ssid())
+}
+
+/// A network is trusted if any local address is in a trusted subnet or the SSID matches
+fn is_trusted(settings: &GeofenceSettings, addrs: &[IpAddr], ssid: Option<&str>) -> bool {
+    let subnet_match = addrs.iter().any(|addr| {
+        settings
+            .trusted_subnets
+            .iter()
+            .any(|subnet| subnet.contains(addr))
+    });
+    let ssid_match =
+        ssid.is_some_and(|ssid| settings.trusted_ssids.iter().any(|trusted| trusted == ssid));
+    subnet_match || ssid_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> GeofenceSettings {
+        GeofenceSettings {
+            enabled: true,
+            trusted_subnets: vec!["192.168.1.0/24".parse().unwrap()],
+            trusted_ssids: vec!["HomeNet".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_trusted_by_subnet() {
+        let addrs = ["10.0.0.5".parse().unwrap(), "192.168.1.20".parse().unwrap()];
+        assert!(is_trusted(&settings(), &addrs, None));
+    }
+
+    #[test]
+    fn test_trusted_by_ssid() {
+        let addrs = ["10.0.0.5".parse().unwrap()];
+        assert!(is_trusted(&settings(), &addrs, Some("HomeNet")));
+    }
+
+    #[test]
+    fn test_manual_resume_holds_until_ssid_changes() {
+        let mut network = NetworkState::default();
+        assert!(network.observe(false, Some("CafeWifi".to_string())));
+        network.resume();
+
+        assert!(!network.observe(false, Some("CafeWifi".to_string())));
+        assert!(!network.observe(false, Some("CafeWifi".to_string())));
+        assert!(network.observe(false, Some("AirportWifi".to_string())));
+        assert!(network.observe(false, Some("CafeWifi".to_string())));
+    }
+
+    #[test]
+    fn test_trusted_network_clears_manual_resume() {
+        let mut network = NetworkState::default();
+        network.observe(false, None);
+        network.resume();
+
+        assert!(!network.observe(true, None));
+        assert!(network.observe(false, None));
+    }
+
+    #[test]
+    fn test_untrusted_network() {
+        let addrs = ["10.0.0.5".parse().unwrap()];
+        assert!(!is_trusted(&settings(), &addrs, Some("CafeWifi")));
+        assert!(!is_trusted(&settings(), &[], None));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod geofence_service;
+mod ssid;
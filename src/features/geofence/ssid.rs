@@ -0,0 +1,42 @@
+use std::process::Command;
+
+/// Returns the SSID of the currently associated Wi-Fi network, if any
+pub fn current_ssid() -> Option<String> {
+    let ssid = query_ssid()?;
+    let ssid = ssid.trim();
+    (!ssid.is_empty()).then(|| ssid.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn query_ssid() -> Option<String> {
+    let output = Command::new("iwgetid").arg("-r").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+fn query_ssid() -> Option<String> {
+    let output = Command::new("networksetup")
+        .args(["-getairportnetwork", "en0"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .split_once("Current Wi-Fi Network:")
+        .map(|(_, ssid)| ssid.to_string())
+}
+
+#[cfg(windows)]
+fn query_ssid() -> Option<String> {
+    let output = Command::new("netsh")
+        .args(["wlan", "show", "interfaces"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "SSID").then(|| value.to_string())
+    })
+}
@@ -0,0 +1,184 @@
+use crate::domain::config::settings::{CornerAction, HotCornerSettings};
+use crate::domain::config::ServerConfig;
+use crate::input::desktop_actions;
+use crate::input::pointer::{self, DesktopBounds};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Fires a desktop action once the remote-driven pointer has rested in a configured corner
+///
+/// Only positions this server injected are watched, so moving the local mouse into a corner
+/// keeps the OS's own hot-corner behavior.
+pub struct HotCornerService {
+    settings: HotCornerSettings,
+    dwell: Mutex<Option<Dwell>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+struct Dwell {
+    corner: Corner,
+    since: Instant,
+    /// Leaving the corner re-arms it, so one visit fires once
+    fired: bool,
+}
+
+impl HotCornerService {
+    pub fn new(settings: HotCornerSettings) -> Self {
+        Self {
+            settings,
+            dwell: Mutex::new(None),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        [
+            Corner::TopLeft,
+            Corner::TopRight,
+            Corner::BottomLeft,
+            Corner::BottomRight,
+        ]
+        .into_iter()
+        .any(|corner| self.action(corner).is_some())
+    }
+
+    fn action(&self, corner: Corner) -> Option<CornerAction> {
+        match corner {
+            Corner::TopLeft => self.settings.top_left,
+            Corner::TopRight => self.settings.top_right,
+            Corner::BottomLeft => self.settings.bottom_left,
+            Corner::BottomRight => self.settings.bottom_right,
+        }
+    }
+
+    /// Notes where an injected move left the pointer
+    pub fn observe(&self, position: (f64, f64)) {
+        if !self.enabled() {
+            return;
+        }
+        let corner = self
+            .corner_at(position, &pointer::desktop_bounds())
+            .filter(|corner| self.action(*corner).is_some());
+        let mut dwell = self.lock_dwell();
+        match (corner, dwell.as_ref()) {
+            (Some(corner), Some(current)) if current.corner == corner => {}
+            (Some(corner), _) => {
+                *dwell = Some(Dwell {
+                    corner,
+                    since: Instant::now(),
+                    fired: false,
+                })
+            }
+            (None, _) => *dwell = None,
+        }
+    }
+
+    fn corner_at(&self, (x, y): (f64, f64), bounds: &DesktopBounds) -> Option<Corner> {
+        let size = self.settings.size_px.max(0.0);
+        let left = x < bounds.left + size;
+        let right = x >= bounds.right - 1.0 - size;
+        let top = y < bounds.top + size;
+        let bottom = y >= bounds.bottom - 1.0 - size;
+        match (left, right, top, bottom) {
+            (true, _, true, _) => Some(Corner::TopLeft),
+            (_, true, true, _) => Some(Corner::TopRight),
+            (true, _, _, true) => Some(Corner::BottomLeft),
+            (_, true, _, true) => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
+
+    /// The action due now, marking the visit as fired
+    fn due(&self, now: Instant) -> Option<CornerAction> {
+        let mut dwell = self.lock_dwell();
+        let current = dwell.as_mut()?;
+        let rested = now.saturating_duration_since(current.since)
+            >= Duration::from_millis(self.settings.dwell_ms);
+        if current.fired || !rested {
+            return None;
+        }
+        current.fired = true;
+        self.action(current.corner)
+    }
+
+    /// Checks for a completed dwell; returns at once when no corner is configured
+    pub async fn run(&self) {
+        if !self.enabled() {
+            return;
+        }
+        let mut interval =
+            tokio::time::interval(Duration::from_millis(ServerConfig::HOT_CORNER_CHECK_MS));
+        loop {
+            interval.tick().await;
+            let Some(action) = self.due(Instant::now()) else {
+                continue;
+            };
+            log::info!("Hot corner: {:?}", action);
+            let performed = tokio::task::spawn_blocking(move || desktop_actions::perform(action));
+            match performed.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("Hot corner action {:?} failed: {}", action, e),
+                Err(e) => log::warn!("Hot corner action {:?} panicked: {}", action, e),
+            }
+        }
+    }
+
+    fn lock_dwell(&self) -> MutexGuard<'_, Option<Dwell>> {
+        self.dwell.lock().expect("Hot corner mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> HotCornerService {
+        HotCornerService::new(HotCornerSettings {
+            top_right: Some(CornerAction::ShowDesktop),
+            ..HotCornerSettings::default()
+        })
+    }
+
+    #[test]
+    fn test_corner_detection_uses_the_edge_pixels() {
+        let service = service();
+        let bounds = DesktopBounds::fallback();
+        assert_eq!(
+            service.corner_at((1919.0, 0.0), &bounds),
+            Some(Corner::TopRight)
+        );
+        assert_eq!(
+            service.corner_at((0.0, 1079.0), &bounds),
+            Some(Corner::BottomLeft)
+        );
+        assert_eq!(service.corner_at((960.0, 0.0), &bounds), None);
+    }
+
+    #[test]
+    fn test_fires_once_per_visit_after_dwelling() {
+        let service = service();
+        let start = Instant::now();
+        *service.lock_dwell() = Some(Dwell {
+            corner: Corner::TopRight,
+            since: start,
+            fired: false,
+        });
+        assert_eq!(service.due(start), None);
+        let rested = start + Duration::from_millis(service.settings.dwell_ms);
+        assert_eq!(service.due(rested), Some(CornerAction::ShowDesktop));
+        assert_eq!(service.due(rested + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_unconfigured_corner_is_ignored() {
+        let service = service();
+        service.observe((0.0, 0.0));
+        assert!(service.lock_dwell().is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::domain::config::ServerConfig;
+use crate::utils::paths;
+use anyhow::{bail, Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Exclusive lock on the config directory, held for the life of the process
+///
+/// The OS drops the lock when the process exits, so a crashed instance never leaves it stale.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// `None` while another instance holds the lock
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+/// Claims this config directory, asking a running instance to step aside when `takeover` is set
+pub async fn claim(takeover: bool) -> Result<InstanceLock> {
+    let path = paths::lock_file();
+    if let Some(lock) = InstanceLock::try_acquire(&path)? {
+        return Ok(lock);
+    }
+    // Windows keeps the locked file unreadable, so the PID is best effort
+    let holder = std::fs::read_to_string(&path)
+        .ok()
+        .map(|pid| format!(" (pid {})", pid.trim()))
+        .unwrap_or_default();
+    if !takeover {
+        bail!(
+            "PointZerver is already running{}; stop it or start with --takeover to replace it",
+            holder
+        );
+    }
+
+    log::info!("Asking the running instance{} to shut down", holder);
+    request_shutdown().await?;
+    let deadline = Instant::now() + Duration::from_secs(ServerConfig::TAKEOVER_TIMEOUT_SECS);
+    while Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(ServerConfig::TAKEOVER_POLL_MS)).await;
+        if let Some(lock) = InstanceLock::try_acquire(&path)? {
+            log::info!("Took over from the previous instance");
+            return Ok(lock);
+        }
+    }
+    bail!(
+        "The running instance{} did not shut down within {} seconds",
+        holder,
+        ServerConfig::TAKEOVER_TIMEOUT_SECS
+    )
+}
+
+/// Calls `POST /shutdown` on the running instance's status server with the admin token
+async fn request_shutdown() -> Result<()> {
+    let token = std::fs::read_to_string(paths::admin_token_file())
+        .context("Failed to read the admin token")?;
+    let url = format!("http://127.0.0.1:{}/shutdown", ServerConfig::STATUS_PORT);
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(token.trim())
+        .send()
+        .await
+        .context("The running instance did not answer on its status port")?;
+    if !response.status().is_success() {
+        bail!(
+            "The running instance refused to shut down: {}",
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_is_refused_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ServerConfig::LOCK_FILE);
+        let first = InstanceLock::try_acquire(&path).unwrap();
+        assert!(first.is_some());
+        assert!(InstanceLock::try_acquire(&path).unwrap().is_none());
+        drop(first);
+        assert!(InstanceLock::try_acquire(&path).unwrap().is_some());
+    }
+}
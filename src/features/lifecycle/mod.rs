@@ -0,0 +1,3 @@
+pub mod instance_lock;
+pub mod readiness;
+pub mod shutdown;
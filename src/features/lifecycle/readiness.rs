@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Startup phase reported on `/health`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Starting,
+    Ready,
+}
+
+/// Gate opened once the command pipeline can accept clients
+///
+/// Services that make the server visible wait on it, so no client finds the server
+/// before commands it sends would be handled.
+#[derive(Clone)]
+pub struct Readiness {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn mark_ready(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn phase(&self) -> Phase {
+        if *self.sender.borrow() {
+            Phase::Ready
+        } else {
+            Phase::Starting
+        }
+    }
+
+    /// Resolves immediately once ready
+    pub async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives in `self`, so the channel can't close while we wait
+        let _ = receiver.wait_for(|ready| *ready).await;
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_resolves_after_mark_ready() {
+        let readiness = Readiness::new();
+        assert_eq!(readiness.phase(), Phase::Starting);
+
+        let waiter = tokio::spawn({
+            let readiness = readiness.clone();
+            async move { readiness.wait().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        readiness.mark_ready();
+        waiter.await.unwrap();
+        assert_eq!(readiness.phase(), Phase::Ready);
+        readiness.wait().await;
+    }
+}
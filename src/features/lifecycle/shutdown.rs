@@ -0,0 +1,23 @@
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Asks the running server to stop, e.g. when a newer instance takes over
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    notify: Arc<Notify>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Resolves once shutdown was requested, even if that happened before the call
+    pub async fn requested(&self) {
+        self.notify.notified().await;
+    }
+}
@@ -0,0 +1,543 @@
+use crate::domain::config::server_config::ServerConfig;
+use crate::domain::config::settings::{MacroSettings, Settings};
+use crate::domain::models::{Command, MacroStep, ModifierKeys, ServerEvent};
+use crate::features::events::event_bus::EventBus;
+use crate::input::desk_notice;
+use crate::input::hotkey;
+use crate::input::input_tap::{self, TapEvent};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Macros taught by doing: local input recorded for a while at the desk, then saved to the
+/// `macros` settings for clients to play back with `RunMacro`
+///
+/// A recording asked for through the status API only starts once the person at the desk
+/// presses Ctrl+Alt+Enter, and is only saved once they press it again after seeing what was
+/// captured. Each stage is shown at the desk, and remote input is refused while it waits for
+/// them. Capture ends after the requested time, capped by `max_secs`, or when stopped;
+/// stopping while waiting for the desk or counting down discards the recording.
+pub struct MacroRecorder {
+    settings: Mutex<MacroSettings>,
+    settings_path: PathBuf,
+    events: EventBus,
+    /// False when this process can't see the desk's input, as a Windows service can't
+    local: bool,
+    capture: Arc<Mutex<Capture>>,
+    stop: Notify,
+    /// Set while a recording waits for the consent hotkey
+    awaiting_desk: Arc<AtomicBool>,
+    desk_consent: Arc<Notify>,
+    tapped: Once,
+}
+
+#[derive(Default)]
+struct Capture {
+    /// Macro being recorded, from the request until it's saved or discarded
+    name: Option<String>,
+    /// When capture began, once the countdown is over
+    started: Option<Instant>,
+    /// Local input with its time into the capture in milliseconds
+    events: Vec<(u64, TapEvent)>,
+}
+
+/// A recording that was requested, as confirmed to the status API
+#[derive(Serialize, Debug, Clone)]
+pub struct MacroRecording {
+    pub name: String,
+    pub secs: u64,
+    pub countdown_secs: u64,
+    /// How long the desk has to allow the recording
+    pub consent_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RecordError {
+    #[error("a macro needs a name")]
+    NoName,
+    #[error("a macro is already being recorded")]
+    Busy,
+    #[error("local input isn't visible to this process")]
+    Unavailable,
+}
+
+impl MacroRecorder {
+    pub fn new(
+        settings: MacroSettings,
+        settings_path: PathBuf,
+        events: EventBus,
+        local: bool,
+    ) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            settings_path,
+            events,
+            local,
+            capture: Arc::new(Mutex::new(Capture::default())),
+            stop: Notify::new(),
+            awaiting_desk: Arc::new(AtomicBool::new(false)),
+            desk_consent: Arc::new(Notify::new()),
+            tapped: Once::new(),
+        }
+    }
+
+    pub fn saved(&self) -> BTreeMap<String, Vec<MacroStep>> {
+        self.lock_settings().saved.clone()
+    }
+
+    /// The saved steps, their pauses cut so playback never outlasts `max_secs`
+    ///
+    /// Recordings already fit; this bounds macros written into the settings by hand.
+    pub fn get(&self, name: &str) -> Option<Vec<MacroStep>> {
+        let settings = self.lock_settings();
+        let mut budget_ms = settings.max_secs.max(1) * 1000;
+        let steps = settings.saved.get(name)?.iter().map(|step| {
+            let after_ms = step.after_ms.min(budget_ms);
+            budget_ms -= after_ms;
+            MacroStep {
+                after_ms,
+                command: step.command.clone(),
+            }
+        });
+        Some(steps.collect())
+    }
+
+    /// True while a recording waits for the person at the desk, who alone may answer it
+    pub fn is_awaiting_desk(&self) -> bool {
+        self.awaiting_desk.load(Ordering::Relaxed)
+    }
+
+    /// Deletes a saved macro; false when there was none by that name
+    pub fn delete(&self, name: &str) -> bool {
+        let mut settings = self.lock_settings();
+        if settings.saved.remove(name).is_none() {
+            return false;
+        }
+        log::info!("Deleted macro '{}'", name);
+        self.save(&settings);
+        true
+    }
+
+    /// Asks the person at the desk to allow recording `name` for `secs` seconds
+    pub fn start(self: &Arc<Self>, name: &str, secs: u64) -> Result<MacroRecording, RecordError> {
+        if !self.local {
+            return Err(RecordError::Unavailable);
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(RecordError::NoName);
+        }
+        let mut capture = self.lock_capture();
+        if capture.name.is_some() {
+            return Err(RecordError::Busy);
+        }
+        capture.name = Some(name.to_string());
+        drop(capture);
+
+        self.tap();
+        let settings = self.lock_settings();
+        let recording = MacroRecording {
+            name: name.to_string(),
+            secs: secs.clamp(1, settings.max_secs.max(1)),
+            countdown_secs: settings.countdown_secs,
+            consent_secs: ServerConfig::MACRO_CONSENT_TIMEOUT_SECS,
+        };
+        drop(settings);
+        tokio::spawn(Arc::clone(self).record(recording.clone()));
+        Ok(recording)
+    }
+
+    /// Ends the recording in progress early; false when there was none
+    pub fn stop(&self) -> bool {
+        let recording = self.lock_capture().name.is_some();
+        if recording {
+            self.stop.notify_one();
+        }
+        recording
+    }
+
+    async fn record(self: Arc<Self>, recording: MacroRecording) {
+        let MacroRecording {
+            name,
+            secs,
+            countdown_secs,
+            consent_secs,
+        } = recording;
+        self.events.publish(ServerEvent::MacroRecordingRequested {
+            name: name.clone(),
+            secs,
+            consent_secs,
+        });
+        desk_notice::show(
+            "Allow macro recording?",
+            &format!(
+                "A remote client wants to record your keyboard and mouse for {}s as the macro '{}'. \
+                 Press Ctrl+Alt+Enter within {}s to allow it, or ignore this to refuse.",
+                secs, name, consent_secs
+            ),
+        );
+        if !self.desk_accepts().await {
+            self.discard(&name, "not allowed at the desk");
+            return;
+        }
+
+        desk_notice::show(
+            "Macro recording",
+            &format!(
+                "Recording '{}' starts in {}s and runs for {}s.",
+                name, countdown_secs, secs
+            ),
+        );
+        for seconds_left in (1..=countdown_secs).rev() {
+            self.events.publish(ServerEvent::MacroRecordingCountdown {
+                name: name.clone(),
+                seconds_left,
+            });
+            if self.stopped_within(Duration::from_secs(1)).await {
+                self.discard(&name, "cancelled");
+                return;
+            }
+        }
+
+        self.lock_capture().started = Some(Instant::now());
+        log::info!("Recording macro '{}' for up to {}s", name, secs);
+        desk_notice::show(
+            "Recording your keyboard and mouse",
+            &format!("Capturing '{}' for {}s.", name, secs),
+        );
+        self.events.publish(ServerEvent::MacroRecordingStarted {
+            name: name.clone(),
+            secs,
+        });
+        self.stopped_within(Duration::from_secs(secs)).await;
+
+        let captured = {
+            let mut capture = self.lock_capture();
+            capture.started = None;
+            std::mem::take(&mut capture.events)
+        };
+        let steps = macro_steps(&captured);
+        let count = steps.len();
+        desk_notice::show(
+            "Save the recorded macro?",
+            &format!(
+                "Recorded {} steps for '{}'. Press Ctrl+Alt+Enter within {}s to save it, \
+                 or ignore this to discard it.",
+                count, name, consent_secs
+            ),
+        );
+        if !self.desk_accepts().await {
+            self.discard(&name, "not saved at the desk");
+            return;
+        }
+
+        log::info!("Recorded macro '{}' with {} steps", name, count);
+        let mut settings = self.lock_settings();
+        settings.saved.insert(name.clone(), steps);
+        self.save(&settings);
+        drop(settings);
+        *self.lock_capture() = Capture::default();
+        self.events
+            .publish(ServerEvent::MacroRecorded { name, steps: count });
+    }
+
+    /// Waits for the consent hotkey; false when stopped or it isn't pressed in time
+    async fn desk_accepts(&self) -> bool {
+        self.awaiting_desk.store(true, Ordering::Relaxed);
+        let timeout = Duration::from_secs(ServerConfig::MACRO_CONSENT_TIMEOUT_SECS);
+        let accepted = tokio::select! {
+            _ = self.desk_consent.notified() => true,
+            _ = self.stop.notified() => false,
+            _ = tokio::time::sleep(timeout) => false,
+        };
+        self.awaiting_desk.store(false, Ordering::Relaxed);
+        accepted
+    }
+
+    /// Drops the recording in progress and whatever it captured
+    fn discard(&self, name: &str, reason: &str) {
+        log::info!("Recording of macro '{}' {}", name, reason);
+        *self.lock_capture() = Capture::default();
+        self.events.publish(ServerEvent::MacroRecordingCancelled {
+            name: name.to_string(),
+        });
+    }
+
+    async fn stopped_within(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = self.stop.notified() => true,
+            _ = tokio::time::sleep(duration) => false,
+        }
+    }
+
+    /// Subscribes to local input and the consent hotkey the first time a recording starts
+    fn tap(&self) {
+        self.tapped.call_once(|| {
+            let capture = Arc::clone(&self.capture);
+            input_tap::subscribe(move |event| {
+                let mut capture = capture.lock().expect("Macro recorder mutex poisoned");
+                if let Some(started) = capture.started {
+                    let ms = started.elapsed().as_millis() as u64;
+                    capture.events.push((ms, event.clone()));
+                }
+            });
+            let awaiting = Arc::clone(&self.awaiting_desk);
+            let consent = Arc::clone(&self.desk_consent);
+            hotkey::watch(ServerConfig::MACRO_CONSENT_KEY, move || {
+                // Only a press while asked counts, so an earlier one can't answer a later request
+                if awaiting.swap(false, Ordering::Relaxed) {
+                    consent.notify_one();
+                }
+            });
+        });
+    }
+
+    fn save(&self, settings: &MacroSettings) {
+        if let Err(e) = Settings::update_file(&self.settings_path, "macros", settings) {
+            log::warn!("Failed to save {}: {}", self.settings_path.display(), e);
+        }
+    }
+
+    fn lock_settings(&self) -> MutexGuard<'_, MacroSettings> {
+        self.settings.lock().expect("Macro recorder mutex poisoned")
+    }
+
+    fn lock_capture(&self) -> MutexGuard<'_, Capture> {
+        self.capture.lock().expect("Macro recorder mutex poisoned")
+    }
+}
+
+/// Turns captured local input into macro steps, releasing whatever is still held at the end
+///
+/// Pointer positions become relative moves from the first one seen, and releases of anything
+/// pressed before capture began are left out.
+fn macro_steps(captured: &[(u64, TapEvent)]) -> Vec<MacroStep> {
+    let mut timeline = Timeline::default();
+    for (at, event) in captured {
+        timeline.add(*at, event);
+    }
+    timeline.finish()
+}
+
+#[derive(Default)]
+struct Timeline {
+    steps: Vec<MacroStep>,
+    /// Time of the last step, in milliseconds into the capture
+    last_ms: u64,
+    position: Option<(f64, f64)>,
+    modifiers: ModifierKeys,
+    keys: Vec<String>,
+    buttons: Vec<u8>,
+}
+
+impl Timeline {
+    fn add(&mut self, at: u64, event: &TapEvent) {
+        let command = match event {
+            TapEvent::MoveTo { x, y } => {
+                let Some((from_x, from_y)) = self.position.replace((*x, *y)) else {
+                    return;
+                };
+                let (dx, dy) = (x - from_x, y - from_y);
+                if let Some(MacroStep {
+                    command: Command::MouseMove { x, y },
+                    ..
+                }) = self.steps.last_mut()
+                {
+                    if at.saturating_sub(self.last_ms) < ServerConfig::MACRO_MOVE_MERGE_MS {
+                        *x += dx;
+                        *y += dy;
+                        return;
+                    }
+                }
+                Command::MouseMove { x: dx, y: dy }
+            }
+            TapEvent::Button { button, down: true } => {
+                if self.buttons.contains(button) {
+                    return;
+                }
+                self.buttons.push(*button);
+                Command::MouseDown { button: *button }
+            }
+            TapEvent::Button {
+                button,
+                down: false,
+            } => {
+                if !remove(&mut self.buttons, button) {
+                    return;
+                }
+                Command::MouseUp { button: *button }
+            }
+            TapEvent::Wheel { delta_x, delta_y } => Command::MouseScroll {
+                delta_x: *delta_x,
+                delta_y: *delta_y,
+            },
+            // Key repeat is kept, so held keys repeat on playback too
+            TapEvent::Key { key, down: true } => {
+                if !self.keys.contains(key) {
+                    self.keys.push(key.clone());
+                }
+                Command::KeyPress {
+                    key: key.clone(),
+                    modifiers: self.modifiers.clone(),
+                }
+            }
+            TapEvent::Key { key, down: false } => {
+                if !remove(&mut self.keys, key) {
+                    return;
+                }
+                Command::KeyRelease {
+                    key: key.clone(),
+                    modifiers: self.modifiers.clone(),
+                }
+            }
+            TapEvent::Modifier { modifier, down } => {
+                let held = held(&mut self.modifiers, modifier);
+                if *held == *down {
+                    return;
+                }
+                *held = *down;
+                let modifier = modifier.to_string();
+                match down {
+                    true => Command::ModifierPress { modifier },
+                    false => Command::ModifierRelease { modifier },
+                }
+            }
+        };
+        self.push(at, command);
+    }
+
+    fn push(&mut self, at: u64, command: Command) {
+        let at = at.max(self.last_ms);
+        self.steps.push(MacroStep {
+            after_ms: at - self.last_ms,
+            command,
+        });
+        self.last_ms = at;
+    }
+
+    fn finish(mut self) -> Vec<MacroStep> {
+        let at = self.last_ms;
+        for button in std::mem::take(&mut self.buttons) {
+            self.push(at, Command::MouseUp { button });
+        }
+        for key in std::mem::take(&mut self.keys) {
+            let modifiers = self.modifiers.clone();
+            self.push(at, Command::KeyRelease { key, modifiers });
+        }
+        for modifier in ["ctrl", "alt", "shift", "meta"] {
+            if *held(&mut self.modifiers, modifier) {
+                let modifier = modifier.to_string();
+                self.push(at, Command::ModifierRelease { modifier });
+            }
+        }
+        self.steps
+    }
+}
+
+fn held<'a>(modifiers: &'a mut ModifierKeys, modifier: &str) -> &'a mut bool {
+    match modifier {
+        "ctrl" => &mut modifiers.ctrl,
+        "alt" => &mut modifiers.alt,
+        "shift" => &mut modifiers.shift,
+        _ => &mut modifiers.meta,
+    }
+}
+
+fn remove<T: PartialEq>(held: &mut Vec<T>, item: &T) -> bool {
+    let Some(index) = held.iter().position(|held| held == item) else {
+        return false;
+    };
+    held.remove(index);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_merge_moves_carry_modifiers_and_release_what_is_held() {
+        let key = |key: &str, down| TapEvent::Key {
+            key: key.to_string(),
+            down,
+        };
+        let ctrl = |down| TapEvent::Modifier {
+            modifier: "ctrl",
+            down,
+        };
+        let captured = [
+            (0, TapEvent::MoveTo { x: 100.0, y: 100.0 }),
+            (5, TapEvent::MoveTo { x: 110.0, y: 100.0 }),
+            (10, TapEvent::MoveTo { x: 115.0, y: 104.0 }),
+            (40, ctrl(true)),
+            (50, key("c", true)),
+            (55, key("c", true)),
+            (60, ctrl(false)),
+            (
+                70,
+                TapEvent::Button {
+                    button: 1,
+                    down: true,
+                },
+            ),
+            // Pressed before the capture began
+            (80, key("v", false)),
+        ];
+
+        let steps = macro_steps(&captured);
+        let timeline: Vec<_> = steps
+            .iter()
+            .map(|step| (step.after_ms, step.command.kind()))
+            .collect();
+        assert_eq!(
+            timeline,
+            [
+                (5, "MouseMove"),
+                (35, "ModifierPress"),
+                (10, "KeyPress"),
+                (5, "KeyPress"),
+                (5, "ModifierRelease"),
+                (10, "MouseDown"),
+                (0, "MouseUp"),
+                (0, "KeyRelease"),
+            ]
+        );
+        assert!(matches!(
+            steps[0].command,
+            Command::MouseMove { x, y } if x == 15.0 && y == 4.0
+        ));
+        assert!(matches!(
+            &steps[2].command,
+            Command::KeyPress { key, modifiers } if key == "c" && modifiers.ctrl
+        ));
+    }
+
+    #[test]
+    fn test_hand_written_pauses_are_cut_to_max_secs() {
+        let click = |after_ms| MacroStep {
+            after_ms,
+            command: Command::MouseClick { button: 1 },
+        };
+        let mut settings = MacroSettings {
+            max_secs: 2,
+            ..MacroSettings::default()
+        };
+        settings.saved.insert(
+            "Slow".to_string(),
+            vec![click(500), click(u64::MAX), click(700)],
+        );
+        let recorder = MacroRecorder::new(settings, PathBuf::new(), EventBus::new(), false);
+        let pauses: Vec<u64> = recorder
+            .get("Slow")
+            .unwrap()
+            .iter()
+            .map(|step| step.after_ms)
+            .collect();
+        assert_eq!(pauses, [500, 1500, 0]);
+        assert!(recorder.get("Missing").is_none());
+    }
+}
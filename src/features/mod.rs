@@ -1,2 +1,28 @@
+pub mod audit;
+pub mod auth;
+pub mod backup;
+pub mod calibration;
+pub mod captions;
+pub mod clipboard;
 pub mod command;
+pub mod cursor;
+pub mod diagnostics;
 pub mod discovery;
+pub mod events;
+pub mod game_mode;
+pub mod geofence;
+pub mod hot_corners;
+pub mod lifecycle;
+pub mod macros;
+pub mod notifications;
+pub mod presence;
+pub mod profile;
+#[cfg(feature = "rendezvous")]
+pub mod rendezvous;
+pub mod schedule;
+pub mod service;
+pub mod session;
+pub mod smoothing;
+pub mod stats;
+pub mod streaming;
+pub mod telemetry;
@@ -0,0 +1,70 @@
+use crate::domain::models::ServerEvent;
+
+/// Incremental parser for `dbus-monitor` output of `org.freedesktop.Notifications.Notify` calls
+///
+/// The call's leading string arguments are app name, icon, and summary; only the app name
+/// and summary are forwarded, never the body.
+#[derive(Default)]
+pub struct NotifyParser {
+    strings: Option<Vec<String>>,
+}
+
+impl NotifyParser {
+    pub fn feed(&mut self, line: &str) -> Option<ServerEvent> {
+        if !line.starts_with(' ') {
+            let is_notify = line.starts_with("method call") && line.contains("member=Notify");
+            self.strings = is_notify.then(Vec::new);
+            return None;
+        }
+
+        let strings = self.strings.as_mut()?;
+        let value = line.trim_start().strip_prefix("string \"")?;
+        strings.push(value.strip_suffix('"').unwrap_or(value).to_string());
+        if strings.len() < 3 {
+            return None;
+        }
+
+        let strings = self.strings.take()?;
+        Some(ServerEvent::DesktopNotification {
+            app: strings[0].clone(),
+            title: strings[2].clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTIFY: &str = r#"method call time=1700000000.1 sender=:1.58 -> destination=:1.25 serial=7 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "Thunderbird"
+   uint32 0
+   string "mail-unread"
+   string "New message from Ada"
+   string "Lunch tomorrow?"
+   array [
+   ]"#;
+
+    #[test]
+    fn test_parses_app_and_summary() {
+        let mut parser = NotifyParser::default();
+        let events: Vec<_> = NOTIFY
+            .lines()
+            .filter_map(|line| parser.feed(line))
+            .collect();
+        match events.as_slice() {
+            [ServerEvent::DesktopNotification { app, title }] => {
+                assert_eq!(app, "Thunderbird");
+                assert_eq!(title, "New message from Ada");
+            }
+            other => panic!("Unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ignores_other_calls() {
+        let mut parser = NotifyParser::default();
+        let other = "method call time=1 sender=:1.5 -> destination=:1.25 serial=3 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=GetCapabilities\n   string \"x\"\n   string \"y\"\n   string \"z\"";
+        assert!(other.lines().all(|line| parser.feed(line).is_none()));
+    }
+}
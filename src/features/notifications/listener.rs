@@ -0,0 +1,70 @@
+use crate::domain::models::ServerEvent;
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use windows::UI::Notifications::Management::{
+    UserNotificationListener, UserNotificationListenerAccessStatus,
+};
+use windows::UI::Notifications::{KnownNotificationBindings, NotificationKinds};
+
+/// Polls the Windows notification center for toasts not seen before
+pub struct ToastListener {
+    listener: UserNotificationListener,
+    seen: HashSet<u32>,
+    primed: bool,
+}
+
+impl ToastListener {
+    /// Asks for notification access; Windows shows its own consent prompt the first time
+    pub fn new() -> Result<Self> {
+        let listener = UserNotificationListener::Current()?;
+        let status = listener.RequestAccessAsync()?.get()?;
+        if status != UserNotificationListenerAccessStatus::Allowed {
+            return Err(anyhow!("notification access was not granted"));
+        }
+        let mut toasts = Self {
+            listener,
+            seen: HashSet::new(),
+            primed: false,
+        };
+        // Don't replay whatever is already sitting in the action center
+        toasts.poll()?;
+        Ok(toasts)
+    }
+
+    pub fn poll(&mut self) -> Result<Vec<ServerEvent>> {
+        let notifications = self
+            .listener
+            .GetNotificationsAsync(NotificationKinds::Toast)?
+            .get()?;
+        let report = std::mem::replace(&mut self.primed, true);
+        let mut current = HashSet::new();
+        let mut events = Vec::new();
+
+        for notification in &notifications {
+            let id = notification.Id()?;
+            current.insert(id);
+            if !report || self.seen.contains(&id) {
+                continue;
+            }
+            let app = notification
+                .AppInfo()
+                .and_then(|info| info.DisplayInfo())
+                .and_then(|display| display.DisplayName())
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+            let title = notification
+                .Notification()
+                .and_then(|toast| toast.Visual())
+                .and_then(|visual| visual.GetBinding(&KnownNotificationBindings::ToastGeneric()?))
+                .and_then(|binding| binding.GetTextElements())
+                .and_then(|texts| texts.GetAt(0))
+                .and_then(|text| text.Text())
+                .map(|text| text.to_string())
+                .unwrap_or_default();
+            events.push(ServerEvent::DesktopNotification { app, title });
+        }
+
+        self.seen = current;
+        Ok(events)
+    }
+}
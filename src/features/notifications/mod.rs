@@ -0,0 +1,5 @@
+#[cfg(target_os = "linux")]
+mod dbus_monitor;
+#[cfg(windows)]
+mod listener;
+pub mod notification_service;
@@ -0,0 +1,88 @@
+use crate::domain::config::settings::NotificationSettings;
+use crate::features::events::event_bus::EventBus;
+
+/// Opt-in forwarding of desktop notification titles to clients over `/events`
+pub struct NotificationService {
+    settings: NotificationSettings,
+    events: EventBus,
+}
+
+impl NotificationService {
+    pub fn new(settings: NotificationSettings, events: EventBus) -> Self {
+        Self { settings, events }
+    }
+
+    pub async fn run(&self) {
+        if !self.settings.enabled {
+            return;
+        }
+        if let Err(e) = self.mirror().await {
+            log::warn!("Notification mirroring stopped: {}", e);
+        }
+    }
+
+    /// Follows the session bus through `dbus-monitor`
+    #[cfg(target_os = "linux")]
+    async fn mirror(&self) -> anyhow::Result<()> {
+        use super::dbus_monitor::NotifyParser;
+        use std::process::Stdio;
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut child = tokio::process::Command::new("dbus-monitor")
+            .args([
+                "--session",
+                "interface='org.freedesktop.Notifications',member='Notify'",
+            ])
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("dbus-monitor has no stdout"))?;
+
+        let mut parser = NotifyParser::default();
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            if let Some(event) = parser.feed(&line) {
+                self.events.publish(event);
+            }
+        }
+        Err(anyhow::anyhow!("dbus-monitor exited"))
+    }
+
+    /// Polls the notification center; Windows has no push API for unpackaged apps
+    #[cfg(windows)]
+    async fn mirror(&self) -> anyhow::Result<()> {
+        use super::listener::ToastListener;
+        use crate::domain::config::ServerConfig;
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let listener = tokio::task::spawn_blocking(ToastListener::new).await??;
+        let listener = Arc::new(Mutex::new(listener));
+        let mut interval = tokio::time::interval(Duration::from_millis(
+            ServerConfig::NOTIFICATION_POLL_INTERVAL_MS,
+        ));
+        loop {
+            interval.tick().await;
+            let listener = listener.clone();
+            let events = tokio::task::spawn_blocking(move || {
+                listener
+                    .lock()
+                    .expect("Toast listener mutex poisoned")
+                    .poll()
+            })
+            .await??;
+            for event in events {
+                self.events.publish(event);
+            }
+        }
+    }
+
+    /// macOS offers no permitted way to read other apps' notifications
+    #[cfg(target_os = "macos")]
+    async fn mirror(&self) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("not supported on macOS"))
+    }
+}
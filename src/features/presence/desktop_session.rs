@@ -0,0 +1,70 @@
+use crate::domain::models::ServerEvent;
+use crate::features::events::event_bus::EventBus;
+use crate::input::desktop_session::{self, SessionState};
+use std::sync::{Arc, Mutex};
+
+/// Tracks whether the desktop session we inject into is attached to a display
+///
+/// Assumed active until the OS says otherwise; only Windows reports changes today.
+pub struct DesktopSession {
+    state: Mutex<SessionState>,
+    events: EventBus,
+}
+
+impl DesktopSession {
+    pub fn new(events: EventBus) -> Self {
+        Self {
+            state: Mutex::new(SessionState::Active),
+            events,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        *self.lock_state()
+    }
+
+    /// True while injected input would be dropped because no one is attached to the session
+    pub fn is_disconnected(&self) -> bool {
+        self.state() == SessionState::Disconnected
+    }
+
+    /// Watches session notifications on a dedicated thread, since they arrive through a window
+    pub fn watch(self: Arc<Self>) {
+        let spawned = std::thread::Builder::new()
+            .name("desktop-session".to_string())
+            .spawn(move || desktop_session::watch(move |state| self.observe(state)));
+        if let Err(e) = spawned {
+            log::warn!("Failed to start desktop session watch: {}", e);
+        }
+    }
+
+    /// Records a state reported from elsewhere, such as the service control manager
+    pub fn observe(&self, state: SessionState) {
+        let previous = std::mem::replace(&mut *self.lock_state(), state);
+        if previous == state {
+            return;
+        }
+        log::info!("Desktop session changed: {:?} -> {:?}", previous, state);
+        self.events
+            .publish(ServerEvent::DesktopSessionChanged { state });
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, SessionState> {
+        self.state.lock().expect("Desktop session mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disconnect_is_tracked() {
+        let session = DesktopSession::new(EventBus::new());
+        assert!(!session.is_disconnected());
+        session.observe(SessionState::Disconnected);
+        assert!(session.is_disconnected());
+        session.observe(SessionState::Locked);
+        assert_eq!(session.state(), SessionState::Locked);
+    }
+}
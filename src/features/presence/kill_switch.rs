@@ -0,0 +1,108 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::ServerEvent;
+use crate::features::events::event_bus::EventBus;
+use crate::input::hotkey;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Lets the person at the desk suspend all remote input by pressing Ctrl+Alt+Esc twice
+///
+/// While engaged, every command that needs a scope is refused and whatever clients held down
+/// is released. The same double press resumes remote input.
+pub struct KillSwitch {
+    events: EventBus,
+    engaged: AtomicBool,
+    last_press: Mutex<Option<Instant>>,
+    engaged_notify: Notify,
+}
+
+impl KillSwitch {
+    pub fn new(events: EventBus) -> Self {
+        Self {
+            events,
+            engaged: AtomicBool::new(false),
+            last_press: Mutex::new(None),
+            engaged_notify: Notify::new(),
+        }
+    }
+
+    /// True while remote input is suspended
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::Relaxed)
+    }
+
+    /// Counts a hotkey press; the second within the double-press window toggles the switch
+    ///
+    /// Returns whether it toggled.
+    pub fn press(&self, now: Instant) -> bool {
+        let window = Duration::from_millis(ServerConfig::KILL_SWITCH_DOUBLE_PRESS_MS);
+        let mut last_press = self.last_press.lock().expect("Kill switch mutex poisoned");
+        let double = last_press.is_some_and(|last| now.duration_since(last) <= window);
+        if !double {
+            *last_press = Some(now);
+            return false;
+        }
+        *last_press = None;
+        drop(last_press);
+        self.set(!self.is_engaged());
+        true
+    }
+
+    pub fn set(&self, engaged: bool) {
+        if self.engaged.swap(engaged, Ordering::Relaxed) == engaged {
+            return;
+        }
+        if engaged {
+            log::warn!("Kill switch engaged; remote input suspended until it's pressed again");
+            self.engaged_notify.notify_one();
+            self.events.publish(ServerEvent::RemoteInputSuspended);
+        } else {
+            log::info!("Kill switch released; remote input resumed");
+            self.events.publish(ServerEvent::RemoteInputResumed);
+        }
+    }
+
+    /// Waits for the switch to be engaged, for releasing held input right away
+    pub async fn engaged(&self) {
+        self.engaged_notify.notified().await;
+    }
+
+    /// Listens for the hotkey at this desk
+    pub fn watch(self: Arc<Self>) {
+        hotkey::watch(ServerConfig::KILL_SWITCH_KEY, move || {
+            self.press(Instant::now());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_press_toggles() {
+        let switch = KillSwitch::new(EventBus::new());
+        let now = Instant::now();
+        assert!(!switch.press(now));
+        assert!(switch.press(now + Duration::from_millis(200)));
+        assert!(switch.is_engaged());
+
+        let later = now + Duration::from_secs(5);
+        assert!(!switch.press(later));
+        assert!(switch.is_engaged());
+        assert!(switch.press(later + Duration::from_millis(100)));
+        assert!(!switch.is_engaged());
+    }
+
+    #[test]
+    fn test_presses_too_far_apart_do_nothing() {
+        let switch = KillSwitch::new(EventBus::new());
+        let now = Instant::now();
+        let window = Duration::from_millis(ServerConfig::KILL_SWITCH_DOUBLE_PRESS_MS);
+        assert!(!switch.press(now));
+        assert!(!switch.press(now + window * 2));
+        assert!(!switch.is_engaged());
+    }
+}
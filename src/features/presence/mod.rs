@@ -0,0 +1,3 @@
+pub mod desktop_session;
+pub mod kill_switch;
+pub mod presence_monitor;
@@ -0,0 +1,150 @@
+use crate::domain::config::settings::LocalInputSettings;
+use crate::domain::config::ServerConfig;
+use crate::domain::models::ServerEvent;
+use crate::features::events::event_bus::EventBus;
+#[cfg(windows)]
+use crate::features::service::agent_link;
+use crate::input::idle;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Detects input from the person at the desk and yields remote control to them.
+///
+/// The OS only reports when the last input happened, not who produced it, so any input
+/// newer than our own last injection (plus a tolerance) is treated as physical.
+pub struct PresenceMonitor {
+    settings: LocalInputSettings,
+    events: EventBus,
+    state: Mutex<PresenceState>,
+}
+
+struct PresenceState {
+    last_injected: Instant,
+    local_active_until: Option<Instant>,
+}
+
+impl PresenceMonitor {
+    pub fn new(settings: LocalInputSettings, events: EventBus) -> Self {
+        Self {
+            settings,
+            events,
+            state: Mutex::new(PresenceState {
+                last_injected: Instant::now(),
+                local_active_until: None,
+            }),
+        }
+    }
+
+    /// Marks the end of an injected event so the OS idle reset it caused is not misread
+    pub fn record_injected(&self) {
+        self.lock_state().last_injected = Instant::now();
+    }
+
+    /// True while remote input should yield to the local user
+    pub fn is_local_active(&self) -> bool {
+        self.lock_state()
+            .local_active_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Polls the OS idle time; returns immediately when auto-yield is disabled
+    pub async fn run(&self) {
+        if !self.settings.auto_yield {
+            return;
+        }
+        let mut interval = tokio::time::interval(Duration::from_millis(
+            ServerConfig::PRESENCE_POLL_INTERVAL_MS,
+        ));
+        loop {
+            interval.tick().await;
+            if let Some(idle) = time_since_last_input().await {
+                self.observe(Instant::now(), idle);
+            }
+        }
+    }
+
+    fn observe(&self, now: Instant, idle: Duration) {
+        let Some(last_input) = now.checked_sub(idle) else {
+            return;
+        };
+        let tolerance = Duration::from_millis(ServerConfig::PRESENCE_INJECTION_TOLERANCE_MS);
+        let grace = Duration::from_millis(self.settings.grace_ms);
+
+        let mut state = self.lock_state();
+        if last_input <= state.last_injected + tolerance {
+            return;
+        }
+
+        let was_active = state.local_active_until.is_some_and(|until| now < until);
+        state.local_active_until = Some(last_input + grace);
+        drop(state);
+
+        if !was_active {
+            log::info!("Local input detected - yielding remote control");
+            self.events.publish(ServerEvent::LocalInputDetected {
+                grace_ms: self.settings.grace_ms,
+            });
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, PresenceState> {
+        self.state.lock().expect("Presence state mutex poisoned")
+    }
+}
+
+/// Idle time in the user's session, which a Windows service asks its agent for
+async fn time_since_last_input() -> Option<Duration> {
+    #[cfg(windows)]
+    if let Some(agent) = agent_link::installed() {
+        return agent.idle().await;
+    }
+    tokio::task::spawn_blocking(idle::time_since_last_input)
+        .await
+        .ok()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> PresenceMonitor {
+        PresenceMonitor::new(
+            LocalInputSettings {
+                auto_yield: true,
+                grace_ms: 1000,
+                ..LocalInputSettings::default()
+            },
+            EventBus::new(),
+        )
+    }
+
+    #[test]
+    fn test_input_after_injection_yields() {
+        let monitor = monitor();
+        monitor.record_injected();
+        let now = Instant::now() + Duration::from_millis(500);
+        monitor.observe(now, Duration::from_millis(10));
+        assert!(monitor.is_local_active());
+    }
+
+    #[test]
+    fn test_own_injection_does_not_yield() {
+        let monitor = monitor();
+        monitor.record_injected();
+        monitor.observe(Instant::now(), Duration::ZERO);
+        assert!(!monitor.is_local_active());
+    }
+
+    #[test]
+    fn test_yield_expires_after_grace() {
+        let monitor = monitor();
+        let now = Instant::now() + Duration::from_millis(500);
+        monitor.observe(now, Duration::ZERO);
+        let state = monitor.lock_state();
+        assert_eq!(
+            state.local_active_until,
+            Some(now + Duration::from_millis(1000))
+        );
+    }
+}
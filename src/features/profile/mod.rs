@@ -0,0 +1 @@
+pub mod profile_service;
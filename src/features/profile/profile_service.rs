@@ -0,0 +1,198 @@
+use crate::domain::config::settings::{Profile, ProfileSettings};
+use crate::domain::models::{Command, Scope, ServerEvent};
+use crate::features::events::event_bus::EventBus;
+use serde::Serialize;
+use std::sync::{Mutex, MutexGuard};
+
+/// Holds the active settings profile and applies it to incoming input
+pub struct ProfileService {
+    profiles: Vec<Profile>,
+    active: Mutex<Option<Profile>>,
+    events: EventBus,
+}
+
+/// Available profiles and the active one, as reported to clients and the tray
+#[derive(Serialize, Debug, Clone)]
+pub struct ProfileList {
+    pub active: Option<String>,
+    pub profiles: Vec<Profile>,
+}
+
+/// `SetProfile` named a profile that isn't configured
+#[derive(Debug, thiserror::Error)]
+#[error("unknown profile '{0}'")]
+pub struct UnknownProfile(pub String);
+
+impl ProfileService {
+    pub fn new(settings: ProfileSettings, events: EventBus) -> Self {
+        let active = settings.active.as_deref().and_then(|name| {
+            let profile = find(&settings.profiles, name);
+            if profile.is_none() {
+                log::warn!("Active profile '{}' is not configured", name);
+            }
+            profile
+        });
+        Self {
+            profiles: settings.profiles,
+            active: Mutex::new(active),
+            events,
+        }
+    }
+
+    pub fn active_name(&self) -> Option<String> {
+        self.lock_active()
+            .as_ref()
+            .map(|profile| profile.name.clone())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect()
+    }
+
+    pub fn list(&self) -> ProfileList {
+        ProfileList {
+            active: self.active_name(),
+            profiles: self.profiles.clone(),
+        }
+    }
+
+    /// Switches to the profile called `name`, matched case-insensitively
+    pub fn activate(&self, name: &str) -> Result<String, UnknownProfile> {
+        let profile = find(&self.profiles, name).ok_or_else(|| UnknownProfile(name.to_string()))?;
+        let name = profile.name.clone();
+        let previous = self.lock_active().replace(profile);
+        if previous.is_some_and(|previous| previous.name == name) {
+            return Ok(name);
+        }
+        log::info!("Switched to profile '{}'", name);
+        self.events
+            .publish(ServerEvent::ProfileChanged { name: name.clone() });
+        Ok(name)
+    }
+
+    /// False when the active profile disables commands of this scope
+    pub fn permits(&self, scope: Scope) -> bool {
+        self.lock_active()
+            .as_ref()
+            .is_none_or(|profile| profile.scopes.contains(&scope))
+    }
+
+    /// Scales pointer and scroll motion and flattens gestures per the active profile
+    pub fn adjust(&self, command: Command) -> Command {
+        let active = self.lock_active();
+        let Some(profile) = active.as_ref() else {
+            return command;
+        };
+        let scroll = if profile.natural_scroll {
+            -profile.scroll_speed
+        } else {
+            profile.scroll_speed
+        };
+        match command {
+            Command::MouseMove { x, y } => Command::MouseMove {
+                x: x * profile.pointer_sensitivity,
+                y: y * profile.pointer_sensitivity,
+            },
+            Command::MouseScroll { delta_x, delta_y } => Command::MouseScroll {
+                delta_x: delta_x * scroll,
+                delta_y: delta_y * scroll,
+            },
+            Command::MouseMultiClick { button, .. } | Command::MouseLongPress { button, .. }
+                if !profile.gestures =>
+            {
+                Command::MouseClick { button }
+            }
+            command => command,
+        }
+    }
+
+    fn lock_active(&self) -> MutexGuard<'_, Option<Profile>> {
+        self.active.lock().expect("Active profile mutex poisoned")
+    }
+}
+
+fn find(profiles: &[Profile], name: &str) -> Option<Profile> {
+    profiles
+        .iter()
+        .find(|profile| profile.name.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn service(active: Option<&str>) -> ProfileService {
+        let settings = ProfileSettings {
+            active: active.map(str::to_string),
+            ..ProfileSettings::default()
+        };
+        ProfileService::new(settings, EventBus::new())
+    }
+
+    #[test]
+    fn test_no_profile_leaves_input_untouched() {
+        let service = service(None);
+        let adjusted = service.adjust(Command::MouseMove { x: 10.0, y: -4.0 });
+        assert!(matches!(adjusted, Command::MouseMove { x, y } if x == 10.0 && y == -4.0));
+        assert!(service.permits(Scope::System));
+    }
+
+    #[test]
+    fn test_activate_scales_motion() {
+        let service = service(None);
+        assert_eq!(service.activate("precision").unwrap(), "Precision");
+        let adjusted = service.adjust(Command::MouseMove { x: 10.0, y: 5.0 });
+        assert!(matches!(adjusted, Command::MouseMove { x, y } if x == 4.0 && y == 2.0));
+        assert!(service.activate("Missing").is_err());
+        assert_eq!(service.active_name().as_deref(), Some("Precision"));
+    }
+
+    #[test]
+    fn test_presentation_flattens_gestures_and_limits_scopes() {
+        let service = service(Some("Presentation"));
+        let adjusted = service.adjust(Command::MouseMultiClick {
+            button: 1,
+            count: 2,
+        });
+        assert!(matches!(adjusted, Command::MouseClick { button: 1 }));
+        assert!(service.permits(Scope::Keyboard));
+        assert!(!service.permits(Scope::System));
+    }
+
+    fn moved_by(sensitivity: f64, x: f64, y: f64) -> (f64, f64) {
+        let settings = ProfileSettings {
+            active: Some("Custom".to_string()),
+            profiles: vec![Profile {
+                name: "Custom".to_string(),
+                pointer_sensitivity: sensitivity,
+                ..Profile::default()
+            }],
+        };
+        let service = ProfileService::new(settings, EventBus::new());
+        match service.adjust(Command::MouseMove { x, y }) {
+            Command::MouseMove { x, y } => (x, y),
+            other => panic!("Expected MouseMove, got {:?}", other),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_higher_sensitivity_never_moves_less(
+            low in 0.0..10.0f64,
+            extra in 0.0..10.0f64,
+            x in -1.0e6..1.0e6f64,
+            y in -1.0e6..1.0e6f64,
+        ) {
+            let (slow_x, slow_y) = moved_by(low, x, y);
+            let (fast_x, fast_y) = moved_by(low + extra, x, y);
+            prop_assert!(fast_x.abs() >= slow_x.abs());
+            prop_assert!(fast_y.abs() >= slow_y.abs());
+            prop_assert!(fast_x.is_finite() && fast_y.is_finite());
+        }
+    }
+}
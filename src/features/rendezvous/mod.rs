@@ -0,0 +1,3 @@
+pub mod protocol;
+pub mod relay;
+pub mod rendezvous_service;
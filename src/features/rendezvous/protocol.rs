@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+/// Side of a rendezvous room a peer registers as
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Server,
+    Client,
+}
+
+/// Datagrams exchanged with the relay and, for hole punching, between peers
+///
+/// Wire tags are prefixed so they can't be mistaken for commands on the shared socket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum RendezvousMessage {
+    /// Peer → relay; repeated as a keepalive to hold the NAT mapping open
+    #[serde(rename = "RendezvousRegister")]
+    Register { room: String, role: Role },
+    /// Relay → peer; the public address the other side of the room was seen from
+    #[serde(rename = "RendezvousPeer")]
+    Peer { addr: SocketAddr },
+    /// Peer → peer; opens the NAT mapping toward the other side
+    #[serde(rename = "RendezvousPunch")]
+    Punch { room: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_wire_format() {
+        let message = RendezvousMessage::Register {
+            room: "den".to_string(),
+            role: Role::Server,
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"type":"RendezvousRegister","room":"den","role":"server"}"#
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::domain::config::ServerConfig;
+use crate::features::rendezvous::protocol::{RendezvousMessage, Role};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Minimal user-run relay that introduces a server and a client sharing a room name
+///
+/// It only forwards public addresses; commands always flow directly between the peers.
+pub async fn run(bind: SocketAddr) -> Result<()> {
+    let socket = UdpSocket::bind(bind).await?;
+    log::info!("Rendezvous relay listening on {}", bind);
+
+    let mut rooms = Rooms::default();
+    let mut buf = [0; ServerConfig::COMMAND_BUFFER_SIZE];
+    loop {
+        let Ok((size, addr)) = socket.recv_from(&mut buf).await else {
+            continue;
+        };
+        let Ok(RendezvousMessage::Register { room, role }) = serde_json::from_slice(&buf[..size])
+        else {
+            continue;
+        };
+
+        let Some((server, client)) = rooms.register(&room, role, addr, Instant::now()) else {
+            continue;
+        };
+        introduce(&socket, server, client).await;
+        introduce(&socket, client, server).await;
+    }
+}
+
+async fn introduce(socket: &UdpSocket, to: SocketAddr, peer: SocketAddr) {
+    let message = RendezvousMessage::Peer { addr: peer };
+    if let Ok(json) = serde_json::to_vec(&message) {
+        let _ = socket.send_to(&json, to).await;
+    }
+}
+
+#[derive(Default)]
+struct Room {
+    server: Option<(SocketAddr, Instant)>,
+    client: Option<(SocketAddr, Instant)>,
+}
+
+impl Room {
+    /// Forgets sides that went silent; false once neither is left
+    fn expire(&mut self, now: Instant) -> bool {
+        let timeout = Duration::from_secs(ServerConfig::RENDEZVOUS_PEER_TIMEOUT_SECS);
+        for side in [&mut self.server, &mut self.client] {
+            if side.is_some_and(|(_, seen)| now.duration_since(seen) > timeout) {
+                *side = None;
+            }
+        }
+        self.server.is_some() || self.client.is_some()
+    }
+}
+
+#[derive(Default)]
+struct Rooms {
+    rooms: HashMap<String, Room>,
+    swept: Option<Instant>,
+}
+
+impl Rooms {
+    /// Records a registration; returns `(server, client)` once both sides are live
+    ///
+    /// Silent rooms are swept out every few seconds rather than on every datagram, and new
+    /// rooms are turned away while the table is full.
+    fn register(
+        &mut self,
+        room: &str,
+        role: Role,
+        addr: SocketAddr,
+        now: Instant,
+    ) -> Option<(SocketAddr, SocketAddr)> {
+        let sweep = Duration::from_secs(ServerConfig::RENDEZVOUS_SWEEP_SECS);
+        if self
+            .swept
+            .is_none_or(|swept| now.duration_since(swept) >= sweep)
+        {
+            self.rooms.retain(|_, room| room.expire(now));
+            self.swept = Some(now);
+        }
+        if !self.rooms.contains_key(room) && self.rooms.len() >= ServerConfig::RENDEZVOUS_MAX_ROOMS
+        {
+            log::debug!("Relay is full; ignoring registration from {}", addr);
+            return None;
+        }
+
+        let entry = self.rooms.entry(room.to_string()).or_default();
+        entry.expire(now);
+        match role {
+            Role::Server => entry.server = Some((addr, now)),
+            Role::Client => entry.client = Some((addr, now)),
+        }
+        Some((entry.server?.0, entry.client?.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([203, 0, 113, 7], port))
+    }
+
+    #[test]
+    fn test_pairs_server_and_client_in_same_room() {
+        let mut rooms = Rooms::default();
+        let now = Instant::now();
+        assert_eq!(rooms.register("den", Role::Server, addr(1), now), None);
+        assert_eq!(rooms.register("attic", Role::Client, addr(2), now), None);
+        assert_eq!(
+            rooms.register("den", Role::Client, addr(3), now),
+            Some((addr(1), addr(3)))
+        );
+    }
+
+    #[test]
+    fn test_stale_registration_is_forgotten() {
+        let mut rooms = Rooms::default();
+        let now = Instant::now();
+        rooms.register("den", Role::Server, addr(1), now);
+        let later = now + Duration::from_secs(ServerConfig::RENDEZVOUS_PEER_TIMEOUT_SECS + 1);
+        assert_eq!(rooms.register("den", Role::Client, addr(2), later), None);
+    }
+
+    #[test]
+    fn test_full_relay_turns_away_new_rooms_until_they_expire() {
+        let mut rooms = Rooms::default();
+        let now = Instant::now();
+        for i in 0..ServerConfig::RENDEZVOUS_MAX_ROOMS {
+            rooms.register(&format!("room-{}", i), Role::Server, addr(1), now);
+        }
+        rooms.register("den", Role::Server, addr(2), now);
+        assert_eq!(rooms.register("den", Role::Client, addr(3), now), None);
+        assert_eq!(
+            rooms.register("room-0", Role::Client, addr(4), now),
+            Some((addr(1), addr(4)))
+        );
+
+        let later = now + Duration::from_secs(ServerConfig::RENDEZVOUS_PEER_TIMEOUT_SECS + 1);
+        rooms.register("den", Role::Server, addr(2), later);
+        assert_eq!(rooms.rooms.len(), 1);
+        assert_eq!(
+            rooms.register("den", Role::Client, addr(3), later),
+            Some((addr(2), addr(3)))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::domain::config::secrets;
+use crate::domain::config::settings::RendezvousSettings;
+use crate::domain::config::ServerConfig;
+use crate::features::rendezvous::protocol::{RendezvousMessage, Role};
+use anyhow::{anyhow, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Registers the command socket with a relay and punches through to clients it introduces
+///
+/// Shares the command socket so the NAT mapping the relay sees is the one clients reach.
+pub struct RendezvousService {
+    socket: Arc<UdpSocket>,
+    relay: SocketAddr,
+    room: String,
+}
+
+impl RendezvousService {
+    pub async fn new(settings: &RendezvousSettings, socket: Arc<UdpSocket>) -> Result<Self> {
+        let relay = settings
+            .relay
+            .as_deref()
+            .ok_or_else(|| anyhow!("rendezvous.relay is not set"))?;
+        if settings.room.is_empty() {
+            return Err(anyhow!("rendezvous.room is not set"));
+        }
+        let relay = tokio::net::lookup_host(relay)
+            .await?
+            .find(SocketAddr::is_ipv4)
+            .ok_or_else(|| anyhow!("could not resolve relay {}", relay))?;
+        Ok(Self {
+            socket,
+            relay,
+            room: secrets::open(&settings.room)?,
+        })
+    }
+
+    /// Re-registers periodically; the repeat doubles as the NAT keepalive
+    pub async fn run(&self) {
+        log::info!("Registering with rendezvous relay {}", self.relay);
+        let register = RendezvousMessage::Register {
+            room: self.room.clone(),
+            role: Role::Server,
+        };
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(ServerConfig::RENDEZVOUS_KEEPALIVE_SECS));
+        loop {
+            interval.tick().await;
+            self.send(&register, self.relay).await;
+        }
+    }
+
+    /// Handles a rendezvous datagram that arrived on the command socket
+    pub async fn handle(&self, message: RendezvousMessage, from: SocketAddr) {
+        match message {
+            RendezvousMessage::Peer { addr } if from == self.relay => {
+                log::info!("Relay introduced client at {}", addr);
+                let punch = RendezvousMessage::Punch {
+                    room: self.room.clone(),
+                };
+                for _ in 0..ServerConfig::RENDEZVOUS_PUNCH_ATTEMPTS {
+                    self.send(&punch, addr).await;
+                }
+            }
+            RendezvousMessage::Punch { .. } => {
+                log::debug!("Hole punched by {}", from);
+            }
+            _ => {}
+        }
+    }
+
+    async fn send(&self, message: &RendezvousMessage, to: SocketAddr) {
+        let Ok(json) = serde_json::to_vec(message) else {
+            return;
+        };
+        if let Err(e) = self.socket.send_to(&json, to).await {
+            log::debug!("Rendezvous send to {} failed: {}", to, e);
+        }
+    }
+}
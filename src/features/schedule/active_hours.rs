@@ -0,0 +1,71 @@
+use crate::domain::config::settings::ActiveHoursSettings;
+use chrono::{Local, NaiveTime};
+
+/// Daily window during which remote input is accepted
+pub struct ActiveHours {
+    settings: ActiveHoursSettings,
+}
+
+impl ActiveHours {
+    pub fn new(settings: ActiveHoursSettings) -> Self {
+        Self { settings }
+    }
+
+    /// Checks the window against the local wall clock
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(Local::now().time())
+    }
+
+    /// A window whose end precedes its start wraps past midnight (e.g. 22:00-06:00)
+    pub fn is_open_at(&self, time: NaiveTime) -> bool {
+        if !self.settings.enabled {
+            return true;
+        }
+        let ActiveHoursSettings { start, end, .. } = self.settings;
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(start: &str, end: &str) -> ActiveHours {
+        ActiveHours::new(ActiveHoursSettings {
+            enabled: true,
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+        })
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn test_daytime_window() {
+        let hours = hours("08:00", "22:00");
+        assert!(!hours.is_open_at(at("07:59")));
+        assert!(hours.is_open_at(at("08:00")));
+        assert!(hours.is_open_at(at("21:59")));
+        assert!(!hours.is_open_at(at("22:00")));
+    }
+
+    #[test]
+    fn test_window_wrapping_midnight() {
+        let hours = hours("22:00", "06:00");
+        assert!(hours.is_open_at(at("23:30")));
+        assert!(hours.is_open_at(at("05:59")));
+        assert!(!hours.is_open_at(at("12:00")));
+    }
+
+    #[test]
+    fn test_disabled_is_always_open() {
+        let hours = ActiveHours::new(ActiveHoursSettings::default());
+        assert!(hours.is_open_at(at("03:00")));
+    }
+}
@@ -0,0 +1 @@
+pub mod active_hours;
@@ -0,0 +1,117 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{Command, PowerState};
+use crate::features::service::agent_protocol::{self, AgentReply, AgentRequest};
+use crate::input::{cursor_size, display, idle, pointer, InputHandler};
+use anyhow::{anyhow, Result};
+use tokio::io::BufReader;
+use tokio::net::windows::named_pipe::ClientOptions;
+
+/// `pointzerver agent <pipe>`: does what the service in session 0 can't, in the user's session
+///
+/// Launched by the service into the user's session; exits when the service closes the pipe.
+pub async fn run(pipe_name: &str) -> Result<()> {
+    let mut agent = Agent {
+        input_handler: InputHandler::new()?,
+        clipboard: None,
+    };
+    let pipe = ClientOptions::new().open(pipe_name)?;
+    let (reader, mut writer) = tokio::io::split(pipe);
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let reply = match agent_protocol::read_frame::<_, AgentRequest>(&mut reader).await {
+            Ok(Some(request)) => agent.handle(request).await,
+            Ok(None) => return Ok(()),
+            Err(e) => Err(e.context("Malformed request")),
+        };
+        let reply = reply.unwrap_or_else(|e| AgentReply::Failed {
+            error: e.to_string(),
+        });
+        agent_protocol::write_frame(&mut writer, &reply).await?;
+    }
+}
+
+struct Agent {
+    input_handler: InputHandler,
+    /// Opened on first use, like the server's own
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl Agent {
+    async fn handle(&mut self, request: AgentRequest) -> Result<AgentReply> {
+        match request {
+            AgentRequest::Command { command } => self.run(command).await.map(|()| AgentReply::Done),
+            AgentRequest::CursorScale => {
+                let scale = tokio::task::spawn_blocking(cursor_size::current_scale).await??;
+                Ok(AgentReply::CursorScale { scale })
+            }
+            AgentRequest::SetCursorScale { scale } => {
+                tokio::task::spawn_blocking(move || cursor_size::set_scale(scale)).await??;
+                Ok(AgentReply::Done)
+            }
+            AgentRequest::Displays => {
+                let displays = tokio::task::spawn_blocking(pointer::display_info).await?;
+                Ok(AgentReply::Displays { displays })
+            }
+            AgentRequest::SetClipboard { text } => {
+                if self.clipboard.is_none() {
+                    self.clipboard = Some(arboard::Clipboard::new()?);
+                }
+                if let Some(clipboard) = self.clipboard.as_mut() {
+                    clipboard.set_text(text)?;
+                }
+                Ok(AgentReply::Done)
+            }
+            AgentRequest::Idle => {
+                let idle = tokio::task::spawn_blocking(idle::time_since_last_input).await?;
+                Ok(AgentReply::Idle {
+                    idle_ms: idle.map(|idle| idle.as_millis() as u64),
+                })
+            }
+        }
+    }
+
+    /// Display commands go to the monitors here; everything else is input
+    async fn run(&self, command: Command) -> Result<()> {
+        match command {
+            Command::DisplayPower { state } => {
+                let on = state == PowerState::On;
+                tokio::task::spawn_blocking(move || display::set_power(on)).await?
+            }
+            Command::NightLight { enabled } => {
+                tokio::task::spawn_blocking(move || display::set_night_light(enabled)).await?
+            }
+            Command::BrightnessUp | Command::BrightnessDown => {
+                let delta = match command {
+                    Command::BrightnessUp => ServerConfig::BRIGHTNESS_STEP_PERCENT,
+                    _ => -ServerConfig::BRIGHTNESS_STEP_PERCENT,
+                };
+                tokio::task::spawn_blocking(move || display::step_brightness(delta)).await??;
+                Ok(())
+            }
+            Command::BrightnessSet { percent } => {
+                tokio::task::spawn_blocking(move || display::set_brightness(percent)).await?
+            }
+            Command::MoveToDisplay { display, x, y } => {
+                let (x, y) =
+                    tokio::task::spawn_blocking(move || pointer::point_on_display(display, x, y))
+                        .await?
+                        .ok_or_else(|| anyhow!("No display {}", display))?;
+                // Moves are relative, so the pointer needs a tracked position to move from
+                if self.input_handler.pointer_position().is_none() {
+                    let settle = Command::MouseMove { x: 0.0, y: 0.0 };
+                    self.input_handler.handle_command(settle).await?;
+                }
+                let Some((from_x, from_y)) = self.input_handler.pointer_position() else {
+                    return Ok(());
+                };
+                let command = Command::MouseMove {
+                    x: x - from_x,
+                    y: y - from_y,
+                };
+                self.input_handler.handle_command(command).await
+            }
+            command => self.input_handler.handle_command(command).await,
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::agent_protocol::{self, AgentReply, AgentRequest};
+use super::launcher::{self, SessionProcess};
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{Command, DisplayInfo};
+use anyhow::{anyhow, bail, Context, Result};
+use std::os::windows::io::AsRawHandle;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+use tokio::sync::Mutex;
+use windows::core::w;
+use windows::Win32::Foundation::{LocalFree, BOOL, HANDLE, HLOCAL};
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+use windows::Win32::System::Pipes::GetNamedPipeClientProcessId;
+
+static INSTALLED: OnceLock<AgentLink> = OnceLock::new();
+
+/// Routes everything tied to the user's session through the agent for the rest of the process
+pub fn install() -> &'static AgentLink {
+    INSTALLED.get_or_init(AgentLink::new)
+}
+
+/// The agent link, when running as a service
+pub fn installed() -> Option<&'static AgentLink> {
+    INSTALLED.get()
+}
+
+/// Service-side end of the input agent
+///
+/// The agent is (re)launched lazily into whichever session owns the console, so fast
+/// user switching and logging on after boot are picked up on the next command.
+pub struct AgentLink {
+    connection: Mutex<Option<Connection>>,
+}
+
+struct Connection {
+    session_id: u32,
+    pipe: BufReader<NamedPipeServer>,
+    _process: SessionProcess,
+}
+
+impl AgentLink {
+    pub fn new() -> Self {
+        Self {
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Runs an input or display command through the agent in the console session
+    pub async fn send(&self, command: &Command) -> Result<()> {
+        let request = AgentRequest::Command {
+            command: command.clone(),
+        };
+        match self.request(&request).await? {
+            AgentReply::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn cursor_scale(&self) -> Result<f64> {
+        match self.request(&AgentRequest::CursorScale).await? {
+            AgentReply::CursorScale { scale } => Ok(scale),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn set_cursor_scale(&self, scale: f64) -> Result<()> {
+        match self
+            .request(&AgentRequest::SetCursorScale { scale })
+            .await?
+        {
+            AgentReply::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// The console session's monitors; session 0 only sees a virtual one
+    pub async fn displays(&self) -> Result<Vec<DisplayInfo>> {
+        match self.request(&AgentRequest::Displays).await? {
+            AgentReply::Displays { displays } => Ok(displays),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn set_clipboard(&self, text: &str) -> Result<()> {
+        let request = AgentRequest::SetClipboard {
+            text: text.to_string(),
+        };
+        match self.request(&request).await? {
+            AgentReply::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Time since the last input in the console session; `None` while no one is logged on
+    pub async fn idle(&self) -> Option<Duration> {
+        match self.request(&AgentRequest::Idle).await {
+            Ok(AgentReply::Idle { idle_ms }) => idle_ms.map(Duration::from_millis),
+            Ok(_) | Err(_) => None,
+        }
+    }
+
+    async fn request(&self, request: &AgentRequest) -> Result<AgentReply> {
+        let session_id =
+            launcher::active_console_session().ok_or_else(|| anyhow!("No console session"))?;
+
+        let mut connection = self.connection.lock().await;
+        if connection.as_ref().map(|c| c.session_id) != Some(session_id) {
+            // Dropping the old connection closes its pipe, which stops that agent
+            *connection = None;
+            *connection = Some(connect(session_id).await?);
+        }
+        let Some(active) = connection.as_mut() else {
+            bail!("Input agent unavailable");
+        };
+
+        match round_trip(&mut active.pipe, request).await {
+            Ok(AgentReply::Failed { error }) => Err(anyhow!(error)),
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                *connection = None;
+                Err(e.context("Input agent disconnected"))
+            }
+        }
+    }
+}
+
+impl Default for AgentLink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn connect(session_id: u32) -> Result<Connection> {
+    let pipe_name = format!(
+        "{}-{:016x}",
+        ServerConfig::AGENT_PIPE_PREFIX,
+        rand::random::<u64>()
+    );
+    let pipe = create_pipe(&pipe_name)?;
+    let process = launcher::launch_in_session(session_id, &format!("agent {}", pipe_name))?;
+
+    tokio::time::timeout(
+        Duration::from_secs(ServerConfig::AGENT_CONNECT_TIMEOUT_SECS),
+        pipe.connect(),
+    )
+    .await
+    .context("Input agent did not connect")??;
+    if client_pid(&pipe)? != process.pid {
+        bail!("Unexpected process connected to the input agent pipe");
+    }
+
+    log::info!("Input agent running in session {}", session_id);
+    Ok(Connection {
+        session_id,
+        pipe: BufReader::new(pipe),
+        _process: process,
+    })
+}
+
+async fn round_trip(
+    pipe: &mut BufReader<NamedPipeServer>,
+    request: &AgentRequest,
+) -> Result<AgentReply> {
+    agent_protocol::write_frame(pipe.get_mut(), request).await?;
+    agent_protocol::read_frame(pipe)
+        .await?
+        .ok_or_else(|| anyhow!("Pipe closed"))
+}
+
+fn unexpected(reply: AgentReply) -> anyhow::Error {
+    anyhow!("Unexpected reply from the input agent: {:?}", reply)
+}
+
+/// Creates the pipe so only SYSTEM and interactive users can open it
+fn create_pipe(name: &str) -> Result<NamedPipeServer> {
+    unsafe {
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            w!("D:P(A;;GA;;;SY)(A;;GRGW;;;IU)"),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )?;
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor.0,
+            bInheritHandle: BOOL::from(false),
+        };
+        let pipe = ServerOptions::new()
+            .first_pipe_instance(true)
+            .max_instances(1)
+            .create_with_security_attributes_raw(name, &mut attributes as *mut _ as *mut _);
+        let _ = LocalFree(HLOCAL(descriptor.0));
+        Ok(pipe?)
+    }
+}
+
+fn client_pid(pipe: &NamedPipeServer) -> Result<u32> {
+    let mut pid = 0;
+    unsafe { GetNamedPipeClientProcessId(HANDLE(pipe.as_raw_handle() as isize), &mut pid)? };
+    Ok(pid)
+}
@@ -0,0 +1,147 @@
+use crate::domain::models::{Command, DisplayInfo};
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// What the service asks of the input agent; everything that has to happen in the
+/// user's session rather than session 0
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum AgentRequest {
+    /// Input, display and `MoveToDisplay` commands, run as if they had arrived directly
+    Command {
+        command: Command,
+    },
+    CursorScale,
+    SetCursorScale {
+        scale: f64,
+    },
+    Displays,
+    SetClipboard {
+        text: String,
+    },
+    Idle,
+}
+
+/// The agent's answer to one request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum AgentReply {
+    Done,
+    Failed {
+        error: String,
+    },
+    CursorScale {
+        scale: f64,
+    },
+    Displays {
+        displays: Vec<DisplayInfo>,
+    },
+    /// `None` when the session can't say how long it has been idle
+    Idle {
+        idle_ms: Option<u64>,
+    },
+}
+
+/// Writes `message` as one JSON line
+///
+/// JSON escapes newlines inside strings, so a line always holds exactly one message.
+pub async fn write_frame<W, T>(writer: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads the next JSON line; `None` once the other end has closed the pipe
+pub async fn read_frame<R, T>(reader: &mut R) -> Result<Option<T>>
+where
+    R: AsyncBufRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') {
+        bail!("Pipe closed mid-message");
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+
+    #[tokio::test]
+    async fn test_messages_round_trip_one_per_line() {
+        let (mut service, agent) = tokio::io::duplex(1024);
+        let mut agent = BufReader::new(agent);
+        let typed = AgentRequest::Command {
+            command: Command::TypeText {
+                text: "two\nlines".to_string(),
+            },
+        };
+
+        write_frame(&mut service, &typed).await.unwrap();
+        write_frame(&mut service, &AgentRequest::Idle)
+            .await
+            .unwrap();
+
+        let first = read_frame(&mut agent).await.unwrap();
+        let Some(AgentRequest::Command {
+            command: Command::TypeText { text },
+        }) = first
+        else {
+            panic!("expected the typed text, got {:?}", first);
+        };
+        assert_eq!(text, "two\nlines");
+        let second = read_frame(&mut agent).await.unwrap();
+        assert!(matches!(second, Some(AgentRequest::Idle)));
+    }
+
+    #[tokio::test]
+    async fn test_closed_pipe_ends_the_stream() {
+        let (service, agent) = tokio::io::duplex(64);
+        drop(service);
+        let mut agent = BufReader::new(agent);
+        let next: Option<AgentRequest> = read_frame(&mut agent).await.unwrap();
+        assert!(next.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_truncated_message_is_an_error() {
+        let (mut agent, service) = tokio::io::duplex(64);
+        agent.write_all(br#"{"type":"Do"#).await.unwrap();
+        drop(agent);
+        let mut service = BufReader::new(service);
+        assert!(read_frame::<_, AgentReply>(&mut service).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replies_carry_their_values() {
+        let (mut agent, service) = tokio::io::duplex(1024);
+        let mut service = BufReader::new(service);
+        let replies = [
+            AgentReply::Failed {
+                error: "No console session".to_string(),
+            },
+            AgentReply::CursorScale { scale: 1.5 },
+            AgentReply::Idle { idle_ms: None },
+        ];
+        for reply in &replies {
+            write_frame(&mut agent, reply).await.unwrap();
+        }
+        for reply in replies {
+            let read: Option<AgentReply> = read_frame(&mut service).await.unwrap();
+            assert_eq!(read, Some(reply));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::domain::config::ServerConfig;
+use anyhow::{Context, Result};
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Security::SC_HANDLE;
+use windows::Win32::System::Services::{
+    CloseServiceHandle, ControlService, CreateServiceW, DeleteService, OpenSCManagerW,
+    OpenServiceW, StartServiceW, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE, SERVICE_ALL_ACCESS,
+    SERVICE_AUTO_START, SERVICE_CONTROL_STOP, SERVICE_ERROR_NORMAL, SERVICE_STATUS,
+    SERVICE_WIN32_OWN_PROCESS,
+};
+
+/// `pointzerver install-service`: registers this executable as an auto-start service and starts it
+pub fn install() -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let command = HSTRING::from(format!("\"{}\" service", exe.display()));
+    let name = HSTRING::from(ServerConfig::SERVICE_NAME);
+
+    unsafe {
+        let manager = ServiceHandle(
+            OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CREATE_SERVICE)
+                .context("Opening the service manager requires an elevated prompt")?,
+        );
+        let service = ServiceHandle(
+            CreateServiceW(
+                manager.0,
+                &name,
+                &name,
+                SERVICE_ALL_ACCESS,
+                SERVICE_WIN32_OWN_PROCESS,
+                SERVICE_AUTO_START,
+                SERVICE_ERROR_NORMAL,
+                &command,
+                PCWSTR::null(),
+                None,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                PCWSTR::null(),
+            )
+            .context("Failed to create the service")?,
+        );
+        StartServiceW(service.0, None).context("Service installed but failed to start")?;
+    }
+    println!(
+        "Installed and started the {} service",
+        ServerConfig::SERVICE_NAME
+    );
+    Ok(())
+}
+
+/// `pointzerver uninstall-service`: stops and removes the service
+pub fn uninstall() -> Result<()> {
+    let name = HSTRING::from(ServerConfig::SERVICE_NAME);
+
+    unsafe {
+        let manager = ServiceHandle(
+            OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT)
+                .context("Opening the service manager requires an elevated prompt")?,
+        );
+        let service = ServiceHandle(
+            OpenServiceW(manager.0, &name, SERVICE_ALL_ACCESS)
+                .context("The service is not installed")?,
+        );
+        let mut status = SERVICE_STATUS::default();
+        // Fails when the service isn't running, which is fine
+        let _ = ControlService(service.0, SERVICE_CONTROL_STOP, &mut status);
+        DeleteService(service.0).context("Failed to remove the service")?;
+    }
+    println!("Removed the {} service", ServerConfig::SERVICE_NAME);
+    Ok(())
+}
+
+struct ServiceHandle(SC_HANDLE);
+
+impl Drop for ServiceHandle {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseServiceHandle(self.0) };
+    }
+}
@@ -0,0 +1,84 @@
+use super::wide;
+use anyhow::{Context, Result};
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE};
+use windows::Win32::System::Environment::{CreateEnvironmentBlock, DestroyEnvironmentBlock};
+use windows::Win32::System::RemoteDesktop::{WTSGetActiveConsoleSessionId, WTSQueryUserToken};
+use windows::Win32::System::Threading::{
+    CreateProcessAsUserW, TerminateProcess, CREATE_NO_WINDOW, CREATE_UNICODE_ENVIRONMENT,
+    PROCESS_CREATION_FLAGS, PROCESS_INFORMATION, STARTUPINFOW,
+};
+
+const NO_SESSION: u32 = u32::MAX;
+
+/// The session attached to the physical console, if any
+pub fn active_console_session() -> Option<u32> {
+    let session_id = unsafe { WTSGetActiveConsoleSessionId() };
+    (session_id != NO_SESSION).then_some(session_id)
+}
+
+/// A copy of this executable running as the user logged on to a session
+pub struct SessionProcess {
+    pub pid: u32,
+    handle: HANDLE,
+}
+
+impl Drop for SessionProcess {
+    fn drop(&mut self) {
+        unsafe {
+            // The agent exits on its own once its pipe closes; this covers one that hung
+            let _ = TerminateProcess(self.handle, 0);
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
+
+/// Starts this executable with `args` on the interactive desktop of `session_id`
+///
+/// Needs the `SeTcbPrivilege` that services running as LocalSystem hold.
+pub fn launch_in_session(session_id: u32, args: &str) -> Result<SessionProcess> {
+    let exe = std::env::current_exe()?;
+    let mut command_line = wide(&format!("\"{}\" {}", exe.display(), args));
+    let mut desktop = wide("winsta0\\default");
+
+    unsafe {
+        let mut token = HANDLE::default();
+        WTSQueryUserToken(session_id, &mut token)
+            .with_context(|| format!("No user is logged on to session {}", session_id))?;
+
+        let mut environment = std::ptr::null_mut();
+        let has_environment =
+            CreateEnvironmentBlock(&mut environment, token, BOOL::from(false)).is_ok();
+        let startup = STARTUPINFOW {
+            cb: std::mem::size_of::<STARTUPINFOW>() as u32,
+            lpDesktop: PWSTR(desktop.as_mut_ptr()),
+            ..Default::default()
+        };
+        let mut info = PROCESS_INFORMATION::default();
+        let created = CreateProcessAsUserW(
+            token,
+            PCWSTR::null(),
+            PWSTR(command_line.as_mut_ptr()),
+            None,
+            None,
+            BOOL::from(false),
+            PROCESS_CREATION_FLAGS(CREATE_NO_WINDOW.0 | CREATE_UNICODE_ENVIRONMENT.0),
+            has_environment.then_some(environment as *const _),
+            PCWSTR::null(),
+            &startup,
+            &mut info,
+        );
+
+        if has_environment {
+            let _ = DestroyEnvironmentBlock(environment);
+        }
+        let _ = CloseHandle(token);
+        created.context("Failed to start the input agent")?;
+        let _ = CloseHandle(info.hThread);
+
+        Ok(SessionProcess {
+            pid: info.dwProcessId,
+            handle: info.hProcess,
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{anyhow, bail, Result};
+use objc::runtime::{Class, Object, BOOL, NO};
+use objc::{msg_send, sel, sel_impl};
+use std::ffi::{c_char, CStr};
+
+#[link(name = "ServiceManagement", kind = "framework")]
+extern "C" {}
+
+/// `pointzerver install-loginitem`: starts the server at login through SMAppService
+pub fn install() -> Result<()> {
+    unsafe {
+        let service = main_app_service()?;
+        let mut error: *mut Object = std::ptr::null_mut();
+        let ok: BOOL = msg_send![service, registerAndReturnError: &mut error as *mut *mut Object];
+        check(ok, error)?;
+    }
+    println!(
+        "PointZerver will start at login; manage it under System Settings > General > Login Items"
+    );
+    Ok(())
+}
+
+/// `pointzerver uninstall-loginitem`: stops starting the server at login
+pub fn uninstall() -> Result<()> {
+    unsafe {
+        let service = main_app_service()?;
+        let mut error: *mut Object = std::ptr::null_mut();
+        let ok: BOOL = msg_send![service, unregisterAndReturnError: &mut error as *mut *mut Object];
+        check(ok, error)?;
+    }
+    println!("Removed the PointZerver login item");
+    Ok(())
+}
+
+/// SMAppService registers the enclosing app bundle, so a bare binary can't be a login item
+unsafe fn main_app_service() -> Result<*mut Object> {
+    let exe = std::env::current_exe()?;
+    if !exe.to_string_lossy().contains(".app/Contents/MacOS/") {
+        bail!(
+            "{} is not inside an app bundle; build one with `make bundle`",
+            exe.display()
+        );
+    }
+    let class =
+        Class::get("SMAppService").ok_or_else(|| anyhow!("Login items need macOS 13 or later"))?;
+    let service: *mut Object = msg_send![class, mainAppService];
+    if service.is_null() {
+        bail!("SMAppService is unavailable");
+    }
+    Ok(service)
+}
+
+unsafe fn check(ok: BOOL, error: *mut Object) -> Result<()> {
+    if ok != NO {
+        return Ok(());
+    }
+    if error.is_null() {
+        bail!("SMAppService refused the change");
+    }
+    let description: *mut Object = msg_send![error, localizedDescription];
+    let utf8: *const c_char = msg_send![description, UTF8String];
+    if utf8.is_null() {
+        bail!("SMAppService refused the change");
+    }
+    bail!("{}", CStr::from_ptr(utf8).to_string_lossy())
+}
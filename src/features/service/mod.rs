@@ -0,0 +1,26 @@
+//! Starting the server with the OS instead of by hand
+//!
+//! On Windows it runs as a service. Services live in session 0, where `SendInput` can't
+//! reach the user's desktop, so the service launches an agent in the console session and
+//! forwards input, clipboard, cursor size, display and idle queries to it. On macOS it
+//! registers itself as a login item.
+
+#[cfg(windows)]
+pub mod agent;
+#[cfg(windows)]
+pub mod agent_link;
+pub mod agent_protocol;
+#[cfg(windows)]
+pub mod installer;
+#[cfg(windows)]
+mod launcher;
+#[cfg(target_os = "macos")]
+pub mod login_item;
+#[cfg(windows)]
+pub mod service_host;
+
+/// Null-terminated UTF-16 for Win32 string parameters
+#[cfg(windows)]
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
+}
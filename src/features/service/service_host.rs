@@ -0,0 +1,210 @@
+use super::{launcher, wide};
+use crate::domain::config::ServerConfig;
+use crate::input::desktop_session::SessionState;
+use crate::utils::paths;
+use anyhow::{anyhow, Context, Result};
+use std::ffi::c_void;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use tokio::runtime::Handle;
+use tokio::sync::{oneshot, Notify};
+use windows::core::{w, PCWSTR, PWSTR};
+use windows::Win32::Foundation::{
+    LocalFree, BOOL, ERROR_ALREADY_EXISTS, ERROR_CALL_NOT_IMPLEMENTED,
+    ERROR_SERVICE_SPECIFIC_ERROR, HLOCAL, NO_ERROR,
+};
+use windows::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use windows::Win32::Security::{PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES};
+use windows::Win32::Storage::FileSystem::CreateDirectoryW;
+use windows::Win32::System::RemoteDesktop::WTSSESSION_NOTIFICATION;
+use windows::Win32::System::Services::{
+    RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+    SERVICE_ACCEPT_SESSIONCHANGE, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP,
+    SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SESSIONCHANGE, SERVICE_CONTROL_SHUTDOWN,
+    SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE,
+    SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    WTS_CONSOLE_CONNECT, WTS_CONSOLE_DISCONNECT, WTS_SESSION_LOCK, WTS_SESSION_LOGOFF,
+    WTS_SESSION_LOGON, WTS_SESSION_UNLOCK,
+};
+
+/// Starts the server; called once the service control manager hands over
+pub type Serve = fn() -> Pin<Box<dyn Future<Output = Result<()>>>>;
+
+/// The SCM calls back into plain functions, so the host state has to be global
+struct Host {
+    runtime: Handle,
+    serve: Serve,
+    stop: Notify,
+}
+
+type SessionWatch = Box<dyn Fn(SessionState) + Send + Sync>;
+
+static HOST: OnceLock<Host> = OnceLock::new();
+static SESSION_WATCH: OnceLock<SessionWatch> = OnceLock::new();
+
+/// `pointzerver service`: hands the process to the SCM and returns once the service stops
+///
+/// The dispatcher blocks until then, so it gets a thread of its own rather than a runtime worker.
+pub async fn run(serve: Serve) -> Result<()> {
+    let _ = HOST.set(Host {
+        runtime: Handle::current(),
+        serve,
+        stop: Notify::new(),
+    });
+    let (done, dispatched) = oneshot::channel();
+    std::thread::Builder::new()
+        .name("service-dispatcher".to_string())
+        .spawn(move || {
+            let _ = done.send(dispatch());
+        })?;
+    dispatched
+        .await
+        .map_err(|_| anyhow!("Service dispatcher exited without a result"))?
+}
+
+fn dispatch() -> Result<()> {
+    let mut name = wide(ServerConfig::SERVICE_NAME);
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: PWSTR(name.as_mut_ptr()),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW::default(),
+    ];
+    unsafe { StartServiceCtrlDispatcherW(table.as_ptr())? };
+    Ok(())
+}
+
+/// Reports lock, unlock, logon and logoff of the console session, which the SCM tells services
+/// about directly
+pub fn watch_sessions(on_change: impl Fn(SessionState) + Send + Sync + 'static) {
+    let _ = SESSION_WATCH.set(Box::new(on_change));
+}
+
+/// Moves settings and state to ProgramData, readable only by SYSTEM and administrators
+///
+/// LocalSystem's own profile would hide them from anyone configuring the service.
+pub fn use_machine_config_dir() -> Result<PathBuf> {
+    let dir = paths::use_machine_config_dir();
+    let path = wide(&dir.to_string_lossy());
+    unsafe {
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            w!("D:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)"),
+            SDDL_REVISION_1,
+            &mut descriptor,
+            None,
+        )?;
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor.0,
+            bInheritHandle: BOOL::from(false),
+        };
+        let created = CreateDirectoryW(PCWSTR(path.as_ptr()), Some(&attributes as *const _));
+        let _ = LocalFree(HLOCAL(descriptor.0));
+        if let Err(e) = created {
+            if e.code() != ERROR_ALREADY_EXISTS.to_hresult() {
+                return Err(e).with_context(|| format!("Failed to create {}", dir.display()));
+            }
+        }
+    }
+    Ok(dir)
+}
+
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+    let Some(host) = HOST.get() else {
+        return;
+    };
+    let name = wide(ServerConfig::SERVICE_NAME);
+    let status =
+        match RegisterServiceCtrlHandlerExW(PCWSTR(name.as_ptr()), Some(control_handler), None) {
+            Ok(status) => status,
+            Err(e) => {
+                log::error!("Failed to register service control handler: {}", e);
+                return;
+            }
+        };
+
+    set_state(status, SERVICE_RUNNING, 0);
+    let result = host.runtime.block_on(async {
+        tokio::select! {
+            result = (host.serve)() => result,
+            _ = host.stop.notified() => Ok(()),
+        }
+    });
+    let exit_code = match result {
+        Ok(()) => 0,
+        Err(e) => {
+            log::error!("Service stopped: {}", e);
+            1
+        }
+    };
+    set_state(status, SERVICE_STOPPED, exit_code);
+}
+
+unsafe extern "system" fn control_handler(
+    control: u32,
+    event_type: u32,
+    event_data: *mut c_void,
+    _context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_SESSIONCHANGE => {
+            let notification = &*(event_data as *const WTSSESSION_NOTIFICATION);
+            let state = session_state(event_type, notification.dwSessionId);
+            if let (Some(state), Some(on_change)) = (state, SESSION_WATCH.get()) {
+                on_change(state);
+            }
+            NO_ERROR.0
+        }
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            if let Some(host) = HOST.get() {
+                host.stop.notify_one();
+            }
+            NO_ERROR.0
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR.0,
+        _ => ERROR_CALL_NOT_IMPLEMENTED.0,
+    }
+}
+
+/// Console attach and detach always matter; other sessions' locks and logons don't
+fn session_state(event: u32, session_id: u32) -> Option<SessionState> {
+    match event {
+        WTS_CONSOLE_CONNECT => Some(SessionState::Active),
+        WTS_CONSOLE_DISCONNECT => Some(SessionState::Disconnected),
+        _ if launcher::active_console_session() != Some(session_id) => None,
+        WTS_SESSION_LOCK => Some(SessionState::Locked),
+        WTS_SESSION_UNLOCK | WTS_SESSION_LOGON => Some(SessionState::Active),
+        WTS_SESSION_LOGOFF => Some(SessionState::Disconnected),
+        _ => None,
+    }
+}
+
+fn set_state(handle: SERVICE_STATUS_HANDLE, state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: if state == SERVICE_RUNNING {
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN | SERVICE_ACCEPT_SESSIONCHANGE
+        } else {
+            0
+        },
+        dwWin32ExitCode: if exit_code == 0 {
+            NO_ERROR.0
+        } else {
+            ERROR_SERVICE_SPECIFIC_ERROR.0
+        },
+        dwServiceSpecificExitCode: exit_code,
+        ..Default::default()
+    };
+    if let Err(e) = unsafe { SetServiceStatus(handle, &status) } {
+        log::error!("Failed to report service status: {}", e);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::domain::config::settings::{ControlMode, HeartbeatSettings};
+use crate::domain::models::{Command, Scope};
+use crate::features::command::held_input::HeldInput;
+use crate::features::command::policy::Denial;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clients heard from recently, keyed by source address, and which of them has control
+///
+/// A client that goes silent for the heartbeat timeout (a dropped link, a phone locked
+/// mid-drag) is expired, gives up control, and whatever it still held is handed back for
+/// release. Clients that hold input without sending anything else stay with `Heartbeat`.
+pub struct ClientRegistry {
+    heartbeat: HeartbeatSettings,
+    mode: ControlMode,
+    state: Mutex<Clients>,
+}
+
+#[derive(Default)]
+struct Clients {
+    by_addr: HashMap<SocketAddr, Client>,
+    controller: Option<SocketAddr>,
+}
+
+struct Client {
+    last_seen: Instant,
+    held: HeldInput,
+}
+
+/// A client dropped for going silent
+#[derive(Debug)]
+pub struct Expired {
+    pub addr: SocketAddr,
+    /// Commands that let go of what it still held; empty with heartbeats off
+    pub release: Vec<Command>,
+    /// It had control, which is now free
+    pub had_control: bool,
+}
+
+/// Control mode, controller and connected clients as reported on `/control`
+#[derive(Serialize, Debug, Clone)]
+pub struct ControlStatus {
+    pub mode: ControlMode,
+    pub controller: Option<String>,
+    pub clients: Vec<ConnectedClient>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConnectedClient {
+    pub addr: String,
+    pub idle_ms: u64,
+    /// Still holding a mouse button, key or modifier down
+    pub holding: bool,
+}
+
+impl ClientRegistry {
+    pub fn new(heartbeat: HeartbeatSettings, mode: ControlMode) -> Self {
+        Self {
+            heartbeat,
+            mode,
+            state: Mutex::new(Clients::default()),
+        }
+    }
+
+    /// Registers a command from `addr`, refusing input while another client has control
+    ///
+    /// Returns whether control changed hands.
+    pub fn admit(&self, addr: SocketAddr, command: &Command, now: Instant) -> Result<bool, Denial> {
+        let mut state = self.lock_state();
+        let changed = match (self.mode, command) {
+            (ControlMode::Shared, _) => false,
+            (_, Command::ReleaseControl) => {
+                let releasing = state.controller == Some(addr);
+                if releasing {
+                    state.controller = None;
+                }
+                releasing
+            }
+            (_, Command::TakeControl) => state.claim(addr)?,
+            (ControlMode::FirstCome, command) if is_input(command) => state.claim(addr)?,
+            (ControlMode::Exclusive, command) if is_input(command) => {
+                if state.controller != Some(addr) {
+                    return Err(Denial::NotController);
+                }
+                false
+            }
+            _ => false,
+        };
+        state
+            .by_addr
+            .entry(addr)
+            .and_modify(|client| client.last_seen = now)
+            .or_insert_with(|| Client {
+                last_seen: now,
+                held: HeldInput::default(),
+            });
+        Ok(changed)
+    }
+
+    /// Tracks what `command`, injected on behalf of `addr`, pressed or let go of
+    pub fn record(&self, addr: SocketAddr, command: &Command) {
+        if let Some(client) = self.lock_state().by_addr.get_mut(&addr) {
+            client.held.record(command);
+        }
+    }
+
+    /// Drops clients silent past the heartbeat timeout
+    pub fn expire(&self, now: Instant) -> Vec<Expired> {
+        let timeout = Duration::from_secs(self.heartbeat.timeout_secs);
+        let mut state = self.lock_state();
+        let mut expired = Vec::new();
+        state.by_addr.retain(|addr, client| {
+            if now.duration_since(client.last_seen) < timeout {
+                return true;
+            }
+            let release = match self.heartbeat.enabled {
+                true => client.held.release_commands(),
+                false => Vec::new(),
+            };
+            expired.push(Expired {
+                addr: *addr,
+                release,
+                had_control: false,
+            });
+            false
+        });
+        for client in &mut expired {
+            if state.controller == Some(client.addr) {
+                state.controller = None;
+                client.had_control = true;
+            }
+        }
+        expired
+    }
+
+    /// Lets go of everything every client holds, for the kill switch
+    pub fn release_all(&self) -> Vec<Command> {
+        let mut state = self.lock_state();
+        state
+            .by_addr
+            .values_mut()
+            .flat_map(|client| std::mem::take(&mut client.held).release_commands())
+            .collect()
+    }
+
+    pub fn controller(&self) -> Option<SocketAddr> {
+        self.lock_state().controller
+    }
+
+    /// Takes control away from whoever has it, for the host to break a stuck claim
+    pub fn revoke_control(&self) -> Option<SocketAddr> {
+        self.lock_state().controller.take()
+    }
+
+    pub fn status(&self, now: Instant) -> ControlStatus {
+        let state = self.lock_state();
+        let mut clients: Vec<_> = state
+            .by_addr
+            .iter()
+            .map(|(addr, client)| ConnectedClient {
+                addr: addr.to_string(),
+                idle_ms: now.duration_since(client.last_seen).as_millis() as u64,
+                holding: !client.held.is_empty(),
+            })
+            .collect();
+        clients.sort_by_key(|client| client.idle_ms);
+        ControlStatus {
+            mode: self.mode,
+            controller: state.controller.map(|addr| addr.to_string()),
+            clients,
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, Clients> {
+        self.state.lock().expect("Client registry mutex poisoned")
+    }
+}
+
+impl Clients {
+    /// Gives `addr` control unless another client has it; returns whether it was new
+    fn claim(&mut self, addr: SocketAddr) -> Result<bool, Denial> {
+        match self.controller {
+            Some(controller) if controller == addr => Ok(false),
+            Some(_) => Err(Denial::NotController),
+            None => {
+                self.controller = Some(addr);
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Commands that drive the pointer or keyboard, the ones arbitration applies to
+fn is_input(command: &Command) -> bool {
+    matches!(command.scope(), Some(Scope::Pointer | Scope::Keyboard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(last: u8) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, last], 50000))
+    }
+
+    fn click() -> Command {
+        Command::MouseClick { button: 1 }
+    }
+
+    #[test]
+    fn test_silent_client_releases_its_drag() {
+        let registry = ClientRegistry::new(HeartbeatSettings::default(), ControlMode::Shared);
+        let now = Instant::now();
+        registry
+            .admit(client(5), &Command::MouseDown { button: 1 }, now)
+            .unwrap();
+        registry.record(client(5), &Command::MouseDown { button: 1 });
+
+        let later = now + Duration::from_secs(4);
+        registry
+            .admit(client(5), &Command::Heartbeat, later)
+            .unwrap();
+        assert!(registry.expire(later + Duration::from_secs(4)).is_empty());
+
+        match registry.expire(later + Duration::from_secs(5)).as_slice() {
+            [expired] => {
+                assert_eq!(expired.addr, client(5));
+                assert!(matches!(
+                    expired.release.as_slice(),
+                    [Command::MouseUp { button: 1 }]
+                ));
+            }
+            other => panic!("Unexpected expiry: {:?}", other),
+        }
+        assert!(registry.expire(later + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_release_all_lets_go_once() {
+        let registry = ClientRegistry::new(HeartbeatSettings::default(), ControlMode::Shared);
+        let now = Instant::now();
+        for last in [5, 6] {
+            registry.admit(client(last), &click(), now).unwrap();
+            registry.record(client(last), &Command::MouseDown { button: 1 });
+        }
+        assert_eq!(registry.release_all().len(), 2);
+        assert!(registry.release_all().is_empty());
+    }
+
+    #[test]
+    fn test_heartbeats_off_releases_nothing() {
+        let registry = ClientRegistry::new(
+            HeartbeatSettings {
+                enabled: false,
+                ..HeartbeatSettings::default()
+            },
+            ControlMode::Shared,
+        );
+        let now = Instant::now();
+        registry.admit(client(5), &click(), now).unwrap();
+        registry.record(client(5), &Command::MouseDown { button: 1 });
+        let expired = registry.expire(now + Duration::from_secs(60));
+        assert!(expired[0].release.is_empty());
+    }
+
+    #[test]
+    fn test_first_come_holds_control_until_silent() {
+        let registry = ClientRegistry::new(HeartbeatSettings::default(), ControlMode::FirstCome);
+        let now = Instant::now();
+        assert_eq!(registry.admit(client(5), &click(), now), Ok(true));
+        assert_eq!(registry.admit(client(5), &click(), now), Ok(false));
+        assert_eq!(
+            registry.admit(client(6), &click(), now),
+            Err(Denial::NotController)
+        );
+        assert_eq!(
+            registry.admit(client(6), &Command::Heartbeat, now),
+            Ok(false)
+        );
+
+        let expired = registry.expire(now + Duration::from_secs(5));
+        assert!(expired.iter().any(|client| client.had_control));
+        let later = now + Duration::from_secs(6);
+        assert_eq!(registry.admit(client(6), &click(), later), Ok(true));
+        assert_eq!(registry.controller(), Some(client(6)));
+    }
+
+    #[test]
+    fn test_exclusive_needs_an_explicit_claim() {
+        let registry = ClientRegistry::new(HeartbeatSettings::default(), ControlMode::Exclusive);
+        let now = Instant::now();
+        assert_eq!(
+            registry.admit(client(5), &click(), now),
+            Err(Denial::NotController)
+        );
+        assert_eq!(
+            registry.admit(client(5), &Command::TakeControl, now),
+            Ok(true)
+        );
+        assert_eq!(registry.admit(client(5), &click(), now), Ok(false));
+        assert_eq!(
+            registry.admit(client(6), &Command::TakeControl, now),
+            Err(Denial::NotController)
+        );
+        assert_eq!(
+            registry.admit(client(5), &Command::ReleaseControl, now),
+            Ok(true)
+        );
+        assert_eq!(
+            registry.admit(client(6), &Command::TakeControl, now),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_shared_has_no_controller() {
+        let registry = ClientRegistry::new(HeartbeatSettings::default(), ControlMode::Shared);
+        let now = Instant::now();
+        assert_eq!(
+            registry.admit(client(5), &Command::TakeControl, now),
+            Ok(false)
+        );
+        assert_eq!(registry.admit(client(6), &click(), now), Ok(false));
+        assert_eq!(registry.controller(), None);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::PingExchange;
+use std::collections::VecDeque;
+
+/// Per-client clock offset estimate built from `Ping` exchanges
+///
+/// Works like NTP's clock filter: each exchange yields an offset sample assuming a
+/// symmetric path, and the sample with the lowest round trip is trusted most
+/// because it had the least room for queuing delay.
+#[derive(Default)]
+pub struct ClockSync {
+    samples: VecDeque<ClockSample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Server clock minus client clock
+    pub offset_ms: i64,
+    pub rtt_ms: u64,
+}
+
+impl ClockSync {
+    /// Records a finished exchange, timed from its own send, reply and receipt
+    ///
+    /// The server stamps a `Pong` the moment its `Ping` arrives, so its receive and send
+    /// times are the same and the offset is the server time less the exchange's midpoint.
+    pub fn record(&mut self, exchange: &PingExchange) {
+        let Some(rtt_ms) = exchange.received_ms.checked_sub(exchange.client_time_ms) else {
+            return;
+        };
+        let midpoint = exchange.client_time_ms + rtt_ms / 2;
+        let offset_ms = exchange.server_time_ms as i64 - midpoint as i64;
+        if self.samples.len() == ServerConfig::CLOCK_SYNC_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ClockSample { offset_ms, rtt_ms });
+    }
+
+    /// Best current estimate, if the client has reported any exchanges
+    pub fn estimate(&self) -> Option<ClockSample> {
+        self.samples
+            .iter()
+            .min_by_key(|sample| sample.rtt_ms)
+            .copied()
+    }
+
+    /// Converts a client-clock timestamp to server-clock unix milliseconds
+    pub fn to_server_time(&self, client_time_ms: u64) -> Option<u64> {
+        let estimate = self.estimate()?;
+        Some(client_time_ms.saturating_add_signed(estimate.offset_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(client_time_ms: u64, server_time_ms: u64, received_ms: u64) -> PingExchange {
+        PingExchange {
+            client_time_ms,
+            server_time_ms,
+            received_ms,
+        }
+    }
+
+    #[test]
+    fn test_lowest_round_trip_wins() {
+        let mut clock = ClockSync::default();
+        clock.record(&exchange(1_000, 6_020, 1_040));
+        clock.record(&exchange(2_000, 7_400, 2_500));
+
+        let estimate = clock.estimate().unwrap();
+        assert_eq!(estimate.offset_ms, 5_000);
+        assert_eq!(estimate.rtt_ms, 40);
+        assert_eq!(clock.to_server_time(3_000), Some(8_000));
+    }
+
+    #[test]
+    fn test_old_samples_age_out() {
+        let mut clock = ClockSync::default();
+        clock.record(&exchange(0, 100, 2));
+        for i in 0..ServerConfig::CLOCK_SYNC_SAMPLES as u64 {
+            clock.record(&exchange(i, i + 200, i + 10));
+        }
+        assert_eq!(clock.estimate().unwrap().offset_ms, 195);
+    }
+
+    #[test]
+    fn test_exchange_received_before_sent_is_ignored() {
+        let mut clock = ClockSync::default();
+        clock.record(&exchange(1_000, 1_500, 900));
+        assert_eq!(clock.estimate(), None);
+    }
+
+    #[test]
+    fn test_no_estimate_without_samples() {
+        assert_eq!(ClockSync::default().to_server_time(5), None);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod client_registry;
+pub mod clock_sync;
+pub mod session_lock;
+pub mod session_registry;
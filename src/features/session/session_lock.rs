@@ -0,0 +1,187 @@
+use crate::domain::config::secrets;
+use crate::domain::config::settings::AuthSettings;
+use crate::features::auth::pairing_lockout::PairingLockout;
+use crate::features::auth::token_store::{constant_time_eq, DeviceInfo, Role};
+use crate::features::command::policy::Denial;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Locks a paired client's session once it goes idle, until it sends `RefreshSession`
+///
+/// Only scoped commands count as input, so a client that merely heartbeats still locks. The
+/// admin token and tokenless clients never lock, and a restart starts every session fresh.
+pub struct SessionLock {
+    idle: Option<Duration>,
+    /// What `RefreshSession` must carry, if anything beyond the token
+    pin: Option<Vec<u8>>,
+    /// Last dispatched input per token holder
+    last_input: Mutex<HashMap<String, Instant>>,
+    lockout: Mutex<PairingLockout>,
+}
+
+impl SessionLock {
+    pub fn new(settings: &AuthSettings) -> Self {
+        let pin = match settings.session_pin.as_str() {
+            "" => None,
+            pin => Some(match secrets::open(pin) {
+                Ok(pin) => pin.into_bytes(),
+                Err(e) => {
+                    // No PIN can match, so locked sessions stay locked rather than open up
+                    log::warn!(
+                        "Session PIN unreadable; locked sessions can't refresh: {}",
+                        e
+                    );
+                    let mut unguessable = vec![0u8; 32];
+                    rand::thread_rng().fill_bytes(&mut unguessable);
+                    unguessable
+                }
+            }),
+        };
+        Self {
+            idle: (settings.idle_lock_secs > 0)
+                .then(|| Duration::from_secs(settings.idle_lock_secs)),
+            pin,
+            last_input: Mutex::new(HashMap::new()),
+            lockout: Mutex::new(PairingLockout::new()),
+        }
+    }
+
+    /// How long a session may go without input before it locks; 0 when it never does
+    pub fn idle_lock_secs(&self) -> u64 {
+        self.idle.map_or(0, |idle| idle.as_secs())
+    }
+
+    /// Refuses `holder` once its session has locked, without counting anything as input
+    pub fn check(&self, holder: &DeviceInfo, now: Instant) -> Result<(), Denial> {
+        let Some(idle) = self.idle_for(holder) else {
+            return Ok(());
+        };
+        let locked = self
+            .lock_last_input()
+            .get(&holder.id)
+            .is_some_and(|last| now.saturating_duration_since(*last) >= idle);
+        match locked {
+            true => Err(Denial::SessionLocked),
+            false => Ok(()),
+        }
+    }
+
+    /// Restarts `holder`'s idle timer for a command that was actually dispatched
+    pub fn record_input(&self, holder: &DeviceInfo, now: Instant) {
+        if self.idle_for(holder).is_some() {
+            self.lock_last_input().insert(holder.id.clone(), now);
+        }
+    }
+
+    /// Unlocks `holder`'s session if `pin` matches the configured one
+    ///
+    /// Wrong PINs count toward the same escalating lockout pairing uses, per source address.
+    pub fn refresh(
+        &self,
+        holder: &DeviceInfo,
+        pin: Option<&str>,
+        ip: IpAddr,
+        now: Instant,
+    ) -> Result<(), Denial> {
+        if let Some(expected) = &self.pin {
+            let mut lockout = self.lock_lockout();
+            if lockout.remaining(ip, now).is_some() {
+                return Err(Denial::RefreshLockedOut);
+            }
+            let given = pin.unwrap_or_default().as_bytes();
+            if !constant_time_eq(given, expected) {
+                lockout.record_failure(ip, now);
+                return Err(Denial::WrongSessionPin);
+            }
+            lockout.record_success(ip);
+        }
+        self.lock_last_input().insert(holder.id.clone(), now);
+        Ok(())
+    }
+
+    /// The admin token never locks
+    fn idle_for(&self, holder: &DeviceInfo) -> Option<Duration> {
+        self.idle.filter(|_| holder.role != Role::Admin)
+    }
+
+    fn lock_last_input(&self) -> MutexGuard<'_, HashMap<String, Instant>> {
+        self.last_input.lock().expect("Session lock mutex poisoned")
+    }
+
+    fn lock_lockout(&self) -> MutexGuard<'_, PairingLockout> {
+        self.lockout.lock().expect("Session lockout mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder(role: Role) -> DeviceInfo {
+        DeviceInfo {
+            id: format!("{:?}", role),
+            device_name: "Pixel".to_string(),
+            paired_at: 0,
+            role,
+            expires_at: None,
+            scopes: None,
+        }
+    }
+
+    #[test]
+    fn test_idle_session_locks_until_refreshed_with_pin() {
+        let lock = SessionLock::new(&AuthSettings {
+            idle_lock_secs: 60,
+            session_pin: "4321".to_string(),
+            ..Default::default()
+        });
+        let device = holder(Role::Device);
+        let admin = holder(Role::Admin);
+        let ip = "192.168.1.50".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(lock.check(&device, start).is_ok());
+        lock.record_input(&device, start);
+        lock.record_input(&admin, start);
+        let active = start + Duration::from_secs(59);
+        assert!(lock.check(&device, active).is_ok());
+        lock.record_input(&device, active);
+        let idle = active + Duration::from_secs(120);
+        assert_eq!(lock.check(&device, idle), Err(Denial::SessionLocked));
+        assert!(lock.check(&admin, idle).is_ok());
+
+        assert_eq!(
+            lock.refresh(&device, None, ip, idle),
+            Err(Denial::WrongSessionPin)
+        );
+        assert_eq!(
+            lock.refresh(&device, Some("1234"), ip, idle),
+            Err(Denial::WrongSessionPin)
+        );
+        assert!(lock.refresh(&device, Some("4321"), ip, idle).is_ok());
+        assert!(lock.check(&device, idle).is_ok());
+    }
+
+    #[test]
+    fn test_checking_does_not_keep_session_alive() {
+        let lock = SessionLock::new(&AuthSettings {
+            idle_lock_secs: 60,
+            ..Default::default()
+        });
+        let device = holder(Role::Device);
+        let start = Instant::now();
+        lock.record_input(&device, start);
+        for secs in [30, 50, 59] {
+            assert!(lock
+                .check(&device, start + Duration::from_secs(secs))
+                .is_ok());
+        }
+        assert_eq!(
+            lock.check(&device, start + Duration::from_secs(60)),
+            Err(Denial::SessionLocked)
+        );
+    }
+}
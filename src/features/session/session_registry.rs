@@ -0,0 +1,318 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::PingExchange;
+use crate::features::auth::token_store::DeviceInfo;
+use crate::features::session::clock_sync::ClockSync;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Authenticated client sessions keyed by token rather than source address
+///
+/// A phone that roams between access points keeps its token, so its session (and
+/// sequence tracking) follows it to the new address without re-pairing.
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    client_id: String,
+    device_name: String,
+    addr: SocketAddr,
+    last_seen: Instant,
+    /// When the server last sent a `Probe` the client hasn't answered yet
+    probed_at: Option<Instant>,
+    /// False once a probe went unanswered until the next round
+    reachable: bool,
+    last_seq: Option<u64>,
+    clock: ClockSync,
+}
+
+/// What a packet did to its session
+#[derive(Debug, PartialEq, Eq)]
+pub enum Touch {
+    Started,
+    Continued,
+    Resumed { from: SocketAddr },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionInfo {
+    pub device_name: String,
+    pub addr: SocketAddr,
+    pub idle_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seq: Option<u64>,
+    /// Server clock minus client clock, once the client has pinged with a round trip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<u64>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a packet from `addr` authenticated with `token`
+    pub fn touch(
+        &self,
+        token: &str,
+        holder: &DeviceInfo,
+        addr: SocketAddr,
+        seq: Option<u64>,
+    ) -> Touch {
+        let mut sessions = self.lock_sessions();
+        let now = Instant::now();
+        let Some(session) = sessions.get_mut(token) else {
+            make_room(&mut sessions, now);
+            sessions.insert(
+                token.to_string(),
+                Session {
+                    client_id: holder.id.clone(),
+                    device_name: holder.device_name.clone(),
+                    addr,
+                    last_seen: now,
+                    probed_at: None,
+                    reachable: true,
+                    last_seq: seq,
+                    clock: ClockSync::default(),
+                },
+            );
+            return Touch::Started;
+        };
+
+        session.last_seen = now;
+        session.probed_at = None;
+        session.reachable = true;
+        if seq.is_some() {
+            session.last_seq = seq.max(session.last_seq);
+        }
+        if session.addr == addr {
+            return Touch::Continued;
+        }
+        let from = std::mem::replace(&mut session.addr, addr);
+        Touch::Resumed { from }
+    }
+
+    /// Id and device name of the client last seen at `addr`
+    pub fn client_at(&self, addr: SocketAddr) -> Option<(String, String)> {
+        self.lock_sessions()
+            .values()
+            .find(|session| session.addr == addr)
+            .map(|session| (session.client_id.clone(), session.device_name.clone()))
+    }
+
+    /// Feeds a finished ping exchange into the session's clock estimate
+    pub fn record_clock(&self, token: &str, exchange: &PingExchange) {
+        if let Some(session) = self.lock_sessions().get_mut(token) {
+            session.clock.record(exchange);
+        }
+    }
+
+    /// Converts a client-clock timestamp using the session's clock estimate
+    pub fn to_server_time(&self, token: &str, client_time_ms: u64) -> Option<u64> {
+        self.lock_sessions()
+            .get(token)?
+            .clock
+            .to_server_time(client_time_ms)
+    }
+
+    /// Starts a probe round, returning the addresses of sessions quiet for `interval`
+    ///
+    /// A session that left the previous round's probe unanswered is marked unreachable.
+    pub fn probe(&self, now: Instant, interval: Duration) -> Vec<SocketAddr> {
+        let mut sessions = self.lock_sessions();
+        expire(&mut sessions, now);
+        let mut due = Vec::new();
+        for session in sessions.values_mut() {
+            if now.duration_since(session.last_seen) < interval {
+                continue;
+            }
+            if session.probed_at.is_some() && session.reachable {
+                log::debug!("'{}' stopped answering probes", session.device_name);
+                session.reachable = false;
+            }
+            session.probed_at = Some(now);
+            due.push(session.addr);
+        }
+        due
+    }
+
+    /// Whether each client id with a session answered its last probe
+    pub fn reachability(&self) -> HashMap<String, bool> {
+        self.lock_sessions()
+            .values()
+            .map(|session| (session.client_id.clone(), session.reachable))
+            .collect()
+    }
+
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.lock_sessions()
+            .values()
+            .map(|session| {
+                let clock = session.clock.estimate();
+                SessionInfo {
+                    device_name: session.device_name.clone(),
+                    addr: session.addr,
+                    idle_secs: session.last_seen.elapsed().as_secs(),
+                    last_seq: session.last_seq,
+                    clock_offset_ms: clock.map(|sample| sample.offset_ms),
+                    rtt_ms: clock.map(|sample| sample.rtt_ms),
+                }
+            })
+            .collect()
+    }
+
+    fn lock_sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions
+            .lock()
+            .expect("Session registry mutex poisoned")
+    }
+}
+
+/// Drops sessions quiet for longer than a client could still want to resume them
+fn expire(sessions: &mut HashMap<String, Session>, now: Instant) {
+    let ttl = Duration::from_secs(ServerConfig::SESSION_RESUME_TTL_SECS);
+    sessions.retain(|_, session| now.saturating_duration_since(session.last_seen) <= ttl);
+}
+
+/// Expires old sessions and, if the table is still full, drops the one quiet longest
+fn make_room(sessions: &mut HashMap<String, Session>, now: Instant) {
+    expire(sessions, now);
+    if sessions.len() < ServerConfig::SESSION_MAX {
+        return;
+    }
+    let quietest = sessions
+        .iter()
+        .min_by_key(|(_, session)| session.last_seen)
+        .map(|(token, _)| token.clone());
+    if let Some(token) = quietest {
+        sessions.remove(&token);
+    }
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::auth::token_store::Role;
+
+    fn holder() -> DeviceInfo {
+        DeviceInfo {
+            id: "0123456789abcdef".to_string(),
+            device_name: "Pixel 8".to_string(),
+            paired_at: 0,
+            role: Role::Device,
+            expires_at: None,
+            scopes: None,
+        }
+    }
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, last], 50000))
+    }
+
+    #[test]
+    fn test_new_address_resumes_session() {
+        let registry = SessionRegistry::new();
+        assert_eq!(
+            registry.touch("t", &holder(), addr(10), Some(1)),
+            Touch::Started
+        );
+        assert_eq!(
+            registry.touch("t", &holder(), addr(10), Some(2)),
+            Touch::Continued
+        );
+        assert_eq!(
+            registry.touch("t", &holder(), addr(77), Some(3)),
+            Touch::Resumed { from: addr(10) }
+        );
+
+        let sessions = registry.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].addr, addr(77));
+        assert_eq!(sessions[0].last_seq, Some(3));
+    }
+
+    #[test]
+    fn test_sequence_survives_packets_without_seq() {
+        let registry = SessionRegistry::new();
+        registry.touch("t", &holder(), addr(10), Some(9));
+        registry.touch("t", &holder(), addr(11), None);
+        assert_eq!(registry.sessions()[0].last_seq, Some(9));
+    }
+
+    #[test]
+    fn test_clock_estimate_follows_resumed_session() {
+        let registry = SessionRegistry::new();
+        registry.touch("t", &holder(), addr(10), None);
+        let exchange = PingExchange {
+            client_time_ms: 1_000,
+            server_time_ms: 1_510,
+            received_ms: 1_020,
+        };
+        registry.record_clock("t", &exchange);
+        registry.touch("t", &holder(), addr(77), None);
+
+        assert_eq!(registry.to_server_time("t", 2_000), Some(2_500));
+        assert_eq!(registry.sessions()[0].rtt_ms, Some(20));
+        assert_eq!(registry.to_server_time("other", 2_000), None);
+    }
+
+    #[test]
+    fn test_unanswered_probe_marks_session_unreachable() {
+        let registry = SessionRegistry::new();
+        registry.touch("t", &holder(), addr(10), None);
+        let interval = Duration::from_secs(30);
+        let later = Instant::now() + interval;
+
+        assert_eq!(registry.probe(Instant::now(), interval), []);
+        assert_eq!(registry.probe(later, interval), [addr(10)]);
+        assert!(registry.reachability()[&holder().id]);
+        assert_eq!(registry.probe(later + interval, interval), [addr(10)]);
+        assert!(!registry.reachability()[&holder().id]);
+
+        registry.touch("t", &holder(), addr(10), None);
+        assert!(registry.reachability()[&holder().id]);
+    }
+
+    #[test]
+    fn test_quiet_sessions_expire() {
+        let registry = SessionRegistry::new();
+        registry.touch("t", &holder(), addr(10), Some(4));
+        let ttl = Duration::from_secs(ServerConfig::SESSION_RESUME_TTL_SECS);
+        let interval = Duration::from_secs(ServerConfig::CLIENT_PROBE_INTERVAL_SECS);
+
+        assert_eq!(
+            registry.probe(Instant::now() + ttl / 2, interval),
+            [addr(10)]
+        );
+        assert_eq!(registry.probe(Instant::now() + ttl * 2, interval), []);
+        assert!(registry.sessions().is_empty());
+    }
+
+    #[test]
+    fn test_full_table_drops_quietest_session() {
+        let registry = SessionRegistry::new();
+        for i in 0..ServerConfig::SESSION_MAX {
+            registry.touch(&format!("t{}", i), &holder(), addr(10), None);
+        }
+        registry.touch("t0", &holder(), addr(11), None);
+        registry.touch("new", &holder(), addr(12), None);
+
+        let sessions = registry.sessions();
+        assert_eq!(sessions.len(), ServerConfig::SESSION_MAX);
+        assert!(sessions.iter().any(|session| session.addr == addr(11)));
+        assert!(sessions.iter().any(|session| session.addr == addr(12)));
+    }
+}
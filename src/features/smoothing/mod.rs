@@ -0,0 +1,3 @@
+pub mod motion_filter;
+pub mod smoothing_service;
+pub mod tremor_gate;
@@ -0,0 +1,186 @@
+use crate::domain::config::ServerConfig;
+use crate::domain::models::Smoothing;
+use std::f64::consts::TAU;
+use std::time::{Duration, Instant};
+
+/// Moves stamped closer together than this are treated as this far apart
+const MIN_INTERVAL_SECS: f64 = 0.001;
+
+/// Smooths one client's relative pointer motion
+///
+/// The deltas are summed into a position, the position is filtered, and the filtered position's
+/// change is what gets injected. Motion the filter holds back is never lost: it is caught up
+/// when the stroke ends or when [`MotionFilter::settle`] is called before a click.
+pub struct MotionFilter {
+    smoothing: Smoothing,
+    axes: [Axis; 2],
+    last: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Axis {
+    raw: f64,
+    filtered: f64,
+    /// Low-passed speed in pixels per second, steering the 1€ cutoff
+    speed: f64,
+}
+
+impl MotionFilter {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            axes: [Axis::default(); 2],
+            last: None,
+        }
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    /// Smoothed delta to inject for a raw delta that arrived at `now`
+    pub fn filter(&mut self, dx: f64, dy: f64, now: Instant) -> (f64, f64) {
+        let interval = self
+            .last
+            .map(|last| now.saturating_duration_since(last))
+            .unwrap_or(Duration::MAX);
+        self.last = Some(now);
+        let new_stroke = interval >= Duration::from_millis(ServerConfig::SMOOTHING_STROKE_GAP_MS);
+        let dt = interval.as_secs_f64().max(MIN_INTERVAL_SECS);
+
+        let smoothing = self.smoothing;
+        let [x, y] = &mut self.axes;
+        (
+            x.step(dx, dt, new_stroke, smoothing),
+            y.step(dy, dt, new_stroke, smoothing),
+        )
+    }
+
+    /// Releases the motion still held back, so a click lands where the client pointed
+    pub fn settle(&mut self) -> Option<(f64, f64)> {
+        let [x, y] = &mut self.axes;
+        let residual = (x.catch_up(), y.catch_up());
+        (residual != (0.0, 0.0)).then_some(residual)
+    }
+}
+
+impl Axis {
+    fn step(&mut self, delta: f64, dt: f64, new_stroke: bool, smoothing: Smoothing) -> f64 {
+        if new_stroke {
+            // Starting from zero keeps the running sums small over a long session
+            let held_back = self.catch_up();
+            *self = Axis::default();
+            self.raw = delta;
+            self.filtered = delta;
+            return held_back + delta;
+        }
+
+        let before = self.filtered;
+        self.raw += delta;
+        let alpha = match smoothing {
+            Smoothing::Off => 1.0,
+            Smoothing::Ema { alpha } => alpha.clamp(ServerConfig::SMOOTHING_MIN_ALPHA, 1.0),
+            Smoothing::OneEuro { min_cutoff, beta } => {
+                let speed = (self.raw - self.filtered) / dt;
+                let speed_alpha = alpha(ServerConfig::SMOOTHING_DERIVATIVE_CUTOFF_HZ, dt);
+                self.speed += speed_alpha * (speed - self.speed);
+                let cutoff = min_cutoff.max(ServerConfig::SMOOTHING_MIN_CUTOFF_HZ)
+                    + beta.max(0.0) * self.speed.abs();
+                alpha(cutoff, dt)
+            }
+        };
+        self.filtered += alpha * (self.raw - self.filtered);
+        self.filtered - before
+    }
+
+    fn catch_up(&mut self) -> f64 {
+        let residual = self.raw - self.filtered;
+        self.filtered = self.raw;
+        residual
+    }
+}
+
+/// Low-pass weight for a cutoff frequency at sample interval `dt`
+fn alpha(cutoff_hz: f64, dt: f64) -> f64 {
+    let tau = 1.0 / (TAU * cutoff_hz);
+    1.0 / (1.0 + tau / dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(8);
+
+    /// Feeds `deltas` one frame apart, returning what was injected and how far it trails
+    fn run(filter: &mut MotionFilter, deltas: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        let start = Instant::now();
+        let (mut raw, mut emitted) = (0.0, 0.0);
+        let mut outputs = Vec::new();
+        let mut lags = Vec::new();
+        for (i, delta) in deltas.iter().enumerate() {
+            let (dx, _) = filter.filter(*delta, 0.0, start + FRAME * i as u32);
+            raw += delta;
+            emitted += dx;
+            outputs.push(dx);
+            lags.push(raw - emitted);
+        }
+        (outputs, lags)
+    }
+
+    #[test]
+    fn test_off_passes_motion_through() {
+        let mut filter = MotionFilter::new(Smoothing::Off);
+        let (outputs, _) = run(&mut filter, &[3.0, -1.5, 7.25]);
+        assert_eq!(outputs, vec![3.0, -1.5, 7.25]);
+        assert_eq!(filter.settle(), None);
+    }
+
+    #[test]
+    fn test_ema_lag_stays_within_bound() {
+        let alpha = 0.5;
+        let mut filter = MotionFilter::new(Smoothing::Ema { alpha });
+        let (_, lags) = run(&mut filter, &[10.0; 100]);
+        // At a steady speed an EMA trails by speed × (1 − α) / α, one frame here
+        let bound = 10.0 * (1.0 - alpha) / alpha;
+        assert!(lags.iter().all(|lag| *lag <= bound + 1e-9));
+    }
+
+    #[test]
+    fn test_one_euro_lets_fast_motion_through() {
+        let mut filter = MotionFilter::new(Smoothing::OneEuro {
+            min_cutoff: 1.0,
+            beta: 0.007,
+        });
+        // 2000 px/s, a quick flick across the screen
+        let (_, lags) = run(&mut filter, &[16.0; 60]);
+        let trailing_ms = lags.last().unwrap() / 16.0 * FRAME.as_millis() as f64;
+        assert!(trailing_ms < 20.0, "trails by {trailing_ms} ms");
+    }
+
+    #[test]
+    fn test_one_euro_damps_jitter_at_rest() {
+        let mut filter = MotionFilter::new(Smoothing::OneEuro {
+            min_cutoff: 1.0,
+            beta: 0.007,
+        });
+        let jitter: Vec<f64> = (0..40)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let (outputs, _) = run(&mut filter, &jitter);
+        assert!(outputs[1..].iter().all(|dx| dx.abs() < 0.25));
+    }
+
+    #[test]
+    fn test_held_back_motion_is_never_lost() {
+        let mut filter = MotionFilter::new(Smoothing::Ema { alpha: 0.2 });
+        let (outputs, _) = run(&mut filter, &[5.0; 10]);
+        let (settled, _) = filter.settle().unwrap();
+        assert!((outputs.iter().sum::<f64>() + settled - 50.0).abs() < 1e-9);
+
+        let (outputs, _) = run(&mut filter, &[5.0; 10]);
+        let later = Instant::now() + Duration::from_secs(1);
+        let (resumed, _) = filter.filter(1.0, 0.0, later);
+        assert!((outputs.iter().sum::<f64>() + resumed - 51.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::domain::config::settings::JitterSettings;
+use crate::domain::models::{Command, Smoothing};
+use crate::features::smoothing::motion_filter::MotionFilter;
+use crate::features::smoothing::tremor_gate::TremorGate;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Per-client pointer conditioning: jitter rejection, then smoothing
+///
+/// Smoothing defaults to the `smoothing` setting until a client picks its own.
+pub struct SmoothingService {
+    default: Smoothing,
+    jitter: JitterSettings,
+    clients: Mutex<HashMap<SocketAddr, ClientMotion>>,
+}
+
+struct ClientMotion {
+    gate: TremorGate,
+    filter: MotionFilter,
+}
+
+impl SmoothingService {
+    pub fn new(default: Smoothing, jitter: JitterSettings) -> Self {
+        Self {
+            default,
+            jitter,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Switches the filter for `addr`, returning motion the old one still held back
+    pub fn set(&self, addr: SocketAddr, smoothing: Smoothing) -> Option<Command> {
+        let mut clients = self.lock_clients();
+        let client = clients.entry(addr).or_insert_with(|| self.client());
+        let mut previous = std::mem::replace(&mut client.filter, MotionFilter::new(smoothing));
+        previous.settle().map(|(x, y)| Command::MouseMove { x, y })
+    }
+
+    /// Conditions a pointer move from `addr`; `None` when the move is held back entirely
+    ///
+    /// Other commands pass through.
+    pub fn apply(&self, addr: SocketAddr, command: Command) -> Option<Command> {
+        let Command::MouseMove { x, y } = command else {
+            return Some(command);
+        };
+        let mut clients = self.lock_clients();
+        if !self.conditions_by_default() && !clients.contains_key(&addr) {
+            return Some(command);
+        }
+        let client = clients.entry(addr).or_insert_with(|| self.client());
+        let now = Instant::now();
+        let (x, y) = client.gate.admit(x, y, now)?;
+        let (x, y) = client.filter.filter(x, y, now);
+        Some(Command::MouseMove { x, y })
+    }
+
+    /// Motion to inject before `command` so button presses land where the client pointed
+    pub fn settle_before(&self, addr: SocketAddr, command: &Command) -> Option<Command> {
+        let presses_button = matches!(
+            command,
+            Command::MouseClick { .. }
+                | Command::MouseMultiClick { .. }
+                | Command::MouseLongPress { .. }
+                | Command::Drag { .. }
+                | Command::TouchGesture { .. }
+                | Command::MouseDown { .. }
+                | Command::MouseUp { .. }
+        );
+        if !presses_button {
+            return None;
+        }
+        let (x, y) = self.lock_clients().get_mut(&addr)?.filter.settle()?;
+        Some(Command::MouseMove { x, y })
+    }
+
+    fn conditions_by_default(&self) -> bool {
+        self.default != Smoothing::Off
+            || self.jitter.dead_zone_px > 0.0
+            || self.jitter.burst_rejection
+    }
+
+    fn client(&self) -> ClientMotion {
+        ClientMotion {
+            gate: TremorGate::new(self.jitter.clone()),
+            filter: MotionFilter::new(self.default),
+        }
+    }
+
+    fn lock_clients(&self) -> MutexGuard<'_, HashMap<SocketAddr, ClientMotion>> {
+        self.clients.lock().expect("Smoothing mutex poisoned")
+    }
+}
@@ -0,0 +1,139 @@
+use crate::domain::config::settings::JitterSettings;
+use crate::domain::config::ServerConfig;
+use std::time::{Duration, Instant};
+
+/// Holds back micro-movements and lone spikes from a client's touch sensor
+///
+/// Moves are summed until they leave the dead zone, so a finger resting on the glass keeps the
+/// cursor still while slow deliberate motion still gets through. A single move far larger than
+/// the stroke's recent motion is what a palm or second finger brushing the sensor looks like,
+/// and is dropped; a fast flick keeps growing instead, so the move after a spike is let through.
+pub struct TremorGate {
+    settings: JitterSettings,
+    pending: (f64, f64),
+    /// Running mean of move sizes in the current stroke
+    average: f64,
+    samples: usize,
+    spiked: bool,
+    last: Option<Instant>,
+}
+
+impl TremorGate {
+    pub fn new(settings: JitterSettings) -> Self {
+        Self {
+            settings,
+            pending: (0.0, 0.0),
+            average: 0.0,
+            samples: 0,
+            spiked: false,
+            last: None,
+        }
+    }
+
+    /// Motion to pass on for a move that arrived at `now`, or `None` to hold it back
+    pub fn admit(&mut self, dx: f64, dy: f64, now: Instant) -> Option<(f64, f64)> {
+        let new_stroke = self.last.is_none_or(|last| {
+            now.saturating_duration_since(last)
+                >= Duration::from_millis(ServerConfig::SMOOTHING_STROKE_GAP_MS)
+        });
+        self.last = Some(now);
+        if new_stroke {
+            *self = Self {
+                last: self.last,
+                ..Self::new(self.settings.clone())
+            };
+        }
+
+        let size = dx.hypot(dy);
+        if self.is_burst(size) {
+            log::debug!("Dropped a {:.0} px burst", size);
+            self.spiked = true;
+            return None;
+        }
+        self.spiked = false;
+        self.samples += 1;
+        let window = self.samples.min(ServerConfig::JITTER_AVERAGE_WINDOW) as f64;
+        self.average += (size - self.average) / window;
+
+        self.pending = (self.pending.0 + dx, self.pending.1 + dy);
+        if self.pending.0.hypot(self.pending.1) < self.settings.dead_zone_px {
+            return None;
+        }
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    fn is_burst(&self, size: f64) -> bool {
+        self.settings.burst_rejection
+            && !self.spiked
+            && self.samples >= ServerConfig::JITTER_BURST_MIN_SAMPLES
+            && size
+                > self
+                    .settings
+                    .burst_min_px
+                    .max(self.settings.burst_ratio * self.average)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(8);
+
+    fn gate(dead_zone_px: f64, burst_rejection: bool) -> TremorGate {
+        TremorGate::new(JitterSettings {
+            dead_zone_px,
+            burst_rejection,
+            ..JitterSettings::default()
+        })
+    }
+
+    fn admitted(gate: &mut TremorGate, moves: &[(f64, f64)]) -> Vec<Option<(f64, f64)>> {
+        let start = Instant::now();
+        moves
+            .iter()
+            .enumerate()
+            .map(|(i, (dx, dy))| gate.admit(*dx, *dy, start + FRAME * i as u32))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_pass_everything() {
+        let mut gate = TremorGate::new(JitterSettings::default());
+        let moves = [(0.1, 0.0), (500.0, 0.0), (-0.2, 0.3)];
+        let passed = admitted(&mut gate, &moves);
+        assert!(passed.iter().zip(moves).all(|(out, d)| *out == Some(d)));
+    }
+
+    #[test]
+    fn test_dead_zone_swallows_tremble_but_not_drift() {
+        let mut resting = gate(1.0, false);
+        let tremble: Vec<(f64, f64)> = (0..20)
+            .map(|i| if i % 2 == 0 { (0.4, -0.3) } else { (-0.4, 0.3) })
+            .collect();
+        assert!(admitted(&mut resting, &tremble).iter().all(Option::is_none));
+
+        let drift = admitted(&mut resting, &[(0.3, 0.0); 4]);
+        assert_eq!(drift.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn test_lone_spike_is_dropped_but_a_flick_is_not() {
+        let mut palm = gate(0.0, true);
+        let moves = [(3.0, 0.0), (3.0, 0.0), (3.0, 0.0), (0.0, 400.0), (3.0, 0.0)];
+        let passed = admitted(&mut palm, &moves);
+        assert_eq!(passed[3], None);
+        assert_eq!(passed[4], Some((3.0, 0.0)));
+
+        let mut flick = gate(0.0, true);
+        let moves = [
+            (3.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 0.0),
+            (0.0, 400.0),
+            (0.0, 420.0),
+        ];
+        let passed = admitted(&mut flick, &moves);
+        assert_eq!(passed[4], Some((0.0, 420.0)));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::domain::config::ServerConfig;
+use crate::utils;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// In-process counters for command traffic, never recording command content
+pub struct StatsRegistry {
+    started: Instant,
+    commands: Mutex<BTreeMap<&'static str, u64>>,
+    /// Per-kind counts in fixed slices of time, newest last, covering the longest rate window
+    recent: Mutex<VecDeque<Bucket>>,
+    /// Per-client command counts in wall-clock slices, oldest first, keyed by token id
+    activity: Mutex<HashMap<String, VecDeque<ActivityBucket>>>,
+    denied: AtomicU64,
+    malformed: AtomicU64,
+    invalid: AtomicU64,
+    reordered: AtomicU64,
+    replayed: AtomicU64,
+    forged: AtomicU64,
+    blocked: AtomicU64,
+    throttled: AtomicU64,
+}
+
+struct Bucket {
+    /// Index of the slice since startup
+    slice: u64,
+    commands: BTreeMap<&'static str, u64>,
+}
+
+/// Commands one client sent in a slice of `bucket_secs` starting at `start_ms`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ActivityBucket {
+    pub start_ms: u64,
+    pub commands: u64,
+}
+
+/// A client's activity over the last day; slices it sent nothing in are left out
+#[derive(Serialize, Debug, Clone)]
+pub struct ClientActivity {
+    pub client_id: String,
+    pub bucket_secs: u64,
+    pub buckets: Vec<ActivityBucket>,
+}
+
+/// Commands per second, averaged over the last 1, 5 and 15 minutes
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CommandRates {
+    #[serde(rename = "1m")]
+    pub one_minute: f64,
+    #[serde(rename = "5m")]
+    pub five_minutes: f64,
+    #[serde(rename = "15m")]
+    pub fifteen_minutes: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StatsSnapshot {
+    pub uptime_secs: u64,
+    pub commands: BTreeMap<&'static str, u64>,
+    pub denied: u64,
+    pub malformed: u64,
+    /// Parsed, but carrying values the backends can't inject
+    pub invalid: u64,
+    /// Held back until an earlier command that arrived late caught up
+    pub reordered: u64,
+    /// Dropped because their sequence number had already passed
+    pub replayed: u64,
+    /// Signed datagrams whose signer or MAC didn't check out
+    pub forged: u64,
+    /// Command datagrams and TCP connections from outside `network.allow` or inside `network.deny`
+    pub blocked: u64,
+    /// Command datagrams dropped because their client was over `rate_limit`
+    pub throttled: u64,
+}
+
+impl StatsRegistry {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            commands: Mutex::new(BTreeMap::new()),
+            recent: Mutex::new(VecDeque::new()),
+            activity: Mutex::new(HashMap::new()),
+            denied: AtomicU64::new(0),
+            malformed: AtomicU64::new(0),
+            invalid: AtomicU64::new(0),
+            reordered: AtomicU64::new(0),
+            replayed: AtomicU64::new(0),
+            forged: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a command, and against its client too when it carried a token
+    pub fn record_command(&self, kind: &'static str, client_id: Option<&str>) {
+        let mut commands = self.commands.lock().expect("Stats mutex poisoned");
+        *commands.entry(kind).or_insert(0) += 1;
+        drop(commands);
+        self.record_recent(kind, self.started.elapsed());
+        if let Some(client_id) = client_id {
+            self.record_activity(client_id, utils::unix_now_ms());
+        }
+    }
+
+    fn record_activity(&self, client_id: &str, now_ms: u64) {
+        let bucket_ms = ServerConfig::ACTIVITY_BUCKET_SECS * 1000;
+        let start_ms = now_ms - now_ms % bucket_ms;
+        let mut activity = self.activity.lock().expect("Stats mutex poisoned");
+        let buckets = activity.entry(client_id.to_string()).or_default();
+        while buckets
+            .front()
+            .is_some_and(|bucket| !Self::in_activity_window(bucket, now_ms))
+        {
+            buckets.pop_front();
+        }
+        match buckets.back_mut() {
+            Some(bucket) if bucket.start_ms == start_ms => bucket.commands += 1,
+            _ => buckets.push_back(ActivityBucket {
+                start_ms,
+                commands: 1,
+            }),
+        }
+    }
+
+    /// What `client_id` sent over the last day
+    pub fn activity(&self, client_id: &str) -> ClientActivity {
+        self.activity_at(client_id, utils::unix_now_ms())
+    }
+
+    fn activity_at(&self, client_id: &str, now_ms: u64) -> ClientActivity {
+        let activity = self.activity.lock().expect("Stats mutex poisoned");
+        let buckets = activity
+            .get(client_id)
+            .map(|buckets| {
+                buckets
+                    .iter()
+                    .filter(|bucket| Self::in_activity_window(bucket, now_ms))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        ClientActivity {
+            client_id: client_id.to_string(),
+            bucket_secs: ServerConfig::ACTIVITY_BUCKET_SECS,
+            buckets,
+        }
+    }
+
+    fn in_activity_window(bucket: &ActivityBucket, now_ms: u64) -> bool {
+        bucket.start_ms + ServerConfig::ACTIVITY_WINDOW_SECS * 1000 > now_ms
+    }
+
+    fn record_recent(&self, kind: &'static str, uptime: Duration) {
+        let slice = uptime.as_secs() / ServerConfig::STATS_SLICE_SECS;
+        let mut recent = self.recent.lock().expect("Stats mutex poisoned");
+        if recent.back().is_none_or(|bucket| bucket.slice != slice) {
+            recent.push_back(Bucket {
+                slice,
+                commands: BTreeMap::new(),
+            });
+        }
+        let kept = Self::slices(ServerConfig::STATS_LONGEST_WINDOW_SECS);
+        while recent
+            .front()
+            .is_some_and(|bucket| bucket.slice + kept <= slice)
+        {
+            recent.pop_front();
+        }
+        if let Some(bucket) = recent.back_mut() {
+            *bucket.commands.entry(kind).or_insert(0) += 1;
+        }
+    }
+
+    /// Per-kind command rates over the last 1, 5 and 15 minutes
+    ///
+    /// A window longer than the uptime is averaged over the uptime instead.
+    pub fn rates(&self) -> BTreeMap<&'static str, CommandRates> {
+        self.rates_at(self.started.elapsed())
+    }
+
+    fn rates_at(&self, uptime: Duration) -> BTreeMap<&'static str, CommandRates> {
+        let current = uptime.as_secs() / ServerConfig::STATS_SLICE_SECS;
+        let recent = self.recent.lock().expect("Stats mutex poisoned");
+        let rate = |window_secs: u64, kind: &'static str| {
+            let first = current.saturating_sub(Self::slices(window_secs) - 1);
+            let count: u64 = recent
+                .iter()
+                .filter(|bucket| bucket.slice >= first)
+                .filter_map(|bucket| bucket.commands.get(kind))
+                .sum();
+            let covered = uptime.as_secs_f64().clamp(1.0, window_secs as f64);
+            count as f64 / covered
+        };
+        let kinds: std::collections::BTreeSet<&'static str> = recent
+            .iter()
+            .flat_map(|bucket| bucket.commands.keys().copied())
+            .collect();
+        kinds
+            .into_iter()
+            .map(|kind| {
+                let rates = CommandRates {
+                    one_minute: rate(60, kind),
+                    five_minutes: rate(5 * 60, kind),
+                    fifteen_minutes: rate(ServerConfig::STATS_LONGEST_WINDOW_SECS, kind),
+                };
+                (kind, rates)
+            })
+            .collect()
+    }
+
+    fn slices(window_secs: u64) -> u64 {
+        window_secs / ServerConfig::STATS_SLICE_SECS
+    }
+
+    pub fn record_denied(&self) {
+        self.denied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_malformed(&self) {
+        self.malformed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_invalid(&self) {
+        self.invalid.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reordered(&self) {
+        self.reordered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_replayed(&self) {
+        self.replayed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_forged(&self) {
+        self.forged.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_blocked(&self) {
+        self.blocked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_throttled(&self) {
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            uptime_secs: self.started.elapsed().as_secs(),
+            commands: self.commands.lock().expect("Stats mutex poisoned").clone(),
+            denied: self.denied.load(Ordering::Relaxed),
+            malformed: self.malformed.load(Ordering::Relaxed),
+            invalid: self.invalid.load(Ordering::Relaxed),
+            reordered: self.reordered.load(Ordering::Relaxed),
+            replayed: self.replayed.load(Ordering::Relaxed),
+            forged: self.forged.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for StatsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_by_kind() {
+        let stats = StatsRegistry::new();
+        stats.record_command("MouseMove", None);
+        stats.record_command("MouseMove", None);
+        stats.record_command("KeyPress", Some("a1"));
+        stats.record_denied();
+        stats.record_invalid();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.commands["MouseMove"], 2);
+        assert_eq!(snapshot.commands["KeyPress"], 1);
+        assert_eq!(snapshot.denied, 1);
+        assert_eq!(snapshot.malformed, 0);
+        assert_eq!(snapshot.invalid, 1);
+    }
+
+    #[test]
+    fn test_rates_cover_their_windows() {
+        let stats = StatsRegistry::new();
+        for _ in 0..60 {
+            stats.record_recent("MouseMove", Duration::from_secs(100));
+        }
+        for _ in 0..30 {
+            stats.record_recent("KeyPress", Duration::from_secs(890));
+        }
+
+        let rates = stats.rates_at(Duration::from_secs(900));
+        assert_eq!(rates["KeyPress"].one_minute, 0.5);
+        assert_eq!(rates["MouseMove"].one_minute, 0.0);
+        assert_eq!(rates["MouseMove"].five_minutes, 0.0);
+        assert_eq!(rates["MouseMove"].fifteen_minutes, 60.0 / 900.0);
+
+        let early = stats.rates_at(Duration::from_secs(120));
+        assert_eq!(early["MouseMove"].one_minute, 1.0);
+        assert_eq!(early["MouseMove"].five_minutes, 0.5);
+    }
+
+    #[test]
+    fn test_rates_right_after_startup() {
+        let stats = StatsRegistry::new();
+        stats.record_recent("MouseMove", Duration::from_secs(1));
+        stats.record_recent("MouseMove", Duration::from_secs(2));
+        let early = stats.rates_at(Duration::from_secs(2));
+        assert_eq!(early["MouseMove"].one_minute, 1.0);
+        assert_eq!(early["MouseMove"].fifteen_minutes, 1.0);
+    }
+
+    #[test]
+    fn test_activity_buckets_per_client_and_ages_out() {
+        let stats = StatsRegistry::new();
+        let bucket_ms = ServerConfig::ACTIVITY_BUCKET_SECS * 1000;
+        let day_ms = ServerConfig::ACTIVITY_WINDOW_SECS * 1000;
+        let start = 10 * day_ms;
+        stats.record_activity("a1", start + 1);
+        stats.record_activity("a1", start + bucket_ms - 1);
+        stats.record_activity("a1", start + 3 * bucket_ms);
+        stats.record_activity("b2", start);
+
+        let activity = stats.activity_at("a1", start + 3 * bucket_ms);
+        assert_eq!(
+            activity.buckets,
+            [
+                ActivityBucket {
+                    start_ms: start,
+                    commands: 2
+                },
+                ActivityBucket {
+                    start_ms: start + 3 * bucket_ms,
+                    commands: 1
+                },
+            ]
+        );
+        let next_day = stats.activity_at("a1", start + day_ms + bucket_ms);
+        assert_eq!(next_day.buckets.len(), 1);
+        assert!(stats.activity_at("c3", start).buckets.is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+//! JPEG encoding for preview frames
+
+use crate::features::streaming::screen_capture::Frame;
+use anyhow::{Context, Result};
+use jpeg_encoder::{ColorType, Encoder};
+
+/// Encodes `frame` as a JPEG at `quality`, from 1 to 100
+pub fn encode(frame: &Frame, quality: u8) -> Result<Vec<u8>> {
+    let width = u16::try_from(frame.width).context("Frame too wide for JPEG")?;
+    let height = u16::try_from(frame.height).context("Frame too tall for JPEG")?;
+    let mut out = Vec::with_capacity(frame.width * frame.height / 2 + 1024);
+    Encoder::new(&mut out, quality.clamp(1, 100)).encode(
+        &frame.rgb,
+        width,
+        height,
+        ColorType::Rgb,
+    )?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_decode_back_to_roughly_the_same_pixels() {
+        // Not a multiple of 8 in either direction, so the edge blocks are padded
+        let (width, height) = (21, 13);
+        let rgb: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [(x * 12) as u8, (y * 19) as u8, 200]
+            })
+            .collect();
+        let jpeg = encode(
+            &Frame {
+                width,
+                height,
+                rgb: rgb.clone(),
+            },
+            90,
+        )
+        .unwrap();
+
+        let mut decoder = jpeg_decoder::Decoder::new(jpeg.as_slice());
+        let decoded = decoder.decode().unwrap();
+        let info = decoder.info().unwrap();
+        assert_eq!((info.width, info.height), (21, 13));
+        assert_eq!(info.pixel_format, jpeg_decoder::PixelFormat::RGB24);
+        let worst = rgb
+            .iter()
+            .zip(&decoded)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap();
+        assert!(worst < 40, "channel off by {}", worst);
+    }
+}
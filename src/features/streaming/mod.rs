@@ -0,0 +1,3 @@
+pub mod jpeg;
+pub mod preview_server;
+pub mod screen_capture;
@@ -0,0 +1,296 @@
+//! Live thumbnails of the display under the pointer, for clients to show while controlling it
+//!
+//! Frames go out as MJPEG (`multipart/x-mixed-replace`), which an `<img>` tag or any HTTP
+//! client can show without a video decoder or the signalling WebRTC would need. The screen is
+//! only captured while someone is watching, once per frame however many are.
+
+use crate::domain::config::settings::{NetworkSettings, PreviewSettings};
+use crate::domain::config::{ServerConfig, Settings};
+use crate::features::auth::token_store::{DeviceInfo, TokenStore};
+use crate::features::auth::viewer_ticket;
+use crate::features::command::policy::{CommandPolicy, Denial};
+use crate::features::presence::kill_switch::KillSwitch;
+use crate::features::streaming::{jpeg, screen_capture};
+use crate::utils::unix_now;
+use anyhow::{Context, Result};
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Extension;
+use axum::Router;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+
+#[derive(Clone)]
+pub struct PreviewServer {
+    settings: PreviewSettings,
+    network: Arc<NetworkSettings>,
+    tokens: Arc<TokenStore>,
+    policy: Arc<CommandPolicy>,
+    kill_switch: Arc<KillSwitch>,
+    feed: Arc<FrameFeed>,
+    viewers: Arc<Semaphore>,
+}
+
+/// The latest encoded frame, from one capture loop that runs while anyone subscribes
+struct FrameFeed {
+    frames: watch::Sender<FeedFrame>,
+    capturing: Mutex<bool>,
+}
+
+#[derive(Clone)]
+enum FeedFrame {
+    Pending,
+    Ready(Bytes),
+    /// The loop stopped; the next subscriber starts it again
+    Failed(String),
+}
+
+/// `?ticket=`, which an `<img>` can carry, derived from the token with [`viewer_ticket`]
+#[derive(Deserialize)]
+struct TicketQuery {
+    ticket: Option<String>,
+}
+
+impl PreviewServer {
+    pub fn new(
+        settings: &Settings,
+        tokens: Arc<TokenStore>,
+        policy: Arc<CommandPolicy>,
+        kill_switch: Arc<KillSwitch>,
+    ) -> Self {
+        Self {
+            settings: settings.preview.clone(),
+            network: Arc::new(settings.network.clone()),
+            tokens,
+            policy,
+            kill_switch,
+            feed: Arc::new(FrameFeed {
+                frames: watch::Sender::new(FeedFrame::Pending),
+                capturing: Mutex::new(false),
+            }),
+            viewers: Arc::new(Semaphore::new(ServerConfig::PREVIEW_MAX_VIEWERS)),
+        }
+    }
+
+    /// Serves `/preview.mjpeg` and `/preview.jpg`; returns at once unless enabled
+    pub async fn run(self) -> Result<()> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+        let app = Router::new()
+            .route("/preview.mjpeg", get(stream))
+            .route("/preview.jpg", get(snapshot))
+            .route_layer(middleware::from_fn_with_state(self.clone(), require_viewer))
+            .with_state(self);
+
+        let port = ServerConfig::PREVIEW_PORT;
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))
+            .await
+            .with_context(|| format!("Failed to bind preview port {}", port))?;
+        log::info!("Screen preview listening on port {}", port);
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Joins the shared capture loop, starting it if no one else is watching
+    fn subscribe(&self) -> watch::Receiver<FeedFrame> {
+        let mut capturing = self.feed.lock_capturing();
+        let frames = self.feed.frames.subscribe();
+        if !*capturing {
+            *capturing = true;
+            tokio::spawn(self.clone().capture_loop());
+        }
+        frames
+    }
+
+    /// Captures a frame every interval until the last subscriber leaves or capture fails
+    async fn capture_loop(self) {
+        let mut ticker = tokio::time::interval(self.frame_interval());
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let frame = if self.kill_switch.is_engaged() {
+                Err(anyhow::anyhow!("remote input is suspended"))
+            } else {
+                self.frame().await
+            };
+            let mut capturing = self.feed.lock_capturing();
+            match frame {
+                Ok(jpeg) if self.feed.frames.receiver_count() > 0 => {
+                    self.feed.frames.send_replace(FeedFrame::Ready(jpeg));
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::warn!("Screen preview stopped: {}", e);
+                    self.feed
+                        .frames
+                        .send_replace(FeedFrame::Failed(e.to_string()));
+                }
+            }
+            *capturing = false;
+            return;
+        }
+    }
+
+    /// Captures and encodes one frame off the async runtime
+    async fn frame(&self) -> Result<Bytes> {
+        let (max_width, quality) = (self.settings.max_width, self.settings.quality);
+        tokio::task::spawn_blocking(move || {
+            let frame = screen_capture::capture(max_width)?;
+            Ok(Bytes::from(jpeg::encode(&frame, quality)?))
+        })
+        .await?
+    }
+
+    fn frame_interval(&self) -> Duration {
+        let fps = self.settings.fps.clamp(1, ServerConfig::PREVIEW_MAX_FPS);
+        Duration::from_secs(1) / fps
+    }
+
+    /// Holds a viewer slot for as long as the permit lives; `None` when all are taken
+    fn admit(&self) -> Option<OwnedSemaphorePermit> {
+        self.viewers.clone().try_acquire_owned().ok()
+    }
+}
+
+impl FrameFeed {
+    fn lock_capturing(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.capturing
+            .lock()
+            .expect("Preview capture mutex poisoned")
+    }
+}
+
+/// Waits for the feed's next frame
+async fn next_frame(frames: &mut watch::Receiver<FeedFrame>) -> Result<Bytes> {
+    loop {
+        frames.changed().await?;
+        match &*frames.borrow_and_update() {
+            FeedFrame::Pending => continue,
+            FeedFrame::Ready(jpeg) => return Ok(jpeg.clone()),
+            FeedFrame::Failed(reason) => anyhow::bail!("{}", reason),
+        }
+    }
+}
+
+/// Admits clients the network settings let in with a live ticket the policy lets watch
+async fn require_viewer(
+    State(server): State<PreviewServer>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(query): Query<TicketQuery>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if !server.network.admits(peer.ip()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let holder = query
+        .ticket
+        .and_then(|ticket| viewer_ticket::open(&server.tokens, &ticket, unix_now()))
+        .and_then(|token| server.tokens.validate(&token))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    server.policy.admit_viewer(&holder).map_err(denied)?;
+    request.extensions_mut().insert(holder);
+    Ok(next.run(request).await)
+}
+
+fn denied(denial: Denial) -> StatusCode {
+    match denial {
+        Denial::OutOfScope | Denial::SessionLocked => StatusCode::FORBIDDEN,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+async fn snapshot(State(server): State<PreviewServer>) -> Response {
+    let Some(_permit) = server.admit() else {
+        return too_many_viewers();
+    };
+    match next_frame(&mut server.subscribe()).await {
+        Ok(jpeg) => ([(header::CONTENT_TYPE, "image/jpeg")], jpeg).into_response(),
+        Err(e) => unavailable(e),
+    }
+}
+
+/// Sends each frame of the shared feed until the viewer disconnects, the feed stops, or the
+/// policy stops admitting the viewer
+async fn stream(
+    State(server): State<PreviewServer>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Response {
+    let Some(permit) = server.admit() else {
+        return too_many_viewers();
+    };
+    let mut frames = server.subscribe();
+    // The first frame is awaited up front so a capture that can't work fails with a status
+    let first = match next_frame(&mut frames).await {
+        Ok(jpeg) => jpeg,
+        Err(e) => return unavailable(e),
+    };
+    let (parts, receiver) = mpsc::channel::<Result<Bytes, Infallible>>(2);
+    tokio::spawn(async move {
+        let _permit = permit;
+        let mut jpeg = first;
+        loop {
+            let part = Bytes::from(format!(
+                "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                ServerConfig::PREVIEW_BOUNDARY,
+                jpeg.len()
+            ));
+            let sent = async {
+                parts.send(Ok(part)).await?;
+                parts.send(Ok(jpeg)).await?;
+                parts.send(Ok(Bytes::from_static(b"\r\n"))).await
+            };
+            if sent.await.is_err() {
+                return;
+            }
+            jpeg = match next_frame(&mut frames).await {
+                Ok(jpeg) => jpeg,
+                Err(_) => return,
+            };
+            if server.policy.admit_viewer(&holder).is_err() {
+                return;
+            }
+        }
+    });
+
+    let content_type = format!(
+        "multipart/x-mixed-replace; boundary={}",
+        ServerConfig::PREVIEW_BOUNDARY
+    );
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response()
+}
+
+fn too_many_viewers() -> Response {
+    let reason = format!(
+        "at most {} viewers at a time",
+        ServerConfig::PREVIEW_MAX_VIEWERS
+    );
+    (StatusCode::SERVICE_UNAVAILABLE, reason).into_response()
+}
+
+fn unavailable(error: anyhow::Error) -> Response {
+    log::warn!("Screen preview unavailable: {}", error);
+    (StatusCode::SERVICE_UNAVAILABLE, error.to_string()).into_response()
+}
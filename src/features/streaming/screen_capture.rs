@@ -0,0 +1,341 @@
+//! Grabbing the display under the pointer for the preview
+//!
+//! X11 reads the root window, so a Wayland session shows at most its XWayland windows. macOS
+//! needs the Screen Recording permission, and a Windows service in session 0 can't see the
+//! user's desktop at all.
+
+#[cfg(any(target_os = "linux", windows))]
+use crate::input::pointer::{self, DesktopBounds};
+#[cfg(any(target_os = "linux", windows))]
+use anyhow::bail;
+use anyhow::Result;
+
+/// A captured display scaled down for the preview, as packed RGB rows
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+/// Pixels as the OS hands them over: 32-bit rows, blue first and padding or alpha last
+struct Capture {
+    width: usize,
+    height: usize,
+    /// Bytes per row, which can be more than four per pixel
+    stride: usize,
+    bgra: Vec<u8>,
+}
+
+/// The display the pointer is on, at most `max_width` pixels across
+pub fn capture(max_width: usize) -> Result<Frame> {
+    Ok(downscale(&grab()?, max_width))
+}
+
+/// Averages every source pixel into the frame, so thin text and lines still show up
+fn downscale(capture: &Capture, max_width: usize) -> Frame {
+    let width = capture.width.min(max_width.max(1));
+    let height = (capture.height * width / capture.width).max(1);
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let rows = span(y, height, capture.height);
+        for x in 0..width {
+            let columns = span(x, width, capture.width);
+            let mut sum = [0u32; 3];
+            for row in rows.clone() {
+                let row = &capture.bgra[row * capture.stride..];
+                for pixel in row[columns.start * 4..columns.end * 4].chunks_exact(4) {
+                    sum[0] += u32::from(pixel[2]);
+                    sum[1] += u32::from(pixel[1]);
+                    sum[2] += u32::from(pixel[0]);
+                }
+            }
+            let count = (rows.len() * columns.len()) as u32;
+            rgb.extend(sum.map(|channel| (channel / count) as u8));
+        }
+    }
+    Frame { width, height, rgb }
+}
+
+/// The source pixels that output pixel `index` of `out` covers
+fn span(index: usize, out: usize, source: usize) -> std::ops::Range<usize> {
+    let start = index * source / out;
+    let end = ((index + 1) * source / out).clamp(start + 1, source);
+    start..end
+}
+
+/// The display holding `point`, or the first one when it's in a gap between them
+#[cfg(any(target_os = "linux", windows))]
+fn display_under((x, y): (f64, f64)) -> Result<(DesktopBounds, usize, usize)> {
+    let displays = pointer::displays();
+    let bounds = displays
+        .iter()
+        .find(|d| x >= d.left && x < d.right && y >= d.top && y < d.bottom)
+        .or(displays.first())
+        .copied()
+        .unwrap_or_else(DesktopBounds::fallback);
+    let (width, height) = (bounds.right - bounds.left, bounds.bottom - bounds.top);
+    if width < 1.0 || height < 1.0 {
+        bail!("The display under the pointer has no area");
+    }
+    Ok((bounds, width as usize, height as usize))
+}
+
+#[cfg(target_os = "linux")]
+fn grab() -> Result<Capture> {
+    use x11::xlib;
+
+    unsafe {
+        let display = xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            bail!("No X display to capture; the preview needs an X11 session");
+        }
+        let screen = xlib::XDefaultScreen(display);
+        let root = xlib::XRootWindow(display, screen);
+        let (mut root_return, mut child, mut mask) = (0, 0, 0);
+        let (mut x, mut y, mut window_x, mut window_y) = (0, 0, 0, 0);
+        xlib::XQueryPointer(
+            display,
+            root,
+            &mut root_return,
+            &mut child,
+            &mut x,
+            &mut y,
+            &mut window_x,
+            &mut window_y,
+            &mut mask,
+        );
+        // Reading past the root window is a protocol error, which Xlib's handler exits on
+        let screen_bounds = DesktopBounds {
+            left: 0.0,
+            top: 0.0,
+            right: f64::from(xlib::XDisplayWidth(display, screen)),
+            bottom: f64::from(xlib::XDisplayHeight(display, screen)),
+        };
+        let capture = display_under((f64::from(x), f64::from(y)))
+            .and_then(|(bounds, _, _)| {
+                screen_bounds
+                    .intersect(&bounds)
+                    .ok_or_else(|| anyhow::anyhow!("The display under the pointer is off-screen"))
+            })
+            .and_then(|bounds| {
+                let (width, height) = (
+                    (bounds.right - bounds.left) as u32,
+                    (bounds.bottom - bounds.top) as u32,
+                );
+                let image = xlib::XGetImage(
+                    display,
+                    root,
+                    bounds.left as i32,
+                    bounds.top as i32,
+                    width,
+                    height,
+                    !0,
+                    xlib::ZPixmap,
+                );
+                if image.is_null() {
+                    bail!("The X server wouldn't read the screen");
+                }
+                let bits_per_pixel = (*image).bits_per_pixel;
+                let stride = (*image).bytes_per_line as usize;
+                let bgra = std::slice::from_raw_parts(
+                    (*image).data as *const u8,
+                    stride * height as usize,
+                )
+                .to_vec();
+                xlib::XDestroyImage(image);
+                if bits_per_pixel != 32 {
+                    bail!("Can't capture a {}-bit X display", bits_per_pixel);
+                }
+                Ok(Capture {
+                    width: width as usize,
+                    height: height as usize,
+                    stride,
+                    bgra,
+                })
+            });
+        xlib::XCloseDisplay(display);
+        capture
+    }
+}
+
+#[cfg(windows)]
+fn grab() -> Result<Capture> {
+    use windows::Win32::Foundation::{HWND, POINT};
+    use windows::Win32::Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
+        GetDIBits, ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS, SRCCOPY,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+    let mut point = POINT::default();
+    unsafe { GetCursorPos(&mut point)? };
+    let (bounds, width, height) = display_under((f64::from(point.x), f64::from(point.y)))?;
+
+    unsafe {
+        let screen = GetDC(HWND::default());
+        let memory = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, width as i32, height as i32);
+        let previous = SelectObject(memory, bitmap);
+        let copied = BitBlt(
+            memory,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            screen,
+            bounds.left as i32,
+            bounds.top as i32,
+            SRCCOPY,
+        );
+        // GetDIBits wants the bitmap out of the DC before it reads it
+        SelectObject(memory, previous);
+
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width as i32,
+                // Negative for rows top to bottom
+                biHeight: -(height as i32),
+                biPlanes: 1,
+                biBitCount: 32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bgra = vec![0u8; width * height * 4];
+        let lines = match copied {
+            Ok(()) => GetDIBits(
+                memory,
+                bitmap,
+                0,
+                height as u32,
+                Some(bgra.as_mut_ptr().cast()),
+                &mut info,
+                DIB_RGB_COLORS,
+            ),
+            Err(_) => 0,
+        };
+        let _ = DeleteObject(bitmap);
+        let _ = DeleteDC(memory);
+        ReleaseDC(HWND::default(), screen);
+        copied?;
+        if lines == 0 {
+            bail!("Failed to read the captured screen");
+        }
+        Ok(Capture {
+            width,
+            height,
+            stride: width * 4,
+            bgra,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn grab() -> Result<Capture> {
+    use anyhow::anyhow;
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn CGEventCreate(source: *const c_void) -> *const c_void;
+        fn CGEventGetLocation(event: *const c_void) -> CGPoint;
+        fn CGGetDisplaysWithPoint(
+            point: CGPoint,
+            max: u32,
+            displays: *mut u32,
+            count: *mut u32,
+        ) -> i32;
+        fn CGMainDisplayID() -> u32;
+        fn CGDisplayCreateImage(display: u32) -> *const c_void;
+        fn CGImageGetWidth(image: *const c_void) -> usize;
+        fn CGImageGetHeight(image: *const c_void) -> usize;
+        fn CGImageGetBytesPerRow(image: *const c_void) -> usize;
+        fn CGImageGetBitsPerPixel(image: *const c_void) -> usize;
+        fn CGImageGetDataProvider(image: *const c_void) -> *const c_void;
+        fn CGDataProviderCopyData(provider: *const c_void) -> *const c_void;
+        fn CFDataGetBytePtr(data: *const c_void) -> *const u8;
+        fn CFDataGetLength(data: *const c_void) -> isize;
+        fn CFRelease(object: *const c_void);
+    }
+
+    unsafe {
+        let mut display = CGMainDisplayID();
+        let event = CGEventCreate(std::ptr::null());
+        if !event.is_null() {
+            let location = CGEventGetLocation(event);
+            CFRelease(event);
+            let (mut found, mut count) = (0u32, 0u32);
+            if CGGetDisplaysWithPoint(location, 1, &mut found, &mut count) == 0 && count > 0 {
+                display = found;
+            }
+        }
+
+        let image = CGDisplayCreateImage(display);
+        if image.is_null() {
+            return Err(anyhow!(
+                "macOS refused to capture the screen; allow PointZerver under Screen Recording"
+            ));
+        }
+        let (width, height) = (CGImageGetWidth(image), CGImageGetHeight(image));
+        let stride = CGImageGetBytesPerRow(image);
+        let bits_per_pixel = CGImageGetBitsPerPixel(image);
+        let data = CGDataProviderCopyData(CGImageGetDataProvider(image));
+        CFRelease(image);
+        if data.is_null() {
+            return Err(anyhow!("Failed to read the captured screen"));
+        }
+        let bgra =
+            std::slice::from_raw_parts(CFDataGetBytePtr(data), CFDataGetLength(data) as usize)
+                .to_vec();
+        CFRelease(data);
+        if bits_per_pixel != 32 || width == 0 || bgra.len() < stride * height {
+            return Err(anyhow!("Can't read a {}-bit display image", bits_per_pixel));
+        }
+        Ok(Capture {
+            width,
+            height,
+            stride,
+            bgra,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downscale_averages_the_pixels_each_output_pixel_covers() {
+        // 4×2 source with padded rows: a white and a black column pair on top, all red below
+        let white = [255, 255, 255, 0];
+        let black = [0, 0, 0, 0];
+        let red = [0, 0, 255, 0];
+        let mut bgra = Vec::new();
+        for row in [[white, white, black, black], [red; 4]] {
+            bgra.extend(row.concat());
+            bgra.extend([9; 8]);
+        }
+        let capture = Capture {
+            width: 4,
+            height: 2,
+            stride: 24,
+            bgra,
+        };
+
+        let frame = downscale(&capture, 2);
+        assert_eq!((frame.width, frame.height), (2, 1));
+        assert_eq!(frame.rgb, [255, 127, 127, 127, 0, 0]);
+
+        let frame = downscale(&capture, 640);
+        assert_eq!((frame.width, frame.height), (4, 2));
+        assert_eq!(frame.rgb[..3], [255, 255, 255]);
+        assert_eq!(frame.rgb[12..15], [255, 0, 0]);
+    }
+}
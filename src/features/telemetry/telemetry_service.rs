@@ -0,0 +1,82 @@
+use crate::domain::config::settings::TelemetrySettings;
+use crate::features::stats::stats_registry::StatsRegistry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Anonymous usage report; contains counts only, never command content
+#[derive(Serialize, Debug, Clone)]
+pub struct TelemetryPayload {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub uptime_secs: u64,
+    pub command_counts: BTreeMap<&'static str, u64>,
+}
+
+/// What would be sent, and where, as shown by `GET /telemetry`
+#[derive(Serialize, Debug, Clone)]
+pub struct TelemetryPreview {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    pub payload: TelemetryPayload,
+}
+
+/// Periodically reports aggregated stats when the user has opted in
+pub struct TelemetryService {
+    settings: TelemetrySettings,
+    stats: Arc<StatsRegistry>,
+}
+
+impl TelemetryService {
+    pub fn new(settings: TelemetrySettings, stats: Arc<StatsRegistry>) -> Self {
+        Self { settings, stats }
+    }
+
+    pub fn payload(&self) -> TelemetryPayload {
+        let snapshot = self.stats.snapshot();
+        TelemetryPayload {
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            uptime_secs: snapshot.uptime_secs,
+            command_counts: snapshot.commands,
+        }
+    }
+
+    pub fn preview(&self) -> TelemetryPreview {
+        TelemetryPreview {
+            enabled: self.settings.enabled,
+            endpoint: self.settings.endpoint.clone(),
+            payload: self.payload(),
+        }
+    }
+
+    /// Sends the payload on an interval; returns immediately unless enabled with an endpoint
+    pub async fn run(&self) {
+        let (true, Some(endpoint)) = (self.settings.enabled, self.settings.endpoint.as_deref())
+        else {
+            return;
+        };
+
+        log::info!("Telemetry enabled, reporting to {}", endpoint);
+        let client = reqwest::Client::new();
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.settings.interval_hours.max(1) * 3600,
+        ));
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            let payload = self.payload();
+            match client.post(endpoint).json(&payload).send().await {
+                Ok(response) if response.status().is_success() => {
+                    log::debug!("Telemetry report sent");
+                }
+                Ok(response) => log::warn!("Telemetry endpoint returned {}", response.status()),
+                Err(e) => log::warn!("Telemetry report failed: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Accessibility name of the UI element under the cursor, for spoken hover feedback.
+
+/// Whether this platform can report hovered element names
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", windows));
+
+/// AT-SPI lives on D-Bus and needs a client we don't ship yet
+#[cfg(target_os = "linux")]
+pub fn hovered_element_name() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+pub fn hovered_element_name() -> Option<String> {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::string::CFString;
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXUIElementCreateSystemWide() -> *const c_void;
+        fn AXUIElementCopyElementAtPosition(
+            application: *const c_void,
+            x: f32,
+            y: f32,
+            element: *mut *const c_void,
+        ) -> i32;
+        fn AXUIElementCopyAttributeValue(
+            element: *const c_void,
+            attribute: *const c_void,
+            value: *mut *const c_void,
+        ) -> i32;
+        fn CGEventCreate(source: *const c_void) -> *const c_void;
+        fn CGEventGetLocation(event: *const c_void) -> CGPoint;
+    }
+
+    const AX_SUCCESS: i32 = 0;
+    const NAME_ATTRIBUTES: [&str; 3] = ["AXTitle", "AXDescription", "AXValue"];
+
+    unsafe {
+        let event = CGEventCreate(std::ptr::null());
+        if event.is_null() {
+            return None;
+        }
+        let location = CGEventGetLocation(event);
+        drop(CFType::wrap_under_create_rule(event));
+
+        let system = CFType::wrap_under_create_rule(AXUIElementCreateSystemWide());
+        let mut element = std::ptr::null();
+        let status = AXUIElementCopyElementAtPosition(
+            system.as_CFTypeRef(),
+            location.x as f32,
+            location.y as f32,
+            &mut element,
+        );
+        if status != AX_SUCCESS || element.is_null() {
+            return None;
+        }
+        let element = CFType::wrap_under_create_rule(element);
+
+        NAME_ATTRIBUTES.iter().find_map(|attribute| {
+            let attribute = CFString::from_static_string(attribute);
+            let mut value = std::ptr::null();
+            let status = AXUIElementCopyAttributeValue(
+                element.as_CFTypeRef(),
+                attribute.as_CFTypeRef(),
+                &mut value,
+            );
+            if status != AX_SUCCESS || value.is_null() {
+                return None;
+            }
+            let text = CFType::wrap_under_create_rule(value)
+                .downcast::<CFString>()?
+                .to_string();
+            (!text.trim().is_empty()).then_some(text)
+        })
+    }
+}
+
+#[cfg(windows)]
+pub fn hovered_element_name() -> Option<String> {
+    use std::cell::OnceCell;
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+    };
+    use windows::Win32::UI::Accessibility::{CUIAutomation, IUIAutomation};
+    use windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+    thread_local! {
+        static AUTOMATION: OnceCell<Option<IUIAutomation>> = const { OnceCell::new() };
+    }
+
+    AUTOMATION.with(|cell| {
+        let automation = cell
+            .get_or_init(|| unsafe {
+                CoInitializeEx(None, COINIT_MULTITHREADED).ok()?;
+                CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER).ok()
+            })
+            .as_ref()?;
+        unsafe {
+            let mut point = POINT::default();
+            GetCursorPos(&mut point).ok()?;
+            let name = automation
+                .ElementFromPoint(point)
+                .ok()?
+                .CurrentName()
+                .ok()?;
+            let text = name.to_string();
+            (!text.trim().is_empty()).then_some(text)
+        }
+    })
+}
@@ -0,0 +1,175 @@
+use crate::domain::models::{CaretDirection, CaretUnit, ModifierKeys};
+
+/// One key pressed with a set of modifiers held
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    pub key: &'static str,
+    pub modifiers: ModifierKeys,
+}
+
+/// Text-editing shortcuts differ mainly between macOS and everyone else
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    MacOs,
+    Pc,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Pc
+        }
+    }
+
+    /// Modifier that jumps by word: Option on macOS, Ctrl elsewhere
+    fn word(self) -> ModifierKeys {
+        match self {
+            Platform::MacOs => modifiers(false, true, false),
+            Platform::Pc => modifiers(true, false, false),
+        }
+    }
+
+    /// Modifier for app-level shortcuts such as select all: Cmd on macOS, Ctrl elsewhere
+    fn command(self) -> ModifierKeys {
+        match self {
+            Platform::MacOs => ModifierKeys {
+                meta: true,
+                ..ModifierKeys::default()
+            },
+            Platform::Pc => modifiers(true, false, false),
+        }
+    }
+}
+
+pub fn select_all(platform: Platform) -> Vec<Chord> {
+    vec![chord("a", platform.command())]
+}
+
+/// Jumps to the start of the word, then extends to its end
+pub fn select_word(platform: Platform) -> Vec<Chord> {
+    [
+        move_caret(platform, CaretDirection::Left, CaretUnit::Word, false),
+        move_caret(platform, CaretDirection::Right, CaretUnit::Word, true),
+    ]
+    .concat()
+}
+
+/// Jumps to the start of the line, then extends to its end
+pub fn select_line(platform: Platform) -> Vec<Chord> {
+    [
+        move_caret(platform, CaretDirection::Left, CaretUnit::Line, false),
+        move_caret(platform, CaretDirection::Right, CaretUnit::Line, true),
+    ]
+    .concat()
+}
+
+/// `extend` holds Shift so the selection grows with the caret
+pub fn move_caret(
+    platform: Platform,
+    direction: CaretDirection,
+    unit: CaretUnit,
+    extend: bool,
+) -> Vec<Chord> {
+    let arrow = arrow_key(direction);
+    let horizontal = matches!(direction, CaretDirection::Left | CaretDirection::Right);
+    let towards_start = matches!(direction, CaretDirection::Left | CaretDirection::Up);
+
+    let mut chord = match (unit, platform) {
+        (CaretUnit::Char, _) => chord(arrow, ModifierKeys::default()),
+        // Vertically, the word modifier moves by paragraph on both platforms
+        (CaretUnit::Word, _) => chord(arrow, platform.word()),
+        (CaretUnit::Line, _) if !horizontal => chord(arrow, ModifierKeys::default()),
+        (CaretUnit::Line, Platform::MacOs) => chord(arrow, platform.command()),
+        (CaretUnit::Line, Platform::Pc) => chord(
+            if towards_start { "Home" } else { "End" },
+            ModifierKeys::default(),
+        ),
+        (CaretUnit::Document, Platform::MacOs) => chord(
+            if towards_start {
+                "ArrowUp"
+            } else {
+                "ArrowDown"
+            },
+            platform.command(),
+        ),
+        (CaretUnit::Document, Platform::Pc) => chord(
+            if towards_start { "Home" } else { "End" },
+            platform.command(),
+        ),
+    };
+    chord.modifiers.shift = extend;
+    vec![chord]
+}
+
+fn arrow_key(direction: CaretDirection) -> &'static str {
+    match direction {
+        CaretDirection::Left => "ArrowLeft",
+        CaretDirection::Right => "ArrowRight",
+        CaretDirection::Up => "ArrowUp",
+        CaretDirection::Down => "ArrowDown",
+    }
+}
+
+fn chord(key: &'static str, modifiers: ModifierKeys) -> Chord {
+    Chord { key, modifiers }
+}
+
+fn modifiers(ctrl: bool, alt: bool, shift: bool) -> ModifierKeys {
+    ModifierKeys {
+        ctrl,
+        alt,
+        shift,
+        meta: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_all_uses_platform_command_key() {
+        assert!(select_all(Platform::MacOs)[0].modifiers.meta);
+        assert!(select_all(Platform::Pc)[0].modifiers.ctrl);
+    }
+
+    #[test]
+    fn test_extend_word_right() {
+        let pc = move_caret(Platform::Pc, CaretDirection::Right, CaretUnit::Word, true);
+        assert_eq!(pc[0].key, "ArrowRight");
+        assert!(pc[0].modifiers.ctrl && pc[0].modifiers.shift);
+
+        let mac = move_caret(
+            Platform::MacOs,
+            CaretDirection::Right,
+            CaretUnit::Word,
+            true,
+        );
+        assert!(mac[0].modifiers.alt && mac[0].modifiers.shift && !mac[0].modifiers.ctrl);
+    }
+
+    #[test]
+    fn test_line_ends() {
+        let pc = move_caret(Platform::Pc, CaretDirection::Left, CaretUnit::Line, false);
+        assert_eq!(pc[0], chord("Home", ModifierKeys::default()));
+
+        let mac = move_caret(
+            Platform::MacOs,
+            CaretDirection::Left,
+            CaretUnit::Line,
+            false,
+        );
+        assert_eq!(mac[0].key, "ArrowLeft");
+        assert!(mac[0].modifiers.meta);
+    }
+
+    #[test]
+    fn test_select_line_is_jump_then_extend() {
+        let chords = select_line(Platform::Pc);
+        assert_eq!(chords.len(), 2);
+        assert_eq!(chords[1].key, "End");
+        assert!(chords[1].modifiers.shift);
+    }
+}
@@ -0,0 +1,222 @@
+//! Accessibility cursor scale, where the platform exposes one. `1.0` is the default size.
+
+use anyhow::Result;
+
+#[cfg(target_os = "linux")]
+pub use gnome::{current_scale, set_scale};
+
+#[cfg(target_os = "macos")]
+pub use macos::{current_scale, set_scale};
+
+#[cfg(windows)]
+pub use win::{current_scale, set_scale};
+
+/// GNOME keeps the size in pixels; other desktops aren't supported yet
+#[cfg(target_os = "linux")]
+mod gnome {
+    use super::Result;
+    use anyhow::{anyhow, Context};
+    use std::process::Command;
+
+    const SCHEMA: &str = "org.gnome.desktop.interface";
+    const KEY: &str = "cursor-size";
+    const BASE_SIZE: f64 = 24.0;
+
+    pub fn current_scale() -> Result<f64> {
+        let output = Command::new("gsettings")
+            .args(["get", SCHEMA, KEY])
+            .output()
+            .context("gsettings is not available")?;
+        let size: f64 = String::from_utf8_lossy(&output.stdout).trim().parse()?;
+        Ok(size / BASE_SIZE)
+    }
+
+    pub fn set_scale(scale: f64) -> Result<()> {
+        let size = (BASE_SIZE * scale).round() as u32;
+        let status = Command::new("gsettings")
+            .args(["set", SCHEMA, KEY, &size.to_string()])
+            .status()
+            .context("gsettings is not available")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("gsettings exited with {}", status))
+        }
+    }
+}
+
+/// The same private CoreGraphics calls the Accessibility pane uses, applied live
+///
+/// They're looked up at runtime so a macOS without them falls back to the Accessibility
+/// preference, which only takes effect once the system rereads it.
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::Result;
+    use anyhow::{anyhow, Context};
+    use std::ffi::{c_char, c_int, c_void, CString};
+    use std::process::Command;
+
+    const CORE_GRAPHICS: &str = "/System/Library/Frameworks/CoreGraphics.framework/CoreGraphics";
+    const RTLD_LAZY: c_int = 1;
+    const PREFERENCES: &str = "com.apple.universalaccess";
+    const PREFERENCE_KEY: &str = "mouseDriverCursorSize";
+
+    type MainConnection = unsafe extern "C" fn() -> i32;
+    type GetCursorScale = unsafe extern "C" fn(connection: i32, scale: *mut f32) -> i32;
+    type SetCursorScale = unsafe extern "C" fn(connection: i32, scale: f32) -> i32;
+
+    extern "C" {
+        fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    pub fn current_scale() -> Result<f64> {
+        let Some((connection, get)) = (unsafe { lookup::<GetCursorScale>("CGSGetCursorScale") })
+        else {
+            return preference_scale();
+        };
+        let mut scale = 1.0f32;
+        let status = unsafe { get(connection, &mut scale) };
+        if status == 0 {
+            Ok(scale as f64)
+        } else {
+            Err(anyhow!("CGSGetCursorScale failed: {}", status))
+        }
+    }
+
+    pub fn set_scale(scale: f64) -> Result<()> {
+        let Some((connection, set)) = (unsafe { lookup::<SetCursorScale>("CGSSetCursorScale") })
+        else {
+            return set_preference_scale(scale);
+        };
+        let status = unsafe { set(connection, scale as f32) };
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("CGSSetCursorScale failed: {}", status))
+        }
+    }
+
+    /// The main connection and the named function, if this macOS still exports both
+    unsafe fn lookup<F: Copy>(symbol: &str) -> Option<(i32, F)> {
+        let path = CString::new(CORE_GRAPHICS).ok()?;
+        let handle = dlopen(path.as_ptr(), RTLD_LAZY);
+        if handle.is_null() {
+            return None;
+        }
+        let resolve = |name: &str| {
+            let name = CString::new(name).ok()?;
+            let function = dlsym(handle, name.as_ptr());
+            (!function.is_null()).then_some(function)
+        };
+        let main_connection: MainConnection = std::mem::transmute(resolve("CGSMainConnectionID")?);
+        let function = resolve(symbol)?;
+        Some((main_connection(), std::mem::transmute_copy(&function)))
+    }
+
+    fn preference_scale() -> Result<f64> {
+        let output = Command::new("defaults")
+            .args(["read", PREFERENCES, PREFERENCE_KEY])
+            .output()
+            .context("defaults is not available")?;
+        // Unset until the size has been changed once
+        if !output.status.success() {
+            return Ok(1.0);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().parse()?)
+    }
+
+    fn set_preference_scale(scale: f64) -> Result<()> {
+        let status = Command::new("defaults")
+            .args(["write", PREFERENCES, PREFERENCE_KEY, "-float"])
+            .arg(scale.to_string())
+            .status()
+            .context("defaults is not available")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("defaults exited with {}", status))
+        }
+    }
+}
+
+/// Windows 10+ "Make the cursor bigger" setting, in pixels from 32 to 256
+///
+/// Builds without the SPI actions keep the size in the registry, which `SPI_SETCURSORS`
+/// reloads the cursors from.
+#[cfg(windows)]
+mod win {
+    use super::Result;
+    use windows::core::w;
+    use windows::Win32::System::Registry::{
+        RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_DWORD, RRF_RT_REG_DWORD,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPIF_SENDCHANGE, SPI_SETCURSORS, SYSTEM_PARAMETERS_INFO_ACTION,
+    };
+
+    const SPI_GETCURSORSIZE: SYSTEM_PARAMETERS_INFO_ACTION = SYSTEM_PARAMETERS_INFO_ACTION(0x2028);
+    const SPI_SETCURSORSIZE: SYSTEM_PARAMETERS_INFO_ACTION = SYSTEM_PARAMETERS_INFO_ACTION(0x2029);
+    const BASE_SIZE: f64 = 32.0;
+
+    pub fn current_scale() -> Result<f64> {
+        let mut size = 0u32;
+        let queried = unsafe {
+            SystemParametersInfoW(
+                SPI_GETCURSORSIZE,
+                0,
+                Some(&mut size as *mut u32 as *mut _),
+                Default::default(),
+            )
+        };
+        if queried.is_err() {
+            size = registry_size()?;
+        }
+        Ok(size as f64 / BASE_SIZE)
+    }
+
+    pub fn set_scale(scale: f64) -> Result<()> {
+        let size = (BASE_SIZE * scale).round() as usize;
+        let applied = unsafe {
+            SystemParametersInfoW(SPI_SETCURSORSIZE, 0, Some(size as *mut _), SPIF_SENDCHANGE)
+        };
+        if applied.is_err() {
+            set_registry_size(size as u32)?;
+        }
+        Ok(())
+    }
+
+    /// Unset until the size has been changed once
+    fn registry_size() -> Result<u32> {
+        let mut size = 0u32;
+        let mut len = std::mem::size_of::<u32>() as u32;
+        let read = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                w!("Control Panel\\Cursors"),
+                w!("CursorBaseSize"),
+                RRF_RT_REG_DWORD,
+                None,
+                Some(&mut size as *mut u32 as *mut _),
+                Some(&mut len as *mut u32),
+            )
+        };
+        Ok(if read.is_ok() { size } else { BASE_SIZE as u32 })
+    }
+
+    fn set_registry_size(size: u32) -> Result<()> {
+        unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                w!("Control Panel\\Cursors"),
+                w!("CursorBaseSize"),
+                REG_DWORD.0,
+                Some(&size as *const u32 as *const _),
+                std::mem::size_of::<u32>() as u32,
+            )
+            .ok()?;
+            SystemParametersInfoW(SPI_SETCURSORS, 0, None, SPIF_SENDCHANGE)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! Notifications shown to the person at the desk, for things done to this machine remotely
+
+/// PowerShell's AUMID, borrowed so toasts show without registering a Start menu shortcut
+#[cfg(windows)]
+pub const TOAST_APP_ID: &str =
+    "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+/// Shows `title` and `body` as a desktop notification without waiting for it
+///
+/// Must be called within the Tokio runtime, which reaps the notifier process.
+pub fn show(title: &str, body: &str) {
+    if let Err(e) = platform::show(title, body) {
+        log::warn!("Failed to show a notification at the desk: {}", e);
+    }
+}
+
+/// Text from the network must not be able to add markup
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The freedesktop notification daemon, which reads the body as markup
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::escape_xml;
+
+    pub fn show(title: &str, body: &str) -> std::io::Result<()> {
+        tokio::process::Command::new("notify-send")
+            .args(["--app-name=PointZerver", "--urgency=critical", title])
+            .arg(escape_xml(body))
+            .spawn()
+            .map(drop)
+    }
+}
+
+/// The text goes in as script arguments, so it can't break out of the AppleScript
+#[cfg(target_os = "macos")]
+mod platform {
+    pub fn show(title: &str, body: &str) -> std::io::Result<()> {
+        tokio::process::Command::new("osascript")
+            .args([
+                "-e",
+                "on run argv",
+                "-e",
+                "display notification (item 2 of argv) with title (item 1 of argv)",
+                "-e",
+                "end run",
+                title,
+                body,
+            ])
+            .spawn()
+            .map(drop)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{escape_xml, TOAST_APP_ID};
+    use windows::core::HSTRING;
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+
+    pub fn show(title: &str, body: &str) -> windows::core::Result<()> {
+        let xml = format!(
+            concat!(
+                r#"<toast><visual><binding template="ToastGeneric">"#,
+                "<text>{}</text><text>{}</text>",
+                "</binding></visual></toast>"
+            ),
+            escape_xml(title),
+            escape_xml(body),
+        );
+        let document = XmlDocument::new()?;
+        document.LoadXml(&HSTRING::from(xml))?;
+        let toast = ToastNotification::CreateToastNotification(&document)?;
+        ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(TOAST_APP_ID))?
+            .Show(&toast)
+    }
+}
@@ -0,0 +1,112 @@
+//! Desktop-level shortcuts that no single key chord reaches on every platform
+
+use crate::domain::config::settings::CornerAction;
+use anyhow::Result;
+
+#[cfg(target_os = "linux")]
+pub use linux::perform;
+
+#[cfg(target_os = "macos")]
+pub use macos::perform;
+
+#[cfg(windows)]
+pub use win::perform;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    use anyhow::{anyhow, Context};
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("{} is not available", program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} exited with {}", program, status))
+    }
+}
+
+/// Super opens the overview on GNOME and KDE; locking goes through logind
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{run, CornerAction, Result};
+
+    pub fn perform(action: CornerAction) -> Result<()> {
+        match action {
+            CornerAction::MissionControl => run("xdotool", &["key", "super"]),
+            CornerAction::ShowDesktop => run("xdotool", &["key", "super+d"]),
+            CornerAction::Lock => run("loginctl", &["lock-session"]),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::{run, CornerAction, Result};
+
+    const MISSION_CONTROL: &str =
+        "/System/Applications/Mission Control.app/Contents/MacOS/Mission Control";
+
+    pub fn perform(action: CornerAction) -> Result<()> {
+        match action {
+            CornerAction::MissionControl => run(MISSION_CONTROL, &[]),
+            // Mission Control takes the view to open as an argument; 1 is the desktop
+            CornerAction::ShowDesktop => run(MISSION_CONTROL, &["1"]),
+            CornerAction::Lock => run(
+                "osascript",
+                &[
+                    "-e",
+                    "tell application \"System Events\" to keystroke \"q\" using {control down, command down}",
+                ],
+            ),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use super::{CornerAction, Result};
+    use crate::input::signature::INJECTED_EVENT_SIGNATURE;
+    use windows::Win32::System::Shutdown::LockWorkStation;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
+        VIRTUAL_KEY, VK_D, VK_LWIN, VK_TAB,
+    };
+
+    pub fn perform(action: CornerAction) -> Result<()> {
+        match action {
+            CornerAction::MissionControl => win_chord(VK_TAB),
+            CornerAction::ShowDesktop => win_chord(VK_D),
+            // Windows refuses injected Win+L
+            CornerAction::Lock => unsafe { Ok(LockWorkStation()?) },
+        }
+    }
+
+    /// Presses `key` with the Windows key held, all in one `SendInput` batch
+    fn win_chord(key: VIRTUAL_KEY) -> Result<()> {
+        let inputs = [
+            keyboard(VK_LWIN, KEYBD_EVENT_FLAGS(0)),
+            keyboard(key, KEYBD_EVENT_FLAGS(0)),
+            keyboard(key, KEYEVENTF_KEYUP),
+            keyboard(VK_LWIN, KEYEVENTF_KEYUP),
+        ];
+        unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+        Ok(())
+    }
+
+    fn keyboard(key: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: key,
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: INJECTED_EVENT_SIGNATURE,
+                },
+            },
+        }
+    }
+}
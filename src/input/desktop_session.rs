@@ -0,0 +1,169 @@
+//! Whether the login session this process runs in is attached to a display
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    Active,
+    /// Attached but showing the lock screen, which injected input cannot reach
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Locked,
+    /// Not attached to the console or a remote client; Windows drops `SendInput` silently
+    Disconnected,
+}
+
+/// Reports the current state, then every change, until the watch fails
+///
+/// Returns immediately on platforms without observable sessions.
+#[cfg(windows)]
+pub fn watch(on_change: impl FnMut(SessionState) + 'static) {
+    if let Err(e) = windows_session::watch(Box::new(on_change)) {
+        log::warn!("Desktop session monitoring unavailable: {}", e);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn watch(_on_change: impl FnMut(SessionState) + 'static) {}
+
+#[cfg(windows)]
+mod windows_session {
+    use super::SessionState;
+    use std::cell::RefCell;
+    use windows::core::{w, PWSTR};
+    use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::RemoteDesktop::{
+        WTSConnectState, WTSDisconnected, WTSFreeMemory, WTSQuerySessionInformationW,
+        WTSRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION, WTS_CONNECTSTATE_CLASS,
+        WTS_CURRENT_SERVER_HANDLE, WTS_CURRENT_SESSION,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, HMENU,
+        HWND_MESSAGE, MSG, WINDOW_EX_STYLE, WINDOW_STYLE, WM_WTSSESSION_CHANGE, WNDCLASSW,
+        WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+    };
+
+    type Callback = Box<dyn FnMut(SessionState)>;
+
+    struct Watcher {
+        on_change: Callback,
+        locked: bool,
+        last: Option<SessionState>,
+    }
+
+    impl Watcher {
+        fn refresh(&mut self) {
+            let state = if is_disconnected() {
+                SessionState::Disconnected
+            } else if self.locked {
+                SessionState::Locked
+            } else {
+                SessionState::Active
+            };
+            if self.last != Some(state) {
+                self.last = Some(state);
+                (self.on_change)(state);
+            }
+        }
+    }
+
+    thread_local! {
+        static WATCHER: RefCell<Option<Watcher>> = const { RefCell::new(None) };
+    }
+
+    /// Runs a message-only window registered for this session's WTS notifications
+    pub fn watch(on_change: Callback) -> windows::core::Result<()> {
+        WATCHER.with(|watcher| {
+            let mut watcher = watcher.borrow_mut();
+            let watcher = watcher.insert(Watcher {
+                on_change,
+                locked: false,
+                last: None,
+            });
+            watcher.refresh();
+        });
+
+        unsafe {
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(window_proc),
+                lpszClassName: w!("PointZerverSessionWatch"),
+                ..Default::default()
+            };
+            if RegisterClassW(&class) == 0 {
+                return Err(windows::core::Error::from_win32());
+            }
+            let hwnd = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class.lpszClassName,
+                w!(""),
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                HMENU::default(),
+                HINSTANCE::default(),
+                None,
+            );
+            if hwnd.0 == 0 {
+                return Err(windows::core::Error::from_win32());
+            }
+            WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION)?;
+
+            let mut msg = MSG::default();
+            // GetMessageW returns -1 on error and 0 on WM_QUIT
+            while GetMessageW(&mut msg, hwnd, 0, 0).0 > 0 {
+                DispatchMessageW(&msg);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_WTSSESSION_CHANGE {
+            let change = wparam.0 as u32;
+            WATCHER.with(|watcher| {
+                if let Some(watcher) = watcher.borrow_mut().as_mut() {
+                    match change {
+                        WTS_SESSION_LOCK => watcher.locked = true,
+                        WTS_SESSION_UNLOCK => watcher.locked = false,
+                        _ => {}
+                    }
+                    // Connect/disconnect notifications don't say which side moved, so
+                    // the connect state is re-queried on every change
+                    watcher.refresh();
+                }
+            });
+            return LRESULT(0);
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    fn is_disconnected() -> bool {
+        unsafe {
+            let mut buffer = PWSTR::null();
+            let mut bytes = 0;
+            if WTSQuerySessionInformationW(
+                WTS_CURRENT_SERVER_HANDLE,
+                WTS_CURRENT_SESSION,
+                WTSConnectState,
+                &mut buffer,
+                &mut bytes,
+            )
+            .is_err()
+            {
+                return false;
+            }
+            let state = *(buffer.0 as *const WTS_CONNECTSTATE_CLASS);
+            WTSFreeMemory(buffer.0.cast());
+            state == WTSDisconnected
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Display power, brightness and night-light control, distinct from putting the whole system
+//! to sleep.
+
+use anyhow::Result;
+
+#[cfg(target_os = "linux")]
+pub use linux::{brightness, set_brightness, set_night_light, set_power};
+
+#[cfg(target_os = "macos")]
+pub use macos::{brightness, set_brightness, set_night_light, set_power};
+
+#[cfg(windows)]
+pub use win::{brightness, set_brightness, set_night_light, set_power};
+
+/// Moves the brightness by `delta` percentage points, stopping at 0 and 100
+///
+/// Returns the new brightness.
+pub fn step_brightness(delta: i16) -> Result<u8> {
+    let percent = (i16::from(brightness()?) + delta).clamp(0, 100) as u8;
+    set_brightness(percent)?;
+    Ok(percent)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    use anyhow::{anyhow, Context};
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("{} is not available", program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} exited with {}", program, status))
+    }
+}
+
+/// DPMS through `xset`; night light through GNOME's color plugin; brightness read from sysfs
+/// and set through logind, which lets the session's user change it without root
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{run, Result};
+    use anyhow::{anyhow, Context};
+    use std::path::{Path, PathBuf};
+
+    const BACKLIGHTS: &str = "/sys/class/backlight";
+
+    pub fn set_power(on: bool) -> Result<()> {
+        run("xset", &["dpms", "force", if on { "on" } else { "off" }])
+    }
+
+    pub fn set_night_light(enabled: bool) -> Result<()> {
+        run(
+            "gsettings",
+            &[
+                "set",
+                "org.gnome.settings-daemon.plugins.color",
+                "night-light-enabled",
+                if enabled { "true" } else { "false" },
+            ],
+        )
+    }
+
+    pub fn brightness() -> Result<u8> {
+        let device = backlight()?;
+        let max = read_level(&device.join("max_brightness"))?;
+        let current = read_level(&device.join("brightness"))?;
+        Ok(((current * 100 + max / 2) / max.max(1)).min(100) as u8)
+    }
+
+    pub fn set_brightness(percent: u8) -> Result<()> {
+        let device = backlight()?;
+        let max = read_level(&device.join("max_brightness"))?;
+        let level = (max * u64::from(percent) + 50) / 100;
+        let name = device
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Unexpected backlight name {}", device.display()))?;
+        run(
+            "busctl",
+            &[
+                "call",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1/session/auto",
+                "org.freedesktop.login1.Session",
+                "SetBrightness",
+                "ssu",
+                "backlight",
+                name,
+                &level.to_string(),
+            ],
+        )
+    }
+
+    /// The first backlight by name; laptops have one, desktop monitors usually none
+    fn backlight() -> Result<PathBuf> {
+        let mut devices: Vec<PathBuf> = std::fs::read_dir(BACKLIGHTS)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        devices.sort();
+        devices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No display backlight to control"))
+    }
+
+    fn read_level(path: &Path) -> Result<u64> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        text.trim()
+            .parse()
+            .with_context(|| format!("Unexpected value in {}", path.display()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::{run, Result};
+    use anyhow::anyhow;
+    use objc::runtime::{Class, Object, BOOL, NO, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::{c_char, c_int, c_void, CString};
+
+    const CORE_BRIGHTNESS: &str =
+        "/System/Library/PrivateFrameworks/CoreBrightness.framework/CoreBrightness";
+    const DISPLAY_SERVICES: &str =
+        "/System/Library/PrivateFrameworks/DisplayServices.framework/DisplayServices";
+    const RTLD_LAZY: c_int = 1;
+
+    type GetBrightness = unsafe extern "C" fn(display: u32, brightness: *mut f32) -> c_int;
+    type SetBrightness = unsafe extern "C" fn(display: u32, brightness: f32) -> c_int;
+
+    extern "C" {
+        fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn CGMainDisplayID() -> u32;
+    }
+
+    /// Sleeping the display leaves the system awake; asserting user activity wakes it
+    pub fn set_power(on: bool) -> Result<()> {
+        if on {
+            run("caffeinate", &["-u", "-t", "1"])
+        } else {
+            run("pmset", &["displaysleepnow"])
+        }
+    }
+
+    /// Night Shift lives in the private CoreBrightness framework, loaded on first use
+    pub fn set_night_light(enabled: bool) -> Result<()> {
+        let path = CString::new(CORE_BRIGHTNESS)?;
+        unsafe {
+            if dlopen(path.as_ptr(), RTLD_LAZY).is_null() {
+                return Err(anyhow!("CoreBrightness is not available"));
+            }
+            let class = Class::get("CBBlueLightClient")
+                .ok_or_else(|| anyhow!("Night Shift is not available"))?;
+            let client: *mut Object = msg_send![class, new];
+            if client.is_null() {
+                return Err(anyhow!("Night Shift is not available"));
+            }
+            let flag: BOOL = if enabled { YES } else { NO };
+            let ok: BOOL = msg_send![client, setEnabled: flag];
+            let _: () = msg_send![client, release];
+            if ok == NO {
+                return Err(anyhow!("Night Shift refused the change"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Built-in panels only; IOKit's brightness parameter is gone on Apple silicon, and the
+    /// private DisplayServices framework is what the brightness keys use
+    pub fn brightness() -> Result<u8> {
+        unsafe {
+            let get: GetBrightness =
+                std::mem::transmute(display_services("DisplayServicesGetBrightness")?);
+            let mut level = 0.0f32;
+            if get(CGMainDisplayID(), &mut level) != 0 {
+                return Err(anyhow!("The main display has no adjustable brightness"));
+            }
+            Ok((level.clamp(0.0, 1.0) * 100.0).round() as u8)
+        }
+    }
+
+    pub fn set_brightness(percent: u8) -> Result<()> {
+        unsafe {
+            let set: SetBrightness =
+                std::mem::transmute(display_services("DisplayServicesSetBrightness")?);
+            if set(CGMainDisplayID(), f32::from(percent) / 100.0) != 0 {
+                return Err(anyhow!("The main display has no adjustable brightness"));
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn display_services(symbol: &str) -> Result<*mut c_void> {
+        let path = CString::new(DISPLAY_SERVICES)?;
+        let handle = dlopen(path.as_ptr(), RTLD_LAZY);
+        if handle.is_null() {
+            return Err(anyhow!("DisplayServices is not available"));
+        }
+        let name = CString::new(symbol)?;
+        let function = dlsym(handle, name.as_ptr());
+        if function.is_null() {
+            return Err(anyhow!("{} is not available", symbol));
+        }
+        Ok(function)
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use super::Result;
+    use crate::input::signature::INJECTED_EVENT_SIGNATURE;
+    use anyhow::anyhow;
+    use windows::Win32::Foundation::{LPARAM, WPARAM};
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_MOVE, MOUSEINPUT,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        PostMessageW, HWND_BROADCAST, SC_MONITORPOWER, WM_SYSCOMMAND,
+    };
+
+    const MONITOR_OFF: isize = 2;
+    const MONITOR_ON: isize = -1;
+
+    /// Recent Windows ignores "monitor on", so waking also nudges the mouse by zero pixels
+    pub fn set_power(on: bool) -> Result<()> {
+        let state = if on { MONITOR_ON } else { MONITOR_OFF };
+        unsafe {
+            PostMessageW(
+                HWND_BROADCAST,
+                WM_SYSCOMMAND,
+                WPARAM(SC_MONITORPOWER as usize),
+                LPARAM(state),
+            )?;
+            if on {
+                let input = INPUT {
+                    r#type: INPUT_MOUSE,
+                    Anonymous: INPUT_0 {
+                        mi: MOUSEINPUT {
+                            dx: 0,
+                            dy: 0,
+                            mouseData: 0,
+                            dwFlags: MOUSEEVENTF_MOVE,
+                            time: 0,
+                            dwExtraInfo: INJECTED_EVENT_SIGNATURE,
+                        },
+                    },
+                };
+                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+            }
+        }
+        Ok(())
+    }
+
+    /// Night light state is an undocumented registry blob with no public API
+    pub fn set_night_light(_enabled: bool) -> Result<()> {
+        Err(anyhow!("Night light control is not supported on Windows"))
+    }
+
+    /// WMI only reaches built-in panels; external monitors need DDC/CI, which this doesn't speak
+    pub fn brightness() -> Result<u8> {
+        let output = powershell(
+            "(Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightness \
+             | Select-Object -First 1).CurrentBrightness",
+        )?;
+        output
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("The display has no adjustable brightness"))
+    }
+
+    pub fn set_brightness(percent: u8) -> Result<()> {
+        powershell(&format!(
+            "Get-CimInstance -Namespace root/WMI -ClassName WmiMonitorBrightnessMethods \
+             | Invoke-CimMethod -MethodName WmiSetBrightness \
+             -Arguments @{{ Timeout = 1; Brightness = {} }}",
+            percent
+        ))?;
+        Ok(())
+    }
+
+    fn powershell(script: &str) -> Result<String> {
+        use std::os::windows::process::CommandExt;
+        use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+        let output = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .creation_flags(CREATE_NO_WINDOW.0)
+            .output()
+            .map_err(|e| anyhow!("powershell is not available: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "The display has no adjustable brightness: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
@@ -0,0 +1,143 @@
+//! Bounds of the window that has keyboard focus, for confining the remote pointer to it
+
+use crate::input::pointer::DesktopBounds;
+
+/// The focused top-level window in desktop coordinates, or `None` when nothing has focus
+#[cfg(target_os = "linux")]
+pub fn focused_window_bounds() -> Option<DesktopBounds> {
+    use std::os::raw::c_int;
+    use x11::xlib;
+
+    /// `XGetInputFocus` reports `None` (0) or this instead of a window when nothing holds focus
+    const POINTER_ROOT: xlib::Window = 1;
+
+    unsafe {
+        let display = xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let root = xlib::XDefaultRootWindow(display);
+        let mut window: xlib::Window = 0;
+        let mut revert_to: c_int = 0;
+        xlib::XGetInputFocus(display, &mut window, &mut revert_to);
+
+        let mut attributes: xlib::XWindowAttributes = std::mem::zeroed();
+        let (mut x, mut y) = (0, 0);
+        let mut child: xlib::Window = 0;
+        let bounds = (window > POINTER_ROOT
+            && window != root
+            && xlib::XGetWindowAttributes(display, window, &mut attributes) != 0
+            && xlib::XTranslateCoordinates(
+                display, window, root, 0, 0, &mut x, &mut y, &mut child,
+            ) != 0)
+            .then(|| DesktopBounds {
+                left: f64::from(x),
+                top: f64::from(y),
+                right: f64::from(x + attributes.width),
+                bottom: f64::from(y + attributes.height),
+            });
+        xlib::XCloseDisplay(display);
+        bounds
+    }
+}
+
+/// Minimized windows keep a placeholder rectangle far off-screen, so they don't count
+#[cfg(windows)]
+pub fn focused_window_bounds() -> Option<DesktopBounds> {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowRect, IsIconic};
+
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.0 == 0 || IsIconic(window).as_bool() {
+            return None;
+        }
+        let mut rect = RECT::default();
+        GetWindowRect(window, &mut rect).ok()?;
+        Some(DesktopBounds {
+            left: f64::from(rect.left),
+            top: f64::from(rect.top),
+            right: f64::from(rect.right),
+            bottom: f64::from(rect.bottom),
+        })
+    }
+}
+
+/// Asks accessibility for the frontmost app's focused window, so it needs the same
+/// permission as injecting input
+#[cfg(target_os = "macos")]
+pub fn focused_window_bounds() -> Option<DesktopBounds> {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::string::CFString;
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXUIElementCreateSystemWide() -> *const c_void;
+        fn AXUIElementCopyAttributeValue(
+            element: *const c_void,
+            attribute: *const c_void,
+            value: *mut *const c_void,
+        ) -> i32;
+        fn AXValueGetValue(value: *const c_void, kind: u32, out: *mut c_void) -> u8;
+    }
+
+    const AX_SUCCESS: i32 = 0;
+    const AX_VALUE_CG_POINT: u32 = 1;
+    const AX_VALUE_CG_SIZE: u32 = 2;
+
+    unsafe fn attribute(element: &CFType, name: &'static str) -> Option<CFType> {
+        let name = CFString::from_static_string(name);
+        let mut value = std::ptr::null();
+        let status =
+            AXUIElementCopyAttributeValue(element.as_CFTypeRef(), name.as_CFTypeRef(), &mut value);
+        (status == AX_SUCCESS && !value.is_null()).then(|| CFType::wrap_under_create_rule(value))
+    }
+
+    unsafe {
+        let system = CFType::wrap_under_create_rule(AXUIElementCreateSystemWide());
+        let app = attribute(&system, "AXFocusedApplication")?;
+        let window = attribute(&app, "AXFocusedWindow")?;
+
+        let mut origin = CGPoint::default();
+        let position = attribute(&window, "AXPosition")?;
+        if AXValueGetValue(
+            position.as_CFTypeRef(),
+            AX_VALUE_CG_POINT,
+            &mut origin as *mut CGPoint as *mut c_void,
+        ) == 0
+        {
+            return None;
+        }
+        let mut size = CGSize::default();
+        let extent = attribute(&window, "AXSize")?;
+        if AXValueGetValue(
+            extent.as_CFTypeRef(),
+            AX_VALUE_CG_SIZE,
+            &mut size as *mut CGSize as *mut c_void,
+        ) == 0
+        {
+            return None;
+        }
+        Some(DesktopBounds {
+            left: origin.x,
+            top: origin.y,
+            right: origin.x + size.width,
+            bottom: origin.y + size.height,
+        })
+    }
+}
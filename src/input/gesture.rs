@@ -0,0 +1,106 @@
+//! Touch gestures played out as frames, for the backends that can inject them
+//!
+//! macOS takes gestures as trackpad events carrying how much each frame zoomed or turned.
+//! Windows takes touch contacts, so every frame is also laid out as finger positions.
+
+use crate::domain::config::ServerConfig;
+use crate::domain::models::TouchGesture;
+
+/// How far a gesture has got, as totals since it began
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureFrame {
+    pub scale: f64,
+    pub degrees: f64,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+impl GestureFrame {
+    pub const START: Self = Self {
+        scale: 1.0,
+        degrees: 0.0,
+        dx: 0.0,
+        dy: 0.0,
+    };
+}
+
+/// Frames `GESTURE_STEP_MS` apart, ending with the whole gesture
+///
+/// Scale changes by the same ratio each frame, so a pinch zooms at an even pace.
+pub fn frames(gesture: &TouchGesture, duration_ms: u64) -> Vec<GestureFrame> {
+    let duration_ms = duration_ms.min(ServerConfig::GESTURE_MAX_MS);
+    let count = (duration_ms / ServerConfig::GESTURE_STEP_MS).max(1);
+    (1..=count)
+        .map(|n| {
+            let t = n as f64 / count as f64;
+            match *gesture {
+                TouchGesture::Pinch { scale } => GestureFrame {
+                    scale: scale.powf(t),
+                    ..GestureFrame::START
+                },
+                TouchGesture::Rotate { degrees } => GestureFrame {
+                    degrees: degrees * t,
+                    ..GestureFrame::START
+                },
+                TouchGesture::Swipe { dx, dy, .. } => GestureFrame {
+                    dx: dx * t,
+                    dy: dy * t,
+                    ..GestureFrame::START
+                },
+            }
+        })
+        .collect()
+}
+
+/// Where each finger is at `frame`, for fingers spaced evenly around a circle at `centre`
+pub fn contacts(frame: &GestureFrame, fingers: u8, centre: (f64, f64)) -> Vec<(f64, f64)> {
+    let fingers = fingers.clamp(1, ServerConfig::GESTURE_MAX_FINGERS);
+    let radius = match fingers {
+        1 => 0.0,
+        _ => ServerConfig::GESTURE_SPREAD_PX / 2.0 * frame.scale,
+    };
+    (0..fingers)
+        .map(|finger| {
+            let angle = std::f64::consts::TAU * f64::from(finger) / f64::from(fingers)
+                + frame.degrees.to_radians();
+            (
+                centre.0 + frame.dx + radius * angle.cos(),
+                centre.1 + frame.dy + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_pinch_spreads_evenly_and_rotation_turns_the_fingers() {
+        let pinch = frames(&TouchGesture::Pinch { scale: 4.0 }, 16);
+        assert_eq!(pinch.len(), 2);
+        assert!((pinch[0].scale - 2.0).abs() < 1e-9);
+        assert_eq!(pinch[1].scale, 4.0);
+        let spread = contacts(&pinch[1], 2, (500.0, 300.0));
+        assert!(close(spread[0], (900.0, 300.0)));
+        assert!(close(spread[1], (100.0, 300.0)));
+
+        let turned = frames(&TouchGesture::Rotate { degrees: 90.0 }, 0);
+        assert_eq!(turned.len(), 1);
+        let fingers = contacts(&turned[0], 2, (0.0, 0.0));
+        assert!(close(fingers[0], (0.0, 100.0)));
+        assert!(close(fingers[1], (0.0, -100.0)));
+
+        let swipe = TouchGesture::Swipe {
+            dx: 0.0,
+            dy: -50.0,
+            fingers: 1,
+        };
+        let end = *frames(&swipe, 100).last().unwrap();
+        assert_eq!(contacts(&end, 1, (10.0, 10.0)), [(10.0, -40.0)]);
+    }
+}
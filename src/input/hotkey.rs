@@ -0,0 +1,106 @@
+//! Local Ctrl+Alt hotkeys, such as the kill switch's Ctrl+Alt+Esc, seen system-wide before
+//! any application
+//!
+//! Only the person at the desk should be able to press them, so injected keys are ignored
+//! where the local input listener can tell them apart (see [`input_tap`]).
+
+use crate::input::input_tap::{self, TapEvent};
+
+/// Tracks the keys held down and spots the press that completes Ctrl+Alt+`key`
+#[derive(Debug)]
+pub struct HotkeyChord {
+    /// Named as `KeyPress` names it
+    key: &'static str,
+    ctrl: bool,
+    alt: bool,
+    key_held: bool,
+}
+
+impl HotkeyChord {
+    pub fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            key_held: false,
+        }
+    }
+
+    /// Records a local input event; true on the key press that completes the chord
+    ///
+    /// Key repeat from holding the key down doesn't count again.
+    pub fn event(&mut self, event: &TapEvent) -> bool {
+        let (held, down) = match event {
+            TapEvent::Modifier {
+                modifier: "ctrl",
+                down,
+            } => (&mut self.ctrl, *down),
+            TapEvent::Modifier {
+                modifier: "alt",
+                down,
+            } => (&mut self.alt, *down),
+            TapEvent::Key { key, down } if key == self.key => (&mut self.key_held, *down),
+            _ => return false,
+        };
+        let pressed = down && !*held;
+        *held = down;
+        pressed && matches!(event, TapEvent::Key { .. }) && self.ctrl && self.alt
+    }
+}
+
+/// Calls `on_press` each time Ctrl+Alt+`key` is pressed, from the local input listener's thread
+pub fn watch(key: &'static str, mut on_press: impl FnMut() + Send + 'static) {
+    let mut chord = HotkeyChord::new(key);
+    input_tap::subscribe(move |event| {
+        if chord.event(event) {
+            on_press();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(down: bool) -> TapEvent {
+        TapEvent::Key {
+            key: "Escape".to_string(),
+            down,
+        }
+    }
+
+    fn modifier(modifier: &'static str, down: bool) -> TapEvent {
+        TapEvent::Modifier { modifier, down }
+    }
+
+    #[test]
+    fn test_escape_with_ctrl_and_alt_held_completes_chord() {
+        let mut chord = HotkeyChord::new("Escape");
+        assert!(!chord.event(&key(true)));
+        chord.event(&key(false));
+
+        chord.event(&modifier("ctrl", true));
+        chord.event(&modifier("alt", true));
+        assert!(chord.event(&key(true)));
+        // Key repeat while Escape stays down
+        assert!(!chord.event(&key(true)));
+        chord.event(&key(false));
+        assert!(chord.event(&key(true)));
+
+        chord.event(&key(false));
+        chord.event(&modifier("alt", false));
+        assert!(!chord.event(&key(true)));
+    }
+
+    #[test]
+    fn test_other_keys_do_not_complete_chord() {
+        let mut chord = HotkeyChord::new("\n");
+        chord.event(&modifier("ctrl", true));
+        chord.event(&modifier("alt", true));
+        assert!(!chord.event(&key(true)));
+        assert!(chord.event(&TapEvent::Key {
+            key: "\n".to_string(),
+            down: true,
+        }));
+    }
+}
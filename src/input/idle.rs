@@ -0,0 +1,74 @@
+//! Time since the last input event seen by the OS, injected or physical
+
+use std::time::Duration;
+
+/// Blocks on an X round trip, so async callers run it on the blocking pool
+#[cfg(target_os = "linux")]
+pub fn time_since_last_input() -> Option<Duration> {
+    use std::sync::Mutex;
+    use x11::{xlib, xss};
+
+    /// Opened on first use and kept, since the presence monitor polls ten times a second
+    struct Connection {
+        display: *mut xlib::Display,
+        info: *mut xss::XScreenSaverInfo,
+    }
+    // Only ever touched under the mutex
+    unsafe impl Send for Connection {}
+
+    static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+    let mut connection = CONNECTION.lock().expect("Idle connection mutex poisoned");
+    unsafe {
+        if connection.is_none() {
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            if display.is_null() {
+                return None;
+            }
+            let info = xss::XScreenSaverAllocInfo();
+            if info.is_null() {
+                xlib::XCloseDisplay(display);
+                return None;
+            }
+            *connection = Some(Connection { display, info });
+        }
+        let Connection { display, info } = connection.as_ref()?;
+        let root = xlib::XRootWindow(*display, xlib::XDefaultScreen(*display));
+        if xss::XScreenSaverQueryInfo(*display, root, *info) == 0 {
+            return None;
+        }
+        Some(Duration::from_millis((**info).idle))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn time_since_last_input() -> Option<Duration> {
+    const HID_SYSTEM_STATE: i32 = 1;
+    const ANY_INPUT_EVENT_TYPE: u32 = u32::MAX;
+
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+    }
+
+    let seconds =
+        unsafe { CGEventSourceSecondsSinceLastEventType(HID_SYSTEM_STATE, ANY_INPUT_EVENT_TYPE) };
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+#[cfg(windows)]
+pub fn time_since_last_input() -> Option<Duration> {
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    unsafe {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        if !GetLastInputInfo(&mut info).as_bool() {
+            return None;
+        }
+        let idle_ms = GetTickCount().wrapping_sub(info.dwTime);
+        Some(Duration::from_millis(idle_ms as u64))
+    }
+}
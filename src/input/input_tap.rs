@@ -0,0 +1,653 @@
+//! Local keyboard and mouse input, seen system-wide by one listener thread shared by the
+//! kill-switch hotkey and macro recording
+//!
+//! X11 reports raw XInput2 events, rdev keeps its macOS callback in a global, and Windows runs
+//! low-level hooks only on the thread that installed them. Windows marks injected events and
+//! X11 sends them from its XTest devices, so both are skipped; macOS can't tell them apart
+//! through rdev, so remote input shows up here too.
+
+use std::sync::{Mutex, Once};
+
+/// One local input event, with keys named as `KeyPress` names them
+#[derive(Debug, Clone, PartialEq)]
+pub enum TapEvent {
+    Key {
+        key: String,
+        down: bool,
+    },
+    /// `ctrl`, `alt`, `shift` or `meta`, as `ModifierPress` names them
+    Modifier {
+        modifier: &'static str,
+        down: bool,
+    },
+    /// Buttons numbered as in `MouseClick`
+    Button {
+        button: u8,
+        down: bool,
+    },
+    /// Where the pointer went, in desktop coordinates
+    MoveTo {
+        x: f64,
+        y: f64,
+    },
+    /// In lines, positive down and right as in `MouseScroll`
+    Wheel {
+        delta_x: f64,
+        delta_y: f64,
+    },
+}
+
+type Subscriber = Box<dyn FnMut(&TapEvent) + Send>;
+
+static SUBSCRIBERS: Mutex<Vec<Subscriber>> = Mutex::new(Vec::new());
+static STARTED: Once = Once::new();
+
+/// Calls `subscriber` with every local input event from now on, starting the listener the
+/// first time
+///
+/// Subscribers run on the listener thread, so they must be quick and must not subscribe.
+pub fn subscribe(subscriber: impl FnMut(&TapEvent) + Send + 'static) {
+    lock_subscribers().push(Box::new(subscriber));
+    STARTED.call_once(|| {
+        let spawned = std::thread::Builder::new()
+            .name("input-tap".to_string())
+            .spawn(|| {
+                if let Err(e) = listen() {
+                    log::warn!("Local input unavailable: {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start the local input listener: {}", e);
+        }
+    });
+}
+
+fn publish(event: TapEvent) {
+    for subscriber in lock_subscribers().iter_mut() {
+        subscriber(&event);
+    }
+}
+
+fn lock_subscribers() -> std::sync::MutexGuard<'static, Vec<Subscriber>> {
+    SUBSCRIBERS.lock().expect("Input tap mutex poisoned")
+}
+
+/// Blocks until listening fails
+#[cfg(target_os = "linux")]
+fn listen() -> anyhow::Result<()> {
+    xi2::listen()
+}
+
+#[cfg(target_os = "macos")]
+fn listen() -> anyhow::Result<()> {
+    rdev::listen(|event| {
+        if let Some(event) = rdev_event::convert(event.event_type) {
+            publish(event);
+        }
+    })
+    .map_err(|e| anyhow::anyhow!("Failed to listen for local input: {:?}", e))
+}
+
+#[cfg(windows)]
+fn listen() -> anyhow::Result<()> {
+    windows_hooks::listen()?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod rdev_event {
+    use super::TapEvent;
+    use rdev::{Button, EventType, Key};
+
+    pub fn convert(event: EventType) -> Option<TapEvent> {
+        let event = match event {
+            EventType::KeyPress(key) => key_event(key, true)?,
+            EventType::KeyRelease(key) => key_event(key, false)?,
+            EventType::ButtonPress(button) => TapEvent::Button {
+                button: button_number(button)?,
+                down: true,
+            },
+            EventType::ButtonRelease(button) => TapEvent::Button {
+                button: button_number(button)?,
+                down: false,
+            },
+            EventType::MouseMove { x, y } => TapEvent::MoveTo { x, y },
+            // rdev counts up as positive
+            EventType::Wheel { delta_x, delta_y } => TapEvent::Wheel {
+                delta_x: delta_x as f64,
+                delta_y: -delta_y as f64,
+            },
+        };
+        Some(event)
+    }
+
+    /// Side buttons come through as macOS numbers them, 3 and 4
+    fn button_number(button: Button) -> Option<u8> {
+        match button {
+            Button::Left => Some(1),
+            Button::Right => Some(2),
+            Button::Middle => Some(3),
+            Button::Unknown(3) => Some(4),
+            Button::Unknown(4) => Some(5),
+            Button::Unknown(_) => None,
+        }
+    }
+
+    fn key_event(key: Key, down: bool) -> Option<TapEvent> {
+        let modifier = match key {
+            Key::ControlLeft | Key::ControlRight => Some("ctrl"),
+            Key::Alt | Key::AltGr => Some("alt"),
+            Key::ShiftLeft | Key::ShiftRight => Some("shift"),
+            Key::MetaLeft | Key::MetaRight => Some("meta"),
+            _ => None,
+        };
+        if let Some(modifier) = modifier {
+            return Some(TapEvent::Modifier { modifier, down });
+        }
+        Some(TapEvent::Key {
+            key: key_name(key)?.to_string(),
+            down,
+        })
+    }
+
+    fn key_name(key: Key) -> Option<&'static str> {
+        let name = match key {
+            Key::KeyA => "a",
+            Key::KeyB => "b",
+            Key::KeyC => "c",
+            Key::KeyD => "d",
+            Key::KeyE => "e",
+            Key::KeyF => "f",
+            Key::KeyG => "g",
+            Key::KeyH => "h",
+            Key::KeyI => "i",
+            Key::KeyJ => "j",
+            Key::KeyK => "k",
+            Key::KeyL => "l",
+            Key::KeyM => "m",
+            Key::KeyN => "n",
+            Key::KeyO => "o",
+            Key::KeyP => "p",
+            Key::KeyQ => "q",
+            Key::KeyR => "r",
+            Key::KeyS => "s",
+            Key::KeyT => "t",
+            Key::KeyU => "u",
+            Key::KeyV => "v",
+            Key::KeyW => "w",
+            Key::KeyX => "x",
+            Key::KeyY => "y",
+            Key::KeyZ => "z",
+            Key::Num0 => "0",
+            Key::Num1 => "1",
+            Key::Num2 => "2",
+            Key::Num3 => "3",
+            Key::Num4 => "4",
+            Key::Num5 => "5",
+            Key::Num6 => "6",
+            Key::Num7 => "7",
+            Key::Num8 => "8",
+            Key::Num9 => "9",
+            Key::Space => " ",
+            Key::Return => "\n",
+            Key::Tab => "\t",
+            Key::Backspace => "\x08",
+            Key::Dot => ".",
+            Key::Comma => ",",
+            Key::SemiColon => ";",
+            Key::Minus => "-",
+            Key::Equal => "=",
+            Key::LeftBracket => "[",
+            Key::RightBracket => "]",
+            Key::Quote => "'",
+            Key::BackSlash => "\\",
+            Key::Slash => "/",
+            Key::LeftArrow => "ArrowLeft",
+            Key::RightArrow => "ArrowRight",
+            Key::UpArrow => "ArrowUp",
+            Key::DownArrow => "ArrowDown",
+            Key::Home => "Home",
+            Key::End => "End",
+            Key::PageUp => "PageUp",
+            Key::PageDown => "PageDown",
+            Key::Escape => "Escape",
+            Key::Insert => "Insert",
+            Key::Delete => "Delete",
+            Key::F1 => "F1",
+            Key::F2 => "F2",
+            Key::F3 => "F3",
+            Key::F4 => "F4",
+            Key::F5 => "F5",
+            Key::F6 => "F6",
+            Key::F7 => "F7",
+            Key::F8 => "F8",
+            Key::F9 => "F9",
+            Key::F10 => "F10",
+            Key::F11 => "F11",
+            Key::F12 => "F12",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+/// Raw XInput2 events from every device, minus those from the XTest devices injection uses
+#[cfg(target_os = "linux")]
+mod xi2 {
+    use super::{publish, TapEvent};
+    use crate::input::signature::XTEST_DEVICE_NAMES;
+    use anyhow::{bail, Result};
+    use std::ffi::{CStr, CString};
+    use x11::{xinput2, xlib};
+
+    pub fn listen() -> Result<()> {
+        unsafe {
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            if display.is_null() {
+                bail!("Failed to open X display");
+            }
+            let result = follow(display);
+            xlib::XCloseDisplay(display);
+            result
+        }
+    }
+
+    unsafe fn follow(display: *mut xlib::Display) -> Result<()> {
+        let extension = CString::new("XInputExtension")?;
+        let (mut opcode, mut event_base, mut error_base) = (0, 0, 0);
+        let found = xlib::XQueryExtension(
+            display,
+            extension.as_ptr(),
+            &mut opcode,
+            &mut event_base,
+            &mut error_base,
+        );
+        let (mut major, mut minor) = (2, 0);
+        if found == 0
+            || xinput2::XIQueryVersion(display, &mut major, &mut minor) != i32::from(xlib::Success)
+        {
+            bail!("X server has no XInput2");
+        }
+        let injected = xtest_devices(display);
+
+        let mut mask = [0u8; (xinput2::XI_LASTEVENT as usize >> 3) + 1];
+        for event in [
+            xinput2::XI_RawKeyPress,
+            xinput2::XI_RawKeyRelease,
+            xinput2::XI_RawButtonPress,
+            xinput2::XI_RawButtonRelease,
+            xinput2::XI_RawMotion,
+        ] {
+            xinput2::XISetMask(&mut mask, event);
+        }
+        let mut event_mask = xinput2::XIEventMask {
+            deviceid: xinput2::XIAllMasterDevices,
+            mask_len: mask.len() as i32,
+            mask: mask.as_mut_ptr(),
+        };
+        let root = xlib::XDefaultRootWindow(display);
+        xinput2::XISelectEvents(display, root, &mut event_mask, 1);
+
+        let mut event: xlib::XEvent = std::mem::zeroed();
+        loop {
+            xlib::XNextEvent(display, &mut event);
+            let cookie = &mut event.generic_event_cookie;
+            if cookie.type_ != xlib::GenericEvent
+                || cookie.extension != opcode
+                || xlib::XGetEventData(display, cookie) == 0
+            {
+                continue;
+            }
+            // Master device events name the physical or XTest slave they came from
+            let raw = &*(cookie.data as *const xinput2::XIRawEvent);
+            let tapped = match injected.contains(&raw.sourceid) {
+                true => None,
+                false => convert(display, root, raw),
+            };
+            xlib::XFreeEventData(display, cookie);
+            if let Some(tapped) = tapped {
+                publish(tapped);
+            }
+        }
+    }
+
+    /// Ids of the XTest slave devices, which carry every event simulated on X11
+    unsafe fn xtest_devices(display: *mut xlib::Display) -> Vec<i32> {
+        let mut count = 0;
+        let devices = xinput2::XIQueryDevice(display, xinput2::XIAllDevices, &mut count);
+        if devices.is_null() {
+            return Vec::new();
+        }
+        let ids = std::slice::from_raw_parts(devices, count as usize)
+            .iter()
+            .filter(|device| {
+                !device.name.is_null()
+                    && XTEST_DEVICE_NAMES
+                        .contains(&CStr::from_ptr(device.name).to_string_lossy().as_ref())
+            })
+            .map(|device| device.deviceid)
+            .collect();
+        xinput2::XIFreeDeviceInfo(devices);
+        ids
+    }
+
+    unsafe fn convert(
+        display: *mut xlib::Display,
+        root: xlib::Window,
+        raw: &xinput2::XIRawEvent,
+    ) -> Option<TapEvent> {
+        match raw.evtype {
+            xinput2::XI_RawKeyPress | xinput2::XI_RawKeyRelease => {
+                let keysym = xlib::XkbKeycodeToKeysym(display, raw.detail as u8, 0, 0);
+                let name = xlib::XKeysymToString(keysym);
+                if name.is_null() {
+                    return None;
+                }
+                let down = raw.evtype == xinput2::XI_RawKeyPress;
+                key_event(&CStr::from_ptr(name).to_string_lossy(), down)
+            }
+            xinput2::XI_RawButtonPress => button_event(raw.detail, true),
+            xinput2::XI_RawButtonRelease => button_event(raw.detail, false),
+            // Raw motion is the device's own delta, so ask where the pointer ended up
+            xinput2::XI_RawMotion => {
+                let (mut root_return, mut child) = (0, 0);
+                let (mut x, mut y, mut win_x, mut win_y, mut mask) = (0, 0, 0, 0, 0);
+                let found = xlib::XQueryPointer(
+                    display,
+                    root,
+                    &mut root_return,
+                    &mut child,
+                    &mut x,
+                    &mut y,
+                    &mut win_x,
+                    &mut win_y,
+                    &mut mask,
+                );
+                (found != 0).then(|| TapEvent::MoveTo {
+                    x: f64::from(x),
+                    y: f64::from(y),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// X11 buttons 4 to 7 are wheel clicks, sent as a press and release each; 8 and 9 are
+    /// back and forward
+    pub fn button_event(detail: i32, down: bool) -> Option<TapEvent> {
+        let wheel = |delta_x: f64, delta_y: f64| match down {
+            true => Some(TapEvent::Wheel { delta_x, delta_y }),
+            false => None,
+        };
+        let button = match detail {
+            1 => 1,
+            2 => 3,
+            3 => 2,
+            4 => return wheel(0.0, -1.0),
+            5 => return wheel(0.0, 1.0),
+            6 => return wheel(-1.0, 0.0),
+            7 => return wheel(1.0, 0.0),
+            8 => 4,
+            9 => 5,
+            _ => return None,
+        };
+        Some(TapEvent::Button { button, down })
+    }
+
+    /// Keys by their unshifted keysym name, as `XKeysymToString` gives it
+    pub fn key_event(keysym: &str, down: bool) -> Option<TapEvent> {
+        let modifier = match keysym {
+            "Control_L" | "Control_R" => Some("ctrl"),
+            "Alt_L" | "Alt_R" | "ISO_Level3_Shift" => Some("alt"),
+            "Shift_L" | "Shift_R" => Some("shift"),
+            "Super_L" | "Super_R" | "Meta_L" | "Meta_R" => Some("meta"),
+            _ => None,
+        };
+        if let Some(modifier) = modifier {
+            return Some(TapEvent::Modifier { modifier, down });
+        }
+        let key = match keysym {
+            "space" => " ",
+            "Return" => "\n",
+            "Tab" => "\t",
+            "BackSpace" => "\x08",
+            "period" => ".",
+            "comma" => ",",
+            "semicolon" => ";",
+            "minus" => "-",
+            "equal" => "=",
+            "bracketleft" => "[",
+            "bracketright" => "]",
+            "apostrophe" => "'",
+            "backslash" => "\\",
+            "slash" => "/",
+            "Left" => "ArrowLeft",
+            "Right" => "ArrowRight",
+            "Up" => "ArrowUp",
+            "Down" => "ArrowDown",
+            "Prior" => "PageUp",
+            "Next" => "PageDown",
+            "Home" | "End" | "Escape" | "Insert" | "Delete" => keysym,
+            _ => {
+                let single = keysym.len() == 1
+                    && keysym
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+                let function = keysym
+                    .strip_prefix('F')
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .is_some_and(|n| (1..=12).contains(&n));
+                if !single && !function {
+                    return None;
+                }
+                keysym
+            }
+        };
+        Some(TapEvent::Key {
+            key: key.to_string(),
+            down,
+        })
+    }
+}
+
+/// Low-level keyboard and mouse hooks, which Windows calls on the thread that installed them
+#[cfg(windows)]
+mod windows_hooks {
+    use super::{publish, TapEvent};
+    use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, DispatchMessageW, GetMessageW, SetWindowsHookExW, HHOOK, KBDLLHOOKSTRUCT,
+        LLKHF_INJECTED, MSG, MSLLHOOKSTRUCT, WHEEL_DELTA, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_KEYDOWN,
+        WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+        WM_MOUSEWHEEL, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN, WM_XBUTTONDOWN, WM_XBUTTONUP,
+    };
+
+    /// `LLMHF_INJECTED` in `MSLLHOOKSTRUCT::flags`
+    const MOUSE_INJECTED: u32 = 0x1;
+
+    pub fn listen() -> windows::core::Result<()> {
+        unsafe {
+            SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard), HINSTANCE::default(), 0)?;
+            SetWindowsHookExW(WH_MOUSE_LL, Some(mouse), HINSTANCE::default(), 0)?;
+            // The hooks only run while this thread pumps messages
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).0 > 0 {
+                DispatchMessageW(&msg);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe extern "system" fn keyboard(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code >= 0 {
+            let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+            let injected = (info.flags & LLKHF_INJECTED).0 != 0;
+            let down = matches!(wparam.0 as u32, WM_KEYDOWN | WM_SYSKEYDOWN);
+            if let (Some(event), false) = (key_event(info.vkCode as u16, down), injected) {
+                publish(event);
+            }
+        }
+        CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+    }
+
+    unsafe extern "system" fn mouse(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code >= 0 {
+            let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+            let injected = info.flags & MOUSE_INJECTED != 0;
+            // The high word of `mouseData` is the wheel delta or which X button it was
+            let high = (info.mouseData >> 16) as u16;
+            let wheel = f64::from(high as i16) / f64::from(WHEEL_DELTA);
+            let x_button = if high == 1 { 4 } else { 5 };
+            let event = match wparam.0 as u32 {
+                WM_MOUSEMOVE => Some(TapEvent::MoveTo {
+                    x: f64::from(info.pt.x),
+                    y: f64::from(info.pt.y),
+                }),
+                WM_LBUTTONDOWN => Some(TapEvent::Button {
+                    button: 1,
+                    down: true,
+                }),
+                WM_LBUTTONUP => Some(TapEvent::Button {
+                    button: 1,
+                    down: false,
+                }),
+                WM_RBUTTONDOWN => Some(TapEvent::Button {
+                    button: 2,
+                    down: true,
+                }),
+                WM_RBUTTONUP => Some(TapEvent::Button {
+                    button: 2,
+                    down: false,
+                }),
+                WM_MBUTTONDOWN => Some(TapEvent::Button {
+                    button: 3,
+                    down: true,
+                }),
+                WM_MBUTTONUP => Some(TapEvent::Button {
+                    button: 3,
+                    down: false,
+                }),
+                WM_XBUTTONDOWN => Some(TapEvent::Button {
+                    button: x_button,
+                    down: true,
+                }),
+                WM_XBUTTONUP => Some(TapEvent::Button {
+                    button: x_button,
+                    down: false,
+                }),
+                // Windows counts away from the user, up, as positive
+                WM_MOUSEWHEEL => Some(TapEvent::Wheel {
+                    delta_x: 0.0,
+                    delta_y: -wheel,
+                }),
+                WM_MOUSEHWHEEL => Some(TapEvent::Wheel {
+                    delta_x: wheel,
+                    delta_y: 0.0,
+                }),
+                _ => None,
+            };
+            if let (Some(event), false) = (event, injected) {
+                publish(event);
+            }
+        }
+        CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+    }
+
+    fn key_event(vk: u16, down: bool) -> Option<TapEvent> {
+        let modifier = match vk {
+            0x10 | 0xA0 | 0xA1 => Some("shift"),
+            0x11 | 0xA2 | 0xA3 => Some("ctrl"),
+            0x12 | 0xA4 | 0xA5 => Some("alt"),
+            0x5B | 0x5C => Some("meta"),
+            _ => None,
+        };
+        if let Some(modifier) = modifier {
+            return Some(TapEvent::Modifier { modifier, down });
+        }
+        let key = match vk {
+            // Letters and digits share their virtual key codes with ASCII
+            0x30..=0x39 => char::from(vk as u8).to_string(),
+            0x41..=0x5A => char::from(vk as u8).to_ascii_lowercase().to_string(),
+            // F1 to F24
+            0x70..=0x87 => format!("F{}", vk - 0x6F),
+            _ => match vk {
+                0x08 => "\x08",
+                0x09 => "\t",
+                0x0D => "\n",
+                0x1B => "Escape",
+                0x20 => " ",
+                0x21 => "PageUp",
+                0x22 => "PageDown",
+                0x23 => "End",
+                0x24 => "Home",
+                0x25 => "ArrowLeft",
+                0x26 => "ArrowUp",
+                0x27 => "ArrowRight",
+                0x28 => "ArrowDown",
+                0x2D => "Insert",
+                0x2E => "Delete",
+                _ => return None,
+            }
+            .to_string(),
+        };
+        Some(TapEvent::Key { key, down })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::xi2::{button_event, key_event};
+    use super::TapEvent;
+
+    fn key(key: &str) -> Option<TapEvent> {
+        Some(TapEvent::Key {
+            key: key.to_string(),
+            down: true,
+        })
+    }
+
+    #[test]
+    fn test_keysyms_are_named_like_key_press() {
+        assert_eq!(key_event("a", true), key("a"));
+        assert_eq!(key_event("7", true), key("7"));
+        assert_eq!(key_event("Return", true), key("\n"));
+        assert_eq!(key_event("Next", true), key("PageDown"));
+        assert_eq!(key_event("F12", true), key("F12"));
+        assert_eq!(key_event("Escape", true), key("Escape"));
+        assert_eq!(
+            key_event("Control_R", false),
+            Some(TapEvent::Modifier {
+                modifier: "ctrl",
+                down: false,
+            })
+        );
+        assert_eq!(key_event("F13", true), None);
+        assert_eq!(key_event("Caps_Lock", true), None);
+    }
+
+    #[test]
+    fn test_x11_buttons_map_to_click_numbers_and_wheel() {
+        assert_eq!(
+            button_event(3, true),
+            Some(TapEvent::Button {
+                button: 2,
+                down: true,
+            })
+        );
+        assert_eq!(
+            button_event(8, false),
+            Some(TapEvent::Button {
+                button: 4,
+                down: false,
+            })
+        );
+        assert_eq!(
+            button_event(4, true),
+            Some(TapEvent::Wheel {
+                delta_x: 0.0,
+                delta_y: -1.0,
+            })
+        );
+        assert_eq!(button_event(4, false), None);
+    }
+}
@@ -1,7 +1,14 @@
 use crate::domain::config::ServerConfig;
-use crate::domain::models::ModifierKeys;
+use crate::domain::models::{MediaKey, ModifierKeys, NamedKey, TouchGesture};
+use crate::input::gesture::{self, GestureFrame};
+use crate::input::modifiers::{Modifier, ModifierEngine, RawKeys};
+use crate::input::pointer;
+use crate::input::scroll_accumulator::ScrollAccumulator;
+use crate::input::signature::INJECTED_EVENT_SIGNATURE;
 use crate::input::InputHandlerTrait;
 use anyhow::Result;
+use objc::runtime::{Class, Object};
+use objc::{msg_send, sel, sel_impl};
 use rdev::{simulate, Button, EventType, Key, SimulateError};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
@@ -9,11 +16,31 @@ use std::time::{Duration, Instant};
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {}
 
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {}
+
 const DRAG_BATCH_INTERVAL_MS: u64 = 16;
+/// Height of a scrolled line in AppKit, which scroll views step by per wheel notch
+const SCROLL_PIXELS_PER_LINE: f64 = 10.0;
+
+// Gesture events aren't public API; these are the fields trackpads fill in, from IOKit's
+// IOHIDEventTypes.h and the CGEventField numbers AppKit reads
+const IOHID_EVENT_TYPE_ROTATION: i64 = 5;
+const IOHID_EVENT_TYPE_ZOOM: i64 = 8;
+const IOHID_EVENT_TYPE_NAVIGATION_SWIPE: i64 = 16;
+const KCG_EVENT_GESTURE_ZOOM_VALUE: u32 = 113;
+const KCG_EVENT_GESTURE_ROTATION_VALUE: u32 = 114;
+const KCG_EVENT_GESTURE_SWIPE_VALUE: u32 = 115;
+const GESTURE_PHASE_BEGAN: i64 = 1;
+const GESTURE_PHASE_CHANGED: i64 = 2;
+const GESTURE_PHASE_ENDED: i64 = 4;
 
 pub struct InputHandlerImpl {
     current_pos: Mutex<Option<(f64, f64)>>,
-    modifier_state: Mutex<ModifierKeys>,
+    modifiers: ModifierEngine,
+    scroll: ScrollAccumulator,
+    /// Sub-pixel remainder of the deltas relative moves carry, which are whole pixels
+    relative: ScrollAccumulator,
     button_state: Mutex<Option<Button>>,
     last_click: Mutex<Option<ClickState>>,
     drag_state: Mutex<DragState>,
@@ -36,7 +63,9 @@ impl InputHandlerImpl {
     pub fn new() -> Result<Self> {
         Ok(Self {
             current_pos: Mutex::new(None),
-            modifier_state: Mutex::new(ModifierKeys::default()),
+            modifiers: ModifierEngine::new(),
+            scroll: ScrollAccumulator::new(SCROLL_PIXELS_PER_LINE),
+            relative: ScrollAccumulator::new(1.0),
             button_state: Mutex::new(None),
             last_click: Mutex::new(None),
             drag_state: Mutex::new(DragState {
@@ -48,9 +77,32 @@ impl InputHandlerImpl {
         })
     }
 
-    #[allow(dead_code)]
+    /// Reads the cursor in the same global, top-left-origin space that events are posted in
+    ///
+    /// AppKit's `NSEvent.mouseLocation` is flipped and relative to the main display's bottom,
+    /// so it can't be mixed with `CGDisplayBounds`.
     fn get_cursor_position() -> Option<(f64, f64)> {
-        None
+        unsafe {
+            #[repr(C)]
+            struct CGPoint {
+                x: f64,
+                y: f64,
+            }
+
+            extern "C" {
+                fn CGEventCreate(source: *const std::ffi::c_void) -> *const std::ffi::c_void;
+                fn CGEventGetLocation(event: *const std::ffi::c_void) -> CGPoint;
+                fn CFRelease(ptr: *const std::ffi::c_void);
+            }
+
+            let event = CGEventCreate(std::ptr::null());
+            if event.is_null() {
+                return None;
+            }
+            let location = CGEventGetLocation(event);
+            CFRelease(event);
+            Some((location.x, location.y))
+        }
     }
 }
 
@@ -77,14 +129,13 @@ impl InputHandlerTrait for InputHandlerImpl {
                 .lock()
                 .expect("Button state mutex poisoned");
 
-            let (new_x, new_y) = if let Some((px, py)) = *pos_opt {
-                (px + x, py + y)
-            } else {
-                (
-                    ServerConfig::FALLBACK_SCREEN_WIDTH / 2.0 + x,
-                    ServerConfig::FALLBACK_SCREEN_HEIGHT / 2.0 + y,
-                )
-            };
+            let bounds = pointer::movement_bounds();
+            let start = pos_opt
+                .or_else(Self::get_cursor_position)
+                .unwrap_or_else(|| bounds.center());
+            // Displays of different sizes leave gaps that would otherwise swallow a drag
+            // crossing between them, until the cursor jumps back to the nearest screen
+            let (new_x, new_y) = pointer::advance_onto_displays(start, x, y, &bounds);
 
             *pos_opt = Some((new_x, new_y));
             (new_x, new_y, button)
@@ -98,6 +149,34 @@ impl InputHandlerTrait for InputHandlerImpl {
         Ok(())
     }
 
+    /// Moves as `mouse_move` does, stamping the event with its delta for games that hide the
+    /// cursor and read motion instead of position
+    async fn mouse_move_relative(&self, dx: f64, dy: f64) -> Result<()> {
+        let dragging = self
+            .button_state
+            .lock()
+            .expect("Button state mutex poisoned")
+            .is_some();
+        if dragging {
+            return self.mouse_move(dx, dy).await;
+        }
+        let (new_x, new_y) = {
+            let mut pos_opt = self
+                .current_pos
+                .lock()
+                .expect("Cursor position mutex poisoned");
+            let bounds = pointer::movement_bounds();
+            let start = pos_opt
+                .or_else(Self::get_cursor_position)
+                .unwrap_or_else(|| bounds.center());
+            let (new_x, new_y) = pointer::advance_onto_displays(start, dx, dy, &bounds);
+            *pos_opt = Some((new_x, new_y));
+            (new_x, new_y)
+        };
+        let (delta_x, delta_y) = self.relative.take(dx, dy);
+        Self::send_relative_move(new_x, new_y, delta_x, delta_y)
+    }
+
     async fn mouse_click(&self, button: u8) -> Result<()> {
         let button_enum = Self::map_button(button);
         let click_state = self.next_click_count(button);
@@ -162,23 +241,15 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn mouse_scroll(&self, delta_x: f64, delta_y: f64) -> Result<()> {
-        if delta_y != 0.0 {
-            send_event(EventType::Wheel {
-                delta_x: 0i64,
-                delta_y: delta_y as i64,
-            })?;
+        let (pixels_x, pixels_y) = self.scroll.take(delta_x, delta_y);
+        if pixels_x == 0 && pixels_y == 0 {
+            return Ok(());
         }
-        if delta_x != 0.0 {
-            send_event(EventType::Wheel {
-                delta_x: delta_x as i64,
-                delta_y: 0i64,
-            })?;
-        }
-        Ok(())
+        Self::send_scroll(pixels_x, pixels_y)
     }
 
     async fn key_press(&self, key: &str, modifiers: &ModifierKeys) -> Result<()> {
-        Self::apply_modifiers(&self.modifier_state, modifiers)?;
+        self.modifiers.apply(self, modifiers)?;
 
         if let Some(key_enum) = string_to_key(key) {
             send_event(EventType::KeyPress(key_enum))?;
@@ -194,66 +265,84 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn modifier_press(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
-            .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = true;
-                send_event(EventType::KeyPress(Key::ControlLeft))?;
-            }
-            "alt" => {
-                state.alt = true;
-                send_event(EventType::KeyPress(Key::Alt))?;
-            }
-            "shift" => {
-                state.shift = true;
-                send_event(EventType::KeyPress(Key::ShiftLeft))?;
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = true;
-                send_event(EventType::KeyPress(Key::MetaLeft))?;
-            }
-            _ => {}
-        }
-        Ok(())
+        self.modifiers.press(self, modifier)
     }
 
     async fn modifier_release(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
-            .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = false;
-                send_event(EventType::KeyRelease(Key::ControlLeft))?;
-            }
-            "alt" => {
-                state.alt = false;
-                send_event(EventType::KeyRelease(Key::Alt))?;
-            }
-            "shift" => {
-                state.shift = false;
-                send_event(EventType::KeyRelease(Key::ShiftLeft))?;
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = false;
-                send_event(EventType::KeyRelease(Key::MetaLeft))?;
-            }
-            _ => {}
+        self.modifiers.release(self, modifier)
+    }
+
+    async fn media_key(&self, key: MediaKey) -> Result<()> {
+        Self::send_media_key(key, true)?;
+        Self::send_media_key(key, false)
+    }
+
+    async fn type_text(&self, text: &str) -> Result<()> {
+        let mut pending: Vec<u16> = Vec::new();
+        for ch in text.chars() {
+            // Apps expect Return and Tab as keys, not as the characters they'd produce
+            let key = match ch {
+                '\n' => Key::Return,
+                '\t' => Key::Tab,
+                '\r' => continue,
+                _ => {
+                    let mut units = [0u16; 2];
+                    pending.extend_from_slice(ch.encode_utf16(&mut units));
+                    continue;
+                }
+            };
+            Self::send_unicode(&std::mem::take(&mut pending))?;
+            send_event(EventType::KeyPress(key))?;
+            send_event(EventType::KeyRelease(key))?;
         }
-        Ok(())
+        Self::send_unicode(&pending)
+    }
+
+    /// Posts the gesture as trackpad events, each carrying how much that frame zoomed or
+    /// turned; apps zoom and rotate around the pointer
+    async fn touch_gesture(&self, gesture: TouchGesture, duration_ms: u64) -> Result<()> {
+        type Change = fn(&GestureFrame, &GestureFrame) -> f64;
+        let (hid_type, field, change): (i64, u32, Change) = match gesture {
+            TouchGesture::Pinch { .. } => (
+                IOHID_EVENT_TYPE_ZOOM,
+                KCG_EVENT_GESTURE_ZOOM_VALUE,
+                |frame, previous| frame.scale / previous.scale - 1.0,
+            ),
+            // Quartz turns counter-clockwise for positive values
+            TouchGesture::Rotate { .. } => (
+                IOHID_EVENT_TYPE_ROTATION,
+                KCG_EVENT_GESTURE_ROTATION_VALUE,
+                |frame, previous| previous.degrees - frame.degrees,
+            ),
+            TouchGesture::Swipe { dx, dy, .. } => return Self::send_swipe(dx, dy),
+        };
+        Self::send_gesture(hid_type, field, GESTURE_PHASE_BEGAN, 0.0)?;
+        let mut previous = GestureFrame::START;
+        for frame in gesture::frames(&gesture, duration_ms) {
+            tokio::time::sleep(Duration::from_millis(ServerConfig::GESTURE_STEP_MS)).await;
+            let value = change(&frame, &previous);
+            Self::send_gesture(hid_type, field, GESTURE_PHASE_CHANGED, value)?;
+            previous = frame;
+        }
+        Self::send_gesture(hid_type, field, GESTURE_PHASE_ENDED, 0.0)
+    }
+
+    fn pointer_position(&self) -> Option<(f64, f64)> {
+        *self
+            .current_pos
+            .lock()
+            .expect("Cursor position mutex poisoned")
     }
 }
 
 impl InputHandlerImpl {
+    /// Back and forward are Quartz's buttons 3 and 4, carried as `Unknown`
     fn map_button(button: u8) -> Button {
         match button {
-            1 => Button::Left,
             2 => Button::Right,
             3 => Button::Middle,
+            4 => Button::Unknown(3),
+            5 => Button::Unknown(4),
             _ => Button::Left,
         }
     }
@@ -266,10 +355,9 @@ impl InputHandlerImpl {
         if let Some(coords) = *pos {
             coords
         } else {
-            let fallback = (
-                ServerConfig::FALLBACK_SCREEN_WIDTH / 2.0,
-                ServerConfig::FALLBACK_SCREEN_HEIGHT / 2.0,
-            );
+            let fallback = Self::get_cursor_position().unwrap_or_else(|| {
+                pointer::onto_displays(pointer::desktop_bounds().center(), &pointer::displays())
+            });
             *pos = Some(fallback);
             fallback
         }
@@ -280,17 +368,16 @@ impl InputHandlerImpl {
         let now = Instant::now();
         let timeout = Duration::from_millis(ServerConfig::DOUBLE_CLICK_TIMEOUT_MS);
 
-        let count = if let Some(previous) = &*last_click {
-            if previous.button == button
-                && now.duration_since(previous.time) <= timeout
-                && previous.count == 1
+        // Counts 1, 2, 3 within the double-click timeout so triple-click selects a paragraph
+        let count = match &*last_click {
+            Some(previous)
+                if previous.button == button
+                    && now.duration_since(previous.time) <= timeout
+                    && previous.count < ServerConfig::MAX_CLICK_COUNT =>
             {
-                2
-            } else {
-                1
+                previous.count + 1
             }
-        } else {
-            1
+            _ => 1,
         };
 
         *last_click = Some(ClickState {
@@ -322,6 +409,7 @@ impl InputHandlerImpl {
             const OTHER_DOWN: u32 = 25;
             const OTHER_UP: u32 = 26;
             const KCG_MOUSE_EVENT_CLICK_STATE: u32 = 1;
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
 
             extern "C" {
                 fn CGEventCreateMouseEvent(
@@ -346,7 +434,8 @@ impl InputHandlerImpl {
                 (Button::Right, false) => (RIGHT_UP, 1u32),
                 (Button::Middle, true) => (OTHER_DOWN, 2u32),
                 (Button::Middle, false) => (OTHER_UP, 2u32),
-                _ => (LEFT_DOWN, 0u32),
+                (Button::Unknown(index), true) => (OTHER_DOWN, u32::from(index)),
+                (Button::Unknown(index), false) => (OTHER_UP, u32::from(index)),
             };
 
             let point = CGPoint {
@@ -360,6 +449,11 @@ impl InputHandlerImpl {
             }
 
             CGEventSetIntegerValueField(event, KCG_MOUSE_EVENT_CLICK_STATE, click_state);
+            CGEventSetIntegerValueField(
+                event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
             CGEventPost(0, event);
             CFRelease(event);
         }
@@ -375,11 +469,15 @@ impl InputHandlerImpl {
                 y: f64,
             }
 
-            let event_type = match button {
-                Some(Button::Left) => 6u32,
-                Some(Button::Right) => 7u32,
-                Some(Button::Middle) => 8u32,
-                _ => 6u32,
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
+
+            const OTHER_DRAGGED: u32 = 27;
+
+            let (event_type, button_index) = match button {
+                Some(Button::Right) => (7u32, 1u32),
+                Some(Button::Middle) => (OTHER_DRAGGED, 2u32),
+                Some(Button::Unknown(index)) => (OTHER_DRAGGED, u32::from(index)),
+                _ => (6u32, 0u32),
             };
 
             extern "C" {
@@ -389,17 +487,27 @@ impl InputHandlerImpl {
                     mouseCursorPosition: CGPoint,
                     mouseButton: u32,
                 ) -> *const std::ffi::c_void;
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
                 fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
                 fn CFRelease(ptr: *const std::ffi::c_void);
             }
 
             let point = CGPoint { x, y };
-            let event = CGEventCreateMouseEvent(std::ptr::null(), event_type, point, 0);
+            let event = CGEventCreateMouseEvent(std::ptr::null(), event_type, point, button_index);
 
             if event.is_null() {
                 return Err(anyhow::anyhow!("Failed to create mouse drag event"));
             }
 
+            CGEventSetIntegerValueField(
+                event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
             CGEventPost(0, event);
             CFRelease(event);
         }
@@ -407,41 +515,299 @@ impl InputHandlerImpl {
         Ok(())
     }
 
-    fn apply_modifiers(state: &Mutex<ModifierKeys>, modifiers: &ModifierKeys) -> Result<()> {
-        let mut state_guard = state.lock().expect("Modifier state mutex poisoned");
+    fn send_relative_move(x: f64, y: f64, delta_x: i64, delta_y: i64) -> Result<()> {
+        unsafe {
+            #[repr(C)]
+            struct CGPoint {
+                x: f64,
+                y: f64,
+            }
+
+            const MOUSE_MOVED: u32 = 5;
+            const KCG_MOUSE_EVENT_DELTA_X: u32 = 4;
+            const KCG_MOUSE_EVENT_DELTA_Y: u32 = 5;
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
 
-        if modifiers.ctrl && !state_guard.ctrl {
-            send_event(EventType::KeyPress(Key::ControlLeft))?;
-            state_guard.ctrl = true;
-        }
-        if modifiers.alt && !state_guard.alt {
-            send_event(EventType::KeyPress(Key::Alt))?;
-            state_guard.alt = true;
-        }
-        if modifiers.shift && !state_guard.shift {
-            send_event(EventType::KeyPress(Key::ShiftLeft))?;
-            state_guard.shift = true;
-        }
-        if modifiers.meta && !state_guard.meta {
-            send_event(EventType::KeyPress(Key::MetaLeft))?;
-            state_guard.meta = true;
+            extern "C" {
+                fn CGEventCreateMouseEvent(
+                    source: *const std::ffi::c_void,
+                    mouseType: u32,
+                    mouseCursorPosition: CGPoint,
+                    mouseButton: u32,
+                ) -> *const std::ffi::c_void;
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
+                fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
+                fn CFRelease(ptr: *const std::ffi::c_void);
+            }
+
+            let event = CGEventCreateMouseEvent(std::ptr::null(), MOUSE_MOVED, CGPoint { x, y }, 0);
+
+            if event.is_null() {
+                return Err(anyhow::anyhow!("Failed to create mouse move event"));
+            }
+
+            CGEventSetIntegerValueField(event, KCG_MOUSE_EVENT_DELTA_X, delta_x);
+            CGEventSetIntegerValueField(event, KCG_MOUSE_EVENT_DELTA_Y, delta_y);
+            CGEventSetIntegerValueField(
+                event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
+            CGEventPost(0, event);
+            CFRelease(event);
         }
 
-        if !modifiers.ctrl && state_guard.ctrl {
-            send_event(EventType::KeyRelease(Key::ControlLeft))?;
-            state_guard.ctrl = false;
+        Ok(())
+    }
+
+    /// Scrolls by whole pixels as a continuous, trackpad-style gesture
+    ///
+    /// Apps scroll continuous events by exactly the pixels given instead of stepping a line
+    /// per notch, so fractions of a line come through smoothly.
+    fn send_scroll(pixels_x: i64, pixels_y: i64) -> Result<()> {
+        unsafe {
+            const KCG_SCROLL_EVENT_UNIT_PIXEL: u32 = 0;
+            const KCG_SCROLL_WHEEL_EVENT_IS_CONTINUOUS: u32 = 88;
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
+
+            extern "C" {
+                fn CGEventCreateScrollWheelEvent2(
+                    source: *const std::ffi::c_void,
+                    units: u32,
+                    wheelCount: u32,
+                    wheel1: i32,
+                    wheel2: i32,
+                    wheel3: i32,
+                ) -> *const std::ffi::c_void;
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
+                fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
+                fn CFRelease(ptr: *const std::ffi::c_void);
+            }
+
+            let clamp = |pixels: i64| pixels.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            let event = CGEventCreateScrollWheelEvent2(
+                std::ptr::null(),
+                KCG_SCROLL_EVENT_UNIT_PIXEL,
+                2,
+                clamp(pixels_y),
+                clamp(pixels_x),
+                0,
+            );
+            if event.is_null() {
+                return Err(anyhow::anyhow!("Failed to create scroll event"));
+            }
+
+            CGEventSetIntegerValueField(event, KCG_SCROLL_WHEEL_EVENT_IS_CONTINUOUS, 1);
+            CGEventSetIntegerValueField(
+                event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
+            CGEventPost(0, event);
+            CFRelease(event);
         }
-        if !modifiers.alt && state_guard.alt {
-            send_event(EventType::KeyRelease(Key::Alt))?;
-            state_guard.alt = false;
+
+        Ok(())
+    }
+
+    fn send_gesture(hid_type: i64, field: u32, phase: i64, value: f64) -> Result<()> {
+        unsafe {
+            const KCG_SEVENT_GESTURE: u32 = 29;
+            const KCG_EVENT_GESTURE_HID_TYPE: u32 = 110;
+            const KCG_EVENT_GESTURE_PHASE: u32 = 132;
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
+
+            extern "C" {
+                fn CGEventCreate(source: *const std::ffi::c_void) -> *const std::ffi::c_void;
+                fn CGEventSetType(event: *const std::ffi::c_void, event_type: u32);
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
+                fn CGEventSetDoubleValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: f64,
+                );
+                fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
+                fn CFRelease(ptr: *const std::ffi::c_void);
+            }
+
+            let event = CGEventCreate(std::ptr::null());
+            if event.is_null() {
+                return Err(anyhow::anyhow!("Failed to create gesture event"));
+            }
+
+            CGEventSetType(event, KCG_SEVENT_GESTURE);
+            CGEventSetIntegerValueField(event, KCG_EVENT_GESTURE_HID_TYPE, hid_type);
+            CGEventSetIntegerValueField(event, KCG_EVENT_GESTURE_PHASE, phase);
+            CGEventSetDoubleValueField(event, field, value);
+            CGEventSetIntegerValueField(
+                event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
+            CGEventPost(0, event);
+            CFRelease(event);
         }
-        if !modifiers.shift && state_guard.shift {
-            send_event(EventType::KeyRelease(Key::ShiftLeft))?;
-            state_guard.shift = false;
+
+        Ok(())
+    }
+
+    /// AppKit's swipes are the back and forward kind, which carry only a direction, so the
+    /// swipe goes whichever way it moved most
+    fn send_swipe(dx: f64, dy: f64) -> Result<()> {
+        const UP: i64 = 1;
+        const DOWN: i64 = 2;
+        const LEFT: i64 = 4;
+        const RIGHT: i64 = 8;
+
+        let direction = match (dx.abs() >= dy.abs(), dx > 0.0, dy > 0.0) {
+            (true, true, _) => RIGHT,
+            (true, false, _) => LEFT,
+            (false, _, true) => DOWN,
+            (false, _, false) => UP,
+        };
+        Self::send_gesture(
+            IOHID_EVENT_TYPE_NAVIGATION_SWIPE,
+            KCG_EVENT_GESTURE_SWIPE_VALUE,
+            GESTURE_PHASE_BEGAN,
+            direction as f64,
+        )?;
+        Self::send_gesture(
+            IOHID_EVENT_TYPE_NAVIGATION_SWIPE,
+            KCG_EVENT_GESTURE_SWIPE_VALUE,
+            GESTURE_PHASE_ENDED,
+            direction as f64,
+        )
+    }
+
+    /// Media keys aren't key codes on macOS but system-defined events, built through AppKit
+    fn send_media_key(key: MediaKey, down: bool) -> Result<()> {
+        unsafe {
+            #[repr(C)]
+            struct NSPoint {
+                x: f64,
+                y: f64,
+            }
+
+            const NS_EVENT_TYPE_SYSTEM_DEFINED: u64 = 14;
+            const NX_SUBTYPE_AUX_CONTROL_BUTTONS: i16 = 8;
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
+
+            extern "C" {
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
+                fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
+            }
+
+            // NX_KEYTYPE_* from IOKit's ev_keymap.h
+            let code: i64 = match key {
+                MediaKey::PlayPause => 16,
+                MediaKey::NextTrack => 17,
+                MediaKey::PrevTrack => 18,
+                MediaKey::VolumeUp => 0,
+                MediaKey::VolumeDown => 1,
+                MediaKey::Mute => 7,
+            };
+            let state: i64 = if down { 0xa } else { 0xb };
+
+            let class =
+                Class::get("NSEvent").ok_or_else(|| anyhow::anyhow!("AppKit is not available"))?;
+            let event: *mut Object = msg_send![class,
+                otherEventWithType: NS_EVENT_TYPE_SYSTEM_DEFINED
+                location: NSPoint { x: 0.0, y: 0.0 }
+                modifierFlags: (state << 8) as u64
+                timestamp: 0.0f64
+                windowNumber: 0i64
+                context: std::ptr::null_mut::<Object>()
+                subtype: NX_SUBTYPE_AUX_CONTROL_BUTTONS
+                data1: (code << 16) | (state << 8)
+                data2: -1i64];
+            if event.is_null() {
+                return Err(anyhow::anyhow!("Failed to create media key event"));
+            }
+
+            // Owned by the NSEvent, so it isn't released here
+            let cg_event: *const std::ffi::c_void = msg_send![event, CGEvent];
+            if cg_event.is_null() {
+                return Err(anyhow::anyhow!("Failed to create media key event"));
+            }
+            CGEventSetIntegerValueField(
+                cg_event,
+                KCG_EVENT_SOURCE_USER_DATA,
+                INJECTED_EVENT_SIGNATURE as i64,
+            );
+            CGEventPost(0, cg_event);
         }
-        if !modifiers.meta && state_guard.meta {
-            send_event(EventType::KeyRelease(Key::MetaLeft))?;
-            state_guard.meta = false;
+
+        Ok(())
+    }
+
+    /// Types `units` through key events carrying a Unicode string, not a key code
+    ///
+    /// Applications read at most 20 UTF-16 units per event, so longer text is split.
+    fn send_unicode(units: &[u16]) -> Result<()> {
+        unsafe {
+            const KCG_EVENT_SOURCE_USER_DATA: u32 = 42;
+            const MAX_UNITS_PER_EVENT: usize = 20;
+
+            extern "C" {
+                fn CGEventCreateKeyboardEvent(
+                    source: *const std::ffi::c_void,
+                    virtualKey: u16,
+                    keyDown: bool,
+                ) -> *const std::ffi::c_void;
+                fn CGEventKeyboardSetUnicodeString(
+                    event: *const std::ffi::c_void,
+                    stringLength: usize,
+                    unicodeString: *const u16,
+                );
+                fn CGEventSetIntegerValueField(
+                    event: *const std::ffi::c_void,
+                    field: u32,
+                    value: i64,
+                );
+                fn CGEventPost(tap: u32, event: *const std::ffi::c_void) -> i32;
+                fn CFRelease(ptr: *const std::ffi::c_void);
+            }
+
+            let mut start = 0;
+            while start < units.len() {
+                let mut end = (start + MAX_UNITS_PER_EVENT).min(units.len());
+                // Keep surrogate pairs in one event
+                if end < units.len() && (0xd800..0xdc00).contains(&units[end - 1]) {
+                    end -= 1;
+                }
+                let chunk = &units[start..end];
+                for key_down in [true, false] {
+                    let event = CGEventCreateKeyboardEvent(std::ptr::null(), 0, key_down);
+                    if event.is_null() {
+                        return Err(anyhow::anyhow!("Failed to create keyboard event"));
+                    }
+                    CGEventKeyboardSetUnicodeString(event, chunk.len(), chunk.as_ptr());
+                    CGEventSetIntegerValueField(
+                        event,
+                        KCG_EVENT_SOURCE_USER_DATA,
+                        INJECTED_EVENT_SIGNATURE as i64,
+                    );
+                    CGEventPost(0, event);
+                    CFRelease(event);
+                }
+                start = end;
+            }
         }
 
         Ok(())
@@ -487,7 +853,76 @@ impl InputHandlerImpl {
     }
 }
 
+impl RawKeys for InputHandlerImpl {
+    type PlatformKey = Key;
+
+    fn modifier_key(modifier: Modifier) -> Key {
+        match modifier {
+            Modifier::Ctrl => Key::ControlLeft,
+            Modifier::Alt => Key::Alt,
+            Modifier::Shift => Key::ShiftLeft,
+            Modifier::Meta => Key::MetaLeft,
+        }
+    }
+
+    fn press_raw(&self, key: Key) -> Result<()> {
+        send_event(EventType::KeyPress(key))
+    }
+
+    fn release_raw(&self, key: Key) -> Result<()> {
+        send_event(EventType::KeyRelease(key))
+    }
+}
+
+fn named_to_key(key: NamedKey) -> Option<Key> {
+    let key = match key {
+        NamedKey::ArrowLeft => Key::LeftArrow,
+        NamedKey::ArrowRight => Key::RightArrow,
+        NamedKey::ArrowUp => Key::UpArrow,
+        NamedKey::ArrowDown => Key::DownArrow,
+        NamedKey::Home => Key::Home,
+        NamedKey::End => Key::End,
+        NamedKey::PageUp => Key::PageUp,
+        NamedKey::PageDown => Key::PageDown,
+        NamedKey::Escape => Key::Escape,
+        NamedKey::Insert => Key::Insert,
+        NamedKey::Delete => Key::Delete,
+        NamedKey::Function(n) => return function_key(n),
+    };
+    Some(key)
+}
+
+fn function_key(n: u8) -> Option<Key> {
+    match n {
+        1 => Some(Key::F1),
+        2 => Some(Key::F2),
+        3 => Some(Key::F3),
+        4 => Some(Key::F4),
+        5 => Some(Key::F5),
+        6 => Some(Key::F6),
+        7 => Some(Key::F7),
+        8 => Some(Key::F8),
+        9 => Some(Key::F9),
+        10 => Some(Key::F10),
+        11 => Some(Key::F11),
+        12 => Some(Key::F12),
+        // rdev stops at F12; Apple's virtual key codes go up to F20
+        13 => Some(Key::Unknown(105)),
+        14 => Some(Key::Unknown(107)),
+        15 => Some(Key::Unknown(113)),
+        16 => Some(Key::Unknown(106)),
+        17 => Some(Key::Unknown(64)),
+        18 => Some(Key::Unknown(79)),
+        19 => Some(Key::Unknown(80)),
+        20 => Some(Key::Unknown(90)),
+        _ => None,
+    }
+}
+
 fn string_to_key(s: &str) -> Option<Key> {
+    if let Some(named) = NamedKey::parse(s) {
+        return named_to_key(named);
+    }
     match s {
         " " => Some(Key::Space),
         "\n" | "\r" => Some(Key::Return),
@@ -1,12 +1,36 @@
+pub mod a11y;
+mod chords;
+pub mod cursor_size;
+pub mod desk_notice;
+pub mod desktop_actions;
+pub mod desktop_session;
+pub mod display;
+pub mod focus;
+pub mod gesture;
+pub mod hotkey;
+pub mod idle;
+pub mod input_tap;
 #[cfg(target_os = "macos")]
 mod macos;
+mod modifiers;
+pub mod plan;
+pub mod pointer;
+pub mod power;
+pub mod scroll_accumulator;
+pub mod signature;
 #[cfg(target_os = "linux")]
 mod unix;
 #[cfg(windows)]
 mod windows;
+pub mod zoom;
 
-use crate::domain::models::{Command, ModifierKeys};
+use crate::domain::config::settings::ScrollSettings;
+use crate::domain::models::{Command, MediaKey, ModifierKeys, TouchGesture};
+#[cfg(windows)]
+use crate::features::service::agent_link::AgentLink;
 use anyhow::Result;
+use plan::InputStep;
+use std::time::Duration;
 
 #[cfg(target_os = "linux")]
 use unix::InputHandlerImpl;
@@ -20,6 +44,11 @@ use windows::InputHandlerImpl;
 /// Handles input commands and delegates to platform-specific implementations
 pub struct InputHandler {
     inner: InputHandlerImpl,
+    /// Applied to scroll deltas before they're injected or forwarded to an agent
+    scroll_multiplier: f64,
+    /// Set when running as a Windows service, which can't inject from session 0
+    #[cfg(windows)]
+    agent: Option<&'static AgentLink>,
 }
 
 impl InputHandler {
@@ -27,25 +56,132 @@ impl InputHandler {
     pub fn new() -> Result<Self> {
         Ok(Self {
             inner: InputHandlerImpl::new()?,
+            scroll_multiplier: 1.0,
+            #[cfg(windows)]
+            agent: None,
+        })
+    }
+
+    /// Scales every scroll by the `scroll` settings' speed, reversed for natural scrolling
+    pub fn set_scroll(&mut self, scroll: &ScrollSettings) {
+        self.scroll_multiplier = match scroll.natural {
+            true => -scroll.speed,
+            false => scroll.speed,
+        };
+    }
+
+    /// Forwards every command to an agent in the user's session instead of injecting here
+    #[cfg(windows)]
+    pub fn via_agent(agent: &'static AgentLink) -> Result<Self> {
+        Ok(Self {
+            agent: Some(agent),
+            ..Self::new()?
         })
     }
 
+    /// False when injection happens in another process, whose session state is its own
+    #[cfg(windows)]
+    pub fn injects_locally(&self) -> bool {
+        self.agent.is_none()
+    }
+
+    #[cfg(not(windows))]
+    pub fn injects_locally(&self) -> bool {
+        true
+    }
+
+    /// Where this process last put the pointer; `None` until it has moved it, or under an agent
+    pub fn pointer_position(&self) -> Option<(f64, f64)> {
+        if !self.injects_locally() {
+            return None;
+        }
+        self.inner.pointer_position()
+    }
+
     /// Processes a command and executes the corresponding input action
     pub async fn handle_command(&self, command: Command) -> Result<()> {
-        match command {
-            Command::MouseMove { x, y } => self.inner.mouse_move(x, y).await,
-            Command::MouseClick { button } => self.inner.mouse_click(button).await,
-            Command::MouseDown { button } => self.inner.mouse_down(button).await,
-            Command::MouseUp { button } => self.inner.mouse_up(button).await,
-            Command::MouseScroll { delta_x, delta_y } => {
+        let command = match command {
+            Command::MouseScroll { delta_x, delta_y } => Command::MouseScroll {
+                delta_x: delta_x * self.scroll_multiplier,
+                delta_y: delta_y * self.scroll_multiplier,
+            },
+            command => command,
+        };
+        #[cfg(windows)]
+        if let Some(agent) = &self.agent {
+            return agent.send(&command).await;
+        }
+        if let Command::ConfineCursor { enabled } = command {
+            pointer::confine_to_focused_window(enabled);
+            return Ok(());
+        }
+        if let Command::ZoomFollow { enabled } = command {
+            zoom::follow_pointer(enabled)?;
+            self.follow_with_zoom();
+            return Ok(());
+        }
+        let steps = plan::plan(&command)
+            .ok_or_else(|| anyhow::anyhow!("Not an input command: {}", command.kind()))?;
+        for step in steps {
+            self.execute(step).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the pointer through the OS's relative motion path, which games reading raw
+    /// mouse input see as real motion
+    ///
+    /// The position isn't tracked here, so hot corners and zoom panning don't follow it. An
+    /// agent is sent an ordinary move.
+    pub async fn move_relative(&self, dx: f64, dy: f64) -> Result<()> {
+        #[cfg(windows)]
+        if let Some(agent) = &self.agent {
+            return agent.send(&Command::MouseMove { x: dx, y: dy }).await;
+        }
+        self.inner.mouse_move_relative(dx, dy).await
+    }
+
+    /// Pans the zoomed view to the pointer while `ZoomFollow` is on; a failed pan isn't fatal
+    fn follow_with_zoom(&self) {
+        if !zoom::following_pointer() {
+            return;
+        }
+        if let Some(position) = self.inner.pointer_position() {
+            if let Err(e) = zoom::pan_to(position) {
+                log::debug!("Failed to pan zoom: {}", e);
+            }
+        }
+    }
+
+    async fn execute(&self, step: InputStep) -> Result<()> {
+        match step {
+            InputStep::MouseMove { dx, dy } => {
+                self.inner.mouse_move(dx, dy).await?;
+                self.follow_with_zoom();
+                Ok(())
+            }
+            InputStep::MouseClick { button } => self.inner.mouse_click(button).await,
+            InputStep::MouseDown { button } => self.inner.mouse_down(button).await,
+            InputStep::MouseUp { button } => self.inner.mouse_up(button).await,
+            InputStep::MouseScroll { delta_x, delta_y } => {
                 self.inner.mouse_scroll(delta_x, delta_y).await
             }
-            Command::KeyPress { key, modifiers } => self.inner.key_press(&key, &modifiers).await,
-            Command::KeyRelease { key, modifiers } => {
+            InputStep::KeyPress { key, modifiers } => self.inner.key_press(&key, &modifiers).await,
+            InputStep::KeyRelease { key, modifiers } => {
                 self.inner.key_release(&key, &modifiers).await
             }
-            Command::ModifierPress { modifier } => self.inner.modifier_press(&modifier).await,
-            Command::ModifierRelease { modifier } => self.inner.modifier_release(&modifier).await,
+            InputStep::ModifierPress { modifier } => self.inner.modifier_press(&modifier).await,
+            InputStep::ModifierRelease { modifier } => self.inner.modifier_release(&modifier).await,
+            InputStep::MediaKey { key } => self.inner.media_key(key).await,
+            InputStep::TypeText { text } => self.inner.type_text(&text).await,
+            InputStep::TouchGesture {
+                gesture,
+                duration_ms,
+            } => self.inner.touch_gesture(gesture, duration_ms).await,
+            InputStep::Wait { ms } => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(())
+            }
         }
     }
 }
@@ -53,6 +189,7 @@ impl InputHandler {
 #[async_trait::async_trait]
 pub(crate) trait InputHandlerTrait: Send + Sync {
     async fn mouse_move(&self, x: f64, y: f64) -> Result<()>;
+    async fn mouse_move_relative(&self, dx: f64, dy: f64) -> Result<()>;
     async fn mouse_click(&self, button: u8) -> Result<()>;
     async fn mouse_down(&self, button: u8) -> Result<()>;
     async fn mouse_up(&self, button: u8) -> Result<()>;
@@ -61,4 +198,8 @@ pub(crate) trait InputHandlerTrait: Send + Sync {
     async fn key_release(&self, key: &str, modifiers: &ModifierKeys) -> Result<()>;
     async fn modifier_press(&self, modifier: &str) -> Result<()>;
     async fn modifier_release(&self, modifier: &str) -> Result<()>;
+    async fn media_key(&self, key: MediaKey) -> Result<()>;
+    async fn type_text(&self, text: &str) -> Result<()>;
+    async fn touch_gesture(&self, gesture: TouchGesture, duration_ms: u64) -> Result<()>;
+    fn pointer_position(&self) -> Option<(f64, f64)>;
 }
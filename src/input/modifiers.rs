@@ -0,0 +1,190 @@
+use crate::domain::models::ModifierKeys;
+use anyhow::Result;
+use std::sync::Mutex;
+
+/// A modifier key, before a backend maps it to one of its own keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Meta,
+}
+
+impl Modifier {
+    const ALL: [Modifier; 4] = [
+        Modifier::Ctrl,
+        Modifier::Alt,
+        Modifier::Shift,
+        Modifier::Meta,
+    ];
+
+    /// Parses a `ModifierPress` name; `None` for one no backend knows
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ctrl" | "control" => Some(Modifier::Ctrl),
+            "alt" => Some(Modifier::Alt),
+            "shift" => Some(Modifier::Shift),
+            "meta" | "super" | "cmd" => Some(Modifier::Meta),
+            _ => None,
+        }
+    }
+
+    fn is_set(self, keys: &ModifierKeys) -> bool {
+        match self {
+            Modifier::Ctrl => keys.ctrl,
+            Modifier::Alt => keys.alt,
+            Modifier::Shift => keys.shift,
+            Modifier::Meta => keys.meta,
+        }
+    }
+
+    fn set(self, keys: &mut ModifierKeys, held: bool) {
+        match self {
+            Modifier::Ctrl => keys.ctrl = held,
+            Modifier::Alt => keys.alt = held,
+            Modifier::Shift => keys.shift = held,
+            Modifier::Meta => keys.meta = held,
+        }
+    }
+}
+
+/// The one primitive a backend provides for modifiers: pressing and releasing its own keys
+pub(crate) trait RawKeys {
+    type PlatformKey: Copy;
+
+    fn modifier_key(modifier: Modifier) -> Self::PlatformKey;
+    fn press_raw(&self, key: Self::PlatformKey) -> Result<()>;
+    fn release_raw(&self, key: Self::PlatformKey) -> Result<()>;
+}
+
+/// Modifiers the server is holding down, shared by every backend
+///
+/// Tracking them here means a key press only sends the modifier changes it needs, and a
+/// modifier held with `ModifierPress` isn't pressed again by the next shortcut.
+#[derive(Default)]
+pub(crate) struct ModifierEngine {
+    held: Mutex<ModifierKeys>,
+}
+
+impl ModifierEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Holds `name` down until it's released; unknown names are ignored
+    pub fn press<K: RawKeys>(&self, keys: &K, name: &str) -> Result<()> {
+        let Some(modifier) = Modifier::parse(name) else {
+            return Ok(());
+        };
+        let mut held = self.lock_held();
+        modifier.set(&mut held, true);
+        keys.press_raw(K::modifier_key(modifier))
+    }
+
+    pub fn release<K: RawKeys>(&self, keys: &K, name: &str) -> Result<()> {
+        let Some(modifier) = Modifier::parse(name) else {
+            return Ok(());
+        };
+        let mut held = self.lock_held();
+        modifier.set(&mut held, false);
+        keys.release_raw(K::modifier_key(modifier))
+    }
+
+    /// Presses what `wanted` adds and then releases what it drops, ahead of a key press
+    pub fn apply<K: RawKeys>(&self, keys: &K, wanted: &ModifierKeys) -> Result<()> {
+        let mut held = self.lock_held();
+        for modifier in Modifier::ALL {
+            if modifier.is_set(wanted) && !modifier.is_set(&held) {
+                keys.press_raw(K::modifier_key(modifier))?;
+                modifier.set(&mut held, true);
+            }
+        }
+        for modifier in Modifier::ALL {
+            if !modifier.is_set(wanted) && modifier.is_set(&held) {
+                keys.release_raw(K::modifier_key(modifier))?;
+                modifier.set(&mut held, false);
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_held(&self) -> std::sync::MutexGuard<'_, ModifierKeys> {
+        self.held.lock().expect("Modifier state mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what a backend would have sent, as (modifier, down)
+    #[derive(Default)]
+    struct Recorder {
+        sent: Mutex<Vec<(Modifier, bool)>>,
+    }
+
+    impl RawKeys for Recorder {
+        type PlatformKey = Modifier;
+
+        fn modifier_key(modifier: Modifier) -> Modifier {
+            modifier
+        }
+
+        fn press_raw(&self, key: Modifier) -> Result<()> {
+            self.sent.lock().unwrap().push((key, true));
+            Ok(())
+        }
+
+        fn release_raw(&self, key: Modifier) -> Result<()> {
+            self.sent.lock().unwrap().push((key, false));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_apply_sends_only_changes() {
+        let engine = ModifierEngine::new();
+        let keys = Recorder::default();
+        let ctrl_shift = ModifierKeys {
+            ctrl: true,
+            shift: true,
+            ..ModifierKeys::default()
+        };
+        engine.apply(&keys, &ctrl_shift).unwrap();
+        engine.apply(&keys, &ctrl_shift).unwrap();
+        let ctrl_alt = ModifierKeys {
+            ctrl: true,
+            alt: true,
+            ..ModifierKeys::default()
+        };
+        engine.apply(&keys, &ctrl_alt).unwrap();
+        assert_eq!(
+            *keys.sent.lock().unwrap(),
+            [
+                (Modifier::Ctrl, true),
+                (Modifier::Shift, true),
+                (Modifier::Alt, true),
+                (Modifier::Shift, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pressed_modifier_counts_as_held() {
+        let engine = ModifierEngine::new();
+        let keys = Recorder::default();
+        engine.press(&keys, "Control").unwrap();
+        engine.press(&keys, "hyper").unwrap();
+        let ctrl = ModifierKeys {
+            ctrl: true,
+            ..ModifierKeys::default()
+        };
+        engine.apply(&keys, &ctrl).unwrap();
+        engine.release(&keys, "ctrl").unwrap();
+        assert_eq!(
+            *keys.sent.lock().unwrap(),
+            [(Modifier::Ctrl, true), (Modifier::Ctrl, false)]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Expands a command into the primitive events the backend injects, which `/simulate` previews
+
+use crate::domain::config::ServerConfig;
+use crate::domain::models::{Command, MediaKey, ModifierKeys, TouchGesture};
+use crate::input::chords::{self, Chord, Platform};
+use serde::Serialize;
+
+/// One primitive event handed to the platform backend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum InputStep {
+    /// Relative pointer motion
+    MouseMove {
+        dx: f64,
+        dy: f64,
+    },
+    MouseClick {
+        button: u8,
+    },
+    MouseDown {
+        button: u8,
+    },
+    MouseUp {
+        button: u8,
+    },
+    MouseScroll {
+        delta_x: f64,
+        delta_y: f64,
+    },
+    KeyPress {
+        key: String,
+        modifiers: ModifierKeys,
+    },
+    KeyRelease {
+        key: String,
+        modifiers: ModifierKeys,
+    },
+    ModifierPress {
+        modifier: String,
+    },
+    ModifierRelease {
+        modifier: String,
+    },
+    MediaKey {
+        key: MediaKey,
+    },
+    /// Unicode text, injected without going through key codes
+    TypeText {
+        text: String,
+    },
+    /// Played out by the backend frame by frame, since each platform injects touch its own way
+    TouchGesture {
+        gesture: TouchGesture,
+        duration_ms: u64,
+    },
+    Wait {
+        ms: u64,
+    },
+}
+
+/// `None` for commands the server handles itself rather than injecting
+pub fn plan(command: &Command) -> Option<Vec<InputStep>> {
+    let steps = match command {
+        Command::MouseMove { x, y } => vec![InputStep::MouseMove { dx: *x, dy: *y }],
+        Command::MouseClick { button } => vec![InputStep::MouseClick { button: *button }],
+        Command::MouseMultiClick { button, count } => multi_click(*button, *count),
+        Command::MouseLongPress {
+            button,
+            duration_ms,
+        } => long_press(*button, *duration_ms),
+        Command::Drag {
+            button,
+            dx,
+            dy,
+            duration_ms,
+        } => drag(*button, (*dx, *dy), *duration_ms),
+        Command::TouchGesture {
+            gesture,
+            duration_ms,
+        } => vec![InputStep::TouchGesture {
+            gesture: *gesture,
+            duration_ms: (*duration_ms).min(ServerConfig::GESTURE_MAX_MS),
+        }],
+        Command::MouseDown { button } => vec![InputStep::MouseDown { button: *button }],
+        Command::MouseUp { button } => vec![InputStep::MouseUp { button: *button }],
+        Command::MouseScroll { delta_x, delta_y } => vec![InputStep::MouseScroll {
+            delta_x: *delta_x,
+            delta_y: *delta_y,
+        }],
+        Command::KeyPress { key, modifiers } => vec![InputStep::KeyPress {
+            key: key.clone(),
+            modifiers: modifiers.clone(),
+        }],
+        Command::KeyRelease { key, modifiers } => vec![InputStep::KeyRelease {
+            key: key.clone(),
+            modifiers: modifiers.clone(),
+        }],
+        Command::ModifierPress { modifier } => vec![InputStep::ModifierPress {
+            modifier: modifier.clone(),
+        }],
+        Command::ModifierRelease { modifier } => vec![InputStep::ModifierRelease {
+            modifier: modifier.clone(),
+        }],
+        Command::MediaKey { key } => vec![InputStep::MediaKey { key: *key }],
+        Command::TypeText { text } => vec![InputStep::TypeText { text: text.clone() }],
+        Command::SelectWord => press_chords(chords::select_word(Platform::current())),
+        Command::SelectLine => press_chords(chords::select_line(Platform::current())),
+        Command::SelectAll => press_chords(chords::select_all(Platform::current())),
+        Command::MoveCaret {
+            direction,
+            unit,
+            extend,
+        } => press_chords(chords::move_caret(
+            Platform::current(),
+            *direction,
+            *unit,
+            *extend,
+        )),
+        _ => return None,
+    };
+    Some(steps)
+}
+
+/// Clicks in quick succession so the OS (or the macOS click-state field) counts them as one gesture
+fn multi_click(button: u8, count: u8) -> Vec<InputStep> {
+    let mut steps = Vec::new();
+    for i in 0..count.clamp(1, ServerConfig::MAX_CLICK_COUNT) {
+        if i > 0 {
+            steps.push(InputStep::Wait {
+                ms: ServerConfig::MULTI_CLICK_INTERVAL_MS,
+            });
+        }
+        steps.push(InputStep::MouseClick { button });
+    }
+    steps
+}
+
+fn long_press(button: u8, duration_ms: u64) -> Vec<InputStep> {
+    vec![
+        InputStep::MouseDown { button },
+        InputStep::Wait {
+            ms: duration_ms.min(ServerConfig::LONG_PRESS_MAX_MS),
+        },
+        InputStep::MouseUp { button },
+    ]
+}
+
+/// Spreads the motion over moves one step apart, so apps see a drag rather than a jump
+fn drag(button: u8, (dx, dy): (f64, f64), duration_ms: u64) -> Vec<InputStep> {
+    let duration_ms = duration_ms.min(ServerConfig::DRAG_MAX_MS);
+    let moves = (duration_ms / ServerConfig::DRAG_STEP_MS).max(1);
+    let mut steps = vec![InputStep::MouseDown { button }];
+    for n in 0..moves {
+        let ms = duration_ms * (n + 1) / moves - duration_ms * n / moves;
+        if ms > 0 {
+            steps.push(InputStep::Wait { ms });
+        }
+        steps.push(InputStep::MouseMove {
+            dx: dx / moves as f64,
+            dy: dy / moves as f64,
+        });
+    }
+    steps.push(InputStep::MouseUp { button });
+    steps
+}
+
+/// Taps each chord in turn, then lets go of the modifiers the last one held
+fn press_chords(chords: Vec<Chord>) -> Vec<InputStep> {
+    let Some(last) = chords.last().cloned() else {
+        return Vec::new();
+    };
+    let mut steps = Vec::new();
+    for chord in chords {
+        steps.push(InputStep::KeyPress {
+            key: chord.key.to_string(),
+            modifiers: chord.modifiers.clone(),
+        });
+        steps.push(InputStep::KeyRelease {
+            key: chord.key.to_string(),
+            modifiers: chord.modifiers,
+        });
+    }
+
+    let held = [
+        (last.modifiers.ctrl, "ctrl"),
+        (last.modifiers.alt, "alt"),
+        (last.modifiers.shift, "shift"),
+        (last.modifiers.meta, "meta"),
+    ];
+    for (_, modifier) in held.iter().filter(|(held, _)| *held) {
+        steps.push(InputStep::ModifierRelease {
+            modifier: modifier.to_string(),
+        });
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_click_waits_between_clicks() {
+        let steps = plan(&Command::MouseMultiClick {
+            button: 1,
+            count: 9,
+        })
+        .unwrap();
+        let clicks = steps
+            .iter()
+            .filter(|step| matches!(step, InputStep::MouseClick { .. }))
+            .count();
+        assert_eq!(clicks, usize::from(ServerConfig::MAX_CLICK_COUNT));
+        assert_eq!(steps.len(), clicks * 2 - 1);
+    }
+
+    #[test]
+    fn test_long_press_is_capped() {
+        let steps = plan(&Command::MouseLongPress {
+            button: 1,
+            duration_ms: u64::MAX,
+        })
+        .unwrap();
+        assert_eq!(
+            steps[1],
+            InputStep::Wait {
+                ms: ServerConfig::LONG_PRESS_MAX_MS
+            }
+        );
+    }
+
+    #[test]
+    fn test_drag_spreads_motion_between_press_and_release() {
+        let steps = plan(&Command::Drag {
+            button: 1,
+            dx: 100.0,
+            dy: -20.0,
+            duration_ms: 20,
+        })
+        .unwrap();
+        assert_eq!(steps.first(), Some(&InputStep::MouseDown { button: 1 }));
+        assert_eq!(steps.last(), Some(&InputStep::MouseUp { button: 1 }));
+        let (mut dx, mut dy, mut waited) = (0.0, 0.0, 0);
+        for step in &steps {
+            match step {
+                InputStep::MouseMove { dx: x, dy: y } => (dx, dy) = (dx + x, dy + y),
+                InputStep::Wait { ms } => waited += ms,
+                _ => {}
+            }
+        }
+        assert_eq!((dx, dy, waited), (100.0, -20.0, 20));
+
+        let instant = plan(&Command::Drag {
+            button: 2,
+            dx: 5.0,
+            dy: 0.0,
+            duration_ms: 0,
+        })
+        .unwrap();
+        assert_eq!(instant.len(), 3);
+    }
+
+    #[test]
+    fn test_chords_release_held_modifiers() {
+        let steps = plan(&Command::SelectAll).unwrap();
+        assert!(matches!(
+            steps.last(),
+            Some(InputStep::ModifierRelease { .. })
+        ));
+    }
+
+    #[test]
+    fn test_server_commands_are_not_planned() {
+        assert!(plan(&Command::SelectAll).is_some());
+        assert!(plan(&Command::NightLight { enabled: true }).is_none());
+    }
+}
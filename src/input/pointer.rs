@@ -0,0 +1,610 @@
+//! Resolves relative pointer motion to a position on the virtual desktop
+
+use crate::domain::config::ServerConfig;
+use crate::domain::models::DisplayInfo;
+use crate::input::focus;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Bounding box of all displays, in the coordinates the backend injects with
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct DesktopBounds {
+    pub left: f64,
+    pub top: f64,
+    /// Exclusive, like a width added to `left`
+    pub right: f64,
+    pub bottom: f64,
+}
+
+impl DesktopBounds {
+    pub fn fallback() -> Self {
+        Self {
+            left: 0.0,
+            top: 0.0,
+            right: ServerConfig::FALLBACK_SCREEN_WIDTH,
+            bottom: ServerConfig::FALLBACK_SCREEN_HEIGHT,
+        }
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (
+            (self.left + self.right) / 2.0,
+            (self.top + self.bottom) / 2.0,
+        )
+    }
+
+    /// Nearest point on the desktop; the right and bottom edges are the last pixel
+    pub fn clamp(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            x.clamp(self.left, (self.right - 1.0).max(self.left)),
+            y.clamp(self.top, (self.bottom - 1.0).max(self.top)),
+        )
+    }
+
+    /// The part of `other` that lies inside these bounds, if any
+    pub fn intersect(&self, other: &DesktopBounds) -> Option<DesktopBounds> {
+        let overlap = DesktopBounds {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        };
+        (overlap.left < overlap.right && overlap.top < overlap.bottom).then_some(overlap)
+    }
+}
+
+/// Moves `from` by the delta and keeps the result on the desktop
+///
+/// An axis whose delta is NaN stays put, so a bad value can't poison the tracked position.
+pub fn advance(from: (f64, f64), dx: f64, dy: f64, bounds: &DesktopBounds) -> (f64, f64) {
+    let step = |position: f64, delta: f64| {
+        if delta.is_nan() {
+            position
+        } else {
+            position + delta
+        }
+    };
+    let (x, y) = bounds.clamp(from);
+    bounds.clamp((step(x, dx), step(y, dy)))
+}
+
+/// `advance`, then onto the nearest display the way the OS stops the real cursor
+///
+/// Backends track the cursor themselves, so this keeps the tracked position from drifting
+/// off-screen.
+pub fn advance_onto_displays(
+    from: (f64, f64),
+    dx: f64,
+    dy: f64,
+    bounds: &DesktopBounds,
+) -> (f64, f64) {
+    onto_displays(advance(from, dx, dy, bounds), &displays())
+}
+
+/// A display's rectangle and its physical pixels per desktop unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Monitor {
+    bounds: DesktopBounds,
+    scale_factor: f64,
+}
+
+impl Monitor {
+    fn unscaled(bounds: DesktopBounds) -> Self {
+        Self {
+            bounds,
+            scale_factor: 1.0,
+        }
+    }
+}
+
+static DISPLAYS: Mutex<Option<(Instant, Vec<Monitor>)>> = Mutex::new(None);
+/// Set while a watcher keeps `DISPLAYS` current, so the cache never goes stale
+static FOLLOWING: AtomicBool = AtomicBool::new(false);
+
+/// Rectangles of the individual displays, re-queried every few seconds to follow changes
+///
+/// Displays of different sizes, or arranged diagonally, leave gaps in their bounding box
+/// that the OS never lets the cursor into.
+pub fn displays() -> Vec<DesktopBounds> {
+    monitors().iter().map(|monitor| monitor.bounds).collect()
+}
+
+/// The displays as `GetDisplays` and `/status` report them
+pub fn display_info() -> Vec<DisplayInfo> {
+    monitors()
+        .iter()
+        .enumerate()
+        .map(|(index, monitor)| DisplayInfo {
+            index,
+            left: monitor.bounds.left,
+            top: monitor.bounds.top,
+            right: monitor.bounds.right,
+            bottom: monitor.bounds.bottom,
+            scale_factor: monitor.scale_factor,
+            primary: monitor.bounds.left == 0.0 && monitor.bounds.top == 0.0,
+        })
+        .collect()
+}
+
+/// The point `x`, `y` of the way across display `index`, each from 0 to 1
+pub fn point_on_display(index: usize, x: f64, y: f64) -> Option<(f64, f64)> {
+    let bounds = displays().get(index).copied()?;
+    let point = (
+        bounds.left + x * (bounds.right - bounds.left),
+        bounds.top + y * (bounds.bottom - bounds.top),
+    );
+    Some(bounds.clamp(point))
+}
+
+fn monitors() -> Vec<Monitor> {
+    let refresh = Duration::from_secs(ServerConfig::DESKTOP_BOUNDS_REFRESH_SECS);
+    if let Some((checked, monitors)) = &*DISPLAYS.lock().expect("Displays mutex poisoned") {
+        if FOLLOWING.load(Ordering::Relaxed) || checked.elapsed() < refresh {
+            return monitors.clone();
+        }
+    }
+    // Queried without the lock, so a slow display server holds up only this caller
+    remember(query())
+}
+
+/// Caches freshly queried displays, in a stable order, and returns them
+fn remember(monitors: Option<Vec<Monitor>>) -> Vec<Monitor> {
+    let mut monitors = monitors
+        .filter(|monitors| !monitors.is_empty())
+        .unwrap_or_else(|| vec![Monitor::unscaled(DesktopBounds::fallback())]);
+    monitors.sort_by(|a, b| {
+        (a.bounds.left.total_cmp(&b.bounds.left)).then(a.bounds.top.total_cmp(&b.bounds.top))
+    });
+    *DISPLAYS.lock().expect("Displays mutex poisoned") = Some((Instant::now(), monitors.clone()));
+    monitors
+}
+
+/// Current desktop bounds: the box around every display
+pub fn desktop_bounds() -> DesktopBounds {
+    union(&displays()).unwrap_or_else(DesktopBounds::fallback)
+}
+
+fn union(displays: &[DesktopBounds]) -> Option<DesktopBounds> {
+    displays.iter().copied().reduce(|a, b| DesktopBounds {
+        left: a.left.min(b.left),
+        top: a.top.min(b.top),
+        right: a.right.max(b.right),
+        bottom: a.bottom.max(b.bottom),
+    })
+}
+
+/// Nearest point on one of `displays`, where the OS would have stopped the cursor
+///
+/// A point already on a display, or an empty list, leaves the point as it is.
+pub fn onto_displays(point: (f64, f64), displays: &[DesktopBounds]) -> (f64, f64) {
+    let distance = |(x, y): (f64, f64)| (x - point.0).powi(2) + (y - point.1).powi(2);
+    displays
+        .iter()
+        .map(|display| display.clamp(point))
+        .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))
+        .unwrap_or(point)
+}
+
+static CONFINED: AtomicBool = AtomicBool::new(false);
+static WINDOW_BOUNDS: Mutex<Option<(Instant, Option<DesktopBounds>)>> = Mutex::new(None);
+
+/// Keeps injected motion inside the focused window instead of the whole desktop
+pub fn confine_to_focused_window(enabled: bool) {
+    CONFINED.store(enabled, Ordering::Relaxed);
+    *WINDOW_BOUNDS.lock().expect("Window bounds mutex poisoned") = None;
+}
+
+pub fn confined_to_focused_window() -> bool {
+    CONFINED.load(Ordering::Relaxed)
+}
+
+/// Where injected motion may go: the focused window while confined, otherwise the desktop
+///
+/// Falls back to the desktop when no window has focus or the focused one is off-screen.
+pub fn movement_bounds() -> DesktopBounds {
+    let desktop = desktop_bounds();
+    if !confined_to_focused_window() {
+        return desktop;
+    }
+    let mut cached = WINDOW_BOUNDS.lock().expect("Window bounds mutex poisoned");
+    let refresh = Duration::from_millis(ServerConfig::FOCUSED_WINDOW_REFRESH_MS);
+    let window = match *cached {
+        Some((checked, window)) if checked.elapsed() < refresh => window,
+        _ => {
+            let window = focus::focused_window_bounds();
+            *cached = Some((Instant::now(), window));
+            window
+        }
+    };
+    window
+        .and_then(|window| desktop.intersect(&window))
+        .unwrap_or(desktop)
+}
+
+/// Each active CRTC's rectangle from RandR, or the root window when RandR has none
+///
+/// X11 has no per-monitor scale; desktops that scale set `Xft.dpi` for the whole screen.
+#[cfg(target_os = "linux")]
+fn query() -> Option<Vec<Monitor>> {
+    use x11::xlib;
+    unsafe {
+        let display = xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let monitors = query_display(display);
+        xlib::XCloseDisplay(display);
+        Some(monitors)
+    }
+}
+
+/// `query` on a connection the caller already holds open
+#[cfg(target_os = "linux")]
+unsafe fn query_display(display: *mut x11::xlib::Display) -> Vec<Monitor> {
+    use x11::{xlib, xrandr};
+    unsafe {
+        let screen = xlib::XDefaultScreen(display);
+        let root = xlib::XRootWindow(display, screen);
+
+        let mut displays = Vec::new();
+        let resources = xrandr::XRRGetScreenResourcesCurrent(display, root);
+        if !resources.is_null() {
+            let crtcs = std::slice::from_raw_parts((*resources).crtcs, (*resources).ncrtc as usize);
+            for &crtc in crtcs {
+                let info = xrandr::XRRGetCrtcInfo(display, resources, crtc);
+                if info.is_null() {
+                    continue;
+                }
+                // CRTCs without a mode are switched off
+                if (*info).mode != 0 && (*info).width > 0 && (*info).height > 0 {
+                    displays.push(DesktopBounds {
+                        left: f64::from((*info).x),
+                        top: f64::from((*info).y),
+                        right: f64::from((*info).x) + f64::from((*info).width),
+                        bottom: f64::from((*info).y) + f64::from((*info).height),
+                    });
+                }
+                xrandr::XRRFreeCrtcInfo(info);
+            }
+            xrandr::XRRFreeScreenResources(resources);
+        }
+        if displays.is_empty() {
+            displays.push(DesktopBounds {
+                left: 0.0,
+                top: 0.0,
+                right: f64::from(xlib::XDisplayWidth(display, screen)),
+                bottom: f64::from(xlib::XDisplayHeight(display, screen)),
+            });
+        }
+        let resources = xlib::XResourceManagerString(display);
+        let scale_factor = match resources.is_null() {
+            true => 1.0,
+            false => xft_scale(&std::ffi::CStr::from_ptr(resources).to_string_lossy()),
+        };
+        displays
+            .into_iter()
+            .map(|bounds| Monitor {
+                bounds,
+                scale_factor,
+            })
+            .collect()
+    }
+}
+
+/// Scale from the `Xft.dpi` X resource, against the 96 DPI of an unscaled screen
+#[cfg(target_os = "linux")]
+fn xft_scale(resources: &str) -> f64 {
+    resources
+        .lines()
+        .find_map(|line| line.strip_prefix("Xft.dpi:"))
+        .and_then(|dpi| dpi.trim().parse::<f64>().ok())
+        .filter(|dpi| *dpi > 0.0)
+        .map_or(1.0, |dpi| dpi / 96.0)
+}
+
+/// Re-queries the displays on this thread's own connection whenever RandR reports a monitor
+/// added, removed or rearranged
+///
+/// Moves then never wait on the display server; without this, a hotplugged monitor only
+/// counts after the next periodic refresh.
+#[cfg(target_os = "linux")]
+pub fn follow_display_changes() {
+    let spawned = std::thread::Builder::new()
+        .name("display-changes".to_string())
+        .spawn(|| unsafe {
+            use x11::{xlib, xrandr};
+
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            if display.is_null() {
+                log::debug!("No X display; display changes won't be followed");
+                return;
+            }
+            let (mut event_base, mut error_base) = (0, 0);
+            if xrandr::XRRQueryExtension(display, &mut event_base, &mut error_base) == 0 {
+                log::debug!("No RandR; display changes won't be followed");
+                xlib::XCloseDisplay(display);
+                return;
+            }
+            let root = xlib::XDefaultRootWindow(display);
+            xrandr::XRRSelectInput(
+                display,
+                root,
+                xrandr::RRScreenChangeNotifyMask
+                    | xrandr::RRCrtcChangeNotifyMask
+                    | xrandr::RROutputChangeNotifyMask,
+            );
+            remember(Some(query_display(display)));
+            FOLLOWING.store(true, Ordering::Relaxed);
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            loop {
+                xlib::XNextEvent(display, &mut event);
+                xrandr::XRRUpdateConfiguration(&mut event);
+                log::debug!("Display configuration changed");
+                remember(Some(query_display(display)));
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start following display changes: {}", e);
+    }
+}
+
+/// Other platforms pick up display changes on the periodic refresh
+#[cfg(not(target_os = "linux"))]
+pub fn follow_display_changes() {}
+
+/// Each monitor's rectangle on the virtual screen, with its effective DPI as the scale
+///
+/// Monitors left of or above the primary have negative coordinates.
+#[cfg(windows)]
+fn query() -> Option<Vec<Monitor>> {
+    use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+    use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR};
+    use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+
+    unsafe extern "system" fn collect(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        rect: *mut RECT,
+        monitors: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(monitors.0 as *mut Vec<Monitor>);
+        let rect = *rect;
+        let (mut dpi_x, mut dpi_y) = (0u32, 0u32);
+        let scale_factor =
+            match GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) {
+                Ok(()) if dpi_x > 0 => f64::from(dpi_x) / 96.0,
+                _ => 1.0,
+            };
+        monitors.push(Monitor {
+            bounds: DesktopBounds {
+                left: f64::from(rect.left),
+                top: f64::from(rect.top),
+                right: f64::from(rect.right),
+                bottom: f64::from(rect.bottom),
+            },
+            scale_factor,
+        });
+        true.into()
+    }
+
+    let mut monitors: Vec<Monitor> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(collect),
+            LPARAM(&mut monitors as *mut Vec<Monitor> as isize),
+        );
+    }
+    if monitors.is_empty() {
+        return virtual_screen().map(|bounds| vec![Monitor::unscaled(bounds)]);
+    }
+    Some(monitors)
+}
+
+/// The box around every monitor, for when they can't be enumerated one by one
+#[cfg(windows)]
+fn virtual_screen() -> Option<DesktopBounds> {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+        SM_YVIRTUALSCREEN,
+    };
+    let (left, top, width, height) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    Some(DesktopBounds {
+        left: f64::from(left),
+        top: f64::from(top),
+        right: f64::from(left + width),
+        bottom: f64::from(top + height),
+    })
+}
+
+/// The active displays in global display coordinates, scaled by their backing pixels
+#[cfg(target_os = "macos")]
+fn query() -> Option<Vec<Monitor>> {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct CGRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    extern "C" {
+        fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        fn CGDisplayBounds(display: u32) -> CGRect;
+        fn CGDisplayCopyDisplayMode(display: u32) -> *mut c_void;
+        fn CGDisplayModeGetWidth(mode: *mut c_void) -> usize;
+        fn CGDisplayModeGetPixelWidth(mode: *mut c_void) -> usize;
+        fn CGDisplayModeRelease(mode: *mut c_void);
+    }
+
+    /// Backing pixels per point, 2 on Retina panels
+    unsafe fn scale_factor(display: u32) -> f64 {
+        let mode = CGDisplayCopyDisplayMode(display);
+        if mode.is_null() {
+            return 1.0;
+        }
+        let (points, pixels) = (
+            CGDisplayModeGetWidth(mode),
+            CGDisplayModeGetPixelWidth(mode),
+        );
+        CGDisplayModeRelease(mode);
+        match points {
+            0 => 1.0,
+            points => pixels as f64 / points as f64,
+        }
+    }
+
+    const MAX_DISPLAYS: usize = 16;
+    let mut displays = [0u32; MAX_DISPLAYS];
+    let mut count = 0u32;
+    let status =
+        unsafe { CGGetActiveDisplayList(MAX_DISPLAYS as u32, displays.as_mut_ptr(), &mut count) };
+    if status != 0 || count == 0 {
+        return None;
+    }
+    let monitors = displays[..count as usize]
+        .iter()
+        .map(|&display| unsafe {
+            let rect = CGDisplayBounds(display);
+            Monitor {
+                bounds: DesktopBounds {
+                    left: rect.x,
+                    top: rect.y,
+                    right: rect.x + rect.width,
+                    bottom: rect.y + rect.height,
+                },
+                scale_factor: scale_factor(display),
+            }
+        })
+        .collect();
+    Some(monitors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn bounds() -> impl Strategy<Value = DesktopBounds> {
+        (
+            -4000.0..4000.0f64,
+            -4000.0..4000.0f64,
+            1.0..8000.0f64,
+            1.0..8000.0f64,
+        )
+            .prop_map(|(left, top, width, height)| DesktopBounds {
+                left,
+                top,
+                right: left + width,
+                bottom: top + height,
+            })
+    }
+
+    fn on_desktop(bounds: &DesktopBounds, (x, y): (f64, f64)) -> bool {
+        x.is_finite()
+            && y.is_finite()
+            && (bounds.left..bounds.right.max(bounds.left + 1.0)).contains(&x)
+            && (bounds.top..bounds.bottom.max(bounds.top + 1.0)).contains(&y)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_xft_dpi_sets_the_scale() {
+        assert_eq!(xft_scale("Xft.antialias:\t1\nXft.dpi:\t192\n"), 2.0);
+        assert_eq!(xft_scale("Xft.dpi: 144"), 1.5);
+        assert_eq!(xft_scale("Xcursor.size:\t24\n"), 1.0);
+    }
+
+    #[test]
+    fn test_window_partly_off_screen_is_cut_to_the_desktop() {
+        let desktop = DesktopBounds::fallback();
+        let window = DesktopBounds {
+            left: -200.0,
+            top: 100.0,
+            right: 600.0,
+            bottom: 1400.0,
+        };
+        let visible = desktop.intersect(&window).unwrap();
+        assert_eq!((visible.left, visible.top), (0.0, 100.0));
+        assert_eq!((visible.right, visible.bottom), (600.0, 1080.0));
+
+        let off_screen = DesktopBounds {
+            left: 3000.0,
+            right: 3500.0,
+            ..window
+        };
+        assert_eq!(desktop.intersect(&off_screen), None);
+    }
+
+    #[test]
+    fn test_gap_between_diagonal_displays_snaps_to_the_nearest() {
+        let primary = DesktopBounds::fallback();
+        let above_left = DesktopBounds {
+            left: -1280.0,
+            top: -1024.0,
+            right: 0.0,
+            bottom: 0.0,
+        };
+        let displays = [primary, above_left];
+        let desktop = union(&displays).unwrap();
+        assert_eq!((desktop.left, desktop.top), (-1280.0, -1024.0));
+
+        let start = (10.0, 500.0);
+        let moved = advance(start, -200.0, 0.0, &desktop);
+        assert_eq!(moved, (-190.0, 500.0));
+        assert_eq!(onto_displays(moved, &displays), (0.0, 500.0));
+        assert_eq!(onto_displays((-5.0, -5.0), &displays), (-5.0, -5.0));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_advance_stays_on_desktop(
+            bounds in bounds(),
+            from in (any::<f64>(), any::<f64>()),
+            dx in any::<f64>(),
+            dy in any::<f64>(),
+        ) {
+            let from = (
+                if from.0.is_nan() { 0.0 } else { from.0 },
+                if from.1.is_nan() { 0.0 } else { from.1 },
+            );
+            prop_assert!(on_desktop(&bounds, advance(from, dx, dy, &bounds)));
+        }
+
+        #[test]
+        fn prop_nan_delta_leaves_axis_unchanged(bounds in bounds(), dy in -100.0..100.0f64) {
+            let from = bounds.center();
+            let (x, _) = advance(from, f64::NAN, dy, &bounds);
+            prop_assert_eq!(x, bounds.clamp(from).0);
+        }
+
+        #[test]
+        fn prop_moves_inside_the_desktop_are_exact(
+            bounds in bounds(),
+            dx in -0.5..0.5f64,
+            dy in -0.5..0.5f64,
+        ) {
+            let from = bounds.center();
+            let (x, y) = advance(from, dx, dy, &bounds);
+            // The last pixel is at `right - 1`, so narrower desktops have no half pixel to spare
+            prop_assert!((x - (from.0 + dx)).abs() < 1e-9 || bounds.right - bounds.left < 3.0);
+            prop_assert!((y - (from.1 + dy)).abs() < 1e-9 || bounds.bottom - bounds.top < 3.0);
+        }
+    }
+}
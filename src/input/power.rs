@@ -0,0 +1,103 @@
+//! Locking the session and putting the whole system to sleep, shutting it down or restarting it
+
+use crate::domain::config::settings::CornerAction;
+use crate::domain::models::PowerAction;
+use crate::input::desktop_actions;
+use anyhow::{anyhow, Context, Result};
+
+/// Locking is the hot-corner action; the rest goes through each platform's power tooling
+pub fn perform(action: PowerAction) -> Result<()> {
+    match action {
+        PowerAction::Lock => desktop_actions::perform(CornerAction::Lock),
+        PowerAction::Sleep => platform::sleep(),
+        PowerAction::Shutdown => platform::shutdown(),
+        PowerAction::Restart => platform::restart(),
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+        command.creation_flags(CREATE_NO_WINDOW.0);
+    }
+    let status = command
+        .status()
+        .with_context(|| format!("{} is not available", program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} exited with {}", program, status))
+    }
+}
+
+/// systemd through logind, which lets the session's user do this without root
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{run, Result};
+
+    pub fn sleep() -> Result<()> {
+        run("systemctl", &["suspend"])
+    }
+
+    pub fn shutdown() -> Result<()> {
+        run("systemctl", &["poweroff"])
+    }
+
+    pub fn restart() -> Result<()> {
+        run("systemctl", &["reboot"])
+    }
+}
+
+/// Shutting down through System Events lets apps ask to save their documents first
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{run, Result};
+
+    pub fn sleep() -> Result<()> {
+        run("pmset", &["sleepnow"])
+    }
+
+    pub fn shutdown() -> Result<()> {
+        run(
+            "osascript",
+            &["-e", "tell application \"System Events\" to shut down"],
+        )
+    }
+
+    pub fn restart() -> Result<()> {
+        run(
+            "osascript",
+            &["-e", "tell application \"System Events\" to restart"],
+        )
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{run, Result};
+    use anyhow::anyhow;
+    use windows::Win32::Foundation::BOOLEAN;
+    use windows::Win32::System::Power::SetSuspendState;
+
+    /// Sleeps rather than hibernates, and lets wake timers wake the system again
+    pub fn sleep() -> Result<()> {
+        let slept = unsafe { SetSuspendState(BOOLEAN(0), BOOLEAN(0), BOOLEAN(0)) };
+        if !slept.as_bool() {
+            return Err(anyhow!("Windows refused to sleep"));
+        }
+        Ok(())
+    }
+
+    pub fn shutdown() -> Result<()> {
+        run("shutdown", &["/s", "/t", "0"])
+    }
+
+    pub fn restart() -> Result<()> {
+        run("shutdown", &["/r", "/t", "0"])
+    }
+}
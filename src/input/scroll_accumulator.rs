@@ -0,0 +1,84 @@
+//! Carries sub-unit scroll deltas over to the next command
+//!
+//! Backends inject whole wheel units, so a slow precise scroll of 0.3 lines per command used
+//! to truncate to zero forever. The remainder is kept per axis until it adds up to a unit.
+
+use std::sync::Mutex;
+
+pub struct ScrollAccumulator {
+    /// Backend scroll units in one line: 1 wheel click on X11, 10 pixels on macOS and
+    /// `WHEEL_DELTA` on Windows
+    units_per_line: f64,
+    remainder: Mutex<(f64, f64)>,
+}
+
+impl ScrollAccumulator {
+    pub fn new(units_per_line: f64) -> Self {
+        Self {
+            units_per_line,
+            remainder: Mutex::new((0.0, 0.0)),
+        }
+    }
+
+    /// Whole wheel units to inject for a delta in lines, keeping the fraction for later
+    pub fn take(&self, delta_x: f64, delta_y: f64) -> (i64, i64) {
+        let mut remainder = self
+            .remainder
+            .lock()
+            .expect("Scroll remainder mutex poisoned");
+        let (x, rest_x) = split(remainder.0, delta_x * self.units_per_line);
+        let (y, rest_y) = split(remainder.1, delta_y * self.units_per_line);
+        *remainder = (rest_x, rest_y);
+        (x, y)
+    }
+}
+
+/// A reversal drops what was carried, so the new direction responds immediately
+fn split(carried: f64, units: f64) -> (i64, f64) {
+    if units == 0.0 {
+        return (0, carried);
+    }
+    let carried = if carried.signum() == units.signum() {
+        carried
+    } else {
+        0.0
+    };
+    let total = carried + units;
+    let whole = total.trunc();
+    (whole as i64, total - whole)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fractions_add_up_to_whole_lines() {
+        let scroll = ScrollAccumulator::new(1.0);
+        let lines: i64 = (0..10).map(|_| scroll.take(0.0, 0.25).1).sum();
+        assert_eq!(lines, 2);
+    }
+
+    #[test]
+    fn test_whole_deltas_pass_through() {
+        let scroll = ScrollAccumulator::new(1.0);
+        assert_eq!(scroll.take(-2.0, 3.0), (-2, 3));
+        assert_eq!(scroll.take(0.0, 0.0), (0, 0));
+    }
+
+    #[test]
+    fn test_reversal_discards_carried_fraction() {
+        let scroll = ScrollAccumulator::new(1.0);
+        assert_eq!(scroll.take(0.0, 0.9), (0, 0));
+        assert_eq!(scroll.take(0.0, -0.5), (0, 0));
+        assert_eq!(scroll.take(0.0, -0.5), (0, -1));
+    }
+
+    #[test]
+    fn test_scales_to_backend_units() {
+        let scroll = ScrollAccumulator::new(120.0);
+        assert_eq!(scroll.take(0.004, 0.5), (0, 60));
+        assert_eq!(scroll.take(0.004, 0.0), (0, 0));
+        assert_eq!(scroll.take(0.004, 0.0), (1, 0));
+    }
+}
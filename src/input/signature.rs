@@ -0,0 +1,26 @@
+/// Marker written into events pointzerver injects, so hooks can tell them from real input.
+///
+/// Windows carries it in `dwExtraInfo`, macOS in the `kCGEventSourceUserData` field.
+/// Linux injects through XTest, whose events can only be told apart by source device.
+pub const INJECTED_EVENT_SIGNATURE: usize = 0x505A_5256;
+
+/// XTest slave devices that carry events simulated on X11
+#[cfg(target_os = "linux")]
+pub const XTEST_DEVICE_NAMES: [&str; 2] =
+    ["Virtual core XTEST pointer", "Virtual core XTEST keyboard"];
+
+/// Returns true if an event's extra-info/user-data value marks it as injected by pointzerver
+pub fn is_injected(tag: u64) -> bool {
+    tag == INJECTED_EVENT_SIGNATURE as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_injected() {
+        assert!(is_injected(INJECTED_EVENT_SIGNATURE as u64));
+        assert!(!is_injected(0));
+    }
+}
@@ -1,5 +1,8 @@
 use crate::domain::config::ServerConfig;
-use crate::domain::models::ModifierKeys;
+use crate::domain::models::{MediaKey, ModifierKeys, NamedKey, TouchGesture};
+use crate::input::modifiers::{Modifier, ModifierEngine, RawKeys};
+use crate::input::pointer;
+use crate::input::scroll_accumulator::ScrollAccumulator;
 use crate::input::InputHandlerTrait;
 use anyhow::Result;
 use rdev::{simulate, Button, EventType, Key, SimulateError};
@@ -7,18 +10,23 @@ use std::sync::Mutex;
 use std::time::Duration;
 
 #[cfg(target_os = "linux")]
-use x11::xlib;
+use x11::{keysym, xlib, xtest};
 
 pub struct InputHandlerImpl {
     current_pos: Mutex<Option<(f64, f64)>>,
-    modifier_state: Mutex<ModifierKeys>,
+    modifiers: ModifierEngine,
+    scroll: ScrollAccumulator,
+    /// Sub-pixel remainder of relative moves, which X11 takes in whole pixels
+    relative: ScrollAccumulator,
 }
 
 impl InputHandlerImpl {
     pub fn new() -> Result<Self> {
         Ok(Self {
             current_pos: Mutex::new(None),
-            modifier_state: Mutex::new(ModifierKeys::default()),
+            modifiers: ModifierEngine::new(),
+            scroll: ScrollAccumulator::new(1.0),
+            relative: ScrollAccumulator::new(1.0),
         })
     }
 
@@ -55,6 +63,17 @@ impl InputHandlerImpl {
     }
 }
 
+/// X11 numbers back and forward 8 and 9, after the wheel's buttons 4 to 7
+fn map_button(button: u8) -> Button {
+    match button {
+        2 => Button::Right,
+        3 => Button::Middle,
+        4 => Button::Unknown(8),
+        5 => Button::Unknown(9),
+        _ => Button::Left,
+    }
+}
+
 fn send_event(event_type: EventType) -> Result<()> {
     match simulate(&event_type) {
         Ok(()) => Ok(()),
@@ -73,16 +92,11 @@ impl InputHandlerTrait for InputHandlerImpl {
             .lock()
             .expect("Cursor position mutex poisoned");
 
-        let (new_x, new_y) = if let Some((px, py)) = *pos_opt {
-            (px + x, py + y)
-        } else if let Some((cx, cy)) = Self::get_cursor_position() {
-            (cx + x, cy + y)
-        } else {
-            (
-                ServerConfig::FALLBACK_SCREEN_WIDTH / 2.0 + x,
-                ServerConfig::FALLBACK_SCREEN_HEIGHT / 2.0 + y,
-            )
-        };
+        let bounds = pointer::movement_bounds();
+        let start = pos_opt
+            .or_else(Self::get_cursor_position)
+            .unwrap_or_else(|| bounds.center());
+        let (new_x, new_y) = pointer::advance_onto_displays(start, x, y, &bounds);
 
         *pos_opt = Some((new_x, new_y));
 
@@ -90,13 +104,31 @@ impl InputHandlerTrait for InputHandlerImpl {
         Ok(())
     }
 
+    async fn mouse_move_relative(&self, dx: f64, dy: f64) -> Result<()> {
+        // The X server moves the pointer now, so the next absolute move reads it back
+        *self
+            .current_pos
+            .lock()
+            .expect("Cursor position mutex poisoned") = None;
+        let (dx, dy) = self.relative.take(dx, dy);
+        if dx == 0 && dy == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            if display.is_null() {
+                return Err(anyhow::anyhow!("Failed to open X display"));
+            }
+            xtest::XTestFakeRelativeMotionEvent(display, -1, dx as i32, dy as i32, 0);
+            xlib::XFlush(display);
+            xlib::XCloseDisplay(display);
+        }
+        Ok(())
+    }
+
     async fn mouse_click(&self, button: u8) -> Result<()> {
-        let button_enum = match button {
-            1 => Button::Left,
-            2 => Button::Right,
-            3 => Button::Middle,
-            _ => Button::Left,
-        };
+        let button_enum = map_button(button);
 
         send_event(EventType::ButtonPress(button_enum))?;
         tokio::time::sleep(Duration::from_millis(ServerConfig::MOUSE_CLICK_DELAY_MS)).await;
@@ -105,47 +137,38 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn mouse_down(&self, button: u8) -> Result<()> {
-        let button_enum = match button {
-            1 => Button::Left,
-            2 => Button::Right,
-            3 => Button::Middle,
-            _ => Button::Left,
-        };
+        let button_enum = map_button(button);
 
         send_event(EventType::ButtonPress(button_enum))?;
         Ok(())
     }
 
     async fn mouse_up(&self, button: u8) -> Result<()> {
-        let button_enum = match button {
-            1 => Button::Left,
-            2 => Button::Right,
-            3 => Button::Middle,
-            _ => Button::Left,
-        };
+        let button_enum = map_button(button);
 
         send_event(EventType::ButtonRelease(button_enum))?;
         Ok(())
     }
 
     async fn mouse_scroll(&self, delta_x: f64, delta_y: f64) -> Result<()> {
-        if delta_y != 0.0 {
+        let (wheel_x, wheel_y) = self.scroll.take(delta_x, delta_y);
+        if wheel_y != 0 {
             send_event(EventType::Wheel {
-                delta_x: 0i64,
-                delta_y: delta_y as i64,
+                delta_x: 0,
+                delta_y: wheel_y,
             })?;
         }
-        if delta_x != 0.0 {
+        if wheel_x != 0 {
             send_event(EventType::Wheel {
-                delta_x: delta_x as i64,
-                delta_y: 0i64,
+                delta_x: wheel_x,
+                delta_y: 0,
             })?;
         }
         Ok(())
     }
 
     async fn key_press(&self, key: &str, modifiers: &ModifierKeys) -> Result<()> {
-        Self::apply_modifiers(&self.modifier_state, modifiers)?;
+        self.modifiers.apply(self, modifiers)?;
 
         if let Some(key_enum) = string_to_key(key) {
             send_event(EventType::KeyPress(key_enum))?;
@@ -161,103 +184,187 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn modifier_press(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
-            .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = true;
-                send_event(EventType::KeyPress(Key::ControlLeft))?;
-            }
-            "alt" => {
-                state.alt = true;
-                send_event(EventType::KeyPress(Key::Alt))?;
-            }
-            "shift" => {
-                state.shift = true;
-                send_event(EventType::KeyPress(Key::ShiftLeft))?;
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = true;
-                send_event(EventType::KeyPress(Key::MetaLeft))?;
-            }
-            _ => {}
-        }
-        Ok(())
+        self.modifiers.press(self, modifier)
     }
 
     async fn modifier_release(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
+        self.modifiers.release(self, modifier)
+    }
+
+    async fn media_key(&self, key: MediaKey) -> Result<()> {
+        let key = media_to_key(key);
+        send_event(EventType::KeyPress(key))?;
+        send_event(EventType::KeyRelease(key))
+    }
+
+    async fn type_text(&self, text: &str) -> Result<()> {
+        let text = text.to_string();
+        tokio::task::spawn_blocking(move || type_unicode(&text)).await?
+    }
+
+    /// XTest only fakes a core pointer, which has no touch contacts
+    async fn touch_gesture(&self, _gesture: TouchGesture, _duration_ms: u64) -> Result<()> {
+        Err(anyhow::anyhow!("Touch gestures aren't supported on Linux"))
+    }
+
+    fn pointer_position(&self) -> Option<(f64, f64)> {
+        *self
+            .current_pos
             .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = false;
-                send_event(EventType::KeyRelease(Key::ControlLeft))?;
-            }
-            "alt" => {
-                state.alt = false;
-                send_event(EventType::KeyRelease(Key::Alt))?;
-            }
-            "shift" => {
-                state.shift = false;
-                send_event(EventType::KeyRelease(Key::ShiftLeft))?;
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = false;
-                send_event(EventType::KeyRelease(Key::MetaLeft))?;
-            }
-            _ => {}
-        }
-        Ok(())
+            .expect("Cursor position mutex poisoned")
     }
 }
 
-impl InputHandlerImpl {
-    fn apply_modifiers(state: &Mutex<ModifierKeys>, modifiers: &ModifierKeys) -> Result<()> {
-        let mut state_guard = state.lock().expect("Modifier state mutex poisoned");
+impl RawKeys for InputHandlerImpl {
+    type PlatformKey = Key;
 
-        if modifiers.ctrl && !state_guard.ctrl {
-            send_event(EventType::KeyPress(Key::ControlLeft))?;
-            state_guard.ctrl = true;
-        }
-        if modifiers.alt && !state_guard.alt {
-            send_event(EventType::KeyPress(Key::Alt))?;
-            state_guard.alt = true;
-        }
-        if modifiers.shift && !state_guard.shift {
-            send_event(EventType::KeyPress(Key::ShiftLeft))?;
-            state_guard.shift = true;
-        }
-        if modifiers.meta && !state_guard.meta {
-            send_event(EventType::KeyPress(Key::MetaLeft))?;
-            state_guard.meta = true;
+    fn modifier_key(modifier: Modifier) -> Key {
+        match modifier {
+            Modifier::Ctrl => Key::ControlLeft,
+            Modifier::Alt => Key::Alt,
+            Modifier::Shift => Key::ShiftLeft,
+            Modifier::Meta => Key::MetaLeft,
         }
+    }
 
-        if !modifiers.ctrl && state_guard.ctrl {
-            send_event(EventType::KeyRelease(Key::ControlLeft))?;
-            state_guard.ctrl = false;
-        }
-        if !modifiers.alt && state_guard.alt {
-            send_event(EventType::KeyRelease(Key::Alt))?;
-            state_guard.alt = false;
-        }
-        if !modifiers.shift && state_guard.shift {
-            send_event(EventType::KeyRelease(Key::ShiftLeft))?;
-            state_guard.shift = false;
-        }
-        if !modifiers.meta && state_guard.meta {
-            send_event(EventType::KeyRelease(Key::MetaLeft))?;
-            state_guard.meta = false;
+    fn press_raw(&self, key: Key) -> Result<()> {
+        send_event(EventType::KeyPress(key))
+    }
+
+    fn release_raw(&self, key: Key) -> Result<()> {
+        send_event(EventType::KeyRelease(key))
+    }
+}
+
+/// rdev has no media keys; these are their evdev keycodes, which X11 maps to the XF86 keysyms
+fn media_to_key(key: MediaKey) -> Key {
+    let keycode = match key {
+        MediaKey::PlayPause => 172,
+        MediaKey::NextTrack => 171,
+        MediaKey::PrevTrack => 173,
+        MediaKey::VolumeUp => 123,
+        MediaKey::VolumeDown => 122,
+        MediaKey::Mute => 121,
+    };
+    Key::Unknown(keycode)
+}
+
+/// Types `text` by pointing a spare keycode at each character's keysym in turn
+///
+/// Keysyms cover all of Unicode, so this works whatever the active layout. The keycode is
+/// left unmapped again afterwards.
+fn type_unicode(text: &str) -> Result<()> {
+    unsafe {
+        let display = xlib::XOpenDisplay(std::ptr::null());
+        if display.is_null() {
+            return Err(anyhow::anyhow!("Failed to open X display"));
         }
 
-        Ok(())
+        let result = match spare_keycode(display) {
+            Some(keycode) => {
+                for keysym in text.chars().filter_map(char_to_keysym) {
+                    tap_keysym(display, keycode, keysym);
+                    std::thread::sleep(Duration::from_millis(
+                        ServerConfig::TYPE_TEXT_REMAP_DELAY_MS,
+                    ));
+                }
+                let mut no_symbol: xlib::KeySym = 0;
+                xlib::XChangeKeyboardMapping(display, keycode, 1, &mut no_symbol, 1);
+                xlib::XSync(display, xlib::False);
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("No unmapped keycode to type text with")),
+        };
+
+        xlib::XCloseDisplay(display);
+        result
+    }
+}
+
+/// The highest keycode with no keysyms at all, which no layout is using
+unsafe fn spare_keycode(display: *mut xlib::Display) -> Option<i32> {
+    let mut min = 0;
+    let mut max = 0;
+    xlib::XDisplayKeycodes(display, &mut min, &mut max);
+    let count = max - min + 1;
+    let mut per_keycode = 0;
+    let keysyms = xlib::XGetKeyboardMapping(display, min as u8, count, &mut per_keycode);
+    if keysyms.is_null() {
+        return None;
+    }
+
+    let mapping = std::slice::from_raw_parts(keysyms, (count * per_keycode) as usize);
+    let spare = mapping
+        .chunks(per_keycode.max(1) as usize)
+        .rposition(|syms| syms.iter().all(|&sym| sym == 0))
+        .map(|index| min + index as i32);
+    xlib::XFree(keysyms.cast());
+    spare
+}
+
+unsafe fn tap_keysym(display: *mut xlib::Display, keycode: i32, keysym: xlib::KeySym) {
+    let mut keysym = keysym;
+    xlib::XChangeKeyboardMapping(display, keycode, 1, &mut keysym, 1);
+    xlib::XSync(display, xlib::False);
+    xtest::XTestFakeKeyEvent(display, keycode as u32, xlib::True, 0);
+    xtest::XTestFakeKeyEvent(display, keycode as u32, xlib::False, 0);
+    xlib::XSync(display, xlib::False);
+}
+
+/// Latin-1 keysyms equal their code points; the rest of Unicode is offset by `0x1000000`
+fn char_to_keysym(ch: char) -> Option<xlib::KeySym> {
+    match ch {
+        // Apps expect Return and Tab as keys, not as the characters they'd produce
+        '\n' => Some(keysym::XK_Return.into()),
+        '\t' => Some(keysym::XK_Tab.into()),
+        '\r' => None,
+        '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => Some(ch as xlib::KeySym),
+        _ => Some(0x0100_0000 + ch as xlib::KeySym),
+    }
+}
+
+fn named_to_key(key: NamedKey) -> Option<Key> {
+    let key = match key {
+        NamedKey::ArrowLeft => Key::LeftArrow,
+        NamedKey::ArrowRight => Key::RightArrow,
+        NamedKey::ArrowUp => Key::UpArrow,
+        NamedKey::ArrowDown => Key::DownArrow,
+        NamedKey::Home => Key::Home,
+        NamedKey::End => Key::End,
+        NamedKey::PageUp => Key::PageUp,
+        NamedKey::PageDown => Key::PageDown,
+        NamedKey::Escape => Key::Escape,
+        NamedKey::Insert => Key::Insert,
+        NamedKey::Delete => Key::Delete,
+        NamedKey::Function(n) => return function_key(n),
+    };
+    Some(key)
+}
+
+fn function_key(n: u8) -> Option<Key> {
+    match n {
+        1 => Some(Key::F1),
+        2 => Some(Key::F2),
+        3 => Some(Key::F3),
+        4 => Some(Key::F4),
+        5 => Some(Key::F5),
+        6 => Some(Key::F6),
+        7 => Some(Key::F7),
+        8 => Some(Key::F8),
+        9 => Some(Key::F9),
+        10 => Some(Key::F10),
+        11 => Some(Key::F11),
+        12 => Some(Key::F12),
+        // rdev stops at F12; evdev keycodes carry on from F13 at 191
+        13..=24 => Some(Key::Unknown(191 + u32::from(n - 13))),
+        _ => None,
     }
 }
 
 fn string_to_key(s: &str) -> Option<Key> {
+    if let Some(named) = NamedKey::parse(s) {
+        return named_to_key(named);
+    }
     match s {
         " " => Some(Key::Space),
         "\n" | "\r" => Some(Key::Return),
@@ -1,22 +1,53 @@
 use crate::domain::config::ServerConfig;
-use crate::domain::models::ModifierKeys;
+use crate::domain::models::{MediaKey, ModifierKeys, NamedKey, TouchGesture};
+use crate::input::gesture::{self, GestureFrame};
+use crate::input::modifiers::{Modifier, ModifierEngine, RawKeys};
+use crate::input::pointer;
+use crate::input::scroll_accumulator::ScrollAccumulator;
+use crate::input::signature::INJECTED_EVENT_SIGNATURE;
 use crate::input::InputHandlerTrait;
 use anyhow::Result;
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 use std::time::Duration;
-use windows::Win32::UI::WindowsAndMessaging::{GetCursorPos, SetCursorPos};
+use windows::Win32::UI::Input::Pointer::{
+    InitializeTouchInjection, InjectTouchInput, POINTER_FLAGS, POINTER_FLAG_DOWN,
+    POINTER_FLAG_INCONTACT, POINTER_FLAG_INRANGE, POINTER_FLAG_UP, POINTER_FLAG_UPDATE,
+    POINTER_INFO, POINTER_TOUCH_INFO, TOUCH_FEEDBACK_DEFAULT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetCursorPos, SetCursorPos, PT_TOUCH, WHEEL_DELTA};
 use windows::{Win32::Foundation::POINT, Win32::UI::Input::KeyboardAndMouse::*};
 
 pub struct InputHandlerImpl {
     current_pos: Mutex<Option<(f64, f64)>>,
-    modifier_state: Mutex<ModifierKeys>,
+    modifiers: ModifierEngine,
+    scroll: ScrollAccumulator,
+    /// Sub-pixel remainder of relative moves, which `SendInput` takes in whole mickeys
+    relative: ScrollAccumulator,
 }
 
 impl InputHandlerImpl {
+    /// Event flags and `mouseData` for pressing or releasing a protocol button
+    ///
+    /// Buttons 4 and 5 are the X buttons, back and forward, told apart by `mouseData`.
+    fn button_input(button: u8, down: bool) -> (MOUSE_EVENT_FLAGS, u32) {
+        const XBUTTON1: u32 = 0x0001;
+        const XBUTTON2: u32 = 0x0002;
+        let (down_flags, up_flags, mouse_data) = match button {
+            2 => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, 0),
+            3 => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, 0),
+            4 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, XBUTTON1),
+            5 => (MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, XBUTTON2),
+            _ => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, 0),
+        };
+        (if down { down_flags } else { up_flags }, mouse_data)
+    }
+
     pub fn new() -> Result<Self> {
         Ok(Self {
             current_pos: Mutex::new(None),
-            modifier_state: Mutex::new(ModifierKeys::default()),
+            modifiers: ModifierEngine::new(),
+            scroll: ScrollAccumulator::new(f64::from(WHEEL_DELTA)),
+            relative: ScrollAccumulator::new(1.0),
         })
     }
 
@@ -40,16 +71,11 @@ impl InputHandlerTrait for InputHandlerImpl {
             .lock()
             .expect("Cursor position mutex poisoned");
 
-        let (new_x, new_y) = if let Some((px, py)) = *pos_opt {
-            (px + x, py + y)
-        } else if let Some((cx, cy)) = Self::get_cursor_position() {
-            (cx + x, cy + y)
-        } else {
-            (
-                ServerConfig::FALLBACK_SCREEN_WIDTH / 2.0 + x,
-                ServerConfig::FALLBACK_SCREEN_HEIGHT / 2.0 + y,
-            )
-        };
+        let bounds = pointer::movement_bounds();
+        let start = pos_opt
+            .or_else(Self::get_cursor_position)
+            .unwrap_or_else(|| bounds.center());
+        let (new_x, new_y) = pointer::advance_onto_displays(start, x, y, &bounds);
 
         *pos_opt = Some((new_x, new_y));
 
@@ -59,6 +85,37 @@ impl InputHandlerTrait for InputHandlerImpl {
         Ok(())
     }
 
+    /// Sends motion as a mouse would, which raw input readers see and the pointer speed setting
+    /// scales
+    async fn mouse_move_relative(&self, dx: f64, dy: f64) -> Result<()> {
+        *self
+            .current_pos
+            .lock()
+            .expect("Cursor position mutex poisoned") = None;
+        let (dx, dy) = self.relative.take(dx, dy);
+        if dx == 0 && dy == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let input = INPUT {
+                r#type: INPUT_MOUSE,
+                Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
+                    mi: MOUSEINPUT {
+                        dx: dx as i32,
+                        dy: dy as i32,
+                        mouseData: 0,
+                        dwFlags: MOUSEEVENTF_MOVE,
+                        time: 0,
+                        dwExtraInfo: INJECTED_EVENT_SIGNATURE,
+                    },
+                },
+            };
+            SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+        }
+        Ok(())
+    }
+
     async fn mouse_click(&self, button: u8) -> Result<()> {
         self.mouse_down(button).await?;
         tokio::time::sleep(Duration::from_millis(ServerConfig::MOUSE_CLICK_DELAY_MS)).await;
@@ -67,12 +124,7 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn mouse_down(&self, button: u8) -> Result<()> {
-        let flags = match button {
-            1 => MOUSEEVENTF_LEFTDOWN,
-            2 => MOUSEEVENTF_RIGHTDOWN,
-            3 => MOUSEEVENTF_MIDDLEDOWN,
-            _ => MOUSEEVENTF_LEFTDOWN,
-        };
+        let (flags, mouse_data) = Self::button_input(button, true);
 
         unsafe {
             let input = INPUT {
@@ -81,10 +133,10 @@ impl InputHandlerTrait for InputHandlerImpl {
                     mi: MOUSEINPUT {
                         dx: 0,
                         dy: 0,
-                        mouseData: 0,
+                        mouseData: mouse_data,
                         dwFlags: flags,
                         time: 0,
-                        dwExtraInfo: 0,
+                        dwExtraInfo: INJECTED_EVENT_SIGNATURE,
                     },
                 },
             };
@@ -94,12 +146,7 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn mouse_up(&self, button: u8) -> Result<()> {
-        let flags = match button {
-            1 => MOUSEEVENTF_LEFTUP,
-            2 => MOUSEEVENTF_RIGHTUP,
-            3 => MOUSEEVENTF_MIDDLEUP,
-            _ => MOUSEEVENTF_LEFTUP,
-        };
+        let (flags, mouse_data) = Self::button_input(button, false);
 
         unsafe {
             let input = INPUT {
@@ -108,10 +155,10 @@ impl InputHandlerTrait for InputHandlerImpl {
                     mi: MOUSEINPUT {
                         dx: 0,
                         dy: 0,
-                        mouseData: 0,
+                        mouseData: mouse_data,
                         dwFlags: flags,
                         time: 0,
-                        dwExtraInfo: 0,
+                        dwExtraInfo: INJECTED_EVENT_SIGNATURE,
                     },
                 },
             };
@@ -121,34 +168,35 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn mouse_scroll(&self, delta_x: f64, delta_y: f64) -> Result<()> {
+        let (wheel_x, wheel_y) = self.scroll.take(delta_x, delta_y);
         unsafe {
-            if delta_y != 0.0 {
+            if wheel_y != 0 {
                 let input = INPUT {
                     r#type: INPUT_MOUSE,
                     Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
                         mi: MOUSEINPUT {
                             dx: 0,
                             dy: 0,
-                            mouseData: (delta_y * 120.0) as i32 as u32,
+                            mouseData: wheel_y as i32 as u32,
                             dwFlags: MOUSEEVENTF_WHEEL,
                             time: 0,
-                            dwExtraInfo: 0,
+                            dwExtraInfo: INJECTED_EVENT_SIGNATURE,
                         },
                     },
                 };
                 SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
             }
-            if delta_x != 0.0 {
+            if wheel_x != 0 {
                 let input = INPUT {
                     r#type: INPUT_MOUSE,
                     Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
                         mi: MOUSEINPUT {
                             dx: 0,
                             dy: 0,
-                            mouseData: (delta_x * 120.0) as i32 as u32,
+                            mouseData: wheel_x as i32 as u32,
                             dwFlags: MOUSEEVENTF_HWHEEL,
                             time: 0,
-                            dwExtraInfo: 0,
+                            dwExtraInfo: INJECTED_EVENT_SIGNATURE,
                         },
                     },
                 };
@@ -159,371 +207,212 @@ impl InputHandlerTrait for InputHandlerImpl {
     }
 
     async fn key_press(&self, key: &str, modifiers: &ModifierKeys) -> Result<()> {
-        Self::apply_modifiers(&self.modifier_state, modifiers)?;
+        self.modifiers.apply(self, modifiers)?;
 
         if let Some(vk_code) = string_to_vk(key) {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VIRTUAL_KEY(vk_code),
-                            wScan: 0,
-                            dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
+            send_key(VIRTUAL_KEY(vk_code), KEYBD_EVENT_FLAGS(0u32));
         }
         Ok(())
     }
 
     async fn key_release(&self, key: &str, _modifiers: &ModifierKeys) -> Result<()> {
         if let Some(vk_code) = string_to_vk(key) {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VIRTUAL_KEY(vk_code),
-                            wScan: 0,
-                            dwFlags: KEYEVENTF_KEYUP,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
+            send_key(VIRTUAL_KEY(vk_code), KEYEVENTF_KEYUP);
         }
         Ok(())
     }
 
     async fn modifier_press(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
-            .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = true;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_CONTROL,
-                                wScan: 0,
-                                dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "alt" => {
-                state.alt = true;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_MENU,
-                                wScan: 0,
-                                dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "shift" => {
-                state.shift = true;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_SHIFT,
-                                wScan: 0,
-                                dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = true;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_LWIN,
-                                wScan: 0,
-                                dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+        self.modifiers.press(self, modifier)
+    }
+
+    async fn modifier_release(&self, modifier: &str) -> Result<()> {
+        self.modifiers.release(self, modifier)
+    }
+
+    async fn media_key(&self, key: MediaKey) -> Result<()> {
+        let vk = match key {
+            MediaKey::PlayPause => VK_MEDIA_PLAY_PAUSE,
+            MediaKey::NextTrack => VK_MEDIA_NEXT_TRACK,
+            MediaKey::PrevTrack => VK_MEDIA_PREV_TRACK,
+            MediaKey::VolumeUp => VK_VOLUME_UP,
+            MediaKey::VolumeDown => VK_VOLUME_DOWN,
+            MediaKey::Mute => VK_VOLUME_MUTE,
+        };
+        send_key(vk, KEYEVENTF_EXTENDEDKEY);
+        send_key(vk, KEYEVENTF_EXTENDEDKEY | KEYEVENTF_KEYUP);
+        Ok(())
+    }
+
+    async fn type_text(&self, text: &str) -> Result<()> {
+        let mut units = [0u16; 2];
+        for ch in text.chars() {
+            // Apps expect Return and Tab as keys, not as the characters they'd produce
+            let key = match ch {
+                '\n' => VK_RETURN,
+                '\t' => VK_TAB,
+                '\r' => continue,
+                _ => {
+                    for unit in ch.encode_utf16(&mut units) {
+                        send_keyboard(VIRTUAL_KEY(0), *unit, KEYEVENTF_UNICODE);
+                        send_keyboard(VIRTUAL_KEY(0), *unit, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP);
+                    }
+                    continue;
                 }
-            }
-            _ => {}
+            };
+            send_key(key, KEYBD_EVENT_FLAGS(0u32));
+            send_key(key, KEYEVENTF_KEYUP);
         }
         Ok(())
     }
 
-    async fn modifier_release(&self, modifier: &str) -> Result<()> {
-        let mut state = self
-            .modifier_state
-            .lock()
-            .expect("Modifier state mutex poisoned");
-        match modifier.to_lowercase().as_str() {
-            "ctrl" | "control" => {
-                state.ctrl = false;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_CONTROL,
-                                wScan: 0,
-                                dwFlags: KEYEVENTF_KEYUP,
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "alt" => {
-                state.alt = false;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_MENU,
-                                wScan: 0,
-                                dwFlags: KEYEVENTF_KEYUP,
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "shift" => {
-                state.shift = false;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_SHIFT,
-                                wScan: 0,
-                                dwFlags: KEYEVENTF_KEYUP,
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
-            }
-            "meta" | "super" | "cmd" => {
-                state.meta = false;
-                unsafe {
-                    let input = INPUT {
-                        r#type: INPUT_KEYBOARD,
-                        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                            ki: KEYBDINPUT {
-                                wVk: VK_LWIN,
-                                wScan: 0,
-                                dwFlags: KEYEVENTF_KEYUP,
-                                time: 0,
-                                dwExtraInfo: 0,
-                            },
-                        },
-                    };
-                    SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-                }
+    /// Puts touch contacts down around the pointer and moves them through the gesture, as a
+    /// touch screen would report fingers; apps that take touch zoom, turn and pan with them
+    async fn touch_gesture(&self, gesture: TouchGesture, duration_ms: u64) -> Result<()> {
+        init_touch_injection()?;
+        let centre = self
+            .pointer_position()
+            .or_else(Self::get_cursor_position)
+            .unwrap_or_else(|| pointer::movement_bounds().center());
+        let fingers = gesture.fingers();
+        let mut positions = gesture::contacts(&GestureFrame::START, fingers, centre);
+        inject_touch(
+            &positions,
+            POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        )?;
+        let mut moved = Ok(());
+        for frame in gesture::frames(&gesture, duration_ms) {
+            tokio::time::sleep(Duration::from_millis(ServerConfig::GESTURE_STEP_MS)).await;
+            positions = gesture::contacts(&frame, fingers, centre);
+            moved = inject_touch(
+                &positions,
+                POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+            );
+            if moved.is_err() {
+                break;
             }
-            _ => {}
         }
-        Ok(())
+        // Lifted even after a failed frame, so no finger stays on the screen
+        inject_touch(&positions, POINTER_FLAG_UP)?;
+        moved
+    }
+
+    fn pointer_position(&self) -> Option<(f64, f64)> {
+        *self
+            .current_pos
+            .lock()
+            .expect("Cursor position mutex poisoned")
     }
 }
 
-impl InputHandlerImpl {
-    fn apply_modifiers(state: &Mutex<ModifierKeys>, modifiers: &ModifierKeys) -> Result<()> {
-        let mut state_guard = state.lock().expect("Modifier state mutex poisoned");
+impl RawKeys for InputHandlerImpl {
+    type PlatformKey = VIRTUAL_KEY;
 
-        if modifiers.ctrl && !state_guard.ctrl {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_CONTROL,
-                            wScan: 0,
-                            dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.ctrl = true;
-        }
-        if modifiers.alt && !state_guard.alt {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_MENU,
-                            wScan: 0,
-                            dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.alt = true;
-        }
-        if modifiers.shift && !state_guard.shift {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_SHIFT,
-                            wScan: 0,
-                            dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.shift = true;
-        }
-        if modifiers.meta && !state_guard.meta {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_LWIN,
-                            wScan: 0,
-                            dwFlags: KEYBD_EVENT_FLAGS(0u32),
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.meta = true;
+    fn modifier_key(modifier: Modifier) -> VIRTUAL_KEY {
+        match modifier {
+            Modifier::Ctrl => VK_CONTROL,
+            Modifier::Alt => VK_MENU,
+            Modifier::Shift => VK_SHIFT,
+            Modifier::Meta => VK_LWIN,
         }
+    }
 
-        if !modifiers.ctrl && state_guard.ctrl {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_CONTROL,
-                            wScan: 0,
-                            dwFlags: KEYEVENTF_KEYUP,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.ctrl = false;
-        }
-        if !modifiers.alt && state_guard.alt {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_MENU,
-                            wScan: 0,
-                            dwFlags: KEYEVENTF_KEYUP,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.alt = false;
-        }
-        if !modifiers.shift && state_guard.shift {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_SHIFT,
-                            wScan: 0,
-                            dwFlags: KEYEVENTF_KEYUP,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.shift = false;
-        }
-        if !modifiers.meta && state_guard.meta {
-            unsafe {
-                let input = INPUT {
-                    r#type: INPUT_KEYBOARD,
-                    Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
-                        ki: KEYBDINPUT {
-                            wVk: VK_LWIN,
-                            wScan: 0,
-                            dwFlags: KEYEVENTF_KEYUP,
-                            time: 0,
-                            dwExtraInfo: 0,
-                        },
-                    },
-                };
-                SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
-            }
-            state_guard.meta = false;
-        }
+    fn press_raw(&self, key: VIRTUAL_KEY) -> Result<()> {
+        send_key(key, KEYBD_EVENT_FLAGS(0u32));
+        Ok(())
+    }
 
+    fn release_raw(&self, key: VIRTUAL_KEY) -> Result<()> {
+        send_key(key, KEYEVENTF_KEYUP);
         Ok(())
     }
 }
 
+/// Touch injection is set up once per process, for as many contacts as a gesture can have
+fn init_touch_injection() -> Result<()> {
+    static INITIALIZED: OnceLock<bool> = OnceLock::new();
+    let initialized = *INITIALIZED.get_or_init(|| unsafe {
+        InitializeTouchInjection(
+            u32::from(ServerConfig::GESTURE_MAX_FINGERS),
+            TOUCH_FEEDBACK_DEFAULT,
+        )
+        .is_ok()
+    });
+    match initialized {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("Touch injection isn't available")),
+    }
+}
+
+/// One frame of touch input, with a contact per finger at `positions`
+fn inject_touch(positions: &[(f64, f64)], flags: POINTER_FLAGS) -> Result<()> {
+    let contacts: Vec<POINTER_TOUCH_INFO> = positions
+        .iter()
+        .enumerate()
+        .map(|(id, &(x, y))| POINTER_TOUCH_INFO {
+            pointerInfo: POINTER_INFO {
+                pointerType: PT_TOUCH,
+                pointerId: id as u32,
+                pointerFlags: flags,
+                ptPixelLocation: POINT {
+                    x: x.round() as i32,
+                    y: y.round() as i32,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect();
+    unsafe { InjectTouchInput(&contacts)? };
+    Ok(())
+}
+
+fn send_key(key: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) {
+    send_keyboard(key, 0, flags);
+}
+
+/// `scan` carries a UTF-16 code unit when `flags` include `KEYEVENTF_UNICODE`
+fn send_keyboard(key: VIRTUAL_KEY, scan: u16, flags: KEYBD_EVENT_FLAGS) {
+    unsafe {
+        let input = INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: key,
+                    wScan: scan,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: INJECTED_EVENT_SIGNATURE,
+                },
+            },
+        };
+        SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+fn named_to_vk(key: NamedKey) -> u16 {
+    let vk = match key {
+        NamedKey::ArrowLeft => VK_LEFT,
+        NamedKey::ArrowRight => VK_RIGHT,
+        NamedKey::ArrowUp => VK_UP,
+        NamedKey::ArrowDown => VK_DOWN,
+        NamedKey::Home => VK_HOME,
+        NamedKey::End => VK_END,
+        NamedKey::PageUp => VK_PRIOR,
+        NamedKey::PageDown => VK_NEXT,
+        NamedKey::Escape => VK_ESCAPE,
+        NamedKey::Insert => VK_INSERT,
+        NamedKey::Delete => VK_DELETE,
+        // VK_F1 through VK_F24 are consecutive
+        NamedKey::Function(n) => return VK_F1.0 + u16::from(n - 1),
+    };
+    vk.0
+}
+
 fn string_to_vk(s: &str) -> Option<u16> {
+    if let Some(named) = NamedKey::parse(s) {
+        return Some(named_to_vk(named));
+    }
     match s {
         " " => Some(VK_SPACE.0),
         "\n" | "\r" => Some(VK_RETURN.0),
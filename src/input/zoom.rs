@@ -0,0 +1,157 @@
+//! Screen zoom that follows the remote pointer, so an audience can see what a presenter points at
+
+use crate::input::pointer::DesktopBounds;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_os = "linux")]
+use gnome as platform;
+
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+#[cfg(windows)]
+use win as platform;
+
+static FOLLOWING: AtomicBool = AtomicBool::new(false);
+
+/// Starts or stops panning the zoomed view to each injected pointer move
+pub fn follow_pointer(enabled: bool) -> Result<()> {
+    FOLLOWING.store(enabled, Ordering::Relaxed);
+    platform::set_enabled(enabled)
+}
+
+pub fn following_pointer() -> bool {
+    FOLLOWING.load(Ordering::Relaxed)
+}
+
+/// Pans the zoomed view so `point` stays in sight
+pub fn pan_to(point: (f64, f64)) -> Result<()> {
+    platform::pan_to(point)
+}
+
+/// Top-left corner of a view magnified `level` times that centres on `point`, kept on the desktop
+pub fn view_origin(point: (f64, f64), desktop: &DesktopBounds, level: f64) -> (f64, f64) {
+    let width = (desktop.right - desktop.left) / level;
+    let height = (desktop.bottom - desktop.top) / level;
+    let x = (point.0 - width / 2.0).clamp(desktop.left, desktop.right - width);
+    let y = (point.1 - height / 2.0).clamp(desktop.top, desktop.bottom - height);
+    (x, y)
+}
+
+/// GNOME's magnifier already tracks the real pointer, which injected motion moves
+#[cfg(target_os = "linux")]
+mod gnome {
+    use super::Result;
+
+    pub fn set_enabled(_enabled: bool) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn pan_to(_point: (f64, f64)) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Moves the Zoom accessibility view, at whatever level the user zoomed to
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::Result;
+    use anyhow::anyhow;
+
+    #[repr(C)]
+    struct CGRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    const ZOOM_FOCUS_TYPE_OTHER: u32 = 0;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn UAZoomEnabled() -> u8;
+        fn UAZoomChangeFocus(rect: *const CGRect, highlight: *const CGRect, kind: u32) -> i32;
+    }
+
+    pub fn set_enabled(_enabled: bool) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn pan_to(point: (f64, f64)) -> Result<()> {
+        unsafe {
+            if UAZoomEnabled() == 0 {
+                return Ok(());
+            }
+            let focus = CGRect {
+                x: point.0,
+                y: point.1,
+                width: 1.0,
+                height: 1.0,
+            };
+            let status = UAZoomChangeFocus(&focus, std::ptr::null(), ZOOM_FOCUS_TYPE_OTHER);
+            if status != 0 {
+                return Err(anyhow!("UAZoomChangeFocus failed: {}", status));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The Magnification API's full-screen transform, which zooms as well as pans
+#[cfg(windows)]
+mod win {
+    use super::{view_origin, Result};
+    use crate::domain::config::ServerConfig;
+    use crate::input::pointer;
+    use anyhow::anyhow;
+    use std::sync::Once;
+    use windows::Win32::UI::Magnification::{MagInitialize, MagSetFullscreenTransform};
+
+    static INITIALIZED: Once = Once::new();
+
+    pub fn set_enabled(enabled: bool) -> Result<()> {
+        INITIALIZED.call_once(|| unsafe {
+            if !MagInitialize().as_bool() {
+                log::warn!("Failed to initialize the Magnification API");
+            }
+        });
+        match enabled {
+            true => Ok(()),
+            false => transform(1.0, 0, 0),
+        }
+    }
+
+    pub fn pan_to(point: (f64, f64)) -> Result<()> {
+        let level = ServerConfig::ZOOM_FOLLOW_LEVEL;
+        let (x, y) = view_origin(point, &pointer::desktop_bounds(), level);
+        transform(level as f32, x.round() as i32, y.round() as i32)
+    }
+
+    fn transform(level: f32, x: i32, y: i32) -> Result<()> {
+        if unsafe { MagSetFullscreenTransform(level, x, y) }.as_bool() {
+            Ok(())
+        } else {
+            Err(anyhow!("MagSetFullscreenTransform failed"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_centres_on_point_but_stays_on_desktop() {
+        let desktop = DesktopBounds {
+            left: 0.0,
+            top: 0.0,
+            right: 1920.0,
+            bottom: 1080.0,
+        };
+        assert_eq!(view_origin((960.0, 540.0), &desktop, 2.0), (480.0, 270.0));
+        assert_eq!(view_origin((10.0, 1070.0), &desktop, 2.0), (0.0, 540.0));
+        assert_eq!(view_origin((500.0, 500.0), &desktop, 1.0), (0.0, 0.0));
+    }
+}
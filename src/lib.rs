@@ -0,0 +1,9 @@
+//! PointZerver: headless server for remote PC control from mobile devices
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod domain;
+pub mod features;
+pub mod input;
+pub mod status_server;
+pub mod utils;
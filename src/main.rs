@@ -1,43 +1,474 @@
-mod domain;
-mod features;
-mod input;
-mod status_server;
-mod utils;
-
 use anyhow::Result;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use pointzerver::domain::config::{secrets, ServerConfig, Settings};
+use pointzerver::features::audit::audit_log::AuditLog;
+use pointzerver::features::audit::webhook_service::WebhookService;
+use pointzerver::features::auth::pairing_qr::PairingPayload;
+use pointzerver::features::auth::pairing_service::PairingService;
+#[cfg(windows)]
+use pointzerver::features::auth::pairing_toast;
+use pointzerver::features::auth::secret_storage;
+use pointzerver::features::auth::token_store::TokenStore;
+use pointzerver::features::backup::config_bundle;
+use pointzerver::features::calibration::pointer_tuning::PointerTuning;
+use pointzerver::features::calibration::scroll_calibration::ScrollCalibration;
+use pointzerver::features::captions::caption_service::CaptionService;
+use pointzerver::features::clipboard::clipboard_service::ClipboardService;
+use pointzerver::features::command::command_service::{CommandContext, CommandService};
+use pointzerver::features::command::dispatch_queue::DispatchQueue;
+use pointzerver::features::command::policy::CommandPolicy;
+use pointzerver::features::cursor::cursor_service::CursorService;
+use pointzerver::features::diagnostics::soak_test::{self, SoakOptions};
+use pointzerver::features::diagnostics::support_bundle;
+use pointzerver::features::discovery::discovery_service::DiscoveryService;
+use pointzerver::features::events::event_bus::EventBus;
+use pointzerver::features::geofence::geofence_service::GeofenceService;
+use pointzerver::features::hot_corners::hot_corner_service::HotCornerService;
+use pointzerver::features::lifecycle::instance_lock;
+use pointzerver::features::lifecycle::readiness::Readiness;
+use pointzerver::features::lifecycle::shutdown::Shutdown;
+use pointzerver::features::macros::macro_recorder::MacroRecorder;
+use pointzerver::features::notifications::notification_service::NotificationService;
+use pointzerver::features::presence::desktop_session::DesktopSession;
+use pointzerver::features::presence::kill_switch::KillSwitch;
+use pointzerver::features::presence::presence_monitor::PresenceMonitor;
+use pointzerver::features::profile::profile_service::ProfileService;
+#[cfg(feature = "rendezvous")]
+use pointzerver::features::rendezvous::{relay, rendezvous_service::RendezvousService};
+#[cfg(target_os = "macos")]
+use pointzerver::features::service::login_item;
+#[cfg(windows)]
+use pointzerver::features::service::{agent, agent_link, installer, service_host};
+use pointzerver::features::session::client_registry::ClientRegistry;
+use pointzerver::features::session::session_registry::SessionRegistry;
+use pointzerver::features::stats::stats_registry::StatsRegistry;
+use pointzerver::features::streaming::preview_server::PreviewServer;
+use pointzerver::features::telemetry::telemetry_service::TelemetryService;
+use pointzerver::input::{pointer, InputHandler};
+use pointzerver::status_server::{self, StatusState};
+use pointzerver::utils::paths;
+
+/// Flags that may go anywhere on the command line, before or after a subcommand
+#[derive(Default)]
+struct GlobalFlags {
+    portable: bool,
+    takeover: bool,
+}
 
-use crate::features::command::command_service::CommandService;
-use crate::features::discovery::discovery_service::DiscoveryService;
+/// Splits the global flags from the subcommand and its arguments
+fn parse_args(args: impl IntoIterator<Item = String>) -> (GlobalFlags, Vec<String>) {
+    let mut flags = GlobalFlags::default();
+    let mut rest = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--portable" => flags.portable = true,
+            "--takeover" => flags.takeover = true,
+            _ => rest.push(arg),
+        }
+    }
+    (flags, rest)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    let (flags, args) = parse_args(std::env::args().skip(1));
+    if flags.portable {
+        paths::enable_portable()?;
+    }
+    init_logging();
 
+    if let Some(result) = run_config_command(&args) {
+        return result;
+    }
+    let command = args.first().map(String::as_str);
+    if command == Some("support-bundle") {
+        return support_bundle::run(args.get(1).map(PathBuf::from)).await;
+    }
+    if command == Some("soak") {
+        return soak_test::run(SoakOptions::parse(args[1..].iter().cloned())?).await;
+    }
+    #[cfg(feature = "rendezvous")]
+    if command == Some("relay") {
+        return run_relay(&args).await;
+    }
+    #[cfg(windows)]
+    if let Some(result) = run_windows_command(&args).await {
+        return result;
+    }
+    #[cfg(target_os = "macos")]
+    match command {
+        Some("install-loginitem") => return login_item::install(),
+        Some("uninstall-loginitem") => return login_item::uninstall(),
+        _ => paths::migrate_legacy_config_dir(),
+    }
+
+    serve(InputHandler::new()?, flags.takeover).await
+}
+
+async fn serve(mut input_handler: InputHandler, takeover: bool) -> Result<()> {
     log::info!("Starting PointZerver (headless mode)...");
+    let _instance = instance_lock::claim(takeover).await?;
+
+    if let Some(method) = secrets::unlock(&paths::secrets_file())? {
+        log::info!("Unlocked stored secrets with {:?}", method);
+    }
+    let settings = Arc::new(Settings::load_or_default(&paths::settings_file()));
+    input_handler.set_scroll(&settings.scroll);
+    let tokens = Arc::new(TokenStore::load(
+        paths::tokens_file(),
+        &paths::admin_token_file(),
+    )?);
+    let events = EventBus::new();
+    let stats = Arc::new(StatsRegistry::new());
+    let telemetry = Arc::new(TelemetryService::new(
+        settings.telemetry.clone(),
+        stats.clone(),
+    ));
+
+    let captions = CaptionService::new(settings.captions.clone(), events.clone());
+    let notifications = NotificationService::new(settings.notifications.clone(), events.clone());
+
+    let injects_locally = input_handler.injects_locally();
+    let pairing_service = Arc::new(PairingService::new(tokens.clone(), events.clone()));
+    let geofence = Arc::new(GeofenceService::new(
+        settings.geofence.clone(),
+        events.clone(),
+    ));
+    geofence.check().await;
+    let presence = Arc::new(PresenceMonitor::new(
+        settings.local_input.clone(),
+        events.clone(),
+    ));
+    let desktop_session = Arc::new(DesktopSession::new(events.clone()));
+    let kill_switch = Arc::new(KillSwitch::new(events.clone()));
+    let audit = Arc::new(AuditLog::new(
+        settings.audit.clone(),
+        paths::audit_log_file(),
+    ));
+    let profiles = Arc::new(ProfileService::new(
+        settings.profiles.clone(),
+        events.clone(),
+    ));
+    let macros = Arc::new(MacroRecorder::new(
+        settings.macros.clone(),
+        paths::settings_file(),
+        events.clone(),
+        injects_locally,
+    ));
+    let queue = Arc::new(DispatchQueue::new(settings.latency.stale_move_budget()));
+    let sessions = Arc::new(SessionRegistry::new());
+    let clients = Arc::new(ClientRegistry::new(
+        settings.heartbeat.clone(),
+        settings.control.mode,
+    ));
 
-    let input_handler = input::InputHandler::new()?;
-    let discovery_service = DiscoveryService::new().await?;
-    let command_service = CommandService::new(input_handler).await?;
+    let policy = Arc::new(CommandPolicy::new(
+        tokens.clone(),
+        geofence.clone(),
+        presence.clone(),
+        desktop_session.clone(),
+        kill_switch.clone(),
+        profiles.clone(),
+        &settings,
+    ));
 
-    spawn_discovery_service(discovery_service);
-    spawn_status_server();
+    // Up first so `/health` can report "starting" while the rest comes up
+    let readiness = Readiness::new();
+    let shutdown = Shutdown::new();
+    spawn_status_server(StatusState {
+        settings: settings.clone(),
+        tokens: tokens.clone(),
+        pairing: pairing_service.clone(),
+        policy: policy.clone(),
+        geofence: geofence.clone(),
+        telemetry: telemetry.clone(),
+        queue: queue.clone(),
+        stats: stats.clone(),
+        sessions: sessions.clone(),
+        clients: clients.clone(),
+        desktop_session: desktop_session.clone(),
+        profiles: profiles.clone(),
+        macros: macros.clone(),
+        readiness: readiness.clone(),
+        shutdown: shutdown.clone(),
+        events: events.clone(),
+        audit: audit.clone(),
+    });
+
+    // A Windows service would capture session 0, not the user's desktop
+    let preview = injects_locally.then(|| {
+        PreviewServer::new(
+            &settings,
+            tokens.clone(),
+            policy.clone(),
+            kill_switch.clone(),
+        )
+    });
 
+    let clipboard_service = ClipboardService::new(&settings.clipboard_sync).await?;
+    let command_service = CommandService::new(
+        input_handler,
+        clipboard_service,
+        policy,
+        CommandContext {
+            settings: settings.clone(),
+            pairing: pairing_service.clone(),
+            tokens,
+            stats,
+            presence: presence.clone(),
+            kill_switch: kill_switch.clone(),
+            audit: audit.clone(),
+            calibration: Arc::new(ScrollCalibration::new(
+                settings.scroll.clone(),
+                paths::settings_file(),
+            )),
+            tuning: Arc::new(PointerTuning::new(
+                settings.pointer_tuning.clone(),
+                paths::settings_file(),
+                events.clone(),
+            )),
+            macros,
+            profiles,
+            queue,
+            sessions,
+            clients,
+            events: events.clone(),
+        },
+    )
+    .await?;
+    #[cfg(feature = "rendezvous")]
+    let command_service = attach_rendezvous(command_service, &settings).await;
+    let preview_port =
+        (preview.is_some() && settings.preview.enabled).then_some(ServerConfig::PREVIEW_PORT);
+    let discovery_service = DiscoveryService::new(settings.network.clone(), preview_port).await?;
+
+    spawn_discovery_service(discovery_service, readiness.clone());
+    spawn_geofence(geofence);
+    spawn_telemetry(telemetry);
+    spawn_webhooks(WebhookService::new(
+        settings.webhooks.clone(),
+        events.clone(),
+    ));
+    spawn_presence_monitor(presence);
+    if let Some(preview) = preview {
+        spawn_preview_server(preview);
+    }
+    #[cfg(windows)]
+    if !injects_locally {
+        let desktop_session = desktop_session.clone();
+        service_host::watch_sessions(move |state| desktop_session.observe(state));
+    }
+    if injects_locally {
+        desktop_session.watch();
+        pointer::follow_display_changes();
+    }
+    // The hotkey has to be pressed at this desktop, which a Windows service can't listen to
+    if injects_locally && settings.local_input.kill_switch {
+        kill_switch.watch();
+    }
+    spawn_captions(captions);
+    spawn_notifications(notifications);
+    #[cfg(windows)]
+    if injects_locally {
+        spawn_pairing_toasts(pairing_service.clone(), events);
+    }
+    spawn_cursor_restore(command_service.cursor());
+    spawn_hot_corners(command_service.hot_corners());
+
+    // The input backend, auth and command socket are all up; let clients find us
+    readiness.mark_ready();
     log::info!("PointZerver ready - discovery and command services running");
+    print_pairing_banner(&pairing_service);
+
+    tokio::select! {
+        result = command_service.run() => result,
+        _ = shutdown.requested() => {
+            log::info!("PointZerver shutting down");
+            Ok(())
+        }
+    }
+}
+
+/// Shows the pairing QR code when someone is watching, e.g. over SSH on a headless box
+fn print_pairing_banner(pairing: &PairingService) {
+    if !std::io::stdout().is_terminal() {
+        return;
+    }
+    let payload = PairingPayload::current(pairing);
+    match payload.to_terminal() {
+        Ok(code) => println!(
+            "\n{}\nScan with PointZ to pair with {} (PIN {})\n",
+            code, payload.hostname, payload.pin
+        ),
+        Err(e) => log::warn!("Failed to render pairing QR code: {}", e),
+    }
+}
+
+/// Logs to stderr, or to a file beside the executable in portable mode
+fn init_logging() {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(path) = paths::log_file() {
+        let file = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+            });
+        match file {
+            Ok(file) => {
+                builder.target(env_logger::Target::Pipe(Box::new(file)));
+            }
+            Err(e) => eprintln!(
+                "Logging to stderr, {} is not writable: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    builder.init();
+}
+
+/// `export-config` / `import-config <bundle>`, moving settings and pairings between machines,
+/// and `encrypt-secrets <method>`
+fn run_config_command(args: &[String]) -> Option<Result<()>> {
+    let command = args.first()?;
+    if command == "encrypt-secrets" {
+        return Some(match args.get(1) {
+            Some(method) => method.parse().and_then(secret_storage::encrypt),
+            None => Err(anyhow::anyhow!(
+                "Usage: pointzerver encrypt-secrets <passphrase|keychain|tpm>"
+            )),
+        });
+    }
+    let run = match command.as_str() {
+        "export-config" => config_bundle::export,
+        "import-config" => config_bundle::import,
+        _ => return None,
+    };
+    Some(match args.get(1) {
+        Some(bundle) => run(Path::new(bundle)),
+        None => Err(anyhow::anyhow!(
+            "Usage: pointzerver {} <bundle.pzb>",
+            command
+        )),
+    })
+}
+
+/// Service management and the two halves of the Windows service
+#[cfg(windows)]
+async fn run_windows_command(args: &[String]) -> Option<Result<()>> {
+    let command = args.first()?;
+    Some(match command.as_str() {
+        "install-service" => installer::install(),
+        "uninstall-service" => installer::uninstall(),
+        "service" => service_host::run(serve_as_service).await,
+        "agent" => match args.get(1) {
+            Some(pipe_name) => agent::run(pipe_name).await,
+            None => Err(anyhow::anyhow!("Usage: pointzerver agent <pipe>")),
+        },
+        _ => return None,
+    })
+}
 
-    command_service.run().await
+#[cfg(windows)]
+fn serve_as_service() -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>>>> {
+    Box::pin(async {
+        service_host::use_machine_config_dir()?;
+        serve(InputHandler::via_agent(agent_link::install())?, false).await
+    })
 }
 
-fn spawn_discovery_service(discovery_service: DiscoveryService) {
+/// `pointzerver relay [bind-addr]` runs only the rendezvous relay
+#[cfg(feature = "rendezvous")]
+async fn run_relay(args: &[String]) -> Result<()> {
+    let bind = match args.get(1) {
+        Some(addr) => addr.parse()?,
+        None => ([0, 0, 0, 0], ServerConfig::RENDEZVOUS_RELAY_PORT).into(),
+    };
+    relay::run(bind).await
+}
+
+#[cfg(feature = "rendezvous")]
+async fn attach_rendezvous(
+    mut command_service: CommandService,
+    settings: &Settings,
+) -> CommandService {
+    if !settings.rendezvous.enabled {
+        return command_service;
+    }
+    match RendezvousService::new(&settings.rendezvous, command_service.socket()).await {
+        Ok(rendezvous) => {
+            let rendezvous = Arc::new(rendezvous);
+            command_service.set_rendezvous(rendezvous.clone());
+            tokio::spawn(async move { rendezvous.run().await });
+        }
+        Err(e) => log::error!("Rendezvous disabled: {}", e),
+    }
+    command_service
+}
+
+fn spawn_discovery_service(discovery_service: DiscoveryService, readiness: Readiness) {
     tokio::spawn(async move {
-        if let Err(e) = discovery_service.run().await {
+        if let Err(e) = discovery_service.run(readiness).await {
             log::error!("Discovery loop error: {}", e);
         }
     });
 }
 
-fn spawn_status_server() {
+fn spawn_geofence(geofence: Arc<GeofenceService>) {
+    tokio::spawn(async move { geofence.run().await });
+}
+
+fn spawn_telemetry(telemetry: Arc<TelemetryService>) {
+    tokio::spawn(async move { telemetry.run().await });
+}
+
+fn spawn_webhooks(webhooks: WebhookService) {
+    tokio::spawn(async move { webhooks.run().await });
+}
+
+fn spawn_preview_server(preview: PreviewServer) {
+    tokio::spawn(async move {
+        if let Err(e) = preview.run().await {
+            log::error!("Screen preview error: {}", e);
+        }
+    });
+}
+
+fn spawn_presence_monitor(presence: Arc<PresenceMonitor>) {
+    tokio::spawn(async move { presence.run().await });
+}
+
+fn spawn_captions(captions: CaptionService) {
+    tokio::spawn(async move { captions.run().await });
+}
+
+fn spawn_notifications(notifications: NotificationService) {
+    tokio::spawn(async move { notifications.run().await });
+}
+
+#[cfg(windows)]
+fn spawn_pairing_toasts(pairing: Arc<PairingService>, events: EventBus) {
+    tokio::spawn(async move { pairing_toast::run(pairing, events).await });
+}
+
+fn spawn_cursor_restore(cursor: Arc<CursorService>) {
+    tokio::spawn(async move { cursor.run().await });
+}
+
+fn spawn_hot_corners(hot_corners: Arc<HotCornerService>) {
+    tokio::spawn(async move { hot_corners.run().await });
+}
+
+fn spawn_status_server(state: StatusState) {
     tokio::spawn(async move {
-        if let Err(e) = status_server::run().await {
+        if let Err(e) = status_server::run(state).await {
             log::error!("Status server error: {}", e);
         }
     });
@@ -1,12 +1,76 @@
 use anyhow::Result;
-use axum::{routing::get, Json, Router};
-use serde::Serialize;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use axum::routing::{delete, get, post};
+use axum::Extension;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::domain::config::ServerConfig;
+use crate::domain::config::{ServerConfig, Settings};
+use crate::domain::models::{
+    AlternateEndpoint, CommandEnvelope, DisplayInfo, MacroStep, ProtocolSchema, Scope, ServerEvent,
+};
+use crate::features::audit::audit_log::{AuditEntry, AuditLog};
+use crate::features::auth::command_signing::signer_id;
+use crate::features::auth::pairing_lockout::LockoutInfo;
+use crate::features::auth::pairing_qr::PairingPayload;
+use crate::features::auth::pairing_requests::PairingRequestInfo;
+use crate::features::auth::pairing_service::PairingService;
+use crate::features::auth::token_store::{DeviceInfo, Role, TokenStore};
+use crate::features::command::dispatch_queue::{DispatchQueue, LatencyPercentiles, QueueSnapshot};
+use crate::features::command::policy::CommandPolicy;
+use crate::features::command::simulation::{self, Simulation};
+#[cfg(all(feature = "profiling", unix))]
+use crate::features::diagnostics::process_profile::Flamegraph;
+use crate::features::diagnostics::process_profile::{self, ProcessProfile};
+use crate::features::discovery::overlay;
+use crate::features::events::event_bus::EventBus;
+use crate::features::geofence::geofence_service::{GeofenceService, GeofenceStatus};
+use crate::features::lifecycle::readiness::{Phase, Readiness};
+use crate::features::lifecycle::shutdown::Shutdown;
+use crate::features::macros::macro_recorder::{MacroRecorder, MacroRecording, RecordError};
+use crate::features::presence::desktop_session::DesktopSession;
+use crate::features::profile::profile_service::{ProfileList, ProfileService};
+use crate::features::session::client_registry::{ClientRegistry, ControlStatus};
+use crate::features::session::session_registry::{SessionInfo, SessionRegistry};
+use crate::features::stats::stats_registry::{ClientActivity, CommandRates, StatsRegistry};
+use crate::features::telemetry::telemetry_service::{TelemetryPreview, TelemetryService};
+use crate::input::desktop_session::SessionState;
+use crate::input::pointer;
+use crate::input::signature::INJECTED_EVENT_SIGNATURE;
 use crate::utils;
 
-const STATUS_PORT: u16 = 45460;
+/// Shared handles the status routes read from
+#[derive(Clone)]
+pub struct StatusState {
+    pub settings: Arc<Settings>,
+    pub tokens: Arc<TokenStore>,
+    pub pairing: Arc<PairingService>,
+    pub policy: Arc<CommandPolicy>,
+    pub geofence: Arc<GeofenceService>,
+    pub telemetry: Arc<TelemetryService>,
+    pub queue: Arc<DispatchQueue>,
+    pub stats: Arc<StatsRegistry>,
+    pub sessions: Arc<SessionRegistry>,
+    pub clients: Arc<ClientRegistry>,
+    pub desktop_session: Arc<DesktopSession>,
+    pub profiles: Arc<ProfileService>,
+    pub macros: Arc<MacroRecorder>,
+    pub readiness: Readiness,
+    pub shutdown: Shutdown,
+    pub events: EventBus,
+    pub audit: Arc<AuditLog>,
+}
 
 #[derive(Serialize)]
 pub struct ServerStatus {
@@ -14,18 +78,175 @@ pub struct ServerStatus {
     ip: Option<String>,
     discovery_port: u16,
     command_port: u16,
+    command_tcp_port: Option<u16>,
+    alternate_endpoints: Vec<AlternateEndpoint>,
     app_download_url: String,
+    pairing_lockouts: Vec<LockoutInfo>,
+    geofence: GeofenceStatus,
+    desktop_session: SessionState,
+    profile: Option<String>,
+    /// Each display's rectangle in the coordinates pointer motion moves through
+    displays: Vec<DisplayInfo>,
+    injected_event_signature: usize,
+}
+
+/// Compact numbers for the web panel's charts
+#[derive(Serialize)]
+pub struct StatsReport {
+    uptime_secs: u64,
+    /// Commands per second by kind
+    rates: BTreeMap<&'static str, CommandRates>,
+    drops: DropCounts,
+    /// Sessions heard from in the last minute
+    active_clients: usize,
+    queue_depth: usize,
+    dispatch_latency: Option<LatencyPercentiles>,
+}
+
+/// Commands refused, dropped or merged on the way to injection, since startup
+#[derive(Serialize)]
+pub struct DropCounts {
+    denied: u64,
+    malformed: u64,
+    invalid: u64,
+    /// Duplicates and commands that arrived after their gap was given up on
+    replayed: u64,
+    /// Held back for a late predecessor; run, but not in arrival order
+    reordered: u64,
+    /// Signed datagrams with an unknown signer or a bad MAC
+    forged: u64,
+    /// From addresses the network allow and deny lists keep out
+    blocked: u64,
+    /// From clients sending faster than the rate limit allows
+    throttled: u64,
+    queue_full: u64,
+    stale_moves: u64,
+    coalesced_moves: u64,
+}
+
+#[derive(Deserialize)]
+pub struct GuestRequest {
+    #[serde(default = "default_guest_label")]
+    label: String,
+    #[serde(default = "default_guest_minutes")]
+    minutes: u64,
+    #[serde(default = "default_guest_scopes")]
+    scopes: Vec<Scope>,
+}
+
+/// Scopes to limit a paired device to; `null` grants every scope again
+#[derive(Deserialize)]
+pub struct ClientScopesRequest {
+    #[serde(default)]
+    scopes: Option<Vec<Scope>>,
+}
+
+#[derive(Deserialize)]
+pub struct SetProfileRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct RecordMacroRequest {
+    name: String,
+    #[serde(default = "default_macro_secs")]
+    secs: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileRequest {
+    #[serde(default = "default_profile_secs")]
+    seconds: u64,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    #[serde(default = "default_audit_limit")]
+    limit: usize,
+    /// Only entries from the token with this id
+    #[serde(default)]
+    client: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GuestToken {
+    token: String,
+    expires_at: u64,
+}
+
+#[derive(Deserialize)]
+pub struct ObserverRequest {
+    #[serde(default = "default_observer_label")]
+    label: String,
+}
+
+/// A token holder, and whether it answered the server's last probe
+#[derive(Serialize)]
+pub struct ClientListing {
+    #[serde(flatten)]
+    device: DeviceInfo,
+    /// `None` until the client has sent a command since the server started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reachable: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct ObserverToken {
+    token: String,
 }
 
-pub async fn run() -> Result<()> {
-    let cors = CorsLayer::new().allow_origin(Any).allow_methods(Any);
+pub async fn run(state: StatusState) -> Result<()> {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let authenticated = Router::new()
+        .route("/clients", get(get_clients))
+        .route(
+            "/clients/:id",
+            delete(revoke_client).patch(set_client_scopes),
+        )
+        .route("/clients/:id/activity", get(get_client_activity))
+        .route("/sessions", get(get_sessions))
+        .route("/audit", get(get_audit))
+        .route("/control", get(get_control))
+        .route("/control/release", post(release_control))
+        .route("/events", get(get_events))
+        .route("/settings", get(get_settings))
+        .route("/profiles", get(get_profiles))
+        .route("/profiles/active", post(set_profile))
+        .route("/macros", get(get_macros))
+        .route("/macros/record", post(record_macro))
+        .route("/macros/record/stop", post(stop_macro))
+        .route("/macros/:name", delete(delete_macro))
+        .route("/simulate", post(simulate))
+        .route("/guests", post(mint_guest))
+        .route("/observers", post(mint_observer))
+        .route("/qrcode", get(get_qrcode))
+        .route("/pairing/requests", get(get_pairing_requests))
+        .route("/pairing/requests/:id/approve", post(approve_pairing))
+        .route("/pairing/requests/:id/deny", post(deny_pairing))
+        .route("/geofence/enable", post(enable_commands))
+        .route("/shutdown", post(shutdown))
+        .route("/stats", get(get_stats))
+        .route("/debug/queue", get(get_queue))
+        .route("/debug/profile", get(get_profile));
+    #[cfg(all(feature = "profiling", unix))]
+    let authenticated = authenticated.route("/debug/flamegraph", get(get_flamegraph));
+    let authenticated =
+        authenticated.route_layer(middleware::from_fn_with_state(state.clone(), require_token));
 
     let app = Router::new()
         .route("/status", get(get_status))
         .route("/health", get(health_check))
-        .layer(cors);
+        .route("/schema", get(get_schema))
+        .route("/telemetry", get(get_telemetry))
+        .merge(authenticated)
+        .layer(cors)
+        .with_state(state);
 
-    let addr = format!("127.0.0.1:{}", STATUS_PORT);
+    let addr = format!("127.0.0.1:{}", ServerConfig::STATUS_PORT);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
     log::info!("Status server listening on http://{}", addr);
@@ -35,16 +256,529 @@ pub async fn run() -> Result<()> {
     Ok(())
 }
 
-async fn get_status() -> Json<ServerStatus> {
+/// Rejects requests without a bearer token issued at pairing time
+async fn require_token(
+    State(state): State<StatusState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let holder = token
+        .and_then(|token| state.tokens.validate(token))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    // Observers only watch, so nothing that changes state is open to them
+    if holder.role == Role::Observer && request.method() != Method::GET {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut request = request;
+    request.extensions_mut().insert(holder);
+    Ok(next.run(request).await)
+}
+
+async fn get_status(State(state): State<StatusState>) -> Json<ServerStatus> {
     Json(ServerStatus {
         hostname: utils::get_hostname(),
         ip: utils::get_local_ip().map(|ip| ip.to_string()),
         discovery_port: ServerConfig::DISCOVERY_PORT,
         command_port: ServerConfig::COMMAND_PORT,
+        command_tcp_port: state
+            .settings
+            .network
+            .tcp
+            .then_some(ServerConfig::COMMAND_TCP_PORT),
+        alternate_endpoints: overlay::overlay_endpoints(),
         app_download_url: "https://github.com/qol-tools/pointz/releases/latest".to_string(),
+        pairing_lockouts: state.pairing.lockouts(),
+        geofence: state.geofence.status(),
+        desktop_session: state.desktop_session.state(),
+        profile: state.profiles.active_name(),
+        displays: pointer::display_info(),
+        injected_event_signature: INJECTED_EVENT_SIGNATURE,
     })
 }
 
-async fn health_check() -> &'static str {
-    "ok"
+/// `503 starting` until clients can connect, then `200 ready`
+async fn health_check(State(state): State<StatusState>) -> (StatusCode, &'static str) {
+    match state.readiness.phase() {
+        Phase::Starting => (StatusCode::SERVICE_UNAVAILABLE, "starting"),
+        Phase::Ready => (StatusCode::OK, "ready"),
+    }
+}
+
+async fn get_schema() -> Json<ProtocolSchema> {
+    Json(ProtocolSchema::generate())
+}
+
+/// Exactly what telemetry would send, so users can inspect it before opting in
+async fn get_telemetry(State(state): State<StatusState>) -> Json<TelemetryPreview> {
+    Json(state.telemetry.preview())
+}
+
+async fn get_clients(State(state): State<StatusState>) -> Json<Vec<ClientListing>> {
+    let reachability = state.sessions.reachability();
+    let clients = state
+        .tokens
+        .devices()
+        .into_iter()
+        .map(|device| ClientListing {
+            reachable: reachability.get(&device.id).copied(),
+            device,
+        })
+        .collect();
+    Json(clients)
+}
+
+/// Revokes a paired device's or guest's token, for a lost phone; host tools only
+async fn revoke_client(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(id): Path<String>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    match state.tokens.revoke(&id) {
+        Ok(Some(device)) => {
+            log::info!("Revoked the token of '{}'", device.device_name);
+            state.events.publish(ServerEvent::DeviceRevoked {
+                device_name: device.device_name,
+            });
+            StatusCode::NO_CONTENT
+        }
+        Ok(None) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            log::error!("Failed to revoke token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Commands a client sent over the last day, bucketed for a timeline; host tools only
+async fn get_client_activity(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(id): Path<String>,
+) -> Result<Json<ClientActivity>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !state.tokens.devices().iter().any(|device| device.id == id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(state.stats.activity(&id)))
+}
+
+/// Narrows what a paired device may do, such as a presentation remote limited to `pointer`
+/// and `keyboard`; takes effect from its next command
+async fn set_client_scopes(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(id): Path<String>,
+    Json(request): Json<ClientScopesRequest>,
+) -> Result<Json<DeviceInfo>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match state.tokens.set_scopes(&id, request.scopes) {
+        Ok(Some(device)) => {
+            log::info!(
+                "Set the scopes of '{}' to {:?}",
+                device.device_name,
+                device.scopes
+            );
+            Ok(Json(device))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            log::error!("Failed to set token scopes: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_sessions(State(state): State<StatusState>) -> Json<Vec<SessionInfo>> {
+    Json(state.sessions.sessions())
+}
+
+async fn get_control(State(state): State<StatusState>) -> Json<ControlStatus> {
+    Json(state.clients.status(Instant::now()))
+}
+
+/// Frees control from a client that claimed it and won't let go
+async fn release_control(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Result<Json<ControlStatus>, StatusCode> {
+    if holder.role == Role::Guest {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if let Some(addr) = state.clients.revoke_control() {
+        log::info!("Took control away from {} through the status API", addr);
+        state
+            .events
+            .publish(ServerEvent::ControllerChanged { addr: None });
+    }
+    Ok(Json(state.clients.status(Instant::now())))
+}
+
+async fn get_settings(State(state): State<StatusState>) -> Json<serde_json::Value> {
+    Json(state.settings.redacted())
+}
+
+async fn get_events(
+    State(state): State<StatusState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(|event| {
+        let event = event.ok()?;
+        Event::default().json_data(event).ok().map(Ok)
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn get_profiles(State(state): State<StatusState>) -> Json<ProfileList> {
+    Json(state.profiles.list())
+}
+
+/// Switches profile from the tray menu
+async fn set_profile(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Json(request): Json<SetProfileRequest>,
+) -> Result<Json<ProfileList>, StatusCode> {
+    if holder.role == Role::Guest {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    state
+        .profiles
+        .activate(&request.name)
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(Json(state.profiles.list()))
+}
+
+async fn get_macros(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Result<Json<BTreeMap<String, Vec<MacroStep>>>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Json(state.macros.saved()))
+}
+
+/// Asks the person at the desk to allow recording a macro from local input
+async fn record_macro(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Json(request): Json<RecordMacroRequest>,
+) -> Result<Json<MacroRecording>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let recording = state
+        .macros
+        .start(&request.name, request.secs)
+        .map_err(|e| match e {
+            RecordError::NoName => StatusCode::BAD_REQUEST,
+            RecordError::Busy => StatusCode::CONFLICT,
+            RecordError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        })?;
+    log::info!(
+        "Macro '{}' recording requested by {}",
+        recording.name,
+        holder.device_name
+    );
+    Ok(Json(recording))
+}
+
+async fn stop_macro(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    match state.macros.stop() {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn delete_macro(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    match state.macros.delete(&name) {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Dry run of a command for client developers: policy decision and the exact injected events
+///
+/// Only the admin token may simulate with someone else's token, so the result can't be used to
+/// check whether a guessed token is valid.
+async fn simulate(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Json(envelope): Json<CommandEnvelope>,
+) -> Result<Json<Simulation>, StatusCode> {
+    let own_token = envelope
+        .auth
+        .as_deref()
+        .is_none_or(|token| signer_id(token) == holder.id);
+    if holder.role != Role::Admin && !own_token {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Json(simulation::simulate(
+        &envelope,
+        &state.policy,
+        &state.profiles,
+    )))
+}
+
+/// Mints a time-limited guest token; guests cannot mint further guests
+async fn mint_guest(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Json(request): Json<GuestRequest>,
+) -> Result<Json<GuestToken>, StatusCode> {
+    if holder.role == Role::Guest {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let minutes = request
+        .minutes
+        .clamp(1, ServerConfig::GUEST_TOKEN_MAX_MINUTES);
+    let (token, expires_at) = state
+        .tokens
+        .issue_guest(&request.label, minutes, holder.delegable(request.scopes))
+        .map_err(|e| {
+            log::error!("Failed to mint guest token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    log::info!(
+        "Minted guest token '{}' for {} minute(s)",
+        request.label,
+        minutes
+    );
+    Ok(Json(GuestToken { token, expires_at }))
+}
+
+/// Devices waiting to pair, for the web panel and tray to show with Approve/Deny
+async fn get_pairing_requests(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Result<Json<Vec<PairingRequestInfo>>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Json(state.pairing.pending_requests()))
+}
+
+/// The pairing QR code as SVG, carrying the current PIN
+async fn get_qrcode(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let svg = PairingPayload::current(&state.pairing)
+        .to_svg()
+        .map_err(|e| {
+            log::error!("Failed to render pairing QR code: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
+
+/// Pairs the requesting device and sends it its token; host tools only
+async fn approve_pairing(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(id): Path<u64>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    match state.pairing.approve(id) {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn deny_pairing(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Path(id): Path<u64>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    match state.pairing.deny(id) {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Mints a read-only token for a wall-mounted display or similar
+async fn mint_observer(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Json(request): Json<ObserverRequest>,
+) -> Result<Json<ObserverToken>, StatusCode> {
+    if holder.role == Role::Guest {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let token = state.tokens.issue_observer(&request.label).map_err(|e| {
+        log::error!("Failed to mint observer token: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    log::info!("Minted observer token '{}'", request.label);
+    Ok(Json(ObserverToken { token }))
+}
+
+/// Resumes command processing after the geofence suspended it
+async fn enable_commands(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> Result<Json<GeofenceStatus>, StatusCode> {
+    if holder.role == Role::Guest {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    state.geofence.enable();
+    Ok(Json(state.geofence.status()))
+}
+
+/// Stops the server so a new instance can take over; local tools only
+async fn shutdown(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+) -> StatusCode {
+    if holder.role != Role::Admin {
+        return StatusCode::FORBIDDEN;
+    }
+    log::info!("Shutdown requested through the status API");
+    state.shutdown.request();
+    StatusCode::ACCEPTED
+}
+
+/// Rates, drops and latency in one poll, for dashboards without a metrics stack
+async fn get_stats(State(state): State<StatusState>) -> Json<StatsReport> {
+    let stats = state.stats.snapshot();
+    let queue = state.queue.snapshot();
+    let active_clients = state
+        .sessions
+        .sessions()
+        .iter()
+        .filter(|session| session.idle_secs < ServerConfig::STATS_ACTIVE_CLIENT_SECS)
+        .count();
+    Json(StatsReport {
+        uptime_secs: stats.uptime_secs,
+        rates: state.stats.rates(),
+        drops: DropCounts {
+            denied: stats.denied,
+            malformed: stats.malformed,
+            invalid: stats.invalid,
+            replayed: stats.replayed,
+            reordered: stats.reordered,
+            forged: stats.forged,
+            blocked: stats.blocked,
+            throttled: stats.throttled,
+            queue_full: queue.dropped,
+            stale_moves: queue.discarded_stale,
+            coalesced_moves: queue.coalesced_moves,
+        },
+        active_clients,
+        queue_depth: queue.depth,
+        dispatch_latency: state.queue.latency_percentiles(),
+    })
+}
+
+/// Queue depth and dispatch latency, to tell network lag from server-side lag
+async fn get_queue(State(state): State<StatusState>) -> Json<QueueSnapshot> {
+    Json(state.queue.snapshot())
+}
+
+/// CPU, memory and runtime counters sampled over `?seconds=` (default 1, max 10)
+async fn get_profile(
+    Query(request): Query<ProfileRequest>,
+) -> Result<Json<ProcessProfile>, StatusCode> {
+    let seconds = request.seconds.clamp(1, ServerConfig::PROFILE_MAX_SECS);
+    process_profile::sample(Duration::from_secs(seconds))
+        .await
+        .map(Json)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// CPU flamegraph over `seconds`, as SVG; 204 if the server was idle throughout, 409 while
+/// another capture is running
+#[cfg(all(feature = "profiling", unix))]
+async fn get_flamegraph(Query(request): Query<ProfileRequest>) -> Response {
+    use axum::response::IntoResponse;
+
+    let seconds = request.seconds.clamp(1, ServerConfig::PROFILE_MAX_SECS);
+    match process_profile::flamegraph(Duration::from_secs(seconds)).await {
+        Ok(Flamegraph::Svg(svg)) => {
+            ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response()
+        }
+        Ok(Flamegraph::Idle) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Flamegraph::Busy) => StatusCode::CONFLICT.into_response(),
+        Err(e) => {
+            log::warn!("Failed to render flamegraph: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Latest audit log entries, newest first; admin token only
+async fn get_audit(
+    State(state): State<StatusState>,
+    Extension(holder): Extension<DeviceInfo>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, StatusCode> {
+    if holder.role != Role::Admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let limit = query.limit.clamp(1, ServerConfig::AUDIT_QUERY_MAX);
+    Ok(Json(state.audit.recent(limit, query.client.as_deref())))
+}
+
+fn default_guest_label() -> String {
+    "Guest".to_string()
+}
+
+fn default_observer_label() -> String {
+    "Observer".to_string()
+}
+
+fn default_guest_minutes() -> u64 {
+    ServerConfig::GUEST_TOKEN_DEFAULT_MINUTES
+}
+
+fn default_audit_limit() -> usize {
+    ServerConfig::AUDIT_QUERY_DEFAULT
+}
+
+fn default_macro_secs() -> u64 {
+    ServerConfig::MACRO_DEFAULT_MAX_SECS
+}
+
+fn default_profile_secs() -> u64 {
+    ServerConfig::PROFILE_DEFAULT_SECS
+}
+
+fn default_guest_scopes() -> Vec<Scope> {
+    vec![Scope::Pointer]
 }
@@ -0,0 +1,147 @@
+//! Versioned on-disk state, upgraded in place the first time a newer server reads it
+//!
+//! Each file records its `version`, and files from before versioning count as version 1.
+//! Reading an older file runs its migrations from that version up, keeps the original next
+//! to it as `<name>.v<N>.bak` and writes the upgraded file back. Imported bundles and
+//! restored backups are upgraded the same way on the next start.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Lifts a file's contents from one version to the next
+pub type Migration = fn(Value) -> Result<Value>;
+
+/// The migrations of one kind of file, oldest first
+pub struct Schema {
+    pub name: &'static str,
+    /// `migrations[n]` upgrades version `n + 1`; the current version is one past the last
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    pub const fn current(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+}
+
+/// Reads `path` as JSON at `schema`'s current version, or `None` when there's no file
+///
+/// A file written by a newer server is read as it is, which works as long as the newer
+/// version only added fields.
+pub fn load(path: &Path, schema: &Schema) -> Result<Option<Value>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut value: Value = serde_json::from_str(&contents)?;
+    let version = version_of(&value);
+    let current = schema.current();
+    if version > current {
+        log::warn!(
+            "{} is {} version {}, newer than version {} this server reads; reading it as is",
+            path.display(),
+            schema.name,
+            version,
+            current
+        );
+    }
+    if version >= current {
+        return Ok(Some(value));
+    }
+
+    for migration in &schema.migrations[version as usize - 1..] {
+        value = migration(value)?;
+    }
+    let Value::Object(sections) = &mut value else {
+        bail!("{} {} must be a JSON object", schema.name, current);
+    };
+    sections.insert("version".to_string(), current.into());
+
+    let backup = backup_path(path, version);
+    std::fs::copy(path, &backup)
+        .with_context(|| format!("Failed to back up {}", path.display()))?;
+    std::fs::write(path, serde_json::to_string_pretty(&value)? + "\n")?;
+    log::info!(
+        "Migrated {} from version {} to {}; the previous file is {}",
+        path.display(),
+        version,
+        current,
+        backup.display()
+    );
+    Ok(Some(value))
+}
+
+/// The `version` a file records, or 1 for files from before versioning
+fn version_of(value: &Value) -> u32 {
+    value
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |version| version.clamp(1, u32::MAX as u64) as u32)
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    const WIDGETS: Schema = Schema {
+        name: "widgets",
+        migrations: &[wrap_list, rename_size],
+    };
+
+    fn wrap_list(list: Value) -> Result<Value> {
+        Ok(json!({ "widgets": list }))
+    }
+
+    fn rename_size(mut file: Value) -> Result<Value> {
+        for widget in file["widgets"].as_array_mut().into_iter().flatten() {
+            if let Some(size) = widget.as_object_mut().and_then(|w| w.remove("size")) {
+                widget["width"] = size;
+            }
+        }
+        Ok(file)
+    }
+
+    fn file(contents: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("widgets.json");
+        std::fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_upgrades_through_every_version_and_keeps_a_backup() {
+        let (_dir, path) = file(r#"[{ "size": 3 }]"#);
+        let value = load(&path, &WIDGETS).unwrap().unwrap();
+        assert_eq!(value, json!({ "version": 3, "widgets": [{ "width": 3 }] }));
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&path, 1)).unwrap(),
+            r#"[{ "size": 3 }]"#
+        );
+
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, value);
+        assert_eq!(load(&path, &WIDGETS).unwrap().unwrap(), value);
+        assert!(!backup_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_current_and_newer_files_are_left_alone() {
+        let newer = r#"{ "version": 7, "widgets": [] }"#;
+        let (_dir, path) = file(newer);
+        assert_eq!(load(&path, &WIDGETS).unwrap().unwrap()["version"], 7);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+        assert!(load(&path.with_file_name("missing.json"), &WIDGETS)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,6 +1,10 @@
+pub mod migrations;
+pub mod paths;
+
 use crate::domain::config::ServerConfig;
 use if_addrs::get_if_addrs;
 use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn get_local_ip() -> Option<IpAddr> {
     get_if_addrs()
@@ -16,3 +20,33 @@ pub fn get_hostname() -> String {
         .and_then(|h| h.into_string().ok())
         .unwrap_or_else(|| ServerConfig::UNKNOWN_HOSTNAME.to_string())
 }
+
+/// Seconds since the Unix epoch, used for persisted timestamps
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses lowercase or uppercase hex; `None` for odd lengths or other characters
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,122 @@
+//! Where settings, persisted state and logs live; `--portable` moves all of it at once
+
+use crate::domain::config::ServerConfig;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static PORTABLE_ROOT: OnceLock<PathBuf> = OnceLock::new();
+#[cfg(windows)]
+static MACHINE_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keeps everything in a directory next to the executable; call before anything touches disk
+pub fn enable_portable() -> std::io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let root = exe
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(ServerConfig::PORTABLE_DIR_NAME);
+    Ok(PORTABLE_ROOT.get_or_init(|| root).clone())
+}
+
+/// Keeps everything under ProgramData, shared by the service and whoever administers it
+#[cfg(windows)]
+pub fn use_machine_config_dir() -> PathBuf {
+    let base = std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"));
+    MACHINE_ROOT
+        .get_or_init(|| base.join(ServerConfig::APP_DIR_NAME))
+        .clone()
+}
+
+/// Directory for settings and persisted state
+pub fn config_dir() -> PathBuf {
+    if let Some(root) = PORTABLE_ROOT.get() {
+        return root.clone();
+    }
+    #[cfg(windows)]
+    if let Some(root) = MACHINE_ROOT.get() {
+        return root.clone();
+    }
+    user_config_dir()
+}
+
+pub fn settings_file() -> PathBuf {
+    config_dir().join(ServerConfig::SETTINGS_FILE)
+}
+
+pub fn tokens_file() -> PathBuf {
+    config_dir().join(ServerConfig::TOKENS_FILE)
+}
+
+pub fn admin_token_file() -> PathBuf {
+    config_dir().join(ServerConfig::ADMIN_TOKEN_FILE)
+}
+
+/// Wrapped key for sealed tokens and settings; absent until `encrypt-secrets` has run
+pub fn secrets_file() -> PathBuf {
+    config_dir().join(ServerConfig::SECRETS_FILE)
+}
+
+/// Commands remote clients injected, when `audit.enabled` is set
+pub fn audit_log_file() -> PathBuf {
+    config_dir().join(ServerConfig::AUDIT_LOG_FILE)
+}
+
+/// Held while an instance runs against this config directory
+pub fn lock_file() -> PathBuf {
+    config_dir().join(ServerConfig::LOCK_FILE)
+}
+
+/// Only set in portable mode; otherwise logs go to stderr for the service manager to collect
+pub fn log_file() -> Option<PathBuf> {
+    PORTABLE_ROOT
+        .get()
+        .map(|root| root.join(ServerConfig::LOG_FILE))
+}
+
+/// Per-user directory for settings and persisted state
+///
+/// On macOS this is under Application Support, which also resolves inside the app
+/// container when sandboxed.
+fn user_config_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| {
+            PathBuf::from(home)
+                .join("Library")
+                .join("Application Support")
+        })
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    base.unwrap_or_else(|| PathBuf::from("."))
+        .join(ServerConfig::APP_DIR_NAME)
+}
+
+/// Moves state written by versions that kept it in `~/.config` on macOS
+#[cfg(target_os = "macos")]
+pub fn migrate_legacy_config_dir() {
+    let Some(home) = std::env::var_os("HOME") else {
+        return;
+    };
+    let legacy = PathBuf::from(home)
+        .join(".config")
+        .join(ServerConfig::APP_DIR_NAME);
+    let current = user_config_dir();
+    if !legacy.is_dir() || current.exists() {
+        return;
+    }
+    let moved = current
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::rename(&legacy, &current));
+    match moved {
+        Ok(()) => log::info!("Moved {} to {}", legacy.display(), current.display()),
+        Err(e) => log::warn!("Failed to move {}: {}", legacy.display(), e),
+    }
+}